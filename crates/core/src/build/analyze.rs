@@ -0,0 +1,251 @@
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    fmt::{self, Display},
+    io,
+    path::{Path, PathBuf},
+};
+
+use decorous_backend::RenderOut;
+use decorous_frontend::{
+    rslint_parser::{self, ast::FnDecl, AstNode},
+    Component,
+};
+use itertools::Itertools;
+
+use crate::utils::format_bytes;
+
+/// What's gathered over a build for `--analyze`, other than the rendered output itself.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// The sizes of each WebAssembly module, before and after being optimized and stripped.
+    wasm: RefCell<Vec<(PathBuf, u64, u64)>>,
+}
+
+impl Analysis {
+    pub fn record_wasm(&self, path: impl Into<PathBuf>, before: u64, after: u64) {
+        self.wasm.borrow_mut().push((path.into(), before, after));
+    }
+}
+
+/// A [`RenderOut`] that keeps a copy of the JavaScript and CSS written to it, so their size can
+/// be analyzed.
+pub struct Measured<T> {
+    inner: T,
+    js: Vec<u8>,
+    css: Vec<u8>,
+}
+
+impl<T: RenderOut> Measured<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            js: vec![],
+            css: vec![],
+        }
+    }
+}
+
+impl<T: RenderOut> RenderOut for Measured<T> {
+    fn write_js(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.js.extend_from_slice(buf);
+        self.inner.write_js(buf)
+    }
+
+    fn write_css(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.css.extend_from_slice(buf);
+        self.inner.write_css(buf)
+    }
+
+    fn write_html(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_html(buf)
+    }
+
+    fn js_handle(&mut self) -> &mut dyn io::Write {
+        self
+    }
+
+    fn generated_name(&mut self, name: &str) {
+        self.inner.generated_name(name)
+    }
+}
+
+impl<T: RenderOut> io::Write for Measured<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_js(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.js_handle().flush()
+    }
+}
+
+/// The breakdown of the size of a component's output that is printed by `--analyze`.
+#[derive(Debug)]
+pub struct Report {
+    js_total: usize,
+    /// The functions in the JavaScript by name, with how many there are and their total size.
+    /// Blocks are rendered to functions, so these are the sizes of each block.
+    functions: Vec<(String, usize, usize)>,
+    css_total: usize,
+    css_rules: Vec<(String, usize)>,
+    wasm: Vec<(PathBuf, u64, u64)>,
+    reactive_vars: usize,
+    enable_color: bool,
+}
+
+impl Report {
+    pub fn new<T>(
+        component: &Component<'_>,
+        out: &Measured<T>,
+        analysis: &Analysis,
+        enable_color: bool,
+    ) -> Self {
+        let js = String::from_utf8_lossy(&out.js);
+        let css = String::from_utf8_lossy(&out.css);
+        Self {
+            js_total: out.js.len(),
+            functions: function_sizes(&js),
+            css_total: out.css.len(),
+            css_rules: css_rule_sizes(&css),
+            wasm: analysis.wasm.borrow().clone(),
+            reactive_vars: component.declared_vars.all_vars().len(),
+            enable_color,
+        }
+    }
+
+    fn write_heading(&self, f: &mut fmt::Formatter<'_>, heading: &str, size: &str) -> fmt::Result {
+        if self.enable_color {
+            writeln!(f, "\x1b[1m{heading:<40}\x1b[0m {size:>10}")
+        } else {
+            writeln!(f, "{heading:<40} {size:>10}")
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_heading(f, "JavaScript", &format_bytes(self.js_total as u64))?;
+        for (name, count, size) in &self.functions {
+            let name = if *count > 1 {
+                format!("{name} (x{count})")
+            } else {
+                name.clone()
+            };
+            writeln!(f, "  {name:<38} {:>10}", format_bytes(*size as u64))?;
+        }
+
+        if self.css_total > 0 {
+            self.write_heading(f, "CSS", &format_bytes(self.css_total as u64))?;
+            for (selector, size) in &self.css_rules {
+                writeln!(f, "  {selector:<38} {:>10}", format_bytes(*size as u64))?;
+            }
+        }
+
+        if !self.wasm.is_empty() {
+            self.write_heading(f, "WebAssembly", "")?;
+            for (path, before, after) in &self.wasm {
+                let name = path.display().to_string();
+                if before == after {
+                    writeln!(f, "  {name:<38} {:>10}", format_bytes(*after))?;
+                } else {
+                    writeln!(
+                        f,
+                        "  {name:<38} {:>10} -> {}",
+                        format_bytes(*before),
+                        format_bytes(*after)
+                    )?;
+                }
+            }
+        }
+
+        self.write_heading(f, "Reactive variables", &self.reactive_vars.to_string())
+    }
+}
+
+/// Gets the size of every named function in `js`, largest first. Functions with the same name,
+/// like the `mount` helper of each block, are added up. Nested functions are also counted in the
+/// functions that they are in.
+fn function_sizes(js: &str) -> Vec<(String, usize, usize)> {
+    let parse = rslint_parser::parse_module(js, 0);
+    let mut sizes: Vec<(String, usize, usize)> = vec![];
+    for decl in parse.syntax().descendants().filter_map(FnDecl::cast) {
+        let Some(name) = decl.name().and_then(|name| name.ident_token()) else {
+            continue;
+        };
+        let size = usize::from(decl.syntax().text_range().len());
+        match sizes.iter_mut().find(|(n, _, _)| n == name.text().as_str()) {
+            Some((_, count, total)) => {
+                *count += 1;
+                *total += size;
+            }
+            None => sizes.push((name.text().to_string(), 1, size)),
+        }
+    }
+    sizes.into_iter().sorted_by(|a, b| b.2.cmp(&a.2)).collect()
+}
+
+/// Gets the size of every top-level rule in `css`, largest first. At-rules are counted as one
+/// rule, including the rules nested in them.
+fn css_rule_sizes(css: &str) -> Vec<(String, usize)> {
+    let mut rules = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut quote = None;
+    for (i, c) in css.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    let rule = &css[start..=i];
+                    let selector = rule[..rule.find('{').unwrap_or(0)].trim();
+                    rules.push((selector.to_owned(), rule.trim().len()));
+                    start = i + 1;
+                }
+            }
+            (None, _) => {}
+        }
+    }
+    rules.sort_by_key(|(_, size)| Reverse(*size));
+    rules
+}
+
+/// Gets the size of the file at `path`, or `0` if it can't be read.
+pub fn file_size(path: &Path) -> u64 {
+    path.metadata().map_or(0, |meta| meta.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn functions_with_the_same_name_are_added_up() {
+        let js = "function create_0_block() { function mount() {} }\nfunction create_main_block() { function mount() {} let x = 0; }";
+        assert_eq!(
+            vec![
+                ("create_main_block".to_owned(), 1, 63),
+                ("create_0_block".to_owned(), 1, 49),
+                ("mount".to_owned(), 2, 38),
+            ],
+            function_sizes(js)
+        );
+    }
+
+    #[test]
+    fn css_is_split_into_top_level_rules() {
+        let css = "p { color: red; }\n@media (width > 1px) { a { content: \"}\"; } }\n";
+        assert_eq!(
+            vec![
+                ("@media (width > 1px)".to_owned(), 44),
+                ("p".to_owned(), 17),
+            ],
+            css_rule_sizes(css)
+        );
+    }
+}
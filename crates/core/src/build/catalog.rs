@@ -0,0 +1,63 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use decorous_frontend::Messages;
+
+use crate::config::Config;
+
+/// The messages of the locale being built, read from its catalog in the directory set by
+/// `i18n.catalogs`. Catalogs are TOML files that map the key of each message to its text, like
+/// `greeting = "Bonjour"`.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    /// The file the messages were read from, or `None` if there are no locales.
+    path: Option<PathBuf>,
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Reads the catalog of `locale`, or of the first locale in the config file if it's `None`.
+    /// A locale without a catalog has no messages.
+    pub fn load(config: &Config, locale: Option<&str>) -> Result<Self> {
+        let Some(locale) = locale.or(config.i18n.locales.first().map(String::as_str)) else {
+            return Ok(Self::default());
+        };
+        let path = config.i18n.catalog(locale);
+        let messages = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("error reading {}", path.display()))?;
+            toml::from_str(&contents)
+                .with_context(|| format!("error parsing {}", path.display()))?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path),
+            messages,
+        })
+    }
+
+    /// The file the messages were read from, if it exists. Builds depend on it, since it decides
+    /// what their `{@t}` expressions are replaced with.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref().filter(|path| path.exists())
+    }
+}
+
+impl Messages for Catalog {
+    fn message(&self, key: &str) -> Option<String> {
+        if self.path.is_none() {
+            return Some(key.to_owned());
+        }
+        // Keys are extracted with empty messages, which are left for translators to fill in
+        self.messages
+            .get(key)
+            .filter(|message| !message.is_empty())
+            .cloned()
+    }
+}
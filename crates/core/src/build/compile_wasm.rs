@@ -7,9 +7,10 @@ use std::{
     path::{Path, PathBuf},
     process::Command,
     str,
+    sync::{Mutex, MutexGuard, PoisonError},
 };
 
-use anyhow::{bail, Context, Error, Result};
+use anyhow::{bail, ensure, Context, Error, Result};
 use decorous_backend::{CodeInfo, JsDecl, JsEnv, WasmCompiler};
 use decorous_errors::{DiagnosticBuilder, Severity};
 use decorous_frontend::{ast::Code, CodeExecutor};
@@ -19,17 +20,24 @@ use tempdir::TempDir;
 use wasi_common::pipe::WritePipe;
 use wasm_opt::OptimizationOptions;
 use wasmtime::*;
-use wasmtime_wasi::sync::WasiCtxBuilder;
 use which::which;
 
+use rust::RustCompiler;
+use sandbox::Grants;
+
 use crate::{
-    build::global_ctx::GlobalCtx,
+    build::{analyze::file_size, global_ctx::GlobalCtx},
+    cache::StaticCache,
     cli::OptimizationLevel,
-    config::{ScriptOrFile, WasmFeature},
-    indicators::{FinishLog, Spinner},
-    utils,
+    config::{BuiltinCompiler, Config, ScriptOrFile, WasmFeature},
+    indicators::{FinishLog, Phase},
+    interrupt, utils,
 };
 
+mod bindings;
+mod rust;
+mod sandbox;
+
 pub struct MainCompiler<'a> {
     global_ctx: &'a GlobalCtx<'a>,
     comptime: Cell<bool>,
@@ -43,22 +51,14 @@ impl<'a> MainCompiler<'a> {
         }
     }
 
-    fn get_python(&self) -> Option<Cow<'_, Path>> {
-        if let Some(py) = &self.global_ctx.config.python {
-            return Some(Cow::Borrowed(py));
-        }
-
-        match which("python") {
-            Ok(bin) => Some(bin.into()),
-            Err(which::Error::CannotFindBinaryPath) => {
-                which("python3").map_or(None, |bin| Some(bin.into()))
+    /// Warns about the programs in `deps` that aren't installed, and records the versions of the
+    /// rest.
+    fn check_deps(&self, deps: &[String]) {
+        for bin in deps {
+            if which(bin).is_ok() {
+                self.global_ctx.record.ran(bin);
+                continue;
             }
-            Err(_) => None,
-        }
-    }
-
-    fn warn_unused_deps(&self, deps: &[String]) {
-        for bin in deps.iter().filter(|b| which(b).is_err()) {
             self.global_ctx.errs.emit(
                 DiagnosticBuilder::new(format!("script dependency not found: {bin}"), 0)
                     .severity(Severity::Warning)
@@ -68,8 +68,110 @@ impl<'a> MainCompiler<'a> {
     }
 }
 
+fn find_python(config: &Config) -> Option<Cow<'_, Path>> {
+    if let Some(py) = &config.python {
+        return Some(Cow::Borrowed(py));
+    }
+
+    match which("python") {
+        Ok(bin) => Some(bin.into()),
+        Err(which::Error::CannotFindBinaryPath) => {
+            which("python3").map_or(None, |bin| Some(bin.into()))
+        }
+        Err(_) => None,
+    }
+}
+
+/// Prints every configured compiler, along with anything its toolchain is missing. Fails if
+/// any toolchain is incomplete.
+pub fn list_compilers(config: &Config, enable_color: bool) -> Result<()> {
+    let (ok, missing) = if enable_color {
+        ("\x1b[32;1mOK\x1b[0m", "\x1b[31;1mMISSING\x1b[0m")
+    } else {
+        ("OK", "MISSING")
+    };
+    let has_python = find_python(config).is_some();
+
+    let mut compilers = config.compilers.iter().collect::<Vec<_>>();
+    compilers.sort_unstable_by_key(|(lang, _)| *lang);
+    let mut incomplete = 0;
+    for (lang, compiler) in compilers {
+        let mut problems = compiler
+            .deps
+            .iter()
+            .filter(|bin| which(bin).is_err())
+            .map(|bin| format!("`{bin}` not found"))
+            .collect::<Vec<_>>();
+        if let ScriptOrFile::File(file) = &compiler.script {
+            if !file.exists() {
+                problems.push(format!("script {} does not exist", file.display()));
+            }
+        }
+        if !has_python && !matches!(compiler.script, ScriptOrFile::Builtin(_)) {
+            problems.push("python not found".to_owned());
+        }
+
+        if problems.is_empty() {
+            println!("{ok} {lang}");
+        } else {
+            incomplete += 1;
+            println!("{missing} {lang}: {}", problems.join(", "));
+        }
+    }
+
+    ensure!(
+        incomplete == 0,
+        "{incomplete} compiler(s) have missing dependencies"
+    );
+    Ok(())
+}
+
+/// Runs a compiler hook with `sh`, failing if it does.
+fn run_hook(hook: &str, mut sh: Command) -> Result<()> {
+    let out = sh
+        .arg("-c")
+        .arg(hook)
+        .output()
+        .with_context(|| format!("error running hook `{hook}`"))?;
+    if !out.status.success() {
+        bail!(
+            "hook `{hook}` failed:\n{}\nwith stdout:\n{}",
+            String::from_utf8_lossy(&out.stderr),
+            String::from_utf8_lossy(&out.stdout),
+        );
+    }
+
+    Ok(())
+}
+
+/// Held while WebAssembly is compiled or run, since every compiler writes to the same out
+/// directory. Used components may be compiled on different threads.
+static OUTDIR_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_outdir() -> MutexGuard<'static, ()> {
+    OUTDIR_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
 impl WasmCompiler for MainCompiler<'_> {
-    fn compile(
+    fn compile(&self, info: CodeInfo) -> Result<String, Error> {
+        let _guard = lock_outdir();
+        self.compile_unlocked(info)
+    }
+
+    fn compile_comptime(&self, info: CodeInfo) -> Result<JsEnv> {
+        Ok(self
+            .run_static(info)?
+            .into_iter()
+            .map(|(name, value)| JsDecl {
+                name,
+                value: value.to_string(),
+            })
+            .collect())
+    }
+}
+
+impl MainCompiler<'_> {
+    fn compile_unlocked(
         &self,
         CodeInfo {
             lang,
@@ -77,14 +179,21 @@ impl WasmCompiler for MainCompiler<'_> {
             exports,
         }: CodeInfo,
     ) -> Result<String, Error> {
+        let category = if self.comptime.get() {
+            "static wasm"
+        } else {
+            "wasm"
+        };
+        let _span = self.global_ctx.time(category, lang);
         let config = self
             .global_ctx
             .config
             .compilers
             .get(lang)
             .with_context(|| format!("unsupported language: {lang}"))?;
-        self.warn_unused_deps(&config.deps);
+        self.check_deps(&config.deps);
         let dir = TempDir::new(lang).context("error creating temp dir for compiler")?;
+        let _cleanup = interrupt::temp_dir(dir.path());
         let path: PathBuf = dir.path().join(format!(
             "__tmp.{}",
             config.ext_override.as_deref().unwrap_or(lang)
@@ -95,7 +204,8 @@ impl WasmCompiler for MainCompiler<'_> {
         } else {
             format!("Building static WebAssembly ({lang})...")
         };
-        let spinner = Spinner::new(msg);
+        let logger = self.global_ctx.log();
+        let spinner = logger.spinner(msg);
 
         fs::write(&path, body)?;
 
@@ -116,27 +226,11 @@ impl WasmCompiler for MainCompiler<'_> {
             }
             Err(err) => bail!(err),
         }
+        let _partial = interrupt::partial_dir(Path::new(&self.global_ctx.args.out));
         let outdir = fs::canonicalize(&self.global_ctx.args.out).unwrap();
-
-        let python = self
-            .get_python()
-            .context("python not found in $PATH! Make sure to install it!")?;
-        let file_loc = match &config.script {
-            ScriptOrFile::File(file) => Cow::Owned(
-                fs::canonicalize(file.as_path())
-                    .context("error getting absolute path of script")?,
-            ),
-            ScriptOrFile::Script(script) => {
-                fs::write(dir.path().join("__tmp.py"), script)?;
-                Cow::Borrowed(Path::new("__tmp.py"))
-            }
-        };
-        // This defer! cannot be used in the above match statement, as it executes when a
-        // scope ends, and match arms have individual scopes
-        defer! {
-            if matches!(&config.script, ScriptOrFile::Script(_)) {
-                fs::remove_file(dir.path().join("__tmp.py")).expect("error removing \"__tmp.py\"! Remove it manually!");
-            }
+        // Static code blocks remove the directory once they are run
+        if !self.comptime.get() {
+            self.global_ctx.record.wrote(&self.global_ctx.args.out);
         }
 
         let input_path = fs::canonicalize(&self.global_ctx.args.input)
@@ -146,21 +240,57 @@ impl WasmCompiler for MainCompiler<'_> {
         } else {
             PathBuf::new()
         };
-        let script_out = Command::new(python.as_ref())
-            .arg(file_loc.as_ref())
-            .env("DECOR_INPUT", &path)
-            .env("DECOR_OUT", &self.global_ctx.args.out)
-            .env("DECOR_OUT_DIR", outdir)
-            .env("DECOR_EXPORTS", exports.iter().join(" "))
-            .env("DECOR_CACHE", &cache_path)
-            .env(
-                "DECOR_COMPTIME",
-                self.comptime.get().then_some("1").unwrap_or_default(),
-            )
-            .current_dir(dir.path())
-            .args(&self.global_ctx.args.build_args)
-            .output()?;
-        let (status, stdout, stderr) = (script_out.status, script_out.stdout, script_out.stderr);
+        let out_name = match &config.out_name {
+            Some(template) => template
+                .replace(
+                    "{component}",
+                    &self
+                        .global_ctx
+                        .args
+                        .input
+                        .file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                )
+                .replace("{lang}", lang),
+            None => path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        };
+        // The script and its hooks all run in the same environment
+        let command = |program: &OsStr| {
+            let mut command = Command::new(program);
+            command
+                .env("DECOR_INPUT", &path)
+                .env("DECOR_OUT", &self.global_ctx.args.out)
+                .env("DECOR_OUT_DIR", &outdir)
+                .env("DECOR_OUT_NAME", &out_name)
+                .env("DECOR_EXPORTS", exports.iter().join(" "))
+                .env("DECOR_CACHE", &cache_path)
+                .env("DECOR_COMPTIME", if self.comptime.get() { "1" } else { "" })
+                .envs(&config.env)
+                .current_dir(dir.path());
+            command
+        };
+
+        for hook in &config.hooks.pre {
+            run_hook(hook, command(OsStr::new("sh")))?;
+        }
+        let stdout = match &config.script {
+            ScriptOrFile::Builtin(BuiltinCompiler::Rust) => RustCompiler {
+                body,
+                out: &self.global_ctx.args.out,
+                out_dir: &outdir,
+                out_name: &out_name,
+                target_dir: &cache_path,
+                comptime: self.comptime.get(),
+                build_args: &self.global_ctx.args.build_args,
+            }
+            .compile(dir.path(), command),
+            script => self.run_script(script, dir.path(), command),
+        };
         if cache_path != Path::new("")
             && fs::read_dir(&cache_path)
                 .context("error reading cache dir")?
@@ -170,15 +300,16 @@ impl WasmCompiler for MainCompiler<'_> {
             fs::remove_dir(&cache_path).context("error removing cache dir - should be empty")?;
         }
 
-        if !status.success() {
-            bail!(
-                "failed to compile to WebAssembly:\n{}\nwith stdout:\n{}",
-                str::from_utf8(&stderr)?,
-                str::from_utf8(&stdout)?,
-            );
+        let mut stdout = stdout?;
+        for hook in &config.hooks.post {
+            run_hook(hook, command(OsStr::new("sh")))?;
         }
 
-        let stdout = String::from_utf8(stdout).context("error converting script out to utf-8")?;
+        let wasm_files = fs::read_dir(&self.global_ctx.args.out)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| matches!(path.extension(), Some(ext) if ext == OsStr::new("wasm")))
+            .collect_vec();
+        let sizes_before = wasm_files.iter().map(|path| file_size(path)).collect_vec();
 
         spinner.finish(
             FinishLog::default()
@@ -194,109 +325,167 @@ impl WasmCompiler for MainCompiler<'_> {
                     args
                 }))
                 .with_file(&self.global_ctx.args.out)
-                .enable_color(self.global_ctx.args.color)
-                .to_string(),
+                .with_phase(Phase::WasmCompile)
+                .with_size(sizes_before.iter().sum()),
         );
 
-        let wasm_files = fs::read_dir(&self.global_ctx.args.out)?
-            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
-            .filter(|path| matches!(path.extension(), Some(ext) if ext == OsStr::new("wasm")))
-            .collect_vec();
-
         if let Some(opt) = self.global_ctx.args.optimize {
             for path in &wasm_files {
-                let spinner = Spinner::new(format!("Optimizing WebAssembly ({opt})..."));
+                let _span = self.global_ctx.time("optimize", opt.to_string());
+                let spinner = logger.spinner(format!("Optimizing WebAssembly ({opt})..."));
                 optimize(path, opt, &config.features).context("problem optimizing WebAssembly")?;
                 spinner.finish(
                     FinishLog::default()
                         .with_main_message("optimized WebAssembly")
                         .with_sub_message(opt.to_string())
                         .with_file(path)
-                        .enable_color(self.global_ctx.args.color)
-                        .to_string(),
+                        .with_phase(Phase::Optimize)
+                        .with_size(file_size(path)),
                 );
             }
         }
 
         if self.global_ctx.args.strip {
             for path in &wasm_files {
-                let spinner = Spinner::new("Stripping WebAssembly...");
+                let _span = self.global_ctx.time("strip", "");
+                let spinner = logger.spinner("Stripping WebAssembly...");
                 strip(path).context("problem stripping WebAssembly binary")?;
                 spinner.finish(
                     FinishLog::default()
                         .with_main_message("stripped WebAssembly")
                         .with_file(path)
-                        .enable_color(self.global_ctx.args.color)
-                        .to_string(),
+                        .with_phase(Phase::Strip)
+                        .with_size(file_size(path)),
                 );
             }
         }
 
-        Ok(stdout)
-    }
+        if self.global_ctx.args.analyze && !self.comptime.get() {
+            for (path, before) in wasm_files.iter().zip(sizes_before) {
+                self.global_ctx
+                    .analysis
+                    .record_wasm(path, before, file_size(path));
+            }
+        }
 
-    fn compile_comptime(&self, info: CodeInfo) -> Result<JsEnv> {
-        self.comptime.set(true);
-        self.compile(info)?;
-        self.comptime.set(false);
+        let module = outdir.join(format!("{out_name}.wasm"));
+        if config.bindings && !self.comptime.get() && module.exists() {
+            stdout.push_str(&bindings::generate(&module).context("error generating bindings")?);
+        }
 
-        let outdir =
-            fs::canonicalize(&self.global_ctx.args.out).expect("outdir should have been created");
-        let wasm_path = fs::read_dir(&outdir)?
-            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
-            .find(|path| matches!(path.extension(), Some(ext) if ext == OsStr::new("wasm")))
-            .context("no WebAssembly file outputted from static compiler")?;
+        Ok(stdout)
+    }
+}
 
-        // Run wasi module
-        let (stdout, _stderr) = {
-            let engine = Engine::default();
-            let mut linker = Linker::new(&engine);
-            wasmtime_wasi::add_to_linker(&mut linker, |s| s).unwrap();
-            let stdout = WritePipe::new_in_memory();
-            let stderr = WritePipe::new_in_memory();
-            let wasi = WasiCtxBuilder::new()
-                .stdout(Box::new(stdout.clone()))
-                .stderr(Box::new(stderr.clone()))
-                .build();
-            let mut store = Store::new(&engine, wasi);
-            let module = Module::from_file(&engine, wasm_path)?;
-            linker.module(&mut store, "", &module)?;
-            linker
-                .get_default(&mut store, "")?
-                .typed::<(), ()>(&store)?
-                .call(&mut store, ())?;
-            // Dropped so stdout and stderr can be acquired
-            drop(store);
-            (
-                stdout.try_into_inner().unwrap().into_inner(),
-                stderr.try_into_inner().unwrap().into_inner(),
-            )
+impl MainCompiler<'_> {
+    /// Runs the Python script of a compiler in `dir`, returning its stdout.
+    fn run_script(
+        &self,
+        script: &ScriptOrFile,
+        dir: &Path,
+        command: impl Fn(&OsStr) -> Command,
+    ) -> Result<String> {
+        let python = find_python(self.global_ctx.config)
+            .context("python not found in $PATH! Make sure to install it!")?;
+        let file_loc = match script {
+            ScriptOrFile::File(file) => {
+                self.global_ctx.record.read(file);
+                Cow::Owned(
+                    fs::canonicalize(file.as_path())
+                        .context("error getting absolute path of script")?,
+                )
+            }
+            ScriptOrFile::Script(script) => {
+                fs::write(dir.join("__tmp.py"), script)?;
+                Cow::Borrowed(Path::new("__tmp.py"))
+            }
+            ScriptOrFile::Builtin(_) => unreachable!("built-in compilers have no script"),
         };
+        // This defer! cannot be used in the above match statement, as it executes when a
+        // scope ends, and match arms have individual scopes
+        defer! {
+            if matches!(script, ScriptOrFile::Script(_)) {
+                fs::remove_file(dir.join("__tmp.py")).expect("error removing \"__tmp.py\"! Remove it manually!");
+            }
+        }
 
-        fs::remove_dir_all(outdir).context("error removing outdir")?;
-
-        let out = serde_json::from_slice::<HashMap<String, serde_json::Value>>(&stdout)
-            .context("error deserializing static code block stdout")?;
+        let out = command(python.as_os_str())
+            .arg(file_loc.as_ref())
+            .args(&self.global_ctx.args.build_args)
+            .output()?;
+        if !out.status.success() {
+            bail!(
+                "failed to compile to WebAssembly:\n{}\nwith stdout:\n{}",
+                str::from_utf8(&out.stderr)?,
+                str::from_utf8(&out.stdout)?,
+            );
+        }
 
-        Ok(out
-            .into_iter()
-            .map(|(name, value)| JsDecl {
-                name,
-                value: value.to_string(),
-            })
-            .collect())
+        String::from_utf8(out.stdout).context("error converting script out to utf-8")
     }
 }
 
 impl CodeExecutor for MainCompiler<'_> {
     fn execute(&self, code: &Code) -> Result<decorous_frontend::JsEnv> {
-        self.comptime.set(true);
         let info = CodeInfo {
             lang: code.lang,
             body: code.body,
             exports: &[],
         };
-        self.compile(info)?;
+        Ok(self
+            .run_static(info)?
+            .into_iter()
+            .map(|(name, value)| decorous_frontend::JsDecl {
+                name,
+                value: value.to_string(),
+            })
+            .collect())
+    }
+}
+
+impl MainCompiler<'_> {
+    /// Compiles and runs a static code block, returning the values it printed. The output is
+    /// cached until the block, its compiler or what it's granted changes.
+    fn run_static(&self, info: CodeInfo) -> Result<HashMap<String, serde_json::Value>> {
+        let grants = Grants::collect(self.global_ctx)?;
+        let cache = match self.global_ctx.config.compilers.get(info.lang) {
+            Some(compiler) if !self.global_ctx.args.no_cache => Some(StaticCache::new(
+                info.lang,
+                info.body,
+                compiler,
+                &self.global_ctx.args.build_args,
+                &grants.fingerprint()?,
+            )?),
+            _ => None,
+        };
+        let restored = cache.as_ref().and_then(StaticCache::restore);
+        let stdout = match restored {
+            Some(stdout) => {
+                self.global_ctx.log().log(
+                    FinishLog::default()
+                        .with_main_message("static WebAssembly")
+                        .with_sub_message(format!("{}: restored from cache", info.lang))
+                        .with_phase(Phase::Cache),
+                );
+                stdout
+            }
+            None => {
+                let stdout = self.compile_and_run(info, &grants)?;
+                if let Some(cache) = &cache {
+                    cache.store(&stdout)?;
+                }
+                stdout
+            }
+        };
+
+        serde_json::from_slice(&stdout).context("error deserializing static code block stdout")
+    }
+
+    /// Compiles a static code block and runs it in its sandbox, returning its stdout.
+    fn compile_and_run(&self, info: CodeInfo, grants: &Grants) -> Result<Vec<u8>> {
+        let _guard = lock_outdir();
+        self.comptime.set(true);
+        self.compile_unlocked(info)?;
         self.comptime.set(false);
 
         let outdir =
@@ -313,10 +502,7 @@ impl CodeExecutor for MainCompiler<'_> {
             wasmtime_wasi::add_to_linker(&mut linker, |s| s).unwrap();
             let stdout = WritePipe::new_in_memory();
             let stderr = WritePipe::new_in_memory();
-            let wasi = WasiCtxBuilder::new()
-                .stdout(Box::new(stdout.clone()))
-                .stderr(Box::new(stderr.clone()))
-                .build();
+            let wasi = grants.ctx(Box::new(stdout.clone()), Box::new(stderr.clone()))?;
             let mut store = Store::new(&engine, wasi);
             let module = Module::from_file(&engine, wasm_path)?;
             linker.module(&mut store, "", &module)?;
@@ -334,16 +520,7 @@ impl CodeExecutor for MainCompiler<'_> {
 
         fs::remove_dir_all(outdir).context("error removing outdir")?;
 
-        let out = serde_json::from_slice::<HashMap<String, serde_json::Value>>(&stdout)
-            .context("error deserializing static code block stdout")?;
-
-        Ok(out
-            .into_iter()
-            .map(|(name, value)| decorous_frontend::JsDecl {
-                name,
-                value: value.to_string(),
-            })
-            .collect())
+        Ok(stdout)
     }
 }
 
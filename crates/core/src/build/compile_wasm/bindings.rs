@@ -0,0 +1,195 @@
+//! JavaScript bindings for raw WebAssembly modules, generated from their export section.
+//!
+//! Every exported function is wrapped so it can be called with strings and typed arrays. These
+//! are copied into the module's memory with its allocator, and passed as a pointer and a
+//! length. Numbers are converted to `BigInt`s for `i64` parameters, and booleans to `0` or `1`.
+
+use std::{fmt::Write, path::Path};
+
+use anyhow::Result;
+use itertools::Itertools;
+use walrus::{ExportItem, Module, ValType};
+
+/// Exports that allocate `n` bytes, returning a pointer, in order of preference.
+const ALLOCATORS: &[&str] = &["alloc", "malloc"];
+/// Exports that free memory returned by the allocator.
+const DEALLOCATORS: &[&str] = &["dealloc", "free"];
+
+/// Generates JavaScript that replaces the `wasm` exports object with wrapped exports.
+pub fn generate(path: impl AsRef<Path>) -> Result<String> {
+    let module = Module::from_file(path)?;
+    Ok(generate_for(&module))
+}
+
+fn generate_for(module: &Module) -> String {
+    let export_of = |name: &str| module.exports.iter().find(|export| export.name == name);
+    let memory = module
+        .exports
+        .iter()
+        .find(|export| matches!(export.item, ExportItem::Memory(_)))
+        .map(|export| export.name.as_str());
+    let allocator = ALLOCATORS.iter().find(|name| export_of(name).is_some());
+    let deallocator = DEALLOCATORS.iter().find(|name| export_of(name).is_some());
+
+    let mut out = String::from("wasm = ((raw) => {\n  const encoder = new TextEncoder();\n");
+    match (memory, allocator) {
+        (Some(memory), Some(allocator)) => {
+            write!(
+                out,
+                r#"  const copy = (bytes) => {{
+    const ptr = raw[{allocator}](bytes.length);
+    new Uint8Array(raw[{memory}].buffer, ptr, bytes.length).set(bytes);
+    return ptr;
+  }};
+"#,
+                allocator = quote(allocator),
+                memory = quote(memory),
+            )
+            .unwrap();
+            match deallocator {
+                Some(dealloc) => writeln!(
+                    out,
+                    "  const free = (allocs) => allocs.forEach(([ptr, len]) => raw[{}](ptr, len));",
+                    quote(dealloc)
+                )
+                .unwrap(),
+                None => out.push_str("  const free = () => {};\n"),
+            }
+        }
+        _ => out.push_str(
+            r#"  const copy = () => {
+    throw new TypeError("this WebAssembly module does not export its memory and an allocator");
+  };
+  const free = () => {};
+"#,
+        ),
+    }
+    out.push_str(
+        r#"  const lower = (args, types) => {
+    const lowered = [];
+    const allocs = [];
+    for (const arg of args) {
+      if (typeof arg === "string" || ArrayBuffer.isView(arg) || arg instanceof ArrayBuffer) {
+        const bytes = typeof arg === "string"
+          ? encoder.encode(arg)
+          : new Uint8Array(arg.buffer ?? arg, arg.byteOffset ?? 0, arg.byteLength);
+        const ptr = copy(bytes);
+        allocs.push([ptr, bytes.length]);
+        lowered.push(ptr, bytes.length);
+      } else {
+        lowered.push(typeof arg === "boolean" ? Number(arg) : arg);
+      }
+    }
+    if (lowered.length !== types.length) {
+      free(allocs);
+      throw new TypeError(`expected ${types.length} WebAssembly arguments, got ${lowered.length}`);
+    }
+    return [lowered.map((arg, i) => types[i] === "i64" ? BigInt(arg) : arg), allocs];
+  };
+  return {
+    ...raw,
+"#,
+    );
+
+    for export in module.exports.iter() {
+        let ExportItem::Function(func) = export.item else {
+            continue;
+        };
+        if ALLOCATORS.contains(&export.name.as_str())
+            || DEALLOCATORS.contains(&export.name.as_str())
+        {
+            continue;
+        }
+        let ty = module.types.get(module.funcs.get(func).ty());
+        let name = quote(&export.name);
+        writeln!(
+            out,
+            "    /** `{}({}){}` @returns {{{}}} */",
+            export.name,
+            ty.params().iter().map(|ty| ty.to_string()).join(", "),
+            match ty.results() {
+                [] => String::new(),
+                results => format!(" -> {}", results.iter().join(", ")),
+            },
+            js_type(ty.results()),
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    {name}(...args) {{\n      const [lowered, allocs] = lower(args, [{}]);\n      try {{\n        return raw[{name}](...lowered);\n      }} finally {{\n        free(allocs);\n      }}\n    }},",
+            ty.params().iter().map(|ty| quote(&ty.to_string())).join(", "),
+        )
+        .unwrap();
+    }
+    out.push_str("  };\n})(wasm);\n");
+
+    out
+}
+
+/// The JSDoc type of the results of a function.
+fn js_type(results: &[ValType]) -> &'static str {
+    match results {
+        [] => "void",
+        [ValType::I64] => "bigint",
+        [ValType::I32 | ValType::F32 | ValType::F64] => "number",
+        [_] => "any",
+        _ => "any[]",
+    }
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).expect("strings should serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use walrus::{FunctionBuilder, ModuleConfig};
+
+    use super::*;
+
+    fn add_func(module: &mut Module, name: &str, params: &[ValType], results: &[ValType]) {
+        let mut builder = FunctionBuilder::new(&mut module.types, params, results);
+        for result in results {
+            match result {
+                ValType::I64 => builder.func_body().i64_const(0),
+                _ => builder.func_body().i32_const(0),
+            };
+        }
+        let locals = params.iter().map(|ty| module.locals.add(*ty)).collect_vec();
+        let func = builder.finish(locals, &mut module.funcs);
+        module.exports.add(name, func);
+    }
+
+    #[test]
+    fn wraps_exported_functions() {
+        let mut module = Module::with_config(ModuleConfig::new());
+        add_func(&mut module, "greet", &[ValType::I32, ValType::I32], &[]);
+        add_func(&mut module, "big", &[ValType::I64], &[ValType::I64]);
+        add_func(&mut module, "alloc", &[ValType::I32], &[ValType::I32]);
+        let memory = module.memories.add_local(false, 1, None);
+        module.exports.add("memory", memory);
+
+        let js = generate_for(&module);
+        assert!(js.contains("const ptr = raw[\"alloc\"](bytes.length);"));
+        assert!(js.contains("/** `greet(i32, i32)` @returns {void} */"));
+        assert!(js.contains("\"greet\"(...args) {"));
+        assert!(js.contains("/** `big(i64) -> i64` @returns {bigint} */"));
+        assert!(js.contains("lower(args, [\"i64\"])"));
+        assert!(!js.contains("\"alloc\"(...args)"));
+    }
+
+    #[test]
+    fn strings_need_an_allocator() {
+        let mut module = Module::with_config(ModuleConfig::new());
+        add_func(
+            &mut module,
+            "add",
+            &[ValType::I32, ValType::I32],
+            &[ValType::I32],
+        );
+
+        let js = generate_for(&module);
+        assert!(js.contains("does not export its memory and an allocator"));
+        assert!(js.contains("/** `add(i32, i32) -> i32` @returns {number} */"));
+    }
+}
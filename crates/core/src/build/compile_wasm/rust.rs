@@ -0,0 +1,141 @@
+//! The built-in compiler for `---rust` blocks, which runs `cargo` and `wasm-bindgen` directly
+//! instead of going through a Python script.
+
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+
+const PROJECT_NAME: &str = "decor-out";
+
+pub struct RustCompiler<'a> {
+    pub body: &'a str,
+    /// The out directory, as it should appear in import statements.
+    pub out: &'a str,
+    pub out_dir: &'a Path,
+    pub out_name: &'a str,
+    /// Shared cargo target directory. Empty if builds should not be cached.
+    pub target_dir: &'a Path,
+    pub comptime: bool,
+    pub build_args: &'a [String],
+}
+
+impl RustCompiler<'_> {
+    /// Builds the code block as a crate in `dir`, returning the JavaScript that imports it.
+    /// `command` creates the commands that are run, with the environment of the compiler.
+    pub fn compile(&self, dir: &Path, command: impl Fn(&OsStr) -> Command) -> Result<String> {
+        let target_dir = if self.target_dir == Path::new("") {
+            dir.join("target")
+        } else {
+            self.target_dir.to_path_buf()
+        };
+
+        if self.comptime {
+            self.write_project(dir, None)?;
+            run(command(OsStr::new("cargo"))
+                .args(["build", "--target", "wasm32-wasi", "--color", "always"])
+                .arg("--target-dir")
+                .arg(&target_dir)
+                .args(self.build_args))?;
+            let wasm = artifact(&target_dir, "wasm32-wasi/debug", PROJECT_NAME);
+            fs::rename(&wasm, self.out_dir.join(format!("{}.wasm", self.out_name)))
+                .with_context(|| format!("error moving {}", wasm.display()))?;
+            return Ok(String::new());
+        }
+
+        self.write_project(dir, Some(&bindgen_version(&command)))?;
+        run(command(OsStr::new("cargo"))
+            .args([
+                "build",
+                "--lib",
+                "--release",
+                "--target",
+                "wasm32-unknown-unknown",
+                "--color",
+                "always",
+            ])
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .args(self.build_args))?;
+        run(command(OsStr::new("wasm-bindgen"))
+            .args(["--target", "web", "--out-name", self.out_name])
+            .arg("--out-dir")
+            .arg(self.out_dir)
+            .arg(artifact(
+                &target_dir,
+                "wasm32-unknown-unknown/release",
+                &PROJECT_NAME.replace('-', "_"),
+            )))?;
+
+        Ok(format!(
+            "import init, * as wasm from \"/{}/{}.js\";\nawait init();\n",
+            self.out, self.out_name
+        ))
+    }
+
+    /// Writes the manifest and source of the crate. Static code blocks are binaries, and the rest
+    /// are libraries that depend on `bindgen`, the version of `wasm-bindgen`.
+    fn write_project(&self, dir: &Path, bindgen: Option<&str>) -> Result<()> {
+        let mut manifest = format!(
+            "[package]\nname = \"{PROJECT_NAME}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+             [workspace]\n"
+        );
+        if let Some(version) = bindgen {
+            manifest.push_str(&format!(
+                "\n[lib]\ncrate-type = [\"cdylib\"]\n\n[dependencies]\nwasm-bindgen = \"{version}\"\n"
+            ));
+        }
+        fs::write(dir.join("Cargo.toml"), manifest).context("error writing Cargo.toml")?;
+
+        let src = dir.join("src");
+        fs::create_dir_all(&src).context("error creating src directory")?;
+        let entry = if bindgen.is_some() {
+            "lib.rs"
+        } else {
+            "main.rs"
+        };
+        fs::write(src.join(entry), self.body).with_context(|| format!("error writing {entry}"))?;
+
+        Ok(())
+    }
+}
+
+/// The version requirement for the `wasm-bindgen` crate. The generated glue only works when it
+/// matches the installed CLI exactly, so it is pinned whenever the CLI reports its version.
+fn bindgen_version(command: impl Fn(&OsStr) -> Command) -> String {
+    command(OsStr::new("wasm-bindgen"))
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| {
+            let stdout = String::from_utf8(out.stdout).ok()?;
+            let version = stdout.split_whitespace().nth(1)?;
+            Some(format!("={version}"))
+        })
+        .unwrap_or_else(|| "0.2".to_owned())
+}
+
+fn artifact(target_dir: &Path, profile: &str, name: &str) -> PathBuf {
+    target_dir.join(profile).join(format!("{name}.wasm"))
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let out = command
+        .output()
+        .with_context(|| format!("error running `{program}`"))?;
+    if !out.status.success() {
+        bail!(
+            "failed to compile to WebAssembly:\n{}\nwith stdout:\n{}",
+            String::from_utf8_lossy(&out.stderr),
+            String::from_utf8_lossy(&out.stdout),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use std::{
+    any::Any,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use decorous_frontend::EnvVars;
+use itertools::Itertools;
+use wasi_common::{
+    dir::{OpenResult, ReaddirCursor, ReaddirEntity},
+    file::{FdFlags, Filestat, OFlags},
+    Error, ErrorExt, WasiCtx, WasiDir, WasiFile,
+};
+use wasmtime_wasi::sync::{ambient_authority, dir, Dir, WasiCtxBuilder};
+
+use crate::build::{global_ctx::GlobalCtx, Env};
+
+/// What static code blocks can read. They can't touch the filesystem or see the environment,
+/// except for the directories and variables granted in the `static` table of the config file.
+#[derive(Debug)]
+pub struct Grants<'a> {
+    dirs: &'a [PathBuf],
+    env: Vec<(String, String)>,
+    /// Every file in the granted directories.
+    files: Vec<PathBuf>,
+}
+
+impl<'a> Grants<'a> {
+    /// Reads the granted variables and finds the files in the granted directories, recording all
+    /// of them as read by the build.
+    pub fn collect(global_ctx: &'a GlobalCtx) -> Result<Self> {
+        let grants = &global_ctx.config.static_blocks;
+        let vars = Env::new(global_ctx.config).recorded_in(&global_ctx.record);
+        let env = grants
+            .env
+            .iter()
+            .filter_map(|name| Some((name.clone(), vars.get(name)?)))
+            .collect();
+        let mut files = vec![];
+        for path in &grants.read {
+            find_files(global_ctx, path, &mut files)?;
+        }
+        Ok(Self {
+            dirs: &grants.read,
+            env,
+            files,
+        })
+    }
+
+    /// A hash of everything granted, which changes whenever a static code block could print
+    /// something different.
+    pub fn fingerprint(&self) -> Result<String> {
+        let mut granted = self
+            .env
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>();
+        for path in &self.files {
+            let contents =
+                fs::read(path).with_context(|| format!("error reading {}", path.display()))?;
+            granted.push(format!(
+                "{}\0{}",
+                path.display(),
+                sha256::digest(contents.as_slice())
+            ));
+        }
+        Ok(sha256::digest(granted.join("\0")))
+    }
+
+    /// The WASI context that static code blocks run in. Granted directories can only be read.
+    pub fn ctx(&self, stdout: Box<dyn WasiFile>, stderr: Box<dyn WasiFile>) -> Result<WasiCtx> {
+        let mut builder = WasiCtxBuilder::new().stdout(stdout).stderr(stderr);
+        for (name, value) in &self.env {
+            builder = builder
+                .env(name, value)
+                .with_context(|| format!("error passing {name} to static code block"))?;
+        }
+
+        let ctx = builder.build();
+        for path in self.dirs {
+            let granted = Dir::open_ambient_dir(path, ambient_authority()).with_context(|| {
+                format!("error opening {} for static code blocks", path.display())
+            })?;
+            ctx.push_preopened_dir(
+                Box::new(ReadOnlyDir(Box::new(dir::Dir::from_cap_std(granted)))),
+                path,
+            )
+            .with_context(|| format!("error granting {} to static code blocks", path.display()))?;
+        }
+        Ok(ctx)
+    }
+}
+
+/// Finds every file in `path`, recording them and the directories they're in as read.
+fn find_files(global_ctx: &GlobalCtx, path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    global_ctx.record.read(path);
+    let entries = fs::read_dir(path)
+        .with_context(|| format!("error reading {}", path.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    for path in entries.into_iter().sorted() {
+        if path.is_dir() {
+            find_files(global_ctx, &path, files)?;
+        } else {
+            global_ctx.record.read(&path);
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// A directory that can be read, but not written to. Whatever isn't forwarded is unsupported.
+struct ReadOnlyDir(Box<dyn WasiDir>);
+
+#[async_trait::async_trait]
+impl WasiDir for ReadOnlyDir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn open_file(
+        &self,
+        symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<OpenResult, Error> {
+        if write || oflags.intersects(OFlags::CREATE | OFlags::TRUNCATE) {
+            return Err(Error::perm());
+        }
+        match self
+            .0
+            .open_file(symlink_follow, path, oflags, read, false, fdflags)
+            .await?
+        {
+            OpenResult::Dir(dir) => Ok(OpenResult::Dir(Box::new(ReadOnlyDir(dir)))),
+            file => Ok(file),
+        }
+    }
+
+    async fn readdir(
+        &self,
+        cursor: ReaddirCursor,
+    ) -> Result<Box<dyn Iterator<Item = Result<ReaddirEntity, Error>> + Send>, Error> {
+        self.0.readdir(cursor).await
+    }
+
+    async fn read_link(&self, path: &str) -> Result<PathBuf, Error> {
+        self.0.read_link(path).await
+    }
+
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        self.0.get_filestat().await
+    }
+
+    async fn get_path_filestat(
+        &self,
+        path: &str,
+        follow_symlinks: bool,
+    ) -> Result<Filestat, Error> {
+        self.0.get_path_filestat(path, follow_symlinks).await
+    }
+}
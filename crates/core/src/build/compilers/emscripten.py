@@ -7,7 +7,7 @@ def main():
     input = Path(os.environ["DECOR_INPUT"])
     outdir = Path(os.environ["DECOR_OUT"])
     outdir_abs = Path(os.environ["DECOR_OUT_DIR"])
-    name = input.stem
+    name = os.environ["DECOR_OUT_NAME"]
     pre = "__pre.js"
 
     # The contents of this file will run before the JavaScript glue
@@ -8,6 +8,7 @@ def main():
     outdir = os.environ["DECOR_OUT"]
     outdir_abs = os.environ["DECOR_OUT_DIR"]
     exports = os.environ["DECOR_EXPORTS"]
+    name = os.environ["DECOR_OUT_NAME"]
 
     os.environ["GOOS"] = "js"
     os.environ["GOARCH"] = "wasm"
@@ -19,7 +20,7 @@ def main():
     shutil.copy(input, "main.go")
     subprocess.run(["go", "mod", "init", "github.com/dzfrias/decorous"], check=True)
     subprocess.run(
-        ["go", "build", "-o", os.path.join(outdir_abs, "out.wasm")], check=True
+        ["go", "build", "-o", os.path.join(outdir_abs, f"{name}.wasm")], check=True
     )
 
     print(f'import "./{outdir}/wasm_exec.js";\nconst go = new Go();')
@@ -27,7 +28,7 @@ def main():
         import_inner = ", ".join([str(exp) for exp in exports.split(" ")])
         print(f"go.importObject.env = {{ {import_inner} }};")
     print(
-        f"""let wasm = await WebAssembly.instantiateStreaming(fetch("{outdir}/out.wasm"), go.importObject);
+        f"""let wasm = await WebAssembly.instantiateStreaming(fetch("{outdir}/{name}.wasm"), go.importObject);
 go.run(wasm.instance);
 wasm = wasm.instance.exports;"""
     )
@@ -546,6 +546,7 @@ def main():
     outdir = os.environ["DECOR_OUT"]
     outdir_abs = os.environ["DECOR_OUT_DIR"]
     exports = os.environ["DECOR_EXPORTS"]
+    name = os.environ["DECOR_OUT_NAME"]
 
     with open(os.path.join(outdir_abs, "wasm_exec.js"), "w") as f:
         f.write(WASM_EXEC)
@@ -556,7 +557,7 @@ def main():
                 "tinygo",
                 "build",
                 "-o",
-                os.path.join(outdir_abs, "out.wasm"),
+                os.path.join(outdir_abs, f"{name}.wasm"),
                 "-target",
                 "wasm",
                 "main.go",
@@ -569,7 +570,7 @@ def main():
                 "tinygo",
                 "build",
                 "-o",
-                os.path.join(outdir_abs, "out.wasm"),
+                os.path.join(outdir_abs, f"{name}.wasm"),
                 "-target",
                 "wasi",
                 "main.go",
@@ -582,7 +583,7 @@ def main():
         import_inner = ", ".join([str(exp) for exp in exports.split(" ")])
         print(f"go.importObject.env = {{ {import_inner} }};")
     print(
-        f"""let wasm = await WebAssembly.instantiateStreaming(fetch("{outdir}/out.wasm"), go.importObject);
+        f"""let wasm = await WebAssembly.instantiateStreaming(fetch("{outdir}/{name}.wasm"), go.importObject);
 go.run(wasm.instance);
 wasm = wasm.instance.exports;"""
     )
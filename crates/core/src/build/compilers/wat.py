@@ -9,7 +9,7 @@ def main():
     outdir = os.environ["DECOR_OUT"]
     outdir_abs = os.environ["DECOR_OUT_DIR"]
     exports = os.environ["DECOR_EXPORTS"]
-    name = input.stem
+    name = os.environ["DECOR_OUT_NAME"]
 
     subprocess.run(
         [
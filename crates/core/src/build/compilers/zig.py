@@ -10,7 +10,7 @@ def main():
     outdir = Path(os.environ["DECOR_OUT"])
     outdir_abs = Path(os.environ["DECOR_OUT_DIR"])
     exports = os.environ["DECOR_EXPORTS"]
-    name = input.stem
+    name = os.environ["DECOR_OUT_NAME"]
 
     subprocess.run(
         [
@@ -27,7 +27,7 @@ def main():
         ],
         check=True,
     )
-    shutil.move(f"{name}.wasm", outdir_abs)
+    shutil.move(f"{input.stem}.wasm", outdir_abs / f"{name}.wasm")
 
     if not exports:
         print(
@@ -0,0 +1,38 @@
+use std::env;
+
+use decorous_frontend::EnvVars;
+
+use crate::{cache::BuildRecord, config::Config};
+
+/// Looks up the variables that components read with `$env.NAME` in the environment, falling back
+/// to the `env` table of the config file.
+pub struct Env<'a> {
+    config: &'a Config,
+    record: Option<&'a BuildRecord>,
+}
+
+impl<'a> Env<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            record: None,
+        }
+    }
+
+    /// Records the variables that are read in `record`, so the outputs of the build are only
+    /// reused while they are unchanged.
+    pub fn recorded_in(mut self, record: &'a BuildRecord) -> Self {
+        self.record = Some(record);
+        self
+    }
+}
+
+impl EnvVars for Env<'_> {
+    fn get(&self, name: &str) -> Option<String> {
+        let value = match self.record {
+            Some(record) => record.read_env(name),
+            None => env::var(name).ok(),
+        };
+        value.or_else(|| self.config.env.get(name).cloned())
+    }
+}
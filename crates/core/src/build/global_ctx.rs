@@ -1,9 +1,51 @@
+use decorous_backend::{runtime, Linker, OutputFormat};
 use decorous_errors::DynErrStream;
 
-use crate::{cli::Build, config::Config};
+use crate::{
+    build::{
+        analyze::Analysis,
+        catalog::Catalog,
+        packages::Packages,
+        resolver::Workers,
+        timings::{SpanGuard, Timings},
+    },
+    cache::BuildRecord,
+    cli::Build,
+    config::Config,
+    indicators::Logger,
+};
 
 pub struct GlobalCtx<'ctx> {
     pub config: &'ctx Config,
     pub args: &'ctx Build,
     pub errs: DynErrStream<'ctx>,
+    pub record: BuildRecord,
+    pub analysis: Analysis,
+    /// Shared by the builds of every used component, so each is only emitted once.
+    pub linker: &'ctx Linker,
+    pub packages: &'ctx Packages<'ctx>,
+    /// The messages of the locale being built.
+    pub catalog: &'ctx Catalog,
+    /// Shared by the builds of every used component, which each have their own track.
+    pub timings: &'ctx Timings,
+    pub workers: &'ctx Workers,
+}
+
+impl GlobalCtx<'_> {
+    /// The path that components rendered in `format` import their helpers from, or `None` if
+    /// they define their own. Bundles and immediately invoked functions are a single file, so
+    /// they always define their own.
+    pub fn runtime(&self, format: OutputFormat, bundle: bool) -> Option<String> {
+        (!self.args.inline_runtime && !bundle && format != OutputFormat::Iife)
+            .then(|| format!("./{}", runtime::file_name(format)))
+    }
+
+    /// Starts timing a phase of the build of this component. See [`Timings::start`].
+    pub fn time(&self, category: &'static str, name: impl Into<String>) -> SpanGuard<'_> {
+        self.timings.start(&self.errs.source().name, category, name)
+    }
+
+    pub fn log(&self) -> Logger {
+        Logger::for_build(self.args)
+    }
 }
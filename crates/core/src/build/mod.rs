@@ -1,68 +1,282 @@
+mod analyze;
+mod catalog;
 mod compile_wasm;
+mod env;
 mod global_ctx;
+mod packages;
+mod page;
 mod preprocessor;
 mod resolver;
+mod timings;
 
 use std::{
     borrow::Cow,
+    collections::HashSet,
     fs::{self, File},
     io::{self, BufWriter, Write},
+    iter, mem,
     path::{Path, PathBuf},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use anyhow::{ensure, Context, Result};
 use decorous_backend::{
-    dom_render::{CsrOptions, CsrRenderer},
-    prerender::Prerenderer,
-    Ctx as RenderCtx, HtmlInfo, RenderBackend, RenderOut,
+    css_render::CssOptions,
+    dom_render::{self, CsrOptions, CsrRenderer},
+    minify_js,
+    prerender::{PrerenderOptions, Prerenderer},
+    runtime, AsciiOnly, Ctx as RenderCtx, DynRenderBackend, HtmlInfo, HtmlTemplate, Linker,
+    Minified, OutputFormat, RenderBackend, RenderOut,
 };
 use decorous_errors::{DiagnosticBuilder, DynErrStream, Severity, Source};
-use decorous_frontend::{Component, Ctx as ParseCtx, Parser};
-use notify::{
-    event::{DataChange, ModifyKind},
-    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
-};
+use decorous_frontend::{errors::ParseErrorType, Component, Ctx as ParseCtx, HashedIds, Parser};
+use notify::{event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 
 use crate::{
-    build::{global_ctx::GlobalCtx, resolver::Resolver},
-    cli::{Build, RenderMethod},
+    build::{
+        analyze::{file_size, Analysis, Measured, Report},
+        catalog::Catalog,
+        global_ctx::GlobalCtx,
+        packages::Packages,
+        resolver::{Resolver, Workers},
+        timings::Timings,
+    },
+    cache::{BuildCache, BuildRecord},
+    clean,
+    cli::{Build, Format, RenderMethod, Target},
     config::Config,
-    indicators::FinishLog,
-    utils,
+    indicators::{FinishLog, Logger, Phase},
+    interrupt, utils,
 };
 use compile_wasm::MainCompiler;
-use preprocessor::Preproc;
+pub(crate) use env::Env;
+pub(crate) use preprocessor::Preproc;
 
 pub fn build(args: &Build) -> Result<()> {
     ensure!(
         !(args.render_method == RenderMethod::Prerender && args.modularize),
         "component cannot be both modularized and prerendered!"
     );
+    ensure!(
+        !(args.render_method == RenderMethod::Prerender && args.target == Target::CustomElement),
+        "custom elements cannot be prerendered!"
+    );
 
-    let config = utils::get_config()?;
-    compile(args, &config)?;
+    if args.list_compilers {
+        return compile_wasm::list_compilers(&utils::get_config()?, args.color);
+    }
+    let load = || load_config(args.clone(), |_| {});
+    let (profiled, config) = load()?;
+    let deps = match build_entries(&profiled, &config) {
+        Ok(deps) => deps,
+        Err(err) if args.watch => {
+            report_error(&err);
+            inputs(args)
+        }
+        Err(err) => return Err(err),
+    };
 
     if args.watch {
-        watch(args, &config)?;
+        watch(args, deps, load, || Ok(()))?;
     }
 
     Ok(())
 }
 
-fn compile(args: &Build, config: &Config) -> Result<(), anyhow::Error> {
+/// Reads the config file, changing it with `configure`, and applies the profile of `args` from it.
+pub(crate) fn load_config(
+    mut args: Build,
+    configure: impl FnOnce(&mut Config),
+) -> Result<(Build, Config)> {
+    let mut config = utils::get_config()?;
+    configure(&mut config);
+    config.apply_profile(&mut args)?;
+    Ok((args, config))
+}
+
+/// Compiles the entries of `args`, recording their outputs so that `decorous clean` can remove
+/// them. Returns the files that were read.
+fn build_entries(args: &Build, config: &Config) -> Result<Vec<PathBuf>> {
+    let compiled = compile_entries(args, config)?;
+    clean::record_outputs(&args.out, &compiled.outputs)?;
+    Ok(compiled.deps)
+}
+
+/// Compiles the input, or builds a page with every component if there are several. Unless a
+/// locale is given, everything is built once for every locale in the config file, with outputs
+/// named like `out_fr.js`.
+fn compile_entries(args: &Build, config: &Config) -> Result<Compiled> {
+    if args.locale.is_none() && !config.i18n.locales.is_empty() {
+        let mut compiled = Compiled {
+            outputs: vec![],
+            deps: vec![],
+        };
+        for locale in &config.i18n.locales {
+            let localized = compile_entries(
+                &Build {
+                    out: format!("{}_{locale}", args.out),
+                    locale: Some(locale.clone()),
+                    localized: true,
+                    ..args.clone()
+                },
+                config,
+            )?;
+            compiled.outputs.extend(localized.outputs);
+            compiled.deps.extend(localized.deps);
+        }
+        return Ok(compiled);
+    }
+
+    if args.others.is_empty() {
+        compile(args, config)
+    } else {
+        page::build_page(args, config)
+    }
+}
+
+/// What a compilation generated and depended on.
+#[derive(Debug)]
+pub(crate) struct Compiled {
+    /// The paths of the generated files.
+    pub outputs: Vec<PathBuf>,
+    /// The files and directories that were read, like the input and the components it uses.
+    pub deps: Vec<PathBuf>,
+}
+
+/// Compiles the input, returning the paths of the files that were generated and read.
+pub(crate) fn compile(args: &Build, config: &Config) -> Result<Compiled, anyhow::Error> {
     let start = Instant::now();
+    let logger = Logger::for_build(args);
 
     let input = fs::read_to_string(&args.input).context("error reading provided input file")?;
-    let errs = DynErrStream::new(
-        Box::new(io::stderr()),
-        Source {
-            src: &input,
-            name: args.input.to_string_lossy().to_string(),
-        },
-    );
-    let global_ctx = GlobalCtx { config, args, errs };
-    let compiler = MainCompiler::new(&global_ctx);
+    let timed = args.timings || args.trace.is_some();
+    let cache = if args.no_cache || args.analyze || args.fix || timed {
+        None
+    } else {
+        Some(BuildCache::new(&input, args)?)
+    };
+    let restored = match &cache {
+        Some(cache) => cache.restore()?,
+        None => None,
+    };
+    let timings = Timings::default();
+    let mut compiled = match restored {
+        Some(compiled) => {
+            logger.log(
+                FinishLog::default()
+                    .with_main_message("restored from cache")
+                    .with_phase(Phase::Cache),
+            );
+            compiled
+        }
+        None => {
+            let linker = Linker::new();
+            let packages = Packages::new(config)?;
+            let catalog = Catalog::load(config, args.locale.as_deref())?;
+            let workers = Workers::new();
+            let record = BuildRecord::new();
+            let errs = DynErrStream::new(
+                record.stderr(),
+                Source {
+                    src: &input,
+                    name: args.input.to_string_lossy().to_string(),
+                },
+            );
+            let global_ctx = GlobalCtx {
+                config,
+                args,
+                errs,
+                record,
+                analysis: Analysis::default(),
+                linker: &linker,
+                packages: &packages,
+                catalog: &catalog,
+                timings: &timings,
+                workers: &workers,
+            };
+            let result = compile_uncached(&global_ctx, &input);
+            if let Some(lockfile) = packages.lockfile() {
+                global_ctx.record.read(lockfile);
+            }
+            if let Some(path) = catalog.path() {
+                global_ctx.record.read(path);
+            }
+            if args.fix {
+                write_fixes(args, &input, &global_ctx.errs)?;
+            }
+            result?;
+            let compiled = Compiled {
+                outputs: global_ctx.record.outputs(),
+                deps: global_ctx.record.deps(),
+            };
+            if let Some(cache) = &cache {
+                cache
+                    .store(&global_ctx.record)
+                    .context("error caching build outputs")?;
+            }
+            compiled
+        }
+    };
+    compiled.deps.push(args.input.clone());
+
+    {
+        let mut log = FinishLog::default();
+        log.with_main_message("compiled")
+            .with_phase(Phase::Compile)
+            .with_duration(start.elapsed())
+            // The time of the whole build is always shown
+            .show_details(true)
+            .with_mod(
+                args.optimize
+                    .map_or(Cow::Borrowed("debug"), |opt| opt.to_string().into()),
+            );
+        if let Some(profile) = &args.profile {
+            log.with_mod(profile.clone());
+        }
+        if args.modularize {
+            log.with_mod("modularized");
+        }
+        if args.format != Format::Esm {
+            log.with_mod(args.format.to_string());
+        }
+        if args.bundle {
+            log.with_mod("bundled");
+        }
+        if args.minify {
+            log.with_mod("minified");
+        }
+        if args.minify_css || config.css.minify {
+            log.with_mod("minified css");
+        }
+        if args.ascii_only {
+            log.with_mod("ascii only");
+        }
+        if args.target == Target::CustomElement {
+            log.with_mod("custom element");
+        }
+        logger.log(&mut log);
+    }
+
+    if let Some(path) = &args.trace {
+        timings.write_trace(path)?;
+        logger.log(
+            FinishLog::default()
+                .with_main_message("trace")
+                .with_file(path),
+        );
+    }
+    if args.timings {
+        println!("\n{}", timings.breakdown(args.color));
+    }
+
+    Ok(compiled)
+}
+
+/// Compiles the component in `input`, without looking at the build cache.
+fn compile_uncached(global_ctx: &GlobalCtx, input: &str) -> Result<()> {
+    let args = global_ctx.args;
+    let compiler = MainCompiler::new(global_ctx);
+    let resolver = Resolver::new(global_ctx, &compiler, &args.input);
     let metadata = RenderCtx {
         name: {
             &args
@@ -74,80 +288,238 @@ fn compile(args: &Build, config: &Config) -> Result<(), anyhow::Error> {
         index_html: if global_ctx.args.html {
             Some(HtmlInfo {
                 basename: global_ctx.args.out.clone(),
+                template: html_template(global_ctx)?,
             })
         } else {
             None
         },
         wasm_compiler: &compiler,
-        use_resolver: &Resolver {
-            global_ctx: &global_ctx,
-            compiler: &compiler,
-        },
+        use_resolver: &resolver,
         errs: global_ctx.errs.clone(),
+        linker: Some(global_ctx.linker),
     };
 
-    let preproc = Preproc::new(config, args.color);
-    let component = parse_component(
-        &input,
-        &global_ctx,
+    let preproc = Preproc::new(global_ctx.config, global_ctx.log()).recorded_in(&global_ctx.record);
+    let env = Env::new(global_ctx.config).recorded_in(&global_ctx.record);
+    let mut component = parse_component(
+        input,
+        global_ctx,
         ParseCtx {
             executor: &compiler,
             preprocessor: &preproc,
             errs: global_ctx.errs.clone(),
+            lints: global_ctx.config.lint_levels(),
+            component_ids: &HashedIds,
+            features: &args.features,
+            env: &env,
+            messages: global_ctx.catalog,
+            drop_unused_uses: args.drop_unused_uses,
         },
     )?;
-    warn_on_unused_wasm(&global_ctx, &component)?;
-    render_all(&global_ctx, &component, &metadata)?;
+    warn_on_unused_wasm(global_ctx, &component)?;
+    resolver.expand_uses(&mut component)?;
+    resolver.resolve_all(&component.uses, args.bundle || args.format == Format::Iife)?;
+    render_all(global_ctx, &component, &metadata)?;
+    if shared_runtime(global_ctx, &component).is_some() {
+        write_runtime(global_ctx)?;
+    }
 
-    {
-        let mut log = FinishLog::default();
-        log.with_main_message(format!("compiled in ~{:.2?}", start.elapsed()))
-            .enable_color(args.color)
-            .with_mod(
-                args.optimize
-                    .map_or(Cow::Borrowed("debug"), |opt| opt.to_string().into()),
-            );
-        if args.modularize {
-            log.with_mod("modularized");
-        }
-        println!("{log}");
+    Ok(())
+}
+
+/// Reads the HTML template of the build, recording it as a dependency.
+fn html_template(global_ctx: &GlobalCtx) -> Result<Option<HtmlTemplate>> {
+    let Some((path, template)) = load_html_template(global_ctx.args, global_ctx.config)? else {
+        return Ok(None);
+    };
+    global_ctx.record.read(path);
+    Ok(Some(template))
+}
+
+/// The page that `--html` generates. Each locale of a translated build gets its own, named after
+/// its outputs.
+fn index_html(args: &Build) -> PathBuf {
+    if args.localized {
+        PathBuf::from(format!("{}.html", args.out))
+    } else {
+        PathBuf::from("index.html")
     }
+}
+
+/// Reads the HTML template from `--html-template` or the config file, if there is one, along with
+/// its path.
+fn load_html_template<'a>(
+    args: &'a Build,
+    config: &'a Config,
+) -> Result<Option<(&'a Path, HtmlTemplate)>> {
+    let Some(path) = args
+        .html_template
+        .as_ref()
+        .or(config.html.template.as_ref())
+    else {
+        return Ok(None);
+    };
+    let src = fs::read_to_string(path)
+        .with_context(|| format!("error reading HTML template {}", path.display()))?;
+    let template = HtmlTemplate::parse(&src)
+        .with_context(|| format!("invalid HTML template {}", path.display()))?;
+    Ok(Some((path, template)))
+}
+
+/// Applies the fixes of every diagnostic emitted while compiling to the input file, even if the
+/// compilation failed.
+fn write_fixes(args: &Build, input: &str, errs: &DynErrStream) -> Result<()> {
+    let (fixed, applied) = decorous_errors::apply_fixes(input, &errs.take_fixes());
+    if applied == 0 {
+        return Ok(());
+    }
+    fs::write(&args.input, fixed).context("error writing fixes to input file")?;
+    Logger::for_build(args).log(
+        FinishLog::default()
+            .with_main_message(format!(
+                "fixed {applied} problem{}",
+                if applied == 1 { "" } else { "s" }
+            ))
+            .with_phase(Phase::Fix),
+    );
 
     Ok(())
 }
 
-fn watch(args: &Build, config: &Config) -> Result<(), anyhow::Error> {
+/// Watches the files that the build of `args` read, like the components it uses and the scripts
+/// of its preprocessors, as well as the config file, recompiling when any of them change. The
+/// arguments and config are loaded again with `load` before each recompilation, and the files that
+/// are watched are updated after it. Components of a page that weren't affected are restored from
+/// the build cache. `on_compile` is called after each recompilation.
+pub(crate) fn watch<L, F>(
+    args: &Build,
+    deps: Vec<PathBuf>,
+    mut load: L,
+    mut on_compile: F,
+) -> Result<(), anyhow::Error>
+where
+    L: FnMut() -> Result<(Build, Config)>,
+    F: FnMut() -> Result<()>,
+{
+    let logger = Logger::for_build(args);
+    let inputs = inputs(args)
+        .into_iter()
+        .filter_map(|input| fs::canonicalize(input).ok())
+        .collect::<Vec<_>>();
     let (tx, rx) = std::sync::mpsc::channel();
     let mut watcher = RecommendedWatcher::new(tx, notify::Config::default())
         .context("error creating up watcher")?;
-    watcher
-        .watch(&args.input, RecursiveMode::NonRecursive)
-        .context("error watching input file")?;
-    for res in rx {
+    let mut watched = vec![];
+    watch_deps(&mut watcher, &mut watched, deps)?;
+    for res in &rx {
         let event = res?;
-        debug_assert_eq!(1, event.paths.len(), "watching invalid targets!");
-        match event.kind {
-            EventKind::Modify(ModifyKind::Data(DataChange::Content)) => {
-                println!();
-                compile(args, config)?;
-            }
-            EventKind::Remove(_) => {
+        if matches!(event.kind, EventKind::Remove(_))
+            && event.paths.iter().any(|path| inputs.contains(path))
+        {
+            if logger.is_human() {
                 println!("Input file removed... exiting process");
-                break;
             }
-            _ => {}
+            break;
+        }
+        let changed = matches!(
+            event.kind,
+            EventKind::Create(_)
+                | EventKind::Remove(_)
+                | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+        );
+        // Directories are read to expand the globs of `{#use}`, so only components being added
+        // or removed in them matter, not outputs being written next to them
+        let affects_build = |path: &PathBuf| {
+            watched.contains(path)
+                || path.extension().is_some_and(|ext| ext == "decor")
+                    && path
+                        .parent()
+                        .is_some_and(|dir| watched.iter().any(|path| path == dir))
+        };
+        if !changed || !event.paths.iter().any(affects_build) {
+            continue;
+        }
+        // Saving or copying a file often comes with several events, which are all one change
+        while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+
+        if logger.is_human() {
+            if clears_terminal(args) {
+                print!("\x1b[2J\x1b[3J\x1b[H");
+            } else {
+                println!();
+            }
+        }
+        let rebuilt = load().and_then(|(args, config)| {
+            let deps = build_entries(&args, &config)?;
+            on_compile()?;
+            Ok(deps)
+        });
+        match rebuilt {
+            Ok(deps) => watch_deps(&mut watcher, &mut watched, deps)?,
+            // Files are often broken while they're being edited, so the next change is waited
+            // for, still watching what the last successful build read
+            Err(err) => report_error(&err),
         }
     }
 
     Ok(())
 }
 
+/// The files passed to be compiled.
+fn inputs(args: &Build) -> Vec<PathBuf> {
+    iter::once(&args.input)
+        .chain(&args.others)
+        .cloned()
+        .collect()
+}
+
+/// Whether `--watch` clears the terminal before each rebuild.
+pub(crate) fn clears_terminal(args: &Build) -> bool {
+    !args.no_clear && Logger::for_build(args).is_human() && atty::is(atty::Stream::Stdout)
+}
+
+/// Prints an error that doesn't stop `--watch`, the same way as one that ends the process.
+pub(crate) fn report_error(err: &anyhow::Error) {
+    eprintln!("Error: {err:#}");
+}
+
+/// How long `--watch` waits for more events after a change before recompiling.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Replaces the paths that `watcher` watches with `deps` and the config file. Paths are
+/// canonicalized, so they can be compared with the paths of events.
+fn watch_deps(
+    watcher: &mut RecommendedWatcher,
+    watched: &mut Vec<PathBuf>,
+    deps: Vec<PathBuf>,
+) -> Result<()> {
+    for path in watched.drain(..) {
+        // The path may have been removed, which stops it from being watched
+        let _ = watcher.unwatch(&path);
+    }
+    for path in deps.into_iter().chain(utils::find_config()?) {
+        let Ok(path) = fs::canonicalize(&path) else {
+            continue;
+        };
+        if watched.contains(&path) {
+            continue;
+        }
+        watcher
+            .watch(&path, RecursiveMode::NonRecursive)
+            .with_context(|| format!("error watching {}", path.display()))?;
+        watched.push(path);
+    }
+    Ok(())
+}
+
 fn warn_on_unused_wasm(global_ctx: &GlobalCtx, component: &Component<'_>) -> Result<()> {
     if component.wasm.is_some() {
         return Ok(());
     }
 
-    if global_ctx.args.strip {
+    // Profiles are shared by every component, so they aren't expected to fit each one
+    let from_profile = global_ctx.args.profile.is_some();
+    if global_ctx.args.strip && !from_profile {
         global_ctx.errs.emit(
             DiagnosticBuilder::new("no WebAssembly to strip", 0)
                 .severity(Severity::Warning)
@@ -161,7 +533,7 @@ fn warn_on_unused_wasm(global_ctx: &GlobalCtx, component: &Component<'_>) -> Res
                 .build(),
         );
     }
-    if global_ctx.args.optimize.is_some() {
+    if global_ctx.args.optimize.is_some() && !from_profile {
         global_ctx.errs.emit(
             DiagnosticBuilder::new("no WebAssembly to optimize", 0)
                 .severity(Severity::Warning)
@@ -172,13 +544,156 @@ fn warn_on_unused_wasm(global_ctx: &GlobalCtx, component: &Component<'_>) -> Res
     Ok(())
 }
 
+/// Creates the backend chosen by the arguments, configured with them.
+fn backend(global_ctx: &GlobalCtx, component: &Component) -> Result<Box<dyn DynRenderBackend>> {
+    let css = css_options(global_ctx)?;
+    let runtime = shared_runtime(global_ctx, component);
+    Ok(match global_ctx.args.render_method {
+        RenderMethod::Csr => {
+            let mut csr_renderer = CsrRenderer::new();
+            csr_renderer.with_options(CsrOptions {
+                modularize: global_ctx.args.modularize,
+                format: global_ctx.args.format.into(),
+                bundle: global_ctx.args.bundle,
+                custom_element: global_ctx.args.target == Target::CustomElement,
+                css,
+                html_sanitizer: global_ctx.config.html.sanitizer.clone(),
+                no_collapse: global_ctx.config.html.collapse == Some(false),
+                csp: global_ctx.config.html.csp,
+                nonce: global_ctx.config.html.nonce.clone(),
+                runtime,
+            });
+            Box::new(csr_renderer)
+        }
+        RenderMethod::Prerender => {
+            let mut prerenderer = Prerenderer::new();
+            prerenderer.with_options(PrerenderOptions {
+                format: global_ctx.args.format.into(),
+                bundle: global_ctx.args.bundle,
+                css,
+                html_sanitizer: global_ctx.config.html.sanitizer.clone(),
+                no_collapse: global_ctx.config.html.collapse == Some(false),
+                csp: global_ctx.config.html.csp,
+                nonce: global_ctx.config.html.nonce.clone(),
+                // Prerendered components look up their elements by id, which would collide with
+                // the ids of the other components on the page
+                id_prefix: global_ctx
+                    .args
+                    .page
+                    .as_ref()
+                    .map_or_else(String::new, |page| format!("{}-", page.name)),
+                runtime,
+            });
+            Box::new(prerenderer)
+        }
+    })
+}
+
+/// The path that `component` imports its helpers from, if it shares them with the other
+/// components of the build. A component on its own defines them, saving the request for the
+/// runtime module.
+fn shared_runtime(global_ctx: &GlobalCtx, component: &Component) -> Option<String> {
+    if component.uses.is_empty() && global_ctx.args.page.is_none() {
+        return None;
+    }
+    global_ctx.runtime(global_ctx.args.format.into(), global_ctx.args.bundle)
+}
+
+/// Writes the runtime module that the components of the build import their helpers from, next to
+/// the output.
+fn write_runtime(global_ctx: &GlobalCtx) -> Result<()> {
+    let format = OutputFormat::from(global_ctx.args.format);
+    let path = Path::new(&global_ctx.args.out).with_file_name(runtime::file_name(format));
+    let mut js = vec![];
+    runtime::write_runtime(&mut js, format)?;
+    if global_ctx.args.minify {
+        js = minify_js(&String::from_utf8_lossy(&js), &HashSet::new()).into_bytes();
+    }
+    fs::write(&path, js).with_context(|| format!("error writing {}", path.display()))?;
+    global_ctx.record.wrote(path);
+
+    Ok(())
+}
+
+/// The CSS options of the component, with the contents of the global stylesheets from the config
+/// file and `--global-css`.
+fn css_options(global_ctx: &GlobalCtx) -> Result<CssOptions> {
+    let mut opts = global_ctx.config.css.options(global_ctx.args.minify_css);
+    if global_ctx
+        .args
+        .page
+        .as_ref()
+        .is_some_and(|page| !page.global_css)
+    {
+        return Ok(opts);
+    }
+    for path in global_ctx
+        .config
+        .css
+        .global
+        .iter()
+        .chain(&global_ctx.args.global_css)
+    {
+        let css = fs::read_to_string(path)
+            .with_context(|| format!("error reading global stylesheet {}", path.display()))?;
+        global_ctx.record.read(path);
+        opts.global.push(css);
+    }
+    Ok(opts)
+}
+
+fn render_with<T: RenderOut>(
+    global_ctx: &GlobalCtx,
+    component: &Component<'_>,
+    mut out: T,
+    metadata: &RenderCtx<'_>,
+) -> Result<()> {
+    let _span = global_ctx.time("render", global_ctx.args.render_method.to_string());
+    backend(global_ctx, component)?.render_dyn(component, &mut out, metadata)?;
+
+    Ok(())
+}
+
+/// Renders `component` to `out`, minifying the JavaScript if it was asked for.
+fn render_minified<T: RenderOut>(
+    global_ctx: &GlobalCtx,
+    component: &Component<'_>,
+    out: T,
+    metadata: &RenderCtx<'_>,
+) -> Result<()> {
+    if global_ctx.args.minify {
+        let mut minified = Minified::new(out);
+        render_with(global_ctx, component, &mut minified, metadata)?;
+        minified.finish()?;
+    } else {
+        render_with(global_ctx, component, out, metadata)?;
+    }
+
+    Ok(())
+}
+
+/// Renders `component` to `out`, with the JavaScript minified or escaped if it was asked for.
+fn render_to<T: RenderOut>(
+    global_ctx: &GlobalCtx,
+    component: &Component<'_>,
+    out: T,
+    metadata: &RenderCtx<'_>,
+) -> Result<()> {
+    if global_ctx.args.ascii_only {
+        render_minified(global_ctx, component, AsciiOnly::new(out), metadata)
+    } else {
+        render_minified(global_ctx, component, out, metadata)
+    }
+}
+
 fn render_all(
     global_ctx: &GlobalCtx,
     component: &Component<'_>,
     metadata: &RenderCtx<'_>,
 ) -> Result<()> {
-    let js_name = if global_ctx.args.modularize {
-        format!("{}.mjs", global_ctx.args.out)
+    let mut js_name = if global_ctx.args.modularize {
+        let format = OutputFormat::from(global_ctx.args.format);
+        format!("{}.{}", global_ctx.args.out, format.module_extension())
     } else {
         format!("{}.js", global_ctx.args.out)
     };
@@ -189,6 +704,7 @@ fn render_all(
         css: Option<BufWriter<File>>,
         base: &'a str,
         index_html: bool,
+        index_name: PathBuf,
     }
 
     impl RenderOut for Out<'_> {
@@ -212,7 +728,7 @@ fn render_all(
                 Some(html) => html.write_all(buf),
                 None => {
                     let f = if self.index_html {
-                        File::create("index.html")?
+                        File::create(&self.index_name)?
                     } else {
                         File::create(format!("{}.html", self.base))?
                     };
@@ -227,82 +743,202 @@ fn render_all(
         }
     }
 
+    // Outputs are written while rendering, so an interrupted build would leave them half-written
+    let _partial = [
+        interrupt::partial_output(&js_name),
+        interrupt::partial_output(format!("{}.css", global_ctx.args.out)),
+        interrupt::partial_output(if global_ctx.args.html {
+            index_html(global_ctx.args)
+        } else {
+            PathBuf::from(format!("{}.html", global_ctx.args.out))
+        }),
+    ];
+    let start = Instant::now();
     let mut out = Out {
         js: BufWriter::new(File::create(&js_name)?),
         html: None,
         css: None,
         base: &global_ctx.args.out,
         index_html: global_ctx.args.html,
+        index_name: index_html(global_ctx.args),
     };
-    match global_ctx.args.render_method {
-        RenderMethod::Csr => {
-            let mut csr_renderer = CsrRenderer::new();
-            csr_renderer.with_options(CsrOptions {
-                modularize: global_ctx.args.modularize,
-            });
-            csr_renderer.render(component, &mut out, metadata)?;
+    let report = if global_ctx.args.analyze {
+        let mut measured = Measured::new(&mut out);
+        render_to(global_ctx, component, &mut measured, metadata)?;
+        Some(Report::new(
+            component,
+            &measured,
+            &global_ctx.analysis,
+            global_ctx.args.color,
+        ))
+    } else {
+        render_to(global_ctx, component, &mut out, metadata)?;
+        None
+    };
+
+    let wrote_css = out.css.is_some();
+    let wrote_html = out.html.is_some();
+    if let Some(mut html) = out.html {
+        html.flush()?;
+    }
+    if let Some(mut css) = out.css {
+        css.flush()?;
+    }
+    out.js.flush()?;
+    let duration = start.elapsed();
+
+    let html_name = if global_ctx.args.html {
+        index_html(global_ctx.args)
+    } else {
+        PathBuf::from(format!("{}.html", global_ctx.args.out))
+    };
+    let mut css_name = format!("{}.css", global_ctx.args.out);
+    if global_ctx.config.output.hash {
+        let mut renames = vec![];
+        for (name, wrote) in [(&mut js_name, true), (&mut css_name, wrote_css)] {
+            if wrote {
+                let hashed = hash_file_name(name)?;
+                renames.push((mem::replace(name, hashed.clone()), hashed));
+            }
         }
-        RenderMethod::Prerender => {
-            let prerenderer = Prerenderer::new();
-            prerenderer.render(component, &mut out, metadata)?;
+        if wrote_html && global_ctx.args.html {
+            rewrite_references(&html_name, &renames)?;
         }
     }
 
-    if out.html.is_some() {
-        let html_name = if global_ctx.args.html {
-            Cow::Borrowed(Path::new("index.html"))
-        } else {
-            Cow::Owned(PathBuf::from(format!("{}.html", global_ctx.args.out)))
-        };
-        println!(
-            "{}",
+    global_ctx.record.wrote(&js_name);
+    if global_ctx.args.modularize {
+        write_declarations(global_ctx, component, &js_name, metadata.name)?;
+    }
+    if wrote_css {
+        global_ctx.record.wrote(&css_name);
+    }
+    let logger = global_ctx.log();
+    if wrote_html {
+        global_ctx.record.wrote(&html_name);
+        logger.log(
             FinishLog::default()
                 .with_main_message("HTML")
-                .with_file(&html_name)
-                .enable_color(global_ctx.args.color)
+                .with_phase(Phase::Render)
+                .with_size(file_size(&html_name))
+                .with_file(&html_name),
+        );
+    }
+    if wrote_css {
+        logger.detail(
+            FinishLog::default()
+                .with_main_message("CSS")
+                .with_phase(Phase::Render)
+                .with_size(file_size(Path::new(&css_name)))
+                .with_file(&css_name),
         );
     }
 
-    println!(
-        "{}",
+    logger.log(
         FinishLog::default()
             .with_main_message("JavaScript")
             .with_sub_message(global_ctx.args.render_method.to_string())
-            .enable_color(global_ctx.args.color)
-            .with_file(js_name)
+            .with_phase(Phase::Render)
+            .with_duration(duration)
+            .with_size(file_size(Path::new(&js_name)))
+            .with_file(js_name),
     );
 
-    if let Some(mut html) = out.html {
-        html.flush()?;
+    if let Some(report) = report {
+        println!("\n{report}");
     }
-    if let Some(mut css) = out.css {
-        css.flush()?;
-    }
-    out.js.flush()?;
 
     Ok(())
 }
 
+/// Writes the TypeScript declarations of the modularized `component` next to its JavaScript at
+/// `js_name`, like `out.d.mts` for `out.mjs`.
+fn write_declarations(
+    global_ctx: &GlobalCtx,
+    component: &Component<'_>,
+    js_name: &str,
+    name: &str,
+) -> Result<()> {
+    let format = OutputFormat::from(global_ctx.args.format);
+    let Some(extension) = format.declaration_extension() else {
+        return Ok(());
+    };
+    let base = js_name.rsplit_once('.').map_or(js_name, |(base, _)| base);
+    let path = format!("{base}.{extension}");
+    let mut dts = vec![];
+    dom_render::write_declarations(
+        &mut dts,
+        component,
+        name,
+        format,
+        global_ctx.args.target == Target::CustomElement,
+    )?;
+    fs::write(&path, dts).with_context(|| format!("error writing {path}"))?;
+    global_ctx.record.wrote(path);
+
+    Ok(())
+}
+
+/// Renames the file at `path` so that a hash of its contents comes before its extension, like
+/// `out.3f2a9c1e.js`. Returns the new path.
+fn hash_file_name(path: &str) -> Result<String> {
+    let contents = fs::read(path).with_context(|| format!("error reading {path}"))?;
+    let hash = sha256::digest(contents.as_slice());
+    let hashed = match path.rsplit_once('.') {
+        Some((base, ext)) => format!("{base}.{}.{ext}", &hash[..8]),
+        None => format!("{path}.{}", &hash[..8]),
+    };
+    fs::rename(path, &hashed).with_context(|| format!("error renaming {path}"))?;
+    Ok(hashed)
+}
+
+/// Points the references to renamed outputs in the HTML at `path` to their new names.
+fn rewrite_references(path: &Path, renames: &[(String, String)]) -> Result<()> {
+    let mut html = fs::read_to_string(path).context("error reading generated HTML")?;
+    for (old, new) in renames {
+        html = html.replace(&format!("\"{old}\""), &format!("\"{new}\""));
+    }
+    fs::write(path, html).context("error writing generated HTML")?;
+    Ok(())
+}
+
 fn parse_component<'a>(
     input: &'a str,
     global_ctx: &GlobalCtx<'a>,
     ctx: ParseCtx<'a>,
 ) -> Result<Component<'a>> {
+    let start = Instant::now();
+    let span = global_ctx.time("parse", "");
     let parser = Parser::new(input).with_ctx(ctx.clone());
     let mut component = match parser.parse() {
         Ok(ast) => Component::new(ast, ctx),
         Err(err) => {
-            let diagnostic = err.into();
-            global_ctx.errs.emit(diagnostic);
+            // Every error has already been emitted if there was more than one
+            if err.err_type() != &ParseErrorType::DidError {
+                global_ctx.errs.emit(err.into());
+            }
             anyhow::bail!("\nthe decorous parser failed");
         }
     };
-    component.run_passes()?;
-    println!(
-        "{}",
+    drop(span);
+    let logger = global_ctx.log();
+    logger.log(
         FinishLog::default()
             .with_main_message("parsed")
-            .enable_color(global_ctx.args.color)
+            .with_phase(Phase::Parse)
+            .with_duration(start.elapsed()),
+    );
+
+    let start = Instant::now();
+    component.run_passes_with(|name, run| {
+        let _span = global_ctx.time("pass", name);
+        run()
+    })?;
+    logger.detail(
+        FinishLog::default()
+            .with_main_message("ran passes")
+            .with_phase(Phase::Passes)
+            .with_duration(start.elapsed()),
     );
     Ok(component)
 }
@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use duct::cmd;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Config, Dependency},
+    utils,
+};
+
+const LOCKFILE: &str = "decor.lock";
+
+/// The packages of components declared in the `dependencies` table of `decor.toml`. Components
+/// use them with paths that start with `@` and the name of the package, like
+/// `{#use "@ui/button.decor"}`.
+///
+/// Git packages are fetched into the cache. The commit each one was resolved to is kept in
+/// `decor.lock`, next to the config file, so every build uses the same commit until the
+/// dependency changes or the lockfile is removed.
+pub struct Packages<'a> {
+    config: &'a Config,
+    /// The directory of the config file, which the paths of dependencies are relative to.
+    root: Option<PathBuf>,
+    lock: Mutex<Lockfile>,
+    /// The directories that packages were fetched into during this build.
+    fetched: Mutex<HashMap<String, PathBuf>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Lockfile {
+    #[serde(default, rename = "package")]
+    packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LockedPackage {
+    name: String,
+    git: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rev: Option<String>,
+    commit: String,
+}
+
+impl<'a> Packages<'a> {
+    pub fn new(config: &'a Config) -> Result<Self> {
+        let root = utils::find_config()?.and_then(|path| path.parent().map(Path::to_path_buf));
+        let lock = match &root {
+            Some(root) if root.join(LOCKFILE).exists() => {
+                let contents =
+                    fs::read_to_string(root.join(LOCKFILE)).context("error reading lockfile")?;
+                toml::from_str(&contents).context("error parsing lockfile")?
+            }
+            _ => Lockfile::default(),
+        };
+
+        Ok(Self {
+            config,
+            root,
+            lock: Mutex::new(lock),
+            fetched: Mutex::default(),
+        })
+    }
+
+    /// The lockfile, if there are git dependencies. Builds depend on it, since it decides which
+    /// commit of each package is used.
+    pub fn lockfile(&self) -> Option<PathBuf> {
+        let has_git = self
+            .config
+            .dependencies
+            .values()
+            .any(|dep| matches!(dep, Dependency::Git { .. }));
+        self.root
+            .as_ref()
+            .filter(|_| has_git)
+            .map(|root| root.join(LOCKFILE))
+    }
+
+    /// Gets the file that a used component is at. Paths into packages are mapped into the
+    /// directory of the package, which is fetched if it hasn't been yet, and every other path is
+    /// left as is.
+    pub fn locate(&self, path: &Path) -> Result<PathBuf> {
+        let mut components = path.components();
+        let Some(name) = components
+            .next()
+            .and_then(|component| component.as_os_str().to_str())
+            .and_then(|component| component.strip_prefix('@'))
+        else {
+            return Ok(path.to_path_buf());
+        };
+
+        Ok(self.fetch(name)?.join(components.as_path()))
+    }
+
+    fn fetch(&self, name: &str) -> Result<PathBuf> {
+        // Held until the package is fetched, so components used at the same time never fetch it
+        // twice
+        let mut fetched = self
+            .fetched
+            .lock()
+            .expect("package lock should not be poisoned");
+        if let Some(dir) = fetched.get(name) {
+            return Ok(dir.clone());
+        }
+
+        let dep = self
+            .config
+            .dependencies
+            .get(name)
+            .with_context(|| format!("no dependency named {name} in the config file"))?;
+        let dir = match dep {
+            Dependency::Path { path } => match &self.root {
+                Some(root) => root.join(path),
+                None => path.clone(),
+            },
+            Dependency::Git { git, rev } => self
+                .checkout(name, git, rev.as_deref())
+                .with_context(|| format!("error fetching the {name} package from {git}"))?,
+        };
+        fetched.insert(name.to_owned(), dir.clone());
+
+        Ok(dir)
+    }
+
+    /// Checks out the commit of a git package that is in the lockfile, or the one that `rev`
+    /// points to if it isn't locked yet, returning the directory it was checked out in.
+    fn checkout(&self, name: &str, git: &str, rev: Option<&str>) -> Result<PathBuf> {
+        let base = utils::get_cache_base()
+            .context("could not get cache base")?
+            .join("packages");
+        // Every package from the same repository shares a bare clone of it
+        let repo = base.join(sha256::digest(git).chars().take(16).collect::<String>());
+        if !repo.exists() {
+            fs::create_dir_all(&base).context("error creating package cache")?;
+            cmd!("git", "clone", "--bare", "--quiet", git, &repo)
+                .stdout_null()
+                .run()
+                .context("error cloning repository")?;
+        }
+
+        let mut lock = self.lock.lock().expect("lockfile should not be poisoned");
+        let locked = lock
+            .packages
+            .iter()
+            .find(|locked| locked.name == name && locked.git == git && locked.rev.as_deref() == rev)
+            .map(|locked| locked.commit.clone());
+        let commit = match locked {
+            Some(commit) => {
+                if !has_commit(&repo, &commit) {
+                    update(&repo, git)?;
+                }
+                commit
+            }
+            None => {
+                update(&repo, git)?;
+                let commit = cmd!(
+                    "git",
+                    "-C",
+                    &repo,
+                    "rev-parse",
+                    "--verify",
+                    format!("{}^{{commit}}", rev.unwrap_or("HEAD"))
+                )
+                .stderr_null()
+                .read()
+                .with_context(|| format!("no revision {} in repository", rev.unwrap_or("HEAD")))?;
+                lock.packages.retain(|locked| locked.name != name);
+                lock.packages.push(LockedPackage {
+                    name: name.to_owned(),
+                    git: git.to_owned(),
+                    rev: rev.map(str::to_owned),
+                    commit: commit.clone(),
+                });
+                lock.packages.sort_by(|a, b| a.name.cmp(&b.name));
+                self.write_lock(&lock)?;
+                commit
+            }
+        };
+
+        let dir = base.join(format!(
+            "{name}-{}",
+            commit.chars().take(12).collect::<String>()
+        ));
+        if !dir.exists() {
+            // Checked out into a temporary directory first, so a failed checkout is never used
+            let tmp = base.join(format!("tmp-{}", process::id()));
+            fs::create_dir_all(&tmp).context("error creating package directory")?;
+            cmd!("git", "-C", &repo, "archive", "--format=tar", &commit)
+                .pipe(cmd!("tar", "-x", "-C", &tmp))
+                .run()
+                .context("error checking out package")?;
+            if fs::rename(&tmp, &dir).is_err() {
+                fs::remove_dir_all(&tmp).context("error removing temporary package directory")?;
+            }
+        }
+
+        Ok(dir)
+    }
+
+    fn write_lock(&self, lock: &Lockfile) -> Result<()> {
+        let Some(root) = &self.root else {
+            return Ok(());
+        };
+        let contents = format!(
+            "# Generated by decorous. It pins the commits of git dependencies.\n\n{}",
+            toml::to_string(lock).context("error serializing lockfile")?
+        );
+        fs::write(root.join(LOCKFILE), contents).context("error writing lockfile")
+    }
+}
+
+fn has_commit(repo: &Path, commit: &str) -> bool {
+    cmd!(
+        "git",
+        "-C",
+        repo,
+        "cat-file",
+        "-e",
+        format!("{commit}^{{commit}}")
+    )
+    .stderr_null()
+    .unchecked()
+    .run()
+    .is_ok_and(|out| out.status.success())
+}
+
+/// Fetches every branch and tag of the repository that `repo` is a clone of.
+fn update(repo: &Path, git: &str) -> Result<()> {
+    cmd!(
+        "git",
+        "-C",
+        repo,
+        "fetch",
+        "--quiet",
+        "--force",
+        git,
+        "refs/heads/*:refs/heads/*",
+        "refs/tags/*:refs/tags/*"
+    )
+    .run()
+    .context("error fetching repository")?;
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::{collections::HashSet, fs, iter, path::PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use decorous_backend::{HtmlTemplate, TemplateValues};
+
+use super::{analyze::file_size, compile, index_html, load_html_template, Compiled};
+use crate::{
+    cli::{Build, PageEntry, Target},
+    config::Config,
+    indicators::{FinishLog, Logger, Phase},
+};
+
+/// Builds every component in `args` with its own outputs, then writes an index.html that mounts
+/// all of them. Each component is mounted on an element with its name as the id.
+pub fn build_page(args: &Build, config: &Config) -> Result<Compiled> {
+    ensure!(
+        !args.modularize,
+        "modularized components cannot be mounted on a page!"
+    );
+    ensure!(
+        args.target == Target::Document,
+        "custom elements cannot share a page!"
+    );
+    let mut deps = vec![];
+    let mut written = vec![];
+    let template = match load_html_template(args, config)? {
+        Some((path, template)) => {
+            deps.push(path.to_path_buf());
+            template
+        }
+        None => HtmlTemplate::default(),
+    };
+
+    let mut names = HashSet::new();
+    let mut scripts = vec![];
+    let mut css = vec![];
+    let mut markup = vec![];
+    for input in iter::once(&args.input).chain(&args.others) {
+        let name = input
+            .file_stem()
+            .with_context(|| format!("{} is not a file", input.display()))?
+            .to_string_lossy()
+            .into_owned();
+        ensure!(
+            names.insert(name.clone()),
+            "more than one component is named {name}!"
+        );
+
+        let out = format!("{}_{name}", args.out);
+        let compiled = compile(
+            &Build {
+                input: input.clone(),
+                others: vec![],
+                out: out.clone(),
+                html: false,
+                html_template: None,
+                page: Some(PageEntry {
+                    name: name.clone(),
+                    global_css: names.len() == 1,
+                }),
+                ..args.clone()
+            },
+            config,
+        )?;
+        let outputs = compiled.outputs;
+        deps.extend(compiled.deps);
+        written.extend(outputs.iter().cloned());
+
+        // Outputs may have a hash before their extension, like `out_app.3f2a9c1e.js`
+        let find = |ext: &str| {
+            outputs
+                .iter()
+                .map(|path| path.to_string_lossy())
+                .find(|path| path.starts_with(&format!("{out}.")) && path.ends_with(ext))
+                .map(|path| path.into_owned())
+        };
+        scripts.push(
+            find(".js").with_context(|| format!("{} generated no JavaScript", input.display()))?,
+        );
+        css.extend(find(".css"));
+
+        // The prerendered markup is put straight into the page
+        let fragment = PathBuf::from(format!("{out}.html"));
+        let html = if outputs.contains(&fragment) {
+            let html = fs::read_to_string(&fragment)
+                .with_context(|| format!("error reading {}", fragment.display()))?;
+            fs::remove_file(&fragment)
+                .with_context(|| format!("error removing {}", fragment.display()))?;
+            html
+        } else {
+            String::new()
+        };
+        markup.push(format!("<div id=\"{name}\">{}</div>", html.trim()));
+    }
+
+    let title = args.input.file_stem().unwrap_or_default().to_string_lossy();
+    let (start, end) = template.render(&TemplateValues {
+        scripts: &scripts,
+        css: &css,
+        title: &title,
+        head: "",
+        nonce: config.html.nonce.as_deref(),
+    });
+    let index = index_html(args);
+    fs::write(&index, format!("{start}{}{end}", markup.join("\n")))
+        .with_context(|| format!("error writing {}", index.display()))?;
+    Logger::for_build(args).log(
+        FinishLog::default()
+            .with_main_message("HTML")
+            .with_phase(Phase::Render)
+            .with_size(file_size(&index))
+            .with_file(&index),
+    );
+    written.push(index);
+
+    Ok(Compiled {
+        outputs: written,
+        deps,
+    })
+}
@@ -1,47 +1,115 @@
 use duct::cmd;
-use std::borrow::Cow;
+use serde::Deserialize;
+use std::{borrow::Cow, path::Path};
 use tempdir::TempDir;
 
-use decorous_frontend::{location::Location, Override, PreprocessError, Preprocessor};
+use decorous_frontend::{
+    location::Location, Override, PositionMap, PreprocessError, Preprocessed, Preprocessor,
+};
 
 use crate::{
-    config::{Config, PreprocTarget},
-    indicators::{FinishLog, Spinner},
+    cache::BuildRecord,
+    config::{Config, PreprocTarget, PreprocessPipeline},
+    indicators::{FinishLog, Logger, Phase},
+    interrupt,
 };
 
 #[derive(Debug, Clone)]
 pub struct Preproc<'a> {
     config: &'a Config,
-    enable_color: bool,
+    logger: Logger,
+    record: Option<&'a BuildRecord>,
+}
+
+/// What a preprocessor `command` writes to stdout.
+#[derive(Debug, Deserialize)]
+struct CommandOutput {
+    code: String,
+    #[serde(default)]
+    diagnostics: Vec<CommandDiagnostic>,
+    /// Pairs of byte offsets in `code` and in the code block, for a [`PositionMap`].
+    #[serde(default)]
+    mappings: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommandDiagnostic {
+    message: String,
+    #[serde(default)]
+    severity: CommandSeverity,
+    /// Byte offsets into the code block.
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum CommandSeverity {
+    #[default]
+    Error,
+    Warning,
 }
 
 impl<'a> Preproc<'a> {
-    pub fn new(config: &'a Config, enable_color: bool) -> Self {
+    pub fn new(config: &'a Config, logger: Logger) -> Self {
         Self {
             config,
-            enable_color,
+            logger,
+            record: None,
         }
     }
-}
 
-impl Preprocessor for Preproc<'_> {
-    fn preprocess(&self, lang: &str, body: &str) -> Result<Override, PreprocessError> {
-        let Some(cfg) = &self.config.preprocessors.get(lang) else {
-            return Ok(Override::None);
+    /// Records the scripts and programs that preprocessors run in `record`, so the outputs of the
+    /// build are only reused while they are unchanged. Scripts are the files named by the command
+    /// of a preprocessor, its arguments, or the words of its pipeline. Programs are the commands
+    /// and the first word of each step of the pipeline that aren't files, whose versions are
+    /// recorded instead.
+    pub fn recorded_in(mut self, record: &'a BuildRecord) -> Self {
+        self.record = Some(record);
+        self
+    }
+
+    fn record_scripts(&self, cfg: &PreprocessPipeline) {
+        let Some(record) = self.record else {
+            return;
         };
+        let words = cfg
+            .command
+            .iter()
+            .chain(&cfg.args)
+            .map(String::as_str)
+            .chain(cfg.pipeline.iter().flat_map(|comp| comp.split_whitespace()));
+        for word in words {
+            if Path::new(word).is_file() {
+                record.read(word);
+            }
+        }
+        let programs = cfg.command.iter().map(String::as_str).chain(
+            cfg.pipeline
+                .iter()
+                .filter_map(|comp| comp.split_whitespace().next()),
+        );
+        for program in programs.filter(|program| !Path::new(program).is_file()) {
+            record.ran(program);
+        }
+    }
 
+    fn run_pipeline(
+        &self,
+        lang: &str,
+        cfg: &PreprocessPipeline,
+        body: &str,
+    ) -> Result<Option<String>, PreprocessError> {
         let mut to_pipe = Cow::Borrowed(body);
         let len = cfg.pipeline.len();
         for (i, comp) in cfg.pipeline.iter().enumerate() {
-            let spinner = Spinner::new("Running preprocessor");
+            let spinner = self.logger.spinner("Running preprocessor");
             let temp = TempDir::new(lang).map_err(|err| {
-                PreprocessError::new(
-                    Location::default(),
-                    Cow::Owned(format!(
-                        "error creating temporary directory for preprocessing: {err}"
-                    )),
-                )
+                PreprocessError::new(format!(
+                    "error creating temporary directory for preprocessing: {err}"
+                ))
             })?;
+            let _cleanup = interrupt::temp_dir(temp.path());
             let out = cmd!("echo", to_pipe.as_ref())
                 .pipe(cmd!("sh", "-c", comp))
                 .dir(temp.path())
@@ -49,50 +117,130 @@ impl Preprocessor for Preproc<'_> {
                 .unchecked()
                 .run()
                 .map_err(|err| {
-                    PreprocessError::new(
-                        Location::default(),
-                        Cow::Owned(format!("error preprocessing this code block: {err}")),
-                    )
+                    PreprocessError::new(format!("error preprocessing this code block: {err}"))
                 })?;
             let stdout = String::from_utf8(out.stdout).map_err(|err| {
-                PreprocessError::new(
-                    Location::default(),
-                    Cow::Owned(format!(
-                        "preprocessor for {lang} stdout was not valid UTF-8: {err}"
-                    )),
-                )
+                PreprocessError::new(format!(
+                    "preprocessor for {lang} stdout was not valid UTF-8: {err}"
+                ))
             })?;
             if !out.status.success() {
                 // Re-print stdout. stderr is already not redirected
-                return Err(PreprocessError::new(
-                    Location::default(),
-                    Cow::Owned(format!("error preprocessing this code block:\n{stdout}")),
-                ));
+                return Err(PreprocessError::new(format!(
+                    "error preprocessing this code block:\n{stdout}"
+                )));
             }
             to_pipe = Cow::Owned(stdout);
             spinner.finish(
-                FinishLog::default()
-                    .enable_color(self.enable_color)
-                    .with_main_message("preprocessor")
-                    .with_sub_message(format!(
-                        "{} - {lang}",
-                        match cfg.target {
-                            PreprocTarget::Js => "JavaScript",
-                            PreprocTarget::Css => "CSS",
-                        }
-                    ))
-                    .with_mod(format!("{}/{len}", i + 1))
-                    .to_string(),
+                self.finish_log(lang, cfg.target)
+                    .with_mod(format!("{}/{len}", i + 1)),
             );
         }
 
         match to_pipe {
-            Cow::Owned(s) => Ok(if cfg.target == PreprocTarget::Js {
-                Override::Js(s)
+            Cow::Owned(s) => Ok(Some(s)),
+            Cow::Borrowed(_) => Ok(None),
+        }
+    }
+
+    /// Runs the `command` of a preprocessor, which speaks JSON instead of writing the output
+    /// directly. Its error diagnostics are turned into errors, and the rest are returned as
+    /// warnings.
+    fn run_command(
+        &self,
+        lang: &str,
+        cfg: &PreprocessPipeline,
+        command: &str,
+        body: &str,
+    ) -> Result<(String, Vec<PreprocessError>, Option<PositionMap>), PreprocessError> {
+        let spinner = self.logger.spinner("Running preprocessor");
+        let out = duct::cmd(command, &cfg.args)
+            .stdin_bytes(body)
+            .stdout_capture()
+            .unchecked()
+            .run()
+            .map_err(|err| {
+                PreprocessError::new(format!("error running preprocessor `{command}`: {err}"))
+            })?;
+        let stdout = String::from_utf8(out.stdout).map_err(|err| {
+            PreprocessError::new(format!(
+                "preprocessor for {lang} stdout was not valid UTF-8: {err}"
+            ))
+        })?;
+        let output = serde_json::from_str::<CommandOutput>(&stdout).map_err(|err| {
+            if out.status.success() {
+                PreprocessError::new(format!(
+                    "preprocessor for {lang} did not output valid JSON: {err}"
+                ))
             } else {
-                Override::Css(s)
-            }),
-            Cow::Borrowed(_) => Ok(Override::None),
+                PreprocessError::new(format!("error preprocessing this code block:\n{stdout}"))
+            }
+        })?;
+
+        let mut warnings = vec![];
+        for diagnostic in output.diagnostics {
+            let mut err = PreprocessError::new(diagnostic.message);
+            if let Some(start) = diagnostic.start {
+                let end = diagnostic.end.unwrap_or(start + 1).max(start + 1);
+                err = err.with_loc(Location::new(start, end - start));
+            }
+            match diagnostic.severity {
+                CommandSeverity::Error => return Err(err),
+                CommandSeverity::Warning => warnings.push(err),
+            }
+        }
+        if !out.status.success() {
+            return Err(PreprocessError::new(format!(
+                "preprocessor for {lang} failed with {}",
+                out.status
+            )));
         }
+        spinner.finish(&mut self.finish_log(lang, cfg.target));
+
+        let map = (!output.mappings.is_empty())
+            .then(|| output.mappings.into_iter().collect::<PositionMap>());
+        Ok((output.code, warnings, map))
+    }
+
+    fn finish_log(&self, lang: &str, target: PreprocTarget) -> FinishLog {
+        let mut log = FinishLog::default();
+        log.with_main_message("preprocessor")
+            .with_phase(Phase::Preprocess)
+            .with_sub_message(format!(
+                "{} - {lang}",
+                match target {
+                    PreprocTarget::Js => "JavaScript",
+                    PreprocTarget::Css => "CSS",
+                }
+            ));
+        log
+    }
+}
+
+impl Preprocessor for Preproc<'_> {
+    fn preprocess(&self, lang: &str, body: &str) -> Result<Preprocessed, PreprocessError> {
+        let Some(cfg) = &self.config.preprocessors.get(lang) else {
+            return Ok(Override::None.into());
+        };
+        self.record_scripts(cfg);
+
+        let (code, warnings, map) = match &cfg.command {
+            Some(command) => {
+                let (code, warnings, map) = self.run_command(lang, cfg, command, body)?;
+                (Some(code), warnings, map)
+            }
+            None => (self.run_pipeline(lang, cfg, body)?, vec![], None),
+        };
+        let code = match code {
+            Some(code) if cfg.target == PreprocTarget::Js => Override::Js(code),
+            Some(code) => Override::Css(code),
+            None => Override::None,
+        };
+
+        Ok(Preprocessed {
+            code,
+            warnings,
+            map,
+        })
     }
 }
@@ -1,63 +1,466 @@
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     fs::{self, File},
-    io::{self, BufWriter},
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    panic,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
 };
 
 use anyhow::anyhow;
 use decorous_backend::{
     dom_render::{CsrOptions, CsrRenderer},
-    Ctx as RenderCtx, JsFile, RenderBackend, Result, UseInfo, UseResolver,
+    AsciiOnly, ChunkOut, Ctx as RenderCtx, InlineModule, Minified, OutputFormat, RenderBackend,
+    RenderOut, Result, UseInfo, UseResolver,
 };
-use decorous_errors::{ErrStream, Source};
-use decorous_frontend::{Component, Ctx as ParseCtx, Parser};
+use decorous_errors::{DynErrStream, ErrStream, Source};
+use decorous_frontend::{ast::UseBlock, Component, Ctx as ParseCtx, HashedIds, Parser};
+use itertools::{Either, Itertools};
 
-use crate::build::{compile_wasm::MainCompiler, global_ctx::GlobalCtx, preprocessor::Preproc};
+use crate::{
+    build::{
+        analyze::Analysis, compile_wasm::MainCompiler, env::Env, global_ctx::GlobalCtx,
+        preprocessor::Preproc,
+    },
+    cache::BuildRecord,
+    interrupt,
+};
+
+/// The threads that used components can be rendered on, besides the one of the build. They are
+/// shared by the builds of every used component, so nested uses don't multiply them.
+#[derive(Debug)]
+pub struct Workers(AtomicUsize);
+
+impl Workers {
+    pub fn new() -> Self {
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self(AtomicUsize::new(threads - 1))
+    }
+
+    /// Takes a thread, if there are any to spare, until the returned guard is dropped.
+    fn acquire(&self) -> Option<Worker<'_>> {
+        self.0
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |spare| {
+                spare.checked_sub(1)
+            })
+            .ok()
+            .map(|_| Worker(self))
+    }
+}
+
+struct Worker<'a>(&'a Workers);
+
+impl Drop for Worker<'_> {
+    fn drop(&mut self) {
+        self.0 .0.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// The CSS of components, by their chunk keys, in the order it's linked in.
+type HeldCss = Vec<(PathBuf, Vec<u8>)>;
+
+/// A used component that was rendered ahead of time.
+struct Resolved {
+    module: Module,
+    /// The CSS of the component and the ones it uses.
+    css: HeldCss,
+}
+
+enum Module {
+    File(UseInfo),
+    Inline(InlineModule),
+}
 
 pub struct Resolver<'a> {
-    pub global_ctx: &'a GlobalCtx<'a>,
-    pub compiler: &'a MainCompiler<'a>,
+    global_ctx: &'a GlobalCtx<'a>,
+    compiler: &'a MainCompiler<'a>,
+    resolved: RefCell<HashMap<PathBuf, Module>>,
+    /// The components being rendered, from the one being built to the most nested one, used to
+    /// find components that use each other.
+    chain: RefCell<Vec<PathBuf>>,
+    /// The CSS of the components rendered ahead of time, which is held until the component that
+    /// uses them adds it to the linker, so it's linked in the same order however long each takes.
+    held_css: Option<RefCell<HeldCss>>,
 }
 
-impl UseResolver for Resolver<'_> {
-    fn resolve(&self, path: &Path) -> Result<UseInfo> {
+impl<'a> Resolver<'a> {
+    /// Creates a resolver for the uses of the component at `root`.
+    pub fn new(global_ctx: &'a GlobalCtx<'a>, compiler: &'a MainCompiler<'a>, root: &Path) -> Self {
+        Self {
+            global_ctx,
+            compiler,
+            resolved: RefCell::default(),
+            chain: RefCell::new(vec![root.to_path_buf()]),
+            held_css: None,
+        }
+    }
+
+    /// Creates a resolver for a component rendered ahead of time, which holds on to its CSS.
+    fn ahead_of_time(
+        global_ctx: &'a GlobalCtx<'a>,
+        compiler: &'a MainCompiler<'a>,
+        chain: Vec<PathBuf>,
+    ) -> Self {
+        Self {
+            global_ctx,
+            compiler,
+            resolved: RefCell::default(),
+            chain: RefCell::new(chain),
+            held_css: Some(RefCell::default()),
+        }
+    }
+
+    /// Renders the components in `uses` in parallel, before the component using them is, so
+    /// their CSS is in the linker by the time its stylesheet is rendered. Each is rendered with its
+    /// own context, on its own thread while there are [`Workers`] to spare and on this one
+    /// otherwise, and its [`BuildRecord`] and CSS are added to the ones of this build in order.
+    pub fn resolve_all(&self, uses: &[UseBlock], inline: bool) -> Result<()> {
+        let uses = uses
+            .iter()
+            .map(|use_block| &*use_block.path)
+            .unique()
+            .filter(|path| !self.resolved.borrow().contains_key(*path))
+            .collect_vec();
+
+        let (config, args, linker, packages, catalog, timings, workers) = (
+            self.global_ctx.config,
+            self.global_ctx.args,
+            self.global_ctx.linker,
+            self.global_ctx.packages,
+            self.global_ctx.catalog,
+            self.global_ctx.timings,
+            self.global_ctx.workers,
+        );
+        let chain = &*self.chain.borrow();
+        let resolve = move |path: &Path| -> (Result<Resolved>, BuildRecord) {
+            let record = BuildRecord::buffered();
+            let src = match packages
+                .locate(path)
+                .and_then(|path| Ok(fs::read_to_string(path)?))
+            {
+                Ok(src) => src,
+                Err(err) => return (Err(err.into()), record),
+            };
+            let global_ctx = GlobalCtx {
+                config,
+                args,
+                errs: DynErrStream::new(
+                    record.stderr(),
+                    Source {
+                        name: path.display().to_string(),
+                        src: &src,
+                    },
+                ),
+                record,
+                analysis: Analysis::default(),
+                linker,
+                packages,
+                catalog,
+                timings,
+                workers,
+            };
+            let compiler = MainCompiler::new(&global_ctx);
+            let resolver = Resolver::ahead_of_time(&global_ctx, &compiler, chain.clone());
+            let module = if inline {
+                resolver.resolve_inline(path).map(Module::Inline)
+            } else {
+                resolver.resolve(path).map(Module::File)
+            };
+            let resolved = module.map(|module| Resolved {
+                module,
+                css: resolver.held_css.unwrap_or_default().into_inner(),
+            });
+
+            (resolved, global_ctx.record)
+        };
+        let results = thread::scope(|s| {
+            let pending = uses
+                .iter()
+                .map(|path| match workers.acquire() {
+                    Some(worker) => Either::Left(s.spawn(move || {
+                        let _worker = worker;
+                        resolve(path)
+                    })),
+                    None => Either::Right(path),
+                })
+                .collect_vec();
+            pending
+                .into_iter()
+                .map(|pending| match pending {
+                    Either::Left(handle) => handle
+                        .join()
+                        .unwrap_or_else(|err| panic::resume_unwind(err)),
+                    Either::Right(path) => resolve(path),
+                })
+                .collect_vec()
+        });
+
+        // Every record is added before failing, so no diagnostics are lost
+        let mut resolved = vec![];
+        for (path, (result, record)) in uses.into_iter().zip(results) {
+            self.global_ctx.record.extend(record);
+            resolved.push((path, result));
+        }
+        for (path, result) in resolved {
+            let Resolved { module, css } = result?;
+            for (key, css) in css {
+                self.add_css(&key, css);
+            }
+            self.resolved
+                .borrow_mut()
+                .insert(path.to_path_buf(), module);
+        }
+
+        Ok(())
+    }
+
+    /// Expands the `{#use}` globs of `component`, which is the one this resolver is rendering the
+    /// uses of, into the components they match.
+    pub fn expand_uses(&self, component: &mut Component) -> Result<()> {
+        let including = self
+            .chain
+            .borrow()
+            .last()
+            .cloned()
+            .expect("the chain should start with the component being built");
+        component.expand_uses(|use_block| self.expand_glob(use_block, &including))?;
+        Ok(())
+    }
+
+    /// Finds the components that the glob of `use_block` matches in the directory of
+    /// `including`, the component that it's in, sorted by path. `including` itself is never
+    /// matched.
+    fn expand_glob(&self, use_block: &UseBlock, including: &Path) -> io::Result<Vec<PathBuf>> {
+        let (dir, _) = use_block.glob().expect("only globs should be expanded");
+        let dir = including.parent().unwrap_or(Path::new("")).join(dir);
+        // Globs without a directory, like `*.decor`, are in the current one
+        let readable = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &dir
+        };
+        self.global_ctx.record.read(readable);
+        let including = chunk_key(including);
+        let mut paths = vec![];
+        for entry in fs::read_dir(readable)? {
+            let entry = entry?;
+            let path = dir.join(entry.file_name());
+            if entry.file_type()?.is_file()
+                && use_block.matches(&path)
+                && chunk_key(&path) != including
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        Ok(paths)
+    }
+
+    /// Adds the CSS of the component at `key` to the linker, or holds it if this resolver renders
+    /// components ahead of time.
+    fn add_css(&self, key: &Path, css: Vec<u8>) {
+        match &self.held_css {
+            Some(held) => held.borrow_mut().push((key.to_path_buf(), css)),
+            None => self.global_ctx.linker.add_css(key, css),
+        }
+    }
+
+    /// Fails if the component at `path` is already being rendered, meaning that it uses itself,
+    /// naming every component in the cycle.
+    fn check_cycle(&self, path: &Path) -> Result<()> {
+        let chain = self.chain.borrow();
+        let key = chunk_key(path);
+        let Some(start) = chain.iter().position(|used| chunk_key(used) == key) else {
+            return Ok(());
+        };
+        let cycle = chain[start..]
+            .iter()
+            .map(|used| used.as_path())
+            .chain([path])
+            .map(|used| used.display())
+            .join(" -> ");
+        Err(anyhow!("components use each other in a cycle: {cycle}").into())
+    }
+
+    /// Renders the component at `path` to `out`. If `inline` is set, it's rendered to be inlined
+    /// into the components that use it, and its JavaScript is returned instead.
+    fn render<T: RenderOut>(
+        &self,
+        path: &Path,
+        out: T,
+        format: OutputFormat,
+        bundle: bool,
+        inline: bool,
+    ) -> Result<Option<InlineModule>> {
+        self.chain.borrow_mut().push(path.to_path_buf());
+        let result = self.render_component(path, out, format, bundle, inline);
+        self.chain.borrow_mut().pop();
+        result
+    }
+
+    fn render_component<T: RenderOut>(
+        &self,
+        path: &Path,
+        out: T,
+        format: OutputFormat,
+        bundle: bool,
+        inline: bool,
+    ) -> Result<Option<InlineModule>> {
         let contents = fs::read_to_string(path)?;
+        self.global_ctx.record.read(path);
         let stem = path.file_stem().unwrap().to_string_lossy();
 
-        let preproc = Preproc::new(self.global_ctx.config, self.global_ctx.args.color);
+        let preproc = Preproc::new(self.global_ctx.config, self.global_ctx.log())
+            .recorded_in(&self.global_ctx.record);
         let executor = MainCompiler::new(self.global_ctx);
         let ctx = ParseCtx {
             preprocessor: &preproc,
             executor: &executor,
             errs: ErrStream::new(
-                Box::new(io::stderr()),
+                self.global_ctx.record.stderr(),
                 Source {
                     name: stem.to_string(),
                     src: &contents,
                 },
             ),
+            lints: self.global_ctx.config.lint_levels(),
+            component_ids: &HashedIds,
+            features: &self.global_ctx.args.features,
+            env: &Env::new(self.global_ctx.config).recorded_in(&self.global_ctx.record),
+            messages: self.global_ctx.catalog,
+            drop_unused_uses: self.global_ctx.args.drop_unused_uses,
         };
+        let span = self.global_ctx.time("parse", "");
         let parser = Parser::new(&contents).with_ctx(ctx.clone());
         let ast = parser.parse().map_err(|err| anyhow!(err))?;
         let mut component = Component::new(ast, ctx);
-        component.run_passes()?;
+        drop(span);
+        component.run_passes_with(|name, run| {
+            let _span = self.global_ctx.time("pass", name);
+            run()
+        })?;
+        self.expand_uses(&mut component)?;
+        self.resolve_all(&component.uses, bundle || format == OutputFormat::Iife)?;
 
-        let name: PathBuf = format!("{}_{stem}.mjs", self.global_ctx.args.out).into();
-        let mut f = BufWriter::new(File::create(&name)?);
         let mut renderer = CsrRenderer::new();
-        renderer.with_options(CsrOptions { modularize: true });
-        renderer.render(
-            &component,
-            JsFile::new(&mut f),
-            &RenderCtx {
-                name: &stem,
-                wasm_compiler: self.compiler,
-                use_resolver: self,
-                errs: self.global_ctx.errs.clone(),
-                index_html: None,
-            },
-        )?;
+        renderer.with_options(CsrOptions {
+            modularize: true,
+            format,
+            bundle,
+            custom_element: false,
+            css: self
+                .global_ctx
+                .config
+                .css
+                .options(self.global_ctx.args.minify_css),
+            html_sanitizer: self.global_ctx.config.html.sanitizer.clone(),
+            no_collapse: self.global_ctx.config.html.collapse == Some(false),
+            csp: self.global_ctx.config.html.csp,
+            nonce: None,
+            runtime: self.global_ctx.runtime(format, bundle),
+        });
+        let _span = self.global_ctx.time("render", "csr");
+        let ctx = RenderCtx {
+            name: &stem,
+            wasm_compiler: self.compiler,
+            use_resolver: self,
+            errs: self.global_ctx.errs.clone(),
+            index_html: None,
+            linker: None,
+        };
+        if inline {
+            renderer.render_inline(&component, out, &ctx).map(Some)
+        } else {
+            renderer.render(&component, out, &ctx).map(|()| None)
+        }
+    }
+}
+
+impl Resolver<'_> {
+    fn render_minified<T: RenderOut>(
+        &self,
+        path: &Path,
+        out: T,
+        format: OutputFormat,
+        bundle: bool,
+    ) -> Result<()> {
+        if self.global_ctx.args.minify {
+            let mut minified = Minified::new(out);
+            self.render(path, &mut minified, format, bundle, false)?;
+            minified.finish()?;
+        } else {
+            self.render(path, out, format, bundle, false)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UseResolver for Resolver<'_> {
+    fn resolve(&self, path: &Path) -> Result<UseInfo> {
+        if let Some(Module::File(info)) = self.resolved.borrow_mut().remove(path) {
+            return Ok(info);
+        }
+        let path = &self.global_ctx.packages.locate(path)?;
+        self.check_cycle(path)?;
+
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        let format = OutputFormat::from(self.global_ctx.args.format);
+        let name: PathBuf = format!(
+            "{}_{stem}.{}",
+            self.global_ctx.args.out,
+            format.module_extension()
+        )
+        .into();
+        // Components used by more than one other component are only rendered once, but their CSS
+        // is linked where it's first used
+        let key = chunk_key(path);
+        if !self.global_ctx.linker.claim(&key) {
+            self.add_css(&key, vec![]);
+            return Ok(UseInfo { loc: name });
+        }
+        let _partial = interrupt::partial_output(&name);
+        let mut f = BufWriter::new(File::create(&name)?);
+        self.global_ctx.record.wrote(&name);
+        let bundle = self.global_ctx.args.bundle;
+        let mut out = ChunkOut::new(&mut f);
+        if self.global_ctx.args.ascii_only {
+            self.render_minified(path, AsciiOnly::new(&mut out), format, bundle)?;
+        } else {
+            self.render_minified(path, &mut out, format, bundle)?;
+        }
+        self.add_css(&key, out.finish());
+        f.flush()?;
 
         Ok(UseInfo { loc: name })
     }
+
+    fn resolve_inline(&self, path: &Path) -> Result<InlineModule> {
+        if let Some(Module::Inline(module)) = self.resolved.borrow_mut().remove(path) {
+            return Ok(module);
+        }
+        let path = &self.global_ctx.packages.locate(path)?;
+        self.check_cycle(path)?;
+
+        // Inlined components are always ES modules, and everything they use is inlined as well.
+        // Their JavaScript is inlined into every component that uses them, but their CSS is
+        // still only emitted once
+        let mut out = ChunkOut::new(io::sink());
+        let module = self
+            .render(path, &mut out, OutputFormat::Esm, true, true)?
+            .expect("inlined components should return their JavaScript");
+        self.add_css(&chunk_key(path), out.finish());
+
+        Ok(module)
+    }
+}
+
+/// The key that the chunks of the component at `path` are linked by, which is the same however
+/// the path is written.
+fn chunk_key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
 }
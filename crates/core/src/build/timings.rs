@@ -0,0 +1,262 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs,
+    path::Path,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use serde_json::json;
+
+/// How long each phase of a build took, for `--timings` and `--trace`. Used components are built
+/// on their own threads, and record their phases in the same [`Timings`].
+#[derive(Debug)]
+pub struct Timings {
+    start: Instant,
+    spans: Mutex<Vec<Span>>,
+}
+
+/// A phase of the build of a component.
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    component: String,
+    category: &'static str,
+    name: String,
+    /// When the phase started, since the start of the build.
+    start: Duration,
+    duration: Duration,
+}
+
+impl Span {
+    fn end(&self) -> Duration {
+        self.start + self.duration
+    }
+
+    fn label(&self) -> String {
+        if self.name.is_empty() {
+            self.category.to_owned()
+        } else {
+            format!("{}: {}", self.category, self.name)
+        }
+    }
+}
+
+/// Records a phase in its [`Timings`] once it's dropped.
+#[must_use]
+pub struct SpanGuard<'a> {
+    timings: &'a Timings,
+    component: String,
+    category: &'static str,
+    name: String,
+    start: Instant,
+}
+
+impl Drop for SpanGuard<'_> {
+    fn drop(&mut self) {
+        let span = Span {
+            component: std::mem::take(&mut self.component),
+            category: self.category,
+            name: std::mem::take(&mut self.name),
+            start: self.start - self.timings.start,
+            duration: self.start.elapsed(),
+        };
+        self.timings
+            .spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(span);
+    }
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            spans: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl Timings {
+    /// Starts timing a phase of the build of `component`, like the `pass` called `lint`. The phase
+    /// ends when the returned guard is dropped.
+    pub fn start(
+        &self,
+        component: &str,
+        category: &'static str,
+        name: impl Into<String>,
+    ) -> SpanGuard<'_> {
+        SpanGuard {
+            timings: self,
+            component: component.to_owned(),
+            category,
+            name: name.into(),
+            start: Instant::now(),
+        }
+    }
+
+    fn spans(&self) -> Vec<Span> {
+        let mut spans = self
+            .spans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        // Phases that start together are ordered from outermost to innermost
+        spans.sort_by(|a, b| a.start.cmp(&b.start).then(b.duration.cmp(&a.duration)));
+        spans
+    }
+
+    /// The time spent in each phase of the build so far, without the phases nested in it, like
+    /// WebAssembly compiled while rendering.
+    pub fn breakdown(&self, enable_color: bool) -> Breakdown {
+        Breakdown {
+            phases: self_times(&self.spans()),
+            total: self.start.elapsed(),
+            enable_color,
+        }
+    }
+
+    /// Writes the phases as a Chrome trace to `path`, which can be opened with `chrome://tracing`
+    /// or Perfetto. Each component is its own track.
+    pub fn write_trace(&self, path: &Path) -> Result<()> {
+        let spans = self.spans();
+        let components = spans
+            .iter()
+            .map(|span| span.component.as_str())
+            .unique()
+            .collect_vec();
+        let tid = |component: &str| components.iter().position(|c| *c == component);
+
+        let mut events = components
+            .iter()
+            .enumerate()
+            .map(|(tid, component)| {
+                json!({
+                    "name": "thread_name",
+                    "ph": "M",
+                    "pid": 0,
+                    "tid": tid,
+                    "args": { "name": component },
+                })
+            })
+            .collect_vec();
+        events.extend(spans.iter().map(|span| {
+            json!({
+                "name": span.label(),
+                "cat": span.category,
+                "ph": "X",
+                "ts": span.start.as_secs_f64() * 1_000_000.0,
+                "dur": span.duration.as_secs_f64() * 1_000_000.0,
+                "pid": 0,
+                "tid": tid(&span.component),
+            })
+        }));
+
+        let trace = serde_json::to_string(&events).context("error serializing trace")?;
+        fs::write(path, trace).with_context(|| format!("error writing trace {}", path.display()))
+    }
+}
+
+/// Adds up the time spent in each kind of phase, in the order they first started, without the
+/// time spent in the phases nested in them. `spans` have to be sorted by when they start.
+fn self_times(spans: &[Span]) -> Vec<(String, Duration)> {
+    let mut self_times = spans.iter().map(|span| span.duration).collect_vec();
+    // The spans that the current one could be nested in, for each component
+    let mut stacks: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, span) in spans.iter().enumerate() {
+        let stack = stacks.entry(&span.component).or_default();
+        while stack
+            .last()
+            .is_some_and(|&parent| spans[parent].end() < span.end())
+        {
+            stack.pop();
+        }
+        if let Some(&parent) = stack.last() {
+            self_times[parent] = self_times[parent].saturating_sub(span.duration);
+        }
+        stack.push(i);
+    }
+
+    let mut phases: Vec<(String, Duration)> = vec![];
+    for (span, time) in spans.iter().zip(self_times) {
+        let label = span.label();
+        match phases.iter_mut().find(|(l, _)| *l == label) {
+            Some((_, total)) => *total += time,
+            None => phases.push((label, time)),
+        }
+    }
+    phases
+}
+
+/// The table of how long each phase took that `--timings` prints.
+pub struct Breakdown {
+    phases: Vec<(String, Duration)>,
+    total: Duration,
+    enable_color: bool,
+}
+
+impl Display for Breakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = format!("{:.2?}", self.total);
+        if self.enable_color {
+            writeln!(f, "\x1b[1m{:<40}\x1b[0m {total:>10}", "Build")?;
+        } else {
+            writeln!(f, "{:<40} {total:>10}", "Build")?;
+        }
+
+        let timed = self
+            .phases
+            .iter()
+            .map(|(_, time)| time.as_secs_f64())
+            .sum::<f64>();
+        for (label, time) in &self.phases {
+            let share = if timed > 0.0 {
+                time.as_secs_f64() / timed * 100.0
+            } else {
+                0.0
+            };
+            writeln!(
+                f,
+                "  {label:<38} {:>10} {share:>5.1}%",
+                format!("{time:.2?}")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(component: &str, name: &str, start: u64, duration: u64) -> Span {
+        Span {
+            component: component.to_owned(),
+            category: "pass",
+            name: name.to_owned(),
+            start: Duration::from_millis(start),
+            duration: Duration::from_millis(duration),
+        }
+    }
+
+    #[test]
+    fn nested_phases_are_not_counted_twice() {
+        let spans = [
+            span("a", "render", 0, 10),
+            span("a", "wasm", 2, 5),
+            span("b", "render", 3, 4),
+            span("a", "lint", 12, 1),
+        ];
+        assert_eq!(
+            vec![
+                ("pass: render".to_owned(), Duration::from_millis(9)),
+                ("pass: wasm".to_owned(), Duration::from_millis(5)),
+                ("pass: lint".to_owned(), Duration::from_millis(1)),
+            ],
+            self_times(&spans)
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::{build::Compiled, cli::Build, utils};
+
+/// The files that a build reads and writes, which decide whether its outputs can be cached.
+#[derive(Debug, Default)]
+pub struct BuildRecord {
+    deps: RefCell<Vec<PathBuf>>,
+    /// The environment variables the build read, with their values.
+    env: RefCell<Vec<(String, Option<String>)>>,
+    /// The external programs the build ran, with their versions.
+    tools: RefCell<Vec<(String, Option<String>)>>,
+    outputs: RefCell<Vec<PathBuf>>,
+    diagnosed: Arc<AtomicBool>,
+    /// The diagnostics held back until the record is added to the one of the build using it.
+    buffer: Option<Arc<Mutex<Vec<u8>>>>,
+}
+
+impl BuildRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a record whose diagnostics are only written once it's added to another with
+    /// [`BuildRecord::extend`], so those of components built at the same time don't interleave.
+    pub fn buffered() -> Self {
+        Self {
+            buffer: Some(Arc::default()),
+            ..Self::default()
+        }
+    }
+
+    /// Records that the build depends on the file at `path`, so its cached outputs are only used
+    /// while the file is unchanged.
+    pub fn read(&self, path: impl Into<PathBuf>) {
+        self.deps.borrow_mut().push(path.into());
+    }
+
+    /// Reads the environment variable `name`, recording that the build depends on its value.
+    pub fn read_env(&self, name: &str) -> Option<String> {
+        let value = env::var(name).ok();
+        self.env.borrow_mut().push((name.to_owned(), value.clone()));
+        value
+    }
+
+    /// Records that the build runs the external program `name`, like `cargo`, so its cached
+    /// outputs are only used while the same version of it is installed.
+    pub fn ran(&self, name: &str) {
+        self.tools
+            .borrow_mut()
+            .push((name.to_owned(), tool_version(name)));
+    }
+
+    /// Records a file or directory generated by the build.
+    pub fn wrote(&self, path: impl Into<PathBuf>) {
+        self.outputs.borrow_mut().push(path.into());
+    }
+
+    /// The files and directories that the build has read so far.
+    pub fn deps(&self) -> Vec<PathBuf> {
+        self.deps.borrow().iter().unique().cloned().collect()
+    }
+
+    /// The files and directories generated by the build so far.
+    pub fn outputs(&self) -> Vec<PathBuf> {
+        self.outputs.borrow().iter().unique().cloned().collect()
+    }
+
+    /// Gets a writer to stderr for diagnostics. Builds that emit diagnostics aren't cached, so
+    /// they are shown every time.
+    pub fn stderr(&self) -> Box<dyn Write> {
+        Box::new(DiagnosticsOut {
+            diagnosed: Arc::clone(&self.diagnosed),
+            buffer: self.buffer.clone(),
+        })
+    }
+
+    /// Adds everything recorded by `other`, a build of a component that this build uses.
+    pub fn extend(&self, other: BuildRecord) {
+        self.deps.borrow_mut().extend(other.deps.into_inner());
+        self.env.borrow_mut().extend(other.env.into_inner());
+        self.tools.borrow_mut().extend(other.tools.into_inner());
+        self.outputs.borrow_mut().extend(other.outputs.into_inner());
+        if other.diagnosed.load(Ordering::Relaxed) {
+            self.diagnosed.store(true, Ordering::Relaxed);
+        }
+        if let Some(buffer) = other.buffer {
+            let diagnostics =
+                mem::take(&mut *buffer.lock().unwrap_or_else(PoisonError::into_inner));
+            if !diagnostics.is_empty() {
+                // Nothing can be done if stderr is gone
+                let _ = self.stderr().write_all(&diagnostics);
+            }
+        }
+    }
+}
+
+struct DiagnosticsOut {
+    diagnosed: Arc<AtomicBool>,
+    buffer: Option<Arc<Mutex<Vec<u8>>>>,
+}
+
+impl Write for DiagnosticsOut {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.diagnosed.store(true, Ordering::Relaxed);
+        match &self.buffer {
+            Some(buffer) => buffer
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .write(buf),
+            None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.buffer {
+            Some(_) => Ok(()),
+            None => io::stderr().flush(),
+        }
+    }
+}
+
+/// Hashes the contents of a file, or the names of the entries of a directory, so that adding or
+/// removing a file in it is noticed.
+fn dep_hash(path: &Path) -> io::Result<String> {
+    if path.is_dir() {
+        let names = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+            .collect::<io::Result<Vec<_>>>()?;
+        return Ok(sha256::digest(names.into_iter().sorted().join("\n")));
+    }
+    Ok(sha256::digest(fs::read(path)?.as_slice()))
+}
+
+/// What `name --version` prints, or `None` if it can't be run.
+fn tool_version(name: &str) -> Option<String> {
+    let out = duct::cmd(name, ["--version"])
+        .stdin_null()
+        .stdout_capture()
+        .stderr_null()
+        .unchecked()
+        .run()
+        .ok()?;
+    Some(String::from_utf8_lossy(&out.stdout).trim().to_owned())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    /// The files the build read, with the hashes of their contents.
+    deps: Vec<(PathBuf, String)>,
+    /// The environment variables the build read, with their values.
+    #[serde(default)]
+    env: Vec<(String, Option<String>)>,
+    /// The external programs the build ran, with their versions.
+    #[serde(default)]
+    tools: Vec<(String, Option<String>)>,
+    /// The files the build generated. Their cached copies are stored by their index.
+    outputs: Vec<PathBuf>,
+}
+
+/// The files generated by the build cached in `entry`, or `None` if its manifest can't be read.
+pub fn cached_outputs(entry: &Path) -> Option<Vec<PathBuf>> {
+    let manifest = fs::read(entry.join("manifest.json")).ok()?;
+    serde_json::from_slice::<Manifest>(&manifest)
+        .ok()
+        .map(|manifest| manifest.outputs)
+}
+
+/// The cached outputs of a component, keyed by its source and everything it's built with.
+#[derive(Debug)]
+pub struct BuildCache {
+    entry: PathBuf,
+}
+
+impl BuildCache {
+    pub fn new(input: &str, args: &Build) -> Result<Self> {
+        let base = utils::get_cache_base().context("could not get cache base")?;
+        let config = match utils::find_config()? {
+            Some(path) => fs::read_to_string(path).context("error reading config file")?,
+            None => String::new(),
+        };
+        let key = sha256::digest(format!(
+            "{}\0{input}\0{config}\0{:?}",
+            env!("CARGO_PKG_VERSION"),
+            Build {
+                // Neither affect the outputs
+                watch: false,
+                color: false,
+                ..args.clone()
+            }
+        ));
+
+        Ok(Self {
+            entry: base.join(format!("build-{key}")),
+        })
+    }
+
+    /// Copies the cached outputs into place, returning their paths and the files the cached build
+    /// read. Returns `None` if nothing is cached, or if a file that the cached build read has
+    /// changed since.
+    pub fn restore(&self) -> Result<Option<Compiled>> {
+        let Ok(manifest) = fs::read(self.entry.join("manifest.json")) else {
+            return Ok(None);
+        };
+        let manifest = serde_json::from_slice::<Manifest>(&manifest)
+            .context("error reading build cache manifest")?;
+        for (path, hash) in &manifest.deps {
+            match dep_hash(path) {
+                Ok(current) if current == *hash => {}
+                _ => return Ok(None),
+            }
+        }
+        if manifest
+            .env
+            .iter()
+            .any(|(name, value)| env::var(name).ok() != *value)
+        {
+            return Ok(None);
+        }
+        if manifest
+            .tools
+            .iter()
+            .any(|(name, version)| tool_version(name) != *version)
+        {
+            return Ok(None);
+        }
+
+        let files = self.entry.join("files");
+        for (i, output) in manifest.outputs.iter().enumerate() {
+            utils::copy_all(files.join(i.to_string()), output)
+                .with_context(|| format!("error restoring {} from cache", output.display()))?;
+        }
+
+        Ok(Some(Compiled {
+            outputs: manifest.outputs,
+            deps: manifest.deps.into_iter().map(|(path, _)| path).collect(),
+        }))
+    }
+
+    /// Caches the outputs of a finished build.
+    pub fn store(&self, record: &BuildRecord) -> Result<()> {
+        if record.diagnosed.load(Ordering::Relaxed) || self.entry.exists() {
+            return Ok(());
+        }
+
+        // Everything is written to a temporary directory first, so builds running at the same
+        // time never see a partial entry
+        let tmp = self.entry.with_file_name(format!("tmp-{}", process::id()));
+        let files = tmp.join("files");
+        fs::create_dir_all(&files).context("error creating build cache entry")?;
+        let outputs = record.outputs.take().into_iter().unique().collect_vec();
+        for (i, output) in outputs.iter().enumerate() {
+            utils::copy_all(output, files.join(i.to_string()))
+                .with_context(|| format!("error caching {}", output.display()))?;
+        }
+        let deps = record
+            .deps
+            .take()
+            .into_iter()
+            .unique()
+            .map(|path| {
+                let hash =
+                    dep_hash(&path).with_context(|| format!("error reading {}", path.display()))?;
+                Ok((path, hash))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        fs::write(
+            tmp.join("manifest.json"),
+            serde_json::to_vec(&Manifest {
+                deps,
+                env: record.env.take().into_iter().unique().collect(),
+                tools: record.tools.take().into_iter().unique().collect(),
+                outputs,
+            })?,
+        )
+        .context("error writing build cache manifest")?;
+
+        if fs::rename(&tmp, &self.entry).is_err() {
+            // Another build cached the same outputs first
+            fs::remove_dir_all(&tmp).context("error removing temporary cache entry")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffered_diagnostics_are_written_once_the_record_is_added() {
+        let parent = BuildRecord::buffered();
+        let child = BuildRecord::buffered();
+        write!(child.stderr(), "warning").unwrap();
+        assert!(parent.buffer.as_ref().unwrap().lock().unwrap().is_empty());
+
+        parent.extend(child);
+        assert!(parent.diagnosed.load(Ordering::Relaxed));
+        assert_eq!(
+            b"warning".as_slice(),
+            parent.buffer.as_ref().unwrap().lock().unwrap().as_slice()
+        );
+    }
+}
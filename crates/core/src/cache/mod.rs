@@ -0,0 +1,170 @@
+mod builds;
+mod static_blocks;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use indicatif::{HumanBytes, HumanDuration};
+use itertools::Itertools;
+
+use crate::{
+    cli::{Cache, CacheCommand},
+    utils,
+};
+pub use builds::*;
+pub use static_blocks::*;
+
+pub fn cache(args: &Cache) -> Result<()> {
+    let loc = utils::get_cache_base().context("could not get cache base")?;
+    if !loc.exists() && !matches!(args.command, Some(CacheCommand::Path)) {
+        anyhow::bail!("cache does not exist yet!");
+    }
+
+    match &args.command {
+        Some(CacheCommand::List) => list(&loc),
+        Some(CacheCommand::Clean { older_than }) => clean(&loc, *older_than),
+        Some(CacheCommand::Path) => {
+            println!("{}", loc.display());
+            Ok(())
+        }
+        None => {
+            let size = utils::dir_size(&loc).context("error getting size of dir")?;
+            let items = fs::read_dir(&loc)
+                .context("error reading directory")?
+                .count();
+            println!(
+                "decorous cache info\n\nlocation: {}\nsize: {}\nnumber of entries: {items}",
+                loc.display(),
+                HumanBytes(size),
+            );
+            Ok(())
+        }
+    }
+}
+
+/// What a cache entry holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    /// The outputs of a build, keyed by everything the build depends on.
+    Build,
+    /// The artifacts of a WebAssembly compiler, keyed by the path of the component.
+    Wasm,
+    /// What a static code block printed, keyed by its code and what it can read.
+    Static,
+    /// The git repositories and checkouts of packages.
+    Packages,
+}
+
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    kind: EntryKind,
+    /// The hash that the entry is keyed by.
+    hash: String,
+    size: u64,
+    age: Duration,
+}
+
+fn entries(loc: &Path) -> Result<Vec<Entry>> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(loc).context("error reading cache dir")? {
+        let path = entry.context("error getting cache entry")?.path();
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let (kind, hash) = match name.strip_prefix("build-") {
+            Some(hash) => (EntryKind::Build, hash.to_owned()),
+            None if name.starts_with("static-") => {
+                (EntryKind::Static, name["static-".len()..].to_owned())
+            }
+            // Entries that are still being written
+            None if name.starts_with("tmp-") => continue,
+            None if name == "packages" => (EntryKind::Packages, String::new()),
+            None => (EntryKind::Wasm, name),
+        };
+        let modified = path
+            .metadata()
+            .context("error getting cache entry metadata")?
+            .modified()
+            .context("error getting entry modified time")?;
+        entries.push(Entry {
+            size: utils::dir_size(&path).context("error getting size of cache entry")?,
+            age: SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default(),
+            path,
+            kind,
+            hash,
+        });
+    }
+    entries.sort_by_key(|entry| entry.age);
+    Ok(entries)
+}
+
+fn list(loc: &Path) -> Result<()> {
+    let entries = entries(loc)?;
+    if entries.is_empty() {
+        println!("The cache is empty!");
+        return Ok(());
+    }
+
+    println!(
+        "{:<6} {:<12} {:>10}  {:<16} CONTENTS",
+        "KIND", "HASH", "SIZE", "AGE"
+    );
+    for entry in &entries {
+        let contents = match entry.kind {
+            EntryKind::Build => cached_outputs(&entry.path)
+                .map(|outputs| outputs.iter().map(|path| path.display()).join(", "))
+                .unwrap_or_default(),
+            EntryKind::Wasm => "compiler artifacts".to_owned(),
+            EntryKind::Static => "static code block output".to_owned(),
+            EntryKind::Packages => "fetched packages".to_owned(),
+        };
+        println!(
+            "{:<6} {:<12} {:>10}  {:<16} {contents}",
+            match entry.kind {
+                EntryKind::Build => "build",
+                EntryKind::Wasm => "wasm",
+                EntryKind::Static => "static",
+                EntryKind::Packages => "pkg",
+            },
+            entry.hash.chars().take(12).collect::<String>(),
+            HumanBytes(entry.size).to_string(),
+            format!("{} ago", HumanDuration(entry.age)),
+        );
+    }
+    println!(
+        "\n{} entr{}, {}",
+        entries.len(),
+        if entries.len() == 1 { "y" } else { "ies" },
+        HumanBytes(entries.iter().map(|entry| entry.size).sum())
+    );
+    Ok(())
+}
+
+/// Removes every cache entry, or only the ones older than `older_than`.
+fn clean(loc: &Path, older_than: Option<Duration>) -> Result<()> {
+    let mut removed = 0;
+    let mut saved = 0;
+    for entry in entries(loc)? {
+        if older_than.is_some_and(|dur| entry.age < dur) {
+            continue;
+        }
+        fs::remove_dir_all(&entry.path).context("error removing cache entry")?;
+        removed += 1;
+        saved += entry.size;
+    }
+    println!(
+        "Removed {removed} cache entr{}! {} saved!",
+        if removed == 1 { "y" } else { "ies" },
+        HumanBytes(saved)
+    );
+    Ok(())
+}
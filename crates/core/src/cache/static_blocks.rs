@@ -0,0 +1,66 @@
+use std::{fs, path::PathBuf, process};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    config::{CompilerConfig, ScriptOrFile},
+    utils,
+};
+
+/// What a static code block printed, keyed by its code, how it's compiled and everything it's
+/// granted, so unchanged blocks aren't compiled and run again.
+#[derive(Debug)]
+pub struct StaticCache {
+    entry: PathBuf,
+}
+
+impl StaticCache {
+    /// The entry of the block in `lang` with `body`. `grants` is the fingerprint of what the block
+    /// can read.
+    pub fn new(
+        lang: &str,
+        body: &str,
+        compiler: &CompilerConfig,
+        build_args: &[String],
+        grants: &str,
+    ) -> Result<Self> {
+        let base = utils::get_cache_base().context("could not get cache base")?;
+        let script = match &compiler.script {
+            ScriptOrFile::File(path) => fs::read_to_string(path)
+                .with_context(|| format!("error reading {}", path.display()))?,
+            _ => String::new(),
+        };
+        let key = sha256::digest(format!(
+            "{}\0{lang}\0{body}\0{compiler:?}\0{script}\0{build_args:?}\0{grants}",
+            env!("CARGO_PKG_VERSION"),
+        ));
+
+        Ok(Self {
+            entry: base.join(format!("static-{key}")),
+        })
+    }
+
+    /// The stdout of the block when it was cached, if it was.
+    pub fn restore(&self) -> Option<Vec<u8>> {
+        fs::read(self.entry.join("stdout.json")).ok()
+    }
+
+    pub fn store(&self, stdout: &[u8]) -> Result<()> {
+        if self.entry.exists() {
+            return Ok(());
+        }
+
+        let name = self.entry.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = self
+            .entry
+            .with_file_name(format!("tmp-{}-{name}", process::id()));
+        fs::create_dir_all(&tmp).context("error creating static cache entry")?;
+        fs::write(tmp.join("stdout.json"), stdout).context("error caching static code block")?;
+        if fs::rename(&tmp, &self.entry).is_err() {
+            // Another build cached the same block first
+            fs::remove_dir_all(&tmp).context("error removing temporary cache entry")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use decorous_errors::{DynErrStream, Source};
+use decorous_frontend::{
+    errors::ParseErrorType,
+    lint::{Level, Lint},
+    Component, Ctx, Parser,
+};
+
+use crate::{
+    build::{Env, Preproc},
+    cli::Check,
+    config::Config,
+    indicators::{FinishLog, Logger},
+    utils,
+};
+
+pub fn check(args: &Check) -> Result<()> {
+    let config = utils::get_config()?;
+    let preproc = Preproc::new(&config, Logger::new(args.color));
+
+    let mut files = vec![];
+    for path in &args.paths {
+        find_components(path, &mut files)
+            .with_context(|| format!("error searching {}", path.display()))?;
+    }
+    let mut failed = 0;
+    for path in &files {
+        if !check_file(path, &config, &preproc)? {
+            failed += 1;
+        }
+    }
+
+    anyhow::ensure!(
+        failed == 0,
+        "\n{failed} of {} file{} failed to check",
+        files.len(),
+        if files.len() == 1 { "" } else { "s" }
+    );
+    println!(
+        "{}",
+        FinishLog::default()
+            .with_main_message(format!(
+                "checked {} file{}",
+                files.len(),
+                if files.len() == 1 { "" } else { "s" }
+            ))
+            .enable_color(args.color)
+    );
+    Ok(())
+}
+
+/// Parses the component at `path` and runs all of its passes, without rendering it. Returns
+/// `false` if it has errors.
+fn check_file(path: &Path, config: &Config, preproc: &Preproc) -> Result<bool> {
+    let src =
+        fs::read_to_string(path).with_context(|| format!("error reading {}", path.display()))?;
+    let errs = DynErrStream::new(
+        Box::new(io::stderr()),
+        Source {
+            src: &src,
+            name: path.to_string_lossy().to_string(),
+        },
+    );
+    let mut ctx = Ctx {
+        preprocessor: preproc,
+        errs: errs.clone(),
+        lints: config.lint_levels(),
+        env: &Env::new(config),
+        ..Default::default()
+    };
+    let ast = match Parser::new(&src).with_ctx(ctx.clone()).parse() {
+        Ok(ast) => ast,
+        Err(err) => {
+            // Every error has already been emitted if there was more than one
+            if err.err_type() != &ParseErrorType::DidError {
+                errs.emit(err.into());
+            }
+            return Ok(false);
+        }
+    };
+    // Static code blocks aren't compiled, so the variables they declare are unknown
+    if ast.comptime.is_some() {
+        ctx.lints.set(Lint::UnboundVariable, Level::Allow);
+    }
+
+    let mut component = Component::new(ast, ctx);
+    if let Err(err) = component.run_passes() {
+        eprintln!("{}: {err}", path.display());
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Adds the decor files at `path` to `files`, searching directories recursively. Hidden
+/// directories are skipped.
+pub(crate) fn find_components(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        let hidden = entry
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if entry.is_dir() && !hidden {
+            find_components(&entry, files)?;
+        } else if entry.extension().is_some_and(|ext| ext == "decor") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
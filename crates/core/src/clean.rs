@@ -0,0 +1,100 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+
+use crate::cli::Clean;
+
+/// The path of the file listing what the builds with the output name `out` wrote, like
+/// `.out.decor-outputs` for `out`. It is kept next to the outputs.
+fn manifest_path(out: &str) -> Result<PathBuf> {
+    let out = Path::new(out);
+    let base = out
+        .file_name()
+        .context("output name should not end in ..")?
+        .to_string_lossy();
+    Ok(out.with_file_name(format!(".{base}.decor-outputs")))
+}
+
+/// Reads the outputs listed in the manifest at `path`, or nothing if there is no manifest.
+fn read_manifest(path: &Path) -> Result<Vec<PathBuf>> {
+    match fs::read(path) {
+        Ok(manifest) => serde_json::from_slice(&manifest)
+            .with_context(|| format!("error reading {}", path.display())),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+/// Records that a build with the output name `out` wrote `outputs`, along with what earlier builds
+/// with the same name wrote. Paths are made absolute, so the manifest can be read from anywhere.
+pub fn record_outputs(out: &str, outputs: &[PathBuf]) -> Result<()> {
+    let path = manifest_path(out)?;
+    let mut recorded = read_manifest(&path)?;
+    // Outputs that were removed after being written, like the markup put into a page, are skipped
+    recorded.extend(
+        outputs
+            .iter()
+            .filter_map(|output| fs::canonicalize(output).ok()),
+    );
+    let recorded = recorded.into_iter().unique().collect_vec();
+    fs::write(&path, serde_json::to_vec(&recorded)?)
+        .with_context(|| format!("error writing {}", path.display()))
+}
+
+pub fn clean(args: &Clean) -> Result<()> {
+    let manifest = manifest_path(&args.out)?;
+    let (remove, keep): (Vec<_>, Vec<_>) = read_manifest(&manifest)?
+        .into_iter()
+        .partition(|path| args.html || !path.ends_with("index.html"));
+
+    let mut removed = 0;
+    for path in remove {
+        // Only what the builds generated is listed, so directories hold nothing else, like the
+        // outputs of WebAssembly compilers
+        let result = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else if path.exists() {
+            fs::remove_file(&path)
+        } else {
+            continue;
+        };
+        result.with_context(|| format!("error removing {}", path.display()))?;
+        removed += 1;
+    }
+    if keep.is_empty() {
+        if manifest.exists() {
+            fs::remove_file(&manifest)
+                .with_context(|| format!("error removing {}", manifest.display()))?;
+        }
+    } else {
+        fs::write(&manifest, serde_json::to_vec(&keep)?)
+            .with_context(|| format!("error writing {}", manifest.display()))?;
+    }
+
+    println!(
+        "Removed {removed} output{}!",
+        if removed == 1 { "" } else { "s" }
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifests_are_kept_next_to_outputs() {
+        assert_eq!(
+            PathBuf::from(".out.decor-outputs"),
+            manifest_path("out").unwrap()
+        );
+        assert_eq!(
+            PathBuf::from("dist/.app.decor-outputs"),
+            manifest_path("dist/app").unwrap()
+        );
+        assert!(manifest_path("dist/..").is_err());
+    }
+}
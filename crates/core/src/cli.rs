@@ -1,6 +1,12 @@
-use std::{fmt::Display, path::PathBuf, time::Duration};
+use std::{
+    ffi::OsStr,
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use clap::{builder::ArgPredicate, Args, Parser, Subcommand, ValueEnum};
+use decorous_backend::OutputFormat;
 use humantime::parse_duration;
 
 #[derive(Debug, Parser)]
@@ -14,15 +20,43 @@ pub struct Cli {
 pub enum Command {
     /// Build a decorous file.
     Build(Build),
-    /// Interact with the decorous cache. Run with no args to print information.
+    /// Interact with the decorous cache. Run with no subcommand to print information.
     Cache(Cache),
+    /// Remove the outputs of previous builds.
+    Clean(Clean),
+    /// Serve a decorous file, reloading the browser as it changes.
+    Serve(Serve),
+    /// Parse and lint decorous files without building them, failing if any have errors.
+    Check(Check),
+    /// Format decorous files in place.
+    Fmt(Fmt),
+    /// Print the tokens of a decorous file.
+    Tokens(Tokens),
+    /// Create a new project, with a starter component and config file.
+    New(New),
+    /// Manage the message catalogs of translated components.
+    I18n(I18n),
+    /// Run the tests of components in a headless DOM.
+    Test(Test),
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, Args)]
 pub struct Build {
     /// The decor file to compile.
-    #[arg(value_name = "PATH")]
+    #[arg(
+        value_name = "PATH",
+        required = false,
+        required_unless_present = "list_compilers",
+        default_value_if("list_compilers", ArgPredicate::IsPresent, ".")
+    )]
     pub input: PathBuf,
+    /// More decor files to mount on the same index.html. Each component gets its own outputs,
+    /// named like `out_<name>.js`.
+    #[arg(value_name = "MORE", requires = "html")]
+    pub others: Vec<PathBuf>,
+    /// Set when the component is one of several on a page.
+    #[arg(skip)]
+    pub page: Option<PageEntry>,
 
     /// The base name of the output file(s) to generate.
     #[arg(short, long, value_name = "NAME", default_value = "out")]
@@ -31,29 +65,227 @@ pub struct Build {
         short,
         long,
         default_value = "prerender",
-        default_value_if("modularize", ArgPredicate::IsPresent, "csr")
+        default_value_ifs([
+            ("modularize", ArgPredicate::IsPresent, "csr"),
+            ("target", ArgPredicate::Equals("custom-element".into()), "csr"),
+        ])
     )]
     pub render_method: RenderMethod,
+    /// What the component is compiled to.
+    #[arg(long, default_value = "document")]
+    pub target: Target,
+
+    /// Build with the options of a profile in the config file, like `release`.
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Keep the `{#cfg}` blocks of these features, and remove every other one. Can be given more
+    /// than once, or as a comma-separated list.
+    #[arg(long, value_name = "NAME", value_delimiter = ',')]
+    pub features: Vec<String>,
+    /// Only build for this locale, with the usual output names, instead of for every locale in
+    /// the config file.
+    #[arg(long, value_name = "LOCALE")]
+    pub locale: Option<String>,
+    /// Set when the component is built for every locale in the config file.
+    #[arg(skip)]
+    pub localized: bool,
 
     #[arg(short = 'O', default_value = None)]
     pub optimize: Option<OptimizationLevel>,
     /// Strip custom sections from the WebAssembly file.
     #[arg(long)]
     pub strip: bool,
+    /// Remove the `{#use}` declarations of components that are never rendered, instead of only
+    /// warning about them.
+    #[arg(long)]
+    pub drop_unused_uses: bool,
 
     /// Generate a full index.html file instead of just a fragment (or none at all).
     #[arg(long)]
     pub html: bool,
-    /// Generate an ES6 compliant module for the output.
+    /// The page to render the index.html into. Overrides `html.template` in the config file.
+    #[arg(long, value_name = "PATH", requires = "html")]
+    pub html_template: Option<PathBuf>,
+    /// Export the component's initialize function, instead of mounting it on load.
     #[arg(short, long)]
     pub modularize: bool,
+    /// The module format of the JavaScript output.
+    #[arg(long, default_value = "esm")]
+    pub format: Format,
+    /// Inline the JavaScript of all used components into the output, instead of importing them.
+    #[arg(long)]
+    pub bundle: bool,
+    /// Define helpers like `mount` in every component, instead of importing them from a shared
+    /// `decor-runtime` module written next to the output.
+    #[arg(long)]
+    pub inline_runtime: bool,
+    /// Minify the generated JavaScript.
+    #[arg(long)]
+    pub minify: bool,
+    /// Minify the generated CSS. Can also be enabled in the config file.
+    #[arg(long)]
+    pub minify_css: bool,
+    /// A stylesheet to put before the component's CSS without scoping it, like a reset. Can be
+    /// given more than once, and added to `css.global` in the config file.
+    #[arg(long, value_name = "PATH")]
+    pub global_css: Vec<PathBuf>,
+    /// Escape all non-ASCII characters in the output, for targets that only accept ASCII.
+    #[arg(long)]
+    pub ascii_only: bool,
     /// Pass build argument(s) the detected WASM compiler.
     #[arg(short = 'B', long, value_delimiter = ' ', value_name = "ARGS")]
     pub build_args: Vec<String>,
 
+    /// List the configured WebAssembly compilers, checking that their dependencies are installed.
+    #[arg(long)]
+    pub list_compilers: bool,
+
+    /// Always compile, without reusing or caching the outputs of previous builds.
+    #[arg(long)]
+    pub no_cache: bool,
+    /// Print a breakdown of the size of the output. Builds are never restored from the cache.
+    #[arg(long)]
+    pub analyze: bool,
+    /// Print how long each phase of the build took. Builds are never restored from the cache.
+    #[arg(long)]
+    pub timings: bool,
+    /// Write a Chrome trace of the phases of the build to FILE, which can be opened with
+    /// `chrome://tracing` or Perfetto. Builds are never restored from the cache.
+    #[arg(long, value_name = "FILE")]
+    pub trace: Option<PathBuf>,
+    /// Apply the suggested fixes of any errors and warnings to the input file. Builds are never
+    /// restored from the cache.
+    #[arg(long)]
+    pub fix: bool,
+
     /// Watch the input file for changes, recompiling if found.
     #[arg(short, long)]
     pub watch: bool,
+    /// Don't clear the terminal before recompiling in `--watch` mode.
+    #[arg(long)]
+    pub no_clear: bool,
+    /// Only print errors and warnings.
+    #[arg(short, long, conflicts_with_all = ["verbose", "log_json"])]
+    pub quiet: bool,
+    /// Also print how long each phase of the build took, and the size of what it generated.
+    #[arg(short, long)]
+    pub verbose: bool,
+    /// Print each phase of the build as a line of JSON, with its timing and the size of what it
+    /// generated.
+    #[arg(long)]
+    pub log_json: bool,
+    /// Control output colorization.
+    #[arg(short,
+          long,
+          default_value = "auto",
+          value_name = "WHEN",
+          value_parser = determine_color,
+          action = clap::ArgAction::Set
+    )]
+    pub color: bool,
+}
+
+impl Build {
+    /// The arguments of `decorous build <input>`, with every option at its default.
+    pub fn for_input(input: &Path) -> Self {
+        #[derive(Parser)]
+        struct BuildArgs {
+            #[command(flatten)]
+            build: Build,
+        }
+
+        BuildArgs::parse_from([OsStr::new("decorous"), input.as_os_str()]).build
+    }
+}
+
+/// A component that is built as part of a page with others.
+#[derive(Debug, Clone)]
+pub struct PageEntry {
+    /// The name of the component, which the ids of its prerendered elements start with.
+    pub name: String,
+    /// Whether the global stylesheets go in this component's CSS. Only one component of the page
+    /// gets them.
+    pub global_css: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct Serve {
+    #[command(flatten)]
+    pub build: Build,
+    /// The port to serve on.
+    #[arg(short, long, default_value_t = 8000)]
+    pub port: u16,
+}
+
+#[derive(Debug, Args)]
+pub struct Check {
+    /// The decor files to check. Directories are searched for decor files.
+    #[arg(value_name = "PATH", default_value = ".")]
+    pub paths: Vec<PathBuf>,
+    /// Control output colorization.
+    #[arg(short,
+          long,
+          default_value = "auto",
+          value_name = "WHEN",
+          value_parser = determine_color,
+          action = clap::ArgAction::Set
+    )]
+    pub color: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct Test {
+    /// The test scripts to run, named after the component they test, like `counter.test.js` for
+    /// `counter.decor`. Directories are searched for test scripts, or for components with
+    /// `--golden`.
+    #[arg(value_name = "PATH", default_value = ".")]
+    pub paths: Vec<PathBuf>,
+    /// Build components and compare their outputs with the expected ones next to them, like
+    /// `counter.js` and `counter.html` for `counter.decor`.
+    #[arg(long)]
+    pub golden: bool,
+    /// Replace the expected outputs with the ones that are generated now.
+    #[arg(long, requires = "golden")]
+    pub update: bool,
+    /// Keep the `{#cfg}` blocks of these features, and remove every other one.
+    #[arg(long, value_name = "NAME", value_delimiter = ',')]
+    pub features: Vec<String>,
+    /// Control output colorization.
+    #[arg(short,
+          long,
+          default_value = "auto",
+          value_name = "WHEN",
+          value_parser = determine_color,
+          action = clap::ArgAction::Set
+    )]
+    pub color: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct Fmt {
+    /// The decor files to format.
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+    /// Check that the files are formatted without changing them, failing if any aren't.
+    #[arg(long)]
+    pub check: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct Tokens {
+    /// The decor file to tokenize.
+    #[arg(value_name = "PATH")]
+    pub input: PathBuf,
+    /// Print the tokens as a JSON array.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct New {
+    /// The directory to create the project in. Its name is the name of the project.
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
     /// Control output colorization.
     #[arg(short,
           long,
@@ -65,21 +297,60 @@ pub struct Build {
     pub color: bool,
 }
 
+#[derive(Debug, Args)]
+pub struct I18n {
+    #[command(subcommand)]
+    pub command: I18nCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum I18nCommand {
+    /// Add the keys of every `{@t}` expression to the catalog of each locale in the config file.
+    /// Keys that are already in a catalog keep their messages.
+    Extract {
+        /// The decor files to collect keys from. Directories are searched for decor files.
+        #[arg(value_name = "PATH", default_value = ".")]
+        paths: Vec<PathBuf>,
+        /// Control output colorization.
+        #[arg(short,
+              long,
+              default_value = "auto",
+              value_name = "WHEN",
+              value_parser = determine_color,
+              action = clap::ArgAction::Set
+        )]
+        color: bool,
+    },
+}
+
 #[derive(Debug, Args)]
 pub struct Cache {
-    /// Clean the cache.
-    #[arg(short = 'x', long, exclusive = true)]
-    pub clean: bool,
+    #[command(subcommand)]
+    pub command: Option<CacheCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// List the cache entries, with their kind, hash, size and age.
+    List,
+    /// Remove cache entries.
+    Clean {
+        /// Only remove entries that are older than the given time, like `2days`.
+        #[arg(long, value_name = "TIME", value_parser = parse_duration)]
+        older_than: Option<Duration>,
+    },
     /// Print the location of the cache.
-    #[arg(short, long, exclusive = true)]
-    pub print: bool,
-    /// Evict cache entries that are older than the given time.
-    #[arg(long,
-          value_name = "TIME",
-          value_parser = parse_duration,
-          exclusive = true,
-    )]
-    pub evict: Option<Duration>,
+    Path,
+}
+
+#[derive(Debug, Args)]
+pub struct Clean {
+    /// The base name of the outputs to remove.
+    #[arg(short, long, value_name = "NAME", default_value = "out")]
+    pub out: String,
+    /// Remove the index.html generated by `--html` too.
+    #[arg(long)]
+    pub html: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Default)]
@@ -98,6 +369,26 @@ pub enum RenderMethod {
     Prerender,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[clap(rename_all = "kebab-case")]
+pub enum Target {
+    /// Render the component into the element with the id of its name.
+    Document,
+    /// Wrap the component in a custom element with a shadow root.
+    CustomElement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[clap(rename_all = "kebab-case")]
+pub enum Format {
+    /// An ES module.
+    Esm,
+    /// A script wrapped in an immediately invoked function.
+    Iife,
+    /// A CommonJS module.
+    Cjs,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum OptimizationLevel {
     #[clap(name = "1")]
@@ -132,6 +423,26 @@ impl Display for RenderMethod {
     }
 }
 
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Esm => write!(f, "esm"),
+            Self::Iife => write!(f, "iife"),
+            Self::Cjs => write!(f, "cjs"),
+        }
+    }
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Esm => Self::Esm,
+            Format::Iife => Self::Iife,
+            Format::Cjs => Self::Cjs,
+        }
+    }
+}
+
 impl Display for OptimizationLevel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -1,5 +1,15 @@
-use std::{collections::HashMap, hash::Hash, path::PathBuf};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    path::{Path, PathBuf},
+};
 
+use decorous_backend::css_render::{Browser, CssOptions};
+use decorous_frontend::lint::{Level, Lint, LintLevels};
+
+use crate::cli::{Build, OptimizationLevel};
+use anyhow::Context;
+use clap::ValueEnum;
 use merge::Merge;
 use serde::{Deserialize, Deserializer};
 
@@ -7,16 +17,70 @@ use serde::{Deserialize, Deserializer};
 #[serde(default)]
 pub struct Config {
     pub python: Option<PathBuf>,
+    /// The Node.js that `decorous test` runs tests with, instead of the one on the `PATH`.
+    pub node: Option<PathBuf>,
 
     pub compilers: HashMap<String, CompilerConfig>,
     pub preprocessors: HashMap<String, PreprocessPipeline>,
+    pub css: CssConfig,
+    pub html: HtmlConfig,
+    pub output: OutputConfig,
+    /// Sets of build options that are selected with `--profile`, like `[profile.release]`.
+    pub profile: HashMap<String, Profile>,
+    /// The level of each lint, like `unused-variable = "deny"`.
+    pub lints: HashMap<LintName, LintLevel>,
+    /// Packages of components shared between projects, used with paths like `@ui/button.decor`.
+    pub dependencies: HashMap<String, Dependency>,
+    /// Values of `$env.NAME` for variables that aren't set in the environment.
+    pub env: HashMap<String, String>,
+    pub i18n: I18nConfig,
+    /// What `:static` code blocks can read, like `[static]`.
+    #[serde(rename = "static")]
+    pub static_blocks: StaticConfig,
 }
 
 impl Merge for Config {
     fn merge(&mut self, other: Self) {
         self.python.merge(other.python);
+        self.node.merge(other.node);
+        self.css.merge(other.css);
+        self.html.merge(other.html);
+        self.output.merge(other.output);
+        self.i18n.merge(other.i18n);
+        self.static_blocks.merge(other.static_blocks);
         hashmap(&mut self.compilers, other.compilers);
         hashmap(&mut self.preprocessors, other.preprocessors);
+        hashmap(&mut self.lints, other.lints);
+        hashmap(&mut self.profile, other.profile);
+        hashmap(&mut self.dependencies, other.dependencies);
+        hashmap(&mut self.env, other.env);
+    }
+}
+
+impl Config {
+    pub fn lint_levels(&self) -> LintLevels {
+        let mut levels = LintLevels::new();
+        // There's nothing to warn about once the HTML is sanitized
+        if self.html.sanitizer.is_some() {
+            levels.set(Lint::UnsanitizedHtml, Level::Allow);
+        }
+        for (lint, level) in &self.lints {
+            levels.set(lint.0, level.0);
+        }
+        levels
+    }
+
+    /// Applies the profile selected by `args` to them.
+    pub fn apply_profile(&self, args: &mut Build) -> anyhow::Result<()> {
+        let Some(name) = &args.profile else {
+            return Ok(());
+        };
+        let profile = self
+            .profile
+            .get(name)
+            .with_context(|| format!("no profile named {name} in the config file"))?;
+        profile.apply(args);
+        Ok(())
     }
 }
 
@@ -24,11 +88,36 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             python: None,
+            node: None,
+            css: CssConfig::default(),
+            html: HtmlConfig::default(),
+            output: OutputConfig::default(),
+            lints: HashMap::new(),
+            dependencies: HashMap::new(),
+            env: HashMap::new(),
+            i18n: I18nConfig::default(),
+            static_blocks: StaticConfig::default(),
+            profile: HashMap::from_iter([
+                ("dev".to_owned(), Profile::default()),
+                (
+                    "release".to_owned(),
+                    Profile {
+                        optimize: Some(OptLevel(OptimizationLevel::SpeedMajor)),
+                        strip: true,
+                        drop_unused_uses: true,
+                        minify: true,
+                        minify_css: true,
+                        ..Default::default()
+                    },
+                ),
+            ]),
             preprocessors: HashMap::from_iter([
                 (
                     "scss".to_owned(),
                     PreprocessPipeline {
                         pipeline: vec!["sass --stdin".to_owned()],
+                        command: None,
+                        args: vec![],
                         target: PreprocTarget::Css,
                     },
                 ),
@@ -36,19 +125,11 @@ impl Default for Config {
                     "sass".to_owned(),
                     PreprocessPipeline {
                         pipeline: vec!["sass --stdin --indented".to_owned()],
+                        command: None,
+                        args: vec![],
                         target: PreprocTarget::Css,
                     },
                 ),
-                (
-                    "ts".to_owned(),
-                    PreprocessPipeline {
-                        pipeline: vec![
-                            "set -e; cat - > __tmp.ts; tsc __tmp.ts --pretty; cat __tmp.js"
-                                .to_owned(),
-                        ],
-                        target: PreprocTarget::Js,
-                    },
-                ),
             ]),
 
             compilers: HashMap::from_iter([
@@ -56,10 +137,14 @@ impl Default for Config {
                     "rust".to_owned(),
                     CompilerConfig {
                         ext_override: Some("rs".to_owned()),
-                        script: ScriptOrFile::Script(include_str!("./build/compilers/rust.py")),
+                        script: ScriptOrFile::Builtin(BuiltinCompiler::Rust),
                         features: vec![],
-                        deps: vec!["wasm-pack".to_owned(), "cargo".to_owned()],
+                        deps: vec!["cargo".to_owned(), "wasm-bindgen".to_owned()],
                         use_cache: true,
+                        env: HashMap::new(),
+                        hooks: CompilerHooks::default(),
+                        out_name: None,
+                        bindings: false,
                     },
                 ),
                 (
@@ -72,6 +157,10 @@ impl Default for Config {
                         features: vec![],
                         deps: vec!["emcc".to_owned()],
                         use_cache: false,
+                        env: HashMap::new(),
+                        hooks: CompilerHooks::default(),
+                        out_name: None,
+                        bindings: false,
                     },
                 ),
                 (
@@ -84,6 +173,10 @@ impl Default for Config {
                         features: vec![],
                         deps: vec!["emcc".to_owned()],
                         use_cache: false,
+                        env: HashMap::new(),
+                        hooks: CompilerHooks::default(),
+                        out_name: None,
+                        bindings: false,
                     },
                 ),
                 (
@@ -94,6 +187,10 @@ impl Default for Config {
                         features: vec![],
                         deps: vec!["zig".to_owned()],
                         use_cache: false,
+                        env: HashMap::new(),
+                        hooks: CompilerHooks::default(),
+                        out_name: None,
+                        bindings: true,
                     },
                 ),
                 (
@@ -104,6 +201,10 @@ impl Default for Config {
                         features: vec![WasmFeature(wasm_opt::Feature::BulkMemory)],
                         deps: vec!["go".to_owned()],
                         use_cache: false,
+                        env: HashMap::new(),
+                        hooks: CompilerHooks::default(),
+                        out_name: Some("out".to_owned()),
+                        bindings: false,
                     },
                 ),
                 (
@@ -114,6 +215,10 @@ impl Default for Config {
                         features: vec![],
                         deps: vec!["tinygo".to_owned()],
                         use_cache: false,
+                        env: HashMap::new(),
+                        hooks: CompilerHooks::default(),
+                        out_name: Some("out".to_owned()),
+                        bindings: false,
                     },
                 ),
                 (
@@ -124,6 +229,10 @@ impl Default for Config {
                         features: vec![],
                         deps: vec!["wat2wasm".to_owned()],
                         use_cache: false,
+                        env: HashMap::new(),
+                        hooks: CompilerHooks::default(),
+                        out_name: None,
+                        bindings: true,
                     },
                 ),
             ]),
@@ -131,6 +240,235 @@ impl Default for Config {
     }
 }
 
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct CssConfig {
+    /// Minify the generated CSS.
+    pub minify: bool,
+    /// The browsers to add vendor prefixes for.
+    pub targets: Vec<BrowserTarget>,
+    /// Stylesheets put before the component's CSS without being scoped, like a reset.
+    pub global: Vec<PathBuf>,
+}
+
+impl Merge for CssConfig {
+    fn merge(&mut self, other: Self) {
+        self.minify |= other.minify;
+        self.targets.extend(other.targets);
+        self.global.extend(other.global);
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct HtmlConfig {
+    /// The JavaScript function that the HTML of `{#html}` blocks is passed through, like
+    /// `DOMPurify.sanitize`.
+    pub sanitizer: Option<String>,
+    /// Whether the static children of elements are set through `innerHTML`, instead of being
+    /// created one by one. On by default.
+    pub collapse: Option<bool>,
+    /// Avoid what a strict Content Security Policy forbids, like creating elements through
+    /// `innerHTML`.
+    pub csp: bool,
+    /// The nonce put on the `<script>` and `<link>` tags of the generated HTML, like a
+    /// placeholder that the server replaces with the nonce of each response.
+    pub nonce: Option<String>,
+    /// The page that `--html` renders into, instead of the built-in one. See
+    /// [`HtmlTemplate`](decorous_backend::HtmlTemplate) for its placeholders.
+    pub template: Option<PathBuf>,
+}
+
+impl Merge for HtmlConfig {
+    fn merge(&mut self, other: Self) {
+        self.sanitizer.merge(other.sanitizer);
+        self.collapse.merge(other.collapse);
+        self.csp |= other.csp;
+        self.nonce.merge(other.nonce);
+        self.template.merge(other.template);
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Put a hash of the contents of the generated JavaScript and CSS in their file names, like
+    /// `out.3f2a9c1e.js`, so browsers never use a stale cached copy of them.
+    pub hash: bool,
+}
+
+impl Merge for OutputConfig {
+    fn merge(&mut self, other: Self) {
+        self.hash |= other.hash;
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct I18nConfig {
+    /// The locales that components are built for, each into its own outputs. The first one is
+    /// used when no locale is given.
+    pub locales: Vec<String>,
+    /// The directory of the message catalogs, one per locale, like `locales/fr.toml`.
+    pub catalogs: Option<PathBuf>,
+}
+
+impl I18nConfig {
+    /// The path of the message catalog of `locale`.
+    pub fn catalog(&self, locale: &str) -> PathBuf {
+        self.catalogs
+            .as_deref()
+            .unwrap_or(Path::new("locales"))
+            .join(format!("{locale}.toml"))
+    }
+}
+
+impl Merge for I18nConfig {
+    fn merge(&mut self, other: Self) {
+        self.locales.extend(other.locales);
+        self.catalogs.merge(other.catalogs);
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct StaticConfig {
+    /// Directories that static code blocks can read, at the same paths, like `data`.
+    pub read: Vec<PathBuf>,
+    /// Environment variables that static code blocks can see. They fall back to the `env` table,
+    /// like `$env.NAME`.
+    pub env: Vec<String>,
+}
+
+impl Merge for StaticConfig {
+    fn merge(&mut self, other: Self) {
+        self.read.extend(other.read);
+        self.env.extend(other.env);
+    }
+}
+
+/// Build options that are turned on together with `--profile`. Options passed on the command line
+/// are applied on top of them.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// The optimization level of the WebAssembly, like `-O`.
+    pub optimize: Option<OptLevel>,
+    pub strip: bool,
+    pub drop_unused_uses: bool,
+    pub minify: bool,
+    pub minify_css: bool,
+    pub ascii_only: bool,
+    /// Generate a full index.html file.
+    pub html: bool,
+    /// Features to keep the `{#cfg}` blocks of, along with the ones passed with `--features`.
+    pub features: Vec<String>,
+}
+
+impl Profile {
+    fn apply(&self, args: &mut Build) {
+        args.optimize = args.optimize.or(self.optimize.map(|level| level.0));
+        args.strip |= self.strip;
+        args.drop_unused_uses |= self.drop_unused_uses;
+        args.minify |= self.minify;
+        args.minify_css |= self.minify_css;
+        args.ascii_only |= self.ascii_only;
+        args.html |= self.html;
+        for feature in &self.features {
+            if !args.features.contains(feature) {
+                args.features.push(feature.clone());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OptLevel(pub OptimizationLevel);
+
+impl<'de> Deserialize<'de> for OptLevel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Levels can be written like `-O`, as `3` or `"s"`
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(u8),
+            String(String),
+        }
+
+        let level = match Raw::deserialize(deserializer)? {
+            Raw::Number(n) => n.to_string(),
+            Raw::String(s) => s,
+        };
+        OptimizationLevel::from_str(&level, false)
+            .map(OptLevel)
+            .map_err(|_| serde::de::Error::custom("invalid optimization level"))
+    }
+}
+
+impl CssConfig {
+    pub fn options(&self, minify: bool) -> CssOptions {
+        CssOptions {
+            minify: minify || self.minify,
+            targets: self.targets.iter().map(|target| target.0).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BrowserTarget(pub Browser);
+
+impl<'de> Deserialize<'de> for BrowserTarget {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let browser = match String::deserialize(deserializer)?.as_str() {
+            "chrome" => Browser::Chrome,
+            "edge" => Browser::Edge,
+            "firefox" => Browser::Firefox,
+            "safari" => Browser::Safari,
+            "ie" => Browser::Ie,
+            _ => return Err(serde::de::Error::custom("invalid browser target")),
+        };
+
+        Ok(BrowserTarget(browser))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LintName(pub Lint);
+
+impl<'de> Deserialize<'de> for LintName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map(LintName)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LintLevel(pub Level);
+
+impl<'de> Deserialize<'de> for LintLevel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map(LintLevel)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CompilerConfig {
     pub ext_override: Option<String>,
@@ -141,6 +479,28 @@ pub struct CompilerConfig {
     pub deps: Vec<String>,
     #[serde(default)]
     pub use_cache: bool,
+    /// Environment variables set for the script and its hooks.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub hooks: CompilerHooks,
+    /// The base name of the files the script outputs. `{component}` is replaced with the name of
+    /// the component, and `{lang}` with the language of the code block.
+    pub out_name: Option<String>,
+    /// Wrap the exports of the outputted module, so they can be called with strings and typed
+    /// arrays.
+    #[serde(default)]
+    pub bindings: bool,
+}
+
+/// Shell commands that are run in the directory of the script, with the same environment.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct CompilerHooks {
+    /// Run before the script.
+    pub pre: Vec<String>,
+    /// Run after the script succeeds.
+    pub post: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -185,6 +545,14 @@ impl<'de> Deserialize<'de> for WasmFeature {
 pub enum ScriptOrFile {
     Script(&'static str),
     File(PathBuf),
+    /// Compiled by decorous itself, without a script.
+    Builtin(BuiltinCompiler),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BuiltinCompiler {
+    /// Runs `cargo` and `wasm-bindgen`.
+    Rust,
 }
 
 fn deserialize_script<'de: 'a, 'a, D>(des: D) -> Result<ScriptOrFile, D::Error>
@@ -194,9 +562,26 @@ where
     Ok(ScriptOrFile::File(<PathBuf>::deserialize(des)?))
 }
 
+/// Where the components of a package come from.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Dependency {
+    /// A git repository, checked out at `rev`, or at its default branch.
+    Git { git: String, rev: Option<String> },
+    /// A directory, relative to the config file.
+    Path { path: PathBuf },
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PreprocessPipeline {
+    /// Shell commands that the code block is piped through, one after another.
+    #[serde(default)]
     pub pipeline: Vec<String>,
+    /// A program that is run instead of the pipeline. It gets the code block through stdin, and
+    /// writes the output and its diagnostics to stdout as JSON.
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
     pub target: PreprocTarget,
 }
 
@@ -0,0 +1,58 @@
+use std::{fs, io};
+
+use anyhow::{Context, Result};
+use decorous_errors::{DynErrStream, Source};
+use decorous_frontend::{
+    formatter::{self, FormatOptions},
+    Ctx, Parser,
+};
+
+use crate::cli::Fmt;
+
+pub fn fmt(args: &Fmt) -> Result<()> {
+    let mut unformatted = 0;
+    for path in &args.paths {
+        let src = fs::read_to_string(path)
+            .with_context(|| format!("error reading {}", path.display()))?;
+        let errs = DynErrStream::new(
+            Box::new(io::stderr()),
+            Source {
+                src: &src,
+                name: path.to_string_lossy().to_string(),
+            },
+        );
+        let ast = match Parser::new(&src)
+            .with_ctx(Ctx {
+                errs: errs.clone(),
+                ..Default::default()
+            })
+            .parse_raw()
+        {
+            Ok(ast) => ast,
+            Err(err) => {
+                errs.emit(err.into());
+                anyhow::bail!("\ncould not parse {}", path.display());
+            }
+        };
+
+        let formatted = formatter::format_ast(&ast, FormatOptions::default());
+        if formatted == src {
+            continue;
+        }
+        if args.check {
+            println!("{} is not formatted", path.display());
+            unformatted += 1;
+        } else {
+            fs::write(path, formatted)
+                .with_context(|| format!("error writing {}", path.display()))?;
+            println!("formatted {}", path.display());
+        }
+    }
+
+    anyhow::ensure!(
+        unformatted == 0,
+        "{unformatted} file{} not formatted",
+        if unformatted == 1 { " is" } else { "s are" }
+    );
+    Ok(())
+}
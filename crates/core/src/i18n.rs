@@ -0,0 +1,117 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use decorous_errors::{DynErrStream, Source};
+use decorous_frontend::{
+    ast::{traverse, NodeType, SpecialBlock},
+    Ctx, Parser,
+};
+
+use crate::{
+    check::find_components,
+    cli::{I18n, I18nCommand},
+    indicators::FinishLog,
+    utils,
+};
+
+pub fn i18n(args: &I18n) -> Result<()> {
+    match &args.command {
+        I18nCommand::Extract { paths, color } => extract(paths, *color),
+    }
+}
+
+/// Adds the keys used in the components at `paths` to the catalog of every locale, with empty
+/// messages for translators to fill in.
+fn extract(paths: &[PathBuf], color: bool) -> Result<()> {
+    let config = utils::get_config()?;
+    anyhow::ensure!(
+        !config.i18n.locales.is_empty(),
+        "no locales to extract messages for, add them to `i18n.locales` in the config file"
+    );
+
+    let mut files = vec![];
+    for path in paths {
+        find_components(path, &mut files)
+            .with_context(|| format!("error searching {}", path.display()))?;
+    }
+    let mut keys = BTreeSet::new();
+    for path in &files {
+        collect_keys(path, &mut keys)?;
+    }
+
+    for locale in &config.i18n.locales {
+        let path = config.i18n.catalog(locale);
+        let mut catalog: BTreeMap<String, String> = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("error reading {}", path.display()))?;
+            toml::from_str(&contents)
+                .with_context(|| format!("error parsing {}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+        let before = catalog.len();
+        for key in &keys {
+            catalog.entry(key.clone()).or_default();
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_context(|| format!("error creating {}", dir.display()))?;
+        }
+        fs::write(
+            &path,
+            toml::to_string(&catalog).context("error serializing catalog")?,
+        )
+        .with_context(|| format!("error writing {}", path.display()))?;
+
+        let added = catalog.len() - before;
+        println!(
+            "{}",
+            FinishLog::default()
+                .with_main_message(locale.clone())
+                .with_sub_message(format!(
+                    "{added} new key{}",
+                    if added == 1 { "" } else { "s" }
+                ))
+                .with_file(&path)
+                .enable_color(color)
+        );
+    }
+
+    Ok(())
+}
+
+/// Adds the keys of the `{@t}` expressions in the component at `path` to `keys`.
+fn collect_keys(path: &Path, keys: &mut BTreeSet<String>) -> Result<()> {
+    let src =
+        fs::read_to_string(path).with_context(|| format!("error reading {}", path.display()))?;
+    let errs = DynErrStream::new(
+        Box::new(io::stderr()),
+        Source {
+            src: &src,
+            name: path.to_string_lossy().to_string(),
+        },
+    );
+    let ast = match Parser::new(&src)
+        .with_ctx(Ctx {
+            errs: errs.clone(),
+            ..Default::default()
+        })
+        .parse_raw()
+    {
+        Ok(ast) => ast,
+        Err(err) => {
+            errs.emit(err.into());
+            anyhow::bail!("\ncould not parse {}", path.display());
+        }
+    };
+
+    traverse(&ast.nodes, &mut |node| {
+        if let NodeType::SpecialBlock(SpecialBlock::Translate(block)) = &node.node_type {
+            keys.insert(block.key.to_owned());
+        }
+    });
+    Ok(())
+}
@@ -2,10 +2,29 @@ use std::{
     borrow::Cow,
     fmt::{self, Display},
     path::{Path, PathBuf},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use indicatif::ProgressBar;
+use serde::Serialize;
+
+use crate::{cli::Build, utils};
+
+/// The phase of a build that a log is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Cache,
+    Parse,
+    Passes,
+    Preprocess,
+    WasmCompile,
+    Optimize,
+    Strip,
+    Render,
+    Fix,
+    Compile,
+}
 
 #[derive(Debug, Default)]
 pub struct FinishLog {
@@ -14,6 +33,12 @@ pub struct FinishLog {
     files: Vec<PathBuf>,
     mods: Vec<Cow<'static, str>>,
     enable_color: bool,
+    phase: Option<Phase>,
+    duration: Option<Duration>,
+    /// The size of what was generated, in bytes.
+    size: Option<u64>,
+    /// Whether the duration and size are displayed.
+    show_details: bool,
 }
 
 impl FinishLog {
@@ -53,6 +78,60 @@ impl FinishLog {
         self.mods.push(m.into());
         self
     }
+
+    pub fn with_phase(&mut self, phase: Phase) -> &mut Self {
+        self.phase = Some(phase);
+        self
+    }
+
+    pub fn with_duration(&mut self, duration: Duration) -> &mut Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn with_size(&mut self, size: u64) -> &mut Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn show_details(&mut self, show: bool) -> &mut Self {
+        self.show_details = show;
+        self
+    }
+
+    /// The log as a line of JSON. Its message doesn't include the details, which have their own
+    /// fields.
+    fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct JsonLog<'a> {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            phase: Option<Phase>,
+            message: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            detail: Option<&'a str>,
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            mods: &'a [Cow<'static, str>],
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            files: &'a [PathBuf],
+            #[serde(skip_serializing_if = "Option::is_none")]
+            duration_ms: Option<f64>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            size: Option<u64>,
+        }
+
+        serde_json::to_string(&JsonLog {
+            phase: self.phase,
+            message: &self.main_msg,
+            detail: self.sub_msg.as_deref(),
+            mods: &self.mods,
+            files: &self.files,
+            duration_ms: self
+                .duration
+                .map(|duration| duration.as_secs_f64() * 1000.0),
+            size: self.size,
+        })
+        .expect("logs should always serialize")
+    }
 }
 
 impl Display for FinishLog {
@@ -67,13 +146,17 @@ impl Display for FinishLog {
             },
             self.main_msg
         )?;
+        if let Some(duration) = self.duration.filter(|_| self.show_details) {
+            write!(f, " in ~{duration:.2?}")?;
+        }
         if let Some(sub_msg) = self.sub_msg.as_ref() {
             write!(f, ": {}", sub_msg)?;
         }
         if !self.mods.is_empty() {
             write!(f, " [{}]", self.mods.join(" + "))?;
         }
-        if !self.files.is_empty() {
+        let size = self.size.filter(|_| self.show_details);
+        if !self.files.is_empty() || size.is_some() {
             write!(f, " (")?;
             for file in &self.files {
                 if self.enable_color {
@@ -82,31 +165,192 @@ impl Display for FinishLog {
                     write!(f, "{}", file.display())?;
                 }
             }
+            if let Some(size) = size {
+                if !self.files.is_empty() {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", utils::format_bytes(size))?;
+            }
             write!(f, ")")?;
         }
         Ok(())
     }
 }
 
-#[derive(Debug)]
-pub struct Spinner(ProgressBar);
+/// How much is logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
 
-impl Spinner {
-    const SPINNER_TICK: Duration = Duration::from_micros(500);
+/// Prints [`FinishLog`]s, as set by `--quiet`, `--verbose` and `--log-json`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logger {
+    verbosity: Verbosity,
+    json: bool,
+    enable_color: bool,
+}
+
+impl Logger {
+    pub fn new(enable_color: bool) -> Self {
+        Self {
+            enable_color,
+            ..Default::default()
+        }
+    }
+
+    pub fn for_build(args: &Build) -> Self {
+        let verbosity = if args.quiet {
+            Verbosity::Quiet
+        } else if args.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        };
+        Self {
+            verbosity,
+            json: args.log_json,
+            enable_color: args.color,
+        }
+    }
+
+    /// Whether logs are printed for people to read, rather than as JSON or not at all.
+    pub fn is_human(&self) -> bool {
+        !self.json && self.verbosity != Verbosity::Quiet
+    }
 
-    pub fn new<T>(msg: T) -> Self
+    pub fn log(&self, log: &mut FinishLog) {
+        if let Some(line) = self.format(log, false) {
+            println!("{line}");
+        }
+    }
+
+    /// Prints `log` if the build is verbose. JSON logs always include it.
+    pub fn detail(&self, log: &mut FinishLog) {
+        if let Some(line) = self.format(log, true) {
+            println!("{line}");
+        }
+    }
+
+    fn format(&self, log: &mut FinishLog, detail: bool) -> Option<String> {
+        if self.json {
+            return Some(log.to_json());
+        }
+        match self.verbosity {
+            Verbosity::Quiet => None,
+            Verbosity::Normal if detail => None,
+            Verbosity::Normal => Some(log.enable_color(self.enable_color).to_string()),
+            Verbosity::Verbose => Some(
+                log.enable_color(self.enable_color)
+                    .show_details(true)
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// Starts a spinner, which times the phase until it's finished. Nothing is drawn unless logs
+    /// are for people to read.
+    pub fn spinner<T>(&self, msg: T) -> Spinner
     where
         T: Into<Cow<'static, str>>,
     {
-        let bar = ProgressBar::new_spinner().with_message(msg);
-        bar.enable_steady_tick(Self::SPINNER_TICK);
-        Self(bar)
+        let bar = self.is_human().then(|| {
+            let bar = ProgressBar::new_spinner().with_message(msg);
+            bar.enable_steady_tick(Spinner::SPINNER_TICK);
+            bar
+        });
+        Spinner {
+            bar,
+            logger: *self,
+            start: Instant::now(),
+        }
     }
+}
 
-    pub fn finish(&self, finish_log: String) {
-        self.0.suspend(|| {
-            println!("{finish_log}");
-        });
-        self.0.finish_and_clear();
+#[derive(Debug)]
+pub struct Spinner {
+    bar: Option<ProgressBar>,
+    logger: Logger,
+    start: Instant,
+}
+
+impl Spinner {
+    const SPINNER_TICK: Duration = Duration::from_micros(500);
+
+    /// Clears the spinner and prints `finish_log`, with how long the spinner ran for if it
+    /// doesn't have a duration.
+    pub fn finish(&self, finish_log: &mut FinishLog) {
+        if finish_log.duration.is_none() {
+            finish_log.with_duration(self.start.elapsed());
+        }
+        let Some(bar) = &self.bar else {
+            self.logger.log(finish_log);
+            return;
+        };
+        bar.suspend(|| self.logger.log(finish_log));
+        bar.finish_and_clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered() -> FinishLog {
+        let mut log = FinishLog::default();
+        log.with_main_message("JavaScript")
+            .with_sub_message("csr")
+            .with_file("out.js")
+            .with_phase(Phase::Render)
+            .with_duration(Duration::from_millis(2))
+            .with_size(1536);
+        log
+    }
+
+    fn logger(verbosity: Verbosity, json: bool) -> Logger {
+        Logger {
+            verbosity,
+            json,
+            enable_color: false,
+        }
+    }
+
+    #[test]
+    fn details_are_only_shown_when_verbose() {
+        let normal = logger(Verbosity::Normal, false);
+        assert_eq!(
+            Some("DONE JavaScript: csr (out.js)".to_owned()),
+            normal.format(&mut rendered(), false)
+        );
+        assert_eq!(None, normal.format(&mut rendered(), true));
+
+        let verbose = logger(Verbosity::Verbose, false);
+        assert_eq!(
+            Some("DONE JavaScript in ~2.00ms: csr (out.js, 1.5 KiB)".to_owned()),
+            verbose.format(&mut rendered(), false)
+        );
+        assert!(verbose.format(&mut rendered(), true).is_some());
+    }
+
+    #[test]
+    fn nothing_is_shown_when_quiet() {
+        let quiet = logger(Verbosity::Quiet, false);
+        assert_eq!(None, quiet.format(&mut rendered(), false));
+        assert_eq!(None, quiet.format(&mut rendered(), true));
+    }
+
+    #[test]
+    fn json_logs_have_a_field_for_each_detail() {
+        let json = logger(Verbosity::Normal, true);
+        assert_eq!(
+            Some(
+                r#"{"phase":"render","message":"JavaScript","detail":"csr","files":["out.js"],"duration_ms":2.0,"size":1536}"#
+                    .to_owned()
+            ),
+            json.format(&mut rendered(), true)
+        );
     }
 }
@@ -0,0 +1,151 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+};
+
+use anyhow::{Context, Result};
+
+/// What the build registered to undo if it's interrupted.
+static PENDING: Registry = Registry::new();
+
+#[derive(Debug)]
+enum Cleanup {
+    /// An output that existed before the build, with its contents from then.
+    Restore(PathBuf, Vec<u8>),
+    /// An output that the build is creating.
+    Remove(PathBuf),
+    RemoveDir(PathBuf),
+}
+
+/// What to undo if the build is interrupted, with the id of the [`Guard`] that registered it.
+#[derive(Debug)]
+struct Registry {
+    pending: Mutex<Vec<(u64, Cleanup)>>,
+    next_id: AtomicU64,
+}
+
+impl Registry {
+    const fn new() -> Self {
+        Self {
+            pending: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Vec<(u64, Cleanup)>> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn register(&self, cleanup: Cleanup) -> Guard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.pending().push((id, cleanup));
+        Guard { id, registry: self }
+    }
+
+    fn partial_output(&self, path: &Path) -> Guard<'_> {
+        match fs::read(path) {
+            Ok(contents) => self.register(Cleanup::Restore(path.to_path_buf(), contents)),
+            Err(_) => self.register(Cleanup::Remove(path.to_path_buf())),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> Guard<'_> {
+        self.register(Cleanup::RemoveDir(path.to_path_buf()))
+    }
+
+    /// Undoes everything that's registered. Errors are ignored, since the process is exiting
+    /// anyway.
+    fn clean_up(&self) {
+        for (_, cleanup) in self.pending().drain(..) {
+            let _ = match cleanup {
+                Cleanup::Restore(path, contents) => fs::write(path, contents),
+                Cleanup::Remove(path) => fs::remove_file(path),
+                Cleanup::RemoveDir(path) => fs::remove_dir_all(path),
+            };
+        }
+    }
+}
+
+/// Unregisters what it was returned for once it's dropped, when it's safe to leave behind.
+#[must_use]
+#[derive(Debug)]
+pub struct Guard<'a> {
+    id: u64,
+    registry: &'a Registry,
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        self.registry.pending().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Marks the output at `path` as being written until the guard is dropped. If the build is
+/// interrupted before then, the output is restored to what it was before, or removed if it didn't
+/// exist.
+pub fn partial_output(path: impl AsRef<Path>) -> Guard<'static> {
+    PENDING.partial_output(path.as_ref())
+}
+
+/// Removes the temporary directory at `path` if the build is interrupted before the guard is
+/// dropped. Exiting doesn't run the destructors that would otherwise remove it.
+pub fn temp_dir(path: &Path) -> Guard<'static> {
+    PENDING.remove_dir(path)
+}
+
+/// Removes the output directory at `path` if the build is interrupted before the guard is
+/// dropped. Its previous contents are already gone by the time it's being written to.
+pub fn partial_dir(path: &Path) -> Guard<'static> {
+    PENDING.remove_dir(path)
+}
+
+/// Cleans up partial outputs and temporary directories when the process is interrupted with
+/// Ctrl-C or terminated, before exiting.
+pub fn install() -> Result<()> {
+    ctrlc::set_handler(|| {
+        PENDING.clean_up();
+        // The exit code of a process killed by SIGINT
+        process::exit(130);
+    })
+    .context("error handling interrupts")
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn interrupted_outputs_are_restored_or_removed() {
+        let dir = TempDir::new("interrupt").unwrap();
+        let existing = dir.path().join("out.js");
+        let created = dir.path().join("out.css");
+        let finished = dir.path().join("out.html");
+        let temp = dir.path().join("tmp");
+        fs::write(&existing, "before").unwrap();
+        fs::create_dir(&temp).unwrap();
+
+        let registry = Registry::new();
+        let _existing = registry.partial_output(&existing);
+        let _created = registry.partial_output(&created);
+        let _temp = registry.remove_dir(&temp);
+        {
+            let _finished = registry.partial_output(&finished);
+            fs::write(&finished, "done").unwrap();
+        }
+        fs::write(&existing, "half").unwrap();
+        fs::write(&created, "half").unwrap();
+        registry.clean_up();
+
+        assert_eq!("before", fs::read_to_string(&existing).unwrap());
+        assert!(!created.exists());
+        assert!(!temp.exists());
+        assert_eq!("done", fs::read_to_string(&finished).unwrap());
+    }
+}
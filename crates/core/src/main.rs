@@ -1,8 +1,17 @@
 mod build;
 mod cache;
+mod check;
+mod clean;
 mod cli;
 mod config;
+mod fmt;
+mod i18n;
 mod indicators;
+mod interrupt;
+mod new;
+mod serve;
+mod test_runner;
+mod tokens;
 mod utils;
 
 use anyhow::Result;
@@ -20,6 +29,7 @@ fn main() -> Result<()> {
     let _profiler = dhat::Profiler::new_heap();
 
     let args = Cli::parse();
+    interrupt::install()?;
 
     match args.command {
         Command::Build(args) => {
@@ -28,6 +38,30 @@ fn main() -> Result<()> {
         Command::Cache(args) => {
             cache::cache(&args)?;
         }
+        Command::Clean(args) => {
+            clean::clean(&args)?;
+        }
+        Command::Serve(args) => {
+            serve::serve(args)?;
+        }
+        Command::Check(args) => {
+            check::check(&args)?;
+        }
+        Command::Fmt(args) => {
+            fmt::fmt(&args)?;
+        }
+        Command::Tokens(args) => {
+            tokens::tokens(&args)?;
+        }
+        Command::New(args) => {
+            new::new(&args)?;
+        }
+        Command::I18n(args) => {
+            i18n::i18n(&args)?;
+        }
+        Command::Test(args) => {
+            test_runner::test(&args)?;
+        }
     }
 
     #[cfg(feature = "dhat-heap")]
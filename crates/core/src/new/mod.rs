@@ -0,0 +1,55 @@
+use std::{fs, path::Path};
+
+use anyhow::{ensure, Context, Result};
+
+use crate::{cli::New, indicators::FinishLog};
+
+/// The files of a new project, with `%NAME%` standing for the name of the project.
+const FILES: &[(&str, &str)] = &[
+    ("app.decor", include_str!("./templates/app.decor")),
+    ("index.html", include_str!("./templates/index.html")),
+    ("decor.toml", include_str!("./templates/decor.toml")),
+    (".gitignore", include_str!("./templates/gitignore")),
+];
+
+pub fn new(args: &New) -> Result<()> {
+    let dir = &args.path;
+    ensure!(
+        !dir.exists() || is_empty_dir(dir)?,
+        "{} already exists and is not an empty directory",
+        dir.display()
+    );
+    let name = dir
+        .file_name()
+        .context("project path should end in a directory name")?
+        .to_string_lossy();
+
+    fs::create_dir_all(dir).with_context(|| format!("error creating {}", dir.display()))?;
+    for (file, contents) in FILES {
+        let path = dir.join(file);
+        fs::write(&path, contents.replace("%NAME%", &name))
+            .with_context(|| format!("error writing {}", path.display()))?;
+    }
+
+    println!(
+        "{}",
+        FinishLog::default()
+            .with_main_message(format!("created {}", dir.display()))
+            .enable_color(args.color)
+    );
+    println!(
+        "\nTo build it, run:\n  cd {}\n  decorous build app.decor -r csr --watch\nand serve the \
+         directory with any static file server.",
+        dir.display()
+    );
+
+    Ok(())
+}
+
+fn is_empty_dir(path: &Path) -> Result<bool> {
+    Ok(path.is_dir()
+        && fs::read_dir(path)
+            .with_context(|| format!("error reading {}", path.display()))?
+            .next()
+            .is_none())
+}
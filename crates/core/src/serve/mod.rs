@@ -0,0 +1,245 @@
+mod ws;
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::{ensure, Context, Result};
+use serde_json::json;
+
+use crate::{
+    build,
+    cli::{Build, RenderMethod, Serve},
+};
+
+const HMR_PATH: &str = "/__decor_hmr";
+const CLIENT_SCRIPT: &str = include_str!("./templates/client.js");
+
+type Clients = Arc<Mutex<Vec<TcpStream>>>;
+
+pub fn serve(mut args: Serve) -> Result<()> {
+    ensure!(
+        !args.build.modularize,
+        "modularized components cannot be served!"
+    );
+    ensure!(
+        args.build.others.is_empty(),
+        "only one component can be served at a time!"
+    );
+    // The served page is always the generated index.html
+    args.build.html = true;
+
+    // Updates are sent with the paths of the outputs, which have to stay the same between builds
+    let load = || build::load_config(args.build.clone(), |config| config.output.hash = false);
+    let (profiled, config) = load()?;
+    let deps = match build::compile(&profiled, &config) {
+        Ok(compiled) => compiled.deps,
+        Err(err) => {
+            build::report_error(&err);
+            vec![args.build.input.clone()]
+        }
+    };
+
+    let listener = TcpListener::bind(("127.0.0.1", args.port))
+        .with_context(|| format!("error binding to port {}", args.port))?;
+    let clients = Clients::default();
+    {
+        let clients = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let clients = Arc::clone(&clients);
+                thread::spawn(move || {
+                    let _ = handle_connection(stream, &clients);
+                });
+            }
+        });
+    }
+    println!("\nServing at http://127.0.0.1:{}", args.port);
+
+    let mut outputs = Outputs::read(&args.build);
+    build::watch(&args.build, deps, load, || {
+        if build::clears_terminal(&args.build) {
+            println!("\nServing at http://127.0.0.1:{}", args.port);
+        }
+        let new_outputs = Outputs::read(&args.build);
+        if let Some(msg) = outputs.update_message(&new_outputs, &args.build) {
+            broadcast(&clients, &msg.to_string());
+        }
+        outputs = new_outputs;
+        Ok(())
+    })
+}
+
+/// The contents of the files generated by a compilation.
+#[derive(Debug)]
+struct Outputs {
+    html: Option<Vec<u8>>,
+    js: Option<Vec<u8>>,
+    css: Option<Vec<u8>>,
+}
+
+impl Outputs {
+    fn read(args: &Build) -> Self {
+        Self {
+            html: fs::read("index.html").ok(),
+            js: fs::read(format!("{}.js", args.out)).ok(),
+            css: fs::read(format!("{}.css", args.out)).ok(),
+        }
+    }
+
+    /// Determines the least disruptive way for the browser to pick up the new outputs. Returns
+    /// `None` if nothing changed.
+    fn update_message(&self, new: &Outputs, args: &Build) -> Option<serde_json::Value> {
+        let css_changed = self.css != new.css;
+        let js_changed = self.js != new.js;
+        // Prerendered components need their new HTML, so they can't be swapped in place
+        if self.html != new.html || (js_changed && args.render_method == RenderMethod::Prerender) {
+            return Some(json!({ "type": "reload" }));
+        }
+
+        if js_changed {
+            let target = args
+                .input
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+            return Some(json!({
+                "type": "js",
+                "path": format!("{}.js", args.out),
+                "target": target,
+            }));
+        }
+        if css_changed {
+            return Some(json!({ "type": "css", "path": format!("{}.css", args.out) }));
+        }
+
+        None
+    }
+}
+
+fn broadcast(clients: &Clients, msg: &str) {
+    let mut clients = clients.lock().expect("client lock should not be poisoned");
+    clients.retain_mut(|client| ws::send_text(client, msg).is_ok());
+}
+
+fn handle_connection(mut stream: TcpStream, clients: &Clients) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let Some((key, val)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        headers.insert(key.trim().to_ascii_lowercase(), val.trim().to_owned());
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond(&mut stream, "400 Bad Request", "text/plain", b"bad request");
+    };
+    let path = target.split('?').next().unwrap_or(target);
+
+    if path == HMR_PATH {
+        let Some(key) = headers.get("sec-websocket-key") else {
+            return respond(
+                &mut stream,
+                "400 Bad Request",
+                "text/plain",
+                b"expected websocket",
+            );
+        };
+        ws::handshake(&mut stream, key)?;
+        clients
+            .lock()
+            .expect("client lock should not be poisoned")
+            .push(stream);
+        return Ok(());
+    }
+
+    if method != "GET" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"method not allowed",
+        );
+    }
+
+    let Some(file) = resolve_path(path) else {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"not found");
+    };
+    let Ok(mut contents) = fs::read(&file) else {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"not found");
+    };
+    let content_type = content_type(&file);
+    if content_type.starts_with("text/html") {
+        contents = inject_client(&contents);
+    }
+
+    respond(&mut stream, "200 OK", content_type, &contents)
+}
+
+/// Maps a request path to a file in the current directory, refusing to leave it.
+fn resolve_path(path: &str) -> Option<PathBuf> {
+    let relative = match path.trim_start_matches('/') {
+        "" => "index.html",
+        p => p,
+    };
+    let relative = Path::new(relative);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    Some(relative.to_path_buf())
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript",
+        Some("css") => "text/css",
+        Some("wasm") => "application/wasm",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        _ => "application/octet-stream",
+    }
+}
+
+fn inject_client(html: &[u8]) -> Vec<u8> {
+    let html = String::from_utf8_lossy(html);
+    let script = format!("<script>{CLIENT_SCRIPT}</script>\n");
+    match html.rfind("</body>") {
+        Some(idx) => format!("{}{script}{}", &html[..idx], &html[idx..]),
+        None => format!("{html}{script}"),
+    }
+    .into_bytes()
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
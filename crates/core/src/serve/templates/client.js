@@ -0,0 +1,33 @@
+(() => {
+  const socket = new WebSocket(`ws://${location.host}/__decor_hmr`);
+  socket.addEventListener("message", (ev) => {
+    const msg = JSON.parse(ev.data);
+    switch (msg.type) {
+      case "css":
+        for (const link of document.querySelectorAll("link[rel=stylesheet]")) {
+          const url = new URL(link.href);
+          if (url.pathname === `/${msg.path}`) {
+            url.searchParams.set("t", Date.now());
+            link.href = url.toString();
+          }
+        }
+        break;
+      case "js": {
+        const target = document.getElementById(msg.target);
+        if (!target) {
+          location.reload();
+          break;
+        }
+        target.replaceChildren();
+        const script = document.createElement("script");
+        script.type = "module";
+        script.src = `/${msg.path}?t=${Date.now()}`;
+        document.body.appendChild(script);
+        break;
+      }
+      default:
+        location.reload();
+    }
+  });
+  socket.addEventListener("close", () => console.warn("[decorous] lost connection to dev server"));
+})();
@@ -0,0 +1,63 @@
+//! The bare minimum of the WebSocket protocol (RFC 6455) needed to push messages to the
+//! browser. Messages from the client are never read.
+
+use std::io::{self, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Completes the opening handshake, given the `Sec-WebSocket-Key` header of the request.
+pub fn handshake<W: Write>(stream: &mut W, key: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )
+}
+
+/// Writes `msg` as a single, unmasked text frame.
+pub fn send_text<W: Write>(stream: &mut W, msg: &str) -> io::Result<()> {
+    let payload = msg.as_bytes();
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    // FIN bit and the text opcode
+    frame.push(0x81);
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= usize::from(u16::MAX) => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+fn accept_key(key: &str) -> String {
+    STANDARD.encode(Sha1::digest(format!("{key}{GUID}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_accept_key_from_rfc_example() {
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            accept_key("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+    }
+
+    #[test]
+    fn frames_short_text_messages() {
+        let mut out = vec![];
+        send_text(&mut out, "hi").unwrap();
+        assert_eq!(vec![0x81, 2, b'h', b'i'], out);
+    }
+}
@@ -0,0 +1,171 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use tempdir::TempDir;
+
+use crate::{
+    build,
+    check::find_components,
+    cli::{Build, Test},
+    config::Config,
+    indicators::FinishLog,
+};
+
+/// The extensions of the outputs that are compared, even if the component doesn't generate them.
+const OUTPUTS: &[&str] = &["js", "html", "css"];
+
+/// Builds every component at the paths of `args`, comparing the outputs with the expected ones
+/// next to it, like `counter.js` and `counter.html` for `counter.decor`. With `--update`, the
+/// expected outputs are replaced instead.
+pub(super) fn golden(args: &Test, mut config: Config) -> Result<()> {
+    // Hashed names would change with every change to the output
+    config.output.hash = false;
+
+    let mut files = vec![];
+    for path in &args.paths {
+        find_components(path, &mut files)
+            .with_context(|| format!("error searching {}", path.display()))?;
+    }
+    anyhow::ensure!(!files.is_empty(), "no components found");
+
+    let mut failed = 0;
+    for path in &files {
+        let mismatches = check_golden(path, args, &config)?;
+        for mismatch in &mismatches {
+            eprintln!("{mismatch}");
+        }
+        if !mismatches.is_empty() {
+            failed += 1;
+        }
+    }
+
+    anyhow::ensure!(
+        failed == 0,
+        "\n{failed} of {} component{} did not match, run with `--update` to accept their outputs",
+        files.len(),
+        if files.len() == 1 { "" } else { "s" }
+    );
+    println!(
+        "{}",
+        FinishLog::default()
+            .with_main_message(format!(
+                "{} {} component{}",
+                if args.update { "updated" } else { "matched" },
+                files.len(),
+                if files.len() == 1 { "" } else { "s" }
+            ))
+            .enable_color(args.color)
+    );
+    Ok(())
+}
+
+/// Builds the component at `path`, and returns how its outputs differ from the expected ones.
+/// With `--update`, the expected outputs are written instead, and nothing differs.
+fn check_golden(path: &Path, args: &Test, config: &Config) -> Result<Vec<String>> {
+    let stem = path
+        .file_stem()
+        .context("component should have a file name")?
+        .to_string_lossy();
+    let dir = TempDir::new("decor-golden").context("error creating temp dir for outputs")?;
+    let build_args = Build {
+        out: dir.path().join(&*stem).to_string_lossy().into_owned(),
+        // Imports of used components would have the path of the temp dir in them
+        bundle: true,
+        features: args.features.clone(),
+        no_cache: true,
+        color: args.color,
+        ..Build::for_input(path)
+    };
+    let outputs = build::compile(&build_args, config)?
+        .outputs
+        .into_iter()
+        .filter(|output| output.is_file())
+        .collect::<Vec<_>>();
+
+    let expected_path = |output: &Path| path.with_file_name(output.file_name().unwrap_or_default());
+    let mut mismatches = vec![];
+    for output in &outputs {
+        let expected = expected_path(output);
+        let contents = fs::read_to_string(output)
+            .with_context(|| format!("error reading {}", output.display()))?;
+        if args.update {
+            fs::write(&expected, contents)
+                .with_context(|| format!("error writing {}", expected.display()))?;
+            continue;
+        }
+        match fs::read_to_string(&expected) {
+            Ok(golden) => mismatches.extend(compare(&expected, &golden, &contents)),
+            Err(_) => mismatches.push(format!(
+                "{}: expected output is missing",
+                expected.display()
+            )),
+        }
+    }
+
+    // Outputs that are expected, but weren't generated
+    for ext in OUTPUTS {
+        let expected = path.with_extension(ext);
+        let generated = outputs
+            .iter()
+            .any(|output| expected_path(output) == expected);
+        if generated || !expected.is_file() {
+            continue;
+        }
+        if args.update {
+            fs::remove_file(&expected)
+                .with_context(|| format!("error removing {}", expected.display()))?;
+        } else {
+            mismatches.push(format!("{}: was not generated", expected.display()));
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Describes the first line where `actual` differs from the `expected` contents of the file at
+/// `path`, if it does.
+fn compare(path: &Path, expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (None, None) => {
+                return Some(format!(
+                    "{}: output differs in its line endings",
+                    path.display()
+                ))
+            }
+            (e, a) => {
+                return Some(format!(
+                    "{}:{line}: output differs\n  expected: {}\n       got: {}",
+                    path.display(),
+                    e.unwrap_or("<end of file>"),
+                    a.unwrap_or("<end of file>"),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_to_the_first_differing_line() {
+        let path = Path::new("out.js");
+        assert_eq!(None, compare(path, "a\nb\n", "a\nb\n"));
+        assert_eq!(
+            Some("out.js:2: output differs\n  expected: b\n       got: c".to_owned()),
+            compare(path, "a\nb\n", "a\nc\n")
+        );
+        assert_eq!(
+            Some("out.js:2: output differs\n  expected: <end of file>\n       got: b".to_owned()),
+            compare(path, "a\n", "a\nb\n")
+        );
+    }
+}
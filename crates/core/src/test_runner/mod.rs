@@ -0,0 +1,174 @@
+mod golden;
+
+use std::{
+    borrow::Cow,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use tempdir::TempDir;
+use which::which;
+
+use crate::{
+    build,
+    cli::{Build, Format, RenderMethod, Test},
+    config::Config,
+    indicators::FinishLog,
+    utils,
+};
+
+const HARNESS: &str = include_str!("./templates/harness.mjs");
+/// The endings of the names of test scripts.
+const TEST_SUFFIXES: &[&str] = &[".test.js", ".test.mjs"];
+
+pub fn test(args: &Test) -> Result<()> {
+    let config = utils::get_config()?;
+    if args.golden {
+        return golden::golden(args, config);
+    }
+    let node = find_node(&config).context(
+        "could not find node, which is needed to run tests. Set `node` in the config file if it \
+         isn't on your PATH",
+    )?;
+
+    let mut scripts = vec![];
+    for path in &args.paths {
+        find_tests(path, &mut scripts)
+            .with_context(|| format!("error searching {}", path.display()))?;
+    }
+    anyhow::ensure!(!scripts.is_empty(), "no test scripts found");
+
+    let mut failed = 0;
+    for script in &scripts {
+        if !run_test(script, args, &config, &node)? {
+            failed += 1;
+        }
+    }
+
+    anyhow::ensure!(
+        failed == 0,
+        "\n{failed} of {} test script{} failed",
+        scripts.len(),
+        if scripts.len() == 1 { "" } else { "s" }
+    );
+    println!(
+        "{}",
+        FinishLog::default()
+            .with_main_message(format!(
+                "ran {} test script{}",
+                scripts.len(),
+                if scripts.len() == 1 { "" } else { "s" }
+            ))
+            .enable_color(args.color)
+    );
+    Ok(())
+}
+
+/// Compiles the component that `script` tests into a module, and runs the script against it.
+/// Returns `false` if a test failed.
+fn run_test(script: &Path, args: &Test, config: &Config, node: &Path) -> Result<bool> {
+    let component = component_of(script).with_context(|| {
+        format!(
+            "could not find the component tested by {}, it should be named like `{}`",
+            script.display(),
+            component_name(script).display()
+        )
+    })?;
+    println!("\nTesting {}", component.display());
+
+    let dir = TempDir::new("decor-test").context("error creating temp dir for tests")?;
+    let out = dir.path().join("component");
+    let mut build_args = Build {
+        out: out.to_string_lossy().into_owned(),
+        render_method: RenderMethod::Csr,
+        modularize: true,
+        format: Format::Esm,
+        // The module is loaded from a temp dir, so used components are inlined rather than
+        // imported
+        bundle: true,
+        features: args.features.clone(),
+        no_cache: true,
+        color: args.color,
+        ..Build::for_input(&component)
+    };
+    config.apply_profile(&mut build_args)?;
+    build::compile(&build_args, config)?;
+
+    let harness = dir.path().join("harness.mjs");
+    fs::write(&harness, HARNESS).context("error writing test harness")?;
+    let script = script
+        .canonicalize()
+        .with_context(|| format!("error reading {}", script.display()))?;
+    let status = Command::new(node)
+        .arg(&harness)
+        .arg(out.with_extension("mjs"))
+        .arg(&script)
+        .status()
+        .context("error running node")?;
+    Ok(status.success())
+}
+
+fn find_node(config: &Config) -> Option<Cow<'_, Path>> {
+    if let Some(node) = &config.node {
+        return Some(Cow::Borrowed(node));
+    }
+    which("node").ok().map(Cow::Owned)
+}
+
+/// Adds the test scripts at `path` to `scripts`, searching directories recursively. Hidden
+/// directories and `node_modules` are skipped.
+fn find_tests(path: &Path, scripts: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        scripts.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        let name = entry.file_name().unwrap_or_default().to_string_lossy();
+        if entry.is_dir() && !name.starts_with('.') && name != "node_modules" {
+            find_tests(&entry, scripts)?;
+        } else if TEST_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+            scripts.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// The path of the component that the test script at `script` tests, like `counter.decor` for
+/// `counter.test.js`, whether or not it exists.
+fn component_name(script: &Path) -> PathBuf {
+    let name = script.file_name().unwrap_or_default().to_string_lossy();
+    let stem = TEST_SUFFIXES
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .unwrap_or(&name);
+    script.with_file_name(format!("{stem}.decor"))
+}
+
+/// The path of the component that the test script at `script` tests, if it exists.
+fn component_of(script: &Path) -> Option<PathBuf> {
+    Some(component_name(script)).filter(|component| component.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_are_named_after_their_component() {
+        assert_eq!(
+            Path::new("src/counter.decor"),
+            component_name(Path::new("src/counter.test.js"))
+        );
+        assert_eq!(
+            Path::new("todo-list.decor"),
+            component_name(Path::new("todo-list.test.mjs"))
+        );
+    }
+}
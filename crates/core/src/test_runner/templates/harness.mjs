@@ -0,0 +1,61 @@
+// Runs the tests of a test script against a compiled component, in a DOM created by jsdom.
+// Usage: node harness.mjs <component module> <test script>
+import assert from "node:assert/strict";
+import { createRequire } from "node:module";
+import { pathToFileURL } from "node:url";
+
+const [componentPath, scriptPath] = process.argv.slice(2);
+
+// jsdom is installed in the project, not next to this file
+const require = createRequire(pathToFileURL(`${process.cwd()}/`).href);
+let JSDOM;
+try {
+  ({ JSDOM } = require("jsdom"));
+} catch {
+  console.error(
+    "jsdom was not found, install it with `npm install --save-dev jsdom`",
+  );
+  process.exit(2);
+}
+
+const { window } = new JSDOM("<!DOCTYPE html><html><body></body></html>", {
+  pretendToBeVisual: true,
+});
+globalThis.window = window;
+for (const key of Object.getOwnPropertyNames(window)) {
+  if (!(key in globalThis)) {
+    globalThis[key] = window[key];
+  }
+}
+
+const tests = [];
+globalThis.test = (name, fn) => tests.push({ name, fn });
+globalThis.assert = assert;
+
+const { default: component } = await import(pathToFileURL(componentPath).href);
+await import(pathToFileURL(scriptPath).href);
+
+// Updates are applied in a microtask, so they're done once a task has run
+const tick = () => new Promise((resolve) => setTimeout(resolve, 0));
+
+let failed = 0;
+for (const { name, fn } of tests) {
+  document.body.replaceChildren();
+  const target = document.createElement("div");
+  document.body.appendChild(target);
+  try {
+    if (component.prototype instanceof window.HTMLElement) {
+      target.appendChild(new component());
+    } else {
+      await component(target);
+    }
+    await fn({ target, tick });
+    console.log(`ok - ${name}`);
+  } catch (err) {
+    failed += 1;
+    console.log(`FAILED - ${name}`);
+    console.log(`  ${String(err?.stack ?? err).replaceAll("\n", "\n  ")}`);
+  }
+}
+
+process.exit(failed === 0 ? 0 : 1);
@@ -0,0 +1,54 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+use decorous_errors::LineIndex;
+use decorous_frontend::tokenize;
+use serde::Serialize;
+
+use crate::cli::Tokens;
+
+#[derive(Debug, Serialize)]
+struct JsonToken<'a> {
+    kind: &'static str,
+    text: &'a str,
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+pub fn tokens(args: &Tokens) -> Result<()> {
+    let src = fs::read_to_string(&args.input)
+        .with_context(|| format!("error reading {}", args.input.display()))?;
+
+    let lines = LineIndex::new(&src);
+    let tokens = tokenize(&src)
+        .into_iter()
+        .map(|tok| {
+            let start = tok.loc.offset();
+            let end = start + tok.loc.length();
+            let pos = lines.line_col(start);
+            JsonToken {
+                kind: tok.kind.name(),
+                text: &src[start..end],
+                start,
+                end,
+                line: pos.line,
+                column: pos.column,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if args.json {
+        println!("{}", serde_json::to_string(&tokens)?);
+        return Ok(());
+    }
+    for tok in tokens {
+        println!(
+            "{}:{} {}..{} {} {:?}",
+            tok.line, tok.column, tok.start, tok.end, tok.kind, tok.text
+        );
+    }
+
+    Ok(())
+}
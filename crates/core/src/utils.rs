@@ -9,6 +9,9 @@ use merge::Merge;
 use crate::config::Config;
 
 pub fn get_cache_base() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("DECOR_CACHE_DIR") {
+        return Some(dir.into());
+    }
     #[cfg(not(target_os = "macos"))]
     let base = dirs_next::cache_dir()?.join("decorous");
     #[cfg(target_os = "macos")]
@@ -44,13 +47,26 @@ where
     Ok(size_in_bytes)
 }
 
-pub fn get_config() -> Result<Config> {
+/// Formats a size in bytes with the largest unit that keeps it above one, like `1.5 KiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Finds the closest `decor.toml`, searching from the current directory upwards.
+pub fn find_config() -> Result<Option<PathBuf>> {
     let source = env::current_dir().context("error reading current dir")?;
-    let config_path = source.ancestors().find_map(|p| {
+    Ok(source.ancestors().find_map(|p| {
         let joined = p.join("decor.toml");
         joined.exists().then_some(joined)
-    });
-    if let Some(p) = config_path {
+    }))
+}
+
+pub fn get_config() -> Result<Config> {
+    if let Some(p) = find_config()? {
         let contents = fs::read_to_string(p).context("error reading config file")?;
         let cfg = toml::from_str::<Config>(&contents).context("error parsing config")?;
         let mut default = Config::default();
@@ -60,3 +76,35 @@ pub fn get_config() -> Result<Config> {
         Ok(Config::default())
     }
 }
+
+/// Copies the file or directory at `from` to `to`, replacing whatever is at `to`.
+pub fn copy_all(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if !from.is_dir() {
+        fs::copy(from, to)?;
+        return Ok(());
+    }
+
+    if to.exists() {
+        fs::remove_dir_all(to)?;
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_all(entry.path(), to.join(entry.file_name()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_are_formatted_with_units() {
+        assert_eq!("512 B", format_bytes(512));
+        assert_eq!("1.5 KiB", format_bytes(1536));
+        assert_eq!("2.0 MiB", format_bytes(2 * 1_048_576));
+    }
+}
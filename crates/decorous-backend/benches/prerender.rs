@@ -0,0 +1,145 @@
+//! Benchmarks for prerendering large components, measuring both the time it takes and the peak
+//! memory used. Since the HTML of a component is streamed to its output as it's rendered, the
+//! peak memory should stay well under the size of the HTML.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use decorous_backend::{prerender::Prerenderer, Ctx, RenderBackend, RenderOut};
+use decorous_errors::Source;
+use decorous_frontend::{Component, Parser};
+
+/// Keeps track of the most memory that was allocated at once.
+struct PeakAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc;
+
+/// An output that throws away everything written to it, so only the memory used by the renderer
+/// is measured. The amount of HTML written is counted.
+struct Discard {
+    sink: io::Sink,
+    html_len: usize,
+}
+
+impl RenderOut for Discard {
+    fn write_js(&mut self, _buf: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_html(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.html_len += buf.len();
+        Ok(())
+    }
+
+    fn write_css(&mut self, _buf: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn js_handle(&mut self) -> &mut dyn io::Write {
+        &mut self.sink
+    }
+}
+
+/// Creates a component with `n` paragraphs of static markup, and a few dynamic ones.
+fn large_component(n: usize) -> String {
+    let mut src = String::from(
+        "---js let count = 0; ---\n#button[@click={() => count += 1}] {count} /button\n",
+    );
+    for i in 0..n {
+        src.push_str(&format!(
+            "#div[class=\"item\"] #h2 Item {i} /h2 #p Lorem ipsum dolor sit amet, consectetur adipiscing elit. /p /div\n"
+        ));
+    }
+    src
+}
+
+fn parse(src: &str) -> Component<'_> {
+    let ctx = decorous_frontend::Ctx {
+        errs: decorous_errors::stderr(Source {
+            src,
+            name: "BENCH".to_owned(),
+        }),
+        ..Default::default()
+    };
+    let ast = Parser::new(src)
+        .parse()
+        .expect("benchmark input should parse");
+    let mut component = Component::new(ast, ctx);
+    component.run_passes().unwrap();
+    component
+}
+
+/// Renders `component`, returning the length of its HTML.
+fn render(component: &Component<'_>) -> usize {
+    let mut out = Discard {
+        sink: io::sink(),
+        html_len: 0,
+    };
+    Prerenderer::new()
+        .render(component, &mut out, &Ctx::default())
+        .unwrap();
+    out.html_len
+}
+
+fn prerender_time(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prerender");
+    for n in [100, 1000, 10000] {
+        let src = large_component(n);
+        let component = parse(&src);
+        group.throughput(Throughput::Bytes(src.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(n),
+            &component,
+            |b, component| {
+                b.iter(|| render(component));
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Criterion only measures time, so the peak memory of each render is reported next to the size
+/// of the HTML it wrote.
+fn prerender_memory(_c: &mut Criterion) {
+    for n in [100, 1000, 10000] {
+        let src = large_component(n);
+        let component = parse(&src);
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        PEAK.store(allocated, Ordering::Relaxed);
+        let html_len = render(&component);
+        let peak = PEAK.load(Ordering::Relaxed) - allocated;
+        println!(
+            "prerender_peak_memory/{n}\n{:>24}{:.2} KiB peak, {:.2} KiB of HTML",
+            "",
+            peak as f64 / 1024.0,
+            html_len as f64 / 1024.0
+        );
+    }
+}
+
+criterion_group!(timing, prerender_time);
+criterion_group!(memory, prerender_memory);
+criterion_main!(timing, memory);
@@ -0,0 +1,148 @@
+//! Benchmarks for parsing and rendering the components in `benches/inputs`, and one with lots of
+//! scoped CSS, with both backends. Each input stresses a different part of the compiler: deeply
+//! nested markup, large `{#for}` blocks, and scoped CSS.
+use std::{fmt::Write, fs, io};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use decorous_backend::{
+    dom_render::CsrRenderer, prerender::Prerenderer, Ctx, RenderBackend, RenderOut,
+};
+use decorous_errors::Source;
+use decorous_frontend::{Component, Parser};
+
+/// An output that throws away everything written to it.
+struct Discard(io::Sink);
+
+impl RenderOut for Discard {
+    fn write_js(&mut self, _buf: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_html(&mut self, _buf: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_css(&mut self, _buf: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn js_handle(&mut self) -> &mut dyn io::Write {
+        &mut self.0
+    }
+}
+
+/// Generates a component with `cards` elements, each with its own nested rules.
+fn heavy_css(cards: usize) -> String {
+    let mut src = "---css\n".to_owned();
+    for i in 0..cards {
+        write!(
+            src,
+            "\
+.card-{i} {{
+  padding: {i}px;
+  color: rgb({i}, 20, 40);
+  &:hover {{
+    color: blue;
+  }}
+  > p.title-{i} span {{
+    font-weight: bold;
+  }}
+  @media (min-width: {}px) {{
+    display: flex;
+  }}
+}}
+",
+            300 + i
+        )
+        .unwrap();
+    }
+    src.push_str("---\n\n");
+    for i in 0..cards {
+        writeln!(
+            src,
+            "#div[class=\"card-{i}\"] #p[class=\"title-{i}\"] #span Card {i} /span /p /div"
+        )
+        .unwrap();
+    }
+    src
+}
+
+/// Reads every input, returning their names and contents.
+fn inputs() -> Vec<(String, String)> {
+    let inputs =
+        fs::read_dir("./benches/inputs").expect("should not have problem reading directory");
+    let mut inputs = inputs
+        .filter_map(|inp| inp.ok())
+        .map(|input| {
+            let path = input.path();
+            let contents = fs::read_to_string(&path).expect("should be able to read input file");
+            let name = path
+                .file_stem()
+                .expect("should have stem")
+                .to_string_lossy()
+                .into_owned();
+            (name, contents)
+        })
+        .collect::<Vec<_>>();
+    inputs.push(("heavy_css".to_owned(), heavy_css(150)));
+    inputs
+}
+
+fn parse(src: &str) -> Component<'_> {
+    let ctx = decorous_frontend::Ctx {
+        errs: decorous_errors::stderr(Source {
+            src,
+            name: "BENCH".to_owned(),
+        }),
+        ..Default::default()
+    };
+    let ast = Parser::new(src)
+        .parse()
+        .expect("benchmark input should parse");
+    let mut component = Component::new(ast, ctx);
+    component.run_passes().unwrap();
+    component
+}
+
+fn render<T: RenderBackend>(renderer: T, component: &Component<'_>) {
+    let mut out = Discard(io::sink());
+    renderer
+        .render(component, &mut out, &Ctx::default())
+        .unwrap();
+}
+
+/// Parses each input and runs the passes on it, which every backend has to do first.
+fn analyze_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("analyze");
+    for (name, src) in inputs() {
+        group.bench_with_input(BenchmarkId::from_parameter(&name), &src, |b, src| {
+            b.iter(|| parse(src));
+        });
+    }
+    group.finish();
+}
+
+fn render_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render");
+    for (name, src) in inputs() {
+        let component = parse(&src);
+        group.bench_with_input(
+            BenchmarkId::new("csr", &name),
+            &component,
+            |b, component| {
+                b.iter(|| render(CsrRenderer::new(), component));
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("prerender", &name),
+            &component,
+            |b, component| {
+                b.iter(|| render(Prerenderer::new(), component));
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, analyze_bench, render_bench);
+criterion_main!(benches);
@@ -0,0 +1,182 @@
+use std::{fmt::Write, io, str};
+
+use crate::RenderOut;
+
+/// A [`RenderOut`] that escapes every non-ASCII character before writing it to the inner output,
+/// for deployment targets that only accept ASCII. Characters are escaped in the syntax of the
+/// language they're written in: numeric character references in HTML, `\u` escapes in
+/// JavaScript, and hex escapes in CSS.
+pub struct AsciiOnly<T> {
+    inner: T,
+    /// Bytes of a character that was split between two writes, by language.
+    pending: [Vec<u8>; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lang {
+    Js,
+    Html,
+    Css,
+}
+
+impl<T: RenderOut> AsciiOnly<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            pending: Default::default(),
+        }
+    }
+
+    fn write_escaped(&mut self, buf: &[u8], lang: Lang) -> io::Result<()> {
+        let pending = &mut self.pending[lang as usize];
+        if pending.is_empty() && buf.is_ascii() {
+            return self.write_raw(buf, lang);
+        }
+
+        pending.extend_from_slice(buf);
+        let valid_len = match str::from_utf8(pending) {
+            Ok(text) => text.len(),
+            // The last character is incomplete, so the rest of it will come in the next write
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+        let text = str::from_utf8(&pending[..valid_len]).expect("should be valid utf-8");
+        let escaped = escape(text, lang);
+        pending.drain(..valid_len);
+        self.write_raw(escaped.as_bytes(), lang)
+    }
+
+    fn write_raw(&mut self, buf: &[u8], lang: Lang) -> io::Result<()> {
+        match lang {
+            Lang::Js => self.inner.write_js(buf),
+            Lang::Html => self.inner.write_html(buf),
+            Lang::Css => self.inner.write_css(buf),
+        }
+    }
+}
+
+impl<T: RenderOut> RenderOut for AsciiOnly<T> {
+    fn write_js(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.write_escaped(buf, Lang::Js)
+    }
+
+    fn write_html(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.write_escaped(buf, Lang::Html)
+    }
+
+    fn write_css(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.write_escaped(buf, Lang::Css)
+    }
+
+    fn js_handle(&mut self) -> &mut dyn io::Write {
+        self
+    }
+
+    fn generated_name(&mut self, name: &str) {
+        self.inner.generated_name(name)
+    }
+}
+
+impl<T: RenderOut> io::Write for AsciiOnly<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_js(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.js_handle().flush()
+    }
+}
+
+fn escape(text: &str, lang: Lang) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii() {
+            out.push(c);
+            continue;
+        }
+        // Writing to a String can't fail
+        let _ = match lang {
+            Lang::Html => write!(out, "&#x{:X};", c as u32),
+            // Characters outside of the BMP are written as surrogate pairs, which work in strings,
+            // template literals and regular expressions alike
+            Lang::Js => c
+                .encode_utf16(&mut [0; 2])
+                .iter()
+                .try_for_each(|unit| write!(out, "\\u{unit:04X}")),
+            // Always using six digits means that the escape can't run into a following hex digit
+            Lang::Css => write!(out, "\\{:06X}", c as u32),
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Out {
+        js: Vec<u8>,
+        html: Vec<u8>,
+        css: Vec<u8>,
+    }
+
+    impl RenderOut for Out {
+        fn write_js(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.js.extend_from_slice(buf);
+            Ok(())
+        }
+
+        fn write_html(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.html.extend_from_slice(buf);
+            Ok(())
+        }
+
+        fn write_css(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.css.extend_from_slice(buf);
+            Ok(())
+        }
+
+        fn js_handle(&mut self) -> &mut dyn io::Write {
+            &mut self.js
+        }
+    }
+
+    #[test]
+    fn escapes_in_the_syntax_of_each_language() {
+        let mut out = Out::default();
+        let mut ascii = AsciiOnly::new(&mut out);
+        ascii.write_js("\"café 😀\"".as_bytes()).unwrap();
+        ascii
+            .write_html("<p title=\"é\">café</p>".as_bytes())
+            .unwrap();
+        ascii
+            .write_css("p::after { content: \"é\"; }".as_bytes())
+            .unwrap();
+
+        assert_eq!(
+            "\"caf\\u00E9 \\uD83D\\uDE00\"",
+            String::from_utf8(out.js).unwrap()
+        );
+        assert_eq!(
+            "<p title=\"&#xE9;\">caf&#xE9;</p>",
+            String::from_utf8(out.html).unwrap()
+        );
+        assert_eq!(
+            "p::after { content: \"\\0000E9\"; }",
+            String::from_utf8(out.css).unwrap()
+        );
+    }
+
+    #[test]
+    fn handles_characters_split_between_writes() {
+        let mut out = Out::default();
+        let mut ascii = AsciiOnly::new(&mut out);
+        let bytes = "aé".as_bytes();
+        io::Write::write_all(&mut ascii, &bytes[..2]).unwrap();
+        io::Write::write_all(&mut ascii, &bytes[2..]).unwrap();
+
+        assert_eq!("a\\u00E9", String::from_utf8(out.js).unwrap());
+    }
+}
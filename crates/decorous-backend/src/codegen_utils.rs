@@ -1,16 +1,51 @@
 use std::{
+    cell::RefCell,
     fmt::{self, Write},
-    iter,
+    io, iter,
 };
 
-use decorous_frontend::DeclaredVariables;
+use anyhow::anyhow;
+use decorous_errors::{DiagnosticBuilder, DynErrStream, Helper};
+use decorous_frontend::{
+    ast::{
+        Attribute, BindingKind, EventHandler, EventModifier, Node, NodeType, SpecialBlock, Text,
+        TextPart, UseBlock,
+    },
+    utils, Component, DeclaredVariables, FragmentMetadata,
+};
 use itertools::Itertools;
 use rslint_parser::{
-    ast::{ArrowExpr, ArrowExprParams, AssignExpr, NameRef},
-    AstNode, SmolStr, SyntaxNode, SyntaxNodeExt,
+    ast::{
+        ArrowExpr, ArrowExprParams, AssignExpr, ExprStmt, FnDecl, FnExpr, Getter, ImportDecl,
+        Method, NameRef, Script, Setter, Template, TemplateElement, VarDecl,
+    },
+    AstNode, NodeOrToken, SmolStr, SyntaxKind, SyntaxNode, SyntaxNodeExt,
 };
 use rslint_text_edit::{apply_indels, Indel, TextRange};
 
+use crate::{OutputFormat, RenderOut, Result, UseResolver};
+
+/// The names that the renderer made up for its own helpers, like the elements and blocks of a
+/// component, which are passed on to the output with [`RenderOut::generated_name`].
+#[derive(Debug, Default)]
+pub struct GeneratedNames(RefCell<Vec<String>>);
+
+impl GeneratedNames {
+    pub fn add(&self, name: impl fmt::Display) {
+        self.0.borrow_mut().push(name.to_string());
+    }
+
+    pub fn extend(&self, names: impl IntoIterator<Item = String>) {
+        self.0.borrow_mut().extend(names);
+    }
+
+    pub fn write_to<T: RenderOut>(self, out: &mut T) {
+        for name in self.0.into_inner() {
+            out.generated_name(&name);
+        }
+    }
+}
+
 macro_rules! force_write {
     ($($arg:tt)*) => {
         write!($($arg)*).expect("write should not fail")
@@ -35,7 +70,7 @@ macro_rules! sort_if_testing {
 pub(crate) use sort_if_testing;
 
 #[derive(Debug, Clone)]
-pub struct DirtyIndices(pub(self) Vec<(usize, u8)>);
+pub struct DirtyIndices(pub(self) Vec<(usize, u32)>);
 
 impl DirtyIndices {
     pub fn new() -> Self {
@@ -65,10 +100,22 @@ impl fmt::Display for DirtyIndices {
     }
 }
 
-/// Returns an vector of (DIRTY, BITMASK). DIRTY is the index in the u8 buffer on the
-/// JavaScript side. BITMASK is a bit mask for the changed variables in the corresponding u8.
-/// For example, if the 9th variable had to be dirty, this would return [(1, 0b1)]. Or if the
-/// 9th and tenth were dirty, it work be [(1, 0b11)].
+/// The number of 32 bit words in the dirty bitmap of a context with `len` items.
+pub fn dirty_words(len: usize) -> usize {
+    (len + 31) / 32
+}
+
+/// Returns the index of the word in the dirty bitmap that tracks the context item at `ctx_idx`,
+/// and the bit mask of the item in that word. It has to agree with `__schedule_update`, which
+/// sets the bit with `dirty[ctx_idx >> 5] |= 1 << (ctx_idx & 31)`.
+pub fn dirty_bit(ctx_idx: u32) -> (usize, u32) {
+    ((ctx_idx / 32) as usize, 1 << (ctx_idx % 32))
+}
+
+/// Returns an vector of (DIRTY, BITMASK). DIRTY is the index in the `Uint32Array` on the
+/// JavaScript side. BITMASK is a bit mask for the changed variables in the corresponding word.
+/// For example, if the 33rd variable had to be dirty, this would return [(1, 0b1)]. Or if the
+/// 33rd and 34th were dirty, it would be [(1, 0b11)].
 pub fn calc_dirty(
     unbound: &[NameRef],
     declared: &DeclaredVariables,
@@ -88,12 +135,7 @@ pub fn calc_dirty(
         if scope_id.is_some_and(|id| declared.is_scope_var(&ident, id)) {
             continue;
         }
-        // Get the byte index for the dirty bitmap. Need to subtract one because
-        // ceiling division only results in 0 if x == 0.
-        let dirty_idx = ((idx + 7) / 8).saturating_sub(1) as usize;
-
-        // Modulo 8 so it wraps every byte. The byte is tracked by dirty_idx
-        let bitmask = 1 << (idx % 8);
+        let (dirty_idx, bitmask) = dirty_bit(idx);
         if let Some(pos) = dirty_indices
             .0
             .iter()
@@ -107,6 +149,526 @@ pub fn calc_dirty(
     dirty_indices
 }
 
+/// Generates the event listener that writes the bound property of an element to `target`, then
+/// schedules an update of the variable with `var_id`, whose value is `root`. `target` is usually
+/// the variable itself, but it can be a field of it or an item of it when it's an array.
+/// Content bindings ignore `input` events fired in the middle of an IME composition, since the
+/// text is not final yet. The `compositionend` event updates them instead.
+pub fn binding_closure(root: &str, target: &str, kind: BindingKind, var_id: u32) -> String {
+    let value = match kind {
+        BindingKind::Value => "ev.target.value".to_owned(),
+        BindingKind::Html | BindingKind::Text => format!("ev.currentTarget.{}", kind.property()),
+    };
+    let update = if root == target {
+        format!("__schedule_update({var_id}, {target} = {value})")
+    } else {
+        format!("__schedule_update({var_id}, {root}, {target} = {value})")
+    };
+    if kind.is_content() {
+        format!("(ev) => ev.isComposing || {update}")
+    } else {
+        format!("(ev) => {update}")
+    }
+}
+
+/// Writes the components in `uses`, in the syntax of `format`. Their `import`s are written to
+/// `imports`, and everything else to `out`. If `bundle` is set or the format can't import, the
+/// JavaScript of each component is inlined instead, in a function that evaluates to its
+/// `initialize` function. Returns `true` if an inlined component is awaited.
+pub fn write_uses(
+    imports: &mut dyn io::Write,
+    out: &mut dyn io::Write,
+    uses: &[UseBlock],
+    resolver: &dyn UseResolver,
+    format: OutputFormat,
+    bundle: bool,
+    names: &GeneratedNames,
+) -> Result<bool> {
+    let mut awaits = false;
+    for use_decl in uses {
+        let Some(name) = use_decl.name() else {
+            continue;
+        };
+        if bundle || format == OutputFormat::Iife {
+            let module = resolver.resolve_inline(&use_decl.path)?;
+            if format == OutputFormat::Iife && !module.imports.is_empty() {
+                return Err(anyhow!(
+                    "{} imports modules, which immediately invoked functions can't do",
+                    use_decl.path.display()
+                )
+                .into());
+            }
+            imports.write_all(module.imports.as_bytes())?;
+            names.extend(module.names);
+            let (asyncness, call) = if module.is_async {
+                ("async ", "await ")
+            } else {
+                ("", "")
+            };
+            writeln!(
+                out,
+                "const __decor_{name} = {call}({asyncness}() => {{\n{}\n}})();",
+                module.body.trim_end()
+            )?;
+            awaits |= module.is_async;
+        } else if format == OutputFormat::Cjs {
+            let use_info = resolver.resolve(&use_decl.path)?;
+            writeln!(
+                out,
+                "const __decor_{name} = require({});",
+                js_string_literal(&format!("./{}", use_info.loc.display())),
+            )?;
+        } else {
+            let use_info = resolver.resolve(&use_decl.path)?;
+            writeln!(
+                imports,
+                "import __decor_{name} from {};",
+                js_string_literal(&format!("./{}", use_info.loc.display())),
+            )?;
+        }
+    }
+
+    Ok(awaits)
+}
+
+/// Reports the `import`s of `component` and of its WebAssembly `prelude`, which an immediately
+/// invoked function can't have, failing if there are any.
+pub fn deny_iife_imports(
+    component: &Component,
+    prelude: Option<&str>,
+    errs: &DynErrStream,
+) -> Result<()> {
+    let mut denied = 0;
+    for import in component
+        .hoist
+        .iter()
+        .filter(|node| node.is::<ImportDecl>())
+    {
+        let span = component.script_span(import);
+        errs.emit(
+            DiagnosticBuilder::new("immediately invoked functions can't import", span.start)
+                .note("build with `--format esm` or `--format cjs` to import modules")
+                .add_helper(Helper {
+                    msg: "imported here".into(),
+                    span,
+                })
+                .build(),
+        );
+        denied += 1;
+    }
+    if let (Some(prelude), Some(wasm)) = (prelude, &component.wasm) {
+        let module = rslint_parser::parse_module(prelude, 0).syntax();
+        if module.children().any(|item| item.is::<ImportDecl>()) {
+            errs.emit(
+                DiagnosticBuilder::new(
+                    format!(
+                        "the WebAssembly ({}) is loaded with an import, which immediately invoked \
+                         functions can't have",
+                        wasm.lang
+                    ),
+                    wasm.offset,
+                )
+                .note("build with `--format esm` or `--format cjs` to import modules")
+                .build(),
+            );
+            denied += 1;
+        }
+    }
+
+    if denied > 0 {
+        return Err(anyhow!(
+            "could not compile due to {denied} import{} in an immediately invoked function",
+            if denied == 1 { "" } else { "s" }
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Writes the `import`s of the module in `js` to `imports`, and the rest of it to `out`. Returns
+/// `true` if the rest awaits at its top level.
+pub fn split_imports(
+    js: &str,
+    imports: &mut dyn io::Write,
+    out: &mut dyn io::Write,
+) -> io::Result<bool> {
+    let module = rslint_parser::parse_module(js, 0).syntax();
+    for item in module.children() {
+        if item.is::<ImportDecl>() {
+            writeln!(imports, "{}", item.text())?;
+        } else {
+            writeln!(out, "{}", item.text())?;
+        }
+    }
+    Ok(utils::has_toplevel_await(&module))
+}
+
+/// Wraps an event handler in a listener that applies `modifiers` to the event, then calls the
+/// handler with `args` followed by the event.
+pub fn wrap_event_handler(handler: &str, modifiers: &[EventModifier], args: &[String]) -> String {
+    let mut listener = String::from("(ev) => { ");
+    for modifier in modifiers {
+        force_write!(listener, "ev.{}(); ", modifier.method());
+    }
+    force_write!(listener, "({handler})(");
+    for arg in args {
+        force_write!(listener, "{arg}, ");
+    }
+    listener.push_str("ev); }");
+    listener
+}
+
+/// Quotes `s` as a JavaScript string literal. `</` is escaped too, so the literal can be in an
+/// inline `<script>`.
+pub fn js_string_literal(s: &str) -> String {
+    format!("\"{}\"", escape_js_string(s, '"'))
+}
+
+/// Escapes `s` so it can be written between the `quote`s of a JavaScript string, or between the
+/// backticks of a template literal when `quote` is `` ` ``. Like [`js_string_literal`], `</` is
+/// escaped too.
+pub fn escape_js_string(s: &str, quote: char) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            // Template literals can span lines
+            '\n' | '\t' if quote == '`' => out.push(c),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            '$' if quote == '`' && chars.peek() == Some(&'{') => out.push_str("\\$"),
+            '/' if out.ends_with('<') => out.push_str("\\/"),
+            // Line and paragraph separators end lines in older engines, even in strings
+            c if c.is_control() || matches!(c, '\u{2028}' | '\u{2029}') => {
+                force_write!(out, "\\u{{{:x}}}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The attributes that are on or off depending on whether they're present, no matter their value.
+const BOOLEAN_ATTRIBUTES: &[&str] = &[
+    "allowfullscreen",
+    "async",
+    "autofocus",
+    "autoplay",
+    "checked",
+    "controls",
+    "default",
+    "defer",
+    "disabled",
+    "formnovalidate",
+    "hidden",
+    "inert",
+    "ismap",
+    "itemscope",
+    "loop",
+    "multiple",
+    "muted",
+    "nomodule",
+    "novalidate",
+    "open",
+    "playsinline",
+    "readonly",
+    "required",
+    "reversed",
+    "selected",
+];
+
+/// Whether the attribute `key` is a boolean attribute, which is set by being present.
+pub fn is_boolean_attribute(key: &str) -> bool {
+    BOOLEAN_ATTRIBUTES.contains(&key)
+}
+
+/// The attributes that are only the initial state of an element, which is kept in a property of
+/// the same name once the element exists. Setting the attribute wouldn't change what's shown
+/// after the user interacts with the element.
+const LIVE_PROPERTIES: &[&str] = &["value", "checked", "selected", "muted", "indeterminate"];
+
+/// The statement that sets the attribute `key` of `elem` to `value`. Attributes that reflect live
+/// state, like `value`, are assigned to their property instead, and boolean attributes are
+/// toggled by the truthiness of `value`. With `csp`, `style` is set through `style.cssText`
+/// instead, since a Content Security Policy without `'unsafe-inline'` blocks setting it as an
+/// attribute.
+pub fn set_attribute(elem: &str, key: &str, value: &str, csp: bool) -> String {
+    if csp && key == "style" {
+        format!("{elem}.style.cssText = {value};")
+    } else if LIVE_PROPERTIES.contains(&key) {
+        format!("{elem}.{key} = {value};")
+    } else if is_boolean_attribute(key) {
+        format!("{elem}.toggleAttribute(\"{key}\", {value});")
+    } else {
+        format!("{elem}.setAttribute(\"{key}\", {value});")
+    }
+}
+
+/// Escapes `s` so it can be written into HTML text or a quoted attribute value.
+pub fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Elements whose text isn't parsed for tags or character references, only for their end tag.
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+/// Writes `text` as HTML inside an element called `parent`, so it's displayed the same as when
+/// it's set with `textContent`. Character references are kept as they are, since they mean the
+/// same thing in HTML.
+pub fn html_text(text: &Text, parent: Option<&str>) -> String {
+    if parent.is_some_and(|parent| RAW_TEXT_ELEMENTS.contains(&parent)) {
+        // Nothing can be escaped in raw text, so only its end tag is broken up
+        return text.unescaped().replace("</", "<\\/");
+    }
+
+    let mut out = String::with_capacity(text.0.len());
+    for (_, part) in text.parts() {
+        match part {
+            TextPart::Literal(literal) => out.push_str(&escape_html(literal)),
+            TextPart::Escaped(c) => out.push_str(&escape_html(c.encode_utf8(&mut [0; 4]))),
+            TextPart::Reference(reference) => {
+                out.push('&');
+                out.push_str(reference);
+                out.push(';');
+            }
+        }
+    }
+    out
+}
+
+/// Escapes `s` so it can be written between `<!--` and `-->`. Comments can't have `--` in them,
+/// which would end them early, or start with `>` or `->`.
+pub fn escape_comment(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    if s.starts_with('>') || s.starts_with("->") {
+        out.push(' ');
+    }
+    for c in s.chars() {
+        if c == '-' && out.ends_with('-') {
+            out.push(' ');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Collects the variables of every binding in `nodes` and their descendants, in order of
+/// appearance. These are the values that a `#form` passes to its submit handler.
+pub fn bound_vars<'a>(nodes: &[Node<'a, FragmentMetadata>]) -> Vec<&'a str> {
+    fn collect<'a>(nodes: &[Node<'a, FragmentMetadata>], vars: &mut Vec<&'a str>) {
+        for node in nodes {
+            match &node.node_type {
+                NodeType::Element(elem) => {
+                    for attr in &elem.attrs {
+                        if let Attribute::Binding(binding) = attr {
+                            if !vars.contains(&binding.root()) {
+                                vars.push(binding.root());
+                            }
+                        }
+                    }
+                    collect(&elem.children, vars);
+                }
+                NodeType::SpecialBlock(SpecialBlock::If(if_block)) => {
+                    collect(&if_block.inner, vars);
+                    if let Some(else_block) = &if_block.else_block {
+                        collect(else_block, vars);
+                    }
+                }
+                NodeType::SpecialBlock(SpecialBlock::For(for_block)) => {
+                    collect(&for_block.inner, vars);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut vars = vec![];
+    collect(nodes, &mut vars);
+    vars
+}
+
+/// The events that bubble up from an element to its ancestors. Only listeners for these can be
+/// delegated to the parent of a `{#for}` block.
+const BUBBLING_EVENTS: &[&str] = &[
+    "click",
+    "dblclick",
+    "auxclick",
+    "contextmenu",
+    "mousedown",
+    "mouseup",
+    "mouseover",
+    "mouseout",
+    "mousemove",
+    "pointerdown",
+    "pointerup",
+    "pointerover",
+    "pointerout",
+    "pointermove",
+    "pointercancel",
+    "touchstart",
+    "touchend",
+    "touchmove",
+    "touchcancel",
+    "keydown",
+    "keyup",
+    "keypress",
+    "beforeinput",
+    "input",
+    "change",
+    "focusin",
+    "focusout",
+    "submit",
+    "reset",
+    "wheel",
+    "dragstart",
+    "drag",
+    "dragend",
+    "dragenter",
+    "dragover",
+    "dragleave",
+    "drop",
+    "copy",
+    "cut",
+    "paste",
+];
+
+/// Whether `handler` can be delegated to the parent of the `{#for}` block that its element is
+/// in, instead of being attached to every item. Modifiers like `stopPropagation` have to run on
+/// the element itself.
+pub fn can_delegate(handler: &EventHandler) -> bool {
+    handler.modifiers.is_empty() && BUBBLING_EVENTS.contains(&handler.event)
+}
+
+/// Collects the events that the elements of a `{#for}` block listen for with handlers that can
+/// be delegated, in order of appearance. Nested `{#for}` blocks delegate their own.
+pub fn delegated_events<'a>(nodes: &[Node<'a, FragmentMetadata>]) -> Vec<&'a str> {
+    fn collect<'a>(nodes: &[Node<'a, FragmentMetadata>], events: &mut Vec<&'a str>) {
+        for node in nodes {
+            match &node.node_type {
+                NodeType::Element(elem) => {
+                    for attr in &elem.attrs {
+                        let Attribute::EventHandler(handler) = attr else {
+                            continue;
+                        };
+                        // Forms pass their bound values to submit handlers, which needs a
+                        // listener of their own
+                        let is_form_submit = elem.tag == "form"
+                            && elem.tag_expr.is_none()
+                            && handler.event == "submit";
+                        if can_delegate(handler)
+                            && !is_form_submit
+                            && !events.contains(&handler.event)
+                        {
+                            events.push(handler.event);
+                        }
+                    }
+                    collect(&elem.children, events);
+                }
+                NodeType::SpecialBlock(SpecialBlock::If(if_block)) => {
+                    collect(&if_block.inner, events);
+                    if let Some(else_block) = &if_block.else_block {
+                        collect(else_block, events);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut events = vec![];
+    collect(nodes, &mut events);
+    events
+}
+
+/// The property that the elements of the `{#for}` block with `for_id` store their handler for
+/// `event` in, as a JavaScript string. It includes the id of the component, so the elements of
+/// other components rendered in the block aren't mistaken for its own.
+pub fn delegation_key(component: &Component, for_id: u32, event: &str) -> String {
+    js_string_literal(&format!(
+        "__decor{}_{for_id}_{event}",
+        component.component_id
+    ))
+}
+
+/// Generates the listener that a `{#for}` block attaches to its parent, in place of a listener
+/// on each of its items. It calls the handlers stored under `key` on the elements that the event
+/// bubbled through, with `this` and `currentTarget` set to each element, as if it was listening
+/// itself. The handlers are stored along with the values of the `{#for}` block they use.
+pub fn delegated_listener(key: &str) -> String {
+    format!(
+        "(ev) => {{ const parent = ev.currentTarget; for (let node = ev.target; node && node !== parent && !ev.cancelBubble; node = node.parentNode) {{ const handler = node[{key}]; if (handler) {{ Object.defineProperty(ev, \"currentTarget\", {{ configurable: true, value: node }}); handler[0].call(node, ...handler.slice(1), ev); }} }} delete ev.currentTarget; }}"
+    )
+}
+
+/// A piece of a mustache that is a template literal, rendered to a text node of its own.
+#[derive(Debug)]
+pub enum TemplatePart {
+    /// Text that never changes, as it's written in the template literal. It can have escape
+    /// sequences.
+    Static(String),
+    /// A substitution, with the variables it references.
+    Dynamic(SyntaxNode, Vec<NameRef>),
+}
+
+/// Splits a mustache that is a template literal, like {`Hello ${name}!`}, into its text and its
+/// substitutions. Each of them can be its own text node, so an update only touches the
+/// substitutions whose variables changed. Tagged templates get all of their parts at once, so
+/// they're left whole.
+pub fn template_parts(mustache: &SyntaxNode) -> Option<Vec<TemplatePart>> {
+    let expr = match mustache.try_to::<ExprStmt>() {
+        Some(stmt) => stmt.expr()?.syntax().clone(),
+        None if mustache.is::<Script>() => return template_parts(&mustache.first_child()?),
+        None => mustache.clone(),
+    };
+    let template = expr.try_to::<Template>()?;
+    if template.tag().is_some() {
+        return None;
+    }
+    let unbound = utils::get_unbound_refs(mustache);
+    let mut parts = vec![];
+    for child in template.syntax().children_with_tokens() {
+        match child {
+            NodeOrToken::Token(token) if token.kind() == SyntaxKind::TEMPLATE_CHUNK => {
+                parts.push(TemplatePart::Static(token.text().to_string()));
+            }
+            NodeOrToken::Node(node) => {
+                let Some(expr) = node.try_to::<TemplateElement>().and_then(|e| e.expr()) else {
+                    continue;
+                };
+                let range = expr.syntax().text_range();
+                let refs = unbound
+                    .iter()
+                    .filter(|nref| range.contains_range(nref.syntax().text_range()))
+                    .cloned()
+                    .collect();
+                parts.push(TemplatePart::Dynamic(expr.syntax().clone(), refs));
+            }
+            NodeOrToken::Token(_) => {}
+        }
+    }
+    // A template that is only a substitution is no better off split
+    let has_dynamic = parts.iter().any(|p| matches!(p, TemplatePart::Dynamic(..)));
+    let has_static = parts.iter().any(|p| matches!(p, TemplatePart::Static(_)));
+    (has_dynamic && has_static).then_some(parts)
+}
+
 pub fn replace_namerefs(
     syntax_node: &SyntaxNode,
     name_refs: &[NameRef],
@@ -129,12 +691,14 @@ pub fn replace_namerefs(
     }
 
     let mut indels = vec![];
-    for unbound in name_refs.iter().filter_map(|n| n.ident_token()) {
-        if unbound
-            .parent()
-            .parent()
-            .and_then(|parent| parent.try_to::<AssignExpr>())
-            .is_some()
+    for nref in name_refs {
+        let Some(unbound) = nref.ident_token() else {
+            continue;
+        };
+        // Assigned and mutated variables are updated through their names, which are wrapped in
+        // a call to `__schedule_update`
+        if utils::is_from_assignment(nref)
+            || get_mutation(syntax_node, nref, toplevel_vars, scope_id).is_some()
         {
             continue;
         };
@@ -155,6 +719,7 @@ pub fn replace_namerefs(
         toplevel_vars,
         scope_id,
     ));
+    indels.sort_by_key(|indel| (indel.delete.start(), indel.delete.end()));
     apply_indels(&indels, &mut node_text);
 
     node_text
@@ -207,11 +772,15 @@ pub fn replace_assignments(
         toplevel_vars,
         scope_id,
     ));
+    indels.sort_by_key(|indel| (indel.delete.start(), indel.delete.end()));
     apply_indels(&indels, &mut node_text);
 
     node_text
 }
 
+/// Wraps the assignments and mutations of the variables in `name_refs` in calls to
+/// `__schedule_update`. The calls are inserted around them, so that the edits of assignments and
+/// mutations nested in each other don't overlap.
 fn replace_assignments_indels(
     syntax_node: &SyntaxNode,
     name_refs: &[NameRef],
@@ -220,27 +789,91 @@ fn replace_assignments_indels(
 ) -> Vec<Indel> {
     let mut indels = vec![];
     for name_ref in name_refs {
-        let Some(assignment) = name_ref
-            .syntax()
-            .parent()
-            .and_then(|parent| parent.try_to::<AssignExpr>())
-        else {
-            continue;
-        };
         let Some(name) = name_ref.ident_token() else {
             continue;
         };
-        let Some(idx) = toplevel_vars.get_var(name.text(), scope_id) else {
+        let (idx, wrapped, value) = if let Some(assignment) = name_ref
+            .syntax()
+            .parent()
+            .and_then(|parent| parent.try_to::<AssignExpr>())
+            .filter(|assignment| {
+                assignment.syntax().first_child().as_ref() == Some(name_ref.syntax())
+            }) {
+            let Some(idx) = toplevel_vars.get_var(name.text(), scope_id) else {
+                continue;
+            };
+            (idx, assignment.syntax().clone(), String::new())
+        } else if let Some(update) = utils::get_update(name_ref) {
+            let Some(idx) = toplevel_vars.get_var(name.text(), scope_id) else {
+                continue;
+            };
+            let range = update.syntax().text_range();
+            let start = range.start() - syntax_node.text_range().start();
+            let is_postfix = update
+                .op_token()
+                .is_some_and(|op| op.text_range().start() > name.text_range().start());
+            if is_postfix {
+                // A postfix update evaluates to the old value, so the variable is read again to
+                // schedule the update with the new one
+                indels.push(Indel::insert(start, "[".to_owned()));
+                indels.push(Indel::insert(
+                    start + range.len(),
+                    format!(", __schedule_update({idx}, {name})][0]"),
+                ));
+                continue;
+            }
+            (idx, update.syntax().clone(), String::new())
+        } else if let Some((idx, mutation)) =
+            get_mutation(syntax_node, name_ref, toplevel_vars, scope_id)
+        {
+            // The mutated object is passed along with the result of the mutation, which is
+            // what the call evaluates to
+            (idx, mutation, format!("{name}, "))
+        } else {
             continue;
         };
-        let replacement = format!("__schedule_update({}, {})", idx, assignment);
-        let local_offset = assignment.range().start() - syntax_node.text_range().start();
-        let indel = Indel::replace(
-            TextRange::new(local_offset, local_offset + assignment.range().len()),
-            replacement,
-        );
-        indels.push(indel);
+        let range = wrapped.text_range();
+        let start = range.start() - syntax_node.text_range().start();
+        indels.push(Indel::insert(
+            start,
+            format!("__schedule_update({idx}, {value}"),
+        ));
+        indels.push(Indel::insert(start + range.len(), ")".to_owned()));
     }
 
     indels
 }
+
+/// Gets the expression in `syntax_node` that mutates the top-level variable `name_ref` refers to,
+/// along with the index of the variable. Mutations in variable declarations that run as soon as
+/// the declaration does aren't included, since they run while the context is initialized, before
+/// there is anything to update.
+fn get_mutation(
+    syntax_node: &SyntaxNode,
+    name_ref: &NameRef,
+    toplevel_vars: &DeclaredVariables,
+    scope_id: Option<u32>,
+) -> Option<(u32, SyntaxNode)> {
+    let name = name_ref.ident_token()?;
+    // The bindings of {#for} blocks are copies of the items, not the variables themselves
+    if scope_id.is_some_and(|id| toplevel_vars.is_scope_var(name.text(), id)) {
+        return None;
+    }
+    let idx = toplevel_vars.get_var(name.text(), None)?;
+    let mutation = utils::get_mutation(name_ref)?;
+    let runs_eagerly = mutation
+        .ancestors()
+        .take_while(|ancestor| ancestor != syntax_node)
+        .all(|ancestor| {
+            !(ancestor.is::<FnDecl>()
+                || ancestor.is::<FnExpr>()
+                || ancestor.is::<ArrowExpr>()
+                || ancestor.is::<Method>()
+                || ancestor.is::<Getter>()
+                || ancestor.is::<Setter>())
+        });
+    if syntax_node.is::<VarDecl>() && runs_eagerly {
+        return None;
+    }
+    Some((idx, mutation))
+}
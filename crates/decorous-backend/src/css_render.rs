@@ -1,54 +1,292 @@
-use std::io::{self, Write};
+use std::io;
 
 use decorous_frontend::{css::ast::*, Component};
 use itertools::Itertools;
 use superfmt::{ContextBuilder, Formatter};
 
-pub fn render_css<T: io::Write>(css: &Css, out: &mut T, component: &Component) -> io::Result<()> {
+use crate::Linker;
+
+#[derive(Debug, Default, Clone)]
+pub struct CssOptions {
+    /// Strip whitespace and comments, and merge duplicate rules.
+    pub minify: bool,
+    /// The browsers to add vendor prefixes for.
+    pub targets: Vec<Browser>,
+    /// Stylesheets that are put before the component's CSS as they are, without being scoped.
+    pub global: Vec<String>,
+}
+
+/// A browser that vendor prefixes can be added for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser {
+    Chrome,
+    Edge,
+    Firefox,
+    Safari,
+    Ie,
+}
+
+impl Browser {
+    fn prefix(self) -> Prefix {
+        match self {
+            Self::Chrome | Self::Edge | Self::Safari => Prefix::Webkit,
+            Self::Firefox => Prefix::Moz,
+            Self::Ie => Prefix::Ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prefix {
+    Webkit,
+    Moz,
+    Ms,
+}
+
+impl Prefix {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Webkit => "-webkit-",
+            Self::Moz => "-moz-",
+            Self::Ms => "-ms-",
+        }
+    }
+}
+
+/// Properties that still need a vendor prefix in some browsers, and the prefixes they need.
+const PREFIXED_PROPERTIES: &[(&str, &[Prefix])] = &[
+    ("appearance", &[Prefix::Webkit, Prefix::Moz]),
+    ("backdrop-filter", &[Prefix::Webkit]),
+    ("background-clip", &[Prefix::Webkit]),
+    ("box-decoration-break", &[Prefix::Webkit]),
+    ("clip-path", &[Prefix::Webkit]),
+    ("hyphens", &[Prefix::Webkit, Prefix::Ms]),
+    ("mask", &[Prefix::Webkit]),
+    ("mask-image", &[Prefix::Webkit]),
+    ("mask-position", &[Prefix::Webkit]),
+    ("mask-repeat", &[Prefix::Webkit]),
+    ("mask-size", &[Prefix::Webkit]),
+    ("tab-size", &[Prefix::Moz]),
+    (
+        "text-size-adjust",
+        &[Prefix::Webkit, Prefix::Moz, Prefix::Ms],
+    ),
+    ("user-select", &[Prefix::Webkit, Prefix::Moz, Prefix::Ms]),
+];
+
+/// Returns `true` if rendering `component` with `opts` generates any CSS.
+pub(crate) fn has_css(component: &Component, opts: &CssOptions, linker: Option<&Linker>) -> bool {
+    component.css.is_some() || !opts.global.is_empty() || linker.is_some_and(Linker::has_css)
+}
+
+/// Renders the global stylesheets of `opts`, followed by the CSS of the components `component`
+/// uses, and then its own scoped CSS.
+pub(crate) fn render_component_css<T: io::Write>(
+    component: &Component,
+    out: &mut T,
+    opts: &CssOptions,
+    linker: Option<&Linker>,
+) -> io::Result<()> {
+    for stylesheet in &opts.global {
+        if opts.minify {
+            out.write_all(minify_text(stylesheet).as_bytes())?;
+        } else {
+            writeln!(out, "{}", stylesheet.trim_end())?;
+        }
+    }
+    if let Some(linker) = linker {
+        linker.write_css(out)?;
+    }
+    if let Some(css) = &component.css {
+        render_css(css, out, component, opts)?;
+    }
+    Ok(())
+}
+
+pub fn render_css<T: io::Write>(
+    css: &Css,
+    out: &mut T,
+    component: &Component,
+    opts: &CssOptions,
+) -> io::Result<()> {
+    let rules = optimize(&css.rules, opts);
+    if opts.minify {
+        for rule in &rules {
+            write_rule_minified(rule, out, component, opts)?;
+        }
+        return Ok(());
+    }
+
     let mut formatter = Formatter::new(out);
-    for rule in &css.rules {
-        write_rule(rule, &mut formatter, component)?;
+    for rule in &rules {
+        write_rule(rule, &mut formatter, component, opts)?;
     }
     Ok(())
 }
 
+/// A CSS rule that is ready to be written out.
+enum Item<'a> {
+    At(&'a AtRule, Option<Vec<Item<'a>>>),
+    Regular(&'a [Selector], Vec<&'a Declaration>),
+    Keyframes(&'a KeyframesRule),
+}
+
+fn optimize<'a>(rules: &'a [Rule], opts: &CssOptions) -> Vec<Item<'a>> {
+    let mut items: Vec<Item> = vec![];
+    for rule in rules {
+        match rule {
+            Rule::At(at_rule) => items.push(Item::At(
+                at_rule,
+                at_rule
+                    .contents
+                    .as_ref()
+                    .map(|contents| optimize(contents, opts)),
+            )),
+            Rule::Keyframes(keyframes) => items.push(Item::Keyframes(keyframes)),
+            Rule::Regular(regular) => {
+                let decls = regular.declarations.iter();
+                match items.last_mut() {
+                    // Rules right next to each other with the same selectors can always be merged,
+                    // since nothing can come between them in the cascade
+                    Some(Item::Regular(selector, prev))
+                        if opts.minify && *selector == regular.selector.as_slice() =>
+                    {
+                        prev.extend(decls);
+                    }
+                    _ => items.push(Item::Regular(&regular.selector, decls.collect())),
+                }
+            }
+        }
+    }
+    if !opts.minify {
+        return items;
+    }
+
+    for item in &mut items {
+        if let Item::Regular(_, decls) = item {
+            // Only the last of two identical declarations has any effect
+            let mut i = 0;
+            while i < decls.len() {
+                if decls[i + 1..].contains(&decls[i]) {
+                    decls.remove(i);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+    }
+    // The same goes for identical rules
+    let mut i = 0;
+    while i < items.len() {
+        let duplicated = match &items[i] {
+            Item::Regular(selector, decls) => items[i + 1..].iter().any(|item| {
+                matches!(item, Item::Regular(other_selector, other_decls) if other_selector == selector && other_decls == decls)
+            }),
+            Item::At(..) | Item::Keyframes(_) => false,
+        };
+        if duplicated {
+            items.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    items
+}
+
+/// Get the vendor prefixes that should be added to `decl`, leaving out ones that `decls` already
+/// has.
+fn prefixes_for(decl: &Declaration, decls: &[&Declaration], opts: &CssOptions) -> Vec<Prefix> {
+    let needed = PREFIXED_PROPERTIES
+        .iter()
+        .find(|(name, _)| *name == decl.name)
+        .map_or(&[][..], |(_, prefixes)| *prefixes);
+    needed
+        .iter()
+        .copied()
+        .filter(|prefix| {
+            opts.targets
+                .iter()
+                .any(|browser| browser.prefix() == *prefix)
+                && !decls.iter().any(|other| {
+                    other
+                        .name
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|name| name == decl.name)
+                })
+        })
+        .collect()
+}
+
 fn write_rule<T: io::Write>(
-    rule: &Rule,
+    rule: &Item,
     formatter: &mut Formatter<'_, T>,
     component: &Component,
+    opts: &CssOptions,
 ) -> io::Result<()> {
     match rule {
-        Rule::At(at_rule) => {
-            if let Some(contents) = &at_rule.contents {
-                formatter
-                    .write(format_args!("@{} {} ", at_rule.name, at_rule.additional))?
-                    .begin_context(
-                        ContextBuilder::new()
-                            .starts_with("{\n")
-                            .ends_with("}\n")
-                            .prepend("  ")
-                            .build(),
-                    )?;
-                for rule in contents {
-                    write_rule(rule, formatter, component)?;
-                }
-                formatter.pop_ctx()?;
-            } else {
-                formatter.writeln(format_args!("@{} {};", at_rule.name, at_rule.additional))?;
+        Item::At(at_rule, Some(contents)) => {
+            formatter
+                .write(format_args!("@{} {} ", at_rule.name, at_rule.additional))?
+                .begin_context(
+                    ContextBuilder::new()
+                        .starts_with("{\n")
+                        .ends_with("}\n")
+                        .prepend("  ")
+                        .build(),
+                )?;
+            write_decls(
+                &at_rule.declarations.iter().collect_vec(),
+                formatter,
+                component,
+                opts,
+            )?;
+            for rule in contents {
+                write_rule(rule, formatter, component, opts)?;
             }
+            formatter.pop_ctx()?;
+        }
+        Item::At(at_rule, None) => {
+            formatter.writeln(format_args!("@{} {};", at_rule.name, at_rule.additional))?;
         }
-        Rule::Regular(regular) => {
+        Item::Regular(selector, decls) => {
+            formatter.write(selector.iter().join(", "))?.begin_context(
+                ContextBuilder::default()
+                    .prepend("  ")
+                    .starts_with(" {\n")
+                    .ends_with("}\n")
+                    .build(),
+            )?;
+            write_decls(decls, formatter, component, opts)?;
+            formatter.pop_ctx()?;
+        }
+        Item::Keyframes(keyframes) => {
             formatter
-                .write(regular.selector.iter().join(", "))?
+                .write(format_args!("@{} {} ", keyframes.name, keyframes.ident))?
                 .begin_context(
-                    ContextBuilder::default()
-                        .prepend("  ")
-                        .starts_with(" {\n")
+                    ContextBuilder::new()
+                        .starts_with("{\n")
                         .ends_with("}\n")
+                        .prepend("  ")
                         .build(),
                 )?;
-            for decl in &regular.declarations {
-                write_decl(decl, formatter, component)?;
+            for keyframe in &keyframes.keyframes {
+                formatter
+                    .write(keyframe.selectors.iter().join(", "))?
+                    .begin_context(
+                        ContextBuilder::default()
+                            .prepend("  ")
+                            .starts_with(" {\n")
+                            .ends_with("}\n")
+                            .build(),
+                    )?;
+                write_decls(
+                    &keyframe.declarations.iter().collect_vec(),
+                    formatter,
+                    component,
+                    opts,
+                )?;
+                formatter.pop_ctx()?;
             }
             formatter.pop_ctx()?;
         }
@@ -57,12 +295,29 @@ fn write_rule<T: io::Write>(
     Ok(())
 }
 
+fn write_decls<T: io::Write>(
+    decls: &[&Declaration],
+    formatter: &mut Formatter<'_, T>,
+    component: &Component,
+    opts: &CssOptions,
+) -> io::Result<()> {
+    for decl in decls {
+        for prefix in prefixes_for(decl, decls, opts) {
+            write_decl(prefix.as_str(), decl, formatter, component)?;
+        }
+        write_decl("", decl, formatter, component)?;
+    }
+
+    Ok(())
+}
+
 fn write_decl<T: io::Write>(
+    prefix: &str,
     decl: &Declaration,
     f: &mut Formatter<'_, T>,
     component: &Component,
 ) -> io::Result<()> {
-    f.write(format_args!("{}: ", decl.name))?;
+    f.write(format_args!("{prefix}{}: ", decl.name))?;
     for val in &decl.values {
         write_value(val, f, component)?;
     }
@@ -71,35 +326,173 @@ fn write_decl<T: io::Write>(
     Ok(())
 }
 
-fn write_value<T: io::Write>(
-    value: &Value,
-    out: &mut Formatter<'_, T>,
-    component: &Component,
-) -> io::Result<()> {
+fn write_value<T: io::Write>(value: &Value, out: &mut T, component: &Component) -> io::Result<()> {
     match value {
         Value::Css(css) => write!(out, "{css}"),
         Value::Mustache(node) => {
+            let id = component
+                .declared_vars
+                .css_mustaches()
+                .get(node)
+                .expect("all mustaches should be in css_mustaches variable");
+            write!(out, "var({})", component.css_var_name(*id))
+        }
+    }
+}
+
+fn write_rule_minified<T: io::Write>(
+    rule: &Item,
+    out: &mut T,
+    component: &Component,
+    opts: &CssOptions,
+) -> io::Result<()> {
+    match rule {
+        Item::At(at_rule, contents) => {
+            write!(out, "@{}", at_rule.name)?;
+            let additional = minify_text(&at_rule.additional);
+            if !additional.is_empty() {
+                write!(out, " {additional}")?;
+            }
+            let Some(contents) = contents else {
+                return write!(out, ";");
+            };
+            write!(out, "{{")?;
+            write_decls_minified(
+                &at_rule.declarations.iter().collect_vec(),
+                out,
+                component,
+                opts,
+            )?;
+            if !at_rule.declarations.is_empty() && !contents.is_empty() {
+                write!(out, ";")?;
+            }
+            for rule in contents {
+                write_rule_minified(rule, out, component, opts)?;
+            }
+            write!(out, "}}")?;
+        }
+        Item::Regular(selector, decls) => {
             write!(
                 out,
-                "var(--decor-{})",
-                component
-                    .declared_vars
-                    .css_mustaches()
-                    .get(node)
-                    .expect("all mustaches should be in css_mustaches variable")
-            )
+                "{}{{",
+                selector
+                    .iter()
+                    .map(|sel| minify_text(&sel.to_string()))
+                    .join(",")
+            )?;
+            write_decls_minified(decls, out, component, opts)?;
+            write!(out, "}}")?;
+        }
+        Item::Keyframes(keyframes) => {
+            write!(out, "@{} {}{{", keyframes.name, keyframes.ident)?;
+            for keyframe in &keyframes.keyframes {
+                write!(out, "{}{{", keyframe.selectors.iter().join(","))?;
+                write_decls_minified(
+                    &keyframe.declarations.iter().collect_vec(),
+                    out,
+                    component,
+                    opts,
+                )?;
+                write!(out, "}}")?;
+            }
+            write!(out, "}}")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_decls_minified<T: io::Write>(
+    decls: &[&Declaration],
+    out: &mut T,
+    component: &Component,
+    opts: &CssOptions,
+) -> io::Result<()> {
+    let mut first = true;
+    for decl in decls {
+        let prefixes = prefixes_for(decl, decls, opts)
+            .into_iter()
+            .map(Prefix::as_str)
+            .chain([""]);
+        for prefix in prefixes {
+            if !first {
+                write!(out, ";")?;
+            }
+            first = false;
+            write!(out, "{prefix}{}:", decl.name.trim())?;
+            for (i, value) in decl.values.iter().enumerate() {
+                match value {
+                    Value::Css(css) => {
+                        let mut minified = minify_text(css);
+                        // Keep the space separating this value from the next one
+                        if i + 1 < decl.values.len() && css.ends_with(char::is_whitespace) {
+                            minified.push(' ');
+                        }
+                        write!(out, "{minified}")?;
+                    }
+                    Value::Mustache(_) => write_value(value, out, component)?,
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Collapses whitespace and removes comments from a piece of CSS text, leaving strings as is.
+fn minify_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut space = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = None;
+                for c in chars.by_ref() {
+                    if prev == Some('*') && c == '/' {
+                        break;
+                    }
+                    prev = Some(c);
+                }
+                space = true;
+            }
+            c if c.is_whitespace() => space = true,
+            '"' | '\'' => {
+                if space && !out.is_empty() && !out.ends_with(',') && c != ',' {
+                    out.push(' ');
+                }
+                space = false;
+                out.push(c);
+                let mut escaped = false;
+                for next in chars.by_ref() {
+                    out.push(next);
+                    if next == c && !escaped {
+                        break;
+                    }
+                    escaped = next == '\\' && !escaped;
+                }
+            }
+            c => {
+                if space && !out.is_empty() && !out.ends_with(',') && c != ',' {
+                    out.push(' ');
+                }
+                space = false;
+                out.push(c);
+            }
         }
     }
+    out
 }
 
 #[cfg(test)]
 mod tests {
     use decorous_errors::Source;
-    use decorous_frontend::Parser;
+    use decorous_frontend::{FixedId, Parser};
 
     use super::*;
 
-    fn make_component(input: &str) -> Component {
+    fn make_component(input: &str) -> Component<'_> {
         let parser = Parser::new(input);
         let mut c = Component::new(
             parser.parse().expect("should be valid input"),
@@ -108,6 +501,7 @@ mod tests {
                     src: input,
                     name: "TEST".to_owned(),
                 }),
+                component_ids: &FixedId(0),
                 ..Default::default()
             },
         );
@@ -120,7 +514,127 @@ mod tests {
         let mut out = vec![];
         let input = "---css body { color: {color}; } ---";
         let component = make_component(input);
-        render_css(component.css.as_ref().unwrap(), &mut out, &component).unwrap();
+        render_css(
+            component.css.as_ref().unwrap(),
+            &mut out,
+            &component,
+            &CssOptions::default(),
+        )
+        .unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap());
+    }
+
+    fn render_with(input: &str, opts: &CssOptions) -> String {
+        let mut out = vec![];
+        let component = make_component(input);
+        render_css(component.css.as_ref().unwrap(), &mut out, &component, opts).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn can_minify_css() {
+        let opts = CssOptions {
+            minify: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            "p.decor-0{color:red;border:1px solid;background:var(--decor-0-0)}@media screen{p.decor-0{font-family:\"a  b\",serif}}",
+            render_with(
+                "---css p { color: red; border: 1px   solid /* hi */ ; background: {c}; } @media screen { p { font-family: \"a  b\",   serif; } } --- #p:Hi",
+                &opts
+            )
+        );
+    }
+
+    #[test]
+    fn merges_duplicate_rules_when_minifying() {
+        let opts = CssOptions {
+            minify: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            "span.decor-0{color:blue}p.decor-0{color:red}span.decor-0{margin:0;padding:0}",
+            render_with(
+                "---css p { color: red; } span { color: blue; } p { color: red; } span { margin: 0; } span { margin: 0; padding: 0; } --- #p:Hi #span:Hi",
+                &opts
+            )
+        );
+    }
+
+    #[test]
+    fn adds_vendor_prefixes_for_targets() {
+        let opts = CssOptions {
+            minify: false,
+            targets: vec![Browser::Safari, Browser::Firefox],
+            ..Default::default()
+        };
+        insta::assert_snapshot!(render_with(
+            "---css p { user-select: none; -moz-appearance: none; appearance: none; color: red; } --- #p:Hi",
+            &opts
+        ));
+    }
+
+    #[test]
+    fn can_render_keyframes_and_font_faces() {
+        insta::assert_snapshot!(render_with(
+            "---css @font-face { font-family: \"Fira\"; src: url(fira.woff2); } @keyframes spin { from { opacity: 0; } 50%, to { opacity: {o}; } } p { animation: spin 1s; } --- #p:Hi",
+            &CssOptions::default()
+        ));
+    }
+
+    #[test]
+    fn can_minify_keyframes() {
+        let opts = CssOptions {
+            minify: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            "@font-face{font-family:\"Fira\"}@keyframes spin-decor-0{from{opacity:0}to{opacity:1}}",
+            render_with(
+                "---css @font-face { font-family: \"Fira\"; } @keyframes spin { from { opacity: 0; } to { opacity: 1; } } --- #p:Hi",
+                &opts
+            )
+        );
+    }
+
+    #[test]
+    fn global_stylesheets_come_first_unscoped() {
+        let component = make_component("---css p { color: red; } --- #p:Hi");
+        let mut opts = CssOptions {
+            global: vec!["body {\n  margin: 0; /* reset */\n}\n".to_owned()],
+            ..Default::default()
+        };
+        let mut out = vec![];
+        render_component_css(&component, &mut out, &opts, None).unwrap();
         insta::assert_snapshot!(String::from_utf8(out).unwrap());
+
+        opts.minify = true;
+        let mut out = vec![];
+        render_component_css(&component, &mut out, &opts, None).unwrap();
+        assert_eq!(
+            "body { margin: 0; }p.decor-0{color:red}",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn custom_properties_are_namespaced_by_component_id() {
+        let input = "---css p { color: {color}; } --- #p:Hi";
+        let mut component = Component::new(
+            Parser::new(input).parse().unwrap(),
+            decorous_frontend::Ctx::default(),
+        );
+        component.component_id = 7;
+        component.run_passes().unwrap();
+        let mut out = vec![];
+        let opts = CssOptions {
+            minify: true,
+            ..Default::default()
+        };
+        render_css(component.css.as_ref().unwrap(), &mut out, &component, &opts).unwrap();
+        assert_eq!(
+            "p.decor-7{color:var(--decor-7-0)}",
+            String::from_utf8(out).unwrap()
+        );
     }
 }
@@ -0,0 +1,104 @@
+use decorous_frontend::{utils, Component};
+use heck::{ToKebabCase, ToUpperCamelCase};
+use itertools::Itertools;
+use rslint_parser::{ast::VarDecl, SyntaxNodeExt};
+
+use crate::{codegen_utils, render_out::write_js, OutputFormat, RenderOut, Result};
+
+/// Returns the tag that the custom element of the component called `name` is registered as.
+/// Custom element names must contain a hyphen, so one is added if needed.
+pub(super) fn tag_name(name: &str) -> String {
+    let name = name.to_kebab_case();
+    if name.contains('-') {
+        name
+    } else {
+        format!("decor-{name}")
+    }
+}
+
+/// Returns the name of the class of the custom element of the component called `name`.
+pub(super) fn class_name(name: &str) -> String {
+    format!("{}Element", name.to_upper_camel_case())
+}
+
+/// Writes a class that wraps the `initialize` function of the component in a custom element.
+/// The component is rendered into the element's shadow root, and every reactive variable
+/// declared in the component's script can be set through an attribute of the same name. If
+/// `export` is set, the class is exported in that format. With `csp`, the CSS is adopted as a
+/// constructed stylesheet instead of put in a `<style>`, which a Content Security Policy without
+/// `'unsafe-inline'` would block.
+pub(super) fn write_custom_element<T: RenderOut>(
+    out: &mut T,
+    component: &Component,
+    name: &str,
+    css: Option<&str>,
+    export: Option<OutputFormat>,
+    csp: bool,
+) -> Result<()> {
+    let class_name = class_name(name);
+    let props = component
+        .toplevel_nodes
+        .iter()
+        .filter_map(|node| node.node.try_to::<VarDecl>())
+        .flat_map(|decl| decl.declared().filter_map(|d| d.pattern()).collect_vec())
+        .flat_map(utils::get_idents_from_pattern)
+        .filter_map(|var| {
+            let idx = component.declared_vars.get_var(&var, None)?;
+            Some(format!("\"{}\": {idx}", var.to_kebab_case()))
+        })
+        .join(", ");
+
+    if export == Some(OutputFormat::Esm) {
+        write_js!(
+            out,
+            "export default class {class_name} extends HTMLElement {{"
+        )?;
+    } else {
+        write_js!(out, "class {class_name} extends HTMLElement {{")?;
+    }
+    write_js!(out, "static props = {{{props}}};")?;
+    write_js!(out, "static observedAttributes = Object.keys(this.props);")?;
+    write_js!(out, "constructor() {{\nsuper();")?;
+    write_js!(
+        out,
+        "const shadow = this.attachShadow({{ mode: \"open\" }});"
+    )?;
+    if let Some(css) = css {
+        let escaped = codegen_utils::escape_js_string(css, '`');
+        if csp {
+            write_js!(out, "const sheet = new CSSStyleSheet();")?;
+            write_js!(out, "sheet.replaceSync(`{escaped}`);")?;
+            write_js!(out, "shadow.adoptedStyleSheets = [sheet];")?;
+        } else {
+            write_js!(out, "const style = document.createElement(\"style\");")?;
+            write_js!(out, "style.textContent = `{escaped}`;")?;
+            write_js!(out, "shadow.appendChild(style);")?;
+        }
+    }
+    write_js!(out, "this.__schedule_update = initialize(shadow);\n}}")?;
+    // Async components resolve to their update function once they're initialized
+    if component.is_async() {
+        write_js!(
+            out,
+            "attributeChangedCallback(name, _old, value) {{\nthis.__schedule_update.then((update) => update({class_name}.props[name], value));\n}}"
+        )?;
+    } else {
+        write_js!(
+            out,
+            "attributeChangedCallback(name, _old, value) {{\nthis.__schedule_update({class_name}.props[name], value);\n}}"
+        )?;
+    }
+    write_js!(out, "}}")?;
+    write_js!(
+        out,
+        "customElements.define(\"{}\", {class_name});",
+        tag_name(name)
+    )?;
+    match export {
+        Some(OutputFormat::Cjs) => write_js!(out, "module.exports = {class_name};")?,
+        Some(OutputFormat::Iife) => write_js!(out, "return {class_name};")?,
+        Some(OutputFormat::Esm) | None => {}
+    }
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::io;
+
+use decorous_frontend::{utils, Component};
+use itertools::Itertools;
+use rslint_parser::{
+    ast::{Decl, ExportDecl, FnDecl, Name, Pattern},
+    AstNode, SyntaxNodeExt,
+};
+
+use super::custom_element;
+use crate::OutputFormat;
+
+/// Writes the TypeScript declarations of the component called `name` when it's modularized in
+/// `format`: the signature of its `initialize` function, or its class if it's a `custom_element`,
+/// and what the script exports. Nothing is known about the types of the script, so its exports are
+/// typed as `any`.
+///
+/// Exports of the script are only declared for ES modules, since a CommonJS module only exports
+/// the component.
+pub fn write_declarations<W: io::Write>(
+    out: &mut W,
+    component: &Component,
+    name: &str,
+    format: OutputFormat,
+    custom_element: bool,
+) -> io::Result<()> {
+    let default = if custom_element {
+        let class_name = custom_element::class_name(name);
+        writeln!(out, "declare class {class_name} extends HTMLElement {{}}")?;
+        class_name
+    } else {
+        let returns = if component.is_async() {
+            "Promise<void>"
+        } else {
+            "void"
+        };
+        writeln!(
+            out,
+            "declare function initialize(target: Element): {returns};"
+        )?;
+        "initialize".to_owned()
+    };
+
+    match format {
+        OutputFormat::Esm => writeln!(out, "export default {default};")?,
+        OutputFormat::Cjs => return writeln!(out, "export = {default};"),
+        OutputFormat::Iife => panic!("immediately invoked functions have no declarations"),
+    }
+    for decl in component
+        .hoist
+        .iter()
+        .filter_map(|node| node.try_to::<ExportDecl>()?.decl())
+    {
+        match decl {
+            Decl::FnDecl(decl) => {
+                let Some(ident) = decl.name().and_then(|name| name.ident_token()) else {
+                    continue;
+                };
+                writeln!(
+                    out,
+                    "export declare function {ident}({}): {};",
+                    parameters(&decl),
+                    return_type(&decl)
+                )?;
+            }
+            Decl::ClassDecl(decl) => {
+                let Some(ident) = decl.name().and_then(|name| name.ident_token()) else {
+                    continue;
+                };
+                writeln!(
+                    out,
+                    "export declare class {ident} {{\n  constructor(...args: any[]);\n  [key: string]: any;\n}}"
+                )?;
+            }
+            Decl::VarDecl(decl) => {
+                let kind = if decl.is_const() {
+                    "const"
+                } else if decl.is_let() {
+                    "let"
+                } else {
+                    "var"
+                };
+                for ident in decl
+                    .declared()
+                    .filter_map(|d| d.pattern())
+                    .flat_map(utils::get_idents_from_pattern)
+                {
+                    writeln!(out, "export declare {kind} {ident}: any;")?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The parameters of `decl`, typed as `any`. Parameters with a default value are optional, and so
+/// is every parameter after them, since TypeScript doesn't allow a required parameter to follow an
+/// optional one.
+fn parameters(decl: &FnDecl) -> String {
+    let Some(params) = decl.parameters() else {
+        return String::new();
+    };
+    let mut optional = false;
+    params
+        .parameters()
+        .enumerate()
+        .map(|(i, param)| {
+            let name = |pat: Option<Pattern>| match pat {
+                Some(Pattern::SinglePattern(single)) => single
+                    .name()
+                    .and_then(|name| name.ident_token())
+                    .map_or_else(|| format!("arg{i}"), |ident| ident.text().to_string()),
+                _ => format!("arg{i}"),
+            };
+            match param {
+                Pattern::RestPattern(rest) => format!("...{}: any[]", name(rest.pat())),
+                Pattern::AssignPattern(assign) => {
+                    optional = true;
+                    // The name of a parameter with a default value isn't wrapped in a pattern
+                    let ident = assign
+                        .syntax()
+                        .child_with_ast::<Name>()
+                        .and_then(|name| name.ident_token())
+                        .map_or_else(|| format!("arg{i}"), |ident| ident.text().to_string());
+                    format!("{ident}?: any")
+                }
+                param if optional => format!("{}?: any", name(Some(param))),
+                param => format!("{}: any", name(Some(param))),
+            }
+        })
+        .join(", ")
+}
+
+fn return_type(decl: &FnDecl) -> &'static str {
+    // `async` is parsed as an identifier, since it's only a keyword in some places
+    let is_async = decl
+        .syntax()
+        .children_with_tokens()
+        .filter_map(|child| child.into_token())
+        .any(|token| token.text() == "async");
+    match (is_async, decl.star_token().is_some()) {
+        (true, true) => "AsyncGenerator<any>",
+        (true, false) => "Promise<any>",
+        (false, true) => "Generator<any>",
+        (false, false) => "any",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use decorous_errors::Source;
+    use decorous_frontend::{Ctx, FixedId, Parser};
+
+    use super::*;
+
+    fn declarations(source: &str, format: OutputFormat, custom_element: bool) -> String {
+        let ctx = Ctx {
+            errs: decorous_errors::stderr(Source {
+                src: source,
+                name: "TEST".to_owned(),
+            }),
+            component_ids: &FixedId(0),
+            ..Default::default()
+        };
+        let mut component = Component::new(Parser::new(source).parse().unwrap(), ctx);
+        component.run_passes().unwrap();
+        let mut out = vec![];
+        write_declarations(&mut out, &component, "test", format, custom_element).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn declares_initialize_and_the_exports_of_the_script() {
+        insta::assert_snapshot!(declarations(
+            "---js export function greet(name, { loud }, greeting = \"Hi\", punctuation, ...rest) {} export async function load() {} export class Store {} export const [a, b] = [1, 2]; export let count = 0; ---",
+            OutputFormat::Esm,
+            false
+        ));
+    }
+
+    #[test]
+    fn async_components_are_initialized_with_a_promise() {
+        insta::assert_snapshot!(declarations(
+            "---js await 1; --- #p:Hi",
+            OutputFormat::Esm,
+            false
+        ));
+    }
+
+    #[test]
+    fn declares_the_class_of_custom_elements() {
+        insta::assert_snapshot!(declarations(
+            "---js export function f() {} --- #p:Hi",
+            OutputFormat::Esm,
+            true
+        ));
+    }
+
+    #[test]
+    fn commonjs_modules_only_export_the_component() {
+        insta::assert_snapshot!(declarations(
+            "---js export function f() {} --- #p:Hi",
+            OutputFormat::Cjs,
+            false
+        ));
+    }
+}
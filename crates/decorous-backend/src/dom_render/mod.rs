@@ -1,21 +1,57 @@
+mod custom_element;
+mod declarations;
 mod render_fragment;
 
 use decorous_frontend::{utils, Component};
-use heck::ToSnekCase;
+use heck::ToUpperCamelCase;
 use itertools::Itertools;
-use rslint_parser::AstNode;
-use std::{borrow::Cow, io};
+use rslint_parser::{
+    ast::{ExportDecl, ImportDecl},
+    AstNode, SyntaxNodeExt,
+};
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+};
 
 use crate::{
-    codegen_utils, css_render,
+    codegen_utils::{self, sort_if_testing, GeneratedNames},
+    css_render::{self, CssOptions},
+    html_template::{nonce_attr, TemplateValues},
     render_out::{write_html, write_js},
-    CodeInfo, Ctx, RenderBackend, RenderOut, Result,
+    runtime, CodeInfo, Ctx, InlineModule, JsFile, OutputFormat, RenderBackend, RenderOut, Result,
 };
+pub use declarations::write_declarations;
 pub(crate) use render_fragment::{render_fragment, State};
 
 #[derive(Debug, Default)]
 pub struct CsrOptions {
+    /// Export the `initialize` function of the component, instead of mounting it on load.
     pub modularize: bool,
+    /// How imports, exports and used components are written.
+    pub format: OutputFormat,
+    /// Inline the JavaScript of used components, instead of importing them.
+    pub bundle: bool,
+    /// Wrap the component in a custom element, so it can be used as an HTML tag.
+    pub custom_element: bool,
+    /// How the component's CSS is optimized.
+    pub css: CssOptions,
+    /// The JavaScript function that the HTML of `{#html}` blocks is passed through before it's
+    /// rendered, like `DOMPurify.sanitize`.
+    pub html_sanitizer: Option<String>,
+    /// Create every element separately, instead of setting the `innerHTML` of elements whose
+    /// children are all static.
+    pub no_collapse: bool,
+    /// Avoid what a strict Content Security Policy forbids, like creating elements through
+    /// `innerHTML`.
+    pub csp: bool,
+    /// The nonce put on the `<script>` and `<link>` tags of the generated HTML, usually a
+    /// placeholder that the server replaces with the nonce of each response.
+    pub nonce: Option<String>,
+    /// The path of the runtime module that helpers like `mount` are imported from, instead of
+    /// being defined in the component. Ignored for immediately invoked functions, which can't
+    /// import anything.
+    pub runtime: Option<String>,
 }
 
 #[derive(Default)]
@@ -29,79 +65,223 @@ impl CsrRenderer {
             opts: CsrOptions::default(),
         }
     }
-}
 
-impl RenderBackend for CsrRenderer {
-    type Options = CsrOptions;
+    /// The path that the helpers are imported from, if they are.
+    fn runtime(&self) -> Option<&str> {
+        self.opts
+            .runtime
+            .as_deref()
+            .filter(|_| self.opts.format != OutputFormat::Iife)
+    }
 
-    fn with_options(&mut self, options: Self::Options) {
-        self.opts = options;
+    /// Renders the modularized `component` to be inlined into the components that use it. Its CSS
+    /// is written to `out`, and its JavaScript is returned.
+    pub fn render_inline<T: RenderOut>(
+        &self,
+        component: &Component,
+        out: T,
+        ctx: &Ctx,
+    ) -> Result<InlineModule> {
+        let mut imports = vec![];
+        let mut inlined = InlineOut {
+            out,
+            js: vec![],
+            names: vec![],
+        };
+        let is_async = self.render_module(component, &mut inlined, ctx, Some(&mut imports))?;
+        Ok(InlineModule {
+            imports: String::from_utf8(imports).expect("rendered JavaScript should be valid utf-8"),
+            body: String::from_utf8(inlined.js).expect("rendered JavaScript should be valid utf-8"),
+            is_async,
+            names: inlined.names,
+        })
     }
 
-    fn render<T: RenderOut>(&self, component: &Component, mut out: T, ctx: &Ctx) -> Result<()> {
-        if let Some(css) = component.css.as_ref() {
+    /// Renders `component` to `out`. When it's rendered to be inlined, its imports are written to
+    /// `imports` instead, and it returns `initialize` rather than exporting it. Returns `true` if
+    /// the inlined JavaScript awaits at its top level.
+    fn render_module<T: RenderOut>(
+        &self,
+        component: &Component,
+        mut out: T,
+        ctx: &Ctx,
+        mut imports: Option<&mut Vec<u8>>,
+    ) -> Result<bool> {
+        // Styles of custom elements have to be put in their shadow root, so no CSS file is made
+        let mut shadow_css = None;
+        if css_render::has_css(component, &self.opts.css, ctx.linker) {
             let mut css_out = vec![];
-            css_render::render_css(css, &mut css_out, component)?;
-            out.write_css(&css_out)?;
+            css_render::render_component_css(component, &mut css_out, &self.opts.css, ctx.linker)?;
+            if self.opts.custom_element {
+                shadow_css = Some(String::from_utf8_lossy(&css_out).into_owned());
+            } else {
+                out.write_css(&css_out)?;
+            }
         }
 
         if let Some(info) = &ctx.index_html {
-            if component.css.is_some() {
+            let body = if self.opts.custom_element {
+                let tag = custom_element::tag_name(ctx.name);
+                format!("<{tag}></{tag}>")
+            } else {
+                format!("<div id=\"{}\"></div>", ctx.name)
+            };
+            let has_css = css_render::has_css(component, &self.opts.css, ctx.linker)
+                && !self.opts.custom_element;
+            if let Some(template) = &info.template {
+                let css = Vec::from_iter(has_css.then(|| format!("{}.css", info.basename)));
+                let (start, end) = template.render(&TemplateValues {
+                    scripts: &[format!("{}.js", info.basename)],
+                    css: &css,
+                    title: ctx.name,
+                    nonce: self.opts.nonce.as_deref(),
+                    head: "",
+                });
+                write_html!(out, "{start}{body}{end}")?;
+            } else if has_css {
                 write_html!(
                     out,
                     include_str!("./templates/index_css.html"),
-                    name = ctx.name,
+                    body = body,
                     script = format!("{}.js", info.basename),
+                    nonce = nonce_attr(self.opts.nonce.as_deref()),
                     css = format!("{}.css", info.basename),
                 )?;
             } else {
                 write_html!(
                     out,
                     include_str!("./templates/index.html"),
-                    name = ctx.name,
+                    body = body,
                     script = format!("{}.js", info.basename),
+                    nonce = nonce_attr(self.opts.nonce.as_deref()),
                 )?;
             }
         }
 
-        if let Some(wasm) = component.wasm.as_ref() {
-            let wasm_prelude = ctx.wasm_compiler.compile(CodeInfo {
-                lang: wasm.lang,
-                body: wasm.body,
-                exports: &component.exports,
-            })?;
-            out.write_js(wasm_prelude.as_bytes())?;
+        let format = self.opts.format;
+        let wasm_prelude = component
+            .wasm
+            .as_ref()
+            .map(|wasm| {
+                ctx.wasm_compiler.compile(CodeInfo {
+                    lang: wasm.lang,
+                    body: wasm.body,
+                    exports: &component.exports,
+                })
+            })
+            .transpose()?;
+        if format == OutputFormat::Iife {
+            codegen_utils::deny_iife_imports(component, wasm_prelude.as_deref(), &ctx.errs)?;
+        }
+        let mut awaits = false;
+        if let Some(wasm_prelude) = wasm_prelude {
+            match imports.as_deref_mut() {
+                Some(imports) => {
+                    awaits |=
+                        codegen_utils::split_imports(&wasm_prelude, imports, &mut out.js_handle())?;
+                }
+                None => out.write_js(wasm_prelude.as_bytes())?,
+            }
         };
 
-        for use_decl in &component.uses {
-            let Some(stem) = use_decl.file_stem() else {
-                continue;
-            };
-            let use_info = ctx.use_resolver.resolve(use_decl)?;
-            write_js!(
-                out,
-                "import __decor_{} from \"./{}\";",
-                stem.to_string_lossy().to_snek_case(),
-                use_info.loc.display(),
-            )?;
+        // Components with top-level await are initialized in an async function. Components that
+        // aren't wrapped in an `initialize` function are wrapped in an async arrow function instead
+        let is_async = component.is_async();
+        let asyncness = if is_async { "async " } else { "" };
+        let wrap_async = is_async && !self.opts.modularize && !self.opts.custom_element;
+        if format == OutputFormat::Iife {
+            if self.opts.modularize {
+                write_js!(out, "var {} = (() => {{", ctx.name.to_upper_camel_case())?;
+            } else if wrap_async {
+                write_js!(out, "(async () => {{")?;
+            } else {
+                write_js!(out, "(() => {{")?;
+            }
+        }
+
+        if let Some(runtime) = self.runtime() {
+            let helpers = &["mount", "scheduler"];
+            match imports.as_deref_mut() {
+                Some(imports) => {
+                    runtime::write_import(&mut JsFile::new(imports), runtime, helpers, format)?;
+                }
+                None => runtime::write_import(&mut out, runtime, helpers, format)?,
+            }
+        }
+        let names = GeneratedNames::default();
+        let mut uses = vec![];
+        match imports.as_deref_mut() {
+            Some(imports) => {
+                awaits |= codegen_utils::write_uses(
+                    imports,
+                    &mut uses,
+                    &component.uses,
+                    ctx.use_resolver,
+                    format,
+                    self.opts.bundle,
+                    &names,
+                )?;
+            }
+            None => {
+                let mut use_imports = vec![];
+                codegen_utils::write_uses(
+                    &mut use_imports,
+                    &mut uses,
+                    &component.uses,
+                    ctx.use_resolver,
+                    format,
+                    self.opts.bundle,
+                    &names,
+                )?;
+                out.write_js(&use_imports)?;
+            }
         }
+        out.write_js(&uses)?;
 
         // Hoisted syntax nodes should come first
         for hoist in &component.hoist {
+            if let Some(imports) = imports.as_deref_mut() {
+                if hoist.is::<ImportDecl>() {
+                    writeln!(imports, "{hoist}")?;
+                    continue;
+                }
+                // There is no module to export from, so only what is declared is kept
+                if let Some(export) = hoist.try_to::<ExportDecl>() {
+                    if let Some(decl) = export.decl() {
+                        write_js!(out, "{}", decl.syntax())?;
+                    }
+                    continue;
+                }
+            }
             write_js!(out, "{hoist}")?;
         }
 
-        render_init_ctx(&mut out.js_handle(), component)?;
-
-        if self.opts.modularize {
-            write_js!(out, "export default function initialize(target) {{")?;
+        if wrap_async && format != OutputFormat::Iife {
+            write_js!(out, "(async () => {{")?;
         }
+        if self.opts.custom_element || imports.is_some() {
+            write_js!(out, "{asyncness}function initialize(target) {{")?;
+        } else if self.opts.modularize {
+            match format {
+                OutputFormat::Esm => write_js!(
+                    out,
+                    "export default {asyncness}function initialize(target) {{"
+                )?,
+                OutputFormat::Cjs => write_js!(
+                    out,
+                    "module.exports = {asyncness}function initialize(target) {{"
+                )?,
+                OutputFormat::Iife => write_js!(out, "{asyncness}function initialize(target) {{")?,
+            }
+        }
+        // The context is created in `initialize`, so that the closures in it can schedule updates
+        // and every mounted component has its own state
+        render_init_ctx(&mut out.js_handle(), component, &names)?;
 
         write_js!(
             out,
-            "const dirty = new Uint8Array(new ArrayBuffer({}));",
-            // Ceiling division to get the amount of bytes needed in the ArrayBuffer
-            ((component.declared_vars.len() + 7) / 8)
+            "const dirty = new Uint32Array({});",
+            codegen_utils::dirty_words(component.declared_vars.len())
         )?;
 
         let state = State {
@@ -109,45 +289,139 @@ impl RenderBackend for CsrRenderer {
             component,
             root: None,
             uses: vec![],
+            form_values: None,
+            html_sanitizer: self.opts.html_sanitizer.as_deref(),
+            collapse: !self.opts.no_collapse && !self.opts.csp,
+            csp: self.opts.csp,
+            shared_runtime: self.runtime().is_some(),
+            delegate: None,
+            names: &names,
         };
         render_fragment(&component.fragment_tree, state, &mut out.js_handle())?;
 
-        write_js!(out, "const ctx = __init_ctx();")?;
-        if self.opts.modularize {
+        if is_async {
+            write_js!(out, "const ctx = await __init_ctx();")?;
+        } else {
+            write_js!(out, "const ctx = __init_ctx();")?;
+        }
+        if self.opts.modularize || self.opts.custom_element {
             write_js!(out, "const fragment = create_main_block(target);")?;
         } else {
             write_js!(
                 out,
-                "const fragment = create_main_block(document.getElementById(\"{}\"));",
-                ctx.name
+                "const fragment = create_main_block(document.getElementById({}));",
+                codegen_utils::js_string_literal(ctx.name)
             )?;
         }
-        write_js!(out, "let updating = false;")?;
-        write_js!(
-            out,
-            "function __schedule_update(ctx_idx, val) {{
+        if self.runtime().is_some() {
+            write_js!(
+                out,
+                "const __schedule_update = scheduler(ctx, dirty, (dirty) => fragment.u(dirty));"
+            )?;
+        } else {
+            write_js!(out, "let updating = false;")?;
+            write_js!(
+                out,
+                "function __schedule_update(ctx_idx, val, result = val) {{
 ctx[ctx_idx] = val;
-dirty[Math.max(Math.ceil(ctx_idx / 8) - 1, 0)] |= 1 << (ctx_idx % 8);
-if (updating) return;
+dirty[ctx_idx >> 5] |= 1 << (ctx_idx & 31);
+if (updating) return result;
 updating = true;
 Promise.resolve().then(() => {{
 fragment.u(dirty);
 updating = false;
 dirty.fill(0);
 }});
+return result;
 }}"
-        )?;
+            )?;
+        }
 
-        if self.opts.modularize {
+        if self.opts.custom_element {
+            write_js!(out, "return __schedule_update;\n}}")?;
+            custom_element::write_custom_element(
+                &mut out,
+                component,
+                ctx.name,
+                shadow_css.as_deref(),
+                self.opts.modularize.then_some(format),
+                self.opts.csp,
+            )?;
+        } else if self.opts.modularize {
             write_js!(out, "}}")?;
+            if format == OutputFormat::Iife || imports.is_some() {
+                write_js!(out, "return initialize;")?;
+            }
+        } else if wrap_async && format != OutputFormat::Iife {
+            write_js!(out, "}})();")?;
         }
+        if format == OutputFormat::Iife {
+            write_js!(out, "}})();")?;
+        }
+        names.write_to(&mut out);
+
+        Ok(awaits)
+    }
+}
+
+impl RenderBackend for CsrRenderer {
+    type Options = CsrOptions;
 
+    fn with_options(&mut self, options: Self::Options) {
+        self.opts = options;
+    }
+
+    fn render<T: RenderOut>(&self, component: &Component, out: T, ctx: &Ctx) -> Result<()> {
+        self.render_module(component, out, ctx, None)?;
         Ok(())
     }
 }
 
-fn render_init_ctx<W: io::Write>(out: &mut W, component: &Component<'_>) -> io::Result<()> {
-    writeln!(out, "function __init_ctx() {{")?;
+/// Collects the JavaScript of an inlined component and the names generated in it, passing
+/// everything else through to `out`.
+struct InlineOut<T> {
+    out: T,
+    js: Vec<u8>,
+    names: Vec<String>,
+}
+
+impl<T: RenderOut> RenderOut for InlineOut<T> {
+    fn write_js(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.js.extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn write_css(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.out.write_css(buf)
+    }
+
+    fn write_html(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.out.write_html(buf)
+    }
+
+    fn js_handle(&mut self) -> &mut dyn io::Write {
+        &mut self.js
+    }
+
+    fn generated_name(&mut self, name: &str) {
+        self.names.push(name.to_owned());
+    }
+}
+
+fn render_init_ctx<W: io::Write>(
+    out: &mut W,
+    component: &Component<'_>,
+    names: &GeneratedNames,
+) -> io::Result<()> {
+    if component.is_async() {
+        writeln!(out, "async function __init_ctx() {{")?;
+    } else {
+        writeln!(out, "function __init_ctx() {{")?;
+    }
+    let derived_vars = component.declared_vars.derived_vars();
+    if !derived_vars.is_empty() {
+        writeln!(out, "let {};", derived_vars.join(", "))?;
+    }
     writeln!(
         out,
         "{}",
@@ -168,7 +442,11 @@ fn render_init_ctx<W: io::Write>(out: &mut W, component: &Component<'_>) -> io::
             })
             .join("\n")
     )?;
-    for (arrow_expr, (idx, scope)) in component.declared_vars.all_arrow_exprs() {
+    for (arrow_expr, (idx, scope)) in sort_if_testing!(
+        component.declared_vars.all_arrow_exprs().iter(),
+        |(_, (a, _)), (_, (b, _))| a.cmp(b)
+    ) {
+        names.add(format_args!("__closure{idx}"));
         writeln!(
             out,
             "let __closure{idx} = {};",
@@ -180,24 +458,41 @@ fn render_init_ctx<W: io::Write>(out: &mut W, component: &Component<'_>) -> io::
             )
         )?;
     }
-    for (name, id) in component.declared_vars.all_bindings() {
-        if let Some(var_id) = component.declared_vars.get_var(name, None) {
-            writeln!(
-                out,
-                "let __binding{id} = (ev) => __schedule_update({var_id}, {name} = ev.target.value);"
-            )?;
-        } else {
-            todo!("unbound var lint");
-        }
+    for ((name, kind), id) in
+        sort_if_testing!(component.declared_vars.all_bindings().iter(), |a, b| a
+            .1
+            .cmp(b.1))
+    {
+        // Bindings to fields, like `user.name`, update the variable they're a field of
+        let root = name.split('.').next().unwrap_or(name);
+        let var_id = component
+            .declared_vars
+            .get_var(root, None)
+            .expect("BUG: bindings to undeclared variables should be errors");
+        names.add(format_args!("__binding{id}"));
+        writeln!(
+            out,
+            "let __binding{id} = {};",
+            codegen_utils::binding_closure(root, name, *kind, var_id)
+        )?;
     }
-    for (block, id) in component.declared_vars.all_reactive_blocks() {
+    for (block, id) in component.declared_vars.reactive_blocks_in_order() {
         let replaced = codegen_utils::replace_assignments(
             block,
             &utils::get_unbound_refs(block),
             &component.declared_vars,
             None,
         );
-        writeln!(out, "let __reactive{id} = () => {{ {replaced} }};")?;
+        let asyncness = if utils::has_toplevel_await(block) {
+            "async "
+        } else {
+            ""
+        };
+        names.add(format_args!("__reactive{id}"));
+        writeln!(
+            out,
+            "let __reactive{id} = {asyncness}() => {{ {replaced} }};"
+        )?;
     }
     let mut ctx = vec![Cow::Borrowed("undefined"); component.declared_vars.len()];
     for (name, idx) in component.declared_vars.all_vars() {
@@ -225,7 +520,7 @@ mod tests {
     use super::*;
     use crate::{NullCompiler, NullResolver};
     use decorous_errors::Source;
-    use decorous_frontend::Parser;
+    use decorous_frontend::{FixedId, Parser};
 
     #[derive(Default)]
     struct TestOut {
@@ -267,6 +562,7 @@ mod tests {
             });
             let ctx = decorous_frontend::Ctx {
                 errs,
+                component_ids: &FixedId(0),
                 ..Default::default()
             };
             let mut component = Component::new(parser.parse().expect("should be valid input"), ctx);
@@ -327,6 +623,30 @@ mod tests {
         test_render!("#span:hello");
     }
 
+    #[test]
+    fn collapsed_html_is_escaped() {
+        test_render!(r#"#div `$\{x}` <\/script> & #a[title="a<b&c"]:link /div"#);
+    }
+
+    #[test]
+    fn attribute_literals_are_escaped_in_javascript() {
+        test_render!(
+            "#p[title=\"say \\\"hi\\\"\" data-path=\"C:\\\\dir\" data-lines=\"a\nb\u{2028}c\" data-emoji=\"☃ 😀\" @click={() => {}}]:Hi"
+        );
+    }
+
+    #[test]
+    fn collapsing_can_be_turned_off() {
+        test_render!(
+            "#div text #div/div /div",
+            Ctx::default(),
+            CsrOptions {
+                no_collapse: true,
+                ..Default::default()
+            }
+        );
+    }
+
     #[test]
     fn dirty_items_are_in_conditional() {
         test_render!("---js let hello = 0; let test = 1; --- {(hello, test)} #button[@click={() => { test = 3; hello = 3; }}]:Hi");
@@ -342,6 +662,28 @@ mod tests {
         test_render!("{#for i in [1, 2, 3]} {i} {/for}");
     }
 
+    #[test]
+    fn can_render_raw_blocks() {
+        test_render!("---js let show = true; --- #div {#raw}<svg viewBox=\"0 0 10 10\">{not a mustache}</svg>{/raw} /div {#if show} {#raw}<script>\"</script>{/raw} {/if}");
+    }
+
+    #[test]
+    fn can_render_html_blocks() {
+        test_render!("---js let content = \"<b>hi</b>\"; --- #div {#html content} /div {#html \"<i>static</i>\"} #button[@click={() => content = \"\"}]:Clear");
+    }
+
+    #[test]
+    fn html_blocks_are_passed_through_the_sanitizer() {
+        test_render!(
+            "---js let content = \"<b>hi</b>\"; --- {#html content} #button[@click={() => content = \"\"}]:Clear",
+            Ctx::default(),
+            CsrOptions {
+                html_sanitizer: Some("DOMPurify.sanitize".to_owned()),
+                ..Default::default()
+            }
+        );
+    }
+
     #[test]
     fn closures_with_scoped_var_as_part_of_body_take_the_scoped_var_as_argument() {
         test_render!("{#for i in [1, 2, 3]} #button[@click={() => console.log(i)}]:Click {/for}");
@@ -364,16 +706,131 @@ mod tests {
         );
     }
 
+    #[test]
+    fn reactive_css_in_for_blocks_applies_to_block_roots() {
+        test_render!(
+            "---js let items = [\"red\", \"blue\"]; let size = 1; --- ---css p { color: {item}; font-size: {size}; } --- #button[@click={() => { items = []; size = 2; }}]:Click {#for item in items} #p:Hi {#if item} #span:Hi {/if} {/for}"
+        );
+    }
+
+    #[test]
+    fn boolean_attributes_are_toggled() {
+        test_render!(
+            "---js let busy = false; --- #button[disabled={busy} title={busy} @click={() => busy = !busy}]:Go"
+        );
+    }
+
+    #[test]
+    fn live_state_is_set_through_properties() {
+        test_render!(
+            "---js let text = \"\"; let done = false; --- #input[value={text} placeholder={text}]/input #input[type=\"checkbox\" checked={done}]/input #button[@click={() => { text = \"\"; done = false; }}]:Reset"
+        );
+    }
+
+    #[test]
+    fn void_elements_have_no_closing_tag_in_collapsed_html() {
+        test_render!("#div #p Line #br/br Next /p #hr/hr #img[src=\"a.png\"]/img /div");
+    }
+
+    #[test]
+    fn csp_mode_avoids_inner_html_and_style_attributes() {
+        test_render!(
+            "---js let size = 1; --- #div[style=\"color: red\"] text #b:bold /div #p[style={`font-size: ${size}em`}]:Hi",
+            Ctx::default(),
+            CsrOptions {
+                csp: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn csp_mode_adopts_the_css_of_custom_elements() {
+        test_render!(
+            "---css p { color: red; } --- #p:Hi",
+            Ctx::default(),
+            CsrOptions {
+                custom_element: true,
+                csp: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn can_render_custom_elements() {
+        test_render!(
+            "---js let firstName = \"\"; let count = 0; --- ---css p { color: red; } --- #input[:firstName:]/input #p Hi {firstName} /p #button[@click={() => { count = count + 1; }}] {count} /button",
+            Ctx::default(),
+            CsrOptions {
+                modularize: false,
+                format: OutputFormat::Esm,
+                bundle: false,
+                custom_element: true,
+                ..Default::default()
+            }
+        );
+    }
+
     #[test]
     fn can_render_bindings() {
         test_render!("---js let x = 0; --- #input[:x:]/input");
     }
 
+    #[test]
+    fn can_render_bindings_to_fields() {
+        test_render!("---js const user = { name: \"\" }; --- #input[:user.name:]/input #p[contenteditable :text:user.name:]/p");
+    }
+
+    #[test]
+    fn can_render_bindings_to_items_of_for_blocks() {
+        test_render!("---js let todos = [{ text: \"\" }]; let tags = [\"\"]; --- {#for todo in todos} #input[:todo.text:]/input {/for} {#for tag in tags} #input[:tag:]/input {/for}");
+    }
+
+    #[test]
+    fn delegates_event_handlers_of_for_block_items() {
+        test_render!("---js let items = [1, 2]; let selected = 0; --- {#for item in items} #li[@click={() => selected = item} @focus={() => console.log(\"focus\")}] #button[@click|stopPropagation={() => console.log(item)}] {item} /button {#if item === selected} #span[@click={() => selected = 0}] x /span {/if} /li {/for}");
+    }
+
+    #[test]
+    fn splits_template_literal_mustaches_into_text_nodes() {
+        test_render!("---js let name = \"\"; let count = 0; let tag = (s) => s; --- #p {`Hi ${name}, you have\\t${count} messages`} /p #p {tag`${count}!`} /p #button[@click={() => { name = \"Jo\"; count += 1; }}] + /button");
+    }
+
+    #[test]
+    fn can_render_event_modifiers() {
+        test_render!("#a[@click|preventDefault|stopPropagation={() => {}}]/a");
+    }
+
+    #[test]
+    fn passes_bound_values_to_form_submit_handlers() {
+        test_render!("---js let name = \"\"; let age = 0; let submit = (values) => console.log(values); --- #form[@submit|preventDefault={submit}] #input[:name:]/input #div #input[:age:]/input /div /form");
+    }
+
+    #[test]
+    fn can_render_content_bindings() {
+        test_render!("---js let x = \"\"; --- #div[contenteditable :html:x:]/div #p[contenteditable :text:x:]/p");
+    }
+
     #[test]
     fn can_render_reactive_blocks() {
         test_render!("---js let x = 0; let y = 0; $: y = x + 1; --- #input[:x:]/input");
     }
 
+    #[test]
+    fn can_track_more_than_64_variables() {
+        let decls = (0..70).map(|i| format!("let v{i} = 0;")).join(" ");
+        let assigns = (0..70).map(|i| format!("v{i} = 1;")).join(" ");
+        let sum = (0..70).map(|i| format!("v{i}")).join(" + ");
+        let input = format!("---js {decls} --- #button[@click={{() => {{ {assigns} }}}}] {{v0}} {{v31}} {{v32}} {{v64}} {{{sum}}} /button");
+        test_render!(&input);
+    }
+
+    #[test]
+    fn reactive_blocks_can_declare_derived_vars() {
+        test_render!("---js let count = 0; let unused = 1; $: doubled = count * 2; $: quadrupled = doubled * 2; --- #button[@click={() => count += 1}] {doubled} {quadrupled} /button");
+    }
+
     #[test]
     fn can_render_modularize() {
         let src = "---js let x = 0; --- #p {x} /p";
@@ -388,8 +845,15 @@ mod tests {
                     src
                 }),
                 index_html: None,
+                linker: None,
             },
-            CsrOptions { modularize: true }
+            CsrOptions {
+                modularize: true,
+                format: OutputFormat::Esm,
+                bundle: false,
+                custom_element: false,
+                ..Default::default()
+            }
         );
     }
 
@@ -398,8 +862,173 @@ mod tests {
         test_render!("{#use \"./hello.decor\"} #p:Hello #hello /hello");
     }
 
+    #[test]
+    fn can_bundle_uses() {
+        test_render!(
+            "{#use \"./hello.decor\"} #p:Hello #hello /hello",
+            Ctx::default(),
+            CsrOptions {
+                modularize: false,
+                format: OutputFormat::Esm,
+                bundle: true,
+                custom_element: false,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn can_render_iife_format() {
+        test_render!(
+            "{#use \"./hello.decor\"} ---js let x = 0; --- #p {x} /p #hello /hello",
+            Ctx::default(),
+            CsrOptions {
+                modularize: true,
+                format: OutputFormat::Iife,
+                bundle: false,
+                custom_element: false,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn iife_format_denies_imports() {
+        let input = "---js import { x } from \"./x.js\"; --- #p {x} /p";
+        let errs = decorous_errors::stderr(Source {
+            src: input,
+            name: "TEST".to_owned(),
+        });
+        let ctx = decorous_frontend::Ctx {
+            errs: errs.clone(),
+            component_ids: &FixedId(0),
+            ..Default::default()
+        };
+        let mut component = Component::new(Parser::new(input).parse().unwrap(), ctx);
+        component.run_passes().unwrap();
+        let mut renderer = CsrRenderer::new();
+        renderer.with_options(CsrOptions {
+            modularize: true,
+            format: OutputFormat::Iife,
+            ..Default::default()
+        });
+        let ctx = Ctx {
+            errs,
+            ..Default::default()
+        };
+        assert!(renderer
+            .render(&component, &mut TestOut::default(), &ctx)
+            .is_err());
+    }
+
+    #[test]
+    fn can_render_toplevel_await() {
+        test_render!("---js let data = await fetch(\"/data\"); --- #p {data} /p #button[@click={() => data = 1}]:Reset");
+        test_render!(
+            "---js let data = await fetch(\"/data\"); --- #p {data} /p #button[@click={() => data = 1}]:Reset",
+            Ctx::default(),
+            CsrOptions {
+                modularize: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn can_render_cjs_format() {
+        test_render!(
+            "{#use \"./hello.decor\"} ---js let x = 0; --- #p {x} /p #hello /hello",
+            Ctx::default(),
+            CsrOptions {
+                modularize: true,
+                format: OutputFormat::Cjs,
+                bundle: false,
+                custom_element: false,
+                ..Default::default()
+            }
+        );
+    }
+
     #[test]
     fn dashes_in_use_block_are_turned_into_underscores() {
         test_render!("{#use \"./hello-world.decor\"} #hello-world /hello-world");
     }
+
+    #[test]
+    fn can_render_dynamic_tags() {
+        test_render!("---js let heading = true; --- #{heading ? \"h1\" : \"h2\"}[class=\"title\"]:Hello #button[@click={() => heading = !heading}]:Toggle");
+    }
+
+    #[test]
+    fn dynamic_tags_in_for_blocks_follow_the_item() {
+        test_render!("---js let tags = [\"h1\", \"h2\"]; --- {#for tag in tags} #{tag}[class=\"x\"] Hi /{tag} {/for} #button[@click={() => tags = [\"p\"]}]:Change");
+    }
+
+    #[test]
+    fn can_render_through_dyn_backend() {
+        let input = "---js let x = 0; --- #button[@click={() => x += 1}] {x} /button";
+        let mut component = Component::new(
+            Parser::new(input).parse().unwrap(),
+            decorous_frontend::Ctx::default(),
+        );
+        component.run_passes().unwrap();
+
+        let mut direct = TestOut::default();
+        CsrRenderer::new()
+            .render(&component, &mut direct, &Ctx::default())
+            .unwrap();
+        let backend: Box<dyn crate::DynRenderBackend> = Box::new(CsrRenderer::new());
+        let mut dynamic = TestOut::default();
+        backend
+            .render_dyn(&component, &mut dynamic, &Ctx::default())
+            .unwrap();
+
+        assert_eq!(direct.js, dynamic.js);
+    }
+
+    #[test]
+    fn can_render_head_blocks() {
+        test_render!("---js let title = \"Home\"; --- {#head} #title {title} /title #meta[name=\"description\" content=\"A page\"]/meta {/head} #button[@click={() => title = \"Away\"}]:Leave");
+    }
+
+    #[test]
+    fn can_render_escapes_and_character_references() {
+        test_render!("---js let x = 0; --- #p Tom &amp; Jerry \\#1 \"quoted\" /p #div a&lt;b &#x1F600; \\&amp; {x} /div");
+    }
+
+    #[test]
+    fn can_render_static_blocks() {
+        test_render!("---js let x = 0; --- #main {#static} #h1 Welcome /h1 {/static} #button[@click={() => x += 1}] {x} /button /main");
+    }
+
+    #[test]
+    fn mutations_of_objects_and_arrays_schedule_updates() {
+        test_render!("---js let items = []; items.push(0); let user = { name: \"Jo\" }; let n = 0; function add() { items.push(items.length); } --- #ul {#for item in items} #li {item} /li {/for} /ul #p {user.name} {n} /p #button[@click={add}]:Add #button[@click={() => user.name = \"Al\"}]:Rename #button[@click={() => { items[0]++; n = items.length; }}]:Bump");
+    }
+
+    #[test]
+    fn increments_and_decrements_schedule_updates() {
+        test_render!("---js let count = 0; --- #p {count} /p #button[@click={() => count++}]:Up #button[@click={() => --count}]:Down");
+    }
+
+    #[test]
+    fn helpers_can_be_imported_from_the_runtime() {
+        test_render!(
+            "---js let x = 0; --- #p {x} /p #button[@click={() => x += 1}]:Add",
+            Ctx::default(),
+            CsrOptions {
+                runtime: Some("./decor-runtime.mjs".to_owned()),
+                ..Default::default()
+            }
+        );
+        test_render!(
+            "---js let x = 0; --- #p {x} /p #button[@click={() => x += 1}]:Add",
+            Ctx::default(),
+            CsrOptions {
+                format: OutputFormat::Cjs,
+                runtime: Some("./decor-runtime.cjs".to_owned()),
+                ..Default::default()
+            }
+        );
+    }
 }
@@ -1,12 +1,13 @@
 use decorous_frontend::{
     ast::{
-        Attribute, AttributeValue, CollapsedChildrenType, Element, ForBlock, IfBlock, Mustache,
-        Node, NodeType, SpecialBlock, Text, UseBlock,
+        Attribute, AttributeValue, CollapsedChildrenType, Comment, Element, ForBlock, HeadBlock,
+        HtmlBlock, IfBlock, Mustache, Node, NodeType, RawBlock, SpecialBlock, StaticBlock, Text,
+        UseBlock,
     },
     utils, Component, FragmentMetadata,
 };
-use heck::ToSnekCase;
 use itertools::Itertools;
+use rslint_parser::{ast::NameRef, SyntaxNode};
 use std::{
     borrow::Cow,
     fmt::{Display, Write},
@@ -14,20 +15,27 @@ use std::{
     str,
 };
 
-use crate::codegen_utils::{self, force_write, replace_namerefs, sort_if_testing};
+use crate::codegen_utils::{
+    self, force_write, replace_namerefs, sort_if_testing, GeneratedNames, TemplatePart,
+};
 
 macro_rules! default_mount_and_detach {
     ($state:expr, $out:expr, $meta:expr) => {
-        let id = $meta.id();
+        let name = format!("e{}", $meta.id());
+        default_mount_and_detach!($state, $out, $meta, name);
+    };
+    // Mounts the node called `$name` in place of the node of `$meta`
+    ($state:expr, $out:expr, $meta:expr, $name:expr) => {
+        let name = $name;
 
         if $state.root == $meta.parent_id() {
-            $out.write_detachln(format_args!("e{id}.parentNode.removeChild(e{id});"));
+            $out.write_detachln(format_args!("{name}.parentNode.removeChild({name});"));
         }
 
         if $meta.parent_id() == $state.root {
-            $out.write_mountln(format_args!("mount(target, e{id}, anchor);"));
+            $out.write_mountln(format_args!("mount(target, {name}, anchor);"));
         } else if let Some(parent_id) = $meta.parent_id() {
-            $out.write_mountln(format_args!("e{parent_id}.appendChild(e{id});"));
+            $out.write_mountln(format_args!("e{parent_id}.appendChild({name});"));
         } else {
             panic!("BUG: node's parent should never be None while root is Some");
         }
@@ -44,12 +52,18 @@ where
 {
     let mut output = Output::default();
 
+    state.names.add(format_args!("create_{}_block", state.name));
     render_fragment_to_out(nodes, &mut state, &mut output);
 
     write!(
         out,
         include_str!("./templates/fragment.js"),
         id = state.name,
+        helpers = if state.shared_runtime {
+            ""
+        } else {
+            include_str!("./templates/mount.js")
+        },
         decls = unsafe { str::from_utf8_unchecked(&output.decls) },
         mounts = unsafe { str::from_utf8_unchecked(&output.mounts) },
         update_body = unsafe { str::from_utf8_unchecked(&output.updates) },
@@ -62,7 +76,7 @@ fn render_fragment_to_out(
     state: &mut State<'_>,
     out: &mut Output,
 ) {
-    for (block, id) in state.component.declared_vars.all_reactive_blocks() {
+    for (block, id) in state.component.declared_vars.reactive_blocks_in_order() {
         let unbound = utils::get_unbound_refs(block);
         let dirty = codegen_utils::calc_dirty(&unbound, &state.component.declared_vars, None);
         out.write_updateln(format_args!("if ({dirty}) {{ ctx[{id}](); }}"));
@@ -84,6 +98,24 @@ pub(crate) struct State<'ast> {
     pub name: Cow<'static, str>,
     pub root: Option<u32>,
     pub uses: Vec<String>,
+    /// The values passed to the submit handler of the `#form` whose attributes are being
+    /// rendered.
+    pub form_values: Option<String>,
+    /// The function that the HTML of `{#html}` blocks is sanitized with.
+    pub html_sanitizer: Option<&'ast str>,
+    /// Whether the static children of elements are set through `innerHTML`.
+    pub collapse: bool,
+    /// Whether `style` is set through `style.cssText`, which a Content Security Policy without
+    /// `'unsafe-inline'` allows, instead of as an attribute.
+    pub csp: bool,
+    /// Whether helpers like `mount` are imported from the runtime module, instead of being
+    /// defined in every block.
+    pub shared_runtime: bool,
+    /// The `{#for}` block whose parent listens for the events of the elements being rendered,
+    /// if they are in one.
+    pub delegate: Option<u32>,
+    /// The names made up for the elements and blocks, which minifiers are free to shorten.
+    pub names: &'ast GeneratedNames,
 }
 
 #[derive(Debug, Default)]
@@ -146,10 +178,11 @@ impl Render for Text<'_> {
     type Metadata = FragmentMetadata;
 
     fn render(&self, state: &mut State, out: &mut Output, meta: &Self::Metadata) {
+        state.names.add(format_args!("e{}", meta.id()));
         out.write_declln(format_args!(
-            "const e{} = document.createTextNode(\"{}\");",
+            "const e{} = document.createTextNode({});",
             meta.id(),
-            collapse_whitespace(self.0)
+            codegen_utils::js_string_literal(&collapse_whitespace(&self.unescaped()))
         ));
 
         default_mount_and_detach!(state, out, meta);
@@ -160,6 +193,11 @@ impl Render for Mustache {
     type Metadata = FragmentMetadata;
 
     fn render(&self, state: &mut State, out: &mut Output, meta: &Self::Metadata) {
+        if let Some(parts) = codegen_utils::template_parts(&self.0) {
+            render_template_parts(&parts, state, out, meta);
+            return;
+        }
+
         let unbound = utils::get_unbound_refs(&self.0);
         let replaced = codegen_utils::replace_namerefs(
             &self.0,
@@ -170,6 +208,7 @@ impl Render for Mustache {
         let id = meta.id();
 
         // Decl
+        state.names.add(format_args!("e{id}"));
         out.write_declln(format_args!(
             "const e{id} = document.createTextNode({replaced});"
         ));
@@ -185,6 +224,50 @@ impl Render for Mustache {
     }
 }
 
+/// Renders each part of a template literal mustache to its own text node, so updates only
+/// change the substitutions whose variables are dirty.
+fn render_template_parts(
+    parts: &[TemplatePart],
+    state: &mut State,
+    out: &mut Output,
+    meta: &FragmentMetadata,
+) {
+    let id = meta.id();
+    for (i, part) in parts.iter().enumerate() {
+        let name = format!("e{id}_{i}");
+        state.names.add(&name);
+        match part {
+            TemplatePart::Static(raw) => {
+                out.write_declln(format_args!(
+                    "const {name} = document.createTextNode(`{raw}`);"
+                ));
+            }
+            TemplatePart::Dynamic(expr, unbound) => {
+                let replaced = codegen_utils::replace_namerefs(
+                    expr,
+                    unbound,
+                    &state.component.declared_vars,
+                    meta.scope(),
+                );
+                out.write_declln(format_args!(
+                    "const {name} = document.createTextNode(`${{{replaced}}}`);"
+                ));
+                let dirty = codegen_utils::calc_dirty(
+                    unbound,
+                    &state.component.declared_vars,
+                    meta.scope(),
+                );
+                if !dirty.is_empty() {
+                    out.write_updateln(format_args!(
+                        "if ({dirty}) {name}.data = `${{{replaced}}}`;"
+                    ));
+                }
+            }
+        }
+        default_mount_and_detach!(state, out, meta, name);
+    }
+}
+
 impl Render for Element<'_, FragmentMetadata> {
     type Metadata = FragmentMetadata;
 
@@ -193,6 +276,7 @@ impl Render for Element<'_, FragmentMetadata> {
 
         let js_name = self.js_valid_tag_name();
         if state.uses.iter().any(|tag| tag == js_name.as_ref()) {
+            state.names.add(format_args!("e{id}_anchor"));
             out.write_declln(format_args!(
                 "const e{id}_anchor = document.createTextNode(\"\");"
             ));
@@ -214,19 +298,27 @@ impl Render for Element<'_, FragmentMetadata> {
         }
 
         // Decl
-        out.write_declln(format_args!(
-            "const e{id} = document.createElement(\"{}\");",
-            self.tag
-        ));
-        match collapse_children(self) {
+        if let Some(tag_expr) = &self.tag_expr {
+            render_dynamic_tag(tag_expr, &self.attrs, state, out, meta);
+        } else {
+            state.names.add(format_args!("e{id}"));
+            out.write_declln(format_args!(
+                "const e{id} = document.createElement(\"{}\");",
+                self.tag
+            ));
+        }
+        match collapse_children(self, state.collapse) {
             Some(CollapsedChildrenType::Text(t)) => {
                 out.write_declln(format_args!(
-                    "e{id}.textContent = \"{}\";",
-                    collapse_whitespace(t)
+                    "e{id}.textContent = {};",
+                    codegen_utils::js_string_literal(&collapse_whitespace(&t))
                 ));
             }
             Some(CollapsedChildrenType::Html(html)) => {
-                out.write_declln(format_args!("e{id}.innerHTML = `{html}`;"));
+                out.write_declln(format_args!(
+                    "e{id}.innerHTML = {};",
+                    codegen_utils::js_string_literal(&html)
+                ));
             }
             None => {
                 for child in &self.children {
@@ -234,11 +326,30 @@ impl Render for Element<'_, FragmentMetadata> {
                 }
             }
         }
-        for attr in &self.attrs {
-            attr.render(state, out, meta);
+        if !self.is_dynamic() {
+            if self.tag == "form" {
+                let values = codegen_utils::bound_vars(&self.children)
+                    .into_iter()
+                    .filter_map(|var| {
+                        let idx = state.component.declared_vars.get_var(var, None)?;
+                        Some(format!("{var}: ctx[{idx}]"))
+                    })
+                    .join(", ");
+                state.form_values = Some(format!("{{{values}}}"));
+            }
+            for attr in &self.attrs {
+                attr.render(state, out, meta);
+            }
+            state.form_values = None;
         }
 
         default_mount_and_detach!(state, out, meta);
+
+        // The roots of each item of a {#for} block hold the CSS custom properties that use the
+        // bindings of the block
+        if state.root.is_some() && meta.parent_id() == state.root && meta.scope().is_some() {
+            render_css_props(state, out, &format!("e{id}"), meta.scope());
+        }
     }
 }
 
@@ -250,6 +361,136 @@ impl Render for SpecialBlock<'_, FragmentMetadata> {
             Self::If(if_block) => if_block.render(state, out, meta),
             Self::For(for_block) => for_block.render(state, out, meta),
             Self::Use(use_block) => use_block.render(state, out, meta),
+            Self::Raw(raw_block) => raw_block.render(state, out, meta),
+            Self::Html(html_block) => html_block.render(state, out, meta),
+            Self::Head(head_block) => head_block.render(state, out, meta),
+            Self::Static(static_block) => static_block.render(state, out, meta),
+            Self::Cfg(_) => unreachable!("cfg blocks are removed by the frontend"),
+            Self::Translate(_) => unreachable!("translations are replaced by the frontend"),
+        }
+    }
+}
+
+impl Render for HtmlBlock {
+    type Metadata = FragmentMetadata;
+
+    fn render(&self, state: &mut State, out: &mut Output, meta: &Self::Metadata) {
+        let id = meta.id();
+        let unbound = utils::get_unbound_refs(&self.expr);
+        let replaced = codegen_utils::replace_namerefs(
+            &self.expr,
+            &unbound,
+            &state.component.declared_vars,
+            meta.scope(),
+        );
+        let html = match state.html_sanitizer {
+            Some(sanitizer) => format!("{sanitizer}({replaced})"),
+            None => replaced,
+        };
+
+        // Decl
+        for name in ["anchor", "nodes", "set"] {
+            state.names.add(format_args!("e{id}_{name}"));
+        }
+        out.write_declln(format_args!(
+            "const e{id}_anchor = document.createTextNode(\"\");\nlet e{id}_nodes = [];\nfunction e{id}_set(html) {{ for (const node of e{id}_nodes) node.parentNode.removeChild(node); const template = document.createElement(\"template\"); template.innerHTML = html; e{id}_nodes = Array.from(template.content.childNodes); for (const node of e{id}_nodes) e{id}_anchor.parentNode.insertBefore(node, e{id}_anchor); }}"
+        ));
+
+        // Mount
+        if meta.parent_id() == state.root {
+            out.write_mountln(format_args!("mount(target, e{id}_anchor, anchor);"));
+        } else if let Some(parent_id) = meta.parent_id() {
+            out.write_mountln(format_args!("e{parent_id}.appendChild(e{id}_anchor);"));
+        }
+        out.write_mountln(format_args!("e{id}_set({html});"));
+
+        // Update
+        let dirty =
+            codegen_utils::calc_dirty(&unbound, &state.component.declared_vars, meta.scope());
+        if !dirty.is_empty() {
+            out.write_updateln(format_args!("if ({dirty}) e{id}_set({html});"));
+        }
+
+        // Detach
+        if state.root == meta.parent_id() {
+            out.write_detachln(format_args!(
+                "for (const node of e{id}_nodes) node.parentNode.removeChild(node);\ne{id}_anchor.parentNode.removeChild(e{id}_anchor);"
+            ));
+        }
+    }
+}
+
+impl Render for HeadBlock<'_, FragmentMetadata> {
+    type Metadata = FragmentMetadata;
+
+    fn render(&self, state: &mut State, out: &mut Output, meta: &Self::Metadata) {
+        let id = meta.id();
+        let _ = render_fragment(
+            &self.inner,
+            State {
+                name: format!("{id}_head").into(),
+                root: Some(id),
+                uses: vec![],
+                form_values: None,
+                // The contents are mounted in `document.head`, outside of any `{#for}` block
+                delegate: None,
+                ..*state
+            },
+            out,
+        );
+
+        // The contents aren't mounted where the block is, so they're always detached
+        state.names.add(format_args!("e{id}"));
+        out.write_mountln(format_args!(
+            "const e{id} = create_{id}_head_block(document.head);"
+        ));
+        out.write_updateln(format_args!("e{id}.u(dirty);"));
+        out.write_detachln(format_args!("e{id}.d();"));
+    }
+}
+
+impl Render for StaticBlock<'_, FragmentMetadata> {
+    type Metadata = FragmentMetadata;
+
+    // Without prerendering, the contents are created like any other nodes. They're in the parent
+    // of the block, so they're mounted in its place.
+    fn render(&self, state: &mut State, out: &mut Output, _meta: &Self::Metadata) {
+        for child in &self.inner {
+            child.render(state, out, &());
+        }
+    }
+}
+
+impl Render for RawBlock<'_> {
+    type Metadata = FragmentMetadata;
+
+    fn render(&self, state: &mut State, out: &mut Output, meta: &Self::Metadata) {
+        let id = meta.id();
+
+        // Decl
+        state.names.add(format_args!("e{id}"));
+        state.names.add(format_args!("e{id}_nodes"));
+        out.write_declln(format_args!(
+            "const e{id} = document.createElement(\"template\");\ne{id}.innerHTML = {};\nconst e{id}_nodes = Array.from(e{id}.content.childNodes);",
+            codegen_utils::js_string_literal(self.html)
+        ));
+
+        // Mount
+        if meta.parent_id() == state.root {
+            out.write_mountln(format_args!(
+                "for (const node of e{id}_nodes) mount(target, node, anchor);"
+            ));
+        } else if let Some(parent_id) = meta.parent_id() {
+            out.write_mountln(format_args!(
+                "for (const node of e{id}_nodes) e{parent_id}.appendChild(node);"
+            ));
+        }
+
+        // Detach
+        if state.root == meta.parent_id() {
+            out.write_detachln(format_args!(
+                "for (const node of e{id}_nodes) node.parentNode.removeChild(node);"
+            ));
         }
     }
 }
@@ -266,6 +507,8 @@ impl Render for ForBlock<'_, FragmentMetadata> {
                 name: id.to_string().into(),
                 root: Some(id),
                 uses: vec![],
+                form_values: None,
+                delegate: Some(id),
                 ..*state
             },
             out,
@@ -273,9 +516,23 @@ impl Render for ForBlock<'_, FragmentMetadata> {
         .expect("write to memory should not fail");
 
         // Decl
+        state.names.add(format_args!("e{id}_anchor"));
         out.write_declln(format_args!(
             "const e{id}_anchor = document.createTextNode(\"\");"
         ));
+        // The handlers of the items are called by a listener on the parent of the block
+        let delegated = codegen_utils::delegated_events(&self.inner);
+        for (i, event) in delegated.iter().enumerate() {
+            state.names.add(format_args!("e{id}_delegate{i}"));
+            out.write_declln(format_args!(
+                "const e{id}_delegate{i} = {};",
+                codegen_utils::delegated_listener(&codegen_utils::delegation_key(
+                    state.component,
+                    id,
+                    event
+                ))
+            ));
+        }
 
         // Mount
         let unbound = utils::get_unbound_refs(&self.expr);
@@ -285,21 +542,31 @@ impl Render for ForBlock<'_, FragmentMetadata> {
             &state.component.declared_vars,
             meta.scope(),
         );
-        let var_idx = state
-            .component
-            .declared_vars
-            .all_scopes()
-            .get(&id)
-            .unwrap()
-            .get(self.binding)
-            .unwrap();
+        let scope = state.component.declared_vars.all_scopes().get(&id).unwrap();
+        let var_idx = scope.get(self.binding).unwrap();
+        // The index of each item is only kept when an item is bound to
+        let set_index = scope
+            .index()
+            .map(|index_idx| format!(" ctx[{index_idx}] = i;"))
+            .unwrap_or_default();
         out.write_mountln(format_args!("mount(target, e{id}_anchor, anchor);"));
-        out.write_mountln(format_args!("let e{id}_blocks = [];\nlet i = 0;\nfor (const v of ({expr})) {{ ctx[{var_idx}] = v; e{id}_blocks[i] = create_{id}_block(e{id}_anchor.parentNode, e{id}_anchor); i += 1; }}"));
+        for (i, event) in delegated.iter().enumerate() {
+            out.write_mountln(format_args!(
+                "e{id}_anchor.parentNode.addEventListener(\"{event}\", e{id}_delegate{i});"
+            ));
+        }
+        state.names.add(format_args!("e{id}_blocks"));
+        out.write_mountln(format_args!("let e{id}_blocks = [];\nlet i = 0;\nfor (const v of ({expr})) {{ ctx[{var_idx}] = v;{set_index} e{id}_blocks[i] = create_{id}_block(e{id}_anchor.parentNode, e{id}_anchor); i += 1; }}"));
 
         // Update
-        out.write_updateln(format_args!("let i = 0; for (const v of ({expr})) {{ if (i >= e{id}_blocks.length) {{ e{id}_blocks[i] = create_{id}_block(e{id}_anchor.parentNode, e{id}_anchor) }}; ctx[{var_idx}] = v; e{id}_blocks[i].u(dirty); i += 1; }} e{id}_blocks.slice(i).forEach(b => b.d()); e{id}_blocks.length = i;"));
+        out.write_updateln(format_args!("let i = 0; for (const v of ({expr})) {{ if (i >= e{id}_blocks.length) {{ e{id}_blocks[i] = create_{id}_block(e{id}_anchor.parentNode, e{id}_anchor) }}; ctx[{var_idx}] = v;{set_index} e{id}_blocks[i].u(dirty); i += 1; }} e{id}_blocks.slice(i).forEach(b => b.d()); e{id}_blocks.length = i;"));
 
         // Detach
+        for (i, event) in delegated.iter().enumerate() {
+            out.write_detachln(format_args!(
+                "e{id}_anchor.parentNode.removeEventListener(\"{event}\", e{id}_delegate{i});"
+            ));
+        }
         out.write_detachln(format_args!("for (let i = 0; i < e{id}_blocks.length; i++) {{ e{id}_blocks[i].d() }}\ne{id}_anchor.parentNode.removeChild(e{id}_anchor);"));
     }
 }
@@ -308,11 +575,23 @@ impl Render for UseBlock<'_> {
     type Metadata = FragmentMetadata;
 
     fn render(&self, state: &mut State, _out: &mut Output, _meta: &Self::Metadata) {
-        let Some(name) = self.path.file_stem() else {
+        // Globs were expanded into the uses of the component
+        if self.is_glob() {
+            let matched = state
+                .component
+                .uses
+                .iter()
+                .filter(|used| self.matches(&used.path))
+                .filter_map(|used| used.name().map(Cow::into_owned))
+                .collect_vec();
+            state.uses.extend(matched);
+            return;
+        }
+        let Some(name) = self.name() else {
             return;
         };
 
-        state.uses.push(name.to_string_lossy().to_snek_case());
+        state.uses.push(name.into_owned());
     }
 }
 
@@ -335,6 +614,7 @@ impl Render for IfBlock<'_, FragmentMetadata> {
                 name: id.to_string().into(),
                 root: Some(id),
                 uses: vec![],
+                form_values: None,
                 ..*state
             },
             out,
@@ -346,6 +626,7 @@ impl Render for IfBlock<'_, FragmentMetadata> {
                     name: format!("{id}_else").into(),
                     root: Some(id),
                     uses: vec![],
+                    form_values: None,
                     ..*state
                 },
                 out,
@@ -353,6 +634,8 @@ impl Render for IfBlock<'_, FragmentMetadata> {
         }
 
         // Decl
+        state.names.add(format_args!("e{id}"));
+        state.names.add(format_args!("e{id}_anchor"));
         out.write_declln(format_args!(
             "const e{id}_anchor = document.createTextNode(\"\");"
         ));
@@ -361,6 +644,7 @@ impl Render for IfBlock<'_, FragmentMetadata> {
         out.write_mountln(format_args!("mount(target, e{id}_anchor, anchor);"));
 
         if self.else_block.is_some() {
+            state.names.add(format_args!("e{id}_on"));
             out.write_mountln(format_args!("let e{id};\nlet e{id}_on = false;\nif ({replacement}) {{ e{id} = create_{id}_block(e{id}_anchor.parentNode, e{id}_anchor); e{id}_on = true; }} else {{ e{id} = create_{id}_else_block(e{id}_anchor.parentNode, e{id}_anchor); }}"));
             out.write_updateln(format_args!("if ({replacement}) {{ if (e{id} && e{id}_on) {{ e{id}.u(dirty); }} else {{ e{id}_on = true; e{id}.d(); e{id} = create_{id}_block(e{id}_anchor.parentNode, e{id}_anchor); }} }} else if (e{id}_on) {{ e{id}_on = false; e{id}.d(); e{id} = create_{id}_else_block(e{id}_anchor.parentNode, e{id}_anchor); }}"));
         } else {
@@ -390,8 +674,12 @@ impl Render for Attribute<'_> {
                     &state.component.declared_vars,
                     meta.scope(),
                 );
-                out.write_declln(format_args!(
-                    "e{id}.setAttribute(\"{key}\", {replacement});"
+                let elem = format!("e{id}");
+                out.write_declln(codegen_utils::set_attribute(
+                    &elem,
+                    key,
+                    &replacement,
+                    state.csp,
                 ));
                 let dirty = codegen_utils::calc_dirty(
                     &unbound,
@@ -399,17 +687,20 @@ impl Render for Attribute<'_> {
                     meta.scope(),
                 );
                 out.write_updateln(format_args!(
-                    "if ({dirty}) e{id}.setAttribute(\"{key}\", {replacement});"
+                    "if ({dirty}) {}",
+                    codegen_utils::set_attribute(&elem, key, &replacement, state.csp)
                 ));
             }
             Self::KeyValue(key, None) => {
                 out.write_declln(format_args!("e{id}.setAttribute(\"{key}\", \"\")"));
             }
             Self::KeyValue(key, Some(AttributeValue::Literal(literal))) => {
-                out.write_declln(format_args!(
-                    "e{id}.setAttribute(\"{key}\", \"{}\")",
-                    collapse_whitespace(literal)
-                ));
+                let value = codegen_utils::js_string_literal(&collapse_whitespace(literal));
+                out.write_declln(if state.csp && *key == "style" {
+                    codegen_utils::set_attribute(&format!("e{id}"), key, &value, true)
+                } else {
+                    format!("e{id}.setAttribute(\"{key}\", {value})")
+                });
             }
 
             Self::EventHandler(event_handler) => {
@@ -441,6 +732,47 @@ impl Render for Attribute<'_> {
                     })
                     .collect_vec();
 
+                let form_values = state
+                    .form_values
+                    .as_ref()
+                    .filter(|_| event_handler.event == "submit");
+                // In a {#for} block, the handler is stored on the element, along with the values
+                // of the block it uses, for the block's listener to call
+                if let Some(for_id) = state
+                    .delegate
+                    .filter(|_| form_values.is_none() && codegen_utils::can_delegate(event_handler))
+                {
+                    let args = scope_args
+                        .iter()
+                        .map(|arg_idx| format!(", ctx[{arg_idx}]"))
+                        .join("");
+                    out.write_declln(format_args!(
+                        "e{id}[{}] = [{replaced}{args}];",
+                        codegen_utils::delegation_key(state.component, for_id, event_handler.event)
+                    ));
+
+                    return;
+                }
+                if !event_handler.modifiers.is_empty() || form_values.is_some() {
+                    let mut args = vec![];
+                    for (i, arg_idx) in scope_args.iter().enumerate() {
+                        out.write_declln(format_args!("const arg{i} = ctx[{arg_idx}];"));
+                        args.push(format!("arg{i}"));
+                    }
+                    args.extend(form_values.cloned());
+                    out.write_declln(format_args!(
+                        "e{id}.addEventListener(\"{}\", {});",
+                        event_handler.event,
+                        codegen_utils::wrap_event_handler(
+                            &replaced,
+                            &event_handler.modifiers,
+                            &args
+                        )
+                    ));
+
+                    return;
+                }
+
                 // In the case scope_args is empty, attach the event handler as normal
                 if scope_args.is_empty() {
                     out.write_declln(format_args!(
@@ -461,103 +793,285 @@ impl Render for Attribute<'_> {
             }
 
             Self::Binding(binding) => {
-                state
-                    .component
-                    .declared_vars
-                    .get_var(*binding, None)
-                    .map_or_else(
-                        || todo!("unbound var lint"),
-                        |var_id| {
-                            out.write_declln(format_args!("e{id}.value = ctx[{var_id}];"));
-
-                            let dirty_idx = ((var_id + 7) / 8).saturating_sub(1) as usize;
-                            let bitmask = 1 << (var_id % 8);
-                            out.write_updateln(format_args!(
-                                "if (dirty[{dirty_idx}] & {bitmask}) e{id}.value = ctx[{var_id}];"
-                            ));
-                        },
-                    );
-                let binding_idx = state
-                    .component
-                    .declared_vars
-                    .get_binding(*binding)
-                    .expect("BUG: every binding should have a entry in declared vars");
+                let prop = binding.kind.property();
+                let declared = &state.component.declared_vars;
+                // The rest of the binding after its variable, like `.name` in `user.name`
+                let field = &binding.var[binding.root().len()..];
+                let row = meta
+                    .scope()
+                    .and_then(|scope| declared.all_scopes().get(&scope))
+                    .filter(|scope| scope.item() == binding.root());
+                let (var_id, value, listener) = if let Some(scope) = row {
+                    // Each item is rendered with the same `ctx`, so the item of this element is
+                    // kept for its listener
+                    let item_idx = scope
+                        .get(scope.item())
+                        .expect("BUG: the item of a {#for} block should be in its scope");
+                    let source_idx = scope
+                        .source()
+                        .and_then(|source| declared.get_var(source, None))
+                        .expect("BUG: bindings to items of undeclared variables should be errors");
+                    state.names.add(format_args!("e{id}_row"));
+                    out.write_declln(format_args!("let e{id}_row = ctx[{item_idx}];"));
+                    out.write_updateln(format_args!("e{id}_row = ctx[{item_idx}];"));
+                    let target = match scope.index() {
+                        Some(index_idx) if !binding.is_field() => {
+                            state.names.add(format_args!("e{id}_index"));
+                            out.write_declln(format_args!("let e{id}_index = ctx[{index_idx}];"));
+                            out.write_updateln(format_args!("e{id}_index = ctx[{index_idx}];"));
+                            format!("ctx[{source_idx}][e{id}_index]")
+                        }
+                        _ => format!("e{id}_row{field}"),
+                    };
+                    state.names.add(format_args!("e{id}_binding"));
+                    out.write_declln(format_args!(
+                        "const e{id}_binding = {};",
+                        codegen_utils::binding_closure(
+                            &format!("ctx[{source_idx}]"),
+                            &target,
+                            binding.kind,
+                            source_idx
+                        )
+                    ));
+                    (
+                        source_idx,
+                        format!("e{id}_row{field}"),
+                        format!("e{id}_binding"),
+                    )
+                } else {
+                    let var_id = declared
+                        .get_var(binding.root(), None)
+                        .expect("BUG: bindings to undeclared variables should be errors");
+                    let binding_idx = declared
+                        .get_binding(binding.var, binding.kind)
+                        .expect("BUG: every binding should have a entry in declared vars");
+                    (
+                        var_id,
+                        format!("ctx[{var_id}]{field}"),
+                        format!("ctx[{binding_idx}]"),
+                    )
+                };
+
+                out.write_declln(format_args!("e{id}.{prop} = {value};"));
+                let (dirty_idx, bitmask) = codegen_utils::dirty_bit(var_id);
+                if binding.kind.is_content() {
+                    // Resetting the content of the element being edited would move the caret, so
+                    // it is only written when it has actually changed
+                    out.write_updateln(format_args!(
+                        "if (dirty[{dirty_idx}] & {bitmask} && e{id}.{prop} !== {value}) e{id}.{prop} = {value};"
+                    ));
+                } else {
+                    out.write_updateln(format_args!(
+                        "if (dirty[{dirty_idx}] & {bitmask}) e{id}.{prop} = {value};"
+                    ));
+                }
                 out.write_declln(format_args!(
-                    "e{id}.addEventListener(\"input\", ctx[{binding_idx}]);"
+                    "e{id}.addEventListener(\"input\", {listener});"
                 ));
+                if binding.kind.is_content() {
+                    out.write_declln(format_args!(
+                        "e{id}.addEventListener(\"compositionend\", {listener});"
+                    ));
+                }
             }
         }
     }
 }
 
+/// Renders the declaration of an element with a dynamic tag name. Its attributes are set in a
+/// separate function, so they can be reapplied when the element is re-created with a new tag.
+fn render_dynamic_tag(
+    tag_expr: &SyntaxNode,
+    attrs: &[Attribute],
+    state: &mut State,
+    out: &mut Output,
+    meta: &FragmentMetadata,
+) {
+    let id = meta.id();
+    let unbound = utils::get_unbound_refs(tag_expr);
+    let replaced = codegen_utils::replace_namerefs(
+        tag_expr,
+        &unbound,
+        &state.component.declared_vars,
+        meta.scope(),
+    );
+
+    let mut attrs_out = Output::default();
+    for attr in attrs {
+        attr.render(state, &mut attrs_out, meta);
+    }
+    state.names.add(format_args!("e{id}"));
+    state.names.add(format_args!("e{id}_attrs"));
+    out.write_declln(format_args!(
+        "let e{id} = document.createElement({replaced});\nfunction e{id}_attrs() {{\n{}}}\ne{id}_attrs();",
+        unsafe { str::from_utf8_unchecked(&attrs_out.decls) }
+    ));
+    out.updates.extend_from_slice(&attrs_out.updates);
+
+    // The element is only re-created if the tag name it evaluates to is different
+    let recreate = format!("const tag = String({replaced}).toLowerCase(); if (e{id}.localName !== tag) {{ const old = e{id}; e{id} = document.createElement(tag); e{id}_attrs(); while (old.firstChild) e{id}.appendChild(old.firstChild); old.replaceWith(e{id}); }}");
+    let dirty = codegen_utils::calc_dirty(&unbound, &state.component.declared_vars, meta.scope());
+    if uses_scope_vars(state, &unbound, meta.scope()) {
+        out.write_updateln(format_args!("{{ {recreate} }}"));
+    } else if !dirty.is_empty() {
+        out.write_updateln(format_args!("if ({dirty}) {{ {recreate} }}"));
+    }
+}
+
+/// Checks if any of `unbound` are bindings of a `{#for}` block in `scope`. They are never dirty,
+/// but the item they hold can still change when the block is updated.
+fn uses_scope_vars(state: &State, unbound: &[NameRef], scope: Option<u32>) -> bool {
+    scope.is_some_and(|scope| {
+        unbound.iter().any(|nref| {
+            nref.ident_token().is_some_and(|tok| {
+                state
+                    .component
+                    .declared_vars
+                    .is_scope_var(tok.text(), scope)
+            })
+        })
+    })
+}
+
 fn render_reactive_css(state: &mut State, output: &mut Output) {
-    // No reactive CSS
-    if state.component.declared_vars.css_mustaches().is_empty() {
+    // The custom properties are set one by one, so that the rest of the target's inline styles
+    // are left alone, and so that exactly those properties can be removed on detach
+    let Some(names) = render_css_props(state, output, "target", None) else {
         return;
+    };
+    for name in names {
+        output.write_detachln(format_args!("target.style.removeProperty(\"{name}\");"));
     }
+}
 
+/// Renders the code that sets the custom properties of the CSS mustaches in `scope` on `elem`,
+/// and keeps them up to date. Returns the names of the properties, or `None` if there are none.
+fn render_css_props(
+    state: &State,
+    output: &mut Output,
+    elem: &str,
+    scope: Option<u32>,
+) -> Option<Vec<String>> {
     let mut all_unbound = vec![];
-    let mut final_attr = "`".to_owned();
+    let mut set_props = String::new();
+    let mut names = vec![];
     for (mustache, id) in sort_if_testing!(
-        state.component.declared_vars.css_mustaches().iter(),
+        state.component.declared_vars.css_mustaches_in_scope(scope),
         |a, b| a.1.cmp(b.1)
     ) {
         let unbound = utils::get_unbound_refs(mustache);
         let replacement =
-            replace_namerefs(mustache, &unbound, &state.component.declared_vars, None);
+            replace_namerefs(mustache, &unbound, &state.component.declared_vars, scope);
         all_unbound.extend(unbound);
-        force_write!(final_attr, "--decor-{}: ${{{}}}; ", id, replacement);
+        let name = state.component.css_var_name(*id);
+        force_write!(
+            set_props,
+            "{elem}.style.setProperty(\"{name}\", `${{{replacement}}}`); "
+        );
+        names.push(name);
     }
-    final_attr.push('`');
-    let all_dirty = codegen_utils::calc_dirty(&all_unbound, &state.component.declared_vars, None);
-    output.write_updateln(format_args!(
-        "if ({all_dirty}) target.setAttribute(\"style\", {final_attr});"
-    ));
-    output.write_mountln(format_args!(
-        "target.setAttribute(\"style\", {final_attr});"
-    ));
+    // No reactive CSS
+    if names.is_empty() {
+        return None;
+    }
+
+    let set_props = set_props.trim_end();
+    let uses_bindings = uses_scope_vars(state, &all_unbound, scope);
+    let all_dirty = codegen_utils::calc_dirty(&all_unbound, &state.component.declared_vars, scope);
+    if uses_bindings {
+        output.write_updateln(set_props);
+    } else if !all_dirty.is_empty() {
+        output.write_updateln(format_args!("if ({all_dirty}) {{ {set_props} }}"));
+    }
+    output.write_mountln(set_props);
+    Some(names)
 }
 
-fn collapse_whitespace(s: &str) -> Cow<str> {
+fn collapse_whitespace(s: &str) -> Cow<'_, str> {
     match s {
         "\n" | "\r\n" => Cow::Borrowed(" "),
-        s if s.contains('\n') || s.contains("\r\n") => {
-            let mut joined = String::with_capacity(s.len());
-            // We use .lines() to also account for \r\n
-            for line in s.lines() {
-                force_write!(joined, "{line}\\n");
-            }
-
-            joined.into()
-        }
+        s if s.contains("\r\n") => s.replace("\r\n", "\n").into(),
         s => Cow::Borrowed(s),
     }
 }
 
 fn collapse_children<'a>(
     elem: &'a Element<'a, FragmentMetadata>,
+    collapse_html: bool,
 ) -> Option<CollapsedChildrenType<'a>> {
     if elem.children.len() == 1 {
         if let NodeType::Text(t) = elem.children.first().unwrap().node_type {
-            return Some(CollapsedChildrenType::Text(&t));
+            return Some(CollapsedChildrenType::Text(t.unescaped()));
         }
     }
-    if !elem.children.is_empty()
+    if collapse_html
+        && !elem.children.is_empty()
         && elem.descendents().all(|node| match &node.node_type {
             NodeType::Text(_) | NodeType::Comment(_) => true,
             // For elements, check if any attributes have mustache tags
-            NodeType::Element(elem) => elem.attrs.iter().all(|attr| match attr {
-                Attribute::KeyValue(_, None) => true,
-                Attribute::KeyValue(_, Some(val)) => {
-                    matches!(val, AttributeValue::Literal(_))
-                }
-                Attribute::Binding(_) | Attribute::EventHandler(_) => false,
-            }),
+            NodeType::Element(elem) => {
+                !elem.is_dynamic()
+                    && elem.attrs.iter().all(|attr| match attr {
+                        Attribute::KeyValue(_, None) => true,
+                        Attribute::KeyValue(_, Some(val)) => {
+                            matches!(val, AttributeValue::Literal(_))
+                        }
+                        Attribute::Binding(_) | Attribute::EventHandler(_) => false,
+                    })
+            }
             NodeType::Mustache(_) | NodeType::SpecialBlock(_) => false,
         })
     {
-        return Some(CollapsedChildrenType::Html(elem.children.iter().join("")));
+        let mut html = String::new();
+        write_static_html(&elem.children, elem.tag, &mut html);
+        return Some(CollapsedChildrenType::Html(html));
     }
 
     None
 }
+
+/// Writes the HTML of `nodes`, the children of `parent`, which are all text, comments and elements
+/// with literal attributes.
+fn write_static_html(nodes: &[Node<'_, FragmentMetadata>], parent: &str, html: &mut String) {
+    for node in nodes {
+        match &node.node_type {
+            NodeType::Text(t) => html.push_str(&codegen_utils::html_text(t, Some(parent))),
+            NodeType::Comment(Comment(c)) => {
+                html.push_str("<!--");
+                html.push_str(&codegen_utils::escape_comment(c));
+                html.push_str("-->");
+            }
+            NodeType::Element(elem) => {
+                html.push('<');
+                html.push_str(elem.tag);
+                for attr in &elem.attrs {
+                    match attr {
+                        Attribute::KeyValue(key, None) => {
+                            html.push(' ');
+                            html.push_str(key);
+                        }
+                        Attribute::KeyValue(key, Some(AttributeValue::Literal(literal))) => {
+                            html.push(' ');
+                            html.push_str(key);
+                            html.push_str("=\"");
+                            html.push_str(&codegen_utils::escape_html(literal));
+                            html.push('"');
+                        }
+                        _ => unreachable!("only static elements are collapsed"),
+                    }
+                }
+                html.push('>');
+                if elem.is_void() {
+                    continue;
+                }
+                write_static_html(&elem.children, elem.tag, html);
+                html.push_str("</");
+                html.push_str(elem.tag);
+                html.push('>');
+            }
+            NodeType::Mustache(_) | NodeType::SpecialBlock(_) => {
+                unreachable!("only static nodes are collapsed")
+            }
+        }
+    }
+}
@@ -1,8 +1,5 @@
 function create_{id}_block(target, anchor) {{
-function mount(target, newNode, anchor) {{
-target.insertBefore(newNode, anchor || null);
-}}
-{decls}{mounts}return {{
+{helpers}{decls}{mounts}return {{
 u(dirty) {{
 {update_body}}},
 d() {{
@@ -0,0 +1,3 @@
+function mount(target, newNode, anchor) {
+target.insertBefore(newNode, anchor || null);
+}
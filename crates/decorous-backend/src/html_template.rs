@@ -0,0 +1,287 @@
+use std::fmt::Write;
+
+use thiserror::Error;
+
+use crate::codegen_utils;
+
+/// A page that the generated index.html is rendered into, in place of the built-in one.
+///
+/// Templates are HTML with placeholders:
+/// - `{{html}}`: the markup of the component. Required.
+/// - `{{script}}`: the `<script>` tag that loads the JavaScript. Required.
+/// - `{{css}}`: the `<link>` tag that loads the CSS, if there is any.
+/// - `{{title}}`: the name of the component.
+/// - `{{head}}`: the elements of `{#head}` blocks. Without it, they go right before `</head>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Html,
+    Script,
+    Css,
+    Title,
+    Head,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TemplateError {
+    #[error("unknown placeholder {{{{{0}}}}} on line {1}")]
+    UnknownPlaceholder(String, usize),
+    #[error("unclosed placeholder on line {0}")]
+    UnclosedPlaceholder(usize),
+    #[error("the template has no {{{{{0}}}}} placeholder")]
+    Missing(&'static str),
+    #[error("the template has more than one {{{{{0}}}}} placeholder")]
+    Duplicate(&'static str),
+    #[error("the template has no {{{{head}}}} placeholder or </head> tag")]
+    NoHead,
+}
+
+/// The values of the placeholders of a template.
+#[derive(Debug)]
+pub struct TemplateValues<'a> {
+    /// The paths of the scripts to load, which get a `<script>` tag each.
+    pub scripts: &'a [String],
+    /// The paths of the stylesheets to load, which get a `<link>` tag each.
+    pub css: &'a [String],
+    pub title: &'a str,
+    pub head: &'a str,
+    /// The nonce put on each `<script>` and `<link>` tag, so they're allowed by a Content Security
+    /// Policy that requires it.
+    pub nonce: Option<&'a str>,
+}
+
+/// The page that components are rendered into when there is no template, with the same layout as
+/// the built-in `index.html`.
+const DEFAULT_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="UTF-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+    {{css}}
+  </head>
+  <body>
+    {{script}}
+    {{html}}
+  </body>
+</html>
+"#;
+
+impl Placeholder {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Script => "script",
+            Self::Css => "css",
+            Self::Title => "title",
+            Self::Head => "head",
+        }
+    }
+}
+
+impl HtmlTemplate {
+    pub fn parse(src: &str) -> Result<Self, TemplateError> {
+        let mut parts = vec![];
+        let mut rest = src;
+        while let Some(start) = rest.find("{{") {
+            let line = src[..src.len() - rest.len() + start].matches('\n').count() + 1;
+            parts.push(Part::Text(rest[..start].to_owned()));
+            let end = rest[start..]
+                .find("}}")
+                .ok_or(TemplateError::UnclosedPlaceholder(line))?;
+            let name = rest[start + 2..start + end].trim();
+            let placeholder = match name {
+                "html" => Placeholder::Html,
+                "script" => Placeholder::Script,
+                "css" => Placeholder::Css,
+                "title" => Placeholder::Title,
+                "head" => Placeholder::Head,
+                _ => return Err(TemplateError::UnknownPlaceholder(name.to_owned(), line)),
+            };
+            parts.push(Part::Placeholder(placeholder));
+            rest = &rest[start + end + 2..];
+        }
+        parts.push(Part::Text(rest.to_owned()));
+
+        let count = |placeholder| {
+            parts
+                .iter()
+                .filter(|part| **part == Part::Placeholder(placeholder))
+                .count()
+        };
+        for required in [Placeholder::Html, Placeholder::Script] {
+            match count(required) {
+                0 => return Err(TemplateError::Missing(required.name())),
+                1 => {}
+                _ => return Err(TemplateError::Duplicate(required.name())),
+            }
+        }
+        if count(Placeholder::Head) == 0 {
+            insert_head(&mut parts)?;
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Renders the template, returning what goes before and after the markup of the component.
+    pub fn render(&self, values: &TemplateValues) -> (String, String) {
+        let (mut before, mut after) = (String::new(), String::new());
+        let nonce = nonce_attr(values.nonce);
+        let mut out = &mut before;
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Placeholder(Placeholder::Html) => out = &mut after,
+                Part::Placeholder(Placeholder::Script) => {
+                    for (i, script) in values.scripts.iter().enumerate() {
+                        let sep = if i == 0 { "" } else { "\n" };
+                        let _ = write!(
+                            out,
+                            "{sep}<script type=\"module\" src=\"{script}\"{nonce}></script>"
+                        );
+                    }
+                }
+                Part::Placeholder(Placeholder::Css) => {
+                    for (i, css) in values.css.iter().enumerate() {
+                        let sep = if i == 0 { "" } else { "\n" };
+                        let _ = write!(
+                            out,
+                            "{sep}<link href=\"{css}\" rel=\"stylesheet\"{nonce} />"
+                        );
+                    }
+                }
+                Part::Placeholder(Placeholder::Title) => out.push_str(values.title),
+                Part::Placeholder(Placeholder::Head) => out.push_str(values.head),
+            }
+        }
+        (before, after)
+    }
+}
+
+impl Default for HtmlTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_PAGE).expect("default page should be a valid template")
+    }
+}
+
+/// The `nonce` attribute of the tags of a page, with the space before it, or nothing if there is
+/// no nonce.
+pub(crate) fn nonce_attr(nonce: Option<&str>) -> String {
+    nonce.map_or_else(String::new, |nonce| {
+        format!(" nonce=\"{}\"", codegen_utils::escape_html(nonce))
+    })
+}
+
+/// Puts a `{{head}}` placeholder right before `</head>`.
+fn insert_head(parts: &mut Vec<Part>) -> Result<(), TemplateError> {
+    for (i, part) in parts.iter_mut().enumerate() {
+        let Part::Text(text) = part else {
+            continue;
+        };
+        let Some(pos) = text.find("</head>") else {
+            continue;
+        };
+        let after = text.split_off(pos);
+        parts.splice(
+            i + 1..i + 1,
+            [Part::Placeholder(Placeholder::Head), Part::Text(after)],
+        );
+        return Ok(());
+    }
+    Err(TemplateError::NoHead)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_around_the_markup() {
+        let template = HtmlTemplate::parse(
+            "<head><title>{{title}}</title>{{ css }}</head><body>{{html}}{{script}}</body>",
+        )
+        .unwrap();
+        let (before, after) = template.render(&TemplateValues {
+            scripts: &["out.js".to_owned()],
+            css: &["out.css".to_owned()],
+            title: "app",
+            head: "<meta name=\"x\" />",
+            nonce: None,
+        });
+        assert_eq!(
+            "<head><title>app</title><link href=\"out.css\" rel=\"stylesheet\" /><meta name=\"x\" \
+             /></head><body>",
+            before
+        );
+        assert_eq!(
+            "<script type=\"module\" src=\"out.js\"></script></body>",
+            after
+        );
+    }
+
+    #[test]
+    fn validates_placeholders() {
+        assert_eq!(
+            Err(TemplateError::UnknownPlaceholder("body".to_owned(), 2)),
+            HtmlTemplate::parse("<head></head>\n{{body}}")
+        );
+        assert_eq!(
+            Err(TemplateError::UnclosedPlaceholder(1)),
+            HtmlTemplate::parse("</head>{{html}}{{script")
+        );
+        assert_eq!(
+            Err(TemplateError::Missing("script")),
+            HtmlTemplate::parse("</head>{{html}}")
+        );
+        assert_eq!(
+            Err(TemplateError::Duplicate("html")),
+            HtmlTemplate::parse("</head>{{html}}{{script}}{{html}}")
+        );
+        assert_eq!(
+            Err(TemplateError::NoHead),
+            HtmlTemplate::parse("{{html}}{{script}}")
+        );
+        assert!(HtmlTemplate::parse("{{head}}{{html}}{{script}}").is_ok());
+    }
+
+    #[test]
+    fn renders_a_tag_for_each_output() {
+        let (before, after) = HtmlTemplate::default().render(&TemplateValues {
+            scripts: &["out_a.js".to_owned(), "out_b.js".to_owned()],
+            css: &[],
+            title: "a",
+            head: "",
+            nonce: None,
+        });
+        assert!(!before.contains("<link"));
+        assert!(before.contains(
+            "<script type=\"module\" src=\"out_a.js\"></script>\n<script type=\"module\" \
+             src=\"out_b.js\"></script>"
+        ));
+        assert!(after.starts_with("\n  </body>"));
+    }
+
+    #[test]
+    fn puts_the_nonce_on_every_tag() {
+        let (before, _) = HtmlTemplate::default().render(&TemplateValues {
+            scripts: &["out.js".to_owned()],
+            css: &["out.css".to_owned()],
+            title: "a",
+            head: "",
+            nonce: Some("{{nonce}}"),
+        });
+        assert!(before.contains("<link href=\"out.css\" rel=\"stylesheet\" nonce=\"{{nonce}}\" />"));
+        assert!(
+            before.contains("<script type=\"module\" src=\"out.js\" nonce=\"{{nonce}}\"></script>")
+        );
+    }
+}
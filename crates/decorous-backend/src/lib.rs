@@ -1,15 +1,24 @@
+mod ascii;
 pub(crate) mod codegen_utils;
 pub mod css_render;
 pub mod dom_render;
+mod html_template;
+mod linker;
+mod minify;
 pub mod prerender;
 mod render_out;
+pub mod runtime;
 mod use_resolver;
 mod wasm_compiler;
 
 use std::io;
 
+pub use ascii::AsciiOnly;
 use decorous_errors::{DynErrStream, Source};
 use decorous_frontend::Component;
+pub use html_template::{HtmlTemplate, TemplateError, TemplateValues};
+pub use linker::{ChunkOut, Linker};
+pub use minify::{minify_js, Minified};
 pub use render_out::{JsFile, RenderOut};
 pub use use_resolver::*;
 pub use wasm_compiler::*;
@@ -33,9 +42,58 @@ pub trait RenderBackend {
     fn render<T: RenderOut>(&self, component: &Component, out: T, ctx: &Ctx) -> Result<()>;
 }
 
+/// An object safe version of [`RenderBackend`], for choosing a backend at runtime. Every
+/// [`RenderBackend`] implements it, so a configured backend can be used as a
+/// `Box<dyn DynRenderBackend>`.
+pub trait DynRenderBackend {
+    fn render_dyn(&self, component: &Component, out: &mut dyn RenderOut, ctx: &Ctx) -> Result<()>;
+}
+
+impl<B: RenderBackend> DynRenderBackend for B {
+    fn render_dyn(&self, component: &Component, out: &mut dyn RenderOut, ctx: &Ctx) -> Result<()> {
+        self.render(component, out, ctx)
+    }
+}
+
+/// The module format of the generated JavaScript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// An ES module. Used components are imported.
+    #[default]
+    Esm,
+    /// A script wrapped in an immediately invoked function, so nothing leaks into the global
+    /// scope. Used components are always inlined, and the script can't contain imports.
+    Iife,
+    /// A CommonJS module. Used components are required.
+    Cjs,
+}
+
+impl OutputFormat {
+    /// The file extension of a modularized output in this format.
+    pub fn module_extension(self) -> &'static str {
+        match self {
+            Self::Esm => "mjs",
+            Self::Iife => "js",
+            Self::Cjs => "cjs",
+        }
+    }
+
+    /// The file extension of the TypeScript declarations of a modularized output in this format.
+    /// Immediately invoked functions aren't modules, so they have none.
+    pub fn declaration_extension(self) -> Option<&'static str> {
+        match self {
+            Self::Esm => Some("d.mts"),
+            Self::Iife => None,
+            Self::Cjs => Some("d.cts"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct HtmlInfo {
     pub basename: String,
+    /// The page to render into, instead of the built-in one.
+    pub template: Option<HtmlTemplate>,
 }
 
 pub struct Ctx<'a> {
@@ -44,6 +102,8 @@ pub struct Ctx<'a> {
     pub wasm_compiler: &'a dyn WasmCompiler,
     pub use_resolver: &'a dyn UseResolver,
     pub errs: DynErrStream<'a>,
+    /// The chunks of the used components, whose CSS goes in the stylesheet of this component.
+    pub linker: Option<&'a Linker>,
 }
 
 impl Default for Ctx<'_> {
@@ -57,6 +117,7 @@ impl Default for Ctx<'_> {
                 src: "",
                 name: "OPTIONS".to_owned(),
             }),
+            linker: None,
         }
     }
 }
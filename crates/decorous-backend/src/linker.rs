@@ -0,0 +1,139 @@
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::RenderOut;
+
+/// Keeps track of the chunks of the used components of a build, so that a component used by many
+/// others is only emitted once. Its JavaScript is rendered by whoever claims it first, and its
+/// CSS is put in the stylesheet of the main component.
+#[derive(Debug, Default)]
+pub struct Linker {
+    claimed: Mutex<HashSet<PathBuf>>,
+    css: Mutex<Vec<(PathBuf, Vec<u8>)>>,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claims the JavaScript chunk of the component at `path`. Returns `true` only the first
+    /// time, when the caller should render it.
+    pub fn claim(&self, path: &Path) -> bool {
+        self.claimed
+            .lock()
+            .expect("linker lock should not be poisoned")
+            .insert(path.to_path_buf())
+    }
+
+    /// Adds the CSS of the component at `path`, unless it was already added. It's put in the
+    /// stylesheet where it was first added, so a component whose CSS is rendered by another one
+    /// can be added without any to keep its place.
+    pub fn add_css(&self, path: &Path, css: Vec<u8>) {
+        let mut all = self.css.lock().expect("linker lock should not be poisoned");
+        match all.iter_mut().find(|(added, _)| added == path) {
+            Some((_, added)) if added.is_empty() => *added = css,
+            Some(_) => {}
+            None => all.push((path.to_path_buf(), css)),
+        }
+    }
+
+    pub fn has_css(&self) -> bool {
+        self.css
+            .lock()
+            .expect("linker lock should not be poisoned")
+            .iter()
+            .any(|(_, css)| !css.is_empty())
+    }
+
+    /// Writes the CSS of every used component, in the order it was added.
+    pub(crate) fn write_css<T: io::Write>(&self, out: &mut T) -> io::Result<()> {
+        for (_, css) in self
+            .css
+            .lock()
+            .expect("linker lock should not be poisoned")
+            .iter()
+        {
+            out.write_all(css)?;
+        }
+        Ok(())
+    }
+}
+
+/// The output of a used component. Its JavaScript is written to a chunk, and its CSS is kept to
+/// be given to the [`Linker`] once rendering is finished.
+pub struct ChunkOut<T: io::Write> {
+    js: T,
+    css: Vec<u8>,
+}
+
+impl<T: io::Write> ChunkOut<T> {
+    pub fn new(js: T) -> Self {
+        Self { js, css: vec![] }
+    }
+
+    /// Finishes the chunk, returning its CSS.
+    pub fn finish(self) -> Vec<u8> {
+        self.css
+    }
+}
+
+impl<T: io::Write> RenderOut for ChunkOut<T> {
+    fn write_js(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.js.write_all(buf)
+    }
+
+    fn write_css(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.css.extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn write_html(&mut self, _buf: &[u8]) -> io::Result<()> {
+        panic!("cannot write html to a chunk")
+    }
+
+    fn js_handle(&mut self) -> &mut dyn io::Write {
+        &mut self.js
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_are_emitted_once() {
+        let linker = Linker::new();
+        assert!(linker.claim(Path::new("child.decor")));
+        assert!(!linker.claim(Path::new("child.decor")));
+        assert!(!linker.has_css());
+
+        for _ in 0..2 {
+            let mut out = ChunkOut::new(vec![]);
+            out.write_css(b"p.decor-1 { color: red; }\n").unwrap();
+            linker.add_css(Path::new("child.decor"), out.finish());
+        }
+        let mut css = vec![];
+        linker.write_css(&mut css).unwrap();
+        assert_eq!(
+            "p.decor-1 { color: red; }\n",
+            String::from_utf8(css).unwrap()
+        );
+    }
+
+    #[test]
+    fn css_keeps_the_place_it_was_first_added_at() {
+        let linker = Linker::new();
+        linker.add_css(Path::new("a.decor"), vec![]);
+        assert!(!linker.has_css());
+        linker.add_css(Path::new("b.decor"), b"b {}\n".to_vec());
+        linker.add_css(Path::new("a.decor"), b"a {}\n".to_vec());
+        let mut css = vec![];
+        linker.write_css(&mut css).unwrap();
+        assert_eq!("a {}\nb {}\n", String::from_utf8(css).unwrap());
+    }
+}
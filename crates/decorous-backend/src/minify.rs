@@ -0,0 +1,350 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+
+use rslint_parser::{ast::AssignPattern, parse_module, tokenize, AstNode, SyntaxKind, T};
+
+use crate::RenderOut;
+
+const FIRST_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_$";
+const REST_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_$0123456789";
+const RESERVED: &[&str] = &["do", "if", "in", "for", "let", "new", "try", "var"];
+
+/// A [`RenderOut`] that buffers all JavaScript written to it, then writes it to the inner output
+/// minified when [`finish`](Minified::finish) is called. HTML and CSS are passed through.
+pub struct Minified<T> {
+    inner: T,
+    js: Vec<u8>,
+    /// The names that the renderer made up, which are the only ones that are shortened.
+    generated: HashSet<String>,
+}
+
+impl<T: RenderOut> Minified<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            js: vec![],
+            generated: HashSet::new(),
+        }
+    }
+
+    /// Minifies the buffered JavaScript and writes it to the inner output.
+    pub fn finish(mut self) -> io::Result<()> {
+        let js = String::from_utf8(self.js).expect("rendered JavaScript should be valid utf-8");
+        self.inner
+            .write_js(minify_js(&js, &self.generated).as_bytes())
+    }
+}
+
+impl<T: RenderOut> RenderOut for Minified<T> {
+    fn write_js(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.js.extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn write_html(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_html(buf)
+    }
+
+    fn write_css(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_css(buf)
+    }
+
+    fn js_handle(&mut self) -> &mut dyn io::Write {
+        &mut self.js
+    }
+
+    fn generated_name(&mut self, name: &str) {
+        self.generated.insert(name.to_owned());
+    }
+}
+
+/// Minifies JavaScript generated by the compiler. Whitespace and comments are removed, except for
+/// newlines that might be needed for automatic semicolon insertion, and the `generated` names,
+/// which the renderer made up for its own helpers (`__closure0`, `e1`, `create_main_block`, ...),
+/// are shortened.
+///
+/// Names written by the user are never changed, so the output behaves the same as the input.
+pub fn minify_js(js: &str, generated: &HashSet<String>) -> String {
+    let (tokens, _) = tokenize(js, 0);
+    let mut pieces = Vec::with_capacity(tokens.len());
+    let mut offset = 0;
+    for tok in tokens {
+        pieces.push((tok.kind, offset, &js[offset..offset + tok.len]));
+        offset += tok.len;
+    }
+
+    let significant = pieces
+        .iter()
+        .filter(|(kind, ..)| !kind.is_trivia())
+        .collect::<Vec<_>>();
+    let renamed = |i: usize| {
+        let (kind, _, text) = *significant[i];
+        let prev = i
+            .checked_sub(1)
+            .map(|prev| (significant[prev].0, significant[prev].2));
+        let next = significant.get(i + 1).map(|(kind, ..)| *kind);
+        kind == SyntaxKind::IDENT && generated.contains(text) && !is_property(prev, next)
+    };
+
+    let taken = significant
+        .iter()
+        .filter(|(kind, ..)| *kind == SyntaxKind::IDENT)
+        .map(|(.., text)| *text)
+        .collect::<HashSet<_>>();
+    let mut names = ShortNames::default();
+    let mut renames = HashMap::new();
+    for (i, (.., text)) in significant.iter().enumerate() {
+        if renamed(i) && !renames.contains_key(text) {
+            renames.insert(*text, names.next_free(&taken));
+        }
+    }
+    let shorthands = if renames.is_empty() {
+        HashSet::new()
+    } else {
+        shorthand_names(js)
+    };
+
+    let mut out = String::with_capacity(js.len() / 2);
+    // The last token that was written
+    let mut prev: Option<(SyntaxKind, &str)> = None;
+    // Whether there was trivia since the last token, and if it had a newline
+    let mut trivia = false;
+    let mut newline = false;
+    let mut i = 0;
+    for &(kind, offset, text) in &pieces {
+        if kind.is_trivia() {
+            trivia = true;
+            newline |= text.contains('\n');
+            continue;
+        }
+
+        let is_renamed = renamed(i);
+        i += 1;
+        // `{ e0 }` is short for `{ e0: e0 }`, and only the value can be renamed
+        let key = (is_renamed && shorthands.contains(&offset)).then_some(text);
+        let text = if is_renamed {
+            renames[text].as_str()
+        } else {
+            text
+        };
+
+        if let Some(prev) = prev {
+            if newline && needs_newline(prev.0, kind) {
+                out.push('\n');
+            } else if trivia && needs_space(prev.1, key.unwrap_or(text)) {
+                out.push(' ');
+            }
+        }
+        if let Some(key) = key {
+            out.push_str(key);
+            out.push(':');
+        }
+        out.push_str(text);
+        prev = Some((kind, text));
+        trivia = false;
+        newline = false;
+    }
+
+    out
+}
+
+/// Finds where the names of shorthand properties like `{ x }` and shorthand patterns like
+/// `let { x = 1 } = obj` are in `js`, which are both the name of a property and of a variable.
+fn shorthand_names(js: &str) -> HashSet<usize> {
+    parse_module(js, 0)
+        .syntax()
+        .descendants()
+        .filter_map(|node| match node.kind() {
+            SyntaxKind::IDENT_PROP => Some(node),
+            SyntaxKind::SINGLE_PATTERN
+                if node
+                    .parent()
+                    .is_some_and(|parent| parent.kind() == SyntaxKind::OBJECT_PATTERN) =>
+            {
+                Some(node)
+            }
+            SyntaxKind::ASSIGN_PATTERN
+                if node
+                    .parent()
+                    .is_some_and(|parent| parent.kind() == SyntaxKind::OBJECT_PATTERN) =>
+            {
+                AssignPattern::cast(node)?
+                    .key()
+                    .map(|key| key.syntax().clone())
+            }
+            _ => None,
+        })
+        .map(|node| usize::from(node.text_range().start()))
+        .collect()
+}
+
+/// Checks if an identifier between `prev` and `next` is a property name, which can't be renamed
+/// without knowing the object it belongs to.
+fn is_property(prev: Option<(SyntaxKind, &str)>, next: Option<SyntaxKind>) -> bool {
+    match prev {
+        Some((T![.], _)) => true,
+        Some((T!['{'] | T![,], _)) => next == Some(T![:]),
+        _ => false,
+    }
+}
+
+/// Checks if a newline between `prev` and `next` might change the meaning of the code, either
+/// through automatic semicolon insertion or restricted productions like `return\nx`.
+fn needs_newline(prev: SyntaxKind, next: SyntaxKind) -> bool {
+    if matches!(next, T![++] | T![--]) {
+        return true;
+    }
+    if matches!(
+        prev,
+        T![return] | T![throw] | T![break] | T![continue] | T![yield]
+    ) {
+        return true;
+    }
+    let prev_continues = matches!(
+        prev,
+        T![;]
+            | T!['{']
+            | T!['(']
+            | T!['[']
+            | T![,]
+            | T![:]
+            | T![?]
+            | T![=]
+            | T![=>]
+            | T![.]
+            | T![&&]
+            | T![||]
+            | T![+=]
+            | T![-=]
+            | T![|=]
+            | T![&=]
+    );
+    let next_ends = matches!(
+        next,
+        T![;] | T!['}'] | T![')'] | T![']'] | T![,] | T![:] | T![?] | T![.]
+    );
+    !(prev_continues || next_ends)
+}
+
+/// Checks if `prev` and `next` would be lexed differently if they were right next to each other.
+fn needs_space(prev: &str, next: &str) -> bool {
+    let (Some(last), Some(first)) = (prev.chars().last(), next.chars().next()) else {
+        return false;
+    };
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '$' || !c.is_ascii();
+    (is_word(last) && is_word(first))
+        || (last == first && matches!(last, '+' | '-' | '/'))
+        || (last == '/' && first == '*')
+        || (first == '.' && prev.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Generates the shortest identifiers that are still free, in order.
+#[derive(Default)]
+struct ShortNames {
+    count: usize,
+}
+
+impl ShortNames {
+    fn next_free(&mut self, taken: &HashSet<&str>) -> String {
+        loop {
+            let name = self.nth(self.count);
+            self.count += 1;
+            if !taken.contains(name.as_str()) && !RESERVED.contains(&name.as_str()) {
+                return name;
+            }
+        }
+    }
+
+    fn nth(&self, mut n: usize) -> String {
+        let mut name = String::new();
+        name.push(FIRST_CHARS[n % FIRST_CHARS.len()] as char);
+        n /= FIRST_CHARS.len();
+        while n > 0 {
+            n -= 1;
+            name.push(REST_CHARS[n % REST_CHARS.len()] as char);
+            n /= REST_CHARS.len();
+        }
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minify(js: &str, generated: &[&str]) -> String {
+        minify_js(js, &generated.iter().map(|name| name.to_string()).collect())
+    }
+
+    #[test]
+    fn removes_whitespace_and_comments() {
+        assert_eq!(
+            "function f(a,b){return a+b;}",
+            minify("function f(a, b) {\n  // add them\n  return a + b;\n}", &[])
+        );
+    }
+
+    #[test]
+    fn keeps_newlines_needed_for_semicolon_insertion() {
+        assert_eq!(
+            "let x=1\nlet y=x\nreturn\nx",
+            minify("let x = 1\nlet y = x\nreturn\nx", &[])
+        );
+        assert_eq!("a\n++b", minify("a\n++b", &[]));
+    }
+
+    #[test]
+    fn keeps_spaces_between_words_and_operators() {
+        assert_eq!("const x=a+ +b", minify("const x = a + +b", &[]));
+        assert_eq!("1 .toString()", minify("1 .toString()", &[]));
+        assert_eq!("typeof x", minify("typeof   x", &[]));
+    }
+
+    #[test]
+    fn shortens_generated_names() {
+        assert_eq!(
+            "const a=document.createElement(\"p\");let b=()=>a;b();",
+            minify(
+                "const e0 = document.createElement(\"p\");\nlet __closure1 = () => e0;\n__closure1();",
+                &["e0", "__closure1"]
+            )
+        );
+    }
+
+    #[test]
+    fn only_shortens_names_the_renderer_made_up() {
+        assert_eq!(
+            "let a=1;let e1=2;__closure0+a",
+            minify("let e0 = 1;\nlet e1 = 2;\n__closure0 + e0", &["e0"])
+        );
+    }
+
+    #[test]
+    fn does_not_shorten_properties_or_taken_names() {
+        assert_eq!(
+            "let b=a;x.e0;({e0:b})",
+            minify("let e0 = a;\nx.e0;\n({ e0: e0 })", &["e0"])
+        );
+    }
+
+    #[test]
+    fn expands_shorthand_properties() {
+        assert_eq!(
+            "let a=1;f({e0:a,x});let{e1:b=2,c:{e2:d}}=y",
+            minify(
+                "let e0 = 1;\nf({ e0, x });\nlet { e1 = 2, c: { e2 } } = y",
+                &["e0", "e1", "e2"]
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_strings_and_templates() {
+        assert_eq!(
+            "let x=`a  ${y}  b`+\"c  d\"",
+            minify("let x = `a  ${ y }  b` + \"c  d\"", &[])
+        );
+    }
+}
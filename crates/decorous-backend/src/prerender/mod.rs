@@ -3,140 +3,260 @@ mod render_ast;
 use std::{borrow::Cow, collections::HashMap, io};
 
 use crate::{
-    codegen_utils, css_render,
+    codegen_utils::{self, sort_if_testing, GeneratedNames},
+    css_render::{self, CssOptions},
+    html_template::{nonce_attr, TemplateValues},
     render_out::{write_html, write_js},
-    CodeInfo, Ctx, RenderBackend, RenderOut, Result,
+    runtime, CodeInfo, Ctx, OutputFormat, RenderBackend, RenderOut, Result,
 };
 use decorous_frontend::{utils, Component};
-use heck::ToSnekCase;
 use render_ast::*;
 use rslint_parser::AstNode;
 
+#[derive(Debug, Default)]
+pub struct PrerenderOptions {
+    /// How imports and used components are written.
+    pub format: OutputFormat,
+    /// Inline the JavaScript of used components, instead of importing them.
+    pub bundle: bool,
+    /// How the component's CSS is optimized.
+    pub css: CssOptions,
+    /// The JavaScript function that the HTML of `{#html}` blocks is passed through before it's
+    /// rendered, like `DOMPurify.sanitize`.
+    pub html_sanitizer: Option<String>,
+    /// Create every element separately, instead of setting the `innerHTML` of elements whose
+    /// children are all static.
+    pub no_collapse: bool,
+    /// Avoid what a strict Content Security Policy forbids, like creating elements through
+    /// `innerHTML`.
+    pub csp: bool,
+    /// The nonce put on the `<script>` and `<link>` tags of the generated HTML, usually a
+    /// placeholder that the server replaces with the nonce of each response.
+    pub nonce: Option<String>,
+    /// Put before the ids of the elements that the JavaScript looks up, so that several
+    /// prerendered components can share a page.
+    pub id_prefix: String,
+    /// The path of the runtime module that helpers like `replace` are imported from, instead of
+    /// being defined in the component. Ignored for immediately invoked functions, which can't
+    /// import anything.
+    pub runtime: Option<String>,
+}
+
 #[derive(Default)]
-pub struct Prerenderer;
+pub struct Prerenderer {
+    opts: PrerenderOptions,
+}
 
 impl RenderBackend for Prerenderer {
-    type Options = ();
+    type Options = PrerenderOptions;
 
-    fn with_options(&mut self, _options: Self::Options) {}
+    fn with_options(&mut self, options: Self::Options) {
+        self.opts = options;
+    }
 
     fn render<T: RenderOut>(&self, component: &Component, mut out: T, ctx: &Ctx<'_>) -> Result<()> {
-        if let Some(wasm) = component.wasm.as_ref() {
-            let wasm_prelude = ctx.wasm_compiler.compile(CodeInfo {
-                lang: wasm.lang,
-                body: wasm.body,
-                exports: &component.exports,
-            })?;
+        let wasm_prelude = component
+            .wasm
+            .as_ref()
+            .map(|wasm| {
+                ctx.wasm_compiler.compile(CodeInfo {
+                    lang: wasm.lang,
+                    body: wasm.body,
+                    exports: &component.exports,
+                })
+            })
+            .transpose()?;
+        if self.opts.format == OutputFormat::Iife {
+            codegen_utils::deny_iife_imports(component, wasm_prelude.as_deref(), &ctx.errs)?;
+        }
+        if let Some(wasm_prelude) = wasm_prelude {
             out.write_js(wasm_prelude.as_bytes())?;
         }
 
-        let mut output = Output::default();
+        let names = GeneratedNames::default();
         let mut state = State {
             component,
             id_overwrites: HashMap::new(),
             style_cache: None,
             uses: vec![],
+            form_values: None,
+            html_sanitizer: self.opts.html_sanitizer.as_deref(),
+            collapse: !self.opts.no_collapse && !self.opts.csp,
+            csp: self.opts.csp,
+            head_in_template: ctx.index_html.is_some(),
+            in_head: false,
+            id_prefix: &self.opts.id_prefix,
+            loop_values: vec![],
+            shared_runtime: self.runtime().is_some(),
+            parent_tag: None,
+            names: &names,
         };
+        let mut sections = Sections::default();
+        let mut template_end = None;
 
-        for node in &component.fragment_tree {
-            node.render(&mut state, &mut output, &());
-        }
-
-        let html = unsafe { String::from_utf8_unchecked(output.html) };
         if let Some(info) = &ctx.index_html {
-            if component.css.is_some() {
-                write_html!(
-                    out,
-                    include_str!("./templates/index_css.html"),
+            let mut head = HeadHtml::default();
+            let mut head_output = Output::new(&mut head);
+            render_head(&component.fragment_tree, &mut state, &mut head_output);
+            sections = head_output.finish()?;
+            let head = if head.0.is_empty() {
+                String::new()
+            } else {
+                format!("\n    {}", String::from_utf8_lossy(&head.0).trim())
+            };
+
+            // The markup is streamed into the template, so the start of it is written first
+            let has_css = css_render::has_css(component, &self.opts.css, ctx.linker);
+            let start = if let Some(template) = &info.template {
+                let css = Vec::from_iter(has_css.then(|| format!("{}.css", info.basename)));
+                let (start, end) = template.render(&TemplateValues {
+                    scripts: &[format!("{}.js", info.basename)],
+                    css: &css,
+                    title: ctx.name,
+                    nonce: self.opts.nonce.as_deref(),
+                    head: head.trim_start(),
+                });
+                template_end = Some(end);
+                start
+            } else if has_css {
+                format!(
+                    include_str!("./templates/index_css_start.html"),
                     script = format!("{}.js", info.basename),
-                    html = html,
+                    nonce = nonce_attr(self.opts.nonce.as_deref()),
                     css = format!("{}.css", info.basename),
-                )?;
+                    head = head,
+                )
             } else {
-                write_html!(
-                    out,
-                    include_str!("./templates/index.html"),
+                format!(
+                    include_str!("./templates/index_start.html"),
                     script = format!("{}.js", info.basename),
-                    html = html
-                )?;
-            }
+                    nonce = nonce_attr(self.opts.nonce.as_deref()),
+                    head = head,
+                )
+            };
+            out.write_html(start.as_bytes())?;
         } else {
-            out.write_html(html.as_bytes())?;
+            // Makes sure there is HTML output, even if the component has no markup
+            out.write_html(&[])?;
+        }
+
+        let mut output = Output::new(&mut out);
+        for node in &component.fragment_tree {
+            node.render(&mut state, &mut output, &());
         }
+        sections.extend(output.finish()?);
 
-        if let Some(css) = component.css.as_ref() {
+        if let Some(end) = template_end {
+            out.write_html(end.as_bytes())?;
+        } else if ctx.index_html.is_some() {
+            write_html!(
+                out,
+                concat!("\n", include_str!("./templates/index_end.html"))
+            )?;
+        }
+
+        if css_render::has_css(component, &self.opts.css, ctx.linker) {
             let mut css_out = vec![];
-            css_render::render_css(css, &mut css_out, component)?;
+            css_render::render_component_css(component, &mut css_out, &self.opts.css, ctx.linker)?;
             out.write_css(&css_out)?;
         }
 
-        for use_decl in &component.uses {
-            let Some(stem) = use_decl.file_stem() else {
-                continue;
-            };
-            let use_info = ctx.use_resolver.resolve(use_decl)?;
-            write_js!(
-                out,
-                "import __decor_{} from \"./{}\";",
-                stem.to_string_lossy().to_snek_case(),
-                use_info.loc.display(),
+        // Components with top-level await are initialized in an async function, so the code that
+        // runs after initialization is wrapped in one
+        let is_async = component.is_async();
+        if self.opts.format == OutputFormat::Iife {
+            if is_async {
+                write_js!(out, "(async () => {{")?;
+            } else {
+                write_js!(out, "(() => {{")?;
+            }
+        }
+
+        if let Some(runtime) = self.runtime() {
+            runtime::write_import(
+                &mut out,
+                runtime,
+                &["mount", "replace", "scheduler"],
+                self.opts.format,
             )?;
         }
+        let (mut imports, mut uses) = (vec![], vec![]);
+        codegen_utils::write_uses(
+            &mut imports,
+            &mut uses,
+            &component.uses,
+            ctx.use_resolver,
+            self.opts.format,
+            self.opts.bundle,
+            &names,
+        )?;
+        out.write_js(&imports)?;
+        out.write_js(&uses)?;
 
         let has_reactive_variables = !component.declared_vars.all_vars().is_empty();
 
         if has_reactive_variables {
-            let vars = (component.declared_vars.all_vars().len() + 7) / 8;
-            write_js!(
-                out,
-                "const dirty = new Uint8Array(new ArrayBuffer({vars}));"
-            )?;
+            let words = codegen_utils::dirty_words(component.declared_vars.len());
+            write_js!(out, "const dirty = new Uint32Array({words});")?;
         }
 
         // Hoists
         for hoist in &component.hoist {
             write_js!(out, "{hoist}")?;
         }
-        out.write_js(&output.hoists)?;
+        out.write_js(&sections.hoists)?;
 
-        if !output.elements.is_empty() {
+        if !sections.elements.is_empty() {
             // Write elements
-            let elems = unsafe { String::from_utf8_unchecked(output.elements) };
-            write_js!(
-                out,
-                concat!(
-                    "const elems = {{{}}}\n",
-                    include_str!("./templates/replace.js")
-                ),
-                elems
-            )?;
+            let elems = unsafe { String::from_utf8_unchecked(sections.elements) };
+            write_js!(out, "const elems = {{{}}}", elems)?;
+            if self.runtime().is_none() {
+                write_js!(out, include_str!("./templates/replace.js"))?;
+            }
         }
 
-        if !output.ctx_init.is_empty()
+        if !sections.ctx_init.is_empty()
             || !component.declared_vars.is_empty()
             || !component.toplevel_nodes.is_empty()
         {
-            write_ctx_init(&mut out, component, &output.ctx_init)?;
+            if is_async && self.opts.format != OutputFormat::Iife {
+                write_js!(out, "(async () => {{")?;
+            }
+            write_ctx_init(&mut out, component, &sections.ctx_init)?;
 
-            write_js!(out, "const ctx = __init_ctx();")?;
-            if has_reactive_variables {
+            if is_async {
+                write_js!(out, "const ctx = await __init_ctx();")?;
+            } else {
+                write_js!(out, "const ctx = __init_ctx();")?;
+            }
+            if has_reactive_variables && self.runtime().is_some() {
+                write_js!(
+                    out,
+                    "const __schedule_update = scheduler(ctx, dirty, (dirty) => __update(dirty, false));"
+                )?;
+            } else if has_reactive_variables {
                 write_js!(out, "let updating = false;")?;
             }
         }
 
-        if !output.updates.is_empty() || !component.declared_vars.all_reactive_blocks().is_empty() {
-            write_update(&mut out, component, &output.updates)?;
+        if !sections.updates.is_empty() || !component.declared_vars.all_reactive_blocks().is_empty()
+        {
+            write_update(&mut out, component, &sections.updates)?;
             write_js!(
                 out,
-                "dirty.fill(255);
+                "dirty.fill(0xffffffff);
 __update(dirty, true);
 dirty.fill(0);"
             )?;
         }
 
-        if has_reactive_variables {
+        if has_reactive_variables && self.runtime().is_none() {
             write_js!(out, include_str!("./templates/schedule_update.js"))?;
         }
+        if self.opts.format == OutputFormat::Iife || is_async {
+            write_js!(out, "}})();")?;
+        }
+        names.write_to(&mut out);
 
         Ok(())
     }
@@ -144,8 +264,63 @@ dirty.fill(0);"
 
 impl Prerenderer {
     pub fn new() -> Self {
-        Self
+        Self {
+            opts: PrerenderOptions::default(),
+        }
     }
+
+    /// The path that the helpers are imported from, if they are.
+    fn runtime(&self) -> Option<&str> {
+        self.opts
+            .runtime
+            .as_deref()
+            .filter(|_| self.opts.format != OutputFormat::Iife)
+    }
+}
+
+/// Collects the HTML of `{#head}` blocks, which is written into the `<head>` of the template before
+/// the rest of the markup. Only markup can go in the head, so writing anything else is an error.
+#[derive(Default)]
+struct HeadHtml(Vec<u8>, HeadOnly);
+
+impl RenderOut for HeadHtml {
+    fn write_html(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.0.extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn write_css(&mut self, _buf: &[u8]) -> io::Result<()> {
+        Err(head_only_error("css"))
+    }
+
+    fn write_js(&mut self, _buf: &[u8]) -> io::Result<()> {
+        Err(head_only_error("js"))
+    }
+
+    fn js_handle(&mut self) -> &mut dyn io::Write {
+        &mut self.1
+    }
+}
+
+/// The JavaScript handle of [`HeadHtml`], which fails on every write.
+#[derive(Default)]
+struct HeadOnly;
+
+impl io::Write for HeadOnly {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(head_only_error("js"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn head_only_error(kind: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("cannot write {kind} to the head of the template"),
+    )
 }
 
 fn write_ctx_init<T: RenderOut>(
@@ -153,8 +328,20 @@ fn write_ctx_init<T: RenderOut>(
     component: &Component<'_>,
     body: &[u8],
 ) -> io::Result<()> {
-    write_js!(out, "function __init_ctx() {{")?;
-    for (arrow_expr, (idx, scope_id)) in component.declared_vars.all_arrow_exprs() {
+    if component.is_async() {
+        write_js!(out, "async function __init_ctx() {{")?;
+    } else {
+        write_js!(out, "function __init_ctx() {{")?;
+    }
+    let derived_vars = component.declared_vars.derived_vars();
+    if !derived_vars.is_empty() {
+        write_js!(out, "  let {};", derived_vars.join(", "))?;
+    }
+    for (arrow_expr, (idx, scope_id)) in sort_if_testing!(
+        component.declared_vars.all_arrow_exprs().iter(),
+        |(_, (a, _)), (_, (b, _))| a.cmp(b)
+    ) {
+        out.generated_name(&format!("__closure{idx}"));
         write_js!(out, "  let __closure{idx} = {};", {
             codegen_utils::replace_assignments(
                 arrow_expr.syntax(),
@@ -178,14 +365,23 @@ fn write_ctx_init<T: RenderOut>(
         }
     }
     out.write_js(body)?;
-    for (block, id) in component.declared_vars.all_reactive_blocks() {
+    for (block, id) in component.declared_vars.reactive_blocks_in_order() {
         let replaced = codegen_utils::replace_assignments(
             block,
             &utils::get_unbound_refs(block),
             &component.declared_vars,
             None,
         );
-        write_js!(out, "  let __reactive{id} = () => {{ {replaced} }};")?;
+        let asyncness = if utils::has_toplevel_await(block) {
+            "async "
+        } else {
+            ""
+        };
+        out.generated_name(&format!("__reactive{id}"));
+        write_js!(
+            out,
+            "  let __reactive{id} = {asyncness}() => {{ {replaced} }};"
+        )?;
     }
 
     let mut ctx = vec![Cow::Borrowed("undefined"); component.declared_vars.len()];
@@ -212,7 +408,7 @@ fn write_update<T: RenderOut>(
     body: &[u8],
 ) -> io::Result<()> {
     write_js!(out, "function __update(dirty, initial) {{")?;
-    for (block, id) in component.declared_vars.all_reactive_blocks() {
+    for (block, id) in component.declared_vars.reactive_blocks_in_order() {
         let unbound = utils::get_unbound_refs(block);
         let dirty = codegen_utils::calc_dirty(&unbound, &component.declared_vars, None);
         write_js!(out, "  if ({dirty}) {{ ctx[{id}](); }}")?;
@@ -225,18 +421,20 @@ fn write_update<T: RenderOut>(
 #[cfg(test)]
 mod tests {
     use decorous_errors::Source;
-    use decorous_frontend::{Component, Parser};
+    use decorous_frontend::{Component, FixedId, Parser};
+    use itertools::Itertools;
     use std::{fmt::Write, io::Write as IoWrite};
 
     use super::*;
 
-    fn make_component(input: &str) -> Component {
+    fn make_component(input: &str) -> Component<'_> {
         let parser = Parser::new(input);
         let ctx = decorous_frontend::Ctx {
             errs: decorous_errors::stderr(Source {
                 src: input,
                 name: "TEST".to_owned(),
             }),
+            component_ids: &FixedId(0),
             ..Default::default()
         };
         let mut c = Component::new(parser.parse().expect("should be valid input"), ctx);
@@ -270,6 +468,14 @@ mod tests {
     }
 
     macro_rules! test_render {
+        (@ctx $input:expr, $ctx:expr) => {
+            let component = make_component($input);
+            let mut out = TestOut::default();
+            let renderer = Prerenderer::new();
+            renderer.render(&component, &mut out, &$ctx).unwrap();
+            let output = format!("{}\n---\n{}", String::from_utf8(out.js).unwrap(), String::from_utf8(out.html).unwrap());
+            insta::assert_snapshot!(output);
+        };
         ($($input:expr),+) => {
             $(
                 let component = make_component($input);
@@ -290,6 +496,41 @@ mod tests {
         test_render!("#p Hello /p", "#div #p Hi /p Hello, {name} /div");
     }
 
+    #[test]
+    fn text_attributes_and_comments_are_escaped() {
+        test_render!(
+            "// a --> b <i>\n#p[title=\"a<b&c\"] 1 < 2 &amp; a > b \\& c /p #script if (1 < 2) console.log(\"<\\/script>\") /script"
+        );
+    }
+
+    #[test]
+    fn boolean_attributes_are_only_present_when_truthy() {
+        test_render!(
+            "---js let busy = false; --- #input[disabled={busy} checked={true} required={1 > 2}]/input #button[disabled={busy} @click={() => busy = !busy}]:Go"
+        );
+    }
+
+    #[test]
+    fn live_state_is_set_through_properties() {
+        test_render!(
+            "---js let text = \"\"; let done = false; --- #input[value={text} placeholder={text}]/input #input[type=\"checkbox\" checked={done} value={\"on\"}]/input #button[@click={() => { text = \"\"; done = false; }}]:Reset"
+        );
+    }
+
+    #[test]
+    fn void_elements_have_no_closing_tag() {
+        test_render!(
+            "---js let src = \"a.png\"; --- #p Line #br/br Next /p #img[src={src} alt=\"\"]/img #input[type=\"text\"]/input"
+        );
+    }
+
+    #[test]
+    fn custom_ids_and_styles_are_escaped_in_javascript() {
+        test_render!(
+            "---js let color = \"red\"; --- ---css p { color: {color}; } --- #p[id=\"say \\\"hi\\\" \\\\ ☃\" style=\"content: '`${x}`'\" @click={() => color = \"blue\"}]:Hi"
+        );
+    }
+
     #[test]
     fn can_write_basic_js() {
         test_render!(
@@ -351,6 +592,41 @@ mod tests {
         test_render!("---js let x = 0; --- #input[:x:]/input");
     }
 
+    #[test]
+    fn can_render_bindings_to_fields() {
+        test_render!("---js const user = { name: \"\" }; --- #input[:user.name:]/input #p[contenteditable :text:user.name:]/p");
+    }
+
+    #[test]
+    fn can_render_bindings_to_items_of_for_blocks() {
+        test_render!("---js let todos = [{ text: \"\" }]; let tags = [\"\"]; --- {#for todo in todos} #input[:todo.text:]/input {/for} {#for tag in tags} #input[:tag:]/input {/for}");
+    }
+
+    #[test]
+    fn delegates_event_handlers_of_for_block_items() {
+        test_render!("---js let items = [1, 2]; let selected = 0; --- {#for item in items} #li[@click={() => selected = item} @focus={() => console.log(\"focus\")}] #button[@click|stopPropagation={() => console.log(item)}] {item} /button {#if item === selected} #span[@click={() => selected = 0}] x /span {/if} /li {/for}");
+    }
+
+    #[test]
+    fn splits_template_literal_mustaches_into_text_nodes() {
+        test_render!("---js let name = \"\"; let count = 0; let tag = (s) => s; --- #p {`Hi ${name}, you have\\t${count} messages`} /p #p {tag`${count}!`} /p #button[@click={() => { name = \"Jo\"; count += 1; }}] + /button");
+    }
+
+    #[test]
+    fn can_render_event_modifiers() {
+        test_render!("#a[@click|preventDefault|stopPropagation={() => {}}]/a");
+    }
+
+    #[test]
+    fn passes_bound_values_to_form_submit_handlers() {
+        test_render!("---js let name = \"\"; let age = 0; let submit = (values) => console.log(values); --- #form[@submit|preventDefault={submit}] #input[:name:]/input #div #input[:age:]/input /div /form");
+    }
+
+    #[test]
+    fn can_render_content_bindings() {
+        test_render!("---js let x = \"\"; --- #div[contenteditable :html:x:]/div #p[contenteditable :text:x:]/p");
+    }
+
     #[test]
     fn does_not_get_duplicate_elems() {
         test_render!(
@@ -363,6 +639,20 @@ mod tests {
         test_render!("---js let x = 0; let y = 0; $: y = x + 1; --- #input[:x:]/input");
     }
 
+    #[test]
+    fn can_track_more_than_64_variables() {
+        let decls = (0..70).map(|i| format!("let v{i} = 0;")).join(" ");
+        let assigns = (0..70).map(|i| format!("v{i} = 1;")).join(" ");
+        let sum = (0..70).map(|i| format!("v{i}")).join(" + ");
+        let input = format!("---js {decls} --- #button[@click={{() => {{ {assigns} }}}}] {{v0}} {{v31}} {{v32}} {{v64}} {{{sum}}} /button");
+        test_render!(&input);
+    }
+
+    #[test]
+    fn reactive_blocks_can_declare_derived_vars() {
+        test_render!("---js let count = 0; let unused = 1; $: doubled = count * 2; $: quadrupled = doubled * 2; --- #button[@click={() => count += 1}] {doubled} {quadrupled} /button");
+    }
+
     #[test]
     fn can_have_resolver_for_use_path() {
         test_render!("{#use \"./hello.decor\"} #p:Hello #hello /hello");
@@ -372,4 +662,157 @@ mod tests {
     fn dashes_in_use_block_are_turned_into_underscores() {
         test_render!("{#use \"./hello-world.decor\"} #hello-world /hello-world");
     }
+
+    #[test]
+    fn can_render_toplevel_await() {
+        test_render!("---js let data = await fetch(\"/data\"); $: console.log(await data); --- #p {data} /p #button[@click={() => data = 1}]:Reset");
+    }
+
+    #[test]
+    fn can_render_raw_blocks() {
+        test_render!("---js let show = true; --- #div {#raw}<svg viewBox=\"0 0 10 10\">{not a mustache}</svg>{/raw} /div {#if show} {#raw}<b>#p not an element</b>{/raw} {/if}");
+    }
+
+    #[test]
+    fn can_render_html_blocks() {
+        test_render!("---js let content = \"<b>hi</b>\"; --- #div #p before /p {#html content} /div #button[@click={() => content = \"\"}]:Clear");
+    }
+
+    #[test]
+    fn can_render_dynamic_tags() {
+        test_render!("---js let heading = true; --- #div #{heading ? \"h1\" : \"h2\"}[class=\"title\"]:Hello /div #button[@click={() => heading = !heading}]:Toggle");
+    }
+
+    #[test]
+    fn can_render_head_blocks() {
+        test_render!("---js let title = \"Home\"; --- {#head} #title {title} /title {/head} #button[@click={() => title = \"Away\"}]:Leave");
+    }
+
+    #[test]
+    fn static_head_blocks_are_merged_into_the_template() {
+        test_render!(@ctx "---js let desc = \"hi\"; --- {#head} #title Home /title #meta[name=\"description\" content={desc}]/meta {/head} {#head} #title {desc} /title {/head} #p Hello /p", Ctx {
+            index_html: Some(crate::HtmlInfo {
+                basename: "out".to_owned(),
+                template: None,
+            }),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn dynamic_head_blocks_are_hydrated_in_the_template() {
+        test_render!(@ctx "---js let title = \"Home\"; --- {#head} #title Page: {title} \\{ok\\} {\"!\"} /title {/head} {#head} {#if title} #meta[name=\"title\" content={title}]/meta {/if} {/head} #button[@click={() => title = \"Away\"}]:Leave", Ctx {
+            index_html: Some(crate::HtmlInfo {
+                basename: "out".to_owned(),
+                template: None,
+            }),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn can_render_escapes_and_character_references() {
+        test_render!("---js let x = 0; --- #p Tom &amp; Jerry \\#1 \"quoted\" /p #div a&lt;b &#x1F600; \\&amp; {x} /div");
+    }
+
+    #[test]
+    fn constant_for_blocks_are_unrolled() {
+        test_render!("---js let x = 0; --- #ul {#for item in [\"a\", \"b\"]} #li[class={`item-${item}`}] {item} {#for n in [1, 2]} {n * 10} {/for} /li {/for} /ul {#for item in [1, 2]} #button[@click={() => x += 1}] {item} /button {/for}");
+    }
+
+    #[test]
+    fn static_blocks_are_rendered_without_javascript() {
+        test_render!("---js let x = 0; --- #main {#static} #h1[class={\"title-\" + 1}] Welcome /h1 {#for n in [1, 2]} #p {n} /p {/for} {/static} #button[@click={() => x += 1}] {x} /button /main");
+    }
+
+    struct ConstExecutor;
+
+    impl decorous_frontend::CodeExecutor for ConstExecutor {
+        fn execute(
+            &self,
+            _code: &decorous_frontend::ast::Code,
+        ) -> anyhow::Result<decorous_frontend::JsEnv> {
+            Ok([("title", "\"Hi <b>\""), ("n", "3"), ("show", "true")]
+                .into_iter()
+                .map(|(name, value)| decorous_frontend::JsDecl {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn comptime_values_are_folded_into_html() {
+        let input = "---rust:static\nfn main() {}\n---\n---js let x = 0; ---\n#h1[class={`t-${n}`}] {title} {n * 2} {x} /h1\n{#if show}\n#p yes /p\n{:else}\n#p no /p\n{/if}";
+        let ctx = decorous_frontend::Ctx {
+            executor: &ConstExecutor,
+            errs: decorous_errors::stderr(Source {
+                src: input,
+                name: "TEST".to_owned(),
+            }),
+            component_ids: &FixedId(0),
+            ..Default::default()
+        };
+        let mut component = Component::new(Parser::new(input).parse().unwrap(), ctx);
+        component.run_passes().unwrap();
+        let mut out = TestOut::default();
+        Prerenderer::new()
+            .render(&component, &mut out, &Default::default())
+            .unwrap();
+        let output = format!(
+            "{}\n---\n{}",
+            String::from_utf8(out.js).unwrap(),
+            String::from_utf8(out.html).unwrap()
+        );
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn mutations_of_objects_and_arrays_schedule_updates() {
+        test_render!("---js let items = []; items.push(0); let user = { name: \"Jo\" }; let n = 0; function add() { items.push(items.length); } --- #ul {#for item in items} #li {item} /li {/for} /ul #p {user.name} {n} /p #button[@click={add}]:Add #button[@click={() => user.name = \"Al\"}]:Rename #button[@click={() => { items[0]++; n = items.length; }}]:Bump");
+    }
+
+    #[test]
+    fn increments_and_decrements_schedule_updates() {
+        test_render!("---js let count = 0; --- #p {count} /p #button[@click={() => count++}]:Up #button[@click={() => --count}]:Down");
+    }
+
+    #[test]
+    fn helpers_can_be_imported_from_the_runtime() {
+        let component = make_component(
+            "---js let x = 0; --- #p {x} /p {#if x} #span:Hi {/if} #button[@click={() => x += 1}]:Add",
+        );
+        let mut out = TestOut::default();
+        let mut renderer = Prerenderer::new();
+        renderer.with_options(PrerenderOptions {
+            runtime: Some("./decor-runtime.mjs".to_owned()),
+            ..Default::default()
+        });
+        renderer
+            .render(&component, &mut out, &Ctx::default())
+            .unwrap();
+        insta::assert_snapshot!(String::from_utf8(out.js).unwrap());
+    }
+
+    #[test]
+    fn csp_mode_sets_styles_from_the_script() {
+        let component = make_component(
+            "---js let color = \"red\"; --- ---css p { color: {color}; } --- #p[style=\"margin: 0\"]:Hi #div #span[style=\"padding: 0\"]:Static /div",
+        );
+        let mut out = TestOut::default();
+        let mut renderer = Prerenderer::new();
+        renderer.with_options(PrerenderOptions {
+            csp: true,
+            ..Default::default()
+        });
+        renderer
+            .render(&component, &mut out, &Ctx::default())
+            .unwrap();
+        insta::assert_snapshot!(format!(
+            "{}\n---\n{}",
+            String::from_utf8(out.js).unwrap(),
+            String::from_utf8(out.html).unwrap()
+        ));
+    }
 }
@@ -2,47 +2,97 @@ use std::{
     borrow::Cow,
     collections::HashMap,
     fmt::{Display, Write as FmtWrite},
-    io::Write,
+    io::{self, Write},
 };
 
 use crate::{
-    codegen_utils,
+    codegen_utils::{self, GeneratedNames, TemplatePart},
     dom_render::{render_fragment as dom_render_fragment, State as DomRenderState},
+    RenderOut,
 };
 use decorous_frontend::{
     ast::{
         Attribute, AttributeValue, Comment, Element, ForBlock, IfBlock, Mustache, Node, NodeType,
         SpecialBlock, Text, UseBlock,
     },
+    fold::{self, Const},
     utils, Component, FragmentMetadata,
 };
-use heck::ToSnekCase;
 use rslint_parser::{SmolStr, SyntaxNode};
 
+/// The JavaScript written while rendering. Each section is placed somewhere different in the
+/// final output, so they are collected until the whole fragment tree is rendered.
 #[derive(Debug, Default)]
-pub struct Output {
-    pub html: Vec<u8>,
+pub struct Sections {
     pub elements: Vec<u8>,
     pub ctx_init: Vec<u8>,
     pub updates: Vec<u8>,
     pub hoists: Vec<u8>,
 }
 
-impl Output {
+impl Sections {
+    /// Appends each section of `other` to the end of the same section of `self`.
+    pub fn extend(&mut self, other: Sections) {
+        self.elements.extend(other.elements);
+        self.ctx_init.extend(other.ctx_init);
+        self.updates.extend(other.updates);
+        self.hoists.extend(other.hoists);
+    }
+}
+
+/// Where the fragment tree is rendered to. HTML is streamed straight to the [`RenderOut`], in the
+/// order it's rendered in.
+pub struct Output<'a> {
+    html: HtmlWriter<'a>,
+    error: io::Result<()>,
+    pub js: Sections,
+}
+
+impl<'a> Output<'a> {
+    pub fn new(out: &'a mut dyn RenderOut) -> Self {
+        Self {
+            html: HtmlWriter(out),
+            error: Ok(()),
+            js: Sections::default(),
+        }
+    }
+
+    /// Finishes rendering, returning the collected JavaScript. Fails with the first error that
+    /// occurred while writing HTML.
+    pub fn finish(self) -> io::Result<Sections> {
+        self.error?;
+        Ok(self.js)
+    }
+
     fn write_html(&mut self, b: impl Display) {
-        let _ = write!(self.html, "{b}");
+        if self.error.is_ok() {
+            self.error = write!(self.html, "{b}");
+        }
     }
 
     fn write_ctx_initln(&mut self, b: impl Display) {
-        let _ = writeln!(self.ctx_init, "  {b}");
+        let _ = writeln!(self.js.ctx_init, "  {b}");
     }
 
     fn write_updateln(&mut self, b: impl Display) {
-        let _ = writeln!(self.updates, "  {b}");
+        let _ = writeln!(self.js.updates, "  {b}");
     }
 
     fn write_element(&mut self, key: impl Display, val: impl Display) {
-        let _ = write!(self.elements, "\"{key}\": {val}, ");
+        let _ = write!(self.js.elements, "\"{key}\": {val}, ");
+    }
+}
+
+struct HtmlWriter<'a>(&'a mut dyn RenderOut);
+
+impl io::Write for HtmlWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_html(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
 
@@ -52,21 +102,59 @@ pub struct State<'ast> {
     pub id_overwrites: HashMap<u32, SmolStr>,
     pub style_cache: Option<String>,
     pub uses: Vec<Cow<'ast, str>>,
+    /// The values passed to the submit handler of the `#form` whose attributes are being
+    /// rendered.
+    pub form_values: Option<String>,
+    /// The function that the HTML of `{#html}` blocks is sanitized with.
+    pub html_sanitizer: Option<&'ast str>,
+    /// Whether the static children of elements are set through `innerHTML`.
+    pub collapse: bool,
+    /// Whether `style` is set through `style.cssText`, which a Content Security Policy without
+    /// `'unsafe-inline'` allows, instead of as an attribute.
+    pub csp: bool,
+    /// Whether the contents of `{#head}` blocks are written into the `<head>` of an HTML template
+    /// by [`render_head`], instead of being rendered on the client.
+    pub head_in_template: bool,
+    /// Whether the nodes being rendered are in the `<head>` of the template, where placeholder
+    /// `<span>`s can't go.
+    pub in_head: bool,
+    /// The values of the bindings of the `{#for}` blocks that are being unrolled, innermost last.
+    pub loop_values: Vec<(&'ast str, Const)>,
+    /// Put before the ids of elements in the HTML, but not the keys of `elems`.
+    pub id_prefix: &'ast str,
+    /// Whether helpers like `replace` are imported from the runtime module, instead of being
+    /// defined in the component.
+    pub shared_runtime: bool,
+    /// The tag of the element whose children are being rendered, which decides how their text is
+    /// escaped.
+    pub parent_tag: Option<&'ast str>,
+    /// The names made up for the elements, blocks and bindings, which minifiers are free to
+    /// shorten.
+    pub names: &'ast GeneratedNames,
 }
 
 impl<'ast> State<'ast> {
+    /// The id of the element with the node id `id` in the HTML.
+    fn dom_id(&self, id: u32) -> String {
+        format!("{}{id}", self.id_prefix)
+    }
+
     fn use_style_cache(&mut self) -> &str {
         if let Some(ref style) = self.style_cache {
             style.as_str()
         } else {
             let style = {
                 // The minimum length of each part of the eventual style
-                const MIN_LEN: usize = "--decor-0: ${}; ".len();
+                const MIN_LEN: usize = "--decor-0-0: ${}; ".len();
                 let mut style = String::with_capacity(
                     self.component.declared_vars.css_mustaches().len() * MIN_LEN,
                 );
-                for (mustache, id) in self.component.declared_vars.css_mustaches() {
-                    crate::codegen_utils::force_write!(style, "--decor-{id}: ${{{mustache}}}; ");
+                for (mustache, id) in self.component.declared_vars.css_mustaches_in_scope(None) {
+                    crate::codegen_utils::force_write!(
+                        style,
+                        "{}: ${{{mustache}}}; ",
+                        self.component.css_var_name(*id)
+                    );
                 }
                 style
             };
@@ -81,10 +169,11 @@ macro_rules! with_id {
         #[allow(unused)]
         if let Some(id) = $state.id_overwrites.get(&$id).cloned() {
             #[allow(clippy::redundant_closure_call)]
-            $exec(&id);
+            $exec(&id, id.to_string());
         } else {
+            let dom_id = $state.dom_id($id);
             #[allow(clippy::redundant_closure_call)]
-            $exec($id);
+            $exec($id, dom_id);
         }
     };
 }
@@ -100,6 +189,15 @@ impl<'ast> Render<'ast> for Node<'ast, FragmentMetadata> {
 
     fn render(&'ast self, state: &mut State<'ast>, out: &mut Output, _meta: &Self::Metadata) {
         match &self.node_type {
+            NodeType::Element(elem) if elem.is_dynamic() => {
+                render_on_client(self, state, out);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Html(_)) => render_on_client(self, state, out),
+            NodeType::SpecialBlock(SpecialBlock::Head(block))
+                if !(state.head_in_template && can_prerender_head(&block.inner)) =>
+            {
+                render_on_client(self, state, out);
+            }
             NodeType::Element(elem) => elem.render(state, out, &self.metadata),
             NodeType::Text(t) => t.render(state, out, &self.metadata),
             NodeType::Comment(c) => c.render(state, out, &self.metadata),
@@ -109,11 +207,228 @@ impl<'ast> Render<'ast> for Node<'ast, FragmentMetadata> {
     }
 }
 
+/// Folds `js` if its value is known at compile time, so it can be written into the HTML.
+fn fold_js(js: &SyntaxNode, state: &State, meta: &FragmentMetadata) -> Option<Const> {
+    let vars = &state.component.declared_vars;
+    fold::fold(js, &|name| match state
+        .loop_values
+        .iter()
+        .rev()
+        .find(|(binding, _)| *binding == name)
+    {
+        Some((_, value)) => Some(value.clone()),
+        None => vars.get_constant(name, meta.scope()).cloned(),
+    })
+}
+
+/// Whether `nodes` render to the same HTML every time, with no JavaScript. This is what allows a
+/// `{#for}` block to be unrolled, since anything that needs to be updated on the client can't be
+/// repeated.
+fn is_foldable<'ast>(nodes: &'ast [Node<'ast, FragmentMetadata>], state: &mut State<'ast>) -> bool {
+    nodes.iter().all(|node| {
+        let meta = &node.metadata;
+        match &node.node_type {
+            NodeType::Text(_) | NodeType::Comment(_) => true,
+            NodeType::Mustache(Mustache(js)) => {
+                fold_js(js, state, meta).is_some_and(|value| value.to_js_string().is_some())
+            }
+            NodeType::Element(elem) => {
+                !elem.is_dynamic()
+                    && !state.uses.contains(&elem.js_valid_tag_name())
+                    && elem.tag != "form"
+                    && elem.attrs.iter().all(|attr| match attr {
+                        Attribute::KeyValue(key, Some(AttributeValue::Literal(_))) => {
+                            *key != "style" || !state.csp
+                        }
+                        Attribute::KeyValue(_, None) => true,
+                        Attribute::KeyValue(key, Some(AttributeValue::JavaScript(js))) => {
+                            fold_attr(key, js, state, meta).is_some()
+                        }
+                        Attribute::EventHandler(_) | Attribute::Binding(_) => false,
+                    })
+                    && is_foldable(&elem.children, state)
+            }
+            NodeType::SpecialBlock(SpecialBlock::Raw(_)) => true,
+            NodeType::SpecialBlock(SpecialBlock::Static(block)) => is_foldable(&block.inner, state),
+            NodeType::SpecialBlock(SpecialBlock::If(block)) => {
+                match fold_js(&block.expr, state, meta) {
+                    Some(cond) if cond.is_truthy() => is_foldable(&block.inner, state),
+                    Some(_) => block
+                        .else_block
+                        .as_ref()
+                        .map_or(true, |else_block| is_foldable(else_block, state)),
+                    None => false,
+                }
+            }
+            NodeType::SpecialBlock(SpecialBlock::For(block)) => {
+                matches!(fold_js(&block.expr, state, meta), Some(Const::Array(items)) if is_unrollable(block, &items, state))
+            }
+            NodeType::SpecialBlock(_) => false,
+        }
+    })
+}
+
+/// Folds the value of an attribute to the string it's set to, or to `None` if it's a boolean
+/// attribute that's left out. `style` attributes are never folded, since they can have CSS
+/// mustaches added to them.
+fn fold_attr(
+    key: &str,
+    js: &SyntaxNode,
+    state: &State,
+    meta: &FragmentMetadata,
+) -> Option<Option<String>> {
+    if key == "style" {
+        return None;
+    }
+    let value = fold_js(js, state, meta)?;
+    if codegen_utils::is_boolean_attribute(key) {
+        return Some(value.is_truthy().then(String::new));
+    }
+    value.to_js_string().map(Some)
+}
+
+/// Renders the contents of the `{#head}` blocks in `nodes` that can be written straight into the
+/// `<head>` of the template. Blocks that aren't always rendered, like the ones in `{#if}` blocks,
+/// are left to the client.
+pub fn render_head<'ast>(
+    nodes: &'ast [Node<'ast, FragmentMetadata>],
+    state: &mut State<'ast>,
+    out: &mut Output,
+) {
+    for node in nodes {
+        match &node.node_type {
+            NodeType::Element(elem) if !elem.is_dynamic() => {
+                render_head(&elem.children, state, out);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Head(block))
+                if can_prerender_head(&block.inner) =>
+            {
+                state.in_head = true;
+                for child in &block.inner {
+                    child.render(state, out, &());
+                }
+                state.in_head = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Special blocks are rendered with a placeholder `<span>`, which can't be put in the `<head>`, so
+/// `{#head}` blocks with them are always rendered on the client. Mustaches are hydrated by setting
+/// the text of their element, so they can only be in elements with nothing but text.
+fn can_prerender_head<'ast>(nodes: &'ast [Node<'ast, FragmentMetadata>]) -> bool {
+    nodes.iter().all(|node| match &node.node_type {
+        NodeType::Text(_) | NodeType::Comment(_) => true,
+        NodeType::Element(elem) if !elem.is_dynamic() => {
+            is_text(&elem.children) || can_prerender_head(&elem.children)
+        }
+        _ => false,
+    })
+}
+
+fn is_text<'ast>(nodes: &'ast [Node<'ast, FragmentMetadata>]) -> bool {
+    nodes.iter().all(|node| {
+        matches!(
+            node.node_type,
+            NodeType::Text(_) | NodeType::Comment(_) | NodeType::Mustache(_)
+        )
+    })
+}
+
+/// Whether `nodes` have a mustache that can't be written into the HTML.
+fn has_dynamic_text<'ast>(nodes: &'ast [Node<'ast, FragmentMetadata>], state: &State) -> bool {
+    nodes.iter().any(|node| match &node.node_type {
+        NodeType::Mustache(Mustache(js)) => fold_js(js, state, &node.metadata)
+            .and_then(|value| value.to_js_string())
+            .is_none(),
+        _ => false,
+    })
+}
+
+/// Sets the text of an element in the `<head>` whose children have mustaches. The HTML already has
+/// the text around them, which is replaced along with the values of the mustaches.
+fn hydrate_head_text<'ast>(
+    nodes: &'ast [Node<'ast, FragmentMetadata>],
+    state: &mut State<'ast>,
+    out: &mut Output,
+    meta: &FragmentMetadata,
+) {
+    let mut text = String::new();
+    let mut all_unbound = vec![];
+    for node in nodes {
+        match &node.node_type {
+            NodeType::Text(t) => {
+                text.push_str(&codegen_utils::escape_js_string(&t.unescaped(), '`'))
+            }
+            NodeType::Mustache(Mustache(js)) => {
+                let unbound = utils::get_unbound_refs(js);
+                let replaced = codegen_utils::replace_namerefs(
+                    js,
+                    &unbound,
+                    &state.component.declared_vars,
+                    node.metadata.scope(),
+                );
+                codegen_utils::force_write!(text, "${{{replaced}}}");
+                all_unbound.extend(unbound);
+            }
+            _ => {}
+        }
+    }
+
+    with_id!(meta.id(), state, |id, dom_id| {
+        out.write_element(id, format_args!("document.getElementById(\"{dom_id}\")"));
+        let dirty_indices =
+            codegen_utils::calc_dirty(&all_unbound, &state.component.declared_vars, meta.scope());
+        if dirty_indices.is_empty() {
+            out.write_updateln(format_args!(
+                "if (initial) elems[\"{id}\"].textContent = `{text}`;"
+            ));
+        } else {
+            out.write_updateln(format_args!(
+                "if ({dirty_indices}) elems[\"{id}\"].textContent = `{text}`;"
+            ));
+        }
+    });
+}
+
+/// Nodes that can't be known ahead of time, like elements with a dynamic tag name and `{#html}`
+/// blocks, are rendered on the client, in the same way as the contents of a special block.
+fn render_on_client<'ast>(
+    node: &'ast Node<'ast, FragmentMetadata>,
+    state: &mut State<'ast>,
+    out: &mut Output,
+) {
+    let id = node.metadata.id();
+    out.write_html(format_args!("<span id=\"{}\"></span>", state.dom_id(id)));
+    out.write_element(
+        id,
+        format_args!("replace(document.getElementById(\"{}\"))", state.dom_id(id)),
+    );
+    out.write_element(format_args!("{id}_block"), "null");
+
+    let dom_state = DomRenderState {
+        component: state.component,
+        name: id.to_string().into(),
+        root: node.metadata.parent_id(),
+        uses: vec![],
+        form_values: None,
+        html_sanitizer: state.html_sanitizer,
+        collapse: state.collapse,
+        csp: state.csp,
+        shared_runtime: state.shared_runtime,
+        delegate: None,
+        names: state.names,
+    };
+    let _ = dom_render_fragment(std::slice::from_ref(node), dom_state, &mut out.js.hoists);
+    out.write_updateln(format_args!("if (initial) {{ elems[\"{id}_block\"] = create_{id}_block(elems[\"{id}\"].parentNode, elems[\"{id}\"]); }} else {{ elems[\"{id}_block\"].u(dirty); }}"));
+}
+
 impl<'ast> Render<'ast> for Text<'ast> {
     type Metadata = FragmentMetadata;
 
-    fn render(&'ast self, _state: &mut State<'ast>, out: &mut Output, _meta: &Self::Metadata) {
-        out.write_html(self);
+    fn render(&'ast self, state: &mut State<'ast>, out: &mut Output, _meta: &Self::Metadata) {
+        out.write_html(codegen_utils::html_text(self, state.parent_tag));
     }
 }
 
@@ -125,10 +440,10 @@ impl<'ast> Render<'ast> for Element<'ast, FragmentMetadata> {
 
         let js_tag_name = self.js_valid_tag_name();
         if state.uses.contains(&js_tag_name) {
-            out.write_html(format_args!("<span id=\"{id}\"></span>"));
+            out.write_html(format_args!("<span id=\"{}\"></span>", state.dom_id(id)));
             out.write_element(
                 id,
-                format_args!("replace(document.getElementById(\"{id}\"))"),
+                format_args!("replace(document.getElementById(\"{}\"))", state.dom_id(id)),
             );
             out.write_ctx_initln(format_args!(
                 "__decor_{js_tag_name}(elems[\"{id}\"].parentNode, elems[\"{id}\"])",
@@ -141,13 +456,23 @@ impl<'ast> Render<'ast> for Element<'ast, FragmentMetadata> {
         let mut overwritten = false;
         let mut has_dynamic = false;
         let mut has_style = false;
+        if self.tag == "form" {
+            let values = codegen_utils::bound_vars(&self.children)
+                .into_iter()
+                .filter(|var| state.component.declared_vars.get_var(*var, None).is_some())
+                .collect::<Vec<_>>()
+                .join(", ");
+            state.form_values = Some(format!("{{{values}}}"));
+        }
         for attr in &self.attrs {
             attr.render(state, out, meta);
             match attr {
                 Attribute::KeyValue(key, Some(AttributeValue::Literal(literal)))
                     if *key == "id" =>
                 {
-                    state.id_overwrites.insert(meta.id(), SmolStr::new(literal));
+                    // The id is only ever written into JavaScript strings
+                    let escaped = codegen_utils::escape_js_string(literal, '"');
+                    state.id_overwrites.insert(meta.id(), SmolStr::new(escaped));
                     overwritten = true;
                 }
                 Attribute::KeyValue(key, Some(AttributeValue::JavaScript(_)))
@@ -158,32 +483,58 @@ impl<'ast> Render<'ast> for Element<'ast, FragmentMetadata> {
                 }
                 Attribute::KeyValue(key, Some(AttributeValue::Literal(_))) if *key == "style" => {
                     has_style = true;
+                    has_dynamic |= state.csp;
                 }
+                Attribute::KeyValue(key, Some(AttributeValue::JavaScript(js)))
+                    if fold_attr(key, js, state, meta).is_some() => {}
                 Attribute::KeyValue(_, Some(AttributeValue::JavaScript(_)))
                 | Attribute::EventHandler(_)
                 | Attribute::Binding(_) => has_dynamic = true,
                 Attribute::KeyValue(_, None | Some(AttributeValue::Literal(_))) => {}
             }
         }
-        if meta.parent_id().is_none() && !state.component.declared_vars.css_mustaches().is_empty() {
+        state.form_values = None;
+        if meta.parent_id().is_none()
+            && state
+                .component
+                .declared_vars
+                .css_mustaches_in_scope(None)
+                .next()
+                .is_some()
+        {
             has_dynamic = true;
         }
 
-        let inline_styles_candidate =
-            meta.parent_id().is_none() && !state.component.declared_vars.css_mustaches().is_empty();
+        let inline_styles_candidate = meta.parent_id().is_none()
+            && state
+                .component
+                .declared_vars
+                .css_mustaches_in_scope(None)
+                .next()
+                .is_some();
         if !has_style && inline_styles_candidate {
             let style = state.use_style_cache();
             let new_js = rslint_parser::parse_text(&format!("`{style}`"), 0).syntax();
             render_dyn_attr(meta, state, out, "style", &new_js);
         }
 
-        if !overwritten && has_dynamic {
-            out.write_html(format_args!(" id=\"{id}\""));
+        // Mustaches can't have placeholders in the <head>, so the whole text is set instead
+        let dynamic_text = state.in_head && has_dynamic_text(&self.children, state);
+        if !overwritten && (has_dynamic || dynamic_text) {
+            out.write_html(format_args!(" id=\"{}\"", state.dom_id(id)));
         }
         out.write_html(">");
+        if self.is_void() {
+            return;
+        }
+        let parent_tag = state.parent_tag.replace(self.tag);
         for child in &self.children {
             child.render(state, out, &());
         }
+        state.parent_tag = parent_tag;
+        if dynamic_text {
+            hydrate_head_text(&self.children, state, out, meta);
+        }
         out.write_html(format_args!("</{}>", self.tag));
     }
 }
@@ -192,7 +543,10 @@ impl<'ast> Render<'ast> for Comment<'ast> {
     type Metadata = FragmentMetadata;
 
     fn render(&'ast self, _state: &mut State<'ast>, out: &mut Output, _meta: &Self::Metadata) {
-        out.write_html(format_args!("<!--{}-->", self.0));
+        out.write_html(format_args!(
+            "<!--{}-->",
+            codegen_utils::escape_comment(self.0)
+        ));
     }
 }
 
@@ -200,11 +554,35 @@ impl<'ast> Render<'ast> for Mustache {
     type Metadata = FragmentMetadata;
 
     fn render(&'ast self, state: &mut State<'ast>, out: &mut Output, meta: &Self::Metadata) {
+        if let Some(text) = fold_js(&self.0, state, meta).and_then(|value| value.to_js_string()) {
+            out.write_html(codegen_utils::escape_html(&text));
+            return;
+        }
+        // Set along with the rest of the text of the element by `hydrate_head_text`
+        if state.in_head {
+            return;
+        }
+
+        if let Some(parts) = codegen_utils::template_parts(&self.0) {
+            // The text of the template is written as HTML, so it has to be decoded
+            let cooked = parts
+                .iter()
+                .map(|part| match part {
+                    TemplatePart::Static(raw) => fold::unescape(raw).map(Some),
+                    TemplatePart::Dynamic(..) => Some(None),
+                })
+                .collect::<Option<Vec<_>>>();
+            if let Some(cooked) = cooked {
+                render_template_parts(&parts, &cooked, state, out, meta);
+                return;
+            }
+        }
+
         let id = meta.id();
-        out.write_html(format_args!("<span id=\"{id}\"></span>"));
+        out.write_html(format_args!("<span id=\"{}\"></span>", state.dom_id(id)));
         out.write_element(
             id,
-            format_args!("replace(document.getElementById(\"{id}\"))"),
+            format_args!("replace(document.getElementById(\"{}\"))", state.dom_id(id)),
         );
 
         let unbound = utils::get_unbound_refs(&self.0);
@@ -226,6 +604,53 @@ impl<'ast> Render<'ast> for Mustache {
     }
 }
 
+/// Writes the text of a template literal mustache into the HTML, with a text node for each
+/// substitution that is updated on its own. `cooked` has the decoded text of each static part.
+fn render_template_parts(
+    parts: &[TemplatePart],
+    cooked: &[Option<String>],
+    state: &mut State,
+    out: &mut Output,
+    meta: &FragmentMetadata,
+) {
+    let id = meta.id();
+    for (i, (part, text)) in parts.iter().zip(cooked).enumerate() {
+        let TemplatePart::Dynamic(expr, unbound) = part else {
+            out.write_html(codegen_utils::escape_html(
+                text.as_deref().unwrap_or_default(),
+            ));
+            continue;
+        };
+        if let Some(text) = fold_js(expr, state, meta).and_then(|value| value.to_js_string()) {
+            out.write_html(codegen_utils::escape_html(&text));
+            continue;
+        }
+
+        let dom_id = format!("{}_{i}", state.dom_id(id));
+        out.write_html(format_args!("<span id=\"{dom_id}\"></span>"));
+        out.write_element(
+            format_args!("{id}_{i}"),
+            format_args!("replace(document.getElementById(\"{dom_id}\"))"),
+        );
+        let dirty_indices =
+            codegen_utils::calc_dirty(unbound, &state.component.declared_vars, meta.scope());
+        let replaced = codegen_utils::replace_namerefs(
+            expr,
+            unbound,
+            &state.component.declared_vars,
+            meta.scope(),
+        );
+        let condition = if dirty_indices.is_empty() {
+            "initial".to_owned()
+        } else {
+            dirty_indices.to_string()
+        };
+        out.write_updateln(format_args!(
+            "if ({condition}) elems[\"{id}_{i}\"].data = `${{{replaced}}}`;"
+        ));
+    }
+}
+
 impl<'ast> Render<'ast> for SpecialBlock<'ast, FragmentMetadata> {
     type Metadata = FragmentMetadata;
 
@@ -234,6 +659,21 @@ impl<'ast> Render<'ast> for SpecialBlock<'ast, FragmentMetadata> {
             Self::If(block) => block.render(state, out, meta),
             SpecialBlock::For(block) => block.render(state, out, meta),
             SpecialBlock::Use(use_decl) => use_decl.render(state, out, meta),
+            SpecialBlock::Raw(block) => out.write_html(block.html),
+            SpecialBlock::Html(_) => unreachable!("html blocks are rendered on the client"),
+            // Already written into the <head> of the template by `render_head`
+            SpecialBlock::Head(_) => {}
+            // The contents are checked to be known at compile time, so they're all written as
+            // HTML
+            SpecialBlock::Static(block) => {
+                for child in &block.inner {
+                    child.render(state, out, &());
+                }
+            }
+            SpecialBlock::Cfg(_) => unreachable!("cfg blocks are removed by the frontend"),
+            SpecialBlock::Translate(_) => {
+                unreachable!("translations are replaced by the frontend")
+            }
         }
     }
 }
@@ -242,20 +682,23 @@ impl<'ast> Render<'ast> for UseBlock<'ast> {
     type Metadata = FragmentMetadata;
 
     fn render(&'ast self, state: &mut State<'ast>, _out: &mut Output, _meta: &Self::Metadata) {
-        let Some(name) = self.path.file_stem() else {
+        // Globs were expanded into the uses of the component
+        if self.is_glob() {
+            let component = state.component;
+            state.uses.extend(
+                component
+                    .uses
+                    .iter()
+                    .filter(|used| self.matches(&used.path))
+                    .filter_map(|used| used.name()),
+            );
+            return;
+        }
+        let Some(name) = self.name() else {
             return;
         };
 
-        state.uses.push(match <&str>::try_from(name) {
-            Ok(s) => {
-                if s.contains('-') {
-                    s.to_snek_case().into()
-                } else {
-                    s.into()
-                }
-            }
-            Err(_err) => todo!("error"),
-        });
+        state.uses.push(name);
     }
 }
 
@@ -263,6 +706,19 @@ impl<'ast> Render<'ast> for IfBlock<'ast, FragmentMetadata> {
     type Metadata = FragmentMetadata;
 
     fn render(&'ast self, state: &mut State<'ast>, out: &mut Output, meta: &Self::Metadata) {
+        // A constant condition is decided at compile time, so only one branch is rendered
+        if let Some(cond) = fold_js(&self.expr, state, meta) {
+            let branch = if cond.is_truthy() {
+                Some(&self.inner)
+            } else {
+                self.else_block.as_ref()
+            };
+            for child in branch.into_iter().flatten() {
+                child.render(state, out, &());
+            }
+            return;
+        }
+
         let id = meta.id();
         let unbound = utils::get_unbound_refs(&self.expr);
         let replaced = codegen_utils::replace_namerefs(
@@ -272,11 +728,11 @@ impl<'ast> Render<'ast> for IfBlock<'ast, FragmentMetadata> {
             meta.scope(),
         );
 
-        out.write_html(format_args!("<span id=\"{id}\"></span>"));
+        out.write_html(format_args!("<span id=\"{}\"></span>", state.dom_id(id)));
 
         out.write_element(
             id,
-            format_args!("replace(document.getElementById(\"{id}\"))"),
+            format_args!("replace(document.getElementById(\"{}\"))", state.dom_id(id)),
         );
         out.write_element(format_args!("{id}_block"), "null");
 
@@ -285,8 +741,15 @@ impl<'ast> Render<'ast> for IfBlock<'ast, FragmentMetadata> {
             name: meta.id().to_string().into(),
             root: Some(meta.id()),
             uses: vec![],
+            form_values: None,
+            html_sanitizer: state.html_sanitizer,
+            collapse: state.collapse,
+            csp: state.csp,
+            shared_runtime: state.shared_runtime,
+            delegate: None,
+            names: state.names,
         };
-        let _ = dom_render_fragment(&self.inner, state.clone(), &mut out.hoists);
+        let _ = dom_render_fragment(&self.inner, state.clone(), &mut out.js.hoists);
 
         if let Some(else_block) = &self.else_block {
             out.write_element(format_args!("{id}_on"), "true");
@@ -301,8 +764,15 @@ impl<'ast> Render<'ast> for IfBlock<'ast, FragmentMetadata> {
                 name: format!("{}_else", meta.id()).into(),
                 root: Some(meta.id()),
                 uses: vec![],
+                form_values: None,
+                html_sanitizer: state.html_sanitizer,
+                collapse: state.collapse,
+                csp: state.csp,
+                shared_runtime: state.shared_runtime,
+                delegate: None,
+                names: state.names,
             };
-            let _ = dom_render_fragment(else_block, state, &mut out.hoists);
+            let _ = dom_render_fragment(else_block, state, &mut out.js.hoists);
         } else {
             out.write_updateln(format_args!("if ({replaced}) {{ if (elems[\"{id}_block\"]) {{ elems[\"{id}_block\"].u(dirty); }} else {{ elems[\"{id}_block\"] = create_{id}_block(elems[\"{id}\"].parentNode, elems[\"{id}\"]); }} }} else if (elems[\"{id}_block\"]) {{ elems[\"{id}_block\"].d(); elems[\"{id}_block\"] = null; }}"));
         }
@@ -313,6 +783,20 @@ impl<'ast> Render<'ast> for ForBlock<'ast, FragmentMetadata> {
     type Metadata = FragmentMetadata;
 
     fn render(&'ast self, state: &mut State<'ast>, out: &mut Output, meta: &Self::Metadata) {
+        // A constant array whose items all render statically is unrolled into plain HTML
+        if let Some(Const::Array(items)) = fold_js(&self.expr, state, meta) {
+            if is_unrollable(self, &items, state) {
+                for (i, item) in items.into_iter().enumerate() {
+                    let len = push_loop_values(self, state, i, item);
+                    for child in &self.inner {
+                        child.render(state, out, &());
+                    }
+                    state.loop_values.truncate(len);
+                }
+                return;
+            }
+        }
+
         let id = meta.id();
         let unbound = utils::get_unbound_refs(&self.expr);
         let replaced = codegen_utils::replace_namerefs(
@@ -321,19 +805,25 @@ impl<'ast> Render<'ast> for ForBlock<'ast, FragmentMetadata> {
             &state.component.declared_vars,
             meta.scope(),
         );
-        let var_idx = state
+        let scope = state
             .component
             .declared_vars
             .all_scopes()
             .get(&meta.id())
-            .expect("BUG: for block should have an assigned scope")
+            .expect("BUG: for block should have an assigned scope");
+        let var_idx = scope
             .get(self.binding)
             .expect("BUG: for block's scope should contain the binding");
+        // The index of each item is only kept when an item is bound to
+        let set_index = scope
+            .index()
+            .map(|index_idx| format!(" ctx[{index_idx}] = i;"))
+            .unwrap_or_default();
 
-        out.write_html(format_args!("<span id=\"{id}\"></span>"));
+        out.write_html(format_args!("<span id=\"{}\"></span>", state.dom_id(id)));
         out.write_element(
             id,
-            format_args!("replace(document.getElementById(\"{id}\"))"),
+            format_args!("replace(document.getElementById(\"{}\"))", state.dom_id(id)),
         );
         out.write_element(format_args!("{id}_block"), "[]");
 
@@ -342,36 +832,108 @@ impl<'ast> Render<'ast> for ForBlock<'ast, FragmentMetadata> {
             name: meta.id().to_string().into(),
             root: Some(meta.id()),
             uses: vec![],
+            form_values: None,
+            html_sanitizer: state.html_sanitizer,
+            collapse: state.collapse,
+            csp: state.csp,
+            shared_runtime: state.shared_runtime,
+            delegate: Some(id),
+            names: state.names,
         };
-        let _ = dom_render_fragment(&self.inner, state, &mut out.hoists);
+        // The handlers of the items are called by a listener on the parent of the block
+        for event in codegen_utils::delegated_events(&self.inner) {
+            out.write_ctx_initln(format_args!(
+                "elems[\"{id}\"].parentNode.addEventListener(\"{event}\", {});",
+                codegen_utils::delegated_listener(&codegen_utils::delegation_key(
+                    state.component,
+                    id,
+                    event
+                ))
+            ));
+        }
+        let _ = dom_render_fragment(&self.inner, state, &mut out.js.hoists);
 
-        out.write_updateln(format_args!("let i = 0; for (const v of ({replaced})) {{ ctx[{var_idx}] = v; if (i >= elems[\"{id}_block\"].length) {{ elems[\"{id}_block\"][i] = create_{id}_block(elems[\"{id}\"].parentNode, elems[\"{id}\"]); }} elems[\"{id}_block\"][i].u(dirty); i += 1; }} elems[\"{id}_block\"].slice(i).forEach((b) => b.d()); elems[\"{id}_block\"].length = i;"));
+        out.write_updateln(format_args!("let i = 0; for (const v of ({replaced})) {{ ctx[{var_idx}] = v;{set_index} if (i >= elems[\"{id}_block\"].length) {{ elems[\"{id}_block\"][i] = create_{id}_block(elems[\"{id}\"].parentNode, elems[\"{id}\"]); }} elems[\"{id}_block\"][i].u(dirty); i += 1; }} elems[\"{id}_block\"].slice(i).forEach((b) => b.d()); elems[\"{id}_block\"].length = i;"));
     }
 }
 
+/// Whether the body of `block` renders statically for every one of `items`.
+fn is_unrollable<'ast>(
+    block: &'ast ForBlock<'ast, FragmentMetadata>,
+    items: &[Const],
+    state: &mut State<'ast>,
+) -> bool {
+    items.iter().enumerate().all(|(i, item)| {
+        let len = push_loop_values(block, state, i, item.clone());
+        let foldable = is_foldable(&block.inner, state);
+        state.loop_values.truncate(len);
+        foldable
+    })
+}
+
+/// Binds the binding and index of `block` to its `i`th item, returning the length of
+/// `state.loop_values` to truncate back to.
+fn push_loop_values<'ast>(
+    block: &'ast ForBlock<'ast, FragmentMetadata>,
+    state: &mut State<'ast>,
+    i: usize,
+    item: Const,
+) -> usize {
+    let len = state.loop_values.len();
+    state.loop_values.push((block.binding, item));
+    if let Some(index) = block.index {
+        state.loop_values.push((index, Const::Number(i as f64)));
+    }
+    len
+}
+
 impl<'ast> Render<'ast> for Attribute<'ast> {
     type Metadata = FragmentMetadata;
 
     fn render(&'ast self, state: &mut State<'ast>, out: &mut Output, meta: &Self::Metadata) {
         let id = meta.id();
-        let inline_styles_candidate =
-            meta.parent_id().is_none() && !state.component.declared_vars.css_mustaches().is_empty();
+        let inline_styles_candidate = meta.parent_id().is_none()
+            && state
+                .component
+                .declared_vars
+                .css_mustaches_in_scope(None)
+                .next()
+                .is_some();
 
         match self {
             Attribute::KeyValue(key, Some(AttributeValue::Literal(literal))) => {
                 if *key == "style" && inline_styles_candidate {
                     let style = state.use_style_cache();
+                    let literal = codegen_utils::escape_js_string(literal, '`');
                     let new_js =
                         rslint_parser::parse_text(&format!("`{literal} {style}`"), 0).syntax();
                     render_dyn_attr(meta, state, out, "style", &new_js);
+                } else if *key == "style" && state.csp {
+                    with_id!(id, state, |id, dom_id| {
+                        out.write_element(
+                            id,
+                            format_args!("document.getElementById(\"{dom_id}\")"),
+                        );
+                        out.write_ctx_initln(format_args!(
+                            "elems[\"{id}\"].style.cssText = {};",
+                            codegen_utils::js_string_literal(literal)
+                        ));
+                    });
+                }
+                // Inline styles in the markup are blocked by a Content Security Policy without
+                // `'unsafe-inline'`, so they're only set from the script
+                if *key != "style" || !state.csp {
+                    out.write_html(format_args!(
+                        " {key}=\"{}\"",
+                        codegen_utils::escape_html(literal)
+                    ));
                 }
-                out.write_html(format_args!(" {key}=\"{literal}\""));
             }
             Attribute::KeyValue(key, None) => {
                 out.write_html(format_args!(" {key}=\"\""));
             }
             Attribute::EventHandler(evt_handler) => {
-                with_id!(id, state, |id| {
+                with_id!(id, state, |id, dom_id| {
                     let replaced = codegen_utils::replace_assignments(
                         &evt_handler.expr,
                         &utils::get_unbound_refs(&evt_handler.expr),
@@ -379,39 +941,84 @@ impl<'ast> Render<'ast> for Attribute<'ast> {
                         None,
                     );
 
-                    out.write_element(id, format_args!("document.getElementById(\"{id}\")"));
+                    let form_values = state
+                        .form_values
+                        .clone()
+                        .filter(|_| evt_handler.event == "submit");
+                    let listener = if evt_handler.modifiers.is_empty() && form_values.is_none() {
+                        replaced
+                    } else {
+                        codegen_utils::wrap_event_handler(
+                            &replaced,
+                            &evt_handler.modifiers,
+                            &Vec::from_iter(form_values),
+                        )
+                    };
+
+                    out.write_element(id, format_args!("document.getElementById(\"{dom_id}\")"));
                     out.write_ctx_initln(format_args!(
-                        "elems[\"{id}\"].addEventListener(\"{}\", {replaced});",
+                        "elems[\"{id}\"].addEventListener(\"{}\", {listener});",
                         evt_handler.event
                     ));
                 });
             }
             Attribute::Binding(binding) => {
-                with_id!(id, state, |id| {
-                    out.write_element(id, format_args!("document.getElementById(\"{id}\")"));
+                with_id!(id, state, |id, dom_id| {
+                    out.write_element(id, format_args!("document.getElementById(\"{dom_id}\")"));
                     let binding_id = state
                         .component
                         .declared_vars
-                        .get_binding(*binding)
+                        .get_binding(binding.var, binding.kind)
                         .expect("BUG: every binding should have an id in declared vars");
-                    let Some(var_id) = state.component.declared_vars.get_var(*binding, None) else {
-                        todo!("unbound var lint")
-                    };
+                    let var_id = state
+                        .component
+                        .declared_vars
+                        .get_var(binding.root(), None)
+                        .expect("BUG: bindings to undeclared variables should be errors");
+                    let prop = binding.kind.property();
+                    // The rest of the binding after its variable, like `.name` in `user.name`
+                    let field = &binding.var[binding.root().len()..];
 
-                    out.write_ctx_initln(format_args!("elems[\"{id}\"].value = {binding};"));
-                    out.write_ctx_initln(format_args!("let __binding{binding_id} = (ev) => __schedule_update({var_id}, {binding} = ev.target.value);"));
+                    out.write_ctx_initln(format_args!("elems[\"{id}\"].{prop} = {};", binding.var));
+                    state.names.add(format_args!("__binding{binding_id}"));
+                    out.write_ctx_initln(format_args!(
+                        "let __binding{binding_id} = {};",
+                        codegen_utils::binding_closure(
+                            binding.root(),
+                            binding.var,
+                            binding.kind,
+                            var_id
+                        )
+                    ));
                     out.write_ctx_initln(format_args!(
                         "elems[\"{id}\"].addEventListener(\"input\", __binding{binding_id});"
                     ));
 
-                    let dirty_idx = ((var_id + 7) / 8).saturating_sub(1) as usize;
-                    let bitmask = 1 << (var_id % 8);
-                    out.write_updateln(format_args!(
-                        "if (dirty[{dirty_idx}] & {bitmask}) elems[\"{id}\"].value = ctx[{var_id}];"
-                    ));
+                    let (dirty_idx, bitmask) = codegen_utils::dirty_bit(var_id);
+                    if binding.kind.is_content() {
+                        out.write_ctx_initln(format_args!(
+                            "elems[\"{id}\"].addEventListener(\"compositionend\", __binding{binding_id});"
+                        ));
+                        out.write_updateln(format_args!(
+                            "if (dirty[{dirty_idx}] & {bitmask} && elems[\"{id}\"].{prop} !== ctx[{var_id}]{field}) elems[\"{id}\"].{prop} = ctx[{var_id}]{field};"
+                        ));
+                    } else {
+                        out.write_updateln(format_args!(
+                            "if (dirty[{dirty_idx}] & {bitmask}) elems[\"{id}\"].{prop} = ctx[{var_id}]{field};"
+                        ));
+                    }
                 });
             }
             Attribute::KeyValue(key, Some(AttributeValue::JavaScript(js))) => {
+                if let Some(value) = fold_attr(key, js, state, meta) {
+                    if let Some(value) = value {
+                        out.write_html(format_args!(
+                            " {key}=\"{}\"",
+                            codegen_utils::escape_html(&value)
+                        ));
+                    }
+                    return;
+                }
                 let js = if *key == "style" && inline_styles_candidate {
                     let style = state.use_style_cache();
                     rslint_parser::parse_text(&format!("`${{{js}}} {style}`"), 0).syntax()
@@ -431,8 +1038,8 @@ fn render_dyn_attr(
     key: &str,
     js: &SyntaxNode,
 ) {
-    with_id!(meta.id(), state, |id| {
-        out.write_element(id, format_args!("document.getElementById(\"{id}\")"));
+    with_id!(meta.id(), state, |id, dom_id| {
+        out.write_element(id, format_args!("document.getElementById(\"{dom_id}\")"));
         let unbound = utils::get_unbound_refs(js);
         let dirty_indices =
             codegen_utils::calc_dirty(&unbound, &state.component.declared_vars, meta.scope());
@@ -442,14 +1049,16 @@ fn render_dyn_attr(
             &state.component.declared_vars,
             meta.scope(),
         );
+        let set = codegen_utils::set_attribute(
+            &format!("elems[\"{id}\"]"),
+            key,
+            &replaced.to_string(),
+            state.csp,
+        );
         if dirty_indices.is_empty() {
-            out.write_updateln(format_args!(
-                "if (initial) elems[\"{id}\"].setAttribute(\"{key}\", {replaced});"
-            ));
+            out.write_updateln(format_args!("if (initial) {set}"));
         } else {
-            out.write_updateln(format_args!(
-                "if ({dirty_indices}) elems[\"{id}\"].setAttribute(\"{key}\", {replaced});"
-            ));
+            out.write_updateln(format_args!("if ({dirty_indices}) {set}"));
         }
     });
 }
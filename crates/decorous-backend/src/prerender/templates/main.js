@@ -1,4 +1,4 @@
-const dirty = new Uint8Array(new ArrayBuffer({dirty_items}));
+const dirty = new Uint32Array({dirty_items});
 function replace(node) {{
 const text = document.createTextNode("");
 node.replaceWith(text);
@@ -10,14 +10,14 @@ function __init_ctx() {{
 {ctx_body}}}
 const ctx = __init_ctx();
 let updating = false;
-dirty.fill(255);
+dirty.fill(0xffffffff);
 __update(dirty, true);
 dirty.fill(0);
 function __update(dirty, initial) {{
 {update_body}}}
 function __schedule_update(ctx_idx, val) {{
 ctx[ctx_idx] = val;
-dirty[Math.max(Math.ceil(ctx_idx / 8) - 1, 0)] |= 1 << (ctx_idx % 8);
+dirty[ctx_idx >> 5] |= 1 << (ctx_idx & 31);
 if (updating) return;
 updating = true;
 Promise.resolve().then(() => {{
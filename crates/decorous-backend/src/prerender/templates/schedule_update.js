@@ -1,11 +1,12 @@
-function __schedule_update(ctx_idx, val) {{
+function __schedule_update(ctx_idx, val, result = val) {{
   ctx[ctx_idx] = val;
-  dirty[Math.max(Math.ceil(ctx_idx / 8) - 1, 0)] |= 1 << (ctx_idx % 8);
-  if (updating) return;
+  dirty[ctx_idx >> 5] |= 1 << (ctx_idx & 31);
+  if (updating) return result;
   updating = true;
   Promise.resolve().then(() => {{
     __update(dirty, false);
     updating = false;
     dirty.fill(0);
   }});
+  return result;
 }}
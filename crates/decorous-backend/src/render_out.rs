@@ -57,6 +57,10 @@ pub trait RenderOut {
 
     fn js_handle(&mut self) -> &mut dyn io::Write;
 
+    /// Notes that `name` was made up by the renderer for one of its own helpers, like an element
+    /// or a block, rather than written by the user, so it can be renamed.
+    fn generated_name(&mut self, _name: &str) {}
+
     write_fmt!(write_js_fmt, write_js);
     write_fmt!(write_css_fmt, write_css);
     write_fmt!(write_html_fmt, write_html);
@@ -64,7 +68,7 @@ pub trait RenderOut {
 
 impl<T> RenderOut for &mut T
 where
-    T: RenderOut,
+    T: RenderOut + ?Sized,
 {
     fn write_js(&mut self, buf: &[u8]) -> io::Result<()> {
         (*self).write_js(buf)
@@ -81,6 +85,10 @@ where
     fn js_handle(&mut self) -> &mut dyn io::Write {
         (*self).js_handle()
     }
+
+    fn generated_name(&mut self, name: &str) {
+        (*self).generated_name(name)
+    }
 }
 
 pub struct JsFile<T>(T)
@@ -0,0 +1,67 @@
+use std::io;
+
+use crate::{codegen_utils, render_out::write_js, OutputFormat, RenderOut};
+
+/// The helpers that components share when they import them from the runtime module, instead of
+/// defining their own.
+const HELPERS: &[&str] = &["mount", "replace", "scheduler"];
+
+/// The name of the runtime module written for components in `format`, like `decor-runtime.mjs`.
+pub fn file_name(format: OutputFormat) -> String {
+    format!("decor-runtime.{}", format.module_extension())
+}
+
+/// Writes the runtime module, which exports the helpers that components import from it.
+/// Immediately invoked functions can't import anything, so they always define their own helpers,
+/// and asking for their runtime is an error.
+///
+/// The updates scheduled by every component that imports the runtime are run in one flush, so a
+/// component never sees another halfway through an update. Updates scheduled during the flush,
+/// like those of used components, are run in it too.
+pub fn write_runtime<W: io::Write>(out: &mut W, format: OutputFormat) -> io::Result<()> {
+    out.write_all(include_str!("./templates/runtime.js").as_bytes())?;
+    match format {
+        OutputFormat::Esm => writeln!(out, "export {{ {} }};", HELPERS.join(", ")),
+        OutputFormat::Cjs => writeln!(out, "module.exports = {{ {} }};", HELPERS.join(", ")),
+        OutputFormat::Iife => Err(iife_error()),
+    }
+}
+
+/// Imports `helpers` from the runtime module at `path`, which fails for immediately invoked
+/// functions.
+pub(crate) fn write_import<T: RenderOut>(
+    out: &mut T,
+    path: &str,
+    helpers: &[&str],
+    format: OutputFormat,
+) -> io::Result<()> {
+    debug_assert!(helpers.iter().all(|helper| HELPERS.contains(helper)));
+    let path = codegen_utils::js_string_literal(path);
+    match format {
+        OutputFormat::Esm => write_js!(out, "import {{ {} }} from {path};", helpers.join(", ")),
+        OutputFormat::Cjs => {
+            write_js!(out, "const {{ {} }} = require({path});", helpers.join(", "))
+        }
+        OutputFormat::Iife => Err(iife_error()),
+    }
+}
+
+fn iife_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "immediately invoked functions cannot import the runtime",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn immediately_invoked_functions_have_no_runtime() {
+        let mut out = vec![];
+        assert!(write_runtime(&mut out, OutputFormat::Esm).is_ok());
+        let err = write_runtime(&mut vec![], OutputFormat::Iife).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+}
@@ -0,0 +1,36 @@
+const queue = new Set();
+let flushing = false;
+function flush() {
+  for (const update of queue) {
+    queue.delete(update);
+    update();
+  }
+  flushing = false;
+}
+function mount(target, newNode, anchor) {
+  target.insertBefore(newNode, anchor || null);
+}
+function replace(node) {
+  const text = document.createTextNode("");
+  node.replaceWith(text);
+  return text;
+}
+function scheduler(ctx, dirty, update) {
+  let queued = false;
+  const run = () => {
+    update(dirty);
+    queued = false;
+    dirty.fill(0);
+  };
+  return (ctx_idx, val, result = val) => {
+    ctx[ctx_idx] = val;
+    dirty[ctx_idx >> 5] |= 1 << (ctx_idx & 31);
+    if (queued) return result;
+    queued = true;
+    queue.add(run);
+    if (flushing) return result;
+    flushing = true;
+    Promise.resolve().then(flush);
+    return result;
+  };
+}
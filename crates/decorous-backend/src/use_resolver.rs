@@ -6,8 +6,25 @@ pub struct UseInfo {
     pub loc: PathBuf,
 }
 
+/// A used component rendered to be inlined into the JavaScript of the components that use it.
+#[derive(Debug, Default)]
+pub struct InlineModule {
+    /// The `import`s of the component, which have to be at the top level of the output.
+    pub imports: String,
+    /// The body of a function that returns the `initialize` function of the component.
+    pub body: String,
+    /// Whether the body awaits at its top level, so the function has to be async.
+    pub is_async: bool,
+    /// The names in the body that the renderer made up, see
+    /// [`RenderOut::generated_name`](crate::RenderOut::generated_name).
+    pub names: Vec<String>,
+}
+
 pub trait UseResolver {
     fn resolve(&self, path: &Path) -> Result<UseInfo>;
+    /// Renders the component at `path` to be inlined into the output, instead of writing it to a
+    /// separate file.
+    fn resolve_inline(&self, path: &Path) -> Result<InlineModule>;
 }
 
 pub struct NullResolver;
@@ -18,6 +35,13 @@ impl UseResolver for NullResolver {
             loc: path.to_path_buf(),
         })
     }
+
+    fn resolve_inline(&self, _path: &Path) -> Result<InlineModule> {
+        Ok(InlineModule {
+            body: "return function initialize(target) {}".to_owned(),
+            ..Default::default()
+        })
+    }
 }
 
 impl<T> UseResolver for &T
@@ -27,4 +51,8 @@ where
     fn resolve(&self, path: &Path) -> Result<UseInfo> {
         (*self).resolve(path)
     }
+
+    fn resolve_inline(&self, path: &Path) -> Result<InlineModule> {
+        (*self).resolve_inline(path)
+    }
 }
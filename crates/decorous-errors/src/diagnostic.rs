@@ -1,12 +1,19 @@
 use std::{borrow::Cow, ops::Range};
 
+use crate::{Fix, LineCol};
+
 #[derive(Debug, Clone)]
 pub struct Diagnostic {
     pub msg: Cow<'static, str>,
     pub severity: Severity,
     pub helpers: Vec<Helper>,
     pub offset: usize,
+    /// The line and column of `offset`. This is filled in when the diagnostic is emitted to an
+    /// [`ErrStream`](crate::ErrStream), which knows the source it points into.
+    pub position: Option<LineCol>,
     pub note: Option<Cow<'static, str>>,
+    /// Edits that resolve the diagnostic, which `decor build --fix` applies.
+    pub fixes: Vec<Fix>,
 }
 
 #[derive(Debug, Clone)]
@@ -28,6 +35,7 @@ pub struct DiagnosticBuilder {
     offset: usize,
     helpers: Vec<Helper>,
     note: Option<Cow<'static, str>>,
+    fixes: Vec<Fix>,
 }
 
 impl From<Severity> for ariadne::ReportKind<'_> {
@@ -53,6 +61,7 @@ impl DiagnosticBuilder {
             offset,
             helpers: vec![],
             note: None,
+            fixes: vec![],
         }
     }
 
@@ -71,13 +80,20 @@ impl DiagnosticBuilder {
         self
     }
 
+    pub fn add_fix(mut self, fix: Fix) -> Self {
+        self.fixes.push(fix);
+        self
+    }
+
     pub fn build(self) -> Diagnostic {
         Diagnostic {
             msg: self.msg,
             severity: self.severity,
             helpers: self.helpers,
             offset: self.offset,
+            position: None,
             note: self.note,
+            fixes: self.fixes,
         }
     }
 }
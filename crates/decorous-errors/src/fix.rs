@@ -0,0 +1,108 @@
+use std::{borrow::Cow, ops::Range};
+
+/// An edit to the source that resolves a [`Diagnostic`](crate::Diagnostic), like replacing a
+/// misspelled keyword. An empty span inserts the replacement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    pub span: Range<usize>,
+    pub replacement: Cow<'static, str>,
+}
+
+impl Fix {
+    pub fn replace(span: Range<usize>, replacement: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            span,
+            replacement: replacement.into(),
+        }
+    }
+
+    pub fn insert(offset: usize, text: impl Into<Cow<'static, str>>) -> Self {
+        Self::replace(offset..offset, text)
+    }
+}
+
+/// Applies `fixes` to `src`, returning the fixed source and the number of fixes applied. Fixes
+/// that overlap with another one are ambiguous, so they are skipped, as are fixes that don't
+/// point into `src`.
+pub fn apply_fixes(src: &str, fixes: &[Fix]) -> (String, usize) {
+    let mut fixes = fixes
+        .iter()
+        .filter(|fix| {
+            fix.span.start <= fix.span.end
+                && src.is_char_boundary(fix.span.start)
+                && src.is_char_boundary(fix.span.end)
+        })
+        .collect::<Vec<_>>();
+    fixes.sort_by_key(|fix| (fix.span.start, fix.span.end));
+    fixes.dedup();
+
+    let overlaps = |a: &Fix, b: &Fix| {
+        a.span.start < b.span.end && b.span.start < a.span.end
+            || (a.span.is_empty() || b.span.is_empty()) && a.span.start == b.span.start
+    };
+    let unambiguous = fixes
+        .iter()
+        .enumerate()
+        .filter(|(i, fix)| {
+            !fixes
+                .iter()
+                .enumerate()
+                .any(|(j, other)| *i != j && overlaps(fix, other))
+        })
+        .map(|(_, fix)| *fix)
+        .collect::<Vec<_>>();
+
+    let mut fixed = String::with_capacity(src.len());
+    let mut last = 0;
+    for fix in &unambiguous {
+        fixed.push_str(&src[last..fix.span.start]);
+        fixed.push_str(&fix.replacement);
+        last = fix.span.end;
+    }
+    fixed.push_str(&src[last..]);
+
+    (fixed, unambiguous.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_fixes_in_order() {
+        let src = "---js:statc let x = 1; --- #div[class=\"a\" #p/p";
+        let fixes = [
+            Fix::insert(src.find(" #p").unwrap(), "]"),
+            Fix::replace(6..11, "static"),
+        ];
+        assert_eq!(
+            (
+                "---js:static let x = 1; --- #div[class=\"a\"] #p/p".to_owned(),
+                2
+            ),
+            apply_fixes(src, &fixes)
+        );
+    }
+
+    #[test]
+    fn skips_overlapping_fixes() {
+        let src = "hello world";
+        let fixes = [
+            Fix::replace(0..5, "hi"),
+            Fix::replace(2..5, "y"),
+            Fix::insert(11, "!"),
+            Fix::insert(11, "?"),
+            Fix::insert(6, "big "),
+            // The same fix reported twice isn't ambiguous
+            Fix::insert(6, "big "),
+        ];
+        assert_eq!(("hello big world".to_owned(), 1), apply_fixes(src, &fixes));
+    }
+
+    #[test]
+    fn skips_fixes_outside_of_source() {
+        let src = "你好";
+        let fixes = [Fix::insert(1, "x"), Fix::insert(20, "x")];
+        assert_eq!(("你好".to_owned(), 0), apply_fixes(src, &fixes));
+    }
+}
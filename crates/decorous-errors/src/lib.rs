@@ -1,5 +1,9 @@
 mod diagnostic;
+mod fix;
+mod line_index;
 mod stream;
 
 pub use diagnostic::*;
+pub use fix::*;
+pub use line_index::*;
 pub use stream::*;
@@ -0,0 +1,112 @@
+/// A line and column in a source, both starting at 1. Columns are counted in chars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Maps byte offsets in a source to lines and columns. Building it walks the source once, after
+/// which every lookup only has to look at the line the offset is on.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'src> {
+    src: &'src str,
+    /// The byte offset and char offset of the start of each line.
+    line_starts: Vec<(usize, usize)>,
+}
+
+impl<'src> LineIndex<'src> {
+    pub fn new(src: &'src str) -> Self {
+        let mut line_starts = vec![(0, 0)];
+        line_starts.extend(
+            src.char_indices()
+                .enumerate()
+                .filter(|(_, (_, c))| *c == '\n')
+                .map(|(char_idx, (byte_idx, _))| (byte_idx + 1, char_idx + 1)),
+        );
+        Self { src, line_starts }
+    }
+
+    /// The line and column of the char at the byte `offset`. Offsets past the end of the source
+    /// are clamped to it, and offsets inside of a char are rounded down to its start.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let line = self.line_of(offset);
+        let (start, _) = self.line_starts[line];
+        LineCol {
+            line: line + 1,
+            column: self.chars_between(start, offset) + 1,
+        }
+    }
+
+    /// The number of chars before the byte `offset`, for tools that index sources by char.
+    /// Offsets past the end of the source stay past the end, so they can still be told apart.
+    pub fn char_offset(&self, offset: usize) -> usize {
+        let (start, char_start) = self.line_starts[self.line_of(offset)];
+        char_start + self.chars_between(start, offset) + offset.saturating_sub(self.src.len())
+    }
+
+    /// The number of lines in the source. An empty source, or a final line without a line
+    /// terminator, still counts as a line.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        let offset = offset.min(self.src.len());
+        self.line_starts
+            .partition_point(|&(start, _)| start <= offset)
+            - 1
+    }
+
+    fn chars_between(&self, start: usize, end: usize) -> usize {
+        let end = end.min(self.src.len());
+        self.src[start..]
+            .char_indices()
+            .take_while(|(i, c)| start + i + c.len_utf8() <= end)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_lines_and_columns() {
+        let src = "ab\n你好 x\r\n\nlast";
+        let index = LineIndex::new(src);
+        let at = |needle: &str| index.line_col(src.find(needle).unwrap());
+
+        assert_eq!(LineCol { line: 1, column: 1 }, index.line_col(0));
+        assert_eq!(LineCol { line: 1, column: 3 }, at("\n"));
+        assert_eq!(LineCol { line: 2, column: 1 }, at("你"));
+        assert_eq!(LineCol { line: 2, column: 4 }, at("x"));
+        assert_eq!(LineCol { line: 2, column: 5 }, at("\r"));
+        assert_eq!(LineCol { line: 4, column: 1 }, at("last"));
+        assert_eq!(LineCol { line: 4, column: 5 }, index.line_col(src.len()));
+        assert_eq!(
+            LineCol { line: 4, column: 5 },
+            index.line_col(src.len() + 10)
+        );
+        assert_eq!(4, index.line_count());
+    }
+
+    #[test]
+    fn counts_chars_before_offsets() {
+        let src = "a\n好x";
+        let index = LineIndex::new(src);
+        for (char_idx, (byte_idx, _)) in src.char_indices().enumerate() {
+            assert_eq!(char_idx, index.char_offset(byte_idx));
+        }
+        assert_eq!(4, index.char_offset(src.len()));
+        assert_eq!(6, index.char_offset(src.len() + 2));
+        // Inside of `好`
+        assert_eq!(2, index.char_offset(3));
+    }
+
+    #[test]
+    fn empty_source_has_one_line() {
+        let index = LineIndex::new("");
+        assert_eq!(1, index.line_count());
+        assert_eq!(LineCol { line: 1, column: 1 }, index.line_col(0));
+    }
+}
@@ -5,7 +5,7 @@ use std::{
     rc::Rc,
 };
 
-use crate::{Diagnostic, Severity};
+use crate::{Diagnostic, Fix, LineIndex, Severity};
 
 #[derive(Debug, Clone)]
 pub struct Source<'src> {
@@ -15,6 +15,9 @@ pub struct Source<'src> {
 
 pub struct ErrStreamInner<'src, W> {
     source: Source<'src>,
+    lines: LineIndex<'src>,
+    fixes: RefCell<Vec<Fix>>,
+    diagnostics: RefCell<Vec<Diagnostic>>,
     inner: RefCell<W>,
 }
 
@@ -45,24 +48,52 @@ where
     pub fn emit(&self, diagnostic: Diagnostic) {
         self.inner.emit(diagnostic);
     }
+
+    /// The source this stream reports on.
+    pub fn source(&self) -> &Source<'src> {
+        &self.inner.source
+    }
+
+    /// The line index of the source this stream reports on.
+    pub fn lines(&self) -> &LineIndex<'src> {
+        &self.inner.lines
+    }
+
+    /// Takes the fixes of every diagnostic emitted so far.
+    pub fn take_fixes(&self) -> Vec<Fix> {
+        self.inner.fixes.take()
+    }
+
+    /// Takes every diagnostic emitted so far, for callers that report them in their own way.
+    pub fn take_diagnostics(&self) -> Vec<Diagnostic> {
+        self.inner.diagnostics.take()
+    }
 }
 
 impl<'src, W: Write> ErrStreamInner<'src, W> {
     pub fn new(writer: W, source: Source<'src>) -> Self {
         Self {
             inner: writer.into(),
+            lines: LineIndex::new(source.src),
+            fixes: RefCell::default(),
+            diagnostics: RefCell::default(),
             source,
         }
     }
 
-    pub fn emit(&self, diagnostic: Diagnostic) {
+    pub fn emit(&self, mut diagnostic: Diagnostic) {
+        diagnostic.position = Some(self.lines.line_col(diagnostic.offset));
+        self.fixes
+            .borrow_mut()
+            .extend(diagnostic.fixes.iter().cloned());
+        // Diagnostics point into the source by byte, but ariadne counts in chars
+        let offset = self.lines.char_offset(diagnostic.offset);
         let severity = match diagnostic.severity {
             Severity::Error => ariadne::ReportKind::Error,
             Severity::Warning => ariadne::ReportKind::Warning,
         };
-        let mut builder =
-            ariadne::Report::build(severity, self.source.name.as_str(), diagnostic.offset)
-                .with_message(&diagnostic.msg);
+        let mut builder = ariadne::Report::build(severity, self.source.name.as_str(), offset)
+            .with_message(&diagnostic.msg);
 
         if let Some(note) = diagnostic.note.as_ref() {
             builder.set_note(note);
@@ -70,8 +101,12 @@ impl<'src, W: Write> ErrStreamInner<'src, W> {
 
         for helper in &diagnostic.helpers {
             builder.add_label(
-                ariadne::Label::new((self.source.name.as_str(), helper.span.clone()))
-                    .with_message(&helper.msg),
+                ariadne::Label::new((
+                    self.source.name.as_str(),
+                    self.lines.char_offset(helper.span.start)
+                        ..self.lines.char_offset(helper.span.end),
+                ))
+                .with_message(&helper.msg),
             );
         }
 
@@ -87,6 +122,7 @@ impl<'src, W: Write> ErrStreamInner<'src, W> {
             )
             .expect("in memory write should not fail");
         let _ = self.inner.borrow_mut().write_all(&out);
+        self.diagnostics.borrow_mut().push(diagnostic);
     }
 }
 
@@ -101,3 +137,58 @@ impl<W> Debug for ErrStream<'_, W> {
 pub fn stderr(source: Source) -> DynErrStream {
     DynErrStream::new(Box::new(io::stderr()), source)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Helper;
+
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reports_positions_in_chars() {
+        let src = "#p 你好 /p\n#div {x /div";
+        let out = Output::default();
+        let errs = ErrStream::new(
+            out.clone(),
+            Source {
+                name: "input".to_owned(),
+                src,
+            },
+        );
+        for (msg, needle) in [("after the text", "/p"), ("unclosed mustache", "{x")] {
+            let start = src.find(needle).unwrap();
+            errs.emit(
+                Diagnostic::builder(msg, start)
+                    .add_helper(Helper {
+                        msg: "here".into(),
+                        span: start..start + needle.len(),
+                    })
+                    .build(),
+            );
+        }
+
+        let out = String::from_utf8(out.0.take()).unwrap();
+        assert!(out.contains("input:1:7"), "{out}");
+        assert!(out.contains("input:2:6"), "{out}");
+        assert_eq!(2, errs.lines().line_col(src.find('{').unwrap()).line);
+        assert_eq!(
+            vec!["after the text", "unclosed mustache"],
+            errs.take_diagnostics()
+                .iter()
+                .map(|diagnostic| diagnostic.msg.as_ref())
+                .collect::<Vec<_>>()
+        );
+    }
+}
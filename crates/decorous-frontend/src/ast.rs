@@ -1,10 +1,10 @@
-use std::{borrow::Cow, fmt, path::Path};
+use std::{borrow::Cow, fmt, ops::Range, path::Path};
 
 use heck::ToSnekCase;
 use itertools::Itertools;
-use rslint_parser::SyntaxNode;
+use rslint_parser::{SyntaxNode, TextRange};
 
-use crate::{css::ast::Css, location::Location};
+use crate::{css::ast::Css, entities, location::Location};
 
 /// The abstract syntax tree representation of decorous source code.
 ///
@@ -19,12 +19,87 @@ use crate::{css::ast::Css, location::Location};
 #[derive(Debug)]
 pub struct DecorousAst<'a> {
     pub nodes: Vec<Node<'a, Location>>,
-    pub script: Option<SyntaxNode>,
+    pub script: Option<Script>,
     pub css: Option<Css>,
     pub wasm: Option<Code<'a>>,
     pub comptime: Option<Code<'a>>,
 }
 
+/// A builder for a [`DecorousAst`], made from parts that were already parsed elsewhere.
+///
+/// Tools that already have a syntax tree for the script of a component, or for its mustaches,
+/// can pass it in directly instead of serializing it back to text for the [`Parser`] to re-parse.
+///
+/// [`Parser`]: crate::Parser
+#[derive(Debug, Default)]
+pub struct DecorousAstBuilder<'a> {
+    nodes: Vec<Node<'a, Location>>,
+    script: Option<Script>,
+    css: Option<Css>,
+    wasm: Option<Code<'a>>,
+    comptime: Option<Code<'a>>,
+}
+
+/// A decorous file with its code blocks left as they are written, for tools that rewrite the
+/// markup of a file but have to keep its code, like the [formatter](crate::formatter).
+#[derive(Debug)]
+pub struct RawAst<'a> {
+    /// The code blocks that come before the markup.
+    pub head: Vec<Code<'a>>,
+    pub nodes: Vec<Node<'a, Location>>,
+    /// The code blocks that come after the markup.
+    pub tail: Vec<Code<'a>>,
+}
+
+/// The JavaScript of a component, parsed into a syntax tree.
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub node: SyntaxNode,
+    /// Where the script is in the source. Text ranges in [`node`](Self::node) are relative to the
+    /// start of the script.
+    pub offsets: ScriptOffsets,
+}
+
+/// Maps offsets in a [`Script`] back to the source. A script made from several code blocks joins
+/// them in source order, so each block starts at a different place in both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptOffsets {
+    /// The start of each block in the script, and its offset in the source.
+    blocks: Vec<(usize, usize)>,
+}
+
+impl ScriptOffsets {
+    /// The offsets of a script made from a single block, at `offset` in the source.
+    pub fn new(offset: usize) -> Self {
+        Self {
+            blocks: vec![(0, offset)],
+        }
+    }
+
+    /// Adds a block that starts at `start` in the script, and at `offset` in the source.
+    pub fn push(&mut self, start: usize, offset: usize) {
+        self.blocks.push((start, offset));
+    }
+
+    /// Where `pos` in the script is in the source.
+    pub fn source(&self, pos: usize) -> usize {
+        let (start, offset) = self
+            .blocks
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= pos)
+            .copied()
+            .unwrap_or_default();
+        offset + pos - start
+    }
+
+    /// Where `range` in the script is in the source.
+    pub fn source_range(&self, range: TextRange) -> Range<usize> {
+        let start = self.source(range.start().into());
+        start..start + usize::from(range.len())
+    }
+}
+
 /// A node of the [AST](DecorousAst).
 ///
 /// It contains [metadata](`Self::metadata()`) (of type `T`), and the
@@ -47,6 +122,12 @@ pub enum NodeType<'a, T> {
 #[derive(Debug, Clone, PartialEq, Hash)]
 pub struct Mustache(pub SyntaxNode);
 
+impl From<SyntaxNode> for Mustache {
+    fn from(value: SyntaxNode) -> Self {
+        Self(value)
+    }
+}
+
 impl fmt::Display for Mustache {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -61,12 +142,115 @@ impl std::ops::Deref for Mustache {
     }
 }
 
+/// Text, exactly as it was written. Special characters can be escaped with a backslash (`\#`), and
+/// HTML character references (`&amp;`) can be used.
+///
+/// The [`Display`](fmt::Display) implementation writes the text as HTML.
 #[derive(Debug, Clone, PartialEq, Hash, Copy)]
 pub struct Text<'a>(pub &'a str);
 
+/// The characters that can be escaped in text with a backslash.
+const ESCAPABLE: &[char] = &['\\', '#', '{', '}', '/', '-', '&'];
+
+impl<'a> Text<'a> {
+    /// Splits the text into its parts, along with the offset of each part in the text.
+    pub fn parts(&self) -> TextParts<'a> {
+        TextParts {
+            text: self.0,
+            offset: 0,
+        }
+    }
+
+    /// The text as it's displayed, with escapes and character references replaced by the
+    /// characters they stand for.
+    pub fn unescaped(&self) -> Cow<'a, str> {
+        if !self.0.contains(['\\', '&']) {
+            return Cow::Borrowed(self.0);
+        }
+
+        let mut unescaped = String::with_capacity(self.0.len());
+        for (_, part) in self.parts() {
+            match part {
+                TextPart::Literal(literal) => unescaped.push_str(literal),
+                TextPart::Escaped(c) => unescaped.push(c),
+                TextPart::Reference(reference) => match entities::decode(reference) {
+                    Some(decoded) => unescaped.push_str(&decoded),
+                    None => {
+                        unescaped.push('&');
+                        unescaped.push_str(reference);
+                        unescaped.push(';');
+                    }
+                },
+            }
+        }
+        Cow::Owned(unescaped)
+    }
+}
+
 impl fmt::Display for Text<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        for (_, part) in self.parts() {
+            match part {
+                TextPart::Literal(literal) => write!(f, "{literal}")?,
+                TextPart::Escaped('&') => write!(f, "&amp;")?,
+                TextPart::Escaped(c) => write!(f, "{c}")?,
+                // Character references mean the same thing in HTML
+                TextPart::Reference(reference) => write!(f, "&{reference};")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A part of a [`Text`] node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextPart<'a> {
+    Literal(&'a str),
+    /// A character escaped with a backslash.
+    Escaped(char),
+    /// A character reference, without its `&` and `;`, like `amp` or `#x1F600`.
+    Reference(&'a str),
+}
+
+/// An iterator over the [parts](TextPart) of a [`Text`] node.
+#[derive(Debug, Clone)]
+pub struct TextParts<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> Iterator for TextParts<'a> {
+    type Item = (usize, TextPart<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offset;
+        let mut chars = self.text.chars();
+        let (part, len) = match chars.next()? {
+            '\\' => match chars.next().filter(|c| ESCAPABLE.contains(c)) {
+                Some(c) => (TextPart::Escaped(c), 1 + c.len_utf8()),
+                None => (TextPart::Literal("\\"), 1),
+            },
+            '&' => {
+                let reference = self.text[1..]
+                    .split_once(';')
+                    .map(|(reference, _)| reference)
+                    .filter(|reference| {
+                        let name = reference.strip_prefix('#').unwrap_or(reference);
+                        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric())
+                    });
+                match reference {
+                    Some(reference) => (TextPart::Reference(reference), reference.len() + 2),
+                    None => (TextPart::Literal("&"), 1),
+                }
+            }
+            _ => {
+                let len = self.text.find(['\\', '&']).unwrap_or(self.text.len());
+                (TextPart::Literal(&self.text[..len]), len)
+            }
+        };
+        self.text = &self.text[len..];
+        self.offset += len;
+        Some((offset, part))
     }
 }
 
@@ -92,6 +276,9 @@ impl<'a> std::ops::Deref for Comment<'a> {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Element<'a, T> {
     pub tag: &'a str,
+    /// The expression of a dynamic tag name (`#{expr}`). When this is set, [`tag`](Self::tag)
+    /// holds the source text of the expression, braces included.
+    pub tag_expr: Option<SyntaxNode>,
     pub attrs: Vec<Attribute<'a>>,
     pub children: Vec<Node<'a, T>>,
 }
@@ -101,6 +288,12 @@ pub enum SpecialBlock<'a, T> {
     For(ForBlock<'a, T>),
     If(IfBlock<'a, T>),
     Use(UseBlock<'a>),
+    Raw(RawBlock<'a>),
+    Html(HtmlBlock),
+    Head(HeadBlock<'a, T>),
+    Static(StaticBlock<'a, T>),
+    Cfg(CfgBlock<'a, T>),
+    Translate(TranslateBlock<'a>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -118,24 +311,137 @@ pub struct IfBlock<'a, T> {
     pub else_block: Option<Vec<Node<'a, T>>>,
 }
 
+/// A `{#use}` block, which lets the component at `path` be rendered with a tag named after the
+/// file, or after `alias` if it has one.
+///
+/// A path with a `*` in its file name, like `./components/*.decor`, uses every component that
+/// matches it. See [`UseBlock::is_glob`].
 #[derive(Debug, Clone, PartialEq)]
 pub struct UseBlock<'a> {
-    pub path: &'a Path,
+    pub path: Cow<'a, Path>,
+    pub alias: Option<&'a str>,
+}
+
+/// A `{#raw}` block, whose contents are written to the output exactly as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawBlock<'a> {
+    pub html: &'a str,
+}
+
+/// An `{#html}` block, which renders the string its expression evaluates to as HTML.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlBlock {
+    pub expr: SyntaxNode,
+}
+
+/// A `{#head}` block, whose contents are rendered into `document.head` instead of where the block
+/// is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadBlock<'a, T> {
+    pub inner: Vec<Node<'a, T>>,
+}
+
+/// A `{#static}` block, whose contents have no reactivity. The prerenderer writes them into the
+/// HTML without generating any JavaScript for them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticBlock<'a, T> {
+    pub inner: Vec<Node<'a, T>>,
+}
+
+/// A `{#cfg feature="name"}` block, whose contents are only compiled when the feature is enabled.
+/// Disabled blocks are removed from the component before anything else looks at it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CfgBlock<'a, T> {
+    pub feature: &'a str,
+    pub inner: Vec<Node<'a, T>>,
+}
+
+/// A `{@t "key"}` expression, which is replaced with the message of its key in the catalog of the
+/// locale being built.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranslateBlock<'a> {
+    pub key: &'a str,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Attribute<'a> {
     EventHandler(EventHandler<'a>),
-    Binding(&'a str),
+    Binding(Binding<'a>),
     KeyValue(&'a str, Option<AttributeValue<'a>>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct EventHandler<'a> {
     pub event: &'a str,
+    pub modifiers: Vec<EventModifier>,
     pub expr: SyntaxNode,
 }
 
+/// A modifier of an event handler, written as `@event|modifier={handler}`. Modifiers are applied
+/// to the event before the handler is called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventModifier {
+    PreventDefault,
+    StopPropagation,
+}
+
+impl EventModifier {
+    /// The method of the JavaScript `Event` that applies the modifier.
+    pub fn method(&self) -> &'static str {
+        match self {
+            EventModifier::PreventDefault => "preventDefault",
+            EventModifier::StopPropagation => "stopPropagation",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding<'a> {
+    pub var: &'a str,
+    pub kind: BindingKind,
+}
+
+impl<'a> Binding<'a> {
+    /// The variable that is bound to. For `:user.name:`, this is `user`.
+    pub fn root(&self) -> &'a str {
+        self.var.split('.').next().unwrap_or(self.var)
+    }
+
+    /// Whether a field of the variable is bound to, rather than the variable itself.
+    pub fn is_field(&self) -> bool {
+        self.var.contains('.')
+    }
+}
+
+/// The property of an element that a [`Binding`] keeps in sync with its variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BindingKind {
+    /// `:var:`, bound to the `value` of form inputs.
+    #[default]
+    Value,
+    /// `:html:var:`, bound to the `innerHTML` of a `contenteditable` element.
+    Html,
+    /// `:text:var:`, bound to the `textContent` of a `contenteditable` element.
+    Text,
+}
+
+impl BindingKind {
+    /// The DOM property that is read from and written to by the binding.
+    pub fn property(&self) -> &'static str {
+        match self {
+            BindingKind::Value => "value",
+            BindingKind::Html => "innerHTML",
+            BindingKind::Text => "textContent",
+        }
+    }
+
+    /// Whether the binding is to the content of an editable element, rather than a form input.
+    /// These bindings must not update while an IME composition is in progress.
+    pub fn is_content(&self) -> bool {
+        !matches!(self, BindingKind::Value)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum AttributeValue<'a> {
     Literal(Cow<'a, str>),
@@ -144,7 +450,7 @@ pub enum AttributeValue<'a> {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum CollapsedChildrenType<'a> {
-    Text(&'a str),
+    Text(Cow<'a, str>),
     Html(String),
 }
 
@@ -155,6 +461,12 @@ pub struct Code<'a> {
     pub offset: usize,
     pub comptime: bool,
 }
+/// Elements that can't have children, which are written without a closing tag in HTML.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
 impl<'a, T> Element<'a, T> {
     pub fn descendents(&'a self) -> NodeIter<'a, T> {
         NodeIter::new(&self.children)
@@ -178,6 +490,15 @@ impl<'a, T> Element<'a, T> {
             .any(|child| matches!(child.node_type, NodeType::Mustache(_)))
     }
 
+    pub fn is_dynamic(&self) -> bool {
+        self.tag_expr.is_some()
+    }
+
+    /// Whether this is a void element, like `#img`, which can't have children.
+    pub fn is_void(&self) -> bool {
+        !self.is_dynamic() && VOID_ELEMENTS.contains(&self.tag)
+    }
+
     pub fn js_valid_tag_name(&self) -> Cow<'a, str> {
         if self.tag.contains('-') {
             Cow::Owned(self.tag.to_snek_case())
@@ -187,6 +508,73 @@ impl<'a, T> Element<'a, T> {
     }
 }
 
+impl<'a> UseBlock<'a> {
+    /// The name that the used component is rendered with, which is valid in JavaScript like
+    /// [`Element::js_valid_tag_name`]. Returns `None` if the path has no file name, or if it uses
+    /// many components.
+    pub fn name(&self) -> Option<Cow<'_, str>> {
+        if self.is_glob() {
+            return None;
+        }
+        let name = match self.alias {
+            Some(alias) => alias,
+            None => self.path.file_stem()?.to_str()?,
+        };
+        Some(if name.contains('-') {
+            Cow::Owned(name.to_snek_case())
+        } else {
+            Cow::Borrowed(name)
+        })
+    }
+
+    /// Checks if the block uses every component that matches its path, instead of a single one.
+    pub fn is_glob(&self) -> bool {
+        self.glob().is_some()
+    }
+
+    /// Checks if the component at `path` is used by this block. Globs are expanded relative to
+    /// the component they are in, so the directory of `path` only has to end with theirs.
+    pub fn matches(&self, path: &Path) -> bool {
+        let Some((dir, pattern)) = self.glob() else {
+            return *self.path == *path;
+        };
+        let dir = dir.strip_prefix(".").unwrap_or(dir);
+        path.parent().is_some_and(|parent| parent.ends_with(dir))
+            && path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| wildcard_matches(pattern, name))
+    }
+
+    /// The directory and the file name pattern of the components used by a glob.
+    pub fn glob(&self) -> Option<(&Path, &str)> {
+        let name = self.path.file_name()?.to_str()?;
+        name.contains('*')
+            .then(|| (self.path.parent().unwrap_or(Path::new("")), name))
+    }
+}
+
+/// Matches `name` against `pattern`, where `*` matches any number of characters.
+fn wildcard_matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect_vec();
+    let Some(last) = parts.pop() else {
+        // There is no `*`
+        return rest.is_empty();
+    };
+    for part in parts {
+        let Some(pos) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[pos + part.len()..];
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
 impl<'a, T> Node<'a, T> {
     pub fn new(ty: NodeType<'a, T>, metadata: T) -> Self {
         Self {
@@ -232,12 +620,26 @@ impl<'a, T> Node<'a, T> {
                         expr: for_block.expr,
                     }),
                     SpecialBlock::Use(use_block) => SpecialBlock::Use(use_block),
+                    SpecialBlock::Raw(raw_block) => SpecialBlock::Raw(raw_block),
+                    SpecialBlock::Html(html_block) => SpecialBlock::Html(html_block),
+                    SpecialBlock::Translate(translate) => SpecialBlock::Translate(translate),
+                    SpecialBlock::Head(head_block) => SpecialBlock::Head(HeadBlock {
+                        inner: cast_children!(head_block.inner, transfer_func),
+                    }),
+                    SpecialBlock::Static(static_block) => SpecialBlock::Static(StaticBlock {
+                        inner: cast_children!(static_block.inner, transfer_func),
+                    }),
+                    SpecialBlock::Cfg(cfg_block) => SpecialBlock::Cfg(CfgBlock {
+                        feature: cfg_block.feature,
+                        inner: cast_children!(cfg_block.inner, transfer_func),
+                    }),
                 }),
             },
             NodeType::Element(elem) => Node {
                 metadata: new_meta,
                 node_type: NodeType::Element(Element {
                     tag: elem.tag,
+                    tag_expr: elem.tag_expr,
                     attrs: elem.attrs,
                     children: cast_children!(elem.children, transfer_func),
                 }),
@@ -271,12 +673,73 @@ impl<'a, T> IfBlock<'a, T> {
 }
 
 impl<'a> DecorousAst<'a> {
+    pub fn builder() -> DecorousAstBuilder<'a> {
+        DecorousAstBuilder::new()
+    }
+
     /// Creates a recursive iterator over the nodes of the template.
     pub fn iter_nodes(&'a self) -> NodeIter<'a, Location> {
         NodeIter::new(&self.nodes)
     }
 }
 
+impl<'a> DecorousAstBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn node(mut self, node: Node<'a, Location>) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    pub fn nodes(mut self, nodes: impl IntoIterator<Item = Node<'a, Location>>) -> Self {
+        self.nodes.extend(nodes);
+        self
+    }
+
+    /// Sets the script of the component. `node` should be the root of a parsed module, like the
+    /// one returned by [`rslint_parser::parse_module`].
+    pub fn script(self, node: SyntaxNode) -> Self {
+        self.script_at(node, 0)
+    }
+
+    /// Like [`script`](Self::script), but for a script that starts at `offset` in the source of
+    /// the component, so that diagnostics point to the right place.
+    pub fn script_at(mut self, node: SyntaxNode, offset: usize) -> Self {
+        self.script = Some(Script {
+            node,
+            offsets: ScriptOffsets::new(offset),
+        });
+        self
+    }
+
+    pub fn css(mut self, css: Css) -> Self {
+        self.css = Some(css);
+        self
+    }
+
+    pub fn wasm(mut self, wasm: Code<'a>) -> Self {
+        self.wasm = Some(wasm);
+        self
+    }
+
+    pub fn comptime(mut self, comptime: Code<'a>) -> Self {
+        self.comptime = Some(comptime);
+        self
+    }
+
+    pub fn build(self) -> DecorousAst<'a> {
+        DecorousAst {
+            nodes: self.nodes,
+            script: self.script,
+            css: self.css,
+            wasm: self.wasm,
+            comptime: self.comptime,
+        }
+    }
+}
+
 pub fn traverse_with<'a, T, F, G>(nodes: &'a [Node<'a, T>], predicate: &mut F, body_func: &mut G)
 where
     F: FnMut(&Element<'a, T>) -> bool,
@@ -293,6 +756,31 @@ where
     }
 }
 
+/// Calls `f` on every node in `nodes`, including the ones inside of `{#if}`, `{#for}`, `{#head}`,
+/// `{#static}` and `{#cfg}` blocks.
+pub fn traverse<'a, T, F>(nodes: &'a [Node<'a, T>], f: &mut F)
+where
+    F: FnMut(&'a Node<'a, T>),
+{
+    for node in nodes {
+        f(node);
+        match &node.node_type {
+            NodeType::Element(elem) => traverse(&elem.children, f),
+            NodeType::SpecialBlock(SpecialBlock::If(block)) => {
+                traverse(&block.inner, f);
+                if let Some(else_block) = &block.else_block {
+                    traverse(else_block, f);
+                }
+            }
+            NodeType::SpecialBlock(SpecialBlock::For(block)) => traverse(&block.inner, f),
+            NodeType::SpecialBlock(SpecialBlock::Head(block)) => traverse(&block.inner, f),
+            NodeType::SpecialBlock(SpecialBlock::Static(block)) => traverse(&block.inner, f),
+            NodeType::SpecialBlock(SpecialBlock::Cfg(block)) => traverse(&block.inner, f),
+            _ => {}
+        }
+    }
+}
+
 pub fn traverse_mut<'a, T, F>(nodes: &mut [Node<'a, T>], f: &mut F)
 where
     F: FnMut(&mut Node<'a, T>),
@@ -350,9 +838,7 @@ impl<'a, T> fmt::Display for Element<'a, T> {
             f,
             "<{}{}{}>{}</{0}>",
             self.tag,
-            (!self.children.is_empty())
-                .then_some(" ")
-                .unwrap_or_default(),
+            if self.children.is_empty() { "" } else { " " },
             self.attrs.iter().join(" "),
             self.children
                 .iter()
@@ -368,7 +854,7 @@ impl<'a> fmt::Display for Attribute<'a> {
             Attribute::KeyValue(key, Some(val)) => write!(f, "{key}={val}"),
             Attribute::KeyValue(key, None) => write!(f, "{key}"),
             Attribute::EventHandler(event_handler) => write!(f, "{event_handler}"),
-            Attribute::Binding(binding) => write!(f, "bind:{binding}"),
+            Attribute::Binding(binding) => write!(f, "{binding}"),
         }
     }
 }
@@ -384,7 +870,21 @@ impl<'a> fmt::Display for AttributeValue<'a> {
 
 impl<'a> fmt::Display for EventHandler<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "on:{}={{{}}}", self.event, self.expr)
+        write!(f, "on:{}", self.event)?;
+        for modifier in &self.modifiers {
+            write!(f, "|{}", modifier.method())?;
+        }
+        write!(f, "={{{}}}", self.expr)
+    }
+}
+
+impl<'a> fmt::Display for Binding<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            BindingKind::Value => write!(f, "bind:{}", self.var),
+            BindingKind::Html => write!(f, "bind:html:{}", self.var),
+            BindingKind::Text => write!(f, "bind:text:{}", self.var),
+        }
     }
 }
 
@@ -394,6 +894,12 @@ impl<'a, T> fmt::Display for SpecialBlock<'a, T> {
             SpecialBlock::If(if_block) => write!(f, "{if_block}"),
             SpecialBlock::For(for_block) => write!(f, "{for_block}"),
             SpecialBlock::Use(use_block) => write!(f, "{use_block}"),
+            SpecialBlock::Raw(raw_block) => write!(f, "{raw_block}"),
+            SpecialBlock::Html(html_block) => write!(f, "{html_block}"),
+            SpecialBlock::Head(head_block) => write!(f, "{head_block}"),
+            SpecialBlock::Static(static_block) => write!(f, "{static_block}"),
+            SpecialBlock::Cfg(cfg_block) => write!(f, "{cfg_block}"),
+            SpecialBlock::Translate(translate) => write!(f, "{translate}"),
         }
     }
 }
@@ -426,6 +932,59 @@ impl<'a, T> fmt::Display for ForBlock<'a, T> {
 
 impl<'a> fmt::Display for UseBlock<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{{#use \"{}\"}}", self.path.display())
+        write!(f, "{{#use \"{}\"", self.path.display())?;
+        if let Some(alias) = self.alias {
+            write!(f, " as {alias}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl<'a, T> fmt::Display for HeadBlock<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{#head}}\n{}\n{{/head}}",
+            self.inner.iter().map(|elem| format!("  {elem}")).join(""),
+        )
+    }
+}
+
+impl<'a, T> fmt::Display for StaticBlock<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{#static}}\n{}\n{{/static}}",
+            self.inner.iter().map(|elem| format!("  {elem}")).join(""),
+        )
+    }
+}
+
+impl<'a, T> fmt::Display for CfgBlock<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{#cfg feature=\"{}\"}}\n{}\n{{/cfg}}",
+            self.feature,
+            self.inner.iter().map(|elem| format!("  {elem}")).join(""),
+        )
+    }
+}
+
+impl fmt::Display for TranslateBlock<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{@t \"{}\"}}", self.key)
+    }
+}
+
+impl fmt::Display for HtmlBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{#html {}}}", self.expr)
+    }
+}
+
+impl<'a> fmt::Display for RawBlock<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{#raw}}{}{{/raw}}", self.html)
     }
 }
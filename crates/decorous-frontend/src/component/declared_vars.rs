@@ -4,14 +4,23 @@ use std::{borrow::Borrow, collections::HashMap, hash::Hash};
 
 use rslint_parser::{ast::ArrowExpr, SmolStr, SyntaxNode};
 
+use crate::{ast::BindingKind, fold::Const};
+
 #[derive(Debug, Clone, Default)]
 pub struct DeclaredVariables {
     vars: HashMap<SmolStr, u32>,
     arrow_exprs: HashMap<ArrowExpr, (u32, Option<u32>)>,
-    bindings: HashMap<SmolStr, u32>,
+    bindings: HashMap<(SmolStr, BindingKind), u32>,
     scopes: HashMap<u32, Scope>,
     css_mustaches: HashMap<SyntaxNode, u32>,
+    /// The `{#for}` blocks whose items the CSS mustaches are set on, by CSS mustache id.
+    css_scopes: HashMap<u32, Vec<u32>>,
     reactive_blocks: HashMap<SyntaxNode, u32>,
+    /// The variables that are declared by being assigned to in reactive blocks, like
+    /// `$: doubled = count * 2`, in the order they're written.
+    derived_vars: Vec<SmolStr>,
+    /// The values of variables that are known at compile time, from comptime code blocks.
+    constants: HashMap<SmolStr, Const>,
     current_id: u32,
     css_current: u32,
 }
@@ -26,6 +35,13 @@ impl DeclaredVariables {
         self.vars.insert(var, id);
     }
 
+    /// Inserts a variable whose value is known at compile time. It is still declared like any
+    /// other variable, so it can be used where expressions can't be folded.
+    pub fn insert_constant(&mut self, var: SmolStr, value: Const) {
+        self.insert_var(var.clone());
+        self.constants.insert(var, value);
+    }
+
     pub fn insert_arrow_expr(&mut self, var: ArrowExpr, scope_id: Option<u32>) {
         let id = self.generate_id();
         self.arrow_exprs.insert(var, (id, scope_id));
@@ -36,13 +52,17 @@ impl DeclaredVariables {
         self.css_mustaches.insert(node, id);
     }
 
+    pub fn insert_css_scope(&mut self, css_id: u32, scope_id: u32) {
+        self.css_scopes.entry(css_id).or_default().push(scope_id);
+    }
+
     pub fn insert_scope(&mut self, scope_id: u32, scope: Scope) {
         self.scopes.insert(scope_id, scope);
     }
 
-    pub fn insert_binding(&mut self, name: SmolStr) {
+    pub fn insert_binding(&mut self, name: SmolStr, kind: BindingKind) {
         let id = self.generate_id();
-        self.bindings.insert(name, id);
+        self.bindings.insert((name, kind), id);
     }
 
     pub fn insert_reactive_block(&mut self, block: SyntaxNode) {
@@ -50,6 +70,13 @@ impl DeclaredVariables {
         self.reactive_blocks.insert(block, id);
     }
 
+    /// Inserts a variable that the script doesn't declare, but assigns to in a reactive block.
+    /// The variable is declared at the start of the script.
+    pub fn insert_derived_var(&mut self, var: SmolStr) {
+        self.insert_var(var.clone());
+        self.derived_vars.push(var);
+    }
+
     pub fn get_var<K>(&self, var: &K, scope_id: Option<u32>) -> Option<u32>
     where
         SmolStr: Borrow<K>,
@@ -61,16 +88,21 @@ impl DeclaredVariables {
         }
     }
 
+    /// Gets the value of a constant, unless it is shadowed by a variable of the `{#for}` block
+    /// with `scope_id`.
+    pub fn get_constant(&self, var: &str, scope_id: Option<u32>) -> Option<&Const> {
+        if scope_id.is_some_and(|scope_id| self.is_scope_var(var, scope_id)) {
+            return None;
+        }
+        self.constants.get(var)
+    }
+
     pub fn get_arrow_expr(&self, arrow_expr: &ArrowExpr) -> Option<(u32, Option<u32>)> {
         self.arrow_exprs.get(arrow_expr).copied()
     }
 
-    pub fn get_binding<K>(&self, var: &K) -> Option<u32>
-    where
-        SmolStr: Borrow<K>,
-        K: Hash + Eq + ?Sized,
-    {
-        self.bindings.get(var).cloned()
+    pub fn get_binding(&self, var: &str, kind: BindingKind) -> Option<u32> {
+        self.bindings.get(&(SmolStr::new(var), kind)).cloned()
     }
 
     pub fn get_reactive_block(&self, block: &SyntaxNode) -> Option<u32> {
@@ -89,7 +121,7 @@ impl DeclaredVariables {
         &self.scopes
     }
 
-    pub fn all_bindings(&self) -> &HashMap<SmolStr, u32> {
+    pub fn all_bindings(&self) -> &HashMap<(SmolStr, BindingKind), u32> {
         &self.bindings
     }
 
@@ -97,6 +129,23 @@ impl DeclaredVariables {
         &self.reactive_blocks
     }
 
+    /// Gets the reactive blocks in the order they're written, which is the order they run in
+    /// during an update. A block that uses a variable derived by an earlier one sees its new
+    /// value, so derived variables update the ones derived from them in turn.
+    pub fn reactive_blocks_in_order(&self) -> Vec<(&SyntaxNode, u32)> {
+        let mut blocks = self
+            .reactive_blocks
+            .iter()
+            .map(|(block, id)| (block, *id))
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|(_, id)| *id);
+        blocks
+    }
+
+    pub fn derived_vars(&self) -> &[SmolStr] {
+        &self.derived_vars
+    }
+
     pub fn is_scope_var<K>(&self, var: &K, scope_id: u32) -> bool
     where
         SmolStr: Borrow<K>,
@@ -111,7 +160,11 @@ impl DeclaredVariables {
     pub fn len(&self) -> usize {
         self.vars.len()
             + self.arrow_exprs.len()
-            + self.scopes.values().map(|s| s.env.len()).sum::<usize>()
+            + self
+                .scopes
+                .values()
+                .map(|s| s.env.len() + usize::from(s.index.is_some()))
+                .sum::<usize>()
             + self.bindings.len()
             + self.reactive_blocks.len()
     }
@@ -131,6 +184,12 @@ impl DeclaredVariables {
                 .values_mut()
                 .chain(self.arrow_exprs.values_mut().map(|(id, _)| id))
                 .chain(self.bindings.values_mut())
+                .chain(self.reactive_blocks.values_mut())
+                .chain(
+                    self.scopes
+                        .values_mut()
+                        .flat_map(|scope| scope.env.values_mut().chain(&mut scope.index)),
+                )
                 .filter(|id| **id > removed_id)
             {
                 *id -= 1;
@@ -156,21 +215,69 @@ impl DeclaredVariables {
     pub fn css_mustaches(&self) -> &HashMap<SyntaxNode, u32> {
         &self.css_mustaches
     }
+
+    /// Gets the CSS mustaches whose custom properties are set on the root elements of the
+    /// `{#for}` block with `scope_id`. With a `scope_id` of `None`, this gets the CSS mustaches
+    /// that are set on the target of the component instead.
+    pub fn css_mustaches_in_scope(
+        &self,
+        scope_id: Option<u32>,
+    ) -> impl Iterator<Item = (&SyntaxNode, &u32)> {
+        self.css_mustaches
+            .iter()
+            .filter(move |(_, id)| match (self.css_scopes.get(id), scope_id) {
+                (None, None) => true,
+                (Some(scopes), Some(scope_id)) => scopes.contains(&scope_id),
+                _ => false,
+            })
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 #[cfg_attr(test, derive(Serialize))]
 pub struct Scope {
     env: HashMap<SmolStr, u32>,
+    /// The name given to each item of the `{#for}` block.
+    item: SmolStr,
+    /// The variable that the `{#for}` block iterates over, if it iterates over one directly.
+    /// Bindings to the items write back to it.
+    source: Option<SmolStr>,
+    /// The id that holds the index of the current item. It is only allocated when an item itself
+    /// is bound to, since the binding replaces the item in its array.
+    index: Option<u32>,
 }
 
 impl Scope {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the scope of a `{#for}` block that names its items `item`, and iterates over the
+    /// variable `source`, if any.
+    pub fn with_item(item: SmolStr, source: Option<SmolStr>) -> Self {
         Self {
-            env: HashMap::new(),
+            item,
+            source,
+            ..Default::default()
         }
     }
 
+    pub fn item(&self) -> &str {
+        &self.item
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    pub fn index(&self) -> Option<u32> {
+        self.index
+    }
+
+    pub fn set_index(&mut self, id: u32) {
+        self.index = Some(id);
+    }
+
     pub fn get<K>(&self, k: &K) -> Option<u32>
     where
         SmolStr: Borrow<K>,
@@ -3,19 +3,26 @@ mod fragment;
 mod globals;
 mod passes;
 
-use std::path::Path;
+use std::{io, ops::Range, path::PathBuf};
 
-#[cfg(not(debug_assertions))]
-use rand::Rng;
+use decorous_errors::{DiagnosticBuilder, Helper, Severity};
 use rslint_parser::{
     ast::{ArrowExpr, Decl, ExportDecl, FnDecl, ImportDecl, LabelledStmt, VarDecl},
     AstNode, SmolStr, SyntaxNode, SyntaxNodeExt,
 };
 
 use crate::{
-    ast::{Attribute, Code, DecorousAst, Node, NodeIter, NodeType, SpecialBlock},
-    component::passes::{DepAnalysisPass, IsolateCssPass, Pass, StaticPass},
+    ast::{
+        Attribute, Code, DecorousAst, Node, NodeIter, NodeType, Script, ScriptOffsets,
+        SpecialBlock, UseBlock,
+    },
+    component::passes::{
+        CfgBlocksPass, DeadBranchPass, DepAnalysisPass, EnvPass, ExpandUsesPass, IsolateCssPass,
+        LintPass, Pass, StaticBlocksPass, StaticPass, TranslatePass, UnusedUsesPass,
+        ValidateHtmlPass,
+    },
     css::ast::Css,
+    lint::Lint,
     location::Location,
     utils, Ctx,
 };
@@ -29,7 +36,7 @@ pub struct Component<'a> {
     pub toplevel_nodes: Vec<ToplevelNodeData>,
     pub hoist: Vec<SyntaxNode>,
     pub exports: Vec<SmolStr>,
-    pub uses: Vec<&'a Path>,
+    pub uses: Vec<UseBlock<'a>>,
     pub css: Option<Css>,
     pub wasm: Option<Code<'a>>,
     pub comptime: Option<Code<'a>>,
@@ -37,6 +44,9 @@ pub struct Component<'a> {
 
     ctx: Ctx<'a>,
     current_id: u32,
+    /// Where the script is in the source, for mapping script nodes back to it.
+    script_offsets: ScriptOffsets,
+    denied_lints: usize,
 }
 
 #[derive(Debug)]
@@ -55,12 +65,11 @@ impl<'a> Component<'a> {
             hoist: vec![],
             exports: vec![],
             current_id: 0,
-            #[cfg(not(debug_assertions))]
-            component_id: rand::thread_rng().gen(),
-            #[cfg(debug_assertions)]
-            component_id: 0,
+            component_id: ctx.component_ids.component_id(ctx.errs.source()),
             uses: vec![],
             ctx,
+            script_offsets: ScriptOffsets::new(0),
+            denied_lints: 0,
 
             css: None,
             comptime: None,
@@ -72,19 +81,69 @@ impl<'a> Component<'a> {
     }
 
     pub fn run_passes(&mut self) -> anyhow::Result<()> {
-        let isolate_pass = IsolateCssPass::new();
-        let static_pass = StaticPass::new();
-        let dep_pass = DepAnalysisPass::new();
-        isolate_pass.run(self)?;
-        static_pass.run(self)?;
-        dep_pass.run(self)?;
+        self.run_passes_with(|_, run| run())
+    }
+
+    /// Runs the passes like [`Component::run_passes`], calling `wrap` with the name of each pass
+    /// and a function that runs it, like to time them.
+    pub fn run_passes_with<F>(&mut self, mut wrap: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&'static str, &mut dyn FnMut() -> anyhow::Result<()>) -> anyhow::Result<()>,
+    {
+        wrap("cfg_blocks", &mut || CfgBlocksPass::new().run(self))?;
+        wrap("translate", &mut || TranslatePass::new().run(self))?;
+        wrap("lint", &mut || LintPass::new().run(self))?;
+        wrap("validate_html", &mut || ValidateHtmlPass::new().run(self))?;
+        wrap("isolate_css", &mut || IsolateCssPass::new().run(self))?;
+        wrap("env", &mut || EnvPass::new().run(self))?;
+        wrap("run_static", &mut || StaticPass::new().run(self))?;
+        wrap("dead_branches", &mut || DeadBranchPass::new().run(self))?;
+        wrap("static_blocks", &mut || StaticBlocksPass::new().run(self))?;
+        wrap("dep_analysis", &mut || DepAnalysisPass::new().run(self))?;
+        wrap("unused_uses", &mut || UnusedUsesPass::new().run(self))?;
+
+        anyhow::ensure!(
+            self.denied_lints == 0,
+            "could not compile due to {} denied lint{}",
+            self.denied_lints,
+            if self.denied_lints == 1 { "" } else { "s" }
+        );
 
         Ok(())
     }
 
+    /// Replaces the `{#use}` declarations that use many components with one for each component
+    /// that `expand` finds for them, emitting errors for the ones that can't be expanded.
+    pub fn expand_uses<F>(&mut self, expand: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&UseBlock) -> io::Result<Vec<PathBuf>>,
+    {
+        ExpandUsesPass::new(expand).run(self)
+    }
+
     pub fn descendents(&'a self) -> NodeIter<'a, FragmentMetadata> {
         NodeIter::new(&self.fragment_tree)
     }
+
+    /// Checks if the script of the component uses top-level `await`, in which case the component
+    /// has to be initialized asynchronously.
+    pub fn is_async(&self) -> bool {
+        self.toplevel_nodes
+            .iter()
+            .any(|toplevel| utils::has_toplevel_await(&toplevel.node))
+    }
+
+    /// Where the script node `node`, like one of [`Component::hoist`], is in the source.
+    pub fn script_span(&self, node: &SyntaxNode) -> Range<usize> {
+        self.script_offsets.source_range(node.text_range())
+    }
+
+    /// Gets the name of the CSS custom property that holds the value of the CSS mustache with
+    /// `mustache_id`. The name contains the component id, so that custom properties of different
+    /// components can't clash on a shared ancestor.
+    pub fn css_var_name(&self, mustache_id: u32) -> String {
+        format!("--decor-{}-{mustache_id}", self.component_id)
+    }
 }
 
 // Private methods of Component
@@ -99,15 +158,25 @@ impl<'a> Component<'a> {
         self.build_fragment_tree(ast.nodes);
     }
 
-    fn extract_toplevel_data(&mut self, script: SyntaxNode) {
+    fn extract_toplevel_data(&mut self, script: Script) {
+        self.script_offsets = script.offsets.clone();
+        let mut derived = vec![];
         // Only go to top level assignments
-        for child in script.children() {
+        for child in script.node.children() {
             if let Some(var_decl) = child.try_to::<VarDecl>() {
-                for decl in var_decl.declared() {
-                    let idents = utils::get_idents_from_pattern(decl.pattern().unwrap());
-                    for ident in idents {
-                        self.declared_vars.insert_var(ident);
-                    }
+                let Some(patterns) = var_decl
+                    .declared()
+                    .map(|decl| decl.pattern())
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    self.emit_skipped(&script, &child, "variable declaration");
+                    continue;
+                };
+                for ident in patterns
+                    .into_iter()
+                    .flat_map(utils::get_idents_from_pattern)
+                {
+                    self.declared_vars.insert_var(ident);
                 }
                 self.toplevel_nodes.push(ToplevelNodeData {
                     node: child,
@@ -115,6 +184,7 @@ impl<'a> Component<'a> {
                 });
             } else if let Some(fn_decl) = child.try_to::<FnDecl>() {
                 let Some(ident) = fn_decl.name().and_then(|name| name.ident_token()) else {
+                    self.emit_skipped(&script, &child, "function declaration");
                     continue;
                 };
 
@@ -127,14 +197,18 @@ impl<'a> Component<'a> {
                 if let Some(decl) = child.try_to::<ExportDecl>().and_then(|exp| exp.decl()) {
                     match decl {
                         Decl::FnDecl(decl) => {
-                            let tok = decl.name().unwrap().ident_token().unwrap();
-                            let name = tok.text();
-                            self.exports.push(name.clone());
+                            let Some(tok) = decl.name().and_then(|name| name.ident_token()) else {
+                                self.emit_skipped(&script, &child, "exported function");
+                                continue;
+                            };
+                            self.exports.push(tok.text().clone());
                         }
                         Decl::ClassDecl(decl) => {
-                            let tok = decl.name().unwrap().ident_token().unwrap();
-                            let name = tok.text();
-                            self.exports.push(name.clone());
+                            let Some(tok) = decl.name().and_then(|name| name.ident_token()) else {
+                                self.emit_skipped(&script, &child, "exported class");
+                                continue;
+                            };
+                            self.exports.push(tok.text().clone());
                         }
                         Decl::VarDecl(decl) => {
                             for pat in decl.declared().filter_map(|d| d.pattern()) {
@@ -146,7 +220,11 @@ impl<'a> Component<'a> {
                 }
                 self.hoist.push(child);
             } else if let Some(labl_stmt) = child.try_to::<LabelledStmt>() {
-                if labl_stmt.label().unwrap().ident_token().unwrap().text() != "$" {
+                let Some(label) = labl_stmt.label().and_then(|name| name.ident_token()) else {
+                    self.emit_skipped(&script, &child, "labelled statement");
+                    continue;
+                };
+                if label.text() != "$" {
                     self.toplevel_nodes.push(ToplevelNodeData {
                         node: child,
                         substitute_assign_refs: false,
@@ -156,6 +234,7 @@ impl<'a> Component<'a> {
                 let Some(stmt) = labl_stmt.stmt() else {
                     continue;
                 };
+                derived.extend(utils::get_assigned_var(stmt.syntax()));
 
                 self.toplevel_nodes.push(ToplevelNodeData {
                     node: stmt.syntax().clone(),
@@ -170,6 +249,39 @@ impl<'a> Component<'a> {
                 });
             }
         }
+
+        // Variables that reactive blocks assign to without declaring them are declared for them,
+        // so they can be used like any other
+        for var in derived {
+            if self.declared_vars.get_var(&var, None).is_none() && !self.exports.contains(&var) {
+                self.declared_vars.insert_derived_var(var);
+            }
+        }
+    }
+
+    /// Reports `diagnostic` for `lint` at the level it's configured to. Denied lints make
+    /// [`Component::run_passes`] fail once all passes have run.
+    fn emit_lint(&mut self, lint: Lint, diagnostic: DiagnosticBuilder) {
+        if self.ctx.lints.emit(&self.ctx.errs, lint, diagnostic) {
+            self.denied_lints += 1;
+        }
+    }
+
+    /// Emits a diagnostic for a script node that is left out of the component, because it's
+    /// missing a name.
+    fn emit_skipped(&self, script: &Script, node: &SyntaxNode, what: &str) {
+        let span = script.offsets.source_range(node.text_range());
+        let start = span.start;
+        self.ctx.errs.emit(
+            DiagnosticBuilder::new(format!("{what} is missing a name"), start)
+                .severity(Severity::Warning)
+                .note("this statement is left out of the component")
+                .add_helper(Helper {
+                    msg: "no name found here".into(),
+                    span,
+                })
+                .build(),
+        );
     }
 
     fn build_fragment_tree(&mut self, ast: Vec<Node<'a, Location>>) {
@@ -220,8 +332,19 @@ impl<'a> Component<'a> {
                             }
                         }
                         Attribute::Binding(binding) => {
-                            let name = SmolStr::new(binding);
-                            self.declared_vars.insert_binding(name);
+                            // Bindings to the items of a `{#for}` block are made for each item,
+                            // when it is rendered
+                            if let Some(scope) = scope_stack
+                                .last_mut()
+                                .filter(|scope| scope.item() == binding.root())
+                            {
+                                if !binding.is_field() && scope.index().is_none() {
+                                    scope.set_index(self.declared_vars.generate_id());
+                                }
+                                continue;
+                            }
+                            let name = SmolStr::new(binding.var);
+                            self.declared_vars.insert_binding(name, binding.kind);
                         }
                         Attribute::KeyValue(_, _) => continue,
                     }
@@ -244,7 +367,10 @@ impl<'a> Component<'a> {
                     }
                 }
                 SpecialBlock::For(for_block) => {
-                    scope_stack.push(Scope::new());
+                    scope_stack.push(Scope::with_item(
+                        SmolStr::new(for_block.binding),
+                        utils::get_plain_var(&for_block.expr),
+                    ));
                     let var_id = self.declared_vars.generate_id();
                     for scope in scope_stack.iter_mut() {
                         scope.add(SmolStr::new(for_block.binding), var_id);
@@ -255,7 +381,24 @@ impl<'a> Component<'a> {
                     let scope = scope_stack.pop().unwrap();
                     self.declared_vars.insert_scope(id, scope);
                 }
-                SpecialBlock::Use(use_block) => self.uses.push(use_block.path),
+                SpecialBlock::Head(head_block) => {
+                    head_block.inner.iter_mut().for_each(|child| {
+                        self.get_special_vars(child, Some(id), scope_stack);
+                    });
+                }
+                // The block isn't rendered as a node, so its contents are in the parent
+                SpecialBlock::Static(static_block) => {
+                    static_block.inner.iter_mut().for_each(|child| {
+                        self.get_special_vars(child, parent_id, scope_stack);
+                    });
+                }
+                SpecialBlock::Cfg(cfg_block) => {
+                    cfg_block.inner.iter_mut().for_each(|child| {
+                        self.get_special_vars(child, parent_id, scope_stack);
+                    });
+                }
+                SpecialBlock::Use(use_block) => self.uses.push(use_block.clone()),
+                SpecialBlock::Raw(_) | SpecialBlock::Html(_) | SpecialBlock::Translate(_) => {}
             },
 
             _ => {}
@@ -271,13 +414,17 @@ impl<'a> Component<'a> {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{borrow::Cow, collections::HashMap, path::Path};
 
     use decorous_errors::Source;
     use itertools::Itertools;
 
     use super::*;
-    use crate::Parser;
+    use crate::{
+        ast::traverse,
+        lint::{Level, LintLevels},
+        FixedId, HashedIds, Parser,
+    };
 
     fn make_component(source: &str) -> Component<'_> {
         let parser = Parser::new(source);
@@ -289,6 +436,7 @@ mod tests {
                     src: source,
                     name: "TEST".to_owned(),
                 }),
+                component_ids: &FixedId(0),
                 ..Default::default()
             },
         );
@@ -333,6 +481,41 @@ mod tests {
         insta::assert_debug_snapshot!(component.hoist);
     }
 
+    #[test]
+    fn removes_dead_branches_of_constant_if_blocks() {
+        let component = make_component(
+            "#div {#if 1 + 1 === 2} #p:live {#if false} dead {:else} {#if x} kept {/if} {/if} {:else} #p:dead {/if} /div",
+        );
+        insta::assert_debug_snapshot!(component.fragment_tree);
+    }
+
+    #[test]
+    fn static_blocks_must_be_known_at_compile_time() {
+        for source in [
+            "---js let x = 0; --- {#static} #p {x} /p {/static}",
+            "---js let x = 0; --- {#static} #button[@click={() => x += 1}]:Add {/static}",
+            "---js let x = 0; --- {#static} #input[:x:]/input {/static}",
+            "---js let x = 0; --- {#static} #p[class={x}] hi /p {/static}",
+            "---js let x = 0; --- {#static} {#if x} hi {/if} {/static}",
+            "{#static} {#for i in [1, 2]} {#html i} {/for} {/static}",
+        ] {
+            assert!(
+                run_with_lints(source, LintLevels::default()).is_err(),
+                "{source} should not be static"
+            );
+        }
+        for source in [
+            "{#static} #p[class={\"a\" + 1}] {1 + 1} /p {/static}",
+            "{#static} {#for i in [1, 2]} {#if i > 1} {i} {/if} {/for} {/static}",
+            "{#for i in [1, 2]} {i} {/for} {#static} #p hi /p {/static}",
+        ] {
+            assert!(
+                run_with_lints(source, LintLevels::default()).is_ok(),
+                "{source} should be static"
+            );
+        }
+    }
+
     #[test]
     fn can_extract_closures_from_html() {
         let component = make_component("#button[@click={() => console.log(\"hello\")}]/button");
@@ -369,6 +552,14 @@ mod tests {
         assert!(component.hoist.is_empty());
     }
 
+    #[test]
+    fn does_not_hoist_var_if_incremented_in_markup() {
+        let component = make_component(
+            "---js let count = 0; --- #button[@click={() => count++}] {count} /button",
+        );
+        assert!(component.hoist.is_empty());
+    }
+
     #[test]
     fn hoists_var_if_never_mutated() {
         let component = make_component("---js let x = 0--- {x}");
@@ -393,12 +584,73 @@ mod tests {
         insta::assert_debug_snapshot!(component.fragment_tree);
     }
 
+    #[test]
+    fn component_ids_are_hashes_of_the_source() {
+        let id = |name: &str, src: &str| {
+            let component = Component::new(
+                Parser::new(src).parse().unwrap(),
+                Ctx {
+                    errs: decorous_errors::stderr(Source {
+                        src,
+                        name: name.to_owned(),
+                    }),
+                    component_ids: &HashedIds,
+                    ..Default::default()
+                },
+            );
+            component.component_id
+        };
+        let src = "---css p { color: red; } --- #p:Hi";
+        assert_eq!(id("input.decor", src), id("input.decor", src));
+        assert_ne!(id("input.decor", src), id("other.decor", src));
+        assert_ne!(id("input.decor", src), id("input.decor", "#p:Hi"));
+    }
+
     #[test]
     fn modifies_css_selectors_to_use_component_id() {
         let component = make_component("---css p:has(span) { color: red; } ---");
         insta::assert_debug_snapshot!(component.css);
     }
 
+    #[test]
+    fn modifies_css_selectors_in_nested_at_rules() {
+        let component = make_component(
+            "---css @supports (display: grid) { @media screen { p { display: grid; } } } ---",
+        );
+        assert_eq!(
+            "@supports (display: grid)  { @media screen  { p.decor-0 { display: grid; } } }",
+            component.css.unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn flattens_nested_css_rules() {
+        let component = make_component(
+            "---css div, span { color: red; &:hover, &.active { color: blue; } p { @media screen { color: green; a { margin: 0; } } } a { & > b { margin: 0; } } } ---",
+        );
+        assert_eq!(
+            "div.decor-0, span.decor-0 { color: red; }\ndiv.decor-0:hover, div.active.decor-0, span.decor-0:hover, span.active.decor-0 { color: blue; }\n@media screen  { div.decor-0 p.decor-0, span.decor-0 p.decor-0 { color: green; } div.decor-0 p.decor-0 a.decor-0, span.decor-0 p.decor-0 a.decor-0 { margin: 0; } }\ndiv.decor-0 a.decor-0 >.decor-0 b.decor-0, span.decor-0 a.decor-0 >.decor-0 b.decor-0 { margin: 0; }",
+            component.css.unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn renames_keyframes_to_use_component_id() {
+        let component = make_component(
+            "---css p { animation: 1s spin infinite, 2s -global-fade; } @keyframes spin { from { rotate: 0deg; } } @keyframes -global-fade { from { opacity: 0; } } ---",
+        );
+        assert_eq!(
+            "p.decor-0 { animation: 1s spin-decor-0 infinite, 2s fade; }\n@keyframes spin-decor-0 { from { rotate: 0deg; } }\n@keyframes fade { from { opacity: 0; } }",
+            component.css.unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn assigns_ids_to_mustaches_in_keyframes() {
+        let component = make_component("---css @keyframes fade { from { opacity: {start}; } } ---");
+        assert_eq!(1, component.declared_vars.css_mustaches().len());
+    }
+
     #[test]
     fn assigns_ids_to_mustaches_in_css() {
         let component = make_component("---css p { color: {color}; } ---");
@@ -444,6 +696,17 @@ mod tests {
         insta::assert_debug_snapshot!(component.toplevel_nodes);
     }
 
+    #[test]
+    fn reactive_blocks_can_declare_derived_vars() {
+        let component = make_component("---js let count = 0; $: doubled = count * 2; $: quadrupled = doubled * 2; $: count = 1; $: total += 1; --- #button[@click={() => count += 1}] {quadrupled} /button");
+        assert_eq!(
+            component.declared_vars.derived_vars(),
+            ["doubled", "quadrupled"]
+        );
+        assert!(component.declared_vars.get_var("doubled", None).is_some());
+        assert!(component.declared_vars.get_var("total", None).is_none());
+    }
+
     #[test]
     fn globals_are_not_subject_to_hoist_optimizations() {
         let component = make_component(
@@ -452,6 +715,40 @@ mod tests {
         insta::assert_debug_snapshot!(component);
     }
 
+    #[test]
+    fn does_not_hoist_dependents_of_unhoisted_decls() {
+        let component = make_component(
+            "---js let z = document.getElementById(\"id\"); const f = () => z; --- {f}",
+        );
+        assert!(component.hoist.is_empty());
+    }
+
+    #[test]
+    fn does_not_hoist_decls_used_before_initialization() {
+        let component = make_component("---js console.log(x); let x = 1; --- {x}");
+        assert!(component.hoist.is_empty());
+        let component =
+            make_component("---js console.log(f()); const f = () => x; const x = 1; --- {f}");
+        assert!(component.hoist.is_empty());
+    }
+
+    #[test]
+    fn does_not_hoist_redeclared_vars() {
+        let component = make_component("---js var x = 1; var x = 2; --- {x}");
+        assert!(component.hoist.is_empty());
+    }
+
+    #[test]
+    fn hoists_pure_decls() {
+        let component = make_component(
+            "---js const x = [1, { a: 2 }]; let y = `${x}!`; const f = () => console.log(x); let g = !y; --- {x}{y}{f}{g}",
+        );
+        assert_eq!(4, component.hoist.len());
+        let component =
+            make_component("---js const f = () => x; const x = 1; console.log(f()); --- {f}");
+        assert_eq!(2, component.hoist.len());
+    }
+
     #[test]
     fn checks_all_edges_relating_to_dependents_of_hoist_graph() {
         let component = make_component(
@@ -476,4 +773,502 @@ mod tests {
         assert!(component.toplevel_nodes.is_empty());
         insta::assert_debug_snapshot!(component.hoist);
     }
+
+    #[test]
+    fn keeps_uses_rendered_in_nested_blocks() {
+        let component = make_component(
+            "{#use \"./hello-world.decor\"} {#if true} #hello-world /hello-world {/if}",
+        );
+        assert_eq!(
+            vec![Path::new("./hello-world.decor")],
+            component
+                .uses
+                .iter()
+                .map(|use_block| &*use_block.path)
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn uses_are_named_after_their_alias() {
+        let component =
+            make_component("{#use \"./long/path/fancy-widget.decor\" as widget} #widget /widget");
+        assert_eq!(Some("widget".into()), component.uses[0].name());
+        let component =
+            make_component("{#use \"./fancy-widget.decor\"} #fancy-widget /fancy-widget");
+        assert_eq!(Some("fancy_widget".into()), component.uses[0].name());
+    }
+
+    #[test]
+    fn globs_match_the_components_in_their_directory() {
+        let glob = UseBlock {
+            path: Cow::Borrowed(Path::new("./components/*-button.decor")),
+            alias: None,
+        };
+        assert!(glob.is_glob());
+        assert_eq!(None, glob.name());
+        assert!(glob.matches(Path::new("./components/fancy-button.decor")));
+        assert!(glob.matches(Path::new("./components/-button.decor")));
+        assert!(glob.matches(Path::new("pages/./components/fancy-button.decor")));
+        assert!(!glob.matches(Path::new("./components/button.decor")));
+        assert!(!glob.matches(Path::new("./components/nested/fancy-button.decor")));
+        assert!(!glob.matches(Path::new("./fancy-button.decor")));
+    }
+
+    #[test]
+    fn cfg_blocks_are_only_kept_for_enabled_features() {
+        let source = "#nav #a Home /a {#cfg feature=\"admin\"} {#use \"./admin.decor\"} #admin /admin {/cfg} /nav";
+        let build = |features: &[String]| {
+            let mut c = Component::new(
+                Parser::new(source).parse().unwrap(),
+                Ctx {
+                    errs: decorous_errors::stderr(Source {
+                        src: source,
+                        name: "TEST".to_owned(),
+                    }),
+                    component_ids: &FixedId(0),
+                    features,
+                    ..Default::default()
+                },
+            );
+            c.run_passes().unwrap();
+            let nav = match &c.fragment_tree[0].node_type {
+                NodeType::Element(elem) => elem.children.len(),
+                _ => panic!("expected the nav element"),
+            };
+            (nav, c.uses.len())
+        };
+
+        // The contents of the enabled block are put in its place
+        assert_eq!((5, 1), build(&["admin".to_owned()]));
+        assert_eq!((2, 0), build(&[]));
+    }
+
+    #[test]
+    fn env_vars_are_read_when_compiling() {
+        let env = HashMap::from([("API_URL".to_owned(), "https://api.test".to_owned())]);
+        let build = |source| {
+            let mut c = Component::new(
+                Parser::new(source).parse().unwrap(),
+                Ctx {
+                    errs: decorous_errors::stderr(Source {
+                        src: source,
+                        name: "TEST".to_owned(),
+                    }),
+                    component_ids: &FixedId(0),
+                    env: &env,
+                    ..Default::default()
+                },
+            );
+            c.run_passes().map(|_| c.toplevel_nodes[0].node.to_string())
+        };
+
+        assert_eq!(
+            "let $env = {\"API_URL\": \"https://api.test\"};",
+            build("#a[href={$env.API_URL}] {$env.API_URL} /a").unwrap()
+        );
+        assert!(build("#p {$env.SECRET} /p").is_err());
+        assert!(build("#p {$env} /p").is_err());
+    }
+
+    #[test]
+    fn translations_are_replaced_with_their_messages() {
+        let source = "#h1 {@t \"greeting\"} /h1 {#if x} #p {@t \"farewell\"} /p {/if}";
+        let messages = HashMap::from([("greeting".to_owned(), "Bonjour".to_owned())]);
+        let mut c = Component::new(
+            Parser::new(source).parse().unwrap(),
+            Ctx {
+                errs: decorous_errors::stderr(Source {
+                    src: source,
+                    name: "TEST".to_owned(),
+                }),
+                component_ids: &FixedId(0),
+                messages: &messages,
+                ..Default::default()
+            },
+        );
+        c.run_passes().unwrap();
+
+        let mut mustaches = vec![];
+        traverse(&c.fragment_tree, &mut |node| {
+            if let NodeType::Mustache(js) = &node.node_type {
+                mustaches.push(js.text().to_string());
+            }
+        });
+        // Untranslated keys fall back to the key
+        assert_eq!(vec!["\"Bonjour\"", "\"farewell\""], mustaches);
+    }
+
+    #[test]
+    fn unused_uses_are_only_dropped_when_asked_to() {
+        let source = "{#use \"./hello.decor\"} {#use \"./used.decor\"} #used/used";
+        let build = |drop_unused_uses| {
+            let mut c = Component::new(
+                Parser::new(source).parse().unwrap(),
+                Ctx {
+                    errs: decorous_errors::stderr(Source {
+                        src: source,
+                        name: "TEST".to_owned(),
+                    }),
+                    component_ids: &FixedId(0),
+                    drop_unused_uses,
+                    ..Default::default()
+                },
+            );
+            c.run_passes().unwrap();
+            c.uses
+                .iter()
+                .map(|use_block| use_block.path.to_path_buf())
+                .collect_vec()
+        };
+
+        assert_eq!(
+            vec![
+                PathBuf::from("./hello.decor"),
+                PathBuf::from("./used.decor")
+            ],
+            build(false)
+        );
+        assert_eq!(vec![PathBuf::from("./used.decor")], build(true));
+    }
+
+    #[test]
+    fn unused_uses_are_warned_about() {
+        let source = "{#use \"./hello.decor\"} {#use \"./used.decor\"} #used/used";
+        let component = make_component(source);
+        let diagnostics = component
+            .ctx
+            .errs
+            .take_diagnostics()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.msg.starts_with("unused use declaration"))
+            .collect_vec();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!("unused use declaration: ./hello.decor", diagnostics[0].msg);
+        assert_eq!(Severity::Warning, diagnostics[0].severity);
+    }
+
+    #[test]
+    fn diagnostics_point_into_the_script_block_they_are_in() {
+        let source = "---js let x = 0; --- #p {x} /p ---js let unused = 1; ---";
+        let component = make_component(source);
+        let diagnostic = component
+            .ctx
+            .errs
+            .take_diagnostics()
+            .into_iter()
+            .find(|diagnostic| diagnostic.msg == "unused variable: unused")
+            .unwrap();
+        assert_eq!(source.find("let unused").unwrap(), diagnostic.offset);
+    }
+
+    /// Makes a component from a script that may have syntax errors. The parser rejects those, but
+    /// the AST can still be built by hand.
+    fn make_component_from_script(script: &str) -> Component<'_> {
+        let ast = DecorousAst {
+            nodes: vec![],
+            script: Some(Script {
+                node: rslint_parser::parse_module(script, 0).syntax(),
+                offsets: ScriptOffsets::new(0),
+            }),
+            css: None,
+            wasm: None,
+            comptime: None,
+        };
+        Component::new(
+            ast,
+            Ctx {
+                errs: decorous_errors::stderr(Source {
+                    src: script,
+                    name: "TEST".to_owned(),
+                }),
+                component_ids: &FixedId(0),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn skips_unnamed_exports() {
+        let component = make_component_from_script(
+            "export function () {} export class {} export function f() {} export class C {}",
+        );
+        assert_eq!(vec!["f", "C"], component.exports);
+    }
+
+    #[test]
+    fn skips_unnamed_declarations() {
+        let component = make_component_from_script("function () {} let x = 1;");
+        assert_eq!(Some(0), component.declared_vars.get_var("x", None));
+        assert_eq!(1, component.toplevel_nodes.len());
+    }
+
+    #[test]
+    fn assigns_css_mustaches_to_innermost_for_block_that_binds_them() {
+        let component = make_component(
+            "---css p { color: {row}; background: {cell}; margin: {m}; } --- {#for row in rows} {#for cell in row} #p:Hi {/for} {/for}",
+        );
+        let vars = &component.declared_vars;
+        let in_scope = |scope| {
+            vars.css_mustaches_in_scope(scope)
+                .map(|(_, id)| *id)
+                .sorted()
+                .collect_vec()
+        };
+        assert_eq!(vec![2], in_scope(None));
+        assert_eq!(vec![0], in_scope(Some(0)));
+        assert_eq!(vec![1], in_scope(Some(1)));
+    }
+
+    #[test]
+    fn can_build_component_from_preparsed_syntax_nodes() {
+        use crate::{
+            ast::{DecorousAst, Mustache, Node, NodeType},
+            location::Location,
+        };
+
+        let script = rslint_parser::parse_module("let x = 0; setTimeout(() => x = 1);", 0).syntax();
+        let mustache = rslint_parser::parse_module("x", 0)
+            .syntax()
+            .first_child()
+            .unwrap();
+        let ast = DecorousAst::builder()
+            .script(script)
+            .node(Node::new(
+                NodeType::Mustache(Mustache::from(mustache)),
+                Location::default(),
+            ))
+            .build();
+        let mut component = Component::new(ast, Ctx::default());
+        component.run_passes().unwrap();
+
+        assert_eq!(1, component.fragment_tree.len());
+        assert!(component.declared_vars.all_vars().contains_key("x"));
+    }
+
+    fn run_with_lints(source: &str, lints: LintLevels) -> anyhow::Result<Component<'_>> {
+        let ast = Parser::new(source).parse().unwrap();
+        let mut c = Component::new(
+            ast,
+            Ctx {
+                errs: decorous_errors::stderr(Source {
+                    src: source,
+                    name: "TEST".to_owned(),
+                }),
+                component_ids: &FixedId(0),
+                lints,
+                ..Default::default()
+            },
+        );
+        c.run_passes()?;
+        Ok(c)
+    }
+
+    #[test]
+    fn denied_lints_fail_the_component() {
+        let deny_all = || {
+            Lint::ALL
+                .into_iter()
+                .map(|lint| (lint, Level::Deny))
+                .collect()
+        };
+        for source in [
+            "---js let x = 0; --- #p hello /p",
+            "#p {x} /p",
+            "#div[class=\"a\" class=\"b\"]/div",
+            "---js const x = \"\"; --- #input[:x:]/input",
+            "---js let x = \"\"; --- #div[:x:]/div",
+            "---js let x = \"\"; --- #div[:text:x:]/div",
+            "---js let x = \"\"; --- {#html x}",
+        ] {
+            assert!(
+                run_with_lints(source, deny_all()).is_err(),
+                "{source} should fail"
+            );
+        }
+
+        let clean = "---js let x = \"\"; --- #input[:x:]/input #p[contenteditable :html:x:]/p {document.title}";
+        assert!(run_with_lints(clean, deny_all()).is_ok());
+    }
+
+    #[test]
+    fn allowed_lints_are_not_reported() {
+        let lints = [
+            (Lint::UnusedVariable, Level::Allow),
+            (Lint::UnboundVariable, Level::Deny),
+        ]
+        .into_iter()
+        .collect::<LintLevels>();
+        assert!(run_with_lints("---js let x = 0; --- #p hello /p", lints.clone()).is_ok());
+        assert!(run_with_lints("#p {x} /p", lints).is_err());
+
+        let lints = [
+            (Lint::UnsanitizedHtml, Level::Allow),
+            (Lint::UnusedVariable, Level::Deny),
+        ]
+        .into_iter()
+        .collect::<LintLevels>();
+        assert!(run_with_lints("---js let x = \"\"; --- {#html x}", lints).is_ok());
+    }
+
+    #[test]
+    fn components_with_too_many_variables_are_linted() {
+        let source = |vars: usize| {
+            let decls = (0..vars).map(|i| format!("let v{i} = 0;")).join(" ");
+            let assigns = (0..vars).map(|i| format!("v{i} = 1;")).join(" ");
+            let sum = (0..vars).map(|i| format!("v{i}")).join(" + ");
+            format!("---js {decls} --- #button[@click={{() => {{ {assigns} }}}}] {{{sum}}} /button")
+        };
+        let deny = || {
+            [(Lint::TooManyVariables, Level::Deny)]
+                .into_iter()
+                .collect()
+        };
+        // The closure of the button is a reactive value too
+        assert!(run_with_lints(&source(1023), deny()).is_ok());
+        assert!(run_with_lints(&source(1024), deny()).is_err());
+    }
+
+    #[test]
+    fn invalid_html_is_linted() {
+        let deny_html = || {
+            [
+                Lint::InvalidNesting,
+                Lint::VoidElementChildren,
+                Lint::DuplicateId,
+            ]
+            .into_iter()
+            .map(|lint| (lint, Level::Deny))
+            .collect()
+        };
+        for source in [
+            "#p #div hi /div /p",
+            "#div #li item /li /div",
+            "#a[href=\"/\"] #span #a[href=\"/\"] nested /a /span /a",
+            "#img hi /img",
+            "#p[id=\"x\"]/p #div #p[id=\"x\"]/p /div",
+            "{#for i in [1, 2]} #p[id=\"x\"]/p {/for}",
+        ] {
+            assert!(
+                run_with_lints(source, deny_html()).is_err(),
+                "{source} should fail"
+            );
+        }
+
+        for source in [
+            "#ul #li item /li /ul",
+            "#li mounted into a list by the user /li",
+            "#p #span hi /span /p",
+            "#table #tr #td cell /td /tr /table",
+            "#img/img #br   /br",
+            "{#if true} #p[id=\"x\"]/p {:else} #p[id=\"x\"]/p {/if}",
+        ] {
+            assert!(
+                run_with_lints(source, deny_html()).is_ok(),
+                "{source} should pass"
+            );
+        }
+    }
+
+    #[test]
+    fn void_elements_with_children_are_denied_by_default() {
+        assert!(run_with_lints("#img hi /img", LintLevels::new()).is_err());
+        assert!(run_with_lints("#img/img #br   /br", LintLevels::new()).is_ok());
+        let allow = [(Lint::VoidElementChildren, Level::Allow)]
+            .into_iter()
+            .collect();
+        assert!(run_with_lints("#img hi /img", allow).is_ok());
+    }
+
+    #[test]
+    fn bindings_to_undeclared_variables_are_errors() {
+        assert!(run_with_lints("#input[:x:]/input", LintLevels::default()).is_err());
+        assert!(run_with_lints(
+            "{#for todo in todos} #input[:todo.text:]/input {/for}",
+            LintLevels::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn bindings_to_items_of_for_blocks_write_to_their_variable() {
+        let component = make_component(
+            "---js let todos = []; let tags = []; --- {#for todo in todos} #input[:todo.text:]/input {/for} {#for tag in tags} #input[:tag:]/input {/for}",
+        );
+        assert!(component.declared_vars.all_bindings().is_empty());
+        let scopes = component
+            .declared_vars
+            .all_scopes()
+            .values()
+            .map(|scope| (scope.item(), scope.source(), scope.index().is_some()))
+            .sorted()
+            .collect_vec();
+        assert_eq!(
+            vec![("tag", Some("tags"), true), ("todo", Some("todos"), false)],
+            scopes
+        );
+
+        assert!(run_with_lints(
+            "---js let todos = []; --- {#for todo in todos.slice(1)} #input[:todo.text:]/input {/for}",
+            LintLevels::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn fields_of_constants_can_be_bound_to() {
+        let deny = || {
+            [(Lint::InvalidBindingTarget, Level::Deny)]
+                .into_iter()
+                .collect()
+        };
+        assert!(run_with_lints(
+            "---js const user = { name: \"\" }; --- #input[:user.name:]/input",
+            deny()
+        )
+        .is_ok());
+        assert!(run_with_lints("#input[:user.name:]/input", deny()).is_err());
+    }
+
+    #[test]
+    fn keeps_variables_used_in_blocks_and_statements() {
+        let component = make_component(
+            "---js let x = 0; let y = 1; console.log(y); setTimeout(() => x = 1); --- {#if true} #p {x} /p {/if}",
+        );
+        assert!(component.declared_vars.all_vars().contains_key("x"));
+        assert_eq!(
+            1,
+            component
+                .hoist
+                .iter()
+                .filter(|node| node.text().to_string().contains("let y"))
+                .count()
+        );
+    }
+
+    #[test]
+    fn passes_can_be_wrapped() {
+        let source = "#p:Hi";
+        let mut component = Component::new(
+            Parser::new(source).parse().unwrap(),
+            Ctx {
+                errs: decorous_errors::stderr(Source {
+                    src: source,
+                    name: "TEST".to_owned(),
+                }),
+                component_ids: &FixedId(0),
+                ..Default::default()
+            },
+        );
+        let mut ran = vec![];
+        component
+            .run_passes_with(|name, run| {
+                ran.push(name);
+                run()
+            })
+            .unwrap();
+        assert_eq!(11, ran.len());
+        assert_eq!(Some(&"cfg_blocks"), ran.first());
+        assert_eq!(Some(&"unused_uses"), ran.last());
+    }
 }
@@ -0,0 +1,84 @@
+use std::{collections::HashSet, mem};
+
+use crate::{
+    ast::{Node, NodeType, SpecialBlock},
+    component::passes::{unused_uses::collect, Pass},
+    Component, FragmentMetadata,
+};
+
+/// Removes `{#cfg}` blocks whose feature isn't enabled, along with everything in them. The
+/// contents of enabled blocks take the place of the block, so later passes never see one.
+pub struct CfgBlocksPass;
+
+impl CfgBlocksPass {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Pass for CfgBlocksPass {
+    fn run(self, component: &mut Component) -> anyhow::Result<()> {
+        let nodes = mem::take(&mut component.fragment_tree);
+        let mut pruned = false;
+        component.fragment_tree = prune(nodes, component.ctx.features, &mut pruned);
+
+        // Components used in disabled blocks aren't used at all
+        if pruned {
+            let mut use_locations = Vec::new();
+            collect(
+                &component.fragment_tree,
+                &mut HashSet::new(),
+                &mut use_locations,
+            );
+            component.uses = use_locations
+                .into_iter()
+                .map(|(use_block, _)| use_block)
+                .collect();
+        }
+
+        Ok(())
+    }
+}
+
+fn prune<'a>(
+    nodes: Vec<Node<'a, FragmentMetadata>>,
+    features: &[String],
+    pruned: &mut bool,
+) -> Vec<Node<'a, FragmentMetadata>> {
+    let mut out = Vec::with_capacity(nodes.len());
+    for mut node in nodes {
+        match &mut node.node_type {
+            NodeType::SpecialBlock(SpecialBlock::Cfg(cfg_block)) => {
+                if !features.iter().any(|feature| feature == cfg_block.feature) {
+                    *pruned = true;
+                    continue;
+                }
+                // The contents are already in the parent of the block
+                out.extend(prune(mem::take(&mut cfg_block.inner), features, pruned));
+                continue;
+            }
+            NodeType::SpecialBlock(SpecialBlock::If(if_block)) => {
+                if_block.inner = prune(mem::take(&mut if_block.inner), features, pruned);
+                if let Some(else_block) = if_block.else_block.take() {
+                    if_block.else_block = Some(prune(else_block, features, pruned));
+                }
+            }
+            NodeType::SpecialBlock(SpecialBlock::For(for_block)) => {
+                for_block.inner = prune(mem::take(&mut for_block.inner), features, pruned);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Head(head_block)) => {
+                head_block.inner = prune(mem::take(&mut head_block.inner), features, pruned);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Static(static_block)) => {
+                static_block.inner = prune(mem::take(&mut static_block.inner), features, pruned);
+            }
+            NodeType::Element(elem) => {
+                elem.children = prune(mem::take(&mut elem.children), features, pruned);
+            }
+            _ => {}
+        }
+        out.push(node);
+    }
+
+    out
+}
@@ -0,0 +1,79 @@
+use std::mem;
+
+use crate::{
+    ast::{Node, NodeType, SpecialBlock},
+    component::passes::Pass,
+    fold, Component, DeclaredVariables, FragmentMetadata,
+};
+
+/// Removes the dead branch of `{#if}` blocks whose condition is known at compile time. The live
+/// branch takes the place of the block in the fragment tree, so no code is generated for the
+/// block itself.
+pub struct DeadBranchPass;
+
+impl DeadBranchPass {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Pass for DeadBranchPass {
+    fn run(self, component: &mut Component) -> anyhow::Result<()> {
+        let nodes = mem::take(&mut component.fragment_tree);
+        component.fragment_tree = eliminate(nodes, &component.declared_vars);
+
+        Ok(())
+    }
+}
+
+fn eliminate<'a>(
+    nodes: Vec<Node<'a, FragmentMetadata>>,
+    vars: &DeclaredVariables,
+) -> Vec<Node<'a, FragmentMetadata>> {
+    let mut out = Vec::with_capacity(nodes.len());
+    for mut node in nodes {
+        match &mut node.node_type {
+            NodeType::SpecialBlock(SpecialBlock::If(if_block)) => {
+                let scope = node.metadata.scope();
+                let Some(cond) = fold::fold(&if_block.expr, &|name| {
+                    vars.get_constant(name, scope).cloned()
+                }) else {
+                    if_block.inner = eliminate(mem::take(&mut if_block.inner), vars);
+                    if let Some(else_block) = if_block.else_block.take() {
+                        if_block.else_block = Some(eliminate(else_block, vars));
+                    }
+                    out.push(node);
+                    continue;
+                };
+
+                let live = if cond.is_truthy() {
+                    mem::take(&mut if_block.inner)
+                } else {
+                    if_block.else_block.take().unwrap_or_default()
+                };
+                let parent_id = node.metadata.parent_id();
+                out.extend(eliminate(live, vars).into_iter().map(|mut child| {
+                    child.metadata.set_parent_id(parent_id);
+                    child
+                }));
+                continue;
+            }
+            NodeType::SpecialBlock(SpecialBlock::For(for_block)) => {
+                for_block.inner = eliminate(mem::take(&mut for_block.inner), vars);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Head(head_block)) => {
+                head_block.inner = eliminate(mem::take(&mut head_block.inner), vars);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Static(static_block)) => {
+                static_block.inner = eliminate(mem::take(&mut static_block.inner), vars);
+            }
+            NodeType::Element(elem) => {
+                elem.children = eliminate(mem::take(&mut elem.children), vars);
+            }
+            _ => {}
+        }
+        out.push(node);
+    }
+
+    out
+}
@@ -1,7 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use petgraph::{graph::NodeIndex, Direction, Graph};
-use rslint_parser::{ast::Decl, AstNode, SmolStr, SyntaxNode};
+use rslint_parser::{
+    ast::{
+        ArrowExpr, Decl, Expr, ExprOrSpread, FnDecl, FnExpr, Getter, Method, NameRef, ObjectProp,
+        PropName, Setter, UnaryOp,
+    },
+    AstNode, SmolStr, SyntaxNode, SyntaxNodeExt,
+};
 use smallvec::SmallVec;
 
 use crate::utils;
@@ -13,7 +19,6 @@ pub struct DepGraph {
     // Directed graph from variable declarations to their dependents (NOT dependencies)
     graph: Graph<Declaration, ()>,
     var_lookup: HashMap<SmolStr, NodeIndex>,
-    unbound: Vec<SmolStr>,
 }
 
 #[derive(Debug, Clone)]
@@ -22,6 +27,9 @@ pub struct Declaration {
     pub declared_vars: SmallVec<[SmolStr; 1]>,
     pub mutated: bool,
     pub used: bool,
+    /// Whether the declaration has to stay where it is written. Moving it could change when its
+    /// initializer runs, or turn a use before its initialization into a valid one.
+    pub pinned: bool,
 }
 
 impl DepGraph {
@@ -38,6 +46,7 @@ impl DepGraph {
                 declared_vars: SmallVec::new(),
                 mutated: false,
                 used: false,
+                pinned: false,
             };
             let idx = graph.add_node(node);
             match decl {
@@ -50,8 +59,9 @@ impl DepGraph {
                     }
                 }
                 Decl::FnDecl(fn_decl) => {
-                    let name = fn_decl.name().unwrap();
-                    let tok = name.ident_token().unwrap();
+                    let Some(tok) = fn_decl.name().and_then(|name| name.ident_token()) else {
+                        continue;
+                    };
                     let ident = tok.text();
                     var_lookup.insert(ident.clone(), idx);
                     graph[idx].declared_vars.push(ident.clone());
@@ -60,37 +70,36 @@ impl DepGraph {
             }
         }
 
-        let mut s = Self {
-            graph,
-            var_lookup,
-            unbound: vec![],
-        };
+        let mut s = Self { graph, var_lookup };
         s.compute_edges();
+        s.compute_pins();
         s
     }
 
     pub fn mark_used(&mut self, ident: &str) -> bool {
         let target = self.var_lookup.get(ident);
         let Some(target) = target else {
-            self.unbound.push(SmolStr::new(ident));
             return false;
         };
         self.mark_neighbors_used(*target);
         true
     }
 
-    pub fn mark_used_from_node(&mut self, node: &SyntaxNode) {
-        for unbound in utils::get_unbound_refs(node) {
-            let tok = unbound.ident_token().unwrap();
-            let ident = tok.text();
-            self.mark_used(ident);
-        }
+    /// Marks every variable referenced in `node` as used. Returns the references to variables
+    /// that aren't declared in the graph.
+    pub fn mark_used_from_node(&mut self, node: &SyntaxNode) -> Vec<NameRef> {
+        utils::get_unbound_refs(node)
+            .into_iter()
+            .filter(|unbound| {
+                let tok = unbound.ident_token().unwrap();
+                !self.mark_used(tok.text())
+            })
+            .collect()
     }
 
     pub fn mark_mutated(&mut self, ident: &str) -> bool {
         let target = self.var_lookup.get(ident);
         let Some(target) = target else {
-            self.unbound.push(SmolStr::new(ident));
             return false;
         };
         self.mark_neighbors_mutated(*target);
@@ -100,7 +109,7 @@ impl DepGraph {
     pub fn mark_mutated_from_node(&mut self, node: &SyntaxNode) {
         for unbound in utils::get_unbound_refs(node)
             .iter()
-            .filter(|unbd| utils::is_from_assignment(unbd))
+            .filter(|unbd| utils::is_from_assignment(unbd) || utils::get_mutation(unbd).is_some())
         {
             let tok = unbound.ident_token().unwrap();
             let ident = tok.text();
@@ -108,6 +117,30 @@ impl DepGraph {
         }
     }
 
+    /// Pins every declaration that is referenced in `nodes` before it is initialized. `nodes`
+    /// should be all of the toplevel nodes of the script, in the order they were written.
+    pub fn pin_early_refs<'a>(&mut self, nodes: impl IntoIterator<Item = &'a SyntaxNode>) {
+        let mut initialized = HashSet::new();
+        for node in nodes {
+            for nref in get_eager_refs(node) {
+                let Some(tok) = nref.ident_token() else {
+                    continue;
+                };
+                let Some(target) = self.var_lookup.get(tok.text()) else {
+                    continue;
+                };
+                self.pin_uninitialized_deps(*target, &initialized, &mut HashSet::new());
+            }
+            if let Some(idx) = self
+                .graph
+                .node_indices()
+                .find(|idx| self.graph[*idx].decl.syntax() == node)
+            {
+                initialized.insert(idx);
+            }
+        }
+    }
+
     pub fn get_unmutated(&self) -> impl Iterator<Item = &Declaration> + '_ {
         self.graph.raw_nodes().iter().filter_map(|node| {
             if !node.weight.mutated {
@@ -118,6 +151,12 @@ impl DepGraph {
         })
     }
 
+    /// Get the declarations that are safe to hoist: they are never mutated, and moving them before
+    /// the rest of the script doesn't change the behavior of the script.
+    pub fn get_hoistable(&self) -> impl Iterator<Item = &Declaration> + '_ {
+        self.get_unmutated().filter(|decl| !decl.pinned)
+    }
+
     pub fn get_unused(&self) -> impl Iterator<Item = &Declaration> + '_ {
         self.graph.raw_nodes().iter().filter_map(|node| {
             if !node.weight.used {
@@ -128,10 +167,6 @@ impl DepGraph {
         })
     }
 
-    pub fn get_unbound(&self) -> &[SmolStr] {
-        &self.unbound
-    }
-
     fn mark_neighbors_mutated(&mut self, target: NodeIndex) {
         self.graph[target].mutated = true;
         self.graph[target].used = true;
@@ -153,6 +188,121 @@ impl DepGraph {
         }
     }
 
+    fn mark_neighbors_pinned(&mut self, target: NodeIndex) {
+        if self.graph[target].pinned {
+            return;
+        }
+        self.graph[target].pinned = true;
+        let mut edges = self.graph.neighbors(target).detach();
+        while let Some(i) = edges.next_node(&self.graph) {
+            self.mark_neighbors_pinned(i);
+        }
+    }
+
+    /// Pins `target` and everything it depends on that hasn't been initialized yet.
+    fn pin_uninitialized_deps(
+        &mut self,
+        target: NodeIndex,
+        initialized: &HashSet<NodeIndex>,
+        visited: &mut HashSet<NodeIndex>,
+    ) {
+        if !visited.insert(target) {
+            return;
+        }
+        if !initialized.contains(&target) {
+            self.mark_neighbors_pinned(target);
+        }
+        let mut edges = self
+            .graph
+            .neighbors_directed(target, Direction::Incoming)
+            .detach();
+        while let Some(i) = edges.next_node(&self.graph) {
+            self.pin_uninitialized_deps(i, initialized, visited);
+        }
+    }
+
+    fn compute_pins(&mut self) {
+        let mut var_count = HashMap::<&SmolStr, usize>::new();
+        for decl in self.graph.node_weights() {
+            if let Decl::VarDecl(var_decl) = &decl.decl {
+                if var_decl.var_token().is_some() {
+                    for var in &decl.declared_vars {
+                        *var_count.entry(var).or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        let pinned = self
+            .graph
+            .node_indices()
+            .filter(|i| {
+                let decl = &self.graph[*i];
+                match &decl.decl {
+                    // `var`s can be declared multiple times, so which declaration ends up
+                    // initializing the variable depends on the order they're in
+                    Decl::VarDecl(var_decl) => {
+                        decl.declared_vars
+                            .iter()
+                            .any(|var| var_count.get(var).is_some_and(|count| *count > 1))
+                            || var_decl
+                                .declared()
+                                .filter_map(|declarator| declarator.value())
+                                .any(|value| !self.is_pure(&value))
+                    }
+                    _ => false,
+                }
+            })
+            .collect::<Vec<_>>();
+        for i in pinned {
+            self.mark_neighbors_pinned(i);
+        }
+    }
+
+    /// Checks if evaluating `expr` has no side effects and doesn't depend on anything other than
+    /// the variables in this graph.
+    fn is_pure(&self, expr: &Expr) -> bool {
+        let pure = |expr: Option<Expr>| expr.is_some_and(|expr| self.is_pure(&expr));
+        let pure_key = |key: Option<PropName>| !matches!(key, Some(PropName::Computed(_)) | None);
+
+        match expr {
+            Expr::Literal(_) | Expr::ArrowExpr(_) | Expr::FnExpr(_) => true,
+            Expr::NameRef(nref) => nref
+                .ident_token()
+                .is_some_and(|tok| self.var_lookup.contains_key(tok.text())),
+            Expr::Template(template) => {
+                template.tag().is_none() && template.elements().all(|elem| pure(elem.expr()))
+            }
+            Expr::ArrayExpr(array) => array.elements().all(|elem| match elem {
+                ExprOrSpread::Expr(expr) => self.is_pure(&expr),
+                ExprOrSpread::Spread(_) => false,
+            }),
+            Expr::ObjectExpr(object) => object.props().all(|prop| match prop {
+                ObjectProp::LiteralProp(prop) => pure_key(prop.key()) && pure(prop.value()),
+                ObjectProp::IdentProp(prop) => prop
+                    .name()
+                    .and_then(|name| name.ident_token())
+                    .is_some_and(|tok| self.var_lookup.contains_key(tok.text())),
+                ObjectProp::Method(method) => pure_key(method.name()),
+                ObjectProp::Getter(getter) => pure_key(getter.key()),
+                ObjectProp::Setter(setter) => pure_key(setter.key()),
+                ObjectProp::SpreadProp(_) | ObjectProp::InitializedProp(_) => false,
+            }),
+            Expr::GroupingExpr(group) => pure(group.inner()),
+            Expr::UnaryExpr(unary) => {
+                !matches!(
+                    unary.op(),
+                    Some(
+                        UnaryOp::Delete | UnaryOp::Await | UnaryOp::Increment | UnaryOp::Decrement
+                    ) | None
+                ) && pure(unary.expr())
+            }
+            Expr::BinExpr(bin) => pure(bin.lhs()) && pure(bin.rhs()),
+            Expr::CondExpr(cond) => pure(cond.test()) && pure(cond.cons()) && pure(cond.alt()),
+            _ => false,
+        }
+    }
+
     fn compute_edges(&mut self) {
         for i in self.graph.node_indices() {
             let decl = &self.graph[i];
@@ -168,3 +318,28 @@ impl DepGraph {
         }
     }
 }
+
+/// Get the variable references in `node` that are evaluated as soon as `node` runs, meaning the
+/// ones that aren't inside of a function body.
+fn get_eager_refs(node: &SyntaxNode) -> Vec<NameRef> {
+    if node.is::<FnDecl>() {
+        return vec![];
+    }
+    let mut refs = vec![];
+    node.descendants_with(&mut |descendant| {
+        if descendant.is::<FnDecl>()
+            || descendant.is::<FnExpr>()
+            || descendant.is::<ArrowExpr>()
+            || descendant.is::<Method>()
+            || descendant.is::<Getter>()
+            || descendant.is::<Setter>()
+        {
+            return false;
+        }
+        if let Some(nref) = descendant.try_to::<NameRef>() {
+            refs.push(nref);
+        }
+        true
+    });
+    refs
+}
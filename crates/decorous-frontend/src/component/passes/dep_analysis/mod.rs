@@ -1,17 +1,27 @@
 mod dep_graph;
 
-use decorous_errors::{DiagnosticBuilder, Severity};
+use std::collections::HashSet;
+
+use decorous_errors::{DiagnosticBuilder, Helper};
 use itertools::Itertools;
-use rslint_parser::{ast::Decl, AstNode, SyntaxNodeExt};
+use rslint_parser::{
+    ast::{ClassDecl, Decl, ImportDecl, Name, NameRef},
+    AstNode, SmolStr, SyntaxNode, SyntaxNodeExt,
+};
 
 use super::Pass;
 use crate::{
-    ast::{Attribute, AttributeValue, NodeType, SpecialBlock},
+    ast::{traverse, Attribute, AttributeValue, NodeType, SpecialBlock},
     component::globals::GLOBALS,
-    Component,
+    lint::Lint,
+    utils, Component,
 };
 use dep_graph::DepGraph;
 
+/// The number of reactive values, like variables and closures, that a component can have
+/// before it is linted for having too many.
+const MAX_VALUES: usize = 1024;
+
 #[derive(Debug)]
 pub struct DepAnalysisPass;
 
@@ -31,52 +41,160 @@ impl Pass for DepAnalysisPass {
                 .collect_vec(),
         );
 
-        for node in component.descendents() {
+        let mut unbound = vec![];
+        let mut block_bindings = HashSet::new();
+        traverse(&component.fragment_tree, &mut |node| {
+            let offset = node.metadata.location().offset();
             match &node.node_type {
                 NodeType::Element(elem) => {
+                    // Attributes don't have locations, so their references point to the tag name
+                    // of the element
+                    let head = offset..offset + elem.tag.len();
+                    let mut mark = |graph: &mut DepGraph, js: &SyntaxNode| {
+                        unbound.extend(
+                            mark_js(graph, js)
+                                .into_iter()
+                                .map(|nref| (nref, head.clone())),
+                        );
+                    };
+                    if let Some(tag_expr) = &elem.tag_expr {
+                        mark(&mut graph, tag_expr);
+                    }
                     for attr in &elem.attrs {
                         match attr {
                             Attribute::Binding(binding) => {
-                                // Bindings are mutable
-                                graph.mark_mutated(binding);
+                                // Bindings are mutable. Bindings to the items of a `{#for}`
+                                // block write to the variable it iterates over
+                                let source = node
+                                    .metadata
+                                    .scope()
+                                    .and_then(|scope| {
+                                        component.declared_vars.all_scopes().get(&scope)
+                                    })
+                                    .filter(|scope| scope.item() == binding.root())
+                                    .and_then(|scope| scope.source());
+                                graph.mark_mutated(source.unwrap_or(binding.root()));
                             }
                             Attribute::EventHandler(evt_handler) => {
-                                graph.mark_used_from_node(&evt_handler.expr);
-                                graph.mark_mutated_from_node(&evt_handler.expr);
+                                mark(&mut graph, &evt_handler.expr);
                             }
                             Attribute::KeyValue(_, Some(AttributeValue::JavaScript(js))) => {
-                                graph.mark_used_from_node(js);
-                                graph.mark_mutated_from_node(js);
+                                mark(&mut graph, js);
                             }
                             Attribute::KeyValue(_, _) => {}
                         }
                     }
                 }
                 NodeType::SpecialBlock(SpecialBlock::If(block)) => {
-                    graph.mark_used_from_node(&block.expr);
-                    graph.mark_mutated_from_node(&block.expr);
+                    let head = offset..offset + "if".len();
+                    unbound.extend(
+                        mark_js(&mut graph, &block.expr)
+                            .into_iter()
+                            .map(|nref| (nref, head.clone())),
+                    );
+                }
+                NodeType::SpecialBlock(SpecialBlock::Html(block)) => {
+                    let head = offset..offset + "html".len();
+                    unbound.extend(
+                        mark_js(&mut graph, &block.expr)
+                            .into_iter()
+                            .map(|nref| (nref, head.clone())),
+                    );
                 }
                 NodeType::SpecialBlock(SpecialBlock::For(block)) => {
-                    graph.mark_used_from_node(&block.expr);
-                    graph.mark_mutated_from_node(&block.expr);
+                    let head = offset..offset + "for".len();
+                    unbound.extend(
+                        mark_js(&mut graph, &block.expr)
+                            .into_iter()
+                            .map(|nref| (nref, head.clone())),
+                    );
+                    block_bindings.insert(SmolStr::new(block.binding));
+                    block_bindings.extend(block.index.map(SmolStr::new));
                 }
                 NodeType::Mustache(js) => {
-                    graph.mark_used_from_node(js);
-                    graph.mark_mutated_from_node(js);
+                    // Skip the opening brace
+                    let start = offset + 1;
+                    unbound.extend(mark_js(&mut graph, js).into_iter().map(|nref| {
+                        let range = nref.syntax().text_range();
+                        let span =
+                            start + usize::from(range.start())..start + usize::from(range.end());
+                        (nref, span)
+                    }));
                 }
                 NodeType::Text(_)
                 | NodeType::Comment(_)
-                | NodeType::SpecialBlock(SpecialBlock::Use(_)) => {}
+                | NodeType::SpecialBlock(
+                    SpecialBlock::Use(_)
+                    | SpecialBlock::Raw(_)
+                    | SpecialBlock::Head(_)
+                    | SpecialBlock::Static(_)
+                    | SpecialBlock::Cfg(_)
+                    | SpecialBlock::Translate(_),
+                ) => {}
             }
-        }
+        });
 
         for mustache in component.declared_vars.css_mustaches().keys() {
-            graph.mark_used_from_node(mustache);
-            graph.mark_mutated_from_node(mustache);
+            // CSS mustaches can use the bindings of {#for} blocks, which aren't declared in the
+            // script
+            for unbound in utils::get_unbound_refs(mustache) {
+                let Some(tok) = unbound.ident_token() else {
+                    continue;
+                };
+                let is_binding = component
+                    .declared_vars
+                    .all_scopes()
+                    .values()
+                    .any(|scope| scope.get(tok.text()).is_some());
+                if is_binding {
+                    continue;
+                }
+                graph.mark_used(tok.text());
+                if utils::is_from_assignment(&unbound) {
+                    graph.mark_mutated(tok.text());
+                }
+            }
         }
 
         for toplevel in &component.toplevel_nodes {
             graph.mark_mutated_from_node(&toplevel.node);
+            // Statements that aren't declarations always run, so everything they use is needed
+            if !toplevel.node.is::<Decl>() {
+                graph.mark_used_from_node(&toplevel.node);
+            }
+        }
+        for node in &component.hoist {
+            graph.mark_used_from_node(node);
+        }
+        graph.pin_early_refs(
+            component
+                .toplevel_nodes
+                .iter()
+                .map(|toplevel| &toplevel.node),
+        );
+
+        let script_names = script_names(component);
+        let mut reported = HashSet::new();
+        for (nref, span) in unbound {
+            let Some(tok) = nref.ident_token() else {
+                continue;
+            };
+            let name = tok.text();
+            if GLOBALS.contains(&name.as_str())
+                || script_names.contains(name)
+                || block_bindings.contains(name)
+                || !reported.insert((name.clone(), span.start))
+            {
+                continue;
+            }
+            component.emit_lint(
+                Lint::UnboundVariable,
+                DiagnosticBuilder::new(format!("possibly unbound variable: {name}"), span.start)
+                    .add_helper(Helper {
+                        msg: format!("`{name}` is not declared in the script").into(),
+                        span,
+                    }),
+            );
         }
 
         for v in graph.get_unused() {
@@ -89,9 +207,33 @@ impl Pass for DepAnalysisPass {
                 .position(|node| &node.node == v.decl.syntax())
                 .expect("VarDecl should be in toplevel nodes");
             component.toplevel_nodes.remove(pos);
+            // Values of comptime code blocks aren't declared in the script
+            if v.declared_vars
+                .iter()
+                .any(|var| component.declared_vars.get_constant(var, None).is_some())
+            {
+                continue;
+            }
+
+            let span = component
+                .script_offsets
+                .source_range(v.decl.syntax().text_range());
+            let start = span.start;
+            component.emit_lint(
+                Lint::UnusedVariable,
+                DiagnosticBuilder::new(
+                    format!("unused variable: {}", v.declared_vars.join(", ")),
+                    start,
+                )
+                .note("the declaration is left out of the component")
+                .add_helper(Helper {
+                    msg: "never used in the script or the template".into(),
+                    span,
+                }),
+            );
         }
 
-        for v in graph.get_unmutated() {
+        for v in graph.get_hoistable() {
             for var in &v.declared_vars {
                 component.declared_vars.remove_var(var);
             }
@@ -106,18 +248,68 @@ impl Pass for DepAnalysisPass {
             component.hoist.push(v.decl.syntax().clone());
         }
 
-        for unbound in graph
-            .get_unbound()
-            .iter()
-            .filter(|v| !GLOBALS.contains(&v.as_str()))
-        {
-            component.ctx.errs.emit(
-                DiagnosticBuilder::new(format!("possibly unbound variable: {unbound}"), 0)
-                    .severity(Severity::Warning)
-                    .build(),
+        let values = component.declared_vars.len();
+        if values > MAX_VALUES {
+            // Every update checks which of the values changed
+            let start = component.script_offsets.source(0);
+            component.emit_lint(
+                Lint::TooManyVariables,
+                DiagnosticBuilder::new(
+                    format!("{values} reactive values will slow down updates"),
+                    start,
+                )
+                .add_helper(Helper {
+                    msg: format!(
+                        "keep it under {MAX_VALUES} by splitting the component with `{{#use}}`"
+                    )
+                    .into(),
+                    span: start..start,
+                }),
             );
         }
 
         Ok(())
     }
 }
+
+/// Marks the variables that `js` uses and mutates in `graph`. Returns the references to
+/// variables that aren't in `graph`.
+fn mark_js(graph: &mut DepGraph, js: &SyntaxNode) -> Vec<NameRef> {
+    graph.mark_mutated_from_node(js);
+    graph.mark_used_from_node(js)
+}
+
+/// Gets every name that the script declares at its top level, including the ones that aren't
+/// tracked by the dependency graph, like classes and imports.
+fn script_names(component: &Component) -> HashSet<SmolStr> {
+    let mut names = component
+        .declared_vars
+        .all_vars()
+        .keys()
+        .cloned()
+        .collect::<HashSet<_>>();
+    names.extend(component.exports.iter().cloned());
+    for node in component
+        .toplevel_nodes
+        .iter()
+        .map(|toplevel| &toplevel.node)
+        .chain(&component.hoist)
+    {
+        if let Some(class) = node.try_to::<ClassDecl>() {
+            names.extend(
+                class
+                    .name()
+                    .and_then(|name| name.ident_token())
+                    .map(|tok| tok.text().clone()),
+            );
+        } else if node.is::<ImportDecl>() {
+            names.extend(
+                node.descendants()
+                    .filter_map(|desc| desc.try_to::<Name>())
+                    .filter_map(|name| name.ident_token())
+                    .map(|tok| tok.text().clone()),
+            );
+        }
+    }
+    names
+}
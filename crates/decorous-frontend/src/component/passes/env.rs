@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use decorous_errors::{DiagnosticBuilder, Helper, Severity};
+use itertools::Itertools;
+use rslint_parser::{
+    ast::{DotExpr, NameRef},
+    AstNode, SmolStr, SyntaxNode, SyntaxNodeExt, TextRange,
+};
+
+use crate::{
+    ast::{traverse, Attribute, AttributeValue, NodeType, SpecialBlock},
+    component::passes::Pass,
+    fold::Const,
+    Component, ToplevelNodeData,
+};
+
+/// The object whose properties are read from the environment.
+const ENV: &str = "$env";
+
+/// Replaces `$env.NAME` in the script and the template with the value of the variable `NAME`,
+/// looked up when the component is compiled. The variables that are read are declared as one
+/// constant object, so they're folded into the output like the values of comptime code blocks.
+pub struct EnvPass;
+
+impl EnvPass {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Pass for EnvPass {
+    fn run(self, component: &mut Component) -> anyhow::Result<()> {
+        // The script can declare its own `$env`
+        if component.declared_vars.all_vars().contains_key(ENV) {
+            return Ok(());
+        }
+
+        let mut refs = vec![];
+        let offsets = &component.script_offsets;
+        for toplevel in &component.toplevel_nodes {
+            find_refs(&toplevel.node, &mut refs, |range| {
+                offsets.source_range(range)
+            });
+        }
+        traverse(&component.fragment_tree, &mut |node| {
+            let offset = node.metadata.location().offset();
+            // Only mustaches know where their JavaScript is, so references in everything else
+            // point to the start of the node
+            let head = |len: usize| move |_: TextRange| offset..offset + len;
+            match &node.node_type {
+                NodeType::Element(elem) => {
+                    let js = elem.attrs.iter().filter_map(|attr| match attr {
+                        Attribute::EventHandler(handler) => Some(&handler.expr),
+                        Attribute::KeyValue(_, Some(AttributeValue::JavaScript(js))) => Some(js),
+                        _ => None,
+                    });
+                    for js in elem.tag_expr.iter().chain(js) {
+                        find_refs(js, &mut refs, head(elem.tag.len()));
+                    }
+                }
+                NodeType::SpecialBlock(SpecialBlock::If(block)) => {
+                    find_refs(&block.expr, &mut refs, head("if".len()));
+                }
+                NodeType::SpecialBlock(SpecialBlock::For(block)) => {
+                    find_refs(&block.expr, &mut refs, head("for".len()));
+                }
+                NodeType::SpecialBlock(SpecialBlock::Html(block)) => {
+                    find_refs(&block.expr, &mut refs, head("html".len()));
+                }
+                NodeType::Mustache(js) => {
+                    // Skip the opening brace
+                    let start = offset + 1;
+                    find_refs(js, &mut refs, |range| {
+                        start + usize::from(range.start())..start + usize::from(range.end())
+                    });
+                }
+                _ => {}
+            }
+        });
+        if refs.is_empty() {
+            return Ok(());
+        }
+
+        let mut values = BTreeMap::new();
+        let mut has_errors = false;
+        for (name, span) in refs {
+            let Some(name) = name else {
+                has_errors = true;
+                component.ctx.errs.emit(
+                    DiagnosticBuilder::new(format!("{ENV} can only be read from"), span.start)
+                        .severity(Severity::Error)
+                        .add_helper(Helper {
+                            msg: format!("read a variable with `{ENV}.NAME`").into(),
+                            span,
+                        })
+                        .build(),
+                );
+                continue;
+            };
+            if values.contains_key(&name) {
+                continue;
+            }
+            match component.ctx.env.get(&name) {
+                Some(value) => {
+                    values.insert(name, value);
+                }
+                None => {
+                    has_errors = true;
+                    component.ctx.errs.emit(
+                        DiagnosticBuilder::new(
+                            format!("environment variable {name} is not defined"),
+                            span.start,
+                        )
+                        .severity(Severity::Error)
+                        .note("variables are read when the component is compiled")
+                        .add_helper(Helper {
+                            msg: "used here".into(),
+                            span,
+                        })
+                        .build(),
+                    );
+                }
+            }
+        }
+        anyhow::ensure!(!has_errors, "could not read environment variables");
+
+        let object = values
+            .iter()
+            .map(|(name, value)| format!("{:?}: {value:?}", name.as_str()))
+            .join(", ");
+        let syntax_node = rslint_parser::parse_text(&format!("let {ENV} = {{{object}}};"), 0);
+        component.toplevel_nodes.insert(
+            0,
+            ToplevelNodeData {
+                node: syntax_node.syntax(),
+                substitute_assign_refs: true,
+            },
+        );
+        component.declared_vars.insert_constant(
+            SmolStr::new(ENV),
+            Const::Object(
+                values
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), Const::String(value)))
+                    .collect(),
+            ),
+        );
+
+        Ok(())
+    }
+}
+
+/// Finds the references to [`ENV`] in `js`, along with the name of the variable that each one
+/// reads, if it reads one. `span` gives where a reference is in the source of the component.
+fn find_refs(
+    js: &SyntaxNode,
+    refs: &mut Vec<(Option<SmolStr>, Range<usize>)>,
+    span: impl Fn(TextRange) -> Range<usize>,
+) {
+    for nref in js.descendants().filter_map(|node| node.try_to::<NameRef>()) {
+        if nref.ident_token().map_or(true, |tok| tok.text() != ENV) {
+            continue;
+        }
+        let Some(dot) = nref
+            .syntax()
+            .parent()
+            .and_then(|parent| parent.try_to::<DotExpr>())
+            .filter(|dot| {
+                dot.object()
+                    .is_some_and(|object| object.syntax() == nref.syntax())
+            })
+        else {
+            refs.push((None, span(nref.syntax().text_range())));
+            continue;
+        };
+        let name = dot
+            .prop()
+            .and_then(|prop| prop.ident_token())
+            .map(|tok| tok.text().clone());
+        refs.push((name, span(dot.syntax().text_range())));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{borrow::Cow, collections::HashSet, io, path::PathBuf};
+
+use decorous_errors::{DiagnosticBuilder, Severity};
+
+use crate::{
+    ast::UseBlock,
+    component::passes::{unused_uses::collect, Pass},
+    Component,
+};
+
+/// Replaces the `{#use}` declarations of [`Component::uses`] that use many components, like
+/// `{#use "./components/*.decor"}`, with one declaration for each component that they match.
+/// The components are found by `expand`, since the frontend never reads the filesystem.
+pub struct ExpandUsesPass<F> {
+    expand: F,
+}
+
+impl<F> ExpandUsesPass<F>
+where
+    F: FnMut(&UseBlock) -> io::Result<Vec<PathBuf>>,
+{
+    pub fn new(expand: F) -> Self {
+        Self { expand }
+    }
+}
+
+impl<F> Pass for ExpandUsesPass<F>
+where
+    F: FnMut(&UseBlock) -> io::Result<Vec<PathBuf>>,
+{
+    fn run(mut self, component: &mut Component) -> anyhow::Result<()> {
+        if !component.uses.iter().any(UseBlock::is_glob) {
+            return Ok(());
+        }
+
+        let mut use_locations = Vec::new();
+        collect(
+            &component.fragment_tree,
+            &mut HashSet::new(),
+            &mut use_locations,
+        );
+
+        let mut has_errors = false;
+        let mut uses = Vec::with_capacity(component.uses.len());
+        for use_block in &component.uses {
+            let Some((dir, _)) = use_block.glob() else {
+                uses.push(use_block.clone());
+                continue;
+            };
+            let offset = use_locations
+                .iter()
+                .find(|(used, _)| used == use_block)
+                .map_or(0, |(_, offset)| *offset);
+            let mut error = |msg: String, note: String| {
+                has_errors = true;
+                component.ctx.errs.emit(
+                    DiagnosticBuilder::new(msg, offset)
+                        .severity(Severity::Error)
+                        .note(note)
+                        .build(),
+                );
+            };
+
+            if use_block.alias.is_some() {
+                error(
+                    format!("{} uses many components", use_block.path.display()),
+                    "only a single component can be given an alias".to_owned(),
+                );
+                continue;
+            }
+            match (self.expand)(use_block) {
+                Ok(paths) if paths.is_empty() => error(
+                    format!("no components match {}", use_block.path.display()),
+                    "every .decor file that matches the path is used".to_owned(),
+                ),
+                // Components that are also used on their own are only used once
+                Ok(paths) => uses.extend(
+                    paths
+                        .into_iter()
+                        .filter(|path| !component.uses.iter().any(|used| *used.path == **path))
+                        .map(|path| UseBlock {
+                            path: Cow::Owned(path),
+                            alias: None,
+                        }),
+                ),
+                Err(err) => error(format!("could not read {}", dir.display()), err.to_string()),
+            }
+        }
+        anyhow::ensure!(
+            !has_errors,
+            "could not expand the paths of {{#use}} declarations"
+        );
+        component.uses = uses;
+
+        Ok(())
+    }
+}
@@ -1,36 +1,125 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap, mem};
+
+use rslint_parser::SmolStr;
 
 use crate::{
-    ast::{traverse_mut, Attribute, AttributeValue, NodeType},
+    ast::{traverse_mut, Attribute, AttributeValue, Node, NodeType, SpecialBlock},
     component::passes::Pass,
-    css::ast::{RegularRule, Rule, Value},
-    Component, DeclaredVariables,
+    css::ast::{Declaration, RegularRule, Rule, Selector, Value},
+    utils, Component, DeclaredVariables, FragmentMetadata,
 };
 
+/// Keyframes with names that start with this prefix are not renamed, so they can be used outside
+/// of the component.
+const GLOBAL_KEYFRAMES_PREFIX: &str = "-global-";
+
 #[derive(Debug)]
 pub struct IsolateCssPass {
     component_id: u8,
+    /// The new names of the keyframes declared in the component.
+    keyframes: HashMap<SmolStr, SmolStr>,
 }
 
 impl IsolateCssPass {
     pub fn new() -> Self {
-        Self { component_id: 0 }
+        Self {
+            component_id: 0,
+            keyframes: HashMap::new(),
+        }
     }
 
     fn run_css_passes(&self, rules: &mut [Rule], declared_vars: &mut DeclaredVariables) {
         for rule in rules {
             let rule = match rule {
                 Rule::At(at_rule) => {
+                    self.modify_declarations(&mut at_rule.declarations, declared_vars);
                     if let Some(contents) = &mut at_rule.contents {
                         self.run_css_passes(contents, declared_vars);
                     }
                     continue;
                 }
+                Rule::Keyframes(keyframes) => {
+                    if let Some(new_name) = self.keyframes.get(&keyframes.ident) {
+                        keyframes.ident = new_name.clone();
+                    }
+                    for keyframe in &mut keyframes.keyframes {
+                        self.modify_declarations(&mut keyframe.declarations, declared_vars);
+                    }
+                    continue;
+                }
                 Rule::Regular(rule) => rule,
             };
 
             self.modify_selectors(rule);
-            self.assign_css_mustaches(rule, declared_vars);
+            self.modify_declarations(&mut rule.declarations, declared_vars);
+        }
+    }
+
+    /// Finds the keyframes declared in `rules`, and decides what they will be renamed to. Keyframe
+    /// names are scoped to the component like selectors are, unless they're marked as global.
+    fn collect_keyframes(&mut self, rules: &[Rule]) {
+        for rule in rules {
+            match rule {
+                Rule::At(at_rule) => {
+                    if let Some(contents) = &at_rule.contents {
+                        self.collect_keyframes(contents);
+                    }
+                }
+                Rule::Keyframes(keyframes) => {
+                    let new_name = match keyframes.ident.strip_prefix(GLOBAL_KEYFRAMES_PREFIX) {
+                        Some(global) => global.into(),
+                        None => format!("{}-decor-{}", keyframes.ident, self.component_id).into(),
+                    };
+                    self.keyframes.insert(keyframes.ident.clone(), new_name);
+                }
+                Rule::Regular(_) => {}
+            }
+        }
+    }
+
+    fn modify_declarations(
+        &self,
+        declarations: &mut [Declaration],
+        declared_vars: &mut DeclaredVariables,
+    ) {
+        for decl in declarations.iter_mut() {
+            self.rename_keyframes(decl);
+        }
+        self.assign_css_mustaches(declarations, declared_vars);
+    }
+
+    /// Renames the keyframes used in `animation` and `animation-name` declarations.
+    fn rename_keyframes(&self, decl: &mut Declaration) {
+        let name = decl.name.trim();
+        let unprefixed = name
+            .strip_prefix('-')
+            .and_then(|name| name.split_once('-'))
+            .map_or(name, |(_, name)| name);
+        if !matches!(unprefixed, "animation" | "animation-name") || self.keyframes.is_empty() {
+            return;
+        }
+
+        for value in &mut decl.values {
+            let Value::Css(css) = value else {
+                continue;
+            };
+            let mut renamed = String::with_capacity(css.len());
+            let mut word = String::new();
+            for c in css.chars().chain([' ']) {
+                if c.is_whitespace() || c == ',' {
+                    match self.keyframes.get(word.as_str()) {
+                        Some(new_name) => renamed.push_str(new_name),
+                        None => renamed.push_str(&word),
+                    }
+                    word.clear();
+                    renamed.push(c);
+                } else {
+                    word.push(c);
+                }
+            }
+            // Remove the extra space that was chained on
+            renamed.pop();
+            *css = renamed.into();
         }
     }
 
@@ -50,8 +139,12 @@ impl IsolateCssPass {
 
     // TODO: Move to somewhere else? declared vars should be formed by now
     #[allow(clippy::unused_self)]
-    fn assign_css_mustaches(&self, rule: &mut RegularRule, declared_vars: &mut DeclaredVariables) {
-        for decl in &rule.declarations {
+    fn assign_css_mustaches(
+        &self,
+        declarations: &[Declaration],
+        declared_vars: &mut DeclaredVariables,
+    ) {
+        for decl in declarations {
             for mustache in decl.values.iter().filter_map(|val| match val {
                 Value::Mustache(m) => Some(m),
                 Value::Css(_) => None,
@@ -61,6 +154,24 @@ impl IsolateCssPass {
         }
     }
 
+    /// Finds the CSS mustaches that use the bindings of `{#for}` blocks. Their custom properties
+    /// can't be set on the target of the component, so they're set on the root elements of each
+    /// item of the innermost `{#for}` block that binds them.
+    fn assign_css_scopes(component: &mut Component) {
+        for (mustache, css_id) in component.declared_vars.css_mustaches().clone() {
+            let refs = utils::get_unbound_refs(&mustache)
+                .into_iter()
+                .filter_map(|nref| nref.ident_token())
+                .map(|tok| tok.text().clone())
+                .collect::<Vec<_>>();
+            let mut scopes = vec![];
+            find_binding_blocks(&component.fragment_tree, &refs, &mut scopes);
+            for scope_id in scopes {
+                component.declared_vars.insert_css_scope(css_id, scope_id);
+            }
+        }
+    }
+
     fn assign_node_classes(&self, component: &mut Component) {
         traverse_mut(&mut component.fragment_tree, &mut |node| {
             let NodeType::Element(elem) = &mut node.node_type else {
@@ -123,11 +234,155 @@ impl Pass for IsolateCssPass {
                 return Ok(());
             };
             self.component_id = component.component_id;
+            flatten_nesting(&mut css.rules);
+            self.collect_keyframes(&css.rules);
             self.run_css_passes(&mut css.rules, &mut component.declared_vars);
         }
 
+        Self::assign_css_scopes(component);
         self.assign_node_classes(component);
 
         Ok(())
     }
 }
+
+/// Moves the rules nested in other rules out of them, so that no nesting is left in `rules`.
+fn flatten_nesting(rules: &mut Vec<Rule>) {
+    for rule in mem::take(rules) {
+        match rule {
+            Rule::Regular(regular) => flatten_rule(regular, rules),
+            Rule::At(mut at_rule) => {
+                if let Some(contents) = &mut at_rule.contents {
+                    flatten_nesting(contents);
+                }
+                rules.push(Rule::At(at_rule));
+            }
+            Rule::Keyframes(_) => rules.push(rule),
+        }
+    }
+}
+
+/// Pushes `rule` to `out`, followed by the rules nested in it. Their selectors are combined with
+/// the selector of `rule`.
+fn flatten_rule(mut rule: RegularRule, out: &mut Vec<Rule>) {
+    let nested = mem::take(&mut rule.nested);
+    let parents = rule.selector.clone();
+    // Rules that only hold other rules are left out
+    if !rule.declarations.is_empty() || nested.is_empty() {
+        out.push(Rule::Regular(rule));
+    }
+    for child in nested {
+        match child {
+            Rule::Regular(mut child) => {
+                child.selector = parents
+                    .iter()
+                    .flat_map(|parent| {
+                        child
+                            .selector
+                            .iter()
+                            .map(|selector| join_selectors(parent, selector))
+                    })
+                    .collect();
+                flatten_rule(child, out);
+            }
+            // At-rules are moved outside of the rule, and the rule is moved inside of them
+            Rule::At(mut at_rule) if at_rule.contents.is_some() => {
+                let inner = RegularRule {
+                    selector: parents.clone(),
+                    declarations: mem::take(&mut at_rule.declarations),
+                    nested: at_rule.contents.take().unwrap_or_default(),
+                };
+                let mut contents = vec![];
+                flatten_rule(inner, &mut contents);
+                at_rule.contents = Some(contents);
+                out.push(Rule::At(at_rule));
+            }
+            Rule::At(_) | Rule::Keyframes(_) => out.push(child),
+        }
+    }
+}
+
+/// Joins a nested selector with the selector of the rule it's in. `&` is replaced with the parent
+/// selector. Without an `&`, the nested selector matches the descendants of the parent.
+fn join_selectors(parent: &Selector, nested: &Selector) -> Selector {
+    let has_ref = nested
+        .parts
+        .iter()
+        .any(|part| part.text.as_ref().is_some_and(|text| text.contains('&')));
+    if !has_ref {
+        return Selector {
+            parts: parent.parts.iter().chain(&nested.parts).cloned().collect(),
+        };
+    }
+
+    let mut parts = vec![];
+    for part in &nested.parts {
+        let Some((before, after)) = part.text.as_ref().and_then(|text| text.split_once('&')) else {
+            parts.push(part.clone());
+            continue;
+        };
+        let Some((last, rest)) = parent.parts.split_last() else {
+            continue;
+        };
+        parts.extend(rest.iter().cloned());
+        let mut joined = last.clone();
+        let text = format!(
+            "{before}{}{after}",
+            last.text.as_ref().map_or("", |text| text.as_str())
+        );
+        joined.text = (!text.is_empty()).then(|| text.into());
+        joined.pseudoes.extend(part.pseudoes.iter().cloned());
+        parts.push(joined);
+    }
+    Selector { parts }
+}
+
+/// Pushes the ids of the `{#for}` blocks in `nodes` that bind one of `refs` to `out`, unless a
+/// `{#for}` block nested inside of them does so too. Returns whether any block was found.
+fn find_binding_blocks(
+    nodes: &[Node<'_, FragmentMetadata>],
+    refs: &[SmolStr],
+    out: &mut Vec<u32>,
+) -> bool {
+    let mut found = false;
+    for node in nodes {
+        found |= match &node.node_type {
+            NodeType::Element(elem) => find_binding_blocks(&elem.children, refs, out),
+            NodeType::SpecialBlock(SpecialBlock::If(block)) => {
+                let in_inner = find_binding_blocks(&block.inner, refs, out);
+                let in_else = block
+                    .else_block
+                    .as_ref()
+                    .is_some_and(|else_block| find_binding_blocks(else_block, refs, out));
+                in_inner || in_else
+            }
+            NodeType::SpecialBlock(SpecialBlock::For(block)) => {
+                let nested = find_binding_blocks(&block.inner, refs, out);
+                let binds = refs.iter().any(|r| r == block.binding);
+                if binds && !nested {
+                    out.push(node.metadata.id());
+                }
+                binds || nested
+            }
+            NodeType::SpecialBlock(SpecialBlock::Head(block)) => {
+                find_binding_blocks(&block.inner, refs, out)
+            }
+            NodeType::SpecialBlock(SpecialBlock::Static(block)) => {
+                find_binding_blocks(&block.inner, refs, out)
+            }
+            NodeType::SpecialBlock(SpecialBlock::Cfg(block)) => {
+                find_binding_blocks(&block.inner, refs, out)
+            }
+            NodeType::Text(_)
+            | NodeType::Comment(_)
+            | NodeType::Mustache(_)
+            | NodeType::SpecialBlock(
+                SpecialBlock::Use(_)
+                | SpecialBlock::Raw(_)
+                | SpecialBlock::Html(_)
+                | SpecialBlock::Translate(_),
+            ) => false,
+        };
+    }
+    found
+}
@@ -0,0 +1,219 @@
+use std::{collections::HashSet, ops::Range};
+
+use decorous_errors::{DiagnosticBuilder, Helper};
+use rslint_parser::{ast::VarDecl, SyntaxNodeExt};
+
+use crate::{
+    ast::{traverse, Attribute, Binding, BindingKind, Element, NodeType, SpecialBlock},
+    component::passes::Pass,
+    lint::Lint,
+    utils, Component,
+};
+
+/// Runs the lints that only need the template: `duplicate-attribute`, `invalid-binding-target`
+/// and `unsanitized-html`. Bindings to variables that aren't declared at all can't be compiled,
+/// so they are always errors.
+#[derive(Debug)]
+pub struct LintPass;
+
+impl LintPass {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Pass for LintPass {
+    fn run(self, component: &mut Component) -> anyhow::Result<()> {
+        let consts = component
+            .toplevel_nodes
+            .iter()
+            .filter_map(|toplevel| toplevel.node.try_to::<VarDecl>())
+            .filter(VarDecl::is_const)
+            .flat_map(|decl| decl.declared().filter_map(|d| d.pattern()))
+            .flat_map(utils::get_idents_from_pattern)
+            .collect::<HashSet<_>>();
+
+        let mut found = vec![];
+        let mut unbound_bindings = vec![];
+        traverse(&component.fragment_tree, &mut |node| {
+            let offset = node.metadata.location().offset();
+            let elem = match &node.node_type {
+                NodeType::Element(elem) => elem,
+                NodeType::SpecialBlock(SpecialBlock::Html(_)) => {
+                    found.push((
+                        Lint::UnsanitizedHtml,
+                        diagnostic(
+                            "unsanitized HTML".to_owned(),
+                            offset..offset + "html".len(),
+                            "this is rendered as HTML without being sanitized, so it can run \
+                             scripts"
+                                .to_owned(),
+                        ),
+                    ));
+                    return;
+                }
+                _ => return,
+            };
+            // The tag name of the element
+            let head = offset..offset + elem.tag.len();
+            // The `{#for}` block that the element is in, whose items can be bound to
+            let row = node
+                .metadata
+                .scope()
+                .and_then(|scope| component.declared_vars.all_scopes().get(&scope));
+
+            let mut keys = HashSet::new();
+            for attr in &elem.attrs {
+                match attr {
+                    Attribute::KeyValue(key, _) if !keys.insert(*key) => {
+                        found.push((
+                            Lint::DuplicateAttribute,
+                            diagnostic(
+                                format!("duplicate attribute: {key}"),
+                                head.clone(),
+                                format!("`{key}` is set more than once on this element"),
+                            ),
+                        ));
+                    }
+                    Attribute::Binding(binding)
+                        if row.is_some_and(|s| s.item() == binding.root()) =>
+                    {
+                        // The items are written back to the variable that is iterated over
+                        if row.is_some_and(|s| s.source().is_none()) {
+                            unbound_bindings.push(
+                                diagnostic(
+                                    format!("cannot bind to the items of an expression: {}", binding.var),
+                                    head.clone(),
+                                    format!(
+                                        "`{}` must be the items of a variable, like `{{#for {} in items}}`",
+                                        binding.root(),
+                                        binding.root()
+                                    ),
+                                )
+                                .build(),
+                            );
+                            continue;
+                        }
+                        if let Some(source) = row.and_then(|s| s.source()).filter(|source| {
+                            !component.declared_vars.all_vars().contains_key(*source)
+                        }) {
+                            unbound_bindings.push(
+                                diagnostic(
+                                    format!(
+                                        "cannot bind to the items of undeclared variable: {source}"
+                                    ),
+                                    head.clone(),
+                                    format!("`{source}` is not a variable declared in the script"),
+                                )
+                                .build(),
+                            );
+                            continue;
+                        }
+                        let Some(problem) = binding_problem(elem, binding, false) else {
+                            continue;
+                        };
+                        found.push((
+                            Lint::InvalidBindingTarget,
+                            diagnostic(
+                                format!("invalid binding target: {}", binding.var),
+                                head.clone(),
+                                problem,
+                            ),
+                        ));
+                    }
+                    // There's no variable to keep in sync, so this can't be compiled
+                    Attribute::Binding(binding)
+                        if !component
+                            .declared_vars
+                            .all_vars()
+                            .contains_key(binding.root()) =>
+                    {
+                        unbound_bindings.push(
+                            diagnostic(
+                                format!("cannot bind to undeclared variable: {}", binding.root()),
+                                head.clone(),
+                                format!(
+                                    "`{}` is not a variable declared in the script",
+                                    binding.root()
+                                ),
+                            )
+                            .build(),
+                        );
+                    }
+                    Attribute::Binding(binding) => {
+                        // Fields of constant objects can still be assigned to
+                        let is_const = !binding.is_field() && consts.contains(binding.var);
+                        let Some(problem) = binding_problem(elem, binding, is_const) else {
+                            continue;
+                        };
+                        found.push((
+                            Lint::InvalidBindingTarget,
+                            diagnostic(
+                                format!("invalid binding target: {}", binding.var),
+                                head.clone(),
+                                problem,
+                            ),
+                        ));
+                    }
+                    Attribute::KeyValue(_, _) | Attribute::EventHandler(_) => {}
+                }
+            }
+        });
+
+        for (lint, diagnostic) in found {
+            component.emit_lint(lint, diagnostic);
+        }
+        let has_unbound_bindings = !unbound_bindings.is_empty();
+        for diagnostic in unbound_bindings {
+            component.ctx.errs.emit(diagnostic);
+        }
+        anyhow::ensure!(
+            !has_unbound_bindings,
+            "bindings must be to variables declared in the script"
+        );
+
+        Ok(())
+    }
+}
+
+pub(super) fn diagnostic(msg: String, span: Range<usize>, helper: String) -> DiagnosticBuilder {
+    DiagnosticBuilder::new(msg, span.start).add_helper(Helper {
+        msg: helper.into(),
+        span,
+    })
+}
+
+/// Describes why `binding` can't be kept in sync, if it can't.
+fn binding_problem(
+    elem: &Element<'_, impl Sized>,
+    binding: &Binding<'_>,
+    is_const: bool,
+) -> Option<String> {
+    if is_const {
+        return Some(format!(
+            "`{}` is a constant, so it can't be assigned to",
+            binding.var
+        ));
+    }
+    // The tag of dynamic elements isn't known until runtime
+    if elem.tag_expr.is_some() {
+        return None;
+    }
+    match binding.kind {
+        BindingKind::Value if !matches!(elem.tag, "input" | "textarea" | "select") => {
+            Some(format!("`{}` elements have no value to bind to", elem.tag))
+        }
+        BindingKind::Html | BindingKind::Text
+            if !elem
+                .attrs
+                .iter()
+                .any(|attr| matches!(attr, Attribute::KeyValue("contenteditable", _))) =>
+        {
+            Some(format!(
+                "binding to `{}` requires the element to be `contenteditable`",
+                binding.kind.property()
+            ))
+        }
+        _ => None,
+    }
+}
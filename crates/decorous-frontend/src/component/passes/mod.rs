@@ -1,11 +1,29 @@
+mod cfg_blocks;
+mod dead_branches;
 mod dep_analysis;
+mod env;
+mod expand_uses;
 mod isolate_css;
+mod lint;
 mod run_static;
+mod static_blocks;
+mod translate;
+mod unused_uses;
+mod validate_html;
 
 use crate::Component;
+pub use cfg_blocks::*;
+pub use dead_branches::*;
 pub use dep_analysis::*;
+pub use env::*;
+pub use expand_uses::*;
 pub use isolate_css::*;
+pub use lint::*;
 pub use run_static::*;
+pub use static_blocks::*;
+pub use translate::*;
+pub use unused_uses::*;
+pub use validate_html::*;
 
 pub trait Pass {
     fn run(self, component: &mut Component) -> anyhow::Result<()>;
@@ -1,6 +1,6 @@
 use rslint_parser::SmolStr;
 
-use crate::{component::passes::Pass, Component, ToplevelNodeData};
+use crate::{component::passes::Pass, fold::Const, Component, ToplevelNodeData};
 
 pub struct StaticPass;
 
@@ -17,18 +17,22 @@ impl Pass for StaticPass {
         };
 
         let js_env = component.ctx.executor.execute(code)?;
-        for decl in js_env.items() {
+        for (i, decl) in js_env.items().iter().enumerate() {
             let syntax_node =
                 rslint_parser::parse_text(&format!("let {} = {};", decl.name, decl.value), 0);
             // PERF: Ring buffer?
             component.toplevel_nodes.insert(
-                0,
+                i,
                 ToplevelNodeData {
                     node: syntax_node.syntax(),
                     substitute_assign_refs: true,
                 },
             );
-            component.declared_vars.insert_var(SmolStr::new(&decl.name));
+            let name = SmolStr::new(&decl.name);
+            match Const::parse(&decl.value) {
+                Some(value) => component.declared_vars.insert_constant(name, value),
+                None => component.declared_vars.insert_var(name),
+            }
         }
 
         Ok(())
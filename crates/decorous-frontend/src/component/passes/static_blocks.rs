@@ -0,0 +1,229 @@
+use std::{borrow::Cow, collections::HashSet, ops::Range};
+
+use decorous_errors::DiagnosticBuilder;
+use rslint_parser::SyntaxNode;
+
+use crate::{
+    ast::{Attribute, AttributeValue, Node, NodeType, SpecialBlock},
+    component::passes::{lint::diagnostic, Pass},
+    fold::{self, Const},
+    Component, DeclaredVariables, FragmentMetadata,
+};
+
+/// Checks that the contents of `{#static}` blocks can be rendered without any JavaScript. Event
+/// handlers, bindings, components and `{#html}` blocks are never static, and expressions have to
+/// be known at compile time. `{#for}` blocks are checked once for each of their items.
+pub struct StaticBlocksPass;
+
+impl StaticBlocksPass {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Pass for StaticBlocksPass {
+    fn run(self, component: &mut Component) -> anyhow::Result<()> {
+        let mut checker = Checker {
+            vars: &component.declared_vars,
+            components: component
+                .uses
+                .iter()
+                .filter_map(|use_block| use_block.name())
+                .map(Cow::into_owned)
+                .collect(),
+            loop_values: vec![],
+            found: vec![],
+            reported: HashSet::new(),
+        };
+        checker.find_blocks(&component.fragment_tree);
+
+        let has_errors = !checker.found.is_empty();
+        for diagnostic in checker.found {
+            component.ctx.errs.emit(diagnostic.build());
+        }
+        anyhow::ensure!(
+            !has_errors,
+            "{{#static}} blocks can only contain content known at compile time"
+        );
+
+        Ok(())
+    }
+}
+
+struct Checker<'a> {
+    vars: &'a DeclaredVariables,
+    /// The tag names of the components that are used.
+    components: HashSet<String>,
+    /// The values of the bindings of the `{#for}` blocks being checked, innermost last.
+    loop_values: Vec<(&'a str, Const)>,
+    found: Vec<DiagnosticBuilder>,
+    /// The offsets and messages of the problems that have been reported, so the items of a
+    /// `{#for}` block don't report the same problem more than once.
+    reported: HashSet<(usize, String)>,
+}
+
+impl<'a> Checker<'a> {
+    fn find_blocks(&mut self, nodes: &'a [Node<'a, FragmentMetadata>]) {
+        for node in nodes {
+            match &node.node_type {
+                NodeType::Element(elem) => self.find_blocks(&elem.children),
+                NodeType::SpecialBlock(SpecialBlock::If(block)) => {
+                    self.find_blocks(&block.inner);
+                    if let Some(else_block) = &block.else_block {
+                        self.find_blocks(else_block);
+                    }
+                }
+                NodeType::SpecialBlock(SpecialBlock::For(block)) => self.find_blocks(&block.inner),
+                NodeType::SpecialBlock(SpecialBlock::Head(block)) => self.find_blocks(&block.inner),
+                NodeType::SpecialBlock(SpecialBlock::Static(block)) => self.check(&block.inner),
+                _ => {}
+            }
+        }
+    }
+
+    fn check(&mut self, nodes: &'a [Node<'a, FragmentMetadata>]) {
+        for node in nodes {
+            let meta = &node.metadata;
+            let offset = meta.location().offset();
+            match &node.node_type {
+                NodeType::Text(_) | NodeType::Comment(_) => {}
+                NodeType::Mustache(mustache) => {
+                    if self
+                        .fold(&mustache.0, meta)
+                        .and_then(|value| value.to_js_string())
+                        .is_none()
+                    {
+                        self.report(
+                            offset,
+                            "mustache in {#static} block is not known at compile time",
+                            offset..offset + meta.location().length(),
+                            "its value could change, which would need JavaScript to update",
+                        );
+                    }
+                }
+                NodeType::Element(elem) => {
+                    // Attributes don't have locations, so they point to the tag name
+                    let head = offset..offset + elem.tag.len();
+                    if elem.tag_expr.is_some() {
+                        self.report(
+                            offset,
+                            "dynamic tag in {#static} block",
+                            head.clone(),
+                            "elements with a dynamic tag are created with JavaScript",
+                        );
+                    } else if self.components.contains(&*elem.js_valid_tag_name()) {
+                        self.report(
+                            offset,
+                            format!("component `{}` in {{#static}} block", elem.tag),
+                            head.clone(),
+                            "components are mounted with JavaScript",
+                        );
+                    }
+                    for attr in &elem.attrs {
+                        let problem = match attr {
+                            Attribute::EventHandler(handler) => {
+                                format!("`{}` event handler", handler.event)
+                            }
+                            Attribute::Binding(binding) => format!("binding to `{}`", binding.var),
+                            Attribute::KeyValue(key, Some(AttributeValue::JavaScript(js)))
+                                if *key == "style"
+                                    || self
+                                        .fold(js, meta)
+                                        .and_then(|value| value.to_js_string())
+                                        .is_none() =>
+                            {
+                                format!("`{key}` attribute not known at compile time")
+                            }
+                            Attribute::KeyValue(_, _) => continue,
+                        };
+                        self.report(
+                            offset,
+                            format!("{problem} in {{#static}} block"),
+                            head.clone(),
+                            "this element would need JavaScript to stay up to date",
+                        );
+                    }
+                    self.check(&elem.children);
+                }
+                NodeType::SpecialBlock(SpecialBlock::If(block)) => {
+                    match self.fold(&block.expr, meta) {
+                        Some(cond) if cond.is_truthy() => self.check(&block.inner),
+                        Some(_) => {
+                            if let Some(else_block) = &block.else_block {
+                                self.check(else_block);
+                            }
+                        }
+                        None => self.report(
+                            offset,
+                            "{#if} block in {#static} block is not known at compile time",
+                            offset..offset + "if".len(),
+                            "the condition could change, which would need JavaScript to update",
+                        ),
+                    }
+                }
+                NodeType::SpecialBlock(SpecialBlock::For(block)) => {
+                    let Some(Const::Array(items)) = self.fold(&block.expr, meta) else {
+                        self.report(
+                            offset,
+                            "{#for} block in {#static} block is not known at compile time",
+                            offset..offset + "for".len(),
+                            "the items must be an array known at compile time",
+                        );
+                        continue;
+                    };
+                    for (i, item) in items.into_iter().enumerate() {
+                        let len = self.loop_values.len();
+                        self.loop_values.push((block.binding, item));
+                        if let Some(index) = block.index {
+                            self.loop_values.push((index, Const::Number(i as f64)));
+                        }
+                        self.check(&block.inner);
+                        self.loop_values.truncate(len);
+                    }
+                }
+                NodeType::SpecialBlock(SpecialBlock::Html(_)) => self.report(
+                    offset,
+                    "{#html} block in {#static} block",
+                    offset..offset + "html".len(),
+                    "{#html} blocks are rendered with JavaScript",
+                ),
+                NodeType::SpecialBlock(SpecialBlock::Head(_)) => self.report(
+                    offset,
+                    "{#head} block in {#static} block",
+                    offset..offset + "head".len(),
+                    "{#head} blocks are rendered with JavaScript",
+                ),
+                NodeType::SpecialBlock(SpecialBlock::Static(block)) => self.check(&block.inner),
+                NodeType::SpecialBlock(SpecialBlock::Cfg(block)) => self.check(&block.inner),
+                NodeType::SpecialBlock(
+                    SpecialBlock::Use(_) | SpecialBlock::Raw(_) | SpecialBlock::Translate(_),
+                ) => {}
+            }
+        }
+    }
+
+    fn fold(&self, js: &SyntaxNode, meta: &FragmentMetadata) -> Option<Const> {
+        fold::fold(js, &|name| match self
+            .loop_values
+            .iter()
+            .rev()
+            .find(|(binding, _)| *binding == name)
+        {
+            Some((_, value)) => Some(value.clone()),
+            None => self.vars.get_constant(name, meta.scope()).cloned(),
+        })
+    }
+
+    fn report(
+        &mut self,
+        offset: usize,
+        msg: impl Into<String>,
+        span: Range<usize>,
+        helper: impl Into<String>,
+    ) {
+        let msg = msg.into();
+        if self.reported.insert((offset, msg.clone())) {
+            self.found.push(diagnostic(msg, span, helper.into()));
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::ops::Range;
+
+use decorous_errors::{DiagnosticBuilder, Helper, Severity};
+
+use crate::{
+    ast::{Mustache, Node, NodeType, SpecialBlock},
+    component::passes::Pass,
+    Component, FragmentMetadata, Messages,
+};
+
+/// Replaces `{@t "key"}` expressions with the message of their key, as string literals that are
+/// rendered like any other constant mustache. Keys without a message are warned about, and fall
+/// back to the key itself.
+pub struct TranslatePass;
+
+impl TranslatePass {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Pass for TranslatePass {
+    fn run(self, component: &mut Component) -> anyhow::Result<()> {
+        let mut untranslated = vec![];
+        translate(
+            &mut component.fragment_tree,
+            component.ctx.messages,
+            &mut untranslated,
+        );
+
+        for (key, span) in untranslated {
+            component.ctx.errs.emit(
+                DiagnosticBuilder::new(format!("no message for {key:?}"), span.start)
+                    .severity(Severity::Warning)
+                    .note("the key is shown instead")
+                    .add_helper(Helper {
+                        msg: "add it to the catalog of the locale".into(),
+                        span,
+                    })
+                    .build(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn translate<'a>(
+    nodes: &mut [Node<'a, FragmentMetadata>],
+    messages: &dyn Messages,
+    untranslated: &mut Vec<(&'a str, Range<usize>)>,
+) {
+    for node in nodes {
+        match &mut node.node_type {
+            NodeType::SpecialBlock(SpecialBlock::Translate(block)) => {
+                let key = block.key;
+                let message = messages.message(key).unwrap_or_else(|| {
+                    let loc = node.metadata.location();
+                    untranslated.push((key, loc.offset()..loc.offset() + loc.length()));
+                    key.to_owned()
+                });
+                let parse = rslint_parser::parse_module(&format!("{message:?}"), 0);
+                let expr = parse
+                    .syntax()
+                    .first_child()
+                    .expect("string literal should parse");
+                node.node_type = NodeType::Mustache(Mustache(expr));
+            }
+            NodeType::SpecialBlock(SpecialBlock::If(if_block)) => {
+                translate(&mut if_block.inner, messages, untranslated);
+                if let Some(else_block) = &mut if_block.else_block {
+                    translate(else_block, messages, untranslated);
+                }
+            }
+            NodeType::SpecialBlock(SpecialBlock::For(for_block)) => {
+                translate(&mut for_block.inner, messages, untranslated);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Head(head_block)) => {
+                translate(&mut head_block.inner, messages, untranslated);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Static(static_block)) => {
+                translate(&mut static_block.inner, messages, untranslated);
+            }
+            NodeType::Element(elem) => translate(&mut elem.children, messages, untranslated),
+            _ => {}
+        }
+    }
+}
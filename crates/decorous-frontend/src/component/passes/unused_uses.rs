@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+use decorous_errors::{DiagnosticBuilder, Severity};
+
+use crate::{
+    ast::{Node, NodeType, SpecialBlock, UseBlock},
+    component::passes::Pass,
+    Component, FragmentMetadata,
+};
+
+/// Warns on `{#use}` declarations whose component is never rendered. With
+/// [`Ctx::drop_unused_uses`](crate::Ctx::drop_unused_uses), the dead declaration is also removed
+/// from [`Component::uses`], so no import is emitted for it.
+pub struct UnusedUsesPass;
+
+impl UnusedUsesPass {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Pass for UnusedUsesPass {
+    fn run(self, component: &mut Component) -> anyhow::Result<()> {
+        if component.uses.is_empty() {
+            return Ok(());
+        }
+
+        let mut tags = HashSet::new();
+        let mut use_locations = Vec::new();
+        collect(&component.fragment_tree, &mut tags, &mut use_locations);
+
+        let mut unused = Vec::new();
+        for (use_block, offset) in use_locations {
+            let Some(name) = use_block.name() else {
+                continue;
+            };
+            if tags.contains(&*name) {
+                continue;
+            }
+
+            component.ctx.errs.emit(
+                DiagnosticBuilder::new(
+                    format!("unused use declaration: {}", use_block.path.display()),
+                    offset,
+                )
+                .severity(Severity::Warning)
+                .note(format!("the component is rendered with the #{name} tag"))
+                .build(),
+            );
+            unused.push(use_block);
+        }
+
+        if component.ctx.drop_unused_uses {
+            component
+                .uses
+                .retain(|use_block| !unused.contains(use_block));
+        }
+
+        Ok(())
+    }
+}
+
+pub(super) fn collect<'a>(
+    nodes: &[Node<'a, FragmentMetadata>],
+    tags: &mut HashSet<String>,
+    uses: &mut Vec<(UseBlock<'a>, usize)>,
+) {
+    for node in nodes {
+        match &node.node_type {
+            NodeType::Element(elem) => {
+                tags.insert(elem.js_valid_tag_name().into_owned());
+                collect(&elem.children, tags, uses);
+            }
+            NodeType::SpecialBlock(SpecialBlock::If(if_block)) => {
+                collect(&if_block.inner, tags, uses);
+                if let Some(else_block) = &if_block.else_block {
+                    collect(else_block, tags, uses);
+                }
+            }
+            NodeType::SpecialBlock(SpecialBlock::For(for_block)) => {
+                collect(&for_block.inner, tags, uses);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Head(head_block)) => {
+                collect(&head_block.inner, tags, uses);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Static(static_block)) => {
+                collect(&static_block.inner, tags, uses);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Cfg(cfg_block)) => {
+                collect(&cfg_block.inner, tags, uses);
+            }
+            NodeType::SpecialBlock(SpecialBlock::Use(use_block)) => {
+                uses.push((use_block.clone(), node.metadata.location().offset()));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashMap, mem};
+
+use decorous_errors::{DiagnosticBuilder, Helper};
+
+use crate::{
+    ast::{Attribute, AttributeValue, Element, Node, NodeType, SpecialBlock},
+    component::passes::{lint::diagnostic, Pass},
+    lint::Lint,
+    location::Location,
+    Component, FragmentMetadata,
+};
+
+/// Elements that close a `#p` when they are opened, so they can't be inside of one.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Elements that can only be directly inside of certain other elements, and which those are.
+const REQUIRED_PARENTS: &[(&str, &[&str])] = &[
+    ("li", &["ul", "ol", "menu"]),
+    ("dt", &["dl", "div"]),
+    ("dd", &["dl", "div"]),
+    ("tr", &["table", "thead", "tbody", "tfoot"]),
+    ("td", &["tr"]),
+    ("th", &["tr"]),
+    ("option", &["select", "datalist", "optgroup"]),
+];
+
+/// Elements that can't be anywhere inside of an element with the same tag.
+const NOT_NESTABLE: &[&str] = &["a", "button", "form", "label"];
+
+/// Runs the lints that check the markup is valid HTML: `invalid-nesting`,
+/// `void-element-children`, and `duplicate-id`. Nesting that browsers don't accept is fixed up by
+/// them when the page is parsed, so the rendered page wouldn't match the component.
+#[derive(Debug)]
+pub struct ValidateHtmlPass;
+
+impl ValidateHtmlPass {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Pass for ValidateHtmlPass {
+    fn run(self, component: &mut Component) -> anyhow::Result<()> {
+        let mut validator = Validator {
+            ancestors: vec![],
+            ids: HashMap::new(),
+            found: vec![],
+        };
+        validator.validate(&component.fragment_tree, false);
+
+        for (lint, diagnostic) in validator.found {
+            component.emit_lint(lint, diagnostic);
+        }
+
+        Ok(())
+    }
+}
+
+struct Validator<'a> {
+    /// The tags of the elements that the current node is in, from outermost to innermost.
+    /// Elements with a dynamic tag are `None`.
+    ancestors: Vec<Option<&'a str>>,
+    /// Where each literal `id` was first set.
+    ids: HashMap<&'a str, Location>,
+    found: Vec<(Lint, DiagnosticBuilder)>,
+}
+
+impl<'a> Validator<'a> {
+    fn validate(&mut self, nodes: &'a [Node<'a, FragmentMetadata>], in_for: bool) {
+        for node in nodes {
+            match &node.node_type {
+                NodeType::Element(elem) => {
+                    let loc = node.metadata.location();
+                    if elem.tag_expr.is_none() {
+                        self.check_element(elem, loc);
+                    }
+                    self.check_id(elem, loc, in_for);
+                    self.ancestors
+                        .push(elem.tag_expr.is_none().then_some(elem.tag));
+                    self.validate(&elem.children, in_for);
+                    self.ancestors.pop();
+                }
+                NodeType::SpecialBlock(SpecialBlock::If(block)) => {
+                    let before = self.ids.clone();
+                    self.validate(&block.inner, in_for);
+                    if let Some(else_block) = &block.else_block {
+                        // Only one of the branches is ever rendered, so they can use the same ids
+                        let inner_ids = mem::replace(&mut self.ids, before);
+                        self.validate(else_block, in_for);
+                        for (id, loc) in inner_ids {
+                            self.ids.entry(id).or_insert(loc);
+                        }
+                    }
+                }
+                NodeType::SpecialBlock(SpecialBlock::For(block)) => {
+                    self.validate(&block.inner, true);
+                }
+                NodeType::SpecialBlock(SpecialBlock::Head(block)) => {
+                    self.ancestors.push(Some("head"));
+                    self.validate(&block.inner, in_for);
+                    self.ancestors.pop();
+                }
+                NodeType::SpecialBlock(SpecialBlock::Static(block)) => {
+                    self.validate(&block.inner, in_for);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_element(&mut self, elem: &Element<'a, FragmentMetadata>, loc: Location) {
+        let head = loc.offset()..loc.offset() + elem.tag.len();
+        let parent = self.ancestors.last().copied();
+
+        if BLOCK_ELEMENTS.contains(&elem.tag) && parent == Some(Some("p")) {
+            self.found.push((
+                Lint::InvalidNesting,
+                diagnostic(
+                    format!("`{}` cannot be inside of `p`", elem.tag),
+                    head.clone(),
+                    "browsers close the `p` before this element".to_owned(),
+                ),
+            ));
+        }
+        // Top-level elements could be mounted into anything, so they aren't checked
+        if let (Some(Some(parent)), Some((_, allowed))) = (
+            parent,
+            REQUIRED_PARENTS.iter().find(|(tag, _)| *tag == elem.tag),
+        ) {
+            if !allowed.contains(&parent) {
+                self.found.push((
+                    Lint::InvalidNesting,
+                    diagnostic(
+                        format!("`{}` cannot be inside of `{parent}`", elem.tag),
+                        head.clone(),
+                        format!(
+                            "`{}` should be directly inside of {}",
+                            elem.tag,
+                            list(allowed)
+                        ),
+                    ),
+                ));
+            }
+        }
+        if NOT_NESTABLE.contains(&elem.tag) && self.ancestors.contains(&Some(elem.tag)) {
+            self.found.push((
+                Lint::InvalidNesting,
+                diagnostic(
+                    format!("`{0}` cannot be inside of another `{0}`", elem.tag),
+                    head.clone(),
+                    format!("this is nested in a `{}`", elem.tag),
+                ),
+            ));
+        }
+
+        let has_children = elem.children.iter().any(
+            |child| !matches!(&child.node_type, NodeType::Text(text) if text.trim().is_empty()),
+        );
+        if has_children && elem.is_void() {
+            self.found.push((
+                Lint::VoidElementChildren,
+                diagnostic(
+                    format!("void element `{}` cannot have children", elem.tag),
+                    head,
+                    format!("`{}` elements are always empty", elem.tag),
+                ),
+            ));
+        }
+    }
+
+    fn check_id(&mut self, elem: &'a Element<'a, FragmentMetadata>, loc: Location, in_for: bool) {
+        let Some(id) = elem.attrs.iter().find_map(|attr| match attr {
+            Attribute::KeyValue("id", Some(AttributeValue::Literal(id))) => Some(id.as_ref()),
+            _ => None,
+        }) else {
+            return;
+        };
+        let head = loc.offset()..loc.offset() + elem.tag.len();
+
+        if in_for {
+            self.found.push((
+                Lint::DuplicateId,
+                diagnostic(
+                    format!("duplicate id: {id}"),
+                    head,
+                    "this element is rendered once for each item of the `for` block".to_owned(),
+                ),
+            ));
+            return;
+        }
+        match self.ids.get(id) {
+            Some(first) => {
+                let first = first.offset()..first.offset() + first.length();
+                self.found.push((
+                    Lint::DuplicateId,
+                    diagnostic(
+                        format!("duplicate id: {id}"),
+                        head,
+                        format!("`{id}` is used again here"),
+                    )
+                    .add_helper(Helper {
+                        msg: "first used here".into(),
+                        span: first,
+                    }),
+                ));
+            }
+            None => {
+                self.ids
+                    .insert(id, Location::new(loc.offset(), elem.tag.len()));
+            }
+        }
+    }
+}
+
+/// Lists `tags` in prose, like "`ul`, `ol`, or `menu`".
+fn list(tags: &[&str]) -> String {
+    match tags {
+        [] => String::new(),
+        [tag] => format!("`{tag}`"),
+        [tag, other] => format!("`{tag}` or `{other}`"),
+        [rest @ .., last] => {
+            let rest = rest
+                .iter()
+                .map(|tag| format!("`{tag}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{rest}, or `{last}`")
+        }
+    }
+}
@@ -12,12 +12,17 @@ pub struct Css {
 pub enum Rule {
     At(AtRule),
     Regular(RegularRule),
+    Keyframes(KeyframesRule),
 }
 
 #[derive(Debug, PartialEq)]
 pub struct RegularRule {
     pub selector: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    /// Rules nested in this one, which can refer to its selector with `&`. They're moved out of it
+    /// when the CSS is scoped. At-rules nested in a rule can have declarations in them, which
+    /// apply to the selector of the rule.
+    pub nested: Vec<Rule>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -25,20 +30,38 @@ pub struct AtRule {
     pub name: SmolStr,
     pub additional: SmolStr,
     pub contents: Option<Vec<Rule>>,
+    /// Declarations directly in the block of the at-rule, like the ones in `@font-face`.
+    pub declarations: Vec<Declaration>,
 }
 
+/// A `@keyframes` at-rule. `name` is the name of the at-rule itself, which is `keyframes` with an
+/// optional vendor prefix.
 #[derive(Debug, PartialEq)]
+pub struct KeyframesRule {
+    pub name: SmolStr,
+    pub ident: SmolStr,
+    pub keyframes: Vec<Keyframe>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Keyframe {
+    /// Keyframe selectors, like `from` or `50%`.
+    pub selectors: Vec<SmolStr>,
+    pub declarations: Vec<Declaration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Selector {
     pub parts: Vec<SelectorPart>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SelectorPart {
     pub text: Option<SmolStr>,
     pub pseudoes: Vec<Pseudo>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Pseudo {
     Element(SmolStr),
     Class {
@@ -110,7 +133,11 @@ impl fmt::Display for AtRule {
                 "@{} {} {{ {} }}",
                 self.name,
                 self.additional,
-                contents.iter().join(" ")
+                self.declarations
+                    .iter()
+                    .map(ToString::to_string)
+                    .chain(contents.iter().map(ToString::to_string))
+                    .join(" ")
             )
         } else {
             write!(f, "@{} {};", self.name, self.additional)
@@ -124,7 +151,34 @@ impl fmt::Display for RegularRule {
             f,
             "{} {{ {} }}",
             self.selector.iter().join(", "),
-            self.declarations.iter().join("; ")
+            self.declarations
+                .iter()
+                .map(ToString::to_string)
+                .chain(self.nested.iter().map(ToString::to_string))
+                .join("; ")
+        )
+    }
+}
+
+impl fmt::Display for KeyframesRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "@{} {} {{ {} }}",
+            self.name,
+            self.ident,
+            self.keyframes.iter().join(" ")
+        )
+    }
+}
+
+impl fmt::Display for Keyframe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {{ {} }}",
+            self.selectors.iter().join(", "),
+            self.declarations.iter().join(" ")
         )
     }
 }
@@ -134,6 +188,7 @@ impl fmt::Display for Rule {
         match self {
             Self::At(at_rule) => write!(f, "{at_rule}"),
             Self::Regular(regular) => write!(f, "{regular}"),
+            Self::Keyframes(keyframes) => write!(f, "{keyframes}"),
         }
     }
 }
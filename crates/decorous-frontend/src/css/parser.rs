@@ -2,13 +2,26 @@ use harpoon::Harpoon;
 use rslint_parser::AstNode;
 
 use super::{
-    ast::{AtRule, Css, Declaration, Pseudo, RegularRule, Rule, Selector, SelectorPart, Value},
+    ast::{
+        AtRule, Css, Declaration, Keyframe, KeyframesRule, Pseudo, RegularRule, Rule, Selector,
+        SelectorPart, Value,
+    },
     error::{ParseError, ParseErrorType},
 };
 use crate::{errors::Help, location::Location};
 
 pub type Result<T> = std::result::Result<T, ParseError<Location>>;
 
+/// At-rules whose blocks contain declarations instead of rules.
+const DECLARATION_AT_RULES: &[&str] = &[
+    "font-face",
+    "page",
+    "property",
+    "counter-style",
+    "font-palette-values",
+    "viewport",
+];
+
 #[derive(Debug)]
 pub struct Parser<'a> {
     harpoon: Harpoon<'a>,
@@ -33,31 +46,144 @@ impl<'a> Parser<'a> {
 
     fn parse_rule(&mut self) -> Result<Rule> {
         if self.harpoon.peek_is('@') {
-            return Ok(Rule::At(self.parse_at_rule()?));
+            return self.parse_at_rule();
         }
 
+        self.parse_regular_rule().map(Rule::Regular)
+    }
+
+    fn parse_regular_rule(&mut self) -> Result<RegularRule> {
         let selector = self.parse_selector()?;
+        let (declarations, nested) = self.parse_rule_block()?;
+
+        Ok(RegularRule {
+            selector,
+            declarations,
+            nested,
+        })
+    }
+
+    /// Parses the block of a regular rule, which can have rules nested in it.
+    fn parse_rule_block(&mut self) -> Result<(Vec<Declaration>, Vec<Rule>)> {
         self.expect_consume('{')?;
         let mut declarations = vec![];
+        let mut nested = vec![];
         self.skip_whitespace();
         while !self.harpoon.peek_is('}') && self.harpoon.peek().is_some() {
-            declarations.push(self.parse_declaration()?);
+            if self.harpoon.peek_is('@') {
+                nested.push(self.parse_nested_at_rule()?);
+            } else if self.at_nested_rule() {
+                nested.push(Rule::Regular(self.parse_regular_rule()?));
+            } else {
+                declarations.push(self.parse_declaration()?);
+            }
             self.skip_whitespace();
         }
         self.expect_consume('}')?;
 
-        Ok(Rule::Regular(RegularRule {
-            selector,
+        Ok((declarations, nested))
+    }
+
+    /// Parses an at-rule in the block of a regular rule, like `@media`. Its block is parsed like
+    /// the block of the rule it's in.
+    fn parse_nested_at_rule(&mut self) -> Result<Rule> {
+        let (name, additional) = self.parse_at_rule_prelude()?;
+        if self.harpoon.peek_is(';') {
+            let consumed = self.harpoon.consume();
+            debug_assert_eq!(Some(';'), consumed);
+            return Ok(Rule::At(AtRule {
+                name: name.into(),
+                additional: additional.into(),
+                contents: None,
+                declarations: vec![],
+            }));
+        }
+        if unprefixed(name) == "keyframes" {
+            return self.parse_keyframes(name, additional.trim());
+        }
+
+        let (declarations, nested) = self.parse_rule_block()?;
+        Ok(Rule::At(AtRule {
+            name: name.into(),
+            additional: additional.into(),
+            contents: Some(nested),
             declarations,
         }))
     }
 
-    fn parse_at_rule(&mut self) -> Result<AtRule> {
-        debug_assert_eq!(Some('@'), self.harpoon.consume());
+    /// Checks if a nested rule comes next in the block of a rule, instead of a declaration.
+    ///
+    /// Both can have a `{` before the next `;`, since declarations can have mustaches in them. A
+    /// declaration starts with a property name and a colon that isn't directly followed by text,
+    /// which sets `color: {color};` apart from `a:hover {`.
+    fn at_nested_rule(&self) -> bool {
+        let remaining = self.harpoon.remaining();
+        let Some(end) = remaining.find(['{', ';', '}']) else {
+            return false;
+        };
+        if !remaining[end..].starts_with('{') {
+            return false;
+        }
+        let Some((name, value)) = remaining[..end].split_once(':') else {
+            return true;
+        };
+        let is_property = !name.trim().is_empty()
+            && name
+                .trim()
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        !is_property || !(value.is_empty() || value.starts_with(char::is_whitespace))
+    }
+
+    fn parse_at_rule(&mut self) -> Result<Rule> {
+        let (name, additional) = self.parse_at_rule_prelude()?;
+        if self.harpoon.peek_is(';') {
+            let consumed = self.harpoon.consume();
+            debug_assert_eq!(Some(';'), consumed);
+            return Ok(Rule::At(AtRule {
+                name: name.into(),
+                additional: additional.into(),
+                contents: None,
+                declarations: vec![],
+            }));
+        }
+
+        let unprefixed = unprefixed(name);
+        if unprefixed == "keyframes" {
+            return self.parse_keyframes(name, additional.trim());
+        }
+
+        self.expect_consume('{')?;
+        self.skip_whitespace();
+        let mut rules = vec![];
+        let mut declarations = vec![];
+        let has_declarations = DECLARATION_AT_RULES.contains(&unprefixed);
+        while !self.harpoon.peek_is('}') && self.harpoon.peek().is_some() {
+            if has_declarations && !self.harpoon.peek_is('@') {
+                declarations.push(self.parse_declaration()?);
+            } else {
+                rules.push(self.parse_rule()?);
+            }
+            self.skip_whitespace();
+        }
+        self.expect_consume('}')?;
+
+        Ok(Rule::At(AtRule {
+            name: name.into(),
+            additional: additional.into(),
+            contents: Some(rules),
+            declarations,
+        }))
+    }
+
+    /// Parses the name of an at-rule and everything after it, up to its block or semicolon.
+    fn parse_at_rule_prelude(&mut self) -> Result<(&'a str, &'a str)> {
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('@'), consumed);
         let name = self
             .harpoon
             .harpoon(|h| {
-                h.consume_while(|c| !c.is_whitespace());
+                h.consume_while(|c| !c.is_whitespace() && !matches!(c, '{' | ';'));
             })
             .text();
         if name.is_empty() {
@@ -74,29 +200,43 @@ impl<'a> Parser<'a> {
                 h.consume_while(|c| !matches!(c, '{' | ';'));
             })
             .text();
-        if self.harpoon.peek_is(';') {
-            debug_assert_eq!(Some(';'), self.harpoon.consume());
-            return Ok(AtRule {
-                name: name.into(),
-                additional: additional.into(),
-                contents: None,
-            });
-        }
 
+        Ok((name, additional))
+    }
+
+    fn parse_keyframes(&mut self, name: &str, ident: &str) -> Result<Rule> {
         self.expect_consume('{')?;
         self.skip_whitespace();
-        let mut rules = vec![];
+        let mut keyframes = vec![];
         while !self.harpoon.peek_is('}') && self.harpoon.peek().is_some() {
-            rules.push(self.parse_rule()?);
+            let selectors = self
+                .harpoon
+                .consume_while(|c| !matches!(c, '{' | '}'))
+                .text()
+                .split(',')
+                .map(|sel| sel.trim().into())
+                .collect();
+            self.expect_consume('{')?;
+            self.skip_whitespace();
+            let mut declarations = vec![];
+            while !self.harpoon.peek_is('}') && self.harpoon.peek().is_some() {
+                declarations.push(self.parse_declaration()?);
+                self.skip_whitespace();
+            }
+            self.expect_consume('}')?;
             self.skip_whitespace();
+            keyframes.push(Keyframe {
+                selectors,
+                declarations,
+            });
         }
         self.expect_consume('}')?;
 
-        Ok(AtRule {
+        Ok(Rule::Keyframes(KeyframesRule {
             name: name.into(),
-            additional: additional.into(),
-            contents: Some(rules),
-        })
+            ident: ident.into(),
+            keyframes,
+        }))
     }
 
     fn parse_selector(&mut self) -> Result<Vec<Selector>> {
@@ -111,7 +251,9 @@ impl<'a> Parser<'a> {
             selectors.push(Selector { parts });
         }
         while self.harpoon.peek_is(',') {
-            debug_assert_eq!(Some(','), self.harpoon.consume());
+            let consumed = self.harpoon.consume();
+            debug_assert_eq!(Some(','), consumed);
+            self.skip_whitespace();
             let mut parts = vec![];
             while !self.harpoon.peek_is_any(",{") && self.harpoon.peek().is_some() {
                 parts.push(self.parse_selector_part()?);
@@ -140,9 +282,11 @@ impl<'a> Parser<'a> {
 
         let mut pseudoes = vec![];
         while self.harpoon.peek_is(':') {
-            debug_assert_eq!(Some(':'), self.harpoon.consume());
+            let consumed = self.harpoon.consume();
+            debug_assert_eq!(Some(':'), consumed);
             if self.harpoon.peek_is(':') {
-                debug_assert_eq!(Some(':'), self.harpoon.consume());
+                let consumed = self.harpoon.consume();
+                debug_assert_eq!(Some(':'), consumed);
                 pseudoes.push(Pseudo::Element(parse_any(&mut self.harpoon).into()));
             } else {
                 let class_name = self
@@ -154,13 +298,10 @@ impl<'a> Parser<'a> {
                     })
                     .text();
                 let value = if self.harpoon.peek_is('(') {
-                    debug_assert_eq!(Some('('), self.harpoon.consume());
-                    let v = self
-                        .harpoon
-                        .harpoon(|harpoon| harpoon.consume_until(')'))
-                        .text();
-                    self.expect_consume(')')?;
-                    Some(v)
+                    let Some(v) = self.harpoon.consume_balanced('(', ')') else {
+                        return Err(self.expected_char(')', None));
+                    };
+                    Some(v.text())
                 } else {
                     None
                 };
@@ -178,10 +319,7 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_declaration(&mut self) -> Result<Declaration> {
-        let name = self
-            .harpoon
-            .harpoon(|harpoon| harpoon.consume_until(':'))
-            .text();
+        let name = self.harpoon.consume_until(':').text();
         let offset = self.harpoon.offset();
         self.expect_consume(':')?;
         self.skip_whitespace();
@@ -214,20 +352,26 @@ impl<'a> Parser<'a> {
             self.harpoon.consume();
             Ok(())
         } else {
-            Err(ParseError::new(
-                ParseErrorType::ExpectedCharacter(expected),
-                Location::from_source(self.harpoon.offset(), self.harpoon.source()),
-                help,
-            ))
+            Err(self.expected_char(expected, help))
         }
     }
 
+    fn expected_char(&self, expected: char, help: Option<Help>) -> ParseError<Location> {
+        ParseError::new(
+            ParseErrorType::ExpectedCharacter(expected),
+            Location::from_source(self.harpoon.offset(), self.harpoon.source()),
+            help,
+        )
+    }
+
     fn parse_value(&mut self) -> Result<Value> {
         if self.harpoon.peek_is('{') {
-            debug_assert_eq!(Some('{'), self.harpoon.consume());
-            let offset = self.harpoon.offset();
-            let contents = self.harpoon.harpoon(|h| h.consume_until('}')).text();
-            self.expect_consume('}')?;
+            let offset = self.harpoon.offset() + 1;
+            let contents = self
+                .harpoon
+                .consume_balanced('{', '}')
+                .ok_or_else(|| self.expected_char('}', None))?
+                .text();
             let res = rslint_parser::parse_expr(contents, 0).ok().map_err(|err| {
                 ParseError::new(
                     ParseErrorType::JavaScriptParseError(err),
@@ -239,7 +383,7 @@ impl<'a> Parser<'a> {
         } else {
             let t = self
                 .harpoon
-                .harpoon(|h| h.consume_while(|c| !matches!(c, ';' | '{' | '}' | ':')))
+                .consume_while(|c| !matches!(c, ';' | '{' | '}' | ':'))
                 .text();
             Ok(Value::Css(t.into()))
         }
@@ -250,6 +394,13 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Removes the vendor prefix of an at-rule name, like in `@-webkit-keyframes`.
+fn unprefixed(name: &str) -> &str {
+    name.strip_prefix('-')
+        .and_then(|name| name.split_once('-'))
+        .map_or(name, |(_, name)| name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -282,7 +433,8 @@ mod tests {
             "p.green:has(h1, h2):hover::after { color: green; }",
             "p::after { color: green; }",
             "p::after, span.yellow { color: green; }",
-            "p p { color: red; }"
+            "p p { color: red; }",
+            "p:not(:is(h1, h2)) { color: red; }"
         );
     }
 
@@ -291,7 +443,9 @@ mod tests {
         parser_test!(
             "p { color: {color}; }",
             "p { color: {color} yellow blue; }",
-            "p { width: {w}px; }"
+            "p { width: {w}px; }",
+            "p { width: {{ w: 1 }.w}px; }",
+            "p { width: {w"
         );
     }
 
@@ -313,6 +467,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_parse_nested_at_rules() {
+        parser_test!(
+            "@supports (display: grid) { @media screen { p { display: grid; } } }",
+            "@font-face { font-family: \"Fira Mono\"; src: url(fira.woff2); }"
+        );
+    }
+
+    #[test]
+    fn can_parse_keyframes() {
+        parser_test!(
+            "@keyframes spin { from { rotate: 0deg; } 50%, 75% { rotate: 180deg; } to { rotate: 360deg; } }",
+            "@-webkit-keyframes spin { 0% { opacity: {start}; } }"
+        );
+    }
+
+    #[test]
+    fn can_parse_nested_rules() {
+        parser_test!(
+            "div { color: red; &:hover { color: blue; } p { color: {color}; } }",
+            "a { &.active, & + a { span:first-child { margin: 0; } } }",
+            "p { @media screen { color: red; b { margin: 0; } } }"
+        );
+    }
+
     #[test]
     fn parser_throws_errors_on_invalid_input() {
         parser_test!(
@@ -0,0 +1,406 @@
+//! Constant folding for JavaScript expressions in templates.
+//!
+//! An expression is folded if it only uses literals and compile-time constants, with the
+//! operators that can be evaluated without running any JavaScript. Anything else is left to be
+//! evaluated at runtime, so folding is only ever an optimization.
+use rslint_parser::{
+    ast::{
+        BinOp, Expr, ExprOrSpread, ExprStmt, LiteralKind, ObjectProp, PropName, Template,
+        TemplateElement, UnaryOp,
+    },
+    AstNode, SyntaxKind, SyntaxNode, SyntaxNodeExt,
+};
+
+/// A JavaScript value that is known at compile time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Const {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Const>),
+    Object(Vec<(String, Const)>),
+}
+
+impl Const {
+    /// Parses a JavaScript literal, like the JSON values of a comptime code block.
+    pub fn parse(text: &str) -> Option<Self> {
+        let parse = rslint_parser::parse_expr(text, 0);
+        if !parse.errors().is_empty() {
+            return None;
+        }
+        fold_expr(&parse.tree(), &|_| None)
+    }
+
+    /// Converts the value to a string, like `String(value)`. Returns `None` for numbers that
+    /// JavaScript would format differently from Rust.
+    pub fn to_js_string(&self) -> Option<String> {
+        Some(match self {
+            Const::Undefined => "undefined".to_owned(),
+            Const::Null => "null".to_owned(),
+            Const::Bool(b) => b.to_string(),
+            Const::Number(n) => number_to_string(*n)?,
+            Const::String(s) => s.clone(),
+            Const::Array(items) => {
+                let mut out = String::new();
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    if !matches!(item, Const::Undefined | Const::Null) {
+                        out.push_str(&item.to_js_string()?);
+                    }
+                }
+                out
+            }
+            Const::Object(_) => "[object Object]".to_owned(),
+        })
+    }
+
+    /// Whether the value is truthy, like `Boolean(value)`.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Const::Undefined | Const::Null => false,
+            Const::Bool(b) => *b,
+            Const::Number(n) => *n != 0.0 && !n.is_nan(),
+            Const::String(s) => !s.is_empty(),
+            Const::Array(_) | Const::Object(_) => true,
+        }
+    }
+
+    fn to_number(&self) -> Option<f64> {
+        match self {
+            Const::Undefined => Some(f64::NAN),
+            Const::Null => Some(0.0),
+            Const::Bool(b) => Some(f64::from(u8::from(*b))),
+            Const::Number(n) => Some(*n),
+            // String to number conversion has too many edge cases to be worth it
+            Const::String(_) | Const::Array(_) | Const::Object(_) => None,
+        }
+    }
+
+    fn is_primitive(&self) -> bool {
+        !matches!(self, Const::Array(_) | Const::Object(_))
+    }
+}
+
+/// Folds a mustache, attribute or block condition. `lookup` gets the value of a constant
+/// variable.
+pub fn fold(node: &SyntaxNode, lookup: &dyn Fn(&str) -> Option<Const>) -> Option<Const> {
+    if let Some(expr) = node.try_to::<Expr>() {
+        return fold_expr(&expr, lookup);
+    }
+    if let Some(stmt) = node.try_to::<ExprStmt>() {
+        return fold_expr(&stmt.expr()?, lookup);
+    }
+    match node.kind() {
+        SyntaxKind::SCRIPT | SyntaxKind::MODULE if node.children().count() == 1 => {
+            fold(&node.first_child()?, lookup)
+        }
+        _ => None,
+    }
+}
+
+fn fold_expr(expr: &Expr, lookup: &dyn Fn(&str) -> Option<Const>) -> Option<Const> {
+    match expr {
+        Expr::Literal(lit) => match lit.kind() {
+            LiteralKind::Number(_) => parse_number(lit.token().text()).map(Const::Number),
+            LiteralKind::String => Some(Const::String(unescape(
+                &lit.inner_string_text()?.to_string(),
+            )?)),
+            LiteralKind::Null => Some(Const::Null),
+            LiteralKind::Bool(b) => Some(Const::Bool(b)),
+            LiteralKind::BigInt(_) | LiteralKind::Regex => None,
+        },
+        Expr::NameRef(name) => {
+            let ident = name.ident_token()?;
+            match lookup(ident.text()) {
+                Some(value) => Some(value),
+                None if ident.text() == "undefined" => Some(Const::Undefined),
+                None => None,
+            }
+        }
+        Expr::GroupingExpr(group) => fold_expr(&group.inner()?, lookup),
+        Expr::Template(template) => fold_template(template, lookup),
+        Expr::ArrayExpr(array) => array
+            .elements()
+            .map(|elem| match elem {
+                ExprOrSpread::Expr(expr) => fold_expr(&expr, lookup),
+                ExprOrSpread::Spread(_) => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Const::Array),
+        Expr::ObjectExpr(object) => object
+            .props()
+            .map(|prop| {
+                let ObjectProp::LiteralProp(prop) = prop else {
+                    return None;
+                };
+                let key = match prop.key()? {
+                    PropName::Ident(name) => name.ident_token()?.text().to_string(),
+                    PropName::Literal(lit) if lit.is_string() => {
+                        unescape(&lit.inner_string_text()?.to_string())?
+                    }
+                    _ => return None,
+                };
+                Some((key, fold_expr(&prop.value()?, lookup)?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Const::Object),
+        Expr::DotExpr(dot) if dot.opt_chain_token().is_none() => {
+            let object = fold_expr(&dot.object()?, lookup)?;
+            member(&object, dot.prop()?.ident_token()?.text())
+        }
+        Expr::BracketExpr(bracket) if !expr.opt_chain() => {
+            let object = fold_expr(&bracket.object()?, lookup)?;
+            match fold_expr(&bracket.prop()?, lookup)? {
+                Const::String(prop) => member(&object, &prop),
+                Const::Number(n) => member(&object, &number_to_string(n)?),
+                _ => None,
+            }
+        }
+        Expr::UnaryExpr(unary) => {
+            let value = fold_expr(&unary.expr()?, lookup)?;
+            match unary.op()? {
+                UnaryOp::LogicalNot => Some(Const::Bool(!value.is_truthy())),
+                UnaryOp::Minus => Some(Const::Number(-value.to_number()?)),
+                UnaryOp::Plus => Some(Const::Number(value.to_number()?)),
+                _ => None,
+            }
+        }
+        Expr::BinExpr(bin) => {
+            let op = bin.op()?;
+            let lhs = fold_expr(&bin.lhs()?, lookup)?;
+            // The right hand side of logical operators is only needed if it's evaluated
+            match op {
+                BinOp::LogicalAnd if !lhs.is_truthy() => return Some(lhs),
+                BinOp::LogicalOr if lhs.is_truthy() => return Some(lhs),
+                BinOp::NullishCoalescing if !matches!(lhs, Const::Undefined | Const::Null) => {
+                    return Some(lhs)
+                }
+                BinOp::LogicalAnd | BinOp::LogicalOr | BinOp::NullishCoalescing => {
+                    return fold_expr(&bin.rhs()?, lookup)
+                }
+                _ => {}
+            }
+            let rhs = fold_expr(&bin.rhs()?, lookup)?;
+            binary(op, &lhs, &rhs)
+        }
+        Expr::CondExpr(cond) => {
+            if fold_expr(&cond.test()?, lookup)?.is_truthy() {
+                fold_expr(&cond.cons()?, lookup)
+            } else {
+                fold_expr(&cond.alt()?, lookup)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn fold_template(template: &Template, lookup: &dyn Fn(&str) -> Option<Const>) -> Option<Const> {
+    if template.tag().is_some() {
+        return None;
+    }
+    let mut out = String::new();
+    for child in template.syntax().children_with_tokens() {
+        if let Some(token) = child.as_token() {
+            if token.kind() == SyntaxKind::TEMPLATE_CHUNK {
+                out.push_str(&unescape(token.text())?);
+            }
+        } else if let Some(elem) = child.as_node().and_then(|n| n.try_to::<TemplateElement>()) {
+            out.push_str(&fold_expr(&elem.expr()?, lookup)?.to_js_string()?);
+        }
+    }
+    Some(Const::String(out))
+}
+
+fn member(object: &Const, prop: &str) -> Option<Const> {
+    match object {
+        Const::Object(props) => Some(
+            props
+                .iter()
+                .rev()
+                .find(|(key, _)| key == prop)
+                .map_or(Const::Undefined, |(_, value)| value.clone()),
+        ),
+        Const::Array(items) if prop == "length" => Some(Const::Number(items.len() as f64)),
+        Const::Array(items) => match prop.parse::<usize>() {
+            Ok(i) => Some(items.get(i).cloned().unwrap_or(Const::Undefined)),
+            Err(_) => None,
+        },
+        Const::String(s) if prop == "length" => {
+            Some(Const::Number(s.encode_utf16().count() as f64))
+        }
+        _ => None,
+    }
+}
+
+fn binary(op: BinOp, lhs: &Const, rhs: &Const) -> Option<Const> {
+    use std::cmp::Ordering;
+
+    let ordering = || match (lhs, rhs) {
+        (Const::String(a), Const::String(b)) => Some(a.encode_utf16().cmp(b.encode_utf16())),
+        _ => lhs.to_number()?.partial_cmp(&rhs.to_number()?),
+    };
+    let number =
+        |f: fn(f64, f64) -> f64| Some(Const::Number(f(lhs.to_number()?, rhs.to_number()?)));
+
+    match op {
+        BinOp::Plus => match (lhs, rhs) {
+            (Const::String(_), _) | (_, Const::String(_)) => Some(Const::String(format!(
+                "{}{}",
+                lhs.to_js_string()?,
+                rhs.to_js_string()?
+            ))),
+            _ => number(|a, b| a + b),
+        },
+        BinOp::Minus => number(|a, b| a - b),
+        BinOp::Times => number(|a, b| a * b),
+        BinOp::Divide => number(|a, b| a / b),
+        BinOp::Remainder => number(|a, b| a % b),
+        BinOp::Exponent => number(f64::powf),
+        BinOp::StrictEquality | BinOp::StrictInequality
+            if lhs.is_primitive() && rhs.is_primitive() =>
+        {
+            let eq = lhs == rhs;
+            Some(Const::Bool(eq == (op == BinOp::StrictEquality)))
+        }
+        BinOp::LessThan
+        | BinOp::GreaterThan
+        | BinOp::LessThanOrEqual
+        | BinOp::GreaterThanOrEqual => {
+            // Comparisons with NaN are always false
+            let Some(ordering) = ordering() else {
+                return lhs
+                    .to_number()
+                    .zip(rhs.to_number())
+                    .map(|_| Const::Bool(false));
+            };
+            Some(Const::Bool(match op {
+                BinOp::LessThan => ordering == Ordering::Less,
+                BinOp::GreaterThan => ordering == Ordering::Greater,
+                BinOp::LessThanOrEqual => ordering != Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Parses a number literal. rslint's parsed value is imprecise for decimals, so the text is
+/// parsed again.
+fn parse_number(text: &str) -> Option<f64> {
+    let text = text.replace('_', "");
+    let radix = match text.get(..2).map(str::to_ascii_lowercase).as_deref() {
+        Some("0x") => 16,
+        Some("0o") => 8,
+        Some("0b") => 2,
+        _ => return text.parse().ok(),
+    };
+    u64::from_str_radix(&text[2..], radix)
+        .ok()
+        .map(|n| n as f64)
+}
+
+/// Formats a number like JavaScript does, if it's not in exponential notation.
+fn number_to_string(n: f64) -> Option<String> {
+    if n.is_nan() {
+        Some("NaN".to_owned())
+    } else if n.is_infinite() {
+        Some(if n > 0.0 { "Infinity" } else { "-Infinity" }.to_owned())
+    } else if n == 0.0 {
+        Some("0".to_owned())
+    } else if (1e-6..1e21).contains(&n.abs()) {
+        Some(n.to_string())
+    } else {
+        None
+    }
+}
+
+/// Decodes the escapes in the text of a string literal or a template chunk. Returns `None` for
+/// escapes that aren't supported.
+pub fn unescape(s: &str) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            'b' => out.push('\u{8}'),
+            'f' => out.push('\u{c}'),
+            'v' => out.push('\u{b}'),
+            '0' if !chars.as_str().starts_with(|c: char| c.is_ascii_digit()) => out.push('\0'),
+            'u' => {
+                let hex = chars.as_str().get(..4)?;
+                let code = u32::from_str_radix(hex, 16).ok()?;
+                // Surrogate pairs are left to the runtime
+                out.push(char::from_u32(code)?);
+                chars.nth(3);
+            }
+            c @ ('\\' | '\'' | '"' | '`' | '$' | '/') => out.push(c),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use super::*;
+
+    fn fold_text(text: &str) -> Option<String> {
+        let lookup = |name: &str| match name {
+            "n" => Some(Const::Number(3.0)),
+            "title" => Some(Const::String("Hi".to_owned())),
+            "user" => Const::parse(r#"{"name": "Ada", "tags": ["a", "b"]}"#),
+            _ => None,
+        };
+        let syntax = rslint_parser::parse_module(text, 0).syntax();
+        fold(&syntax.first_child().unwrap(), &lookup).and_then(|value| value.to_js_string())
+    }
+
+    #[test]
+    fn folds_constant_expressions() {
+        let mut out = String::new();
+        for input in [
+            "n * 2 + 1",
+            "`${title}, ${user.name}!`",
+            "user.tags[1]",
+            "user.tags",
+            "title + n",
+            "n > 2 ? \"big\" : \"small\"",
+            "!title",
+            "user.missing ?? \"none\"",
+            "title.length",
+            "\"a\\nb\\u0041\"",
+            "0.1 + 0.2",
+            "n / 0",
+        ] {
+            writeln!(out, "{input} => {:?}", fold_text(input)).unwrap();
+        }
+        insta::assert_snapshot!(out);
+    }
+
+    #[test]
+    fn does_not_fold_dynamic_expressions() {
+        for input in [
+            "x + 1",
+            "title.toUpperCase()",
+            "n++",
+            "user?.name",
+            "1e21 + 1",
+            "tag`${n}`",
+            "[...user.tags]",
+        ] {
+            assert_eq!(None, fold_text(input), "{input}");
+        }
+    }
+}
@@ -0,0 +1,560 @@
+//! Pretty-printing of decorous files.
+//!
+//! The markup of a file is re-printed from its [`RawAst`], with the whitespace between nodes
+//! re-indented. Text is rendered as it's written, so it's never changed, and no whitespace is put
+//! between nodes that weren't separated by any. Code blocks, the JavaScript in the markup and the
+//! contents of elements whose whitespace is rendered, like `#pre`, are kept exactly as they are.
+use std::fmt::Write;
+
+use crate::{
+    ast::{
+        Attribute, AttributeValue, BindingKind, Code, Element, Node, NodeType, RawAst, SpecialBlock,
+    },
+    errors::ParseError,
+    location::Location,
+    Parser,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct FormatOptions {
+    /// The number of spaces to indent nested nodes by.
+    pub indent: usize,
+    /// The width that lines are kept under, when possible. Elements with attributes that don't
+    /// fit are wrapped, with one attribute per line.
+    pub max_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: 2,
+            max_width: 80,
+        }
+    }
+}
+
+/// Formats the decorous source `src`.
+pub fn format(src: &str, options: FormatOptions) -> Result<String, ParseError<Location>> {
+    let ast = Parser::new(src).parse_raw()?;
+    Ok(format_ast(&ast, options))
+}
+
+/// Formats a parsed decorous file.
+pub fn format_ast(ast: &RawAst<'_>, options: FormatOptions) -> String {
+    let mut formatter = Formatter {
+        options,
+        out: String::new(),
+        depth: 0,
+    };
+    for (i, code) in ast.head.iter().enumerate() {
+        if i > 0 {
+            formatter.out.push('\n');
+        }
+        formatter.write_code(code);
+    }
+    // The parser strips a space from the start and the end of the file's nodes
+    match ast.nodes.first().map(|node| &node.node_type) {
+        Some(NodeType::Text(text)) if text.starts_with(' ') => formatter.out.push(' '),
+        Some(NodeType::Text(_)) | None => {}
+        // Markup that starts on the line of a code block is separated from it
+        Some(_) if !ast.head.is_empty() => formatter.out.push(' '),
+        Some(_) => {}
+    }
+    formatter.write_nodes(&ast.nodes);
+    let last_text = text_of(ast.nodes.last());
+    if last_text.is_some_and(|text| !ast.tail.is_empty() || text.ends_with(' ')) {
+        formatter.pad_end(&ast.nodes);
+    } else if !ast.tail.is_empty() && !formatter.out.is_empty() {
+        // Code blocks after the markup are put after a blank line
+        if !formatter.out.ends_with('\n') {
+            formatter.out.push('\n');
+        }
+        formatter.out.push('\n');
+    }
+    for (i, code) in ast.tail.iter().enumerate() {
+        if i > 0 {
+            formatter.out.push('\n');
+        }
+        formatter.write_code(code);
+    }
+    // A newline after text at the end of the file would be a part of it
+    if !formatter.out.is_empty()
+        && !formatter.out.ends_with('\n')
+        && (last_text.is_none() || !ast.tail.is_empty())
+    {
+        formatter.out.push('\n');
+    }
+
+    formatter.out
+}
+
+struct Formatter {
+    options: FormatOptions,
+    out: String,
+    depth: usize,
+}
+
+impl Formatter {
+    fn write_code(&mut self, code: &Code<'_>) {
+        let comptime = if code.comptime { ":static" } else { "" };
+        let _ = write!(self.out, "---{}{comptime}{}---", code.lang, code.body);
+    }
+
+    fn write_newline(&mut self) {
+        self.out.push('\n');
+        self.write_indent();
+    }
+
+    fn write_indent(&mut self) {
+        let _ = write!(self.out, "{:1$}", "", self.depth * self.options.indent);
+    }
+
+    /// The column that the next character is written at.
+    fn column(&self) -> usize {
+        self.out.len() - self.out.rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    /// Writes `nodes` on the line that's being written. Only the whitespace between nodes is
+    /// changed: whitespace with a newline in it is re-indented, and other whitespace is turned
+    /// into a single space. Text is written exactly as it is, since it's rendered that way.
+    fn write_nodes<T>(&mut self, nodes: &[Node<'_, T>]) {
+        for node in nodes {
+            match &node.node_type {
+                // Comments end with a newline, so only the indent is put after them
+                NodeType::Text(text) if text.trim().is_empty() && self.out.ends_with('\n') => {
+                    if text.contains('\n') {
+                        self.out.push('\n');
+                    }
+                    self.write_indent();
+                }
+                NodeType::Text(text) if text.trim().is_empty() => {
+                    if !text.contains('\n') {
+                        self.out.push(' ');
+                        continue;
+                    }
+                    // Keep at most one blank line of the ones between nodes
+                    if text.matches('\n').count() > 1 {
+                        self.out.push('\n');
+                    }
+                    self.write_newline();
+                }
+                NodeType::Text(text) => self.out.push_str(text.0),
+                NodeType::Mustache(js) => {
+                    let _ = write!(self.out, "{{{}}}", js.text().to_string().trim());
+                }
+                // The newline that ends a comment is part of it
+                NodeType::Comment(comment) => {
+                    let _ = writeln!(self.out, "//{}", comment.trim_end());
+                }
+                NodeType::Element(elem) => self.write_element(elem),
+                NodeType::SpecialBlock(block) => self.write_special_block(block),
+            }
+        }
+    }
+
+    /// Writes the children of an element or block, followed by `close`. The children are put on
+    /// their own lines if they start with a newline or span multiple lines.
+    fn write_children<T>(&mut self, nodes: &[Node<'_, T>], close: &str) {
+        if nodes.is_empty() {
+            self.out.push_str(close);
+            return;
+        }
+        // The parser strips a space from the start of the children
+        if !starts_with(nodes, |c| c.is_whitespace() && c != ' ') {
+            self.out.push(' ');
+        }
+        let start = self.out.len();
+        self.depth += 1;
+        self.write_nodes(nodes);
+        self.depth -= 1;
+        match nodes.last().map(|node| &node.node_type) {
+            Some(NodeType::Text(_)) => self.pad_end(nodes),
+            Some(NodeType::Comment(_)) => self.write_indent(),
+            // Whitespace at the end of the children isn't kept by the parser, so a newline can be
+            // put there
+            _ if self.out[start..].contains('\n') => self.write_newline(),
+            _ => self.out.push(' '),
+        }
+        self.out.push_str(close);
+    }
+
+    /// Puts a space after `nodes`, which end with text, unless the text ends with whitespace the
+    /// parser doesn't strip.
+    fn pad_end<T>(&mut self, nodes: &[Node<'_, T>]) {
+        if needs_padding(text_of(nodes.last()).and_then(|text| text.chars().last())) {
+            self.out.push(' ');
+        }
+    }
+
+    fn write_element<T>(&mut self, elem: &Element<'_, T>) {
+        let attrs = elem.attrs.iter().map(format_attr).collect::<Vec<_>>();
+        let head = if attrs.is_empty() {
+            format!("#{}", elem.tag)
+        } else {
+            format!("#{}[{}]", elem.tag, attrs.join(" "))
+        };
+        // Dynamic tags are closed with a bare slash
+        let close = if elem.tag_expr.is_some() {
+            "/".to_owned()
+        } else {
+            format!("/{}", elem.tag)
+        };
+        if is_whitespace_sensitive(elem.tag) && !elem.children.is_empty() {
+            self.out.push_str(&head);
+            self.write_verbatim(&elem.children);
+            self.out.push_str(&close);
+            return;
+        }
+
+        if attrs.len() > 1 && self.column() + head.len() > self.options.max_width {
+            let _ = write!(self.out, "#{}[", elem.tag);
+            self.depth += 1;
+            for attr in &attrs {
+                self.write_newline();
+                self.out.push_str(attr);
+            }
+            self.depth -= 1;
+            self.write_newline();
+            self.out.push(']');
+        } else {
+            self.out.push_str(&head);
+        }
+        self.write_children(&elem.children, &close);
+    }
+
+    fn write_special_block<T>(&mut self, block: &SpecialBlock<'_, T>) {
+        match block {
+            SpecialBlock::If(block) => {
+                let _ = write!(self.out, "{{#if {}}}", block.expr.text().to_string().trim());
+                if let Some(else_block) = &block.else_block {
+                    self.write_children(&block.inner, "{:else}");
+                    self.write_children(else_block, "{/if}");
+                } else {
+                    self.write_children(&block.inner, "{/if}");
+                }
+            }
+            SpecialBlock::For(block) => {
+                let binding = block.index.map_or_else(
+                    || block.binding.to_owned(),
+                    |index| format!("{index}, {}", block.binding),
+                );
+                let _ = write!(
+                    self.out,
+                    "{{#for {binding} in {}}}",
+                    block.expr.text().to_string().trim()
+                );
+                self.write_children(&block.inner, "{/for}");
+            }
+            SpecialBlock::Head(block) => {
+                self.out.push_str("{#head}");
+                self.write_children(&block.inner, "{/head}");
+            }
+            SpecialBlock::Static(block) => {
+                self.out.push_str("{#static}");
+                self.write_children(&block.inner, "{/static}");
+            }
+            SpecialBlock::Cfg(block) => {
+                let _ = write!(self.out, "{{#cfg feature=\"{}\"}}", block.feature);
+                self.write_children(&block.inner, "{/cfg}");
+            }
+            SpecialBlock::Html(block) => {
+                let _ = write!(
+                    self.out,
+                    "{{#html {}}}",
+                    block.expr.text().to_string().trim()
+                );
+            }
+            // The contents of raw blocks are kept exactly as they are
+            SpecialBlock::Use(_) | SpecialBlock::Raw(_) | SpecialBlock::Translate(_) => {
+                self.write_verbatim_block(block);
+            }
+        }
+    }
+
+    /// Writes `nodes` exactly as they were written, without indenting them or changing their
+    /// whitespace. The parser strips a space from the start and the end of a list of nodes, so
+    /// one is put around them, unless they start or end with other whitespace.
+    fn write_verbatim<T>(&mut self, nodes: &[Node<'_, T>]) {
+        if nodes.is_empty() {
+            return;
+        }
+        let first = text_of(nodes.first()).and_then(|text| text.chars().next());
+        let last = text_of(nodes.last()).and_then(|text| text.chars().last());
+        if needs_padding(first) {
+            self.out.push(' ');
+        }
+        for node in nodes {
+            match &node.node_type {
+                NodeType::Text(text) => self.out.push_str(text.0),
+                NodeType::Mustache(js) => {
+                    let _ = write!(self.out, "{{{}}}", js.text().to_string().trim());
+                }
+                // The newline that ends a comment is part of it
+                NodeType::Comment(comment) => {
+                    let _ = writeln!(self.out, "//{}", comment.0.trim_end());
+                }
+                NodeType::Element(elem) => {
+                    let attrs = elem.attrs.iter().map(format_attr).collect::<Vec<_>>();
+                    if attrs.is_empty() {
+                        let _ = write!(self.out, "#{}", elem.tag);
+                    } else {
+                        let _ = write!(self.out, "#{}[{}]", elem.tag, attrs.join(" "));
+                    }
+                    self.write_verbatim(&elem.children);
+                    if elem.tag_expr.is_some() {
+                        self.out.push('/');
+                    } else {
+                        let _ = write!(self.out, "/{}", elem.tag);
+                    }
+                }
+                NodeType::SpecialBlock(block) => self.write_verbatim_block(block),
+            }
+        }
+        if needs_padding(last) {
+            self.out.push(' ');
+        }
+    }
+
+    fn write_verbatim_block<T>(&mut self, block: &SpecialBlock<'_, T>) {
+        match block {
+            SpecialBlock::If(block) => {
+                let _ = write!(self.out, "{{#if {}}}", block.expr.text().to_string().trim());
+                self.write_verbatim(&block.inner);
+                if let Some(else_block) = &block.else_block {
+                    self.out.push_str("{:else}");
+                    self.write_verbatim(else_block);
+                }
+                self.out.push_str("{/if}");
+            }
+            SpecialBlock::For(block) => {
+                let binding = block.index.map_or_else(
+                    || block.binding.to_owned(),
+                    |index| format!("{index}, {}", block.binding),
+                );
+                let _ = write!(
+                    self.out,
+                    "{{#for {binding} in {}}}",
+                    block.expr.text().to_string().trim()
+                );
+                self.write_verbatim(&block.inner);
+                self.out.push_str("{/for}");
+            }
+            SpecialBlock::Head(block) => {
+                self.out.push_str("{#head}");
+                self.write_verbatim(&block.inner);
+                self.out.push_str("{/head}");
+            }
+            SpecialBlock::Static(block) => {
+                self.out.push_str("{#static}");
+                self.write_verbatim(&block.inner);
+                self.out.push_str("{/static}");
+            }
+            SpecialBlock::Cfg(block) => {
+                let _ = write!(self.out, "{{#cfg feature=\"{}\"}}", block.feature);
+                self.write_verbatim(&block.inner);
+                self.out.push_str("{/cfg}");
+            }
+            SpecialBlock::Html(block) => {
+                let _ = write!(
+                    self.out,
+                    "{{#html {}}}",
+                    block.expr.text().to_string().trim()
+                );
+            }
+            SpecialBlock::Use(block) => {
+                let _ = write!(self.out, "{block}");
+            }
+            SpecialBlock::Raw(block) => {
+                let _ = write!(self.out, "{block}");
+            }
+            SpecialBlock::Translate(block) => {
+                let _ = write!(self.out, "{block}");
+            }
+        }
+    }
+}
+
+fn text_of<'a, T>(node: Option<&Node<'a, T>>) -> Option<&'a str> {
+    match node.map(|node| &node.node_type) {
+        Some(NodeType::Text(text)) => Some(text.0),
+        _ => None,
+    }
+}
+
+/// Checks if `nodes` start with text whose first character matches `f`.
+fn starts_with<T>(nodes: &[Node<'_, T>], f: impl FnOnce(char) -> bool) -> bool {
+    text_of(nodes.first())
+        .and_then(|text| text.chars().next())
+        .is_some_and(f)
+}
+
+/// Checks if verbatim nodes that start or end with `edge` need a space put around them, which is
+/// the case unless it's whitespace the parser doesn't strip.
+fn needs_padding(edge: Option<char>) -> bool {
+    !edge.is_some_and(|c| c.is_whitespace() && c != ' ')
+}
+
+/// Checks if the whitespace in elements with `tag` is rendered as it is written, so their
+/// contents can't be reformatted.
+fn is_whitespace_sensitive(tag: &str) -> bool {
+    matches!(tag, "pre" | "textarea" | "listing" | "plaintext" | "xmp")
+}
+
+fn format_attr(attr: &Attribute<'_>) -> String {
+    match attr {
+        Attribute::KeyValue(key, None) => (*key).to_owned(),
+        Attribute::KeyValue(key, Some(AttributeValue::Literal(lit))) => format!("{key}=\"{lit}\""),
+        Attribute::KeyValue(key, Some(AttributeValue::JavaScript(js))) => {
+            format!("{key}={{{}}}", js.text().to_string().trim())
+        }
+        Attribute::EventHandler(handler) => {
+            let mut out = format!("@{}", handler.event);
+            for modifier in &handler.modifiers {
+                let _ = write!(out, "|{}", modifier.method());
+            }
+            let _ = write!(out, "={{{}}}", handler.expr.text().to_string().trim());
+            out
+        }
+        Attribute::Binding(binding) => match binding.kind {
+            BindingKind::Value => format!(":{}:", binding.var),
+            BindingKind::Html => format!(":html:{}:", binding.var),
+            BindingKind::Text => format!(":text:{}:", binding.var),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(src: &str) -> String {
+        format(src, FormatOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn can_format_nested_elements() {
+        insta::assert_snapshot!(fmt(
+            "#div[class=\"a\"   id={x}]\n#p   hello   {  x  } /p\n      #ul\n#li one /li\n\n\n #li[@click|preventDefault={() => x += 1}] two /li\n/ul /div"
+        ));
+    }
+
+    #[test]
+    fn keeps_escapes_in_quoted_literals() {
+        insta::assert_snapshot!(fmt(r#"#p[title="say \"hi\" \\ bye"]:Hi"#));
+    }
+
+    #[test]
+    fn wraps_attributes_that_do_not_fit() {
+        insta::assert_snapshot!(fmt(
+            "#input[type=\"text\" placeholder=\"A very long placeholder for the input\" :value: @input={() => console.log(value)}]/input"
+        ));
+    }
+
+    #[test]
+    fn normalizes_special_blocks() {
+        insta::assert_snapshot!(fmt(
+            "{#if   x > 1  } #p big /p {:else} small {/if}\n{#for   item   in   items} {item} {/for}\n{#use \"./button.decor\"}\n{#html    content  }"
+        ));
+    }
+
+    #[test]
+    fn keeps_code_blocks_and_comments() {
+        insta::assert_snapshot!(fmt(
+            "---js\nlet x   =   0;\n---\n---rust:static\nfn main() {}\n---\n// a comment   \n\n\n#p {x} /p\n---css\np { color: red; }\n---"
+        ));
+    }
+
+    #[test]
+    fn keeps_raw_blocks_verbatim() {
+        insta::assert_snapshot!(fmt(
+            "#div\n{#raw}<svg   viewBox=\"0 0 10 10\">\n  <path d=\"M0 0\"/>\n</svg>{/raw}   /div"
+        ));
+    }
+
+    #[test]
+    fn keeps_whitespace_sensitive_elements_verbatim() {
+        insta::assert_snapshot!(fmt(
+            "#div\n#pre\n  let   x = {x};\n    #b:bold  \n  // note\n{#if  y }  y  {/if}\n/pre   #textarea[rows=\"2\"]  a\n\n  b  /textarea /div"
+        ));
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        for src in SOURCES {
+            let formatted = fmt(src);
+            assert_eq!(formatted, fmt(&formatted), "{src}");
+        }
+    }
+
+    #[test]
+    fn formatting_keeps_the_rendered_nodes() {
+        for src in SOURCES {
+            let before = Parser::new(src).parse_raw().unwrap();
+            let after = fmt(src);
+            let after = Parser::new(&after).parse_raw().unwrap();
+            assert_eq!(shape(&before.nodes), shape(&after.nodes), "{src}");
+        }
+    }
+
+    const SOURCES: &[&str] = &[
+        "#div[class=\"a\"] #p hello {x} /p // comment\n #span:Hi /div",
+        "{#if x} #p {x} /p {:else} #p none /p {/if}",
+        "{#head} #title {title} /title #meta[charset=\"utf-8\"]/meta {/head}",
+        "#main {#static} #h1 Welcome /h1 {/static} /main",
+        "#nav {#cfg feature=\"admin\"} #a Admin /a {/cfg} /nav",
+        "#p {@t \"greeting\"}, {name}! /p",
+        "#{tag}[class=\"title\"] Hello /",
+        "#div {#raw}<b>{not parsed}</b>\n  #p /p{/raw} /div",
+        "---js let x = 0; --- #button[@click={() => x += 1}] {x} /button",
+        "#div #pre\n  a   {x}\n    #b:b  \n/pre /div",
+        "#p\n#b:bold\n#i:italic\n/p",
+        "#p:hello   world",
+        "#p   two  spaces  {x}  after   /p",
+        "#ul\n#li:one\n  #li:two\n\n\n#li  three\n/li /ul",
+        "#p hello\n  world\n/p #p{x}{y}/p",
+        "{#if x}yes{:else}  no  {/if}{#for i in items}{i}{/for}",
+        "#div // comment\n/div #p // comment\n  text /p",
+        "  leading #p /p trailing  ",
+    ];
+
+    /// Describes `nodes` without their locations, with all of the whitespace between them
+    /// treated the same.
+    fn shape<T>(nodes: &[Node<'_, T>]) -> String {
+        let mut out = String::new();
+        for node in nodes {
+            match &node.node_type {
+                NodeType::Text(text) if text.trim().is_empty() => out.push_str("<ws>"),
+                NodeType::Element(elem) => {
+                    let attrs = elem.attrs.iter().map(format_attr).collect::<Vec<_>>();
+                    let _ = write!(out, "<{} {attrs:?}>{}</>", elem.tag, shape(&elem.children));
+                }
+                NodeType::SpecialBlock(SpecialBlock::If(block)) => {
+                    let else_block = block.else_block.as_deref().map(shape);
+                    let _ = write!(out, "<if>{}<else>{else_block:?}</>", shape(&block.inner));
+                }
+                NodeType::SpecialBlock(SpecialBlock::For(block)) => {
+                    let _ = write!(out, "<for>{}</>", shape(&block.inner));
+                }
+                NodeType::SpecialBlock(SpecialBlock::Head(block)) => {
+                    let _ = write!(out, "<head>{}</>", shape(&block.inner));
+                }
+                NodeType::SpecialBlock(SpecialBlock::Static(block)) => {
+                    let _ = write!(out, "<static>{}</>", shape(&block.inner));
+                }
+                NodeType::SpecialBlock(SpecialBlock::Cfg(block)) => {
+                    let _ = write!(out, "<cfg>{}</>", shape(&block.inner));
+                }
+                _ => {
+                    let mut formatter = Formatter {
+                        options: FormatOptions::default(),
+                        out: String::new(),
+                        depth: 0,
+                    };
+                    formatter.write_nodes(std::slice::from_ref(node));
+                    let _ = write!(out, "{:?}", formatter.out);
+                }
+            }
+        }
+        out
+    }
+}
@@ -1,9 +1,16 @@
 pub mod ast;
 mod component;
 pub mod css;
+mod entities;
+pub mod fold;
+pub mod formatter;
+pub mod lint;
 pub mod location;
 mod parser;
 pub mod utils;
 
 pub use component::*;
 pub use parser::*;
+/// The JavaScript parser used for scripts and mustaches, for building a
+/// [`DecorousAst`](ast::DecorousAst) from syntax trees that were parsed elsewhere.
+pub use rslint_parser;
@@ -0,0 +1,205 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use decorous_errors::{DiagnosticBuilder, DynErrStream, Severity};
+
+/// A named check for code that is likely to be a mistake. The level of each lint can be
+/// configured with [`LintLevels`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A variable declared in the script that is never used.
+    UnusedVariable,
+    /// A variable that is used, but not declared in the script or known to be a global.
+    UnboundVariable,
+    /// An attribute that is set more than once on the same element.
+    DuplicateAttribute,
+    /// A binding on an element that has no property to bind to, or to something that isn't a
+    /// variable of the script.
+    InvalidBindingTarget,
+    /// An element inside of another that HTML doesn't allow it in, like a `#div` in a `#p`.
+    InvalidNesting,
+    /// A void element, like `#img`, that is given children.
+    VoidElementChildren,
+    /// An `id` that is set on more than one element.
+    DuplicateId,
+    /// An `{#html}` block, which can run scripts if its contents come from users, rendered without
+    /// a sanitizer.
+    UnsanitizedHtml,
+    /// A component with so many reactive values that finding the ones that changed slows down
+    /// its updates.
+    TooManyVariables,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 9] = [
+        Lint::UnusedVariable,
+        Lint::UnboundVariable,
+        Lint::DuplicateAttribute,
+        Lint::InvalidBindingTarget,
+        Lint::InvalidNesting,
+        Lint::VoidElementChildren,
+        Lint::DuplicateId,
+        Lint::UnsanitizedHtml,
+        Lint::TooManyVariables,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnboundVariable => "unbound-variable",
+            Lint::DuplicateAttribute => "duplicate-attribute",
+            Lint::InvalidBindingTarget => "invalid-binding-target",
+            Lint::InvalidNesting => "invalid-nesting",
+            Lint::VoidElementChildren => "void-element-children",
+            Lint::DuplicateId => "duplicate-id",
+            Lint::UnsanitizedHtml => "unsanitized-html",
+            Lint::TooManyVariables => "too-many-variables",
+        }
+    }
+
+    /// The level of the lint when it isn't configured. Void elements with children are denied,
+    /// since their children can't be written into HTML.
+    pub fn default_level(self) -> Level {
+        match self {
+            Lint::VoidElementChildren => Level::Deny,
+            _ => Level::Warn,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Lint {
+    type Err = UnknownLint;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .into_iter()
+            .find(|lint| lint.name() == s)
+            .ok_or_else(|| UnknownLint(s.to_owned()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown lint: {0}")]
+pub struct UnknownLint(pub String);
+
+/// What happens when a [`Lint`] finds something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Level {
+    /// Nothing is reported.
+    Allow,
+    /// A warning is reported.
+    #[default]
+    Warn,
+    /// An error is reported, and the component fails to compile.
+    Deny,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Allow => write!(f, "allow"),
+            Level::Warn => write!(f, "warn"),
+            Level::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = UnknownLevel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Level::Allow),
+            "warn" => Ok(Level::Warn),
+            "deny" => Ok(Level::Deny),
+            _ => Err(UnknownLevel(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown lint level: {0}, expected one of allow, warn or deny")]
+pub struct UnknownLevel(pub String);
+
+/// The configured [`Level`] of each lint. Lints that aren't configured are at their
+/// [default level](Lint::default_level).
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    levels: HashMap<Lint, Level>,
+}
+
+impl LintLevels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    pub fn get(&self, lint: Lint) -> Level {
+        self.levels
+            .get(&lint)
+            .copied()
+            .unwrap_or_else(|| lint.default_level())
+    }
+
+    /// Reports `diagnostic` for `lint` at the configured level. Returns `true` if the lint is
+    /// denied.
+    pub(crate) fn emit(
+        &self,
+        errs: &DynErrStream,
+        lint: Lint,
+        diagnostic: DiagnosticBuilder,
+    ) -> bool {
+        let level = self.get(lint);
+        let severity = match level {
+            Level::Allow => return false,
+            Level::Warn => Severity::Warning,
+            Level::Deny => Severity::Error,
+        };
+        errs.emit(
+            diagnostic
+                .severity(severity)
+                .note(format!("`{lint}` is set to `{level}`"))
+                .build(),
+        );
+        level == Level::Deny
+    }
+}
+
+impl FromIterator<(Lint, Level)> for LintLevels {
+    fn from_iter<T: IntoIterator<Item = (Lint, Level)>>(iter: T) -> Self {
+        Self {
+            levels: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_lint_names_and_levels() {
+        for lint in Lint::ALL {
+            assert_eq!(Ok(lint), lint.name().parse());
+        }
+        assert!("unused_variable".parse::<Lint>().is_err());
+        assert_eq!(Ok(Level::Deny), "deny".parse());
+        assert!("error".parse::<Level>().is_err());
+    }
+
+    #[test]
+    fn unconfigured_lints_are_at_their_default_level() {
+        let levels = LintLevels::from_iter([(Lint::UnusedVariable, Level::Allow)]);
+        assert_eq!(Level::Allow, levels.get(Lint::UnusedVariable));
+        assert_eq!(Level::Warn, levels.get(Lint::DuplicateAttribute));
+        assert_eq!(Level::Deny, levels.get(Lint::VoidElementChildren));
+    }
+}
@@ -1,7 +1,10 @@
-use rslint_parser::SyntaxNode;
+use rslint_parser::{parse_with_syntax, Syntax};
 use thiserror::Error;
 
-use crate::{ast::Code, css::ast::Css};
+use crate::{
+    ast::{Code, Script},
+    css::ast::Css,
+};
 
 macro_rules! setter {
     ($name:ident, $field:ident: $field_type:ty) => {
@@ -23,7 +26,8 @@ pub struct AlreadySetError;
 
 #[derive(Debug, Default)]
 pub struct CodeBlocks<'ast> {
-    script: Option<SyntaxNode>,
+    /// Every script block in source order, with the JavaScript it was parsed from.
+    scripts: Vec<(String, Script)>,
     css: Option<Css>,
     wasm: Option<Code<'ast>>,
     comptime: Option<Code<'ast>>,
@@ -37,16 +41,51 @@ impl<'ast> CodeBlocks<'ast> {
     pub fn into_parts(
         self,
     ) -> (
-        Option<SyntaxNode>,
+        Option<Script>,
         Option<Css>,
         Option<Code<'ast>>,
         Option<Code<'ast>>,
     ) {
-        (self.script, self.css, self.wasm, self.comptime)
+        let script = merge_scripts(self.scripts);
+        (script, self.css, self.wasm, self.comptime)
+    }
+
+    /// Adds a script block, parsed from `js`. Script blocks are joined in the order they're added.
+    pub fn add_script(&mut self, js: String, script: Script) {
+        self.scripts.push((js, script));
+    }
+
+    /// Adds a style block. Its rules come after those of the blocks before it.
+    pub fn add_css(&mut self, css: Css) {
+        match &mut self.css {
+            Some(existing) => existing.rules.extend(css.rules),
+            None => self.css = Some(css),
+        }
     }
 
-    setter!(set_script, script: SyntaxNode);
-    setter!(set_css, css: Css);
     setter!(set_wasm, wasm: Code<'ast>);
     setter!(set_static_wasm, comptime: Code<'ast>);
 }
+
+/// Joins the script blocks into one script. Each block was already parsed on its own, with its
+/// errors reported where they are in the source, so the errors of the joined script are ignored.
+fn merge_scripts(mut scripts: Vec<(String, Script)>) -> Option<Script> {
+    if scripts.len() <= 1 {
+        return scripts.pop().map(|(_, script)| script);
+    }
+
+    let mut blocks = scripts
+        .into_iter()
+        .map(|(text, script)| (text, script.offsets));
+    let (mut js, mut offsets) = blocks.next()?;
+    for (text, block_offsets) in blocks {
+        // A newline keeps a trailing line comment from swallowing the next block
+        js.push('\n');
+        let start = js.len();
+        offsets.push(start, block_offsets.source(0));
+        js.push_str(&text);
+    }
+    let node = parse_with_syntax(&js, 0, Syntax::default().module().top_level_await()).syntax();
+
+    Some(Script { node, offsets })
+}
@@ -1,14 +1,25 @@
-use decorous_errors::DynErrStream;
-use std::{borrow::Cow, fmt, io};
+use decorous_errors::{DynErrStream, Source};
+use std::{borrow::Cow, collections::HashMap, fmt, io};
 use thiserror::Error;
 
-use crate::{ast::Code, location::Location};
+use crate::{ast::Code, lint::LintLevels, location::Location};
 
 #[derive(Clone)]
 pub struct Ctx<'a> {
     pub preprocessor: &'a dyn Preprocessor,
     pub executor: &'a dyn CodeExecutor,
     pub errs: DynErrStream<'a>,
+    pub lints: LintLevels,
+    pub component_ids: &'a dyn ComponentIdScheme,
+    /// The features that `{#cfg}` blocks are kept for.
+    pub features: &'a [String],
+    /// The values that `$env.NAME` is replaced with.
+    pub env: &'a dyn EnvVars,
+    /// The messages that `{@t "key"}` expressions are replaced with.
+    pub messages: &'a dyn Messages,
+    /// Remove the `{#use}` declarations whose component is never rendered, so no import is
+    /// emitted for them.
+    pub drop_unused_uses: bool,
 }
 
 impl fmt::Debug for Ctx<'_> {
@@ -17,6 +28,12 @@ impl fmt::Debug for Ctx<'_> {
             .field("preprocessor", &"preproc")
             .field("preprocessor", &"exec")
             .field("errs", &self.errs)
+            .field("lints", &self.lints)
+            .field("component_ids", &"ids")
+            .field("features", &self.features)
+            .field("env", &"env")
+            .field("messages", &"messages")
+            .field("drop_unused_uses", &self.drop_unused_uses)
             .finish()
     }
 }
@@ -33,21 +50,38 @@ impl Default for Ctx<'_> {
                     src: "",
                 },
             ),
+            lints: LintLevels::default(),
+            component_ids: &HashedIds,
+            features: &[],
+            env: &NullEnv,
+            messages: &NullMessages,
+            drop_unused_uses: false,
         }
     }
 }
 
+/// An error or a warning from a preprocessor.
 #[derive(Debug, Error, Clone, PartialEq)]
 #[error("{msg}")]
 #[non_exhaustive]
 pub struct PreprocessError {
-    pub loc: Location,
+    /// Where the error is in the body of the code block. Without a location, the error is
+    /// reported on the whole code block.
+    pub loc: Option<Location>,
     pub msg: Cow<'static, str>,
 }
 
 impl PreprocessError {
-    pub fn new(loc: Location, msg: Cow<'static, str>) -> Self {
-        Self { loc, msg }
+    pub fn new(msg: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            loc: None,
+            msg: msg.into(),
+        }
+    }
+
+    pub fn with_loc(mut self, loc: Location) -> Self {
+        self.loc = Some(loc);
+        self
     }
 }
 
@@ -58,15 +92,76 @@ pub enum Override {
     None,
 }
 
+/// The output of a [`Preprocessor`].
+#[derive(Debug)]
+pub struct Preprocessed {
+    pub code: Override,
+    /// Warnings about the code block, which don't stop it from being used.
+    pub warnings: Vec<PreprocessError>,
+    /// Maps the output back to the code block. Without a map, errors in the output are reported
+    /// on the whole code block.
+    pub map: Option<PositionMap>,
+}
+
+impl From<Override> for Preprocessed {
+    fn from(code: Override) -> Self {
+        Self {
+            code,
+            warnings: vec![],
+            map: None,
+        }
+    }
+}
+
+/// Maps offsets in the output of a [`Preprocessor`] to offsets in the body of the code block it
+/// preprocessed.
+///
+/// The map is made of mappings from an offset in the output to an offset in the code block. An
+/// offset in the output is mapped relative to the closest mapping before it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PositionMap {
+    mappings: Vec<(usize, usize)>,
+}
+
+impl PositionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a mapping from `output` to `source`.
+    pub fn add(&mut self, output: usize, source: usize) {
+        let idx = self.mappings.partition_point(|(out, _)| *out <= output);
+        self.mappings.insert(idx, (output, source));
+    }
+
+    /// Maps an offset in the output to the code block. Offsets before the first mapping can't be
+    /// mapped.
+    pub fn map(&self, output: usize) -> Option<usize> {
+        let idx = self.mappings.partition_point(|(out, _)| *out <= output);
+        let (out, source) = self.mappings.get(idx.checked_sub(1)?)?;
+        Some(source + (output - out))
+    }
+}
+
+impl FromIterator<(usize, usize)> for PositionMap {
+    fn from_iter<T: IntoIterator<Item = (usize, usize)>>(iter: T) -> Self {
+        let mut map = Self::new();
+        for (output, source) in iter {
+            map.add(output, source);
+        }
+        map
+    }
+}
+
 pub trait Preprocessor {
-    fn preprocess(&self, lang: &str, body: &str) -> Result<Override, PreprocessError>;
+    fn preprocess(&self, lang: &str, body: &str) -> Result<Preprocessed, PreprocessError>;
 }
 
 impl<T> Preprocessor for &T
 where
     T: Preprocessor,
 {
-    fn preprocess(&self, lang: &str, body: &str) -> Result<Override, PreprocessError> {
+    fn preprocess(&self, lang: &str, body: &str) -> Result<Preprocessed, PreprocessError> {
         (*self).preprocess(lang, body)
     }
 }
@@ -78,8 +173,8 @@ impl Preprocessor for NullPreproc {
         &self,
         _lang: &str,
         _body: &str,
-    ) -> std::result::Result<Override, PreprocessError> {
-        Ok(Override::None)
+    ) -> std::result::Result<Preprocessed, PreprocessError> {
+        Ok(Override::None.into())
     }
 }
 
@@ -128,3 +223,87 @@ impl FromIterator<JsDecl> for JsEnv {
         Self(iter.into_iter().collect())
     }
 }
+
+/// Decides the id of a component, which keeps its CSS from applying to other components.
+pub trait ComponentIdScheme {
+    fn component_id(&self, source: &Source) -> u8;
+}
+
+impl<T> ComponentIdScheme for &T
+where
+    T: ComponentIdScheme,
+{
+    fn component_id(&self, source: &Source) -> u8 {
+        (*self).component_id(source)
+    }
+}
+
+/// Derives the id of a component from a hash of its name and contents, so that building the same
+/// component twice gives it the same id.
+pub struct HashedIds;
+
+impl ComponentIdScheme for HashedIds {
+    fn component_id(&self, source: &Source) -> u8 {
+        // FNV-1a, which unlike the hashers of std is guaranteed to give the same hash everywhere
+        let hash = source
+            .name
+            .bytes()
+            .chain([0])
+            .chain(source.src.bytes())
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        hash.to_le_bytes().into_iter().fold(0, |id, byte| id ^ byte)
+    }
+}
+
+/// Gives every component the same id.
+pub struct FixedId(pub u8);
+
+impl ComponentIdScheme for FixedId {
+    fn component_id(&self, _source: &Source) -> u8 {
+        self.0
+    }
+}
+
+/// Looks up the variables that `$env.NAME` reads when a component is compiled.
+pub trait EnvVars {
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+impl EnvVars for HashMap<String, String> {
+    fn get(&self, name: &str) -> Option<String> {
+        HashMap::get(self, name).cloned()
+    }
+}
+
+/// Has no variables, so every use of `$env` is an error.
+pub struct NullEnv;
+
+impl EnvVars for NullEnv {
+    fn get(&self, _name: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Looks up the messages that `{@t "key"}` expressions are replaced with, from the catalog of the
+/// locale being built.
+pub trait Messages {
+    /// Gets the message of `key`, or `None` if it isn't translated.
+    fn message(&self, key: &str) -> Option<String>;
+}
+
+impl Messages for HashMap<String, String> {
+    fn message(&self, key: &str) -> Option<String> {
+        self.get(key).cloned()
+    }
+}
+
+/// Has no catalog, so every `{@t}` expression is replaced with its key.
+pub struct NullMessages;
+
+impl Messages for NullMessages {
+    fn message(&self, key: &str) -> Option<String> {
+        Some(key.to_owned())
+    }
+}
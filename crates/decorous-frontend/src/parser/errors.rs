@@ -1,6 +1,6 @@
 use std::{borrow::Cow, fmt, ops::Range};
 
-use decorous_errors::{Diagnostic, DiagnosticBuilder, Helper};
+use decorous_errors::{Diagnostic, DiagnosticBuilder, Fix, Helper};
 use thiserror::Error;
 
 use crate::{css, location::Location, PreprocessError};
@@ -20,24 +20,34 @@ pub enum ParseErrorType {
     Expected(&'static str),
     #[error("expected {:?}", .0)]
     ExpectedAny(&'static [&'static str]),
+    #[error("unclosed block: {{#{0}}}")]
+    UnclosedBlock(&'static str),
     #[error("unclosed attrs")]
     UnclosedAttrs,
     #[error("invalid character, expected {0:?}, got {1}")]
     ExpectedCharacterAny(Vec<char>, char),
     #[error("expected closing tag. If you meant to escape the slash, use '\\/'")]
     ExpectedClosingTag,
-    #[error("cannot have more than one script block")]
-    CannotHaveTwoScripts,
-    #[error("cannot have more than one style block")]
-    CannotHaveTwoStyles,
     #[error("cannot have more than one static block")]
     CannotHaveTwoStatics,
     #[error("cannot have more than one WebAssembly block")]
     CannotHaveTwoWasmBlocks,
     #[error("parse error in JavaScript: {title}")]
     JavaScriptDiagnostics { title: String },
-    #[error("invalid special block type: {0}. Only `for` and `if` are accepted.")]
+    #[error(
+        "invalid special block type: {0}. Only `for`, `if`, `use`, `raw`, `html`, `head`, `static`, and `cfg` are accepted."
+    )]
     InvalidSpecialBlockType(String),
+    #[error("invalid special expression: {0}. Only `t` is accepted.")]
+    InvalidSpecialExprType(String),
+    #[error(
+        "invalid event modifier: {0}. Only `preventDefault` and `stopPropagation` are accepted."
+    )]
+    InvalidEventModifier(String),
+    #[error("invalid character reference: {0}")]
+    InvalidCharacterReference(String),
+    #[error("{0} aren't supported in TypeScript blocks, because they generate code")]
+    UnsupportedTypeScript(&'static str),
     #[error("the decorous parser failed with an error")]
     DidError,
     // Boxed because this enum variant would otherwise be very large.
@@ -68,13 +78,14 @@ impl From<ParseError<Location>> for Diagnostic {
         let mut diagnostic =
             DiagnosticBuilder::new(value.to_string(), value.fragment().offset()).build();
         if let Some(help) = value.help() {
-            diagnostic.note = Some(Cow::Borrowed(help.message()));
+            diagnostic.note = Some(help.message.clone());
             if let Some(span) = help.corresponding_span() {
                 diagnostic.helpers.push(Helper {
                     msg: Cow::Borrowed("from here"),
                     span: span.clone(),
                 });
             }
+            diagnostic.fixes.extend(help.fix().cloned());
         }
         if value.err_type() == &ParseErrorType::DidError {
             return diagnostic;
@@ -92,7 +103,9 @@ impl From<ParseError<Location>> for Diagnostic {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Help {
     corresponding_span: Option<Range<usize>>,
-    message: &'static str,
+    message: Cow<'static, str>,
+    // Boxed to keep parse errors small, since few of them have a fix
+    fix: Option<Box<Fix>>,
 }
 
 impl<T> ParseError<T> {
@@ -118,28 +131,99 @@ impl<T> ParseError<T> {
 }
 
 impl Help {
-    pub fn with_span(span: Range<usize>, message: &'static str) -> Self {
+    pub fn with_span(span: Range<usize>, message: impl Into<Cow<'static, str>>) -> Self {
         Self {
             corresponding_span: Some(span),
-            message,
+            message: message.into(),
+            fix: None,
         }
     }
 
     /// Creates a new `Help`, with no corresponding line.
-    pub fn with_message(message: &'static str) -> Self {
+    pub fn with_message(message: impl Into<Cow<'static, str>>) -> Self {
         Self {
             corresponding_span: None,
-            message,
+            message: message.into(),
+            fix: None,
         }
     }
 
+    /// Creates a `Help` suggesting the closest of `candidates` to `word`, if any are close
+    /// enough to be a typo of it. The suggestion is written with `write`, e.g. to wrap it in the
+    /// syntax it was written in.
+    pub fn did_you_mean<'a>(
+        word: &str,
+        candidates: impl IntoIterator<Item = &'a str>,
+        write: impl FnOnce(&str) -> String,
+    ) -> Option<Self> {
+        closest_match(word, candidates)
+            .map(|suggestion| Self::with_message(format!("did you mean `{}`?", write(suggestion))))
+    }
+
+    /// Attaches an edit that does what the help message suggests.
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(Box::new(fix));
+        self
+    }
+
     pub fn corresponding_span(&self) -> Option<&Range<usize>> {
         self.corresponding_span.as_ref()
     }
 
-    pub fn message(&self) -> &'static str {
-        self.message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn fix(&self) -> Option<&Fix> {
+        self.fix.as_deref()
+    }
+}
+
+/// Finds the candidate with the smallest edit distance to `word`, if it's small enough for
+/// `word` to be a typo of it.
+pub(crate) fn closest_match<'a>(
+    word: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (word.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != word)
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, candidate)| {
+            *distance <= max_distance && *distance < candidate.chars().count()
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The number of insertions, deletions, substitutions, and swaps of adjacent characters it takes
+/// to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // distances[i][j] is the distance between the first i characters of a and the first j of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
     }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
 }
 
 impl fmt::Display for Help {
@@ -147,3 +231,26 @@ impl fmt::Display for Help {
         write!(f, "{}", self.message)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_swaps_as_one_edit() {
+        assert_eq!(0, edit_distance("div", "div"));
+        assert_eq!(1, edit_distance("dvi", "div"));
+        assert_eq!(1, edit_distance("fro", "for"));
+        assert_eq!(2, edit_distance("span", "sapn_"));
+        assert_eq!(3, edit_distance("", "for"));
+    }
+
+    #[test]
+    fn only_close_matches_are_suggested() {
+        assert_eq!(Some("for"), closest_match("fro", ["if", "for", "use"]));
+        assert_eq!(Some("else"), closest_match("esle", ["else"]));
+        assert_eq!(None, closest_match("while", ["if", "for", "use"]));
+        // Anything is one edit away from a single character
+        assert_eq!(None, closest_match("a", ["p"]));
+    }
+}
@@ -23,6 +23,7 @@ bitflags! {
     }
 }
 
+/// A token of a decorous file, with the location it spans in the source.
 #[derive(Debug, Clone, Copy)]
 pub struct Token<'src> {
     pub kind: TokenKind<'src>,
@@ -37,9 +38,14 @@ pub enum TokenKind<'src> {
     SpecialBlockStart(&'src str),
     SpecialBlockEnd(&'src str),
     SpecialExtender(&'src str),
+    /// The start of an expression like `{@t "key"}`, with the name that follows the `@`.
+    SpecialExpr(&'src str),
     Mustache(&'src str),
     Comment(&'src str),
     CodeBlockIndicator,
+    /// Source text that isn't decorous, like the body of a code block or the expression of an
+    /// if block. Only produced when the lexer is recording tokens.
+    Code(&'src str),
 
     Rbrace,
     Quotes(&'src str),
@@ -47,6 +53,8 @@ pub enum TokenKind<'src> {
     Lbracket,
     Rbracket,
     Colon,
+    Dot,
+    Pipe,
     Equals,
     At,
     In,
@@ -64,11 +72,12 @@ macro_rules! token1 {
     }};
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Lexer<'src> {
     allowed: Allowed,
     attrs_mode: bool,
     harpoon: Harpoon<'src>,
+    recorded: Option<Vec<Token<'src>>>,
 }
 
 impl<'src> Lexer<'src> {
@@ -77,10 +86,50 @@ impl<'src> Lexer<'src> {
             harpoon: Harpoon::new(src),
             allowed: Allowed::empty(),
             attrs_mode: false,
+            recorded: None,
         }
     }
 
+    /// Makes the lexer keep every token it consumes, which can be taken with
+    /// [`Lexer::take_recorded`]. Peeked tokens are not recorded until they are consumed.
+    pub fn recording(mut self) -> Self {
+        self.recorded = Some(vec![]);
+        self
+    }
+
+    pub fn take_recorded(&mut self) -> Vec<Token<'src>> {
+        self.recorded.take().unwrap_or_default()
+    }
+
     pub fn next_token(&mut self) -> Token<'src> {
+        let tok = self.lex_token();
+        if tok.kind != TokenKind::Eof {
+            self.record(tok);
+        }
+        tok
+    }
+
+    fn record(&mut self, mut tok: Token<'src>) {
+        if let Some(recorded) = &mut self.recorded {
+            // Code block indicators only point to their last dash when parsing
+            if tok.kind == TokenKind::CodeBlockIndicator {
+                tok.loc = Location::new(tok.loc.offset() - 2, 3);
+            }
+            recorded.push(tok);
+        }
+    }
+
+    fn record_code(&mut self, code: &'src str) {
+        if !code.is_empty() {
+            let loc = Location::new(self.offset_of(code), code.len());
+            self.record(Token {
+                kind: TokenKind::Code(code),
+                loc,
+            });
+        }
+    }
+
+    fn lex_token(&mut self) -> Token<'src> {
         if self.attrs_mode {
             return self.next_token_attrs();
         }
@@ -92,6 +141,7 @@ impl<'src> Lexer<'src> {
             Some('{') if self.harpoon.peek_equals("{#") => self.consume_special_block_start(),
             Some('{') if self.harpoon.peek_equals("{/") => self.consume_special_block_end(),
             Some('{') if self.harpoon.peek_equals("{:") => self.consume_special_extender(),
+            Some('{') if self.harpoon.peek_equals("{@") => self.consume_special_expr(),
             Some('{') => return self.consume_mustache(),
             Some('-') if self.harpoon.peek_equals("---") => {
                 self.harpoon.consume_n(2);
@@ -123,25 +173,65 @@ impl<'src> Lexer<'src> {
             h.consume();
         });
 
-        span.text().strip_suffix(until).unwrap_or(span.text())
+        let text = span.text().strip_suffix(until);
+        self.record_code(text.unwrap_or(span.text()));
+        if text.is_some() && until == "---" {
+            self.record(token1!(CodeBlockIndicator, self.harpoon.offset() - 1));
+        }
+
+        text.unwrap_or(span.text())
+    }
+
+    /// Consumes everything before `until` as plain text, leaving `until` to be lexed next. If
+    /// there is no `until`, the rest of the source is consumed.
+    pub fn text_before_str(&mut self, until: &str) -> &'src str {
+        let span = self.harpoon.harpoon(|h| {
+            while h.peek().is_some() && !h.peek_starts_with(until) {
+                h.consume();
+            }
+        });
+        let text = span.text();
+        if !text.is_empty() {
+            self.record(Token {
+                kind: TokenKind::Text(text),
+                loc: span_to_loc(span),
+            });
+        }
+
+        text
+    }
+
+    /// Gets the offset of `text` in the source. `text` must be a slice of the source, like the
+    /// text returned by [`Lexer::text_until_str`].
+    pub fn offset_of(&self, text: &'src str) -> usize {
+        let source = self.harpoon.source();
+        let offset = text.as_ptr() as usize - source.as_ptr() as usize;
+        debug_assert!(offset + text.len() <= source.len());
+        offset
     }
 
     pub fn text_until(&mut self, until: char) -> &'src str {
-        let span = self.harpoon.harpoon(|h| h.consume_while(|c| c != until));
+        let span = self.harpoon.consume_while(|c| c != until);
         // Consume the `until` char
         self.harpoon.consume();
+        self.record_code(span.text());
 
         span.text()
     }
 
+    /// The last character that was consumed.
+    pub fn last_char(&self) -> Option<char> {
+        self.harpoon.current()
+    }
+
     pub fn allow(&mut self, allow: Allowed) {
         self.allowed |= allow;
     }
 
     pub fn peek_token(&mut self) -> Token<'src> {
-        let harpoon = self.harpoon.clone();
-        let tok = self.next_token();
-        self.harpoon = harpoon;
+        let checkpoint = self.harpoon.checkpoint();
+        let tok = self.lex_token();
+        self.harpoon.rewind(checkpoint);
         tok
     }
 
@@ -159,6 +249,10 @@ impl<'src> Lexer<'src> {
         tok
     }
 
+    pub fn source(&self) -> &'src str {
+        self.harpoon.source()
+    }
+
     pub fn attrs_mode(&mut self, attrs_mode: bool) {
         self.attrs_mode = attrs_mode;
     }
@@ -171,11 +265,13 @@ impl<'src> Lexer<'src> {
                 self.harpoon.consume();
                 token1!(In, self.harpoon.offset())
             }
-            Some(c) if c.is_alphabetic() => return self.consume_ident(),
+            Some(c) if c.is_ascii_alphabetic() => return self.consume_ident(),
             Some('{') => return self.consume_mustache(),
             Some('"') => return self.consume_quotes(),
             Some('=') => token1!(Equals, self.harpoon.offset()),
             Some(':') => token1!(Colon, self.harpoon.offset()),
+            Some('.') => token1!(Dot, self.harpoon.offset()),
+            Some('|') => token1!(Pipe, self.harpoon.offset()),
             Some('@') => token1!(At, self.harpoon.offset()),
             Some(']') => token1!(Rbracket, self.harpoon.offset()),
             Some('}') => token1!(Rbrace, self.harpoon.offset()),
@@ -201,18 +297,28 @@ impl<'src> Lexer<'src> {
 
     fn consume_text(&mut self) -> Token<'src> {
         let span = self.harpoon.harpoon(|h| loop {
-            h.consume_while(|c| !matches!(c, '{' | '#' | '/' | '-' | '\\'));
+            h.consume_while(|c| !matches!(c, '{' | '#' | '/' | '-' | '\\' | '&'));
             // Escape
             if h.peek().is_some_and(|c| c == '\\') {
                 h.consume();
                 h.consume();
             }
+            // The `#` of a numeric character reference doesn't start an element
+            if h.peek_starts_with("&#") {
+                h.consume_n(2);
+            } else if h.peek_is('&') {
+                h.consume();
+            }
             if h.peek().is_none()
                 || matches!(h.peek().unwrap(), '{' | '#' | '/')
                 || h.peek_equals("---")
             {
                 break;
             }
+            // A dash that doesn't start a code block is plain text
+            if h.peek_is('-') {
+                h.consume();
+            }
         });
 
         Token {
@@ -222,9 +328,10 @@ impl<'src> Lexer<'src> {
     }
 
     fn consume_elem(&mut self) -> Token<'src> {
-        debug_assert_eq!(Some('#'), self.harpoon.consume());
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('#'), consumed);
 
-        let elem = self.harpoon.harpoon(|h| h.consume_while(is_html_ident));
+        let elem = self.consume_tag_name();
 
         Token {
             kind: TokenKind::ElemBegin(elem.text()),
@@ -233,9 +340,10 @@ impl<'src> Lexer<'src> {
     }
 
     fn consume_elem_end(&mut self) -> Token<'src> {
-        debug_assert_eq!(Some('/'), self.harpoon.consume());
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('/'), consumed);
 
-        let elem = self.harpoon.harpoon(|h| h.consume_while(is_html_ident));
+        let elem = self.consume_tag_name();
 
         Token {
             kind: TokenKind::ElemEnd(elem.text()),
@@ -243,35 +351,42 @@ impl<'src> Lexer<'src> {
         }
     }
 
-    fn consume_mustache(&mut self) -> Token<'src> {
-        debug_assert_eq!(Some('{'), self.harpoon.consume());
+    /// Consumes the name of a tag. Dynamic tag names (`{expr}`) are consumed up to and including
+    /// their matching closing brace. Braces in the strings of the expression are skipped.
+    fn consume_tag_name(&mut self) -> Span<'src> {
+        if !self.harpoon.peek_is('{') {
+            return self.harpoon.consume_while(is_html_ident);
+        }
 
-        let mut unclosed = false;
-        let contents = self.harpoon.harpoon(|h| {
-            let mut rbraces_needed = 1;
-            while rbraces_needed > 0 {
-                h.consume();
-                match h.peek() {
-                    Some('{') => {
-                        rbraces_needed += 1;
-                    }
-                    Some('}') => rbraces_needed -= 1,
-                    Some(_) => {}
-                    None => {
-                        unclosed = true;
-                        return;
+        self.harpoon.harpoon(|h| {
+            let mut depth = 0;
+            while let Some(c) = h.peek() {
+                match c {
+                    '"' | '\'' | '`' => {
+                        h.consume_quoted(c, '\\');
+                        continue;
                     }
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                h.consume();
+                if depth == 0 {
+                    break;
                 }
             }
-        });
-        self.harpoon.consume();
+        })
+    }
 
-        if unclosed {
+    fn consume_mustache(&mut self) -> Token<'src> {
+        let start = self.harpoon.offset();
+        let Some(contents) = self.harpoon.consume_balanced('{', '}') else {
+            let rest = &self.harpoon.source()[start + 1..];
             return Token {
-                kind: TokenKind::Text(contents.text()),
-                loc: span_to_loc(contents),
+                kind: TokenKind::Text(rest),
+                loc: Location::new(start + 1, rest.len()),
             };
-        }
+        };
 
         Token {
             kind: TokenKind::Mustache(contents.text()),
@@ -280,7 +395,7 @@ impl<'src> Lexer<'src> {
     }
 
     fn consume_ident(&mut self) -> Token<'src> {
-        let ident = self.harpoon.harpoon(|h| h.consume_while(is_html_ident));
+        let ident = self.harpoon.consume_ident();
 
         Token {
             kind: TokenKind::Ident(ident.text()),
@@ -289,11 +404,11 @@ impl<'src> Lexer<'src> {
     }
 
     fn consume_quotes(&mut self) -> Token<'src> {
-        debug_assert_eq!(Some('"'), self.harpoon.consume());
-
-        // FIX: Allow escaped quotes
-        let contents = self.harpoon.harpoon(|h| h.consume_while(|c| c != '"'));
-        self.harpoon.consume();
+        let start = self.harpoon.offset();
+        let contents = self.harpoon.consume_quoted('"', '\\').unwrap_or_else(|| {
+            let rest = &self.harpoon.source()[start + 1..];
+            Span::new(rest, start + 1)
+        });
 
         Token {
             kind: TokenKind::Quotes(contents.text()),
@@ -302,10 +417,12 @@ impl<'src> Lexer<'src> {
     }
 
     fn consume_special_block_start(&mut self) -> Token<'src> {
-        debug_assert_eq!(Some('{'), self.harpoon.consume());
-        debug_assert_eq!(Some('#'), self.harpoon.consume());
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('{'), consumed);
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('#'), consumed);
 
-        let name = self.harpoon.harpoon(|h| h.consume_while(is_html_ident));
+        let name = self.harpoon.consume_ident();
 
         Token {
             kind: TokenKind::SpecialBlockStart(name.text()),
@@ -314,10 +431,12 @@ impl<'src> Lexer<'src> {
     }
 
     fn consume_special_block_end(&mut self) -> Token<'src> {
-        debug_assert_eq!(Some('{'), self.harpoon.consume());
-        debug_assert_eq!(Some('/'), self.harpoon.consume());
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('{'), consumed);
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('/'), consumed);
 
-        let name = self.harpoon.harpoon(|h| h.consume_while(is_html_ident));
+        let name = self.harpoon.consume_ident();
 
         Token {
             kind: TokenKind::SpecialBlockEnd(name.text()),
@@ -326,10 +445,12 @@ impl<'src> Lexer<'src> {
     }
 
     fn consume_special_extender(&mut self) -> Token<'src> {
-        debug_assert_eq!(Some('{'), self.harpoon.consume());
-        debug_assert_eq!(Some(':'), self.harpoon.consume());
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('{'), consumed);
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some(':'), consumed);
 
-        let name = self.harpoon.harpoon(|h| h.consume_while(is_html_ident));
+        let name = self.harpoon.consume_ident();
 
         Token {
             kind: TokenKind::SpecialExtender(name.text()),
@@ -337,11 +458,27 @@ impl<'src> Lexer<'src> {
         }
     }
 
+    fn consume_special_expr(&mut self) -> Token<'src> {
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('{'), consumed);
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('@'), consumed);
+
+        let name = self.harpoon.consume_ident();
+
+        Token {
+            kind: TokenKind::SpecialExpr(name.text()),
+            loc: span_to_loc(name),
+        }
+    }
+
     fn consume_comment(&mut self) -> Token<'src> {
-        debug_assert_eq!(Some('/'), self.harpoon.consume());
-        debug_assert_eq!(Some('/'), self.harpoon.consume());
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('/'), consumed);
+        let consumed = self.harpoon.consume();
+        debug_assert_eq!(Some('/'), consumed);
 
-        let comment = self.harpoon.harpoon(|h| h.consume_while(|c| c != '\n'));
+        let comment = self.harpoon.consume_until('\n');
 
         Token {
             kind: TokenKind::Comment(comment.text()),
@@ -374,6 +511,36 @@ fn is_html_ident(c: char) -> bool {
 }
 
 impl TokenKind<'_> {
+    /// A short, stable name for the kind of token, for tools that consume the token stream.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::Text(_) => "text",
+            TokenKind::ElemBegin(_) => "elem-begin",
+            TokenKind::ElemEnd(_) => "elem-end",
+            TokenKind::SpecialBlockStart(_) => "special-block-start",
+            TokenKind::SpecialBlockEnd(_) => "special-block-end",
+            TokenKind::SpecialExtender(_) => "special-extender",
+            TokenKind::SpecialExpr(_) => "special-expr",
+            TokenKind::Mustache(_) => "mustache",
+            TokenKind::Comment(_) => "comment",
+            TokenKind::CodeBlockIndicator => "code-block-indicator",
+            TokenKind::Code(_) => "code",
+            TokenKind::Rbrace => "rbrace",
+            TokenKind::Quotes(_) => "quotes",
+            TokenKind::Ident(_) => "ident",
+            TokenKind::Lbracket => "lbracket",
+            TokenKind::Rbracket => "rbracket",
+            TokenKind::Colon => "colon",
+            TokenKind::Dot => "dot",
+            TokenKind::Pipe => "pipe",
+            TokenKind::Equals => "equals",
+            TokenKind::At => "at",
+            TokenKind::In => "in",
+            TokenKind::Invalid(_) => "invalid",
+            TokenKind::Eof => "eof",
+        }
+    }
+
     pub fn display_kind(&self) -> &'static str {
         match self {
             TokenKind::Text(_) => "text",
@@ -386,14 +553,18 @@ impl TokenKind<'_> {
             TokenKind::Lbracket => "a left bracket",
             TokenKind::Rbracket => "a right bracket",
             TokenKind::Colon => "a colon",
+            TokenKind::Dot => "a dot",
+            TokenKind::Pipe => "a pipe",
             TokenKind::Equals => "an equals sign",
             TokenKind::At => "an at symbol",
             TokenKind::SpecialBlockStart(_) => "the start of a special block",
             TokenKind::SpecialExtender(_) => "a special block extender",
+            TokenKind::SpecialExpr(_) => "a special expression",
             TokenKind::SpecialBlockEnd(_) => "the end of a special block",
             TokenKind::Rbrace => "an rbrace",
             TokenKind::Comment(_) => "a comment",
             TokenKind::CodeBlockIndicator => "a code block indicator",
+            TokenKind::Code(_) => "code",
             TokenKind::Invalid(_) => "INVALID",
             TokenKind::Eof => "eof",
         }
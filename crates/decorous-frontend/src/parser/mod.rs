@@ -2,24 +2,28 @@ mod code_blocks;
 mod ctx;
 pub mod errors;
 mod lexer;
+mod typescript;
 
-use std::path::Path;
+use std::{borrow::Cow, io, path::Path};
 
-use decorous_errors::Diagnostic;
-use rslint_parser::{parse_module, SyntaxNode};
+use decorous_errors::{Diagnostic, DynErrStream, Fix, Severity, Source};
+use rslint_parser::{parse_with_syntax, Parse, Syntax, SyntaxNode};
 
 use crate::{
     ast::{
-        Attribute, AttributeValue, Code, Comment, DecorousAst, Element, EventHandler, ForBlock,
-        IfBlock, Mustache, Node, NodeType, SpecialBlock, Text, UseBlock,
+        Attribute, AttributeValue, Binding, BindingKind, CfgBlock, Code, Comment, DecorousAst,
+        Element, EventHandler, EventModifier, ForBlock, HeadBlock, HtmlBlock, IfBlock, Mustache,
+        Node, NodeType, RawAst, RawBlock, Script, ScriptOffsets, SpecialBlock, StaticBlock, Text,
+        TextPart, TranslateBlock, UseBlock,
     },
-    css,
-    errors::{ParseError, ParseErrorType},
+    css, entities,
+    errors::{closest_match, Help, ParseError, ParseErrorType},
     location::Location,
     parser::code_blocks::CodeBlocks,
 };
 pub use ctx::*;
-use lexer::{Allowed, Lexer, Token, TokenKind};
+use lexer::{Allowed, Lexer};
+pub use lexer::{Token, TokenKind};
 
 type Result<T> = std::result::Result<T, ParseError<Location>>;
 
@@ -29,6 +33,7 @@ pub struct Parser<'src, 'ctx> {
     code_blocks: CodeBlocks<'src>,
     ctx: Ctx<'ctx>,
     did_error: bool,
+    first_error: Option<ParseError<Location>>,
 }
 
 macro_rules! expect {
@@ -59,8 +64,12 @@ macro_rules! error {
 
 impl<'src, 'ctx> Parser<'src, 'ctx> {
     pub fn new(src: &'src str) -> Self {
+        Self::with_lexer(Lexer::new(src))
+    }
+
+    fn with_lexer(lexer: Lexer<'src>) -> Self {
         let mut parser = Self {
-            lexer: Lexer::new(src),
+            lexer,
             current_token: Token {
                 kind: TokenKind::Eof,
                 loc: Location::default(),
@@ -68,6 +77,7 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
             code_blocks: CodeBlocks::new(),
             ctx: Ctx::default(),
             did_error: false,
+            first_error: None,
         };
 
         parser.next_token();
@@ -79,7 +89,33 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
         self
     }
 
+    /// Parses the source. Parsing recovers from most errors, so that all of them can be reported
+    /// at once. If only one error is found, it is returned. Otherwise, every error is emitted to
+    /// the [`Ctx`], and [`ParseErrorType::DidError`] is returned.
     pub fn parse(mut self) -> Result<DecorousAst<'src>> {
+        let nodes = self.parse_document();
+        let nodes = self.finish(nodes)?;
+
+        let (script, css, wasm, comptime) = self.code_blocks.into_parts();
+
+        Ok(DecorousAst {
+            nodes,
+            script,
+            css,
+            wasm,
+            comptime,
+        })
+    }
+
+    /// Parses the markup of the source, without interpreting its code blocks. Unlike
+    /// [`Parser::parse`], this never runs the preprocessor, and doesn't care about which
+    /// languages the code blocks are in.
+    pub fn parse_raw(mut self) -> Result<RawAst<'src>> {
+        let ast = self.parse_raw_in_place();
+        self.finish(ast)
+    }
+
+    fn parse_document(&mut self) -> Result<Vec<Node<'src, Location>>> {
         self.parse_code_blocks()?;
         let nodes = self.parse_nodes(|tok| {
             Ok(matches!(
@@ -89,6 +125,34 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
         })?;
         self.parse_code_blocks()?;
 
+        Ok(nodes)
+    }
+
+    fn parse_raw_in_place(&mut self) -> Result<RawAst<'src>> {
+        let head = self.parse_raw_code_blocks()?;
+        let nodes = self.parse_nodes(|tok| {
+            Ok(matches!(
+                tok.kind,
+                TokenKind::CodeBlockIndicator | TokenKind::Eof
+            ))
+        })?;
+        let tail = self.parse_raw_code_blocks()?;
+
+        Ok(RawAst { head, nodes, tail })
+    }
+
+    /// Turns the errors reported while parsing into the result of the parse.
+    fn finish<T>(&mut self, result: Result<T>) -> Result<T> {
+        let value = match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.report(err);
+                None
+            }
+        };
+        if let Some(err) = self.first_error.take() {
+            return Err(err);
+        }
         if self.did_error {
             return Err(ParseError::new(
                 Location::default(),
@@ -97,15 +161,115 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
             ));
         }
 
-        let (script, css, wasm, comptime) = self.code_blocks.into_parts();
+        Ok(value.expect("a failed parse should have reported an error"))
+    }
 
-        Ok(DecorousAst {
-            nodes,
-            script,
-            css,
-            wasm,
-            comptime,
-        })
+    /// Reports an error that parsing recovered from. The first error is held back, so it can be
+    /// returned by itself if it's the only one.
+    fn report(&mut self, err: ParseError<Location>) {
+        if self.did_error || self.first_error.is_some() {
+            self.emit(err.into());
+        } else {
+            self.first_error = Some(err);
+        }
+    }
+
+    /// Emits `diagnostic`, which makes the parse fail.
+    fn emit(&mut self, diagnostic: Diagnostic) {
+        if let Some(first) = self.first_error.take() {
+            self.ctx.errs.emit(first.into());
+        }
+        self.ctx.errs.emit(diagnostic);
+        self.did_error = true;
+    }
+
+    /// Skips the rest of a node that failed to parse, starting with the `start` token. Returns
+    /// `false` without skipping anything if the end of the node can't be found.
+    fn skip_node(&mut self, start: Token<'src>) -> bool {
+        let is_end = |kind: TokenKind| match (start.kind, kind) {
+            (TokenKind::ElemBegin(tag), TokenKind::ElemEnd(end)) => {
+                end == tag || (tag.starts_with('{') && end.is_empty())
+            }
+            (TokenKind::SpecialBlockStart(name), TokenKind::SpecialBlockEnd(end)) => end == name,
+            _ => false,
+        };
+        // Use blocks have no end
+        if !matches!(
+            start.kind,
+            TokenKind::ElemBegin(_)
+                | TokenKind::SpecialBlockStart("if" | "for" | "raw" | "head" | "static" | "cfg")
+        ) {
+            return false;
+        }
+
+        let mut lexer = self.lexer.clone();
+        let mut tok = self.current_token;
+        let mut skipped = 0;
+        if tok.loc == start.loc {
+            tok = lexer.next_token();
+            skipped += 1;
+        }
+        let mut depth = 0;
+        loop {
+            match tok.kind {
+                TokenKind::Eof => return false,
+                kind if kind == start.kind => depth += 1,
+                kind if is_end(kind) && depth == 0 => break,
+                kind if is_end(kind) => depth -= 1,
+                _ => {}
+            }
+            tok = lexer.next_token();
+            skipped += 1;
+        }
+
+        // Past the end of the node
+        for _ in 0..=skipped {
+            self.next_token();
+        }
+        true
+    }
+
+    /// Skips to the next token of `kind`, making it the current token. Returns `false` without
+    /// skipping anything if there is none.
+    fn skip_to(&mut self, kind: TokenKind) -> bool {
+        let mut lexer = self.lexer.clone();
+        let mut tok = self.current_token;
+        let mut skipped = 0;
+        while tok.kind != kind {
+            if tok.kind == TokenKind::Eof {
+                return false;
+            }
+            tok = lexer.next_token();
+            skipped += 1;
+        }
+
+        for _ in 0..skipped {
+            self.next_token();
+        }
+        true
+    }
+
+    /// Skips tokens until one that a node can begin with, or that `stop_pred` stops at.
+    fn synchronize<F>(&mut self, stop_pred: &mut F)
+    where
+        F: FnMut(Token) -> std::result::Result<bool, ParseError<Location>>,
+    {
+        let stops = |tok: Token, stop_pred: &mut F| {
+            tok.kind == TokenKind::Eof || stop_pred(tok).unwrap_or(false)
+        };
+        if stops(self.current_token, stop_pred) {
+            return;
+        }
+        loop {
+            self.next_token();
+            if matches!(
+                self.current_token.kind,
+                TokenKind::ElemBegin(_) | TokenKind::SpecialBlockStart(_)
+            ) || stops(self.current_token, stop_pred)
+            {
+                return;
+            }
+        }
     }
 
     fn next_token(&mut self) {
@@ -130,7 +294,8 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
             TokenKind::ElemBegin(_) => NodeType::Element(self.parse_elem()?),
             TokenKind::Mustache(_) => NodeType::Mustache(self.parse_mustache()?),
             TokenKind::SpecialBlockStart(_) => NodeType::SpecialBlock(self.parse_special_block()?),
-            TokenKind::Text(t) => NodeType::Text(Text(t)),
+            TokenKind::SpecialExpr(_) => NodeType::SpecialBlock(self.parse_special_expr()?),
+            TokenKind::Text(t) => NodeType::Text(self.parse_text(t, begin_loc)?),
             TokenKind::Comment(comment) => NodeType::Comment(Comment(comment)),
             TokenKind::Eof => {
                 return Err(self.error_on_current(ParseErrorType::UnclosedTag(String::new())))
@@ -158,7 +323,25 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
         let mut is_first = true;
         let mut nodes = vec![];
         while !stop_pred(self.current_token)? {
-            let mut node = self.parse_node()?;
+            let start = self.current_token;
+            let mut node = match self.parse_node() {
+                Ok(node) => node,
+                Err(err) => {
+                    // Errors about the end of the node are dealt with by the nodes around it
+                    let is_end_error = matches!(
+                        err.err_type(),
+                        ParseErrorType::UnclosedTag(_)
+                            | ParseErrorType::UnclosedBlock(_)
+                            | ParseErrorType::InvalidClosingTag(_)
+                    );
+                    self.report(err);
+                    self.lexer.attrs_mode(false);
+                    if is_end_error || !self.skip_node(start) {
+                        self.synchronize(&mut stop_pred);
+                    }
+                    continue;
+                }
+            };
             if is_first {
                 // If the first node is a text node with a leading space, strip it
                 if let NodeType::Text(Text(t)) = &mut node.node_type {
@@ -195,6 +378,21 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
             panic!("should be called with ElemBegin");
         };
         let tag_loc = self.current_token.loc;
+        let tag_expr = if tag_name.starts_with('{') {
+            let Some(js) = tag_name.strip_prefix('{').and_then(|t| t.strip_suffix('}')) else {
+                return Err(ParseError::new(
+                    tag_loc,
+                    ParseErrorType::ExpectedCharacter('}'),
+                    None,
+                ));
+            };
+            if js.trim().is_empty() {
+                return error!(self, "a JavaScript expression");
+            }
+            Some(self.parse_js_expr(js)?)
+        } else {
+            None
+        };
 
         let attrs = if self.lexer.peek_token_allow(Allowed::LBRACKET).kind == TokenKind::Lbracket {
             self.next_token_allow(Allowed::LBRACKET);
@@ -210,9 +408,10 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
             let text = expect!(self, Text(_))?;
             return Ok(Element {
                 tag: tag_name,
+                tag_expr,
                 attrs,
                 children: vec![Node::new(
-                    NodeType::Text(Text(text.trim_end())),
+                    NodeType::Text(self.parse_text(text.trim_end(), loc)?),
                     Location::new(loc, text.len()),
                 )],
             });
@@ -229,14 +428,13 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
 
             // Try to close the tag
             if let TokenKind::ElemEnd(end_name) = tok.kind {
-                return if end_name == tag_name {
+                // Dynamic tags can also be closed with a bare slash
+                return if end_name == tag_name || (tag_expr.is_some() && end_name.is_empty()) {
                     Ok(true)
                 } else {
-                    Err(ParseError::new(
-                        tok.loc,
-                        ParseErrorType::InvalidClosingTag(tag_name.to_owned()),
-                        None,
-                    ))
+                    Err(invalid_closing_tag(tok.loc, end_name, tag_name, |tag| {
+                        format!("/{tag}")
+                    }))
                 };
             }
             Ok(false)
@@ -244,6 +442,7 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
 
         Ok(Element {
             tag: tag_name,
+            tag_expr,
             attrs,
             children,
         })
@@ -258,14 +457,19 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
     }
 
     fn parse_js_expr(&mut self, js_text: &str) -> Result<SyntaxNode> {
-        let parse = rslint_parser::parse_module(js_text, 0);
+        let start = self.current_offset();
+        let Some(parse) = self.try_parse_js(js_text, Syntax::default().module(), &|offset| {
+            start + offset
+        }) else {
+            return Ok(parse_with_syntax("", 0, Syntax::default().module()).syntax());
+        };
         if parse.errors().is_empty() {
             Ok(parse.syntax().first_child().unwrap_or(parse.syntax()))
         } else {
             let error = &parse.errors()[0];
             let range = &error.primary.as_ref().unwrap().span.range;
             let start = self.current_offset() + range.start;
-            self.ctx.errs.emit(
+            self.emit(
                 Diagnostic::builder(format!("JavaScript error: {}", error.title), start)
                     .add_helper(decorous_errors::Helper {
                         msg: "the error occurred here".into(),
@@ -273,30 +477,46 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
                     })
                     .build(),
             );
-            self.did_error = true;
             Ok(parse.syntax())
         }
     }
 
-    fn parse_js_block(&mut self, js_text: &str) -> Result<SyntaxNode> {
-        let res = parse_module(js_text, 0);
-        if res.errors().is_empty()
-            || (res.errors().len() == 1
-                && res.errors().first().is_some_and(|err| {
-                    // HACK: This essentially swallows the error... Not very stable, but
-                    // I didn't find a well defined error identification system in the docs
-                    // of rslint_errors.
-                    //
-                    // https://docs.rs/rslint_errors/0.2.0/rslint_errors/struct.Diagnostic.html
-                    err.title.as_str() == "Duplicate statement labels are not allowed"
-                }))
+    fn parse_js_block(
+        &mut self,
+        js_text: &str,
+        locate: &dyn Fn(usize) -> usize,
+    ) -> Result<SyntaxNode> {
+        self.parse_script(
+            js_text,
+            Syntax::default().module().top_level_await(),
+            locate,
+        )
+    }
+
+    /// Parses a script with the given syntax. `locate` finds where an offset in the script is in
+    /// the source, to report errors there.
+    fn parse_script(
+        &mut self,
+        text: &str,
+        syntax: Syntax,
+        locate: &dyn Fn(usize) -> usize,
+    ) -> Result<SyntaxNode> {
+        let Some(res) = self.try_parse_js(text, syntax, locate) else {
+            return Ok(parse_with_syntax("", 0, syntax).syntax());
+        };
+        // HACK: Every reactive block is labelled with `$`, so the errors about duplicate labels
+        // are swallowed. Not very stable, but I didn't find a well defined error identification
+        // system in the docs of rslint_errors.
+        //
+        // https://docs.rs/rslint_errors/0.2.0/rslint_errors/struct.Diagnostic.html
+        if let Some(error) = res
+            .errors()
+            .iter()
+            .find(|err| err.title.as_str() != "Duplicate statement labels are not allowed")
         {
-            Ok(res.syntax())
-        } else {
-            let error = &res.errors()[0];
             let range = &error.primary.as_ref().unwrap().span.range;
-            let start = self.current_offset() + range.start;
-            self.ctx.errs.emit(
+            let start = locate(range.start);
+            self.emit(
                 Diagnostic::builder(format!("JavaScript error: {}", error.title), start)
                     .add_helper(decorous_errors::Helper {
                         msg: "the error occurred here".into(),
@@ -304,9 +524,31 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
                     })
                     .build(),
             );
-            self.did_error = true;
-            Ok(res.syntax())
         }
+        Ok(res.syntax())
+    }
+
+    /// Parses JavaScript like [`parse_with_syntax`]. rslint panics instead of reporting an error on
+    /// some malformed input, like `[/a{/b}]`, so an error is emitted for it and `None` is returned.
+    fn try_parse_js(
+        &mut self,
+        text: &str,
+        syntax: Syntax,
+        locate: &dyn Fn(usize) -> usize,
+    ) -> Option<Parse<()>> {
+        let parse = std::panic::catch_unwind(|| parse_with_syntax(text, 0, syntax)).ok();
+        if parse.is_none() {
+            let start = locate(0);
+            self.emit(
+                Diagnostic::builder("JavaScript error: could not parse this JavaScript", start)
+                    .add_helper(decorous_errors::Helper {
+                        msg: "the error is somewhere in here".into(),
+                        span: start..locate(text.len()),
+                    })
+                    .build(),
+            );
+        }
+        parse
     }
 
     fn parse_attrs(&mut self) -> Result<Vec<Attribute<'src>>> {
@@ -315,17 +557,44 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
 
         self.lexer.attrs_mode(true);
         let mut attrs = vec![];
+        // Where a missing `]` would go
+        let mut attrs_end = lbracket_loc.offset() + lbracket_loc.length();
         self.next_token();
         while self.current_token.kind != TokenKind::Rbracket {
             if self.current_token.kind == TokenKind::Eof {
                 return Err(ParseError::new(
                     lbracket_loc,
                     ParseErrorType::UnclosedAttrs,
-                    None,
+                    Some(
+                        Help::with_message("close the attributes with `]`")
+                            .with_fix(Fix::insert(attrs_end, "]")),
+                    ),
                 ));
             }
-            let attr = self.parse_attr()?;
-            attrs.push(attr);
+            match self.parse_attr() {
+                Ok(attr) => {
+                    attrs.push(attr);
+                    attrs_end = self.current_token.loc.offset() + self.current_token.loc.length();
+                }
+                // Skip the rest of the attributes, if they are closed
+                Err(err) if self.skip_to(TokenKind::Rbracket) => {
+                    self.report(err);
+                    break;
+                }
+                // Markup came up where an attribute was expected, and nothing after it closes the
+                // attributes, so they were most likely left open
+                Err(err) if matches!(err.err_type(), ParseErrorType::ExpectedAny(_)) => {
+                    return Err(ParseError::new(
+                        lbracket_loc,
+                        ParseErrorType::UnclosedAttrs,
+                        Some(
+                            Help::with_message("did you forget to close the attributes with `]`?")
+                                .with_fix(Fix::insert(attrs_end, "]")),
+                        ),
+                    ));
+                }
+                Err(err) => return Err(err),
+            }
             self.next_token();
         }
         self.lexer.attrs_mode(false);
@@ -351,11 +620,25 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
         assert_eq!(TokenKind::At, self.current_token.kind);
 
         let event = expect!(self, Ident(_))?;
+        let mut modifiers = vec![];
+        while self.lexer.peek_token().kind == TokenKind::Pipe {
+            self.next_token();
+            let modifier = match expect!(self, Ident(_))? {
+                "preventDefault" => EventModifier::PreventDefault,
+                "stopPropagation" => EventModifier::StopPropagation,
+                name => {
+                    return Err(self
+                        .error_on_current(ParseErrorType::InvalidEventModifier(name.to_owned())))
+                }
+            };
+            modifiers.push(modifier);
+        }
         expect!(self, Equals)?;
         let expr_text = expect!(self, Mustache(_))?;
 
         Ok(Attribute::EventHandler(EventHandler {
             event,
+            modifiers,
             expr: self.parse_js_expr(expr_text)?,
         }))
     }
@@ -392,10 +675,76 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
     fn parse_binding(&mut self) -> Result<Attribute<'src>> {
         assert_eq!(TokenKind::Colon, self.current_token.kind);
 
-        let bind = expect!(self, Ident(_))?;
+        expect!(self, Ident(_))?;
+        let bind = self.parse_binding_target()?;
         expect!(self, Colon)?;
 
-        Ok(Attribute::Binding(bind))
+        // A mode is only given when the variable name directly follows it, as in `:html:var:`.
+        // Otherwise, `:html:` is a normal binding to a variable called `html`.
+        let kind = match bind {
+            "html" => BindingKind::Html,
+            "text" => BindingKind::Text,
+            _ => BindingKind::Value,
+        };
+        let peeked = self.lexer.peek_token();
+        if kind.is_content()
+            && matches!(peeked.kind, TokenKind::Ident(_))
+            && peeked.loc.offset() == self.current_token.loc.offset() + 1
+        {
+            expect!(self, Ident(_))?;
+            let var = self.parse_binding_target()?;
+            expect!(self, Colon)?;
+            return Ok(Attribute::Binding(Binding { var, kind }));
+        }
+
+        Ok(Attribute::Binding(Binding {
+            var: bind,
+            kind: BindingKind::Value,
+        }))
+    }
+
+    /// Parses what a binding is to, starting at the current identifier. A field of a variable is
+    /// bound to by following it with dots, like `user.name`.
+    fn parse_binding_target(&mut self) -> Result<&'src str> {
+        let start = self.current_token.loc.offset();
+        let mut end = start + self.current_token.loc.length();
+        loop {
+            let peeked = self.lexer.peek_token();
+            if peeked.kind != TokenKind::Dot || peeked.loc.offset() != end {
+                break;
+            }
+            expect!(self, Dot)?;
+            expect!(self, Ident(_))?;
+            if self.current_token.loc.offset() != end + 1 {
+                return error!(self, "a field name right after the dot");
+            }
+            end = self.current_token.loc.offset() + self.current_token.loc.length();
+        }
+        Ok(&self.lexer.source()[start..end])
+    }
+
+    /// Checks that the character references in `text`, which starts at `offset`, are valid.
+    fn parse_text(&self, text: &'src str, offset: usize) -> Result<Text<'src>> {
+        let text = Text(text);
+        for (part_offset, part) in text.parts() {
+            let TextPart::Reference(reference) = part else {
+                continue;
+            };
+            if entities::decode(reference).is_none() {
+                return Err(ParseError::new(
+                    Location::new(offset + part_offset, reference.len() + 2),
+                    ParseErrorType::InvalidCharacterReference(format!("&{reference};")),
+                    Help::did_you_mean(reference, entities::names(), |name| format!("&{name};"))
+                        .or_else(|| {
+                            Some(Help::with_message(
+                                "to write an ampersand, escape it with a backslash: `\\&`",
+                            ))
+                        }),
+                ));
+            }
+        }
+
+        Ok(text)
     }
 
     fn parse_special_block(&mut self) -> Result<SpecialBlock<'src, Location>> {
@@ -407,15 +756,57 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
             "for" => SpecialBlock::For(self.parse_for_block()?),
             "if" => SpecialBlock::If(self.parse_if_block()?),
             "use" => SpecialBlock::Use(self.parse_use_block()?),
+            "raw" => SpecialBlock::Raw(self.parse_raw_block()?),
+            "html" => SpecialBlock::Html(self.parse_html_block()?),
+            "head" => SpecialBlock::Head(self.parse_head_block()?),
+            "static" => SpecialBlock::Static(self.parse_static_block()?),
+            "cfg" => SpecialBlock::Cfg(self.parse_cfg_block()?),
             _ => {
-                return error!(self, "a for block", "an if block", "a use block");
+                return Err(ParseError::new(
+                    self.current_token.loc,
+                    ParseErrorType::InvalidSpecialBlockType(block_name.to_owned()),
+                    Help::did_you_mean(
+                        block_name,
+                        ["for", "if", "use", "raw", "html", "head", "static", "cfg"],
+                        |block| format!("{{#{block}}}"),
+                    ),
+                ));
             }
         };
 
         Ok(block)
     }
 
+    fn parse_special_expr(&mut self) -> Result<SpecialBlock<'src, Location>> {
+        let TokenKind::SpecialExpr(name) = self.current_token.kind else {
+            panic!("should only call with SpecialExpr");
+        };
+
+        let expr = match name {
+            "t" => SpecialBlock::Translate(self.parse_translate()?),
+            _ => {
+                return Err(ParseError::new(
+                    self.current_token.loc,
+                    ParseErrorType::InvalidSpecialExprType(name.to_owned()),
+                    Help::did_you_mean(name, ["t"], |expr| format!("{{@{expr}}}")),
+                ));
+            }
+        };
+
+        Ok(expr)
+    }
+
+    fn parse_translate(&mut self) -> Result<TranslateBlock<'src>> {
+        self.lexer.attrs_mode(true);
+        let key = expect!(self, Quotes(_))?;
+        expect!(self, Rbrace)?;
+        self.lexer.attrs_mode(false);
+
+        Ok(TranslateBlock { key })
+    }
+
     fn parse_for_block(&mut self) -> Result<ForBlock<'src, Location>> {
+        let start = self.current_token.loc;
         self.lexer.attrs_mode(true);
         let binding = expect!(self, Ident(_))?;
         self.lexer.allow(Allowed::IN);
@@ -428,11 +819,12 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
 
         let inner = self.parse_nodes(|tok| match tok.kind {
             TokenKind::SpecialBlockEnd("for") => Ok(true),
-            TokenKind::SpecialBlockEnd(_) => Err(ParseError::new(
-                tok.loc,
-                ParseErrorType::InvalidClosingTag("for".to_owned()),
-                None,
-            )),
+            TokenKind::Eof => Err(unclosed_block(start, "for")),
+            TokenKind::SpecialBlockEnd(end) => {
+                Err(invalid_closing_tag(tok.loc, end, "for", |block| {
+                    format!("{{/{block}}}")
+                }))
+            }
             _ => Ok(false),
         })?;
 
@@ -445,21 +837,23 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
     }
 
     fn parse_if_block(&mut self) -> Result<IfBlock<'src, Location>> {
+        let start = self.current_token.loc;
         let js_text = self.lexer.text_until('}');
         self.next_token();
         let condition = self.parse_js_expr(js_text)?;
 
         let inner = self.parse_nodes(|tok| match tok.kind {
             TokenKind::SpecialBlockEnd("if") | TokenKind::SpecialExtender("else") => Ok(true),
-            TokenKind::SpecialBlockEnd(_) => Err(ParseError::new(
-                tok.loc,
-                ParseErrorType::InvalidClosingTag("if".to_owned()),
-                None,
-            )),
-            TokenKind::SpecialExtender(_) => Err(ParseError::new(
+            TokenKind::Eof => Err(unclosed_block(start, "if")),
+            TokenKind::SpecialBlockEnd(end) => {
+                Err(invalid_closing_tag(tok.loc, end, "if", |block| {
+                    format!("{{/{block}}}")
+                }))
+            }
+            TokenKind::SpecialExtender(extender) => Err(ParseError::new(
                 tok.loc,
                 ParseErrorType::InvalidExtender("else"),
-                None,
+                Help::did_you_mean(extender, ["else"], |extender| format!("{{:{extender}}}")),
             )),
             _ => Ok(false),
         })?;
@@ -468,11 +862,12 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
             self.next_token();
             let inner = self.parse_nodes(|tok| match tok.kind {
                 TokenKind::SpecialBlockEnd("if") => Ok(true),
-                TokenKind::SpecialBlockEnd(_) => Err(ParseError::new(
-                    tok.loc,
-                    ParseErrorType::InvalidClosingTag("if".to_owned()),
-                    None,
-                )),
+                TokenKind::Eof => Err(unclosed_block(start, "if")),
+                TokenKind::SpecialBlockEnd(end) => {
+                    Err(invalid_closing_tag(tok.loc, end, "if", |block| {
+                        format!("{{/{block}}}")
+                    }))
+                }
                 _ => Ok(false),
             })?;
             Some(inner)
@@ -490,84 +885,271 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
     fn parse_use_block(&mut self) -> Result<UseBlock<'src>> {
         self.lexer.attrs_mode(true);
         let path = expect!(self, Quotes(_))?;
-        expect!(self, Rbrace)?;
+        self.next_token();
+        let alias = match self.current_token.kind {
+            TokenKind::Rbrace => None,
+            TokenKind::Ident("as") => {
+                let alias = expect!(self, Ident(_))?;
+                expect!(self, Rbrace)?;
+                Some(alias)
+            }
+            _ => return error!(self, "}", "as"),
+        };
         self.lexer.attrs_mode(false);
 
         Ok(UseBlock {
-            path: Path::new(path),
+            path: Cow::Borrowed(Path::new(path)),
+            alias,
         })
     }
 
+    fn parse_html_block(&mut self) -> Result<HtmlBlock> {
+        let js_text = self.lexer.text_until('}');
+        let expr = self.parse_js_expr(js_text)?;
+
+        Ok(HtmlBlock { expr })
+    }
+
+    fn parse_head_block(&mut self) -> Result<HeadBlock<'src, Location>> {
+        let start = self.current_token.loc;
+        // The character after the name of the block is consumed along with it
+        if self.lexer.last_char() != Some('}') {
+            self.lexer.attrs_mode(true);
+            expect!(self, Rbrace)?;
+            self.lexer.attrs_mode(false);
+        }
+        self.next_token();
+
+        let inner = self.parse_nodes(|tok| match tok.kind {
+            TokenKind::SpecialBlockEnd("head") => Ok(true),
+            TokenKind::Eof => Err(unclosed_block(start, "head")),
+            TokenKind::SpecialBlockEnd(end) => {
+                Err(invalid_closing_tag(tok.loc, end, "head", |block| {
+                    format!("{{/{block}}}")
+                }))
+            }
+            _ => Ok(false),
+        })?;
+
+        Ok(HeadBlock { inner })
+    }
+
+    fn parse_static_block(&mut self) -> Result<StaticBlock<'src, Location>> {
+        let start = self.current_token.loc;
+        // The character after the name of the block is consumed along with it
+        if self.lexer.last_char() != Some('}') {
+            self.lexer.attrs_mode(true);
+            expect!(self, Rbrace)?;
+            self.lexer.attrs_mode(false);
+        }
+        self.next_token();
+
+        let inner = self.parse_nodes(|tok| match tok.kind {
+            TokenKind::SpecialBlockEnd("static") => Ok(true),
+            TokenKind::Eof => Err(unclosed_block(start, "static")),
+            TokenKind::SpecialBlockEnd(end) => {
+                Err(invalid_closing_tag(tok.loc, end, "static", |block| {
+                    format!("{{/{block}}}")
+                }))
+            }
+            _ => Ok(false),
+        })?;
+
+        Ok(StaticBlock { inner })
+    }
+
+    fn parse_cfg_block(&mut self) -> Result<CfgBlock<'src, Location>> {
+        let start = self.current_token.loc;
+        self.lexer.attrs_mode(true);
+        if expect!(self, Ident(_))? != "feature" {
+            return error!(self, "feature");
+        }
+        expect!(self, Equals)?;
+        let feature = expect!(self, Quotes(_))?;
+        expect!(self, Rbrace)?;
+        self.lexer.attrs_mode(false);
+        self.next_token();
+
+        let inner = self.parse_nodes(|tok| match tok.kind {
+            TokenKind::SpecialBlockEnd("cfg") => Ok(true),
+            TokenKind::Eof => Err(unclosed_block(start, "cfg")),
+            TokenKind::SpecialBlockEnd(end) => {
+                Err(invalid_closing_tag(tok.loc, end, "cfg", |block| {
+                    format!("{{/{block}}}")
+                }))
+            }
+            _ => Ok(false),
+        })?;
+
+        Ok(CfgBlock { feature, inner })
+    }
+
+    fn parse_raw_block(&mut self) -> Result<RawBlock<'src>> {
+        let start = self.current_token.loc;
+        // The character after the name of the block is consumed along with it
+        if self.lexer.last_char() != Some('}') {
+            self.lexer.attrs_mode(true);
+            expect!(self, Rbrace)?;
+            self.lexer.attrs_mode(false);
+        }
+
+        // Nothing is parsed until the closing `{/raw}`, so it can't be nested
+        let html = self.lexer.text_before_str("{/raw}");
+        self.next_token();
+        if self.current_token.kind != TokenKind::SpecialBlockEnd("raw") {
+            return Err(ParseError::new(
+                start,
+                ParseErrorType::UnclosedTag("{#raw}".to_owned()),
+                None,
+            ));
+        }
+
+        Ok(RawBlock { html })
+    }
+
     fn parse_code_blocks(&mut self) -> Result<()> {
         let mut did_parse = false;
         while self.current_token.kind == TokenKind::CodeBlockIndicator {
             did_parse = true;
             let offset = self.current_offset();
-            let err_convert = |err| |_| ParseError::new(Location::new(offset, 1), err, None);
             let code = self.parse_code_block()?;
+            // The rest of the code blocks can still be parsed
+            if let Err(err) = self.add_code_block(code, offset) {
+                self.report(err);
+            }
+        }
+        if did_parse {
+            self.next_token();
+        }
 
-            match code.lang {
-                _ if code.comptime => {
-                    self.code_blocks
-                        .set_static_wasm(code)
-                        .map_err(err_convert(ParseErrorType::CannotHaveTwoStatics))?;
-                }
-                "js" => {
-                    let syntax_node = self.parse_js_block(code.body)?;
-                    self.code_blocks
-                        .set_script(syntax_node)
-                        .map_err(err_convert(ParseErrorType::CannotHaveTwoScripts))?;
-                }
-                "css" => {
-                    let css_parser = css::Parser::new(code.body);
-                    let ast = css_parser.parse().map_err(|err| {
-                        // TODO: help
-                        let _help = err.help().cloned();
-                        self.error_on_current(ParseErrorType::CssParsingError(err.into()))
-                    })?;
-                    self.code_blocks
-                        .set_css(ast)
-                        .map_err(err_convert(ParseErrorType::CannotHaveTwoStyles))?;
+        Ok(())
+    }
+
+    fn add_code_block(&mut self, code: Code<'src>, offset: usize) -> Result<()> {
+        let err_convert = |err| |_| ParseError::new(Location::new(offset, 1), err, None);
+        match code.lang {
+            _ if code.comptime => {
+                self.code_blocks
+                    .set_static_wasm(code)
+                    .map_err(err_convert(ParseErrorType::CannotHaveTwoStatics))?;
+            }
+            "js" => {
+                let body_offset = self.lexer.offset_of(code.body);
+                let script = Script {
+                    node: self.parse_js_block(code.body, &|pos| body_offset + pos)?,
+                    offsets: ScriptOffsets::new(body_offset),
+                };
+                self.code_blocks.add_script(code.body.to_owned(), script);
+            }
+            "css" => {
+                let body_offset = self.lexer.offset_of(code.body);
+                let ast = parse_css_block(code.body, &|pos| body_offset + pos)?;
+                self.code_blocks.add_css(ast);
+            }
+            _ => {
+                let body_offset = self.lexer.offset_of(code.body);
+                let preprocessed = match self.ctx.preprocessor.preprocess(code.lang, code.body) {
+                    Ok(preprocessed) => preprocessed,
+                    Err(err) => {
+                        let loc = err.loc.map_or(Location::new(offset, 1), |loc| {
+                            Location::new(body_offset + loc.offset(), loc.length())
+                        });
+                        return Err(ParseError::new(
+                            loc,
+                            ParseErrorType::PreprocError(Box::new(err)),
+                            None,
+                        ));
+                    }
+                };
+                for warning in preprocessed.warnings {
+                    let (start, len) = warning.loc.map_or((offset, 1), |loc| {
+                        (body_offset + loc.offset(), loc.length())
+                    });
+                    self.ctx.errs.emit(
+                        Diagnostic::builder(warning.msg, start)
+                            .severity(Severity::Warning)
+                            .add_helper(decorous_errors::Helper {
+                                msg: "here".into(),
+                                span: start..start + len,
+                            })
+                            .build(),
+                    );
                 }
-                _ => {
-                    match self
-                        .ctx
-                        .preprocessor
-                        .preprocess(code.lang, code.body)
-                        .map_err(|err| {
-                            self.error_on_current(ParseErrorType::PreprocError(Box::new(err)))
-                        })? {
-                        Override::Js(js_text) => {
-                            let syntax_node = self.parse_js_block(&js_text)?;
-                            self.code_blocks
-                                .set_script(syntax_node)
-                                .map_err(err_convert(ParseErrorType::CannotHaveTwoScripts))?;
-                        }
-                        Override::Css(css_text) => {
-                            let css_parser = css::Parser::new(&css_text);
-                            let ast = css_parser.parse().map_err(|err| {
-                                // TODO: help
-                                let _help = err.help().cloned();
-                                self.error_on_current(ParseErrorType::CssParsingError(err.into()))
-                            })?;
-                            self.code_blocks
-                                .set_css(ast)
-                                .map_err(err_convert(ParseErrorType::CannotHaveTwoStyles))?;
-                        }
-                        Override::None => {
-                            self.code_blocks
-                                .set_wasm(code)
-                                .map_err(err_convert(ParseErrorType::CannotHaveTwoWasmBlocks))?;
-                        }
+                // Without a map, everything in the output points to the start of the code block
+                let map = preprocessed.map;
+                let locate = |pos| {
+                    map.as_ref()
+                        .and_then(|map| map.map(pos))
+                        .map_or(offset, |pos| body_offset + pos)
+                };
+                match preprocessed.code {
+                    Override::Js(js_text) => {
+                        let script = Script {
+                            node: self.parse_js_block(&js_text, &locate)?,
+                            offsets: ScriptOffsets::new(offset),
+                        };
+                        self.code_blocks.add_script(js_text, script);
+                    }
+                    Override::Css(css_text) => {
+                        let ast = parse_css_block(&css_text, &locate)?;
+                        self.code_blocks.add_css(ast);
+                    }
+                    // TypeScript is only handled here if no preprocessor was configured for it
+                    Override::None if code.lang == "ts" => {
+                        let (js_text, script) = self.parse_ts_block(code.body)?;
+                        self.code_blocks.add_script(js_text, script);
+                    }
+                    Override::None => {
+                        self.code_blocks
+                            .set_wasm(code)
+                            .map_err(err_convert(ParseErrorType::CannotHaveTwoWasmBlocks))?;
                     }
                 }
             }
         }
-        if did_parse {
+
+        Ok(())
+    }
+
+    /// Parses a TypeScript block, returning the JavaScript it's erased to along with its script.
+    fn parse_ts_block(&mut self, ts_text: &'src str) -> Result<(String, Script)> {
+        let offset = self.lexer.offset_of(ts_text);
+        let ts = self.parse_script(
+            ts_text,
+            Syntax::default().module().top_level_await().typescript(),
+            &|pos| offset + pos,
+        )?;
+        let js_text = typescript::erase_types(&ts).map_err(|unsupported| {
+            ParseError::new(
+                Location::new(offset + unsupported.range.start, unsupported.range.len()),
+                ParseErrorType::UnsupportedTypeScript(unsupported.feature),
+                None,
+            )
+        })?;
+        // Types are erased with whitespace, so the JavaScript lines up with the block. Its errors
+        // were already reported while parsing the TypeScript.
+        let node =
+            parse_with_syntax(&js_text, 0, Syntax::default().module().top_level_await()).syntax();
+
+        Ok((
+            js_text,
+            Script {
+                node,
+                offsets: ScriptOffsets::new(offset),
+            },
+        ))
+    }
+
+    fn parse_raw_code_blocks(&mut self) -> Result<Vec<Code<'src>>> {
+        let mut blocks = vec![];
+        while self.current_token.kind == TokenKind::CodeBlockIndicator {
+            blocks.push(self.parse_code_block()?);
+        }
+        if !blocks.is_empty() {
             self.next_token();
         }
 
-        Ok(())
+        Ok(blocks)
     }
 
     fn parse_code_block(&mut self) -> Result<Code<'src>> {
@@ -580,16 +1162,19 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
             self.next_token();
             let ident = expect!(self, Ident(_))?;
             if ident != "static" {
-                self.ctx.errs.emit(
+                let mut diagnostic =
                     Diagnostic::builder("expected the static keyword", self.current_offset())
                         .note("the static keyword evaluates the code block at compile time")
                         .add_helper(decorous_errors::Helper {
                             msg: "you might've wanted to change this to `static`".into(),
                             span: self.current_token.loc.into(),
-                        })
-                        .build(),
-                );
-                self.did_error = true;
+                        });
+                // Only a typo of `static` is surely meant to be it
+                if closest_match(ident, ["static"]).is_some() {
+                    diagnostic =
+                        diagnostic.add_fix(Fix::replace(self.current_token.loc.into(), "static"));
+                }
+                self.emit(diagnostic.build());
                 false
             } else {
                 true
@@ -613,8 +1198,77 @@ impl<'src, 'ctx> Parser<'src, 'ctx> {
     }
 }
 
+/// Creates an error for a `block` starting at `loc` that the source ends before closing.
+fn unclosed_block(loc: Location, block: &'static str) -> ParseError<Location> {
+    ParseError::new(loc, ParseErrorType::UnclosedBlock(block), None)
+}
+
+/// Creates an error for a closing tag that doesn't close `expected`, suggesting `expected` if
+/// `found` looks like a typo of it. The suggestion is written with `write`.
+fn invalid_closing_tag(
+    loc: Location,
+    found: &str,
+    expected: &str,
+    write: impl FnOnce(&str) -> String,
+) -> ParseError<Location> {
+    ParseError::new(
+        loc,
+        ParseErrorType::InvalidClosingTag(expected.to_owned()),
+        Help::did_you_mean(found, [expected], write),
+    )
+}
+
+/// Splits `src` into the tokens that the parser sees, in the order they appear. Code that is
+/// embedded in the file, like the bodies of code blocks, is given as [`TokenKind::Code`].
+///
+/// This never fails. If the source doesn't parse, everything after the error is tokenized as
+/// markup.
+pub fn tokenize(src: &str) -> Vec<Token<'_>> {
+    let mut parser = Parser::with_lexer(Lexer::new(src).recording()).with_ctx(Ctx {
+        errs: DynErrStream::new(
+            Box::new(io::sink()),
+            Source {
+                name: String::new(),
+                src,
+            },
+        ),
+        ..Default::default()
+    });
+    if parser.parse_raw_in_place().is_err() {
+        parser.lexer.attrs_mode(false);
+        while parser.lexer.next().is_some() {}
+    }
+
+    parser.lexer.take_recorded()
+}
+
+/// Parses a CSS code block. `locate` finds where an offset in the CSS is in the source, to report
+/// errors there.
+fn parse_css_block(css_text: &str, locate: &dyn Fn(usize) -> usize) -> Result<css::ast::Css> {
+    css::Parser::new(css_text).parse().map_err(|err| {
+        let start = locate(err.fragment().offset());
+        let help = err.help().map(|help| match help.corresponding_span() {
+            Some(span) => {
+                let help_start = locate(span.start);
+                Help::with_span(
+                    help_start..help_start + span.len(),
+                    help.message().to_owned(),
+                )
+            }
+            None => Help::with_message(help.message().to_owned()),
+        });
+        ParseError::new(
+            Location::new(start, err.fragment().length()),
+            ParseErrorType::CssParsingError(err.into()),
+            help,
+        )
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
     use super::*;
 
     macro_rules! test {
@@ -638,7 +1292,11 @@ mod tests {
             "#div[hello={ctx[1]}]/div",
             "#div[@click={() => x += 1}]/div",
             "#div[@click   =  \"wrong\"]/div",
-            "#div[@click]/div"
+            "#div[@click]/div",
+            "#form[@submit|preventDefault={handler}]/form",
+            "#form[@submit|preventDefault|stopPropagation={handler}]/form",
+            "#form[@submit|invalid={handler}]/form",
+            "#div[title=\"say \\\"hi\\\"\"]/div"
         );
     }
 
@@ -661,6 +1319,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_parse_dynamic_tags() {
+        test!(
+            "#{tag}:Hello",
+            "#{heading ? \"h1\" : \"h2\"}[class=\"title\"] Hello /{heading ? \"h1\" : \"h2\"}",
+            "#{`h${level}`} #span:Hello / text",
+            "#{}:Hello",
+            "#{tag:Hello",
+            "#{open ? \"}\" : '{'}:Hello"
+        );
+    }
+
     #[test]
     fn can_parse_multiple_elements() {
         test!(
@@ -701,7 +1371,10 @@ mod tests {
 
     #[test]
     fn mustaches_allow_for_curly_braces() {
-        test!("{() => { console.log(\"hi\"); }  }");
+        test!(
+            "{() => { console.log(\"hi\"); }  }",
+            "#div[x={{ a: 1 }}]/div"
+        );
     }
 
     #[test]
@@ -726,7 +1399,22 @@ mod tests {
         test!(
             "#div[:hello:]/div",
             "#div[:invalid]/div",
-            "#input[:bind: attr=\"value\"]/input"
+            "#input[:bind: attr=\"value\"]/input",
+            "#div[contenteditable :html:content:]/div",
+            "#div[contenteditable :text:content:]/div",
+            "#input[:text: attr]/input",
+            "#div[:html:content]/div"
+        );
+    }
+
+    #[test]
+    fn can_parse_bindings_to_fields() {
+        test!(
+            "#input[:user.name:]/input",
+            "#div[contenteditable :text:todo.text:]/div",
+            "#input[:user.address.city:]/input",
+            "#input[:user. name:]/input",
+            "#input[:user.:]/input"
         );
     }
 
@@ -740,6 +1428,15 @@ mod tests {
         test!("---js let x = ; ---");
     }
 
+    #[test]
+    fn can_parse_typescript() {
+        test!(
+            "---ts let x: number = 1; --- #p {x} /p",
+            "---ts let x: = 1; ---",
+            "---ts let x = 1; enum E { A } ---"
+        );
+    }
+
     #[test]
     fn can_preprocess() {
         struct Preproc;
@@ -749,14 +1446,14 @@ mod tests {
                 &self,
                 lang: &str,
                 body: &str,
-            ) -> std::result::Result<Override, PreprocessError> {
+            ) -> std::result::Result<Preprocessed, PreprocessError> {
                 let body = match lang {
                     "ts" => Override::Js(format!("console.log(\"{body}\");")),
                     "sass" => Override::Css(format!("p {{ color: {body}; }}")),
                     _ => Override::None,
                 };
 
-                Ok(body)
+                Ok(body.into())
             }
         }
 
@@ -770,29 +1467,82 @@ mod tests {
     }
 
     #[test]
-    fn cannot_have_two_code_blocks_of_same_type() {
+    fn cannot_have_two_wasm_blocks() {
+        test!("---rust let x = 0; --- ---rust let x = 0; ---");
+    }
+
+    #[test]
+    fn merges_code_blocks_of_same_type() {
         test!(
-            "---js let x = 0; --- ---js let x = 0; ---",
-            "---css p { color: red; } --- ---css p { color: red; } ---",
-            "---rust let x = 0; --- ---rust let x = 0; ---"
+            "---js let x = 0; --- ---js let y = x; ---",
+            "---css p { color: red; } --- ---css a { color: blue; } ---",
+            "---js let x = 0; --- ---ts let y: number = x; ---"
         );
     }
 
     #[test]
-    fn cannot_have_two_preprocessed_scripts() {
+    fn javascript_parse_errors_in_later_blocks_have_proper_location_offsets() {
+        let src = "---js let x = 0; --- ---js let y = ; ---";
+        let errs = DynErrStream::new(
+            Box::new(io::sink()),
+            Source {
+                name: "TEST".to_owned(),
+                src,
+            },
+        );
+        let res = Parser::new(src)
+            .with_ctx(Ctx {
+                errs: errs.clone(),
+                ..Default::default()
+            })
+            .parse();
+        assert!(res.is_err());
+        let diagnostics = errs.take_diagnostics();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(src.rfind("; ---").unwrap(), diagnostics[0].offset);
+    }
+
+    #[test]
+    fn reports_javascript_that_rslint_panics_on() {
+        for src in ["#p {[/a{/b}]} /p", "---js let x = [/a{/b}]; ---"] {
+            let errs = DynErrStream::new(
+                Box::new(io::sink()),
+                Source {
+                    name: "TEST".to_owned(),
+                    src,
+                },
+            );
+            let res = Parser::new(src)
+                .with_ctx(Ctx {
+                    errs: errs.clone(),
+                    ..Default::default()
+                })
+                .parse();
+            assert!(res.is_err());
+            let diagnostics = errs.take_diagnostics();
+            assert_eq!(1, diagnostics.len());
+            assert_eq!(
+                "JavaScript error: could not parse this JavaScript",
+                diagnostics[0].msg
+            );
+        }
+    }
+
+    #[test]
+    fn merges_preprocessed_styles() {
         struct Preproc;
         impl Preprocessor for Preproc {
             fn preprocess(
                 &self,
                 lang: &str,
                 _body: &str,
-            ) -> std::result::Result<Override, PreprocessError> {
+            ) -> std::result::Result<Preprocessed, PreprocessError> {
                 let body = match lang {
                     "sass" => Override::Css("p { color: red; }".to_owned()),
                     _ => Override::None,
                 };
 
-                Ok(body)
+                Ok(body.into())
             }
         }
         let input = "---sass hello --- ---sass sass ---";
@@ -804,13 +1554,435 @@ mod tests {
         insta::assert_debug_snapshot!(ast);
     }
 
+    #[test]
+    fn preprocessor_errors_point_into_code_blocks() {
+        struct Preproc;
+        impl Preprocessor for Preproc {
+            fn preprocess(
+                &self,
+                lang: &str,
+                body: &str,
+            ) -> std::result::Result<Preprocessed, PreprocessError> {
+                match body.find('!') {
+                    Some(bang) => Err(PreprocessError::new(format!("no bangs in {lang}"))
+                        .with_loc(Location::new(bang, 1))),
+                    None => Err(PreprocessError::new("bad code block")),
+                }
+            }
+        }
+        for (input, offset) in [("#p hi /p ---sass a: b! ---", 21), ("---sass a ---", 2)] {
+            let parser = Parser::new(input).with_ctx(Ctx {
+                preprocessor: &Preproc,
+                ..Default::default()
+            });
+            let err = parser.parse().unwrap_err();
+            assert_eq!(offset, err.fragment().offset());
+        }
+    }
+
+    #[test]
+    fn errors_in_preprocessed_code_are_mapped_to_code_blocks() {
+        struct Preproc;
+        impl Preprocessor for Preproc {
+            fn preprocess(
+                &self,
+                _lang: &str,
+                body: &str,
+            ) -> std::result::Result<Preprocessed, PreprocessError> {
+                // Wraps the body in a rule, so it starts 4 characters later in the output
+                Ok(Preprocessed {
+                    code: Override::Css(format!("p {{{body}}}")),
+                    warnings: vec![],
+                    map: Some(PositionMap::from_iter([(0, 0), (3, 0)])),
+                })
+            }
+        }
+        let input = "---sass color: red ---";
+        let parser = Parser::new(input).with_ctx(Ctx {
+            preprocessor: &Preproc,
+            ..Default::default()
+        });
+        let err = parser.parse().unwrap_err();
+        // The semicolon is missing at the end of the code block
+        assert_eq!(19, err.fragment().offset());
+    }
+
+    #[test]
+    fn position_maps_are_relative_to_the_closest_mapping() {
+        let map = PositionMap::from_iter([(10, 2), (0, 5)]);
+        assert_eq!(Some(5), map.map(0));
+        assert_eq!(Some(9), map.map(4));
+        assert_eq!(Some(4), map.map(12));
+        assert_eq!(None, PositionMap::new().map(3));
+    }
+
     #[test]
     fn can_parse_use_decls() {
-        test!("{#use \"path\"} #p hello /p");
+        test!(
+            "{#use \"path\"} #p hello /p",
+            "{#use \"long/path/fancy-widget.decor\" as widget} #widget /widget",
+            "{#use \"path\" widget}"
+        );
+    }
+
+    #[test]
+    fn can_parse_raw_blocks() {
+        test!(
+            "{#raw}<svg viewBox=\"0 0 10 10\"><path d=\"M0 0\"/></svg>{/raw}",
+            "#div {#raw} #p {not parsed} {#if} /div {/raw} /div",
+            "{#raw}<b>unclosed</b>"
+        );
+    }
+
+    #[test]
+    fn can_parse_html_blocks() {
+        test!("#div {#html \"<b>\" + name + \"</b>\"} /div");
+    }
+
+    #[test]
+    fn can_parse_head_blocks() {
+        test!(
+            "{#head} #title {title} /title #meta[name=\"description\" content=\"hi\"]/meta {/head} #p Hello /p",
+            "{#head}#title Home /title{/head}",
+            "{#head} #title Home /title {/if}"
+        );
+    }
+
+    #[test]
+    fn can_parse_static_template_blocks() {
+        test!(
+            "#main {#static} #h1 Welcome /h1 #p {name} /p {/static} #button[@click={go}]:Go /main",
+            "{#static}#p Hello /p{/static}",
+            "{#static} #p Hello /p {/head}"
+        );
+    }
+
+    #[test]
+    fn can_parse_cfg_blocks() {
+        test!(
+            "#nav #a Home /a {#cfg feature=\"admin\"} #a Admin /a {/cfg} /nav",
+            "{#cfg feature=\"admin\"}{#use \"./admin.decor\"}{/cfg}",
+            "{#cfg admin} #p Hi /p {/cfg}"
+        );
+    }
+
+    #[test]
+    fn can_parse_translations() {
+        test!(
+            "#h1 {@t \"greeting\"}, {name}! /h1",
+            "{@t greeting}",
+            "{@tr \"greeting\"}"
+        );
+    }
+
+    #[test]
+    fn suggests_fixes_for_typos() {
+        let help = |src| {
+            Parser::new(src)
+                .parse()
+                .unwrap_err()
+                .help()
+                .map(|help| help.message().to_owned())
+        };
+        assert_eq!(
+            Some("did you mean `/div`?".to_owned()),
+            help("#div #p hi /p /dvi")
+        );
+        assert_eq!(
+            Some("did you mean `{#for}`?".to_owned()),
+            help("{#fro x in y} {x} {/for}")
+        );
+        assert_eq!(
+            Some("did you mean `{/if}`?".to_owned()),
+            help("{#if x} hi {/fi}")
+        );
+        assert_eq!(
+            Some("did you mean `{:else}`?".to_owned()),
+            help("{#if x} hi {:esle} bye {/if}")
+        );
+        assert_eq!(
+            Some("did you forget to close the attributes with `]`?".to_owned()),
+            help("#div[class=\"x\" #p hi /p /div")
+        );
+        assert_eq!(None, help("#div #p hi /p /span"));
+        assert_eq!(
+            Some("did you mean `&nbsp;`?".to_owned()),
+            help("#p a&nbps;b /p")
+        );
+    }
+
+    #[test]
+    fn can_parse_character_references() {
+        test!(
+            "#p Tom &amp; Jerry &#169; &#x1F600; AT&T /p",
+            "#p:Fish &chips; /p",
+            "#p &#xFFFFFFFF; /p"
+        );
+    }
+
+    #[test]
+    fn text_escapes_and_character_references_are_decoded() {
+        let text = Text("\\#1 \\{x\\} \\&amp; &lt;b&gt; &#x1F600; C:\\Users");
+        assert_eq!("#1 {x} &amp; <b> \u{1F600} C:\\Users", text.unescaped());
+        assert_eq!(
+            "#1 {x} &amp;amp; &lt;b&gt; &#x1F600; C:\\Users",
+            text.to_string()
+        );
     }
 
     #[test]
     fn can_parse_static_blocks() {
         test!("---js:static console.log(\"hello\"); ---");
     }
+
+    #[test]
+    fn suggests_fixes_for_unambiguous_errors() {
+        let cases = [
+            ("---js:statc let x = 1; ---", "---js:static let x = 1; ---"),
+            ("---js:foo let x = 1; ---", "---js:foo let x = 1; ---"),
+            (
+                "#div[class=\"a\" #p hi /p /div",
+                "#div[class=\"a\"] #p hi /p /div",
+            ),
+            ("#div[  class=\"a\"", "#div[  class=\"a\"]"),
+            ("#div[", "#div[]"),
+        ];
+        for (src, fixed) in cases {
+            let errs = DynErrStream::new(
+                Box::new(io::sink()),
+                Source {
+                    name: "TEST".to_owned(),
+                    src,
+                },
+            );
+            let res = Parser::new(src)
+                .with_ctx(Ctx {
+                    errs: errs.clone(),
+                    ..Default::default()
+                })
+                .parse();
+            let Err(err) = res else {
+                panic!("{src:?} should fail to parse");
+            };
+            let mut fixes = errs.take_fixes();
+            fixes.extend(Diagnostic::from(err).fixes);
+            assert_eq!(fixed, decorous_errors::apply_fixes(src, &fixes).0);
+        }
+    }
+
+    #[test]
+    fn reports_every_error_it_recovers_from() {
+        #[derive(Clone, Default)]
+        struct Output(Rc<RefCell<Vec<u8>>>);
+
+        impl io::Write for Output {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let src = "#div[class=\"a\" @click] #p hi /span /div {#if x} #ul[:] /ul {:elif} {/if} #p {y = } /p {#cfg x} #p {z = } /p {/cfg}\n---js let x = ; ---";
+        let out = Output::default();
+        let ast = Parser::new(src)
+            .with_ctx(Ctx {
+                errs: DynErrStream::new(
+                    Box::new(out.clone()),
+                    Source {
+                        name: "TEST".to_owned(),
+                        src,
+                    },
+                ),
+                ..Default::default()
+            })
+            .parse();
+        assert_eq!(
+            Some(&ParseErrorType::DidError),
+            ast.as_ref().err().map(ParseError::err_type)
+        );
+
+        let out = String::from_utf8(out.0.take()).unwrap();
+        let msgs = [
+            "expected an equals sign",
+            "invalid closing tag, expected p",
+            "expected an identifier",
+            "invalid extender, expected else",
+            "expected feature",
+            "JavaScript error: Expected an expression",
+            "JavaScript error: expected an expression",
+        ];
+        for msg in msgs {
+            assert!(out.contains(msg), "missing {msg:?} in:\n{out}");
+        }
+        // Nothing is reported twice
+        assert_eq!(msgs.len(), out.matches("Error:").count());
+    }
+
+    #[test]
+    fn reports_unclosed_blocks() {
+        for (src, block) in [
+            ("{#if x} hi", "if"),
+            ("{#if x} {:else} hi", "if"),
+            ("{#for x in y} hi", "for"),
+            ("{#head} hi", "head"),
+            ("{#static} hi", "static"),
+            ("{#cfg feature=\"a\"} hi", "cfg"),
+        ] {
+            let errs = DynErrStream::new(
+                Box::new(io::sink()),
+                Source {
+                    name: "TEST".to_owned(),
+                    src,
+                },
+            );
+            let err = Parser::new(src)
+                .with_ctx(Ctx {
+                    errs,
+                    ..Default::default()
+                })
+                .parse()
+                .unwrap_err();
+            assert_eq!(&ParseErrorType::UnclosedBlock(block), err.err_type());
+        }
+    }
+
+    /// Pieces of the grammar that are put together at random, so that the inputs are close enough
+    /// to valid components to reach every part of the parser. They're all ASCII, since
+    /// `rslint_parser` itself panics on some invalid JavaScript with multibyte characters.
+    const GRAMMAR: &[&str] = &[
+        "#div",
+        "#p[",
+        "#{`h1`}",
+        "]",
+        "[",
+        "/div",
+        "/p",
+        "/",
+        "{",
+        "}",
+        "{x}",
+        "{x + 1",
+        "{#if x}",
+        "{:else}",
+        "{:else if y}",
+        "{/if}",
+        "{#for x in y}",
+        "{#for}",
+        "{/for}",
+        "{#use \"./a.decor\"}",
+        "{#raw}",
+        "{/raw}",
+        "{#html x}",
+        "{#head}",
+        "{/head}",
+        "{#static}",
+        "{/static}",
+        "{#cfg feature=\"a\"}",
+        "{/cfg}",
+        "{#",
+        "{/",
+        "{:",
+        "---js",
+        "---css",
+        "---ts",
+        "---rust:static",
+        "---js:",
+        "---",
+        "\n",
+        " ",
+        "hello",
+        "=",
+        "\"",
+        "'",
+        "@click",
+        "|preventDefault",
+        ":",
+        "bind:",
+        "class",
+        "\\",
+        "&amp;",
+        "&#x1F600;",
+        "&#",
+        "&",
+        "<!--",
+        "-->",
+        "\u{0}",
+        "\t",
+        "let x = 0;",
+        "p { color: red; }",
+    ];
+
+    /// Parses `src`, returning an error if the parser panicked.
+    fn parse_without_panicking(src: &str) -> std::result::Result<(), String> {
+        let parse = || {
+            let _ = Parser::new(src)
+                .with_ctx(Ctx {
+                    errs: DynErrStream::new(
+                        Box::new(io::sink()),
+                        Source {
+                            name: "TEST".to_owned(),
+                            src,
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .parse();
+        };
+        std::panic::catch_unwind(parse).map_err(|_| format!("parser panicked on {src:?}"))
+    }
+
+    #[test]
+    fn does_not_panic_or_hang_on_malformed_input() {
+        use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut rng = StdRng::seed_from_u64(0xdec0);
+            let mut inputs = vec![];
+            for _ in 0..3000 {
+                let len = rng.gen_range(0..24);
+                inputs.push(
+                    (0..len)
+                        .map(|_| *GRAMMAR.choose(&mut rng).unwrap())
+                        .collect::<String>(),
+                );
+            }
+            // Every prefix of a valid component is also tried, since that's what unterminated
+            // input looks like
+            let valid = "---js let x = 0; ---\n#div[class=\"a\" @click={() => x++} bind:value={x}] {x} &amp; /div\n{#if x > 1} #p 你好 /p {:else} {#for y in [1]} {y} {/for} {/if}\n{#raw} <b> {/raw} <!-- hi -->\n---css p { color: red; } ---";
+            inputs.extend(valid.char_indices().map(|(i, _)| valid[..i].to_owned()));
+
+            let result = inputs
+                .iter()
+                .try_for_each(|input| parse_without_panicking(input));
+            tx.send(result).unwrap();
+        });
+
+        // Not finishing in time means the parser is stuck in a loop
+        let result = rx
+            .recv_timeout(std::time::Duration::from_secs(60))
+            .expect("parser did not finish");
+        if let Err(msg) = result {
+            panic!("{msg}");
+        }
+    }
+
+    #[test]
+    fn can_tokenize() {
+        let tokens = tokenize(
+            "---js let x = 0; ---\n#div[class=\"a\" @click={() => x++}] {x} /div\n{#if x > 1} // big\n{/if}\n---css p {} ---",
+        );
+        insta::assert_debug_snapshot!(tokens);
+    }
+
+    #[test]
+    fn tokenizes_sources_that_do_not_parse() {
+        let tokens = tokenize("#div[class=\"a\"] {#for in} /div");
+        assert!(tokens
+            .last()
+            .is_some_and(|tok| tok.kind == TokenKind::ElemEnd("div")));
+    }
 }
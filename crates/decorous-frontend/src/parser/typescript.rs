@@ -0,0 +1,273 @@
+//! Turns TypeScript into JavaScript by erasing its types.
+//!
+//! Everything that only exists for the type checker is replaced with whitespace, instead of being
+//! removed. That way, every position in the JavaScript is the same as in the TypeScript, so
+//! errors can point straight into the original code block.
+
+use std::ops::Range;
+
+use rslint_parser::{NodeOrToken, SyntaxElement, SyntaxKind, SyntaxNode, TextRange};
+
+/// TypeScript that can't be erased, because it generates code, like an `enum`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsupported {
+    pub range: Range<usize>,
+    pub feature: &'static str,
+}
+
+/// Erases the types from `tree`, which must have been parsed as TypeScript.
+pub fn erase_types(tree: &SyntaxNode) -> Result<String, Unsupported> {
+    let mut erased = vec![];
+    visit(tree, &mut erased)?;
+
+    let mut js = tree.to_string();
+    for range in erased {
+        let range = usize::from(range.start())..usize::from(range.end());
+        let blank = js[range.clone()]
+            .chars()
+            .map(|c| if matches!(c, '\n' | '\r') { c } else { ' ' })
+            .collect::<String>();
+        js.replace_range(range, &blank);
+    }
+    Ok(js)
+}
+
+fn visit(node: &SyntaxNode, erased: &mut Vec<TextRange>) -> Result<(), Unsupported> {
+    use SyntaxKind::*;
+
+    let unsupported = |feature| {
+        Err(Unsupported {
+            range: usize::from(node.text_range().start())..usize::from(node.text_range().end()),
+            feature,
+        })
+    };
+    match node.kind() {
+        TS_ENUM => return unsupported("enums"),
+        TS_NAMESPACE_DECL | TS_MODULE_DECL if !has_token(node, DECLARE_KW) => {
+            return unsupported("namespaces")
+        }
+        TS_IMPORT_EQUALS_DECL => return unsupported("`import =` declarations"),
+        TS_EXPORT_ASSIGNMENT => return unsupported("`export =` assignments"),
+        TS_CONSTRUCTOR_PARAM => return unsupported("parameter properties"),
+        _ if is_type_only(node) => {
+            erased.push(node.text_range());
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // A token that is only erased if it's followed by a type, like the colon of an annotation
+    let mut before_type = None;
+    let mut in_implements = false;
+    for child in node.children_with_tokens() {
+        let token = match child {
+            NodeOrToken::Node(child) if is_type(child.kind()) || is_type_only(&child) => {
+                erased.extend(before_type.take());
+                erased.push(child.text_range());
+                continue;
+            }
+            NodeOrToken::Node(child) if in_implements && child.kind() == TS_EXPR_WITH_TYPE_ARGS => {
+                erased.push(child.text_range());
+                continue;
+            }
+            NodeOrToken::Node(child) if is_this_param(&child) => {
+                erased.push(child.text_range());
+                // The comma after it is erased too
+                if let Some(comma) = next_non_trivia(&SyntaxElement::Node(child))
+                    .filter(|comma| comma.kind() == COMMA)
+                {
+                    erased.push(comma.text_range());
+                }
+                continue;
+            }
+            NodeOrToken::Node(child) => {
+                before_type = None;
+                in_implements = false;
+                visit(&child, erased)?;
+                continue;
+            }
+            NodeOrToken::Token(token) => token,
+        };
+        if token.kind().is_trivia() {
+            continue;
+        }
+
+        match (node.kind(), token.kind()) {
+            (_, COLON) => {
+                before_type = Some(token.text_range());
+                continue;
+            }
+            (TS_ASSERTION, IDENT) if token.text() == "as" => {
+                before_type = Some(token.text_range());
+                continue;
+            }
+            (TS_CONST_ASSERTION, IDENT | CONST_KW)
+            | (TS_ASSERTION, L_ANGLE | R_ANGLE)
+            | (TS_NON_NULL | SINGLE_PATTERN, BANG)
+            | (SINGLE_PATTERN | CLASS_PROP | METHOD, QUESTION)
+            | (CLASS_DECL, ABSTRACT_KW)
+            | (
+                CLASS_PROP | METHOD,
+                PRIVATE_KW | PUBLIC_KW | PROTECTED_KW | READONLY_KW | ABSTRACT_KW,
+            ) => erased.push(token.text_range()),
+            (CLASS_DECL, IMPLEMENTS_KW) => {
+                erased.push(token.text_range());
+                in_implements = true;
+                continue;
+            }
+            (_, COMMA) if in_implements => {
+                erased.push(token.text_range());
+                continue;
+            }
+            _ => {}
+        }
+        before_type = None;
+        in_implements = false;
+    }
+
+    Ok(())
+}
+
+/// Checks if `node` is only there for the type checker, like an `interface` or a `declare`
+/// statement, so all of it can be erased.
+fn is_type_only(node: &SyntaxNode) -> bool {
+    use SyntaxKind::*;
+
+    match node.kind() {
+        TS_INTERFACE_DECL | TS_TYPE_ALIAS_DECL | TS_TYPE_PARAMS | TS_TYPE_ARGS => true,
+        EXPORT_DECL => node
+            .children()
+            .any(|child| matches!(child.kind(), TS_INTERFACE_DECL | TS_TYPE_ALIAS_DECL)),
+        IMPORT_DECL => has_token(node, TYPE_KW),
+        VAR_DECL | FN_DECL | CLASS_DECL | CLASS_PROP | TS_NAMESPACE_DECL | TS_MODULE_DECL => {
+            has_token(node, DECLARE_KW)
+        }
+        // Abstract methods have no body
+        METHOD => has_token(node, ABSTRACT_KW),
+        _ => false,
+    }
+}
+
+fn is_type(kind: SyntaxKind) -> bool {
+    use SyntaxKind::*;
+
+    matches!(
+        kind,
+        TS_ANY
+            | TS_ARRAY
+            | TS_BIGINT
+            | TS_BOOLEAN
+            | TS_CONDITIONAL_TYPE
+            | TS_CONSTRUCTOR_TYPE
+            | TS_FN_TYPE
+            | TS_IMPORT
+            | TS_INDEXED_ARRAY
+            | TS_INFER
+            | TS_INTERSECTION
+            | TS_LITERAL
+            | TS_MAPPED_TYPE
+            | TS_NEVER
+            | TS_NULL
+            | TS_NUMBER
+            | TS_OBJECT
+            | TS_OBJECT_TYPE
+            | TS_PAREN
+            | TS_PREDICATE
+            | TS_STRING
+            | TS_SYMBOL
+            | TS_TEMPLATE
+            | TS_THIS
+            | TS_TUPLE
+            | TS_TYPE_OPERATOR
+            | TS_TYPE_QUERY
+            | TS_TYPE_REF
+            | TS_UNDEFINED
+            | TS_UNION
+            | TS_UNKNOWN
+            | TS_VOID
+    )
+}
+
+/// Checks if `node` is a `this` parameter, which only gives the type of `this` in a function.
+fn is_this_param(node: &SyntaxNode) -> bool {
+    node.kind() == SyntaxKind::SINGLE_PATTERN
+        && node.parent().map(|parent| parent.kind()) == Some(SyntaxKind::PARAMETER_LIST)
+        && node.first_child().is_some_and(|name| name.text() == "this")
+}
+
+fn has_token(node: &SyntaxNode, kind: SyntaxKind) -> bool {
+    node.children_with_tokens()
+        .any(|child| child.as_token().is_some_and(|token| token.kind() == kind))
+}
+
+fn next_non_trivia(element: &SyntaxElement) -> Option<SyntaxElement> {
+    let mut next = element.next_sibling_or_token();
+    while next.as_ref().is_some_and(|next| next.kind().is_trivia()) {
+        next = next.and_then(|next| next.next_sibling_or_token());
+    }
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use rslint_parser::{parse_with_syntax, Syntax};
+
+    use super::*;
+
+    fn erase(ts: &str) -> Result<String, Unsupported> {
+        let res = parse_with_syntax(ts, 0, Syntax::default().typescript());
+        assert!(res.errors().is_empty(), "{:?}", res.errors());
+        erase_types(&res.syntax())
+    }
+
+    #[test]
+    fn erases_types_without_moving_anything() {
+        for (ts, js) in [
+            ("let x: number = 1;", "let x         = 1;"),
+            (
+                "const f = (a: string, b?: number): void => {};",
+                "const f = (a        , b         )       => {};",
+            ),
+            (
+                "function g<T>(this: Window, y: T[]): T { return y![0] as T; }",
+                "function g   (              y     )    { return y [0]     ; }",
+            ),
+            ("let m: Array<{ a: number }> = [];", "let m                       = [];"),
+            ("let a = <number>x, b = y as const;", "let a =         x, b = y         ;"),
+            ("let c!: number;", "let c         ;"),
+            (
+                "interface Foo {\n  a: string\n}\ntype Bar = number;",
+                "               \n           \n \n                  ",
+            ),
+            (
+                "abstract class C<T> extends D<T> implements Foo, Bar { private a?: string; abstract m(): void; readonly r = 1; }",
+                "         class C    extends D                        {         a         ;                              r = 1; }",
+            ),
+            (
+                "declare const d: string;\nimport type { I } from \"./i\";\nexport type G = string;",
+                "                        \n                             \n                       ",
+            ),
+        ] {
+            let erased = erase(ts).unwrap();
+            assert_eq!(js, erased);
+            assert_eq!(ts.len(), erased.len());
+        }
+    }
+
+    #[test]
+    fn fails_on_typescript_that_generates_code() {
+        assert_eq!(
+            Err(Unsupported {
+                range: 11..23,
+                feature: "enums"
+            }),
+            erase("let x = 1;\nenum E { A }")
+        );
+        assert_eq!(
+            "parameter properties",
+            erase("class A { constructor(private a: number) {} }")
+                .unwrap_err()
+                .feature
+        );
+    }
+}
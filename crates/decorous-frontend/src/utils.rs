@@ -2,8 +2,10 @@
 //! related things.
 use rslint_parser::{
     ast::{
-        ArrowExpr, ArrowExprParams, AssignExpr, BlockStmt, Decl, Expr, ExprOrBlock, ExprStmt,
-        NameRef, ObjectPatternProp, Pattern, Script, Stmt, VarDecl,
+        ArrowExpr, ArrowExprParams, AssignExpr, AssignOp, AwaitExpr, BlockStmt, BracketExpr,
+        CallExpr, Decl, DotExpr, Expr, ExprOrBlock, ExprStmt, FnDecl, FnExpr, ForOfStmt, Getter,
+        Method, NameRef, ObjectPatternProp, Pattern, Script, Setter, Stmt, UnaryExpr, UnaryOp,
+        VarDecl,
     },
     AstNode, SmolStr, SyntaxNode, SyntaxNodeExt,
 };
@@ -70,12 +72,127 @@ pub fn get_unbound_refs(syntax_node: &SyntaxNode) -> Vec<NameRef> {
     all
 }
 
-/// Checks if a [`NameRef`]'s parent is an [`AssignExpr`].
+/// Checks if a [`NameRef`]'s parent is an [`AssignExpr`], or an increment or decrement like
+/// `count++`.
 pub fn is_from_assignment(nref: &NameRef) -> bool {
     nref.syntax()
         .parent()
-        .and_then(|parent| parent.try_to::<AssignExpr>())
-        .is_some()
+        .is_some_and(|parent| parent.is::<AssignExpr>() || get_update(nref).is_some())
+}
+
+/// Gets the increment or decrement of a [`NameRef`], like `count++` or `--count`.
+pub fn get_update(nref: &NameRef) -> Option<UnaryExpr> {
+    nref.syntax()
+        .parent()?
+        .try_to::<UnaryExpr>()
+        .filter(|unary| matches!(unary.op(), Some(UnaryOp::Increment | UnaryOp::Decrement)))
+}
+
+/// The methods of arrays, maps and sets that change their contents.
+const MUTATING_METHODS: &[&str] = &[
+    "push",
+    "pop",
+    "shift",
+    "unshift",
+    "splice",
+    "sort",
+    "reverse",
+    "fill",
+    "copyWithin",
+    "set",
+    "add",
+    "delete",
+    "clear",
+];
+
+/// Gets the variable that `stmt` assigns a value to, if it's a plain assignment to a single
+/// variable like `doubled = count * 2`.
+pub fn get_assigned_var(stmt: &SyntaxNode) -> Option<SmolStr> {
+    let assignment = stmt
+        .try_to::<ExprStmt>()?
+        .expr()?
+        .syntax()
+        .try_to::<AssignExpr>()?;
+    if assignment.op()? != AssignOp::Assign {
+        return None;
+    }
+    let target = assignment.syntax().first_child()?.try_to::<NameRef>()?;
+    Some(target.ident_token()?.text().clone())
+}
+
+/// Gets the variable that an expression consists of, if it's a lone reference like `items`.
+pub fn get_plain_var(expr: &SyntaxNode) -> Option<SmolStr> {
+    if expr.is::<Script>() {
+        return get_plain_var(&expr.first_child()?);
+    }
+    let expr = match expr.try_to::<ExprStmt>() {
+        Some(stmt) => stmt.expr()?.syntax().clone(),
+        None => expr.clone(),
+    };
+    Some(expr.try_to::<NameRef>()?.ident_token()?.text().clone())
+}
+
+/// Gets the expression that changes the contents of the object or array that a [`NameRef`]
+/// refers to, like `obj.prop = 1`, `arr[0]++`, `delete obj.prop` or `arr.push(1)`. Assigning to
+/// the reference itself isn't a mutation, see [`is_from_assignment`].
+pub fn get_mutation(nref: &NameRef) -> Option<SyntaxNode> {
+    // Go up to the outermost member expression that the reference is the object of
+    let mut target = nref.syntax().clone();
+    while let Some(parent) = target.parent().filter(|parent| {
+        (parent.is::<DotExpr>() || parent.is::<BracketExpr>())
+            && parent.first_child().as_ref() == Some(&target)
+    }) {
+        target = parent;
+    }
+    if &target == nref.syntax() {
+        return None;
+    }
+
+    let parent = target.parent()?;
+    let is_mutation = if parent.is::<AssignExpr>() {
+        parent.first_child().as_ref() == Some(&target)
+    } else if let Some(unary) = parent.try_to::<UnaryExpr>() {
+        matches!(
+            unary.op(),
+            Some(UnaryOp::Increment | UnaryOp::Decrement | UnaryOp::Delete)
+        )
+    } else if parent.is::<CallExpr>() {
+        parent.first_child().as_ref() == Some(&target)
+            && target
+                .try_to::<DotExpr>()
+                .and_then(|dot| dot.prop())
+                .is_some_and(|method| {
+                    MUTATING_METHODS
+                        .iter()
+                        .any(|name| method.syntax().text() == *name)
+                })
+    } else {
+        false
+    };
+    is_mutation.then_some(parent)
+}
+
+/// Checks if a [`SyntaxNode`] awaits outside of any function, like a top-level `await` does.
+pub fn has_toplevel_await(syntax_node: &SyntaxNode) -> bool {
+    let mut found = false;
+    syntax_node.descendants_with(&mut |descendant| {
+        if found
+            || descendant.is::<FnDecl>()
+            || descendant.is::<FnExpr>()
+            || descendant.is::<ArrowExpr>()
+            || descendant.is::<Method>()
+            || descendant.is::<Getter>()
+            || descendant.is::<Setter>()
+        {
+            return false;
+        }
+        found = descendant.is::<AwaitExpr>()
+            || descendant
+                .try_to::<ForOfStmt>()
+                .is_some_and(|for_of| for_of.await_token().is_some());
+        true
+    });
+    found
 }
 
 /// Gets the identifiers from a pattern. This is useful for complex assignments.
@@ -337,4 +454,52 @@ mod tests {
             .unwrap();
         assert_eq!(expected, get_idents_from_pattern(pat).as_slice());
     }
+
+    #[test]
+    fn can_detect_toplevel_await() {
+        for (input, expected) in [
+            ("let x = await fetch(\"/\");", true),
+            ("if (x) { await y; }", true),
+            ("for await (const x of y) {}", true),
+            ("async function f() { await x; }", false),
+            ("let f = async () => await x;", false),
+            ("let x = { async f() { await y; } };", false),
+        ] {
+            let tree = rslint_parser::parse_with_syntax(
+                input,
+                0,
+                rslint_parser::Syntax::default().module().top_level_await(),
+            )
+            .syntax();
+            assert_eq!(expected, has_toplevel_await(&tree), "{input}");
+        }
+    }
+
+    #[test]
+    fn can_get_mutations_of_objects_and_arrays() {
+        for (input, expected) in [
+            ("obj.a.b = 1", Some("obj.a.b = 1")),
+            ("arr[i] += 2", Some("arr[i] += 2")),
+            ("obj.count++", Some("obj.count++")),
+            ("delete obj.prop", Some("delete obj.prop")),
+            ("arr.push(1)", Some("arr.push(1)")),
+            ("obj.items.splice(0, 1)", Some("obj.items.splice(0, 1)")),
+            ("obj = 1", None),
+            ("x = obj.a", None),
+            ("arr.map(f)", None),
+            ("f(arr.push)", None),
+        ] {
+            let tree = parse_text(input, 0).syntax();
+            let nref = tree
+                .descendants()
+                .filter_map(|node| node.try_to::<NameRef>())
+                .find(|nref| nref.syntax().text() == "obj" || nref.syntax().text() == "arr")
+                .unwrap();
+            assert_eq!(
+                expected,
+                get_mutation(&nref).map(|node| node.to_string()).as_deref(),
+                "{input}"
+            );
+        }
+    }
 }
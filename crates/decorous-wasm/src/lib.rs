@@ -0,0 +1,201 @@
+//! JavaScript bindings to the decorous compiler, so that JS build tools (Vite, Rollup, esbuild)
+//! can compile components without running the `decorous` binary.
+//!
+//! Build the npm package with `wasm-pack build crates/decorous-wasm --target nodejs`. It exports
+//! a single function:
+//!
+//! ```js
+//! const { compile } = require("decorous-wasm");
+//! const { js, css, html, diagnostics } = compile(source, { name: "app", minify: true });
+//! ```
+//!
+//! Used components are imported from their paths, for the build tool to resolve. WebAssembly
+//! code blocks aren't compiled.
+
+use decorous::{CompileOptions, CsrOptions, CssOptions, OutputFormat, PrerenderOptions, Severity};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// The options of [`compile`], in the shape they are passed from JavaScript. Every field is
+/// optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct Options {
+    name: Option<String>,
+    render_method: RenderMethod,
+    format: Format,
+    modularize: bool,
+    bundle: bool,
+    custom_element: bool,
+    index_html: Option<String>,
+    minify: bool,
+    minify_css: bool,
+    ascii_only: bool,
+    html_sanitizer: Option<String>,
+    no_collapse: bool,
+    csp: bool,
+    nonce: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RenderMethod {
+    #[default]
+    Csr,
+    Prerender,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    #[default]
+    Esm,
+    Iife,
+    Cjs,
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Esm => Self::Esm,
+            Format::Iife => Self::Iife,
+            Format::Cjs => Self::Cjs,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Output {
+    js: String,
+    css: Option<String>,
+    html: Option<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A diagnostic, with its position as a line and column instead of a byte offset, which JavaScript
+/// strings can't be indexed with.
+#[derive(Debug, Serialize)]
+struct Diagnostic {
+    message: String,
+    severity: &'static str,
+    line: usize,
+    column: usize,
+    note: Option<String>,
+}
+
+/// Compiles the component in `source`. `options` is an object like
+/// `{ name: "app", renderMethod: "prerender", format: "iife", minify: true }`.
+#[wasm_bindgen]
+pub fn compile(source: &str, options: JsValue) -> Result<JsValue, JsError> {
+    let options = if options.is_undefined() || options.is_null() {
+        Options::default()
+    } else {
+        let json = js_sys::JSON::stringify(&options)
+            .map_err(|_| JsError::new("options should be a plain object"))?;
+        serde_json::from_str(&String::from(json))
+            .map_err(|err| JsError::new(&format!("invalid options: {err}")))?
+    };
+    let output = serde_json::to_string(&compile_with(source, options))?;
+    js_sys::JSON::parse(&output).map_err(|_| JsError::new("error returning compiler output"))
+}
+
+fn compile_with(source: &str, options: Options) -> Output {
+    let css = CssOptions {
+        minify: options.minify_css,
+        ..Default::default()
+    };
+    let render_method = match options.render_method {
+        RenderMethod::Csr => decorous::RenderMethod::Csr(CsrOptions {
+            modularize: options.modularize,
+            format: options.format.into(),
+            bundle: options.bundle,
+            custom_element: options.custom_element,
+            css,
+            html_sanitizer: options.html_sanitizer,
+            no_collapse: options.no_collapse,
+            csp: options.csp,
+            nonce: options.nonce,
+            runtime: None,
+        }),
+        RenderMethod::Prerender => decorous::RenderMethod::Prerender(PrerenderOptions {
+            format: options.format.into(),
+            bundle: options.bundle,
+            css,
+            html_sanitizer: options.html_sanitizer,
+            no_collapse: options.no_collapse,
+            csp: options.csp,
+            nonce: options.nonce,
+            ..Default::default()
+        }),
+    };
+    let out = decorous::compile(
+        source,
+        CompileOptions {
+            name: options.name.as_deref().unwrap_or("component"),
+            render_method,
+            index_html: options.index_html,
+            minify: options.minify,
+            ascii_only: options.ascii_only,
+            ..Default::default()
+        },
+    );
+
+    Output {
+        js: out.js,
+        css: out.css,
+        html: out.html,
+        diagnostics: out
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| {
+                let position = diagnostic
+                    .position
+                    .unwrap_or(decorous::LineCol { line: 1, column: 1 });
+                Diagnostic {
+                    message: diagnostic.msg.into_owned(),
+                    severity: match diagnostic.severity {
+                        Severity::Error => "error",
+                        Severity::Warning => "warning",
+                    },
+                    line: position.line,
+                    column: position.column,
+                    note: diagnostic.note.map(|note| note.into_owned()),
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(json: &str) -> Options {
+        serde_json::from_str(json).expect("options should be valid")
+    }
+
+    #[test]
+    fn reads_options_from_js_objects() {
+        let opts = options(r#"{ "name": "app", "renderMethod": "prerender", "format": "iife" }"#);
+        assert_eq!(Some("app"), opts.name.as_deref());
+        assert!(matches!(opts.render_method, RenderMethod::Prerender));
+        assert!(matches!(opts.format, Format::Iife));
+        assert!(serde_json::from_str::<Options>(r#"{ "minfy": true }"#).is_err());
+    }
+
+    #[test]
+    fn returns_outputs_and_diagnostics() {
+        let out = compile_with(
+            "#p Hello! /p\n---css p { color: red; } ---",
+            options(r#"{ "indexHtml": "out" }"#),
+        );
+        assert!(out.js.contains("Hello!"));
+        assert!(out.css.is_some());
+        assert!(out.html.is_some_and(|html| html.contains("out.js")));
+
+        let out = compile_with("#p Hello! /p\n#div {x /div", Options::default());
+        let json = serde_json::to_value(&out.diagnostics[0]).unwrap();
+        assert_eq!("error", json["severity"]);
+        assert_eq!(2, json["line"]);
+    }
+}
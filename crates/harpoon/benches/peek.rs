@@ -0,0 +1,123 @@
+use std::{collections::VecDeque, str::Chars};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use harpoon::Harpoon;
+
+const INPUT: &str = include_str!("../../decorous-frontend/benches/inputs/basic_with_js.decor");
+
+/// The `VecDeque` based peeking that `Harpoon` used before its ring buffer, kept as a baseline.
+struct DequeHarpoon<'a> {
+    chars: Chars<'a>,
+    peek_buf: VecDeque<char>,
+    current: Option<char>,
+    idx: usize,
+}
+
+impl<'a> DequeHarpoon<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars(),
+            peek_buf: VecDeque::new(),
+            current: None,
+            idx: 0,
+        }
+    }
+
+    fn consume(&mut self) -> Option<char> {
+        let next = self.peek_buf.pop_front().or_else(|| self.chars.next());
+        if let Some(next) = next {
+            self.idx += next.len_utf8();
+        }
+        self.current = next;
+        self.current
+    }
+
+    fn peek_n(&mut self, n: usize) -> impl Iterator<Item = char> + '_ {
+        let remaining = n.saturating_sub(self.peek_buf.len());
+        for _ in 0..remaining {
+            let Some(next) = self.chars.next() else {
+                break;
+            };
+            self.peek_buf.push_back(next);
+        }
+        self.peek_buf.iter().take(n).cloned()
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.peek_n(1).next()
+    }
+}
+
+fn peek_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("peek then consume");
+    group.bench_function("deque", |b| {
+        b.iter(|| {
+            let mut harpoon = DequeHarpoon::new(black_box(INPUT));
+            while harpoon.peek().is_some() {
+                harpoon.consume();
+            }
+        })
+    });
+    group.bench_function("ring", |b| {
+        b.iter(|| {
+            let mut harpoon = Harpoon::new(black_box(INPUT));
+            while harpoon.peek().is_some() {
+                harpoon.consume();
+            }
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("peek_n then consume");
+    for n in [3, 8, 32] {
+        group.bench_with_input(BenchmarkId::new("deque", n), &n, |b, &n| {
+            b.iter(|| {
+                let mut harpoon = DequeHarpoon::new(black_box(INPUT));
+                while harpoon.peek_n(n).count() > 0 {
+                    harpoon.consume();
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("ring", n), &n, |b, &n| {
+            b.iter(|| {
+                let mut harpoon = Harpoon::new(black_box(INPUT));
+                while harpoon.peek_n(n).count() > 0 {
+                    harpoon.consume();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Speculatively consumes a few chars at every position and backs out, like a lexer peeking a
+/// token.
+fn backtrack_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("speculate then back out");
+    group.bench_function("clone", |b| {
+        b.iter(|| {
+            let mut harpoon = Harpoon::new(black_box(INPUT));
+            while harpoon.peek().is_some() {
+                let saved = harpoon.clone();
+                harpoon.consume_n(4);
+                harpoon = saved;
+                harpoon.consume();
+            }
+        })
+    });
+    group.bench_function("checkpoint", |b| {
+        b.iter(|| {
+            let mut harpoon = Harpoon::new(black_box(INPUT));
+            while harpoon.peek().is_some() {
+                let checkpoint = harpoon.checkpoint();
+                harpoon.consume_n(4);
+                harpoon.rewind(checkpoint);
+                harpoon.consume();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, peek_bench, backtrack_bench);
+criterion_main!(benches);
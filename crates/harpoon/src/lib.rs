@@ -1,9 +1,15 @@
+mod ring;
 mod span;
 
 use arrayvec::ArrayVec;
 use itertools::{EitherOrBoth, Itertools};
+use ring::RingBuf;
 pub use span::Span;
-use std::{collections::VecDeque, ops::Deref, str::Chars};
+use std::{ops::Deref, str::Chars};
+
+/// The number of chars cached by peeks. Peeking further ahead than this still works, but the
+/// chars past the cache are decoded again on every call.
+const PEEK_CAPACITY: usize = 16;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Peeked<const SIZE: usize>(ArrayVec<char, SIZE>);
@@ -16,15 +22,36 @@ impl<const SIZE: usize> Deref for Peeked<SIZE> {
     }
 }
 
+/// A position in the input of a [`Harpoon`], created by [`Harpoon::checkpoint`]. Rewinding to it
+/// with [`Harpoon::rewind`] undoes everything consumed since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    idx: usize,
+    current: Option<char>,
+    /// The number of chars popped from the peek buffer, to find the chars consumed since.
+    popped: usize,
+    line: usize,
+    col: usize,
+}
+
+impl Checkpoint {
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.idx
+    }
+}
+
 #[derive(Debug)]
 pub struct Harpoon<'a> {
     source: &'a str,
     chars: Chars<'a>,
 
-    peek_buf: VecDeque<char>,
+    peek_buf: RingBuf<PEEK_CAPACITY>,
     current: Option<char>,
 
     idx: usize,
+    line: usize,
+    col: usize,
 }
 
 impl<'a> Harpoon<'a> {
@@ -33,35 +60,44 @@ impl<'a> Harpoon<'a> {
             source: input,
             chars: input.chars(),
             current: None,
-            peek_buf: VecDeque::new(),
+            peek_buf: RingBuf::new(),
             idx: 0,
+            line: 1,
+            col: 1,
         };
         harpoon
     }
 
+    #[inline]
     pub fn consume(&mut self) -> Option<char> {
-        if let Some(next) = self.peek_buf.pop_front() {
-            self.idx += next.len_utf8();
-            self.current = Some(next);
-            return self.current;
-        }
-
-        let next = self.chars.next();
+        // Going through the peek buffer keeps the last consumed chars around, for rewinding
+        self.fill_peek_buf(1);
+        let next = self.peek_buf.pop_front();
         if let Some(next) = next {
             self.idx += next.len_utf8();
+            if next == '\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
         }
         self.current = next;
         self.current
     }
 
+    #[inline]
     pub fn current(&self) -> Option<char> {
         self.current
     }
 
+    #[inline]
     pub fn peek(&mut self) -> Option<char> {
-        self.peek_n_const::<1>().first().copied()
+        self.fill_peek_buf(1);
+        self.peek_buf.front()
     }
 
+    #[inline]
     pub fn peek_is(&mut self, expected: char) -> bool {
         self.peek().is_some_and(|c| c == expected)
     }
@@ -70,43 +106,127 @@ impl<'a> Harpoon<'a> {
         self.peek().is_some_and(|c| expecteds.contains(c))
     }
 
+    #[inline]
     pub fn peek_n_const<const N: usize>(&mut self) -> Peeked<N> {
-        let mut arrvec = ArrayVec::new();
-        let remaining = N.saturating_sub(self.peek_buf.len());
-        for _ in 0..remaining {
-            let Some(next) = self.chars.next() else {
-                break;
-            };
-            self.peek_buf.push_back(next);
-        }
-        for i in self.peek_buf.iter().take(N) {
-            arrvec.push(*i);
-        }
-        Peeked(arrvec)
+        Peeked(self.peek_n(N).collect())
     }
 
+    #[inline]
     pub fn peek_n(&mut self, n: usize) -> impl Iterator<Item = char> + '_ {
-        let remaining = n.saturating_sub(self.peek_buf.len());
-        for _ in 0..remaining {
+        self.fill_peek_buf(n);
+        let uncached = n.saturating_sub(self.peek_buf.len());
+        self.peek_buf
+            .iter()
+            .take(n)
+            .chain(self.chars.clone().take(uncached))
+    }
+
+    #[inline]
+    fn fill_peek_buf(&mut self, n: usize) {
+        while self.peek_buf.len() < n.min(PEEK_CAPACITY) {
             let Some(next) = self.chars.next() else {
                 break;
             };
             self.peek_buf.push_back(next);
         }
-        self.peek_buf.iter().take(n).cloned()
     }
 
-    pub fn consume_while<F>(&mut self, mut f: F)
+    /// Consumes chars while `f` is true for them, returning the span of what was consumed.
+    pub fn consume_while<F>(&mut self, mut f: F) -> Span<'a>
     where
         F: FnMut(char) -> bool,
     {
+        let start = self.idx;
         while self.peek().is_some_and(&mut f) {
             self.consume();
         }
+        Span::new(&self.source[start..self.idx], start)
+    }
+
+    /// Consumes chars up to `stopper`, returning the span of what was consumed. `stopper` itself
+    /// isn't consumed.
+    pub fn consume_until(&mut self, stopper: char) -> Span<'a> {
+        self.consume_while(|c| c != stopper)
     }
 
-    pub fn consume_until(&mut self, stopper: char) {
-        self.consume_while(|c| c != stopper);
+    /// Consumes an identifier: an ASCII letter or `_`, followed by any number of ASCII
+    /// alphanumerics, `_`, or `-`. The returned span is empty if the input doesn't start with one.
+    pub fn consume_ident(&mut self) -> Span<'a> {
+        if !self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        {
+            return Span::new("", self.idx);
+        }
+        self.consume_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+    }
+
+    /// Consumes a decimal number, like `12` or `3.14`. The fractional part is only consumed if a
+    /// digit follows the `.`. The returned span is empty if the input doesn't start with a digit.
+    pub fn consume_decimal(&mut self) -> Span<'a> {
+        let start = self.idx;
+        self.consume_while(|c| c.is_ascii_digit());
+        let fraction = self.peek_n_const::<2>();
+        if self.idx != start && matches!(&fraction[..], ['.', digit] if digit.is_ascii_digit()) {
+            self.consume();
+            self.consume_while(|c| c.is_ascii_digit());
+        }
+        Span::new(&self.source[start..self.idx], start)
+    }
+
+    /// Consumes a string delimited by `delim`, returning the span of its contents. Inside of the
+    /// string, `escape` escapes the char after it, so an escaped `delim` doesn't end the string.
+    /// The contents are returned as written, with the escapes left in.
+    ///
+    /// Nothing is consumed and `None` is returned if the input doesn't start with `delim`. If the
+    /// string is never closed, the rest of the input is consumed and `None` is returned.
+    pub fn consume_quoted(&mut self, delim: char, escape: char) -> Option<Span<'a>> {
+        if !self.peek_is(delim) {
+            return None;
+        }
+        self.consume();
+        let start = self.idx;
+        loop {
+            match self.consume()? {
+                c if c == escape => {
+                    self.consume()?;
+                }
+                c if c == delim => {
+                    let end = self.idx - delim.len_utf8();
+                    return Some(Span::new(&self.source[start..end], start));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Consumes a group starting with `open` up to its matching `close`, returning the span of
+    /// everything between them. Nested groups are matched too, so `(a(b)c)` is consumed as a
+    /// whole, with the contents `a(b)c`.
+    ///
+    /// Nothing is consumed and `None` is returned if the input doesn't start with `open`. If the
+    /// group is never closed, the rest of the input is consumed and `None` is returned.
+    pub fn consume_balanced(&mut self, open: char, close: char) -> Option<Span<'a>> {
+        debug_assert_ne!(open, close, "use consume_quoted for matching delimiters");
+        if !self.peek_is(open) {
+            return None;
+        }
+        self.consume();
+        let start = self.idx;
+        let mut depth = 1;
+        loop {
+            match self.consume()? {
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let end = self.idx - close.len_utf8();
+                        return Some(Span::new(&self.source[start..end], start));
+                    }
+                }
+                _ => {}
+            }
+        }
     }
 
     pub fn try_consume(&mut self, s: &str) -> bool {
@@ -118,46 +238,122 @@ impl<'a> Harpoon<'a> {
         }
     }
 
+    /// Checks if the next chars are equal to `s`. Input past the end of `s` is not considered, so
+    /// this is true whenever the unconsumed input starts with `s`.
     pub fn peek_equals(&mut self, s: &str) -> bool {
         !self
-            .peek_n(s.len())
+            .peek_n(s.chars().count())
             .zip_longest(s.chars())
             .any(|either| !matches!(either, EitherOrBoth::Both(a, b) if a == b))
     }
 
+    /// Checks if the unconsumed input starts with `prefix`. Unlike [`Harpoon::peek_equals`], this
+    /// compares the source directly and doesn't fill the peek cache, so it's cheaper for long
+    /// prefixes.
+    #[inline]
+    pub fn peek_starts_with(&self, prefix: &str) -> bool {
+        self.remaining().starts_with(prefix)
+    }
+
+    /// Consumes the rest of the current line, including its line terminator (`\n` or `\r\n`).
+    /// The returned span does not contain the terminator.
+    pub fn consume_line(&mut self) -> Span<'a> {
+        let line = self.consume_until('\n');
+        self.consume();
+        match line.text().strip_suffix('\r') {
+            Some(text) => Span::new(text, line.start()),
+            None => line,
+        }
+    }
+
     pub fn consume_n(&mut self, n: usize) {
         for _ in 0..n {
             self.consume();
         }
     }
 
+    #[inline]
     pub fn offset(&self) -> usize {
         self.idx
     }
 
+    /// The line and column of the next char to be consumed, both starting at 1. Columns are
+    /// counted in chars, and lines are ended by `\n`.
+    #[inline]
+    pub fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
+
     pub fn harpoon<F>(&mut self, mut f: F) -> Span<'a>
     where
         F: FnMut(&mut Harpoon),
     {
         let start = self.idx;
         f(self);
-        let t = &self.source[start..self.source.len() - self.source[self.idx..].len()];
+        let t = &self.source[start..self.idx];
         Span::new(t, start)
     }
 
+    /// Marks the current position, so it can be returned to with [`Harpoon::rewind`].
+    #[inline]
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            idx: self.idx,
+            current: self.current,
+            popped: self.peek_buf.popped(),
+            line: self.line,
+            col: self.col,
+        }
+    }
+
+    /// Goes back to `checkpoint`, so the input consumed since it was made can be consumed again.
+    /// Unlike restoring a clone, this is free if the chars consumed since are still in the peek
+    /// buffer, which they usually are after backing out of a short lookahead.
+    ///
+    /// The checkpoint must have been made by this harpoon.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        debug_assert!(self.source.is_char_boundary(checkpoint.idx));
+        self.current = checkpoint.current;
+        self.line = checkpoint.line;
+        self.col = checkpoint.col;
+        if let Some(consumed) = self.peek_buf.popped().checked_sub(checkpoint.popped) {
+            let restored = self
+                .peek_buf
+                .recently_popped(consumed)
+                .map(|chars| chars.map(char::len_utf8).sum::<usize>());
+            if restored.is_some_and(|len| self.idx - len == checkpoint.idx) {
+                self.peek_buf.unpop(consumed);
+                self.idx = checkpoint.idx;
+                return;
+            }
+        }
+
+        self.idx = checkpoint.idx;
+        self.chars = self.source[self.idx..].chars();
+        self.peek_buf = RingBuf::new();
+    }
+
     pub fn source(&self) -> &'a str {
         self.source
     }
+
+    /// The input that has not been consumed yet.
+    #[inline]
+    pub fn remaining(&self) -> &'a str {
+        &self.source[self.idx..]
+    }
 }
 
 impl Clone for Harpoon<'_> {
     fn clone(&self) -> Self {
         Self {
             source: self.source(),
-            chars: self.source()[self.offset()..].chars(),
-            peek_buf: VecDeque::new(),
+            chars: self.remaining().chars(),
+            peek_buf: RingBuf::new(),
             current: self.current(),
             idx: self.offset(),
+            line: self.line,
+            col: self.col,
         }
     }
 }
@@ -177,6 +373,19 @@ mod tests {
         assert_eq!(&['2', '3', '4'], &harpoon.peek_n_const::<4>()[..]);
     }
 
+    #[test]
+    fn can_peek_past_peek_capacity() {
+        let input = "abcdefghijklmnopqrstuvwxyz";
+        let mut harpoon = Harpoon::new(input);
+        harpoon.consume();
+        assert_eq!(&input[1..], harpoon.peek_n(100).collect::<String>());
+        assert_eq!(&input[1..21], harpoon.peek_n(20).collect::<String>());
+        assert!(harpoon.peek_equals(&input[1..]));
+        harpoon.consume_n(20);
+        assert_eq!(Some('v'), harpoon.peek());
+        assert_eq!(&['v', 'w', 'x', 'y', 'z'], &harpoon.peek_n_const::<8>()[..]);
+    }
+
     #[test]
     fn harpoon_is_right_exclusive() {
         let mut harpoon = Harpoon::new("1234");
@@ -202,4 +411,203 @@ mod tests {
         assert!(!harpoon.try_consume("33"));
         assert_eq!(Some('1'), harpoon.consume());
     }
+
+    const CORPUS: &[&str] = &[
+        "",
+        "hello world",
+        "héllo wörld",
+        "日本語のテキスト",
+        "a\u{1F600}b\u{1F600}c",
+        "mixed ascii ünd 多字节 \u{1F980}",
+        "line one\nline two\r\nline three",
+    ];
+
+    fn boundaries(s: &str) -> impl Iterator<Item = usize> + '_ {
+        s.char_indices().map(|(i, _)| i).chain([s.len()])
+    }
+
+    #[test]
+    fn peek_equals_matches_str_starts_with() {
+        for input in CORPUS {
+            for start in boundaries(input) {
+                for needle in CORPUS.iter().chain(&["ö", "\u{1F600}b", "本語"]) {
+                    let mut harpoon = Harpoon::new(input);
+                    harpoon.consume_n(input[..start].chars().count());
+                    let expected = input[start..].starts_with(needle);
+                    assert_eq!(
+                        expected,
+                        harpoon.peek_equals(needle),
+                        "{input:?} {needle:?}"
+                    );
+                    assert_eq!(expected, harpoon.peek_starts_with(needle));
+                }
+                for end in boundaries(input).filter(|&end| end >= start) {
+                    let needle = &input[start..end];
+                    let mut harpoon = Harpoon::new(input);
+                    harpoon.consume_n(input[..start].chars().count());
+                    assert!(harpoon.peek_equals(needle), "{input:?} {needle:?}");
+                    assert!(harpoon.try_consume(needle));
+                    assert_eq!(end, harpoon.offset());
+                    assert_eq!(&input[end..], harpoon.remaining());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rewind_restores_checkpoint() {
+        let mut harpoon = Harpoon::new("ab你好cd");
+        harpoon.consume();
+        let checkpoint = harpoon.checkpoint();
+        assert_eq!(1, checkpoint.offset());
+        harpoon.consume_n(3);
+        assert!(harpoon.peek_equals("cd"));
+        harpoon.rewind(checkpoint);
+        assert_eq!(1, harpoon.offset());
+        assert_eq!(Some('a'), harpoon.current());
+        assert_eq!("b你好cd", harpoon.remaining());
+        assert_eq!("b你好cd", harpoon.peek_n(10).collect::<String>());
+        let rest = harpoon.harpoon(|h| while h.consume().is_some() {});
+        assert_eq!("b你好cd", rest.text());
+    }
+
+    #[test]
+    fn can_rewind_past_peek_capacity() {
+        let input = "x".repeat(PEEK_CAPACITY * 3);
+        let mut harpoon = Harpoon::new(&input);
+        let start = harpoon.checkpoint();
+        harpoon.consume_n(PEEK_CAPACITY * 2);
+        harpoon.peek_n(PEEK_CAPACITY).for_each(drop);
+        harpoon.rewind(start);
+        assert_eq!(0, harpoon.offset());
+        assert_eq!(None, harpoon.current());
+        assert_eq!(input.len(), harpoon.peek_n(usize::MAX).count());
+
+        // Rewinding forward works too
+        let end = {
+            harpoon.consume_n(input.len());
+            harpoon.checkpoint()
+        };
+        harpoon.rewind(start);
+        harpoon.rewind(end);
+        assert_eq!(None, harpoon.peek());
+        assert_eq!("", harpoon.remaining());
+    }
+
+    #[test]
+    fn consume_while_returns_consumed_span() {
+        let mut harpoon = Harpoon::new("abc123 def");
+        harpoon.consume();
+        let span = harpoon.consume_while(|c| c.is_alphanumeric());
+        assert_eq!("bc123", span.text());
+        assert_eq!(1, span.start());
+        assert_eq!(" ", harpoon.consume_until('d').text());
+        assert!(harpoon.consume_until('d').is_empty());
+    }
+
+    #[test]
+    fn consumes_idents_and_decimals() {
+        let mut harpoon = Harpoon::new("_on-click2 9lives 3.14.15 7.x");
+        assert_eq!("_on-click2", harpoon.consume_ident().text());
+        harpoon.consume();
+        assert!(harpoon.consume_ident().is_empty());
+        assert_eq!("9", harpoon.consume_decimal().text());
+        assert_eq!("lives", harpoon.consume_ident().text());
+        harpoon.consume();
+        let decimal = harpoon.consume_decimal();
+        assert_eq!("3.14", decimal.text());
+        assert_eq!(18, decimal.start());
+        assert!(harpoon.consume_decimal().is_empty());
+        assert_eq!(Some('.'), harpoon.consume());
+        assert_eq!("15", harpoon.consume_decimal().text());
+        harpoon.consume();
+        assert_eq!("7", harpoon.consume_decimal().text());
+        assert_eq!(".x", harpoon.remaining());
+    }
+
+    #[test]
+    fn consume_quoted_honors_escapes() {
+        let mut harpoon = Harpoon::new(r#""a \"b\" \\" rest"#);
+        let contents = harpoon.consume_quoted('"', '\\').unwrap();
+        assert_eq!(r#"a \"b\" \\"#, contents.text());
+        assert_eq!(1, contents.start());
+        assert_eq!(" rest", harpoon.remaining());
+
+        assert_eq!(None, harpoon.consume_quoted('"', '\\'));
+        assert_eq!(" rest", harpoon.remaining());
+
+        let mut harpoon = Harpoon::new(r#"'never closed\'"#);
+        assert_eq!(None, harpoon.consume_quoted('\'', '\\'));
+        assert_eq!("", harpoon.remaining());
+    }
+
+    #[test]
+    fn consume_balanced_matches_nested_groups() {
+        let mut harpoon = Harpoon::new("{ {a: {}}.a }}");
+        let contents = harpoon.consume_balanced('{', '}').unwrap();
+        assert_eq!(" {a: {}}.a ", contents.text());
+        assert_eq!(1, contents.start());
+        assert_eq!("}", harpoon.remaining());
+        assert_eq!(None, harpoon.consume_balanced('{', '}'));
+        assert_eq!("}", harpoon.remaining());
+
+        let mut harpoon = Harpoon::new("(好(b)");
+        assert_eq!(None, harpoon.consume_balanced('(', ')'));
+        assert_eq!("", harpoon.remaining());
+
+        let mut harpoon = Harpoon::new("()");
+        assert_eq!("", harpoon.consume_balanced('(', ')').unwrap().text());
+    }
+
+    #[test]
+    fn tracks_line_and_column() {
+        let input = "ab\n你好\r\n\nxyz";
+        let mut harpoon = Harpoon::new(input);
+        let mut second_line = None;
+        // Compare against counting from the start of the input at every offset
+        loop {
+            let before = &input[..harpoon.offset()];
+            let line = before.matches('\n').count() + 1;
+            let col = before.rsplit('\n').next().unwrap().chars().count() + 1;
+            assert_eq!((line, col), harpoon.position());
+            if harpoon.position() == (2, 1) {
+                second_line = Some(harpoon.checkpoint());
+            }
+            if harpoon.consume().is_none() {
+                break;
+            }
+        }
+        assert_eq!((4, 4), harpoon.position());
+
+        harpoon.rewind(second_line.unwrap());
+        assert_eq!((2, 1), harpoon.position());
+        harpoon.consume_n(2);
+        assert_eq!((2, 3), harpoon.position());
+    }
+
+    #[test]
+    fn remaining_matches_offset() {
+        for input in CORPUS {
+            let mut harpoon = Harpoon::new(input);
+            for start in boundaries(input) {
+                assert_eq!(&input[start..], harpoon.remaining());
+                harpoon.peek_n(3).for_each(drop);
+                harpoon.consume();
+            }
+        }
+    }
+
+    #[test]
+    fn consume_line_matches_str_lines() {
+        for input in CORPUS {
+            let mut harpoon = Harpoon::new(input);
+            let mut lines = vec![];
+            while !harpoon.remaining().is_empty() {
+                let line = harpoon.consume_line();
+                assert_eq!(&input[line.start()..line.end()], line.text());
+                lines.push(line.text());
+            }
+            assert_eq!(input.lines().collect::<Vec<_>>(), lines);
+        }
+    }
 }
@@ -0,0 +1,125 @@
+/// A fixed-capacity FIFO queue of chars that never allocates.
+#[derive(Debug, Clone, Copy)]
+pub struct RingBuf<const CAP: usize> {
+    buf: [char; CAP],
+    head: usize,
+    len: usize,
+    /// How many chars have ever been popped.
+    popped: usize,
+}
+
+impl<const CAP: usize> RingBuf<CAP> {
+    pub fn new() -> Self {
+        Self {
+            buf: ['\0'; CAP],
+            head: 0,
+            len: 0,
+            popped: 0,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len == CAP
+    }
+
+    #[inline]
+    pub fn front(&self) -> Option<char> {
+        (self.len > 0).then(|| self.buf[self.head])
+    }
+
+    /// Pushes `c` to the back of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is full.
+    #[inline]
+    pub fn push_back(&mut self, c: char) {
+        assert!(!self.is_full(), "ring buffer should not be full");
+        self.buf[(self.head + self.len) % CAP] = c;
+        self.len += 1;
+    }
+
+    #[inline]
+    pub fn pop_front(&mut self) -> Option<char> {
+        let front = self.front()?;
+        self.head = (self.head + 1) % CAP;
+        self.len -= 1;
+        self.popped += 1;
+        Some(front)
+    }
+
+    #[inline]
+    pub fn popped(&self) -> usize {
+        self.popped
+    }
+
+    /// The last `n` chars that were popped, oldest first, if they haven't been overwritten by
+    /// later pushes.
+    #[inline]
+    pub fn recently_popped(&self, n: usize) -> Option<impl Iterator<Item = char> + '_> {
+        (n <= self.popped && n + self.len <= CAP)
+            .then(|| (0..n).map(move |i| self.buf[(self.head + CAP - n + i) % CAP]))
+    }
+
+    /// Puts the last `n` popped chars back at the front of the buffer. They must still be there,
+    /// according to [`RingBuf::recently_popped`].
+    #[inline]
+    pub fn unpop(&mut self, n: usize) {
+        debug_assert!(self.recently_popped(n).is_some());
+        self.head = (self.head + CAP - n) % CAP;
+        self.len += n;
+        self.popped -= n;
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = char> + '_ {
+        (0..self.len).map(|i| self.buf[(self.head + i) % CAP])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_around_capacity() {
+        let mut ring = RingBuf::<3>::new();
+        ring.push_back('a');
+        ring.push_back('b');
+        assert_eq!(Some('a'), ring.pop_front());
+        ring.push_back('c');
+        ring.push_back('d');
+        assert!(ring.is_full());
+        assert_eq!(vec!['b', 'c', 'd'], ring.iter().collect::<Vec<_>>());
+        assert_eq!(Some('b'), ring.pop_front());
+        assert_eq!(Some('c'), ring.pop_front());
+        assert_eq!(Some('d'), ring.pop_front());
+        assert_eq!(None, ring.pop_front());
+    }
+
+    #[test]
+    fn can_unpop_until_overwritten() {
+        let mut ring = RingBuf::<3>::new();
+        ring.push_back('a');
+        ring.push_back('b');
+        ring.pop_front();
+        ring.pop_front();
+        ring.push_back('c');
+        assert_eq!(
+            Some(vec!['a', 'b']),
+            ring.recently_popped(2).map(Iterator::collect)
+        );
+        ring.unpop(1);
+        assert_eq!(vec!['b', 'c'], ring.iter().collect::<Vec<_>>());
+        assert_eq!(1, ring.popped());
+
+        ring.push_back('d');
+        assert!(ring.recently_popped(1).is_none());
+    }
+}
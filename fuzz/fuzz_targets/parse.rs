@@ -0,0 +1,23 @@
+//! Parses arbitrary UTF-8. The parser should report an error for anything it doesn't understand,
+//! and never panic.
+#![no_main]
+
+use decorous_errors::{DynErrStream, Source};
+use decorous_frontend::{Ctx, Parser};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|src: &str| {
+    let errs = DynErrStream::new(
+        Box::new(std::io::sink()),
+        Source {
+            name: "FUZZ".to_owned(),
+            src,
+        },
+    );
+    let _ = Parser::new(src)
+        .with_ctx(Ctx {
+            errs,
+            ..Default::default()
+        })
+        .parse();
+});
@@ -0,0 +1,155 @@
+//! Parses components that are put together from pieces of the grammar, so that most inputs get
+//! past the first few tokens and reach the deeper parts of the parser.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use decorous_errors::{DynErrStream, Source};
+use decorous_frontend::{Ctx, Parser};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, Arbitrary)]
+enum Piece {
+    Elem(Ident),
+    ElemEnd(Ident),
+    BareElemEnd,
+    DynamicTag,
+    Lbracket,
+    Rbracket,
+    Attr(Ident, String),
+    JsAttr(Ident),
+    EventHandler(Ident),
+    Binding(Ident),
+    Mustache(String),
+    If,
+    Else,
+    ElseIf,
+    EndIf,
+    For(Ident),
+    EndFor,
+    Use(String),
+    Raw,
+    EndRaw,
+    Html,
+    Head,
+    EndHead,
+    Static,
+    EndStatic,
+    Cfg(Ident),
+    EndCfg,
+    CodeBlock(Lang, bool),
+    CodeBlockEnd,
+    Comment(String),
+    Escape(char),
+    CharacterReference(String),
+    Text(String),
+}
+
+#[derive(Debug, Arbitrary)]
+enum Ident {
+    Div,
+    P,
+    Span,
+    X,
+    Y,
+    Other(String),
+}
+
+#[derive(Debug, Arbitrary)]
+enum Lang {
+    Js,
+    Ts,
+    Css,
+    Rust,
+    Other(String),
+}
+
+impl Ident {
+    fn as_str(&self) -> &str {
+        match self {
+            Ident::Div => "div",
+            Ident::P => "p",
+            Ident::Span => "span",
+            Ident::X => "x",
+            Ident::Y => "y",
+            Ident::Other(s) => s,
+        }
+    }
+}
+
+impl Piece {
+    fn write(&self, src: &mut String) {
+        match self {
+            Piece::Elem(tag) => src.push_str(&format!("#{}", tag.as_str())),
+            Piece::ElemEnd(tag) => src.push_str(&format!("/{}", tag.as_str())),
+            Piece::BareElemEnd => src.push('/'),
+            Piece::DynamicTag => src.push_str("#{`h${x}`}"),
+            Piece::Lbracket => src.push('['),
+            Piece::Rbracket => src.push(']'),
+            Piece::Attr(key, value) => src.push_str(&format!("{}={value:?} ", key.as_str())),
+            Piece::JsAttr(key) => src.push_str(&format!("{}={{x}} ", key.as_str())),
+            Piece::EventHandler(event) => {
+                src.push_str(&format!("@{}={{() => x += 1}} ", event.as_str()))
+            }
+            Piece::Binding(var) => src.push_str(&format!("bind:{}", var.as_str())),
+            Piece::Mustache(js) => src.push_str(&format!("{{{js}}}")),
+            Piece::If => src.push_str("{#if x}"),
+            Piece::Else => src.push_str("{:else}"),
+            Piece::ElseIf => src.push_str("{:else if y}"),
+            Piece::EndIf => src.push_str("{/if}"),
+            Piece::For(binding) => src.push_str(&format!("{{#for {} in y}}", binding.as_str())),
+            Piece::EndFor => src.push_str("{/for}"),
+            Piece::Use(path) => src.push_str(&format!("{{#use {path:?}}}")),
+            Piece::Raw => src.push_str("{#raw}"),
+            Piece::EndRaw => src.push_str("{/raw}"),
+            Piece::Html => src.push_str("{#html x}"),
+            Piece::Head => src.push_str("{#head}"),
+            Piece::EndHead => src.push_str("{/head}"),
+            Piece::Static => src.push_str("{#static}"),
+            Piece::EndStatic => src.push_str("{/static}"),
+            Piece::Cfg(feature) => {
+                src.push_str(&format!("{{#cfg feature=\"{}\"}}", feature.as_str()))
+            }
+            Piece::EndCfg => src.push_str("{/cfg}"),
+            Piece::CodeBlock(lang, comptime) => {
+                let lang = match lang {
+                    Lang::Js => "js",
+                    Lang::Ts => "ts",
+                    Lang::Css => "css",
+                    Lang::Rust => "rust",
+                    Lang::Other(lang) => lang,
+                };
+                src.push_str(&format!(
+                    "---{lang}{}\n",
+                    if *comptime { ":static" } else { "" }
+                ));
+            }
+            Piece::CodeBlockEnd => src.push_str("\n---\n"),
+            Piece::Comment(text) => src.push_str(&format!("<!--{text}-->")),
+            Piece::Escape(c) => src.push_str(&format!("\\{c}")),
+            Piece::CharacterReference(name) => src.push_str(&format!("&{name};")),
+            Piece::Text(text) => src.push_str(text),
+        }
+        src.push(' ');
+    }
+}
+
+fuzz_target!(|pieces: Vec<Piece>| {
+    let mut src = String::new();
+    for piece in &pieces {
+        piece.write(&mut src);
+    }
+
+    let errs = DynErrStream::new(
+        Box::new(std::io::sink()),
+        Source {
+            name: "FUZZ".to_owned(),
+            src: &src,
+        },
+    );
+    let _ = Parser::new(&src)
+        .with_ctx(Ctx {
+            errs,
+            ..Default::default()
+        })
+        .parse();
+});
@@ -0,0 +1,318 @@
+//! The decorous compiler as a library, for tools that build components themselves, like bundler
+//! plugins or web servers.
+//!
+//! [`compile`] turns the source of a component into JavaScript, CSS and HTML without touching the
+//! filesystem:
+//!
+//! ```
+//! let src = "#p Hello, {name}! /p\n---js let name = \"world\"; ---";
+//! let out = decorous::compile(src, decorous::CompileOptions::default());
+//! assert!(out.is_ok());
+//! assert!(out.js.contains("world"));
+//! ```
+
+use std::io::{self, Write};
+
+pub use decorous_backend::{
+    css_render::CssOptions, dom_render::CsrOptions, prerender::PrerenderOptions, HtmlTemplate,
+    NullCompiler, NullResolver, OutputFormat, TemplateError, UseResolver, WasmCompiler,
+};
+use decorous_backend::{
+    dom_render::CsrRenderer, prerender::Prerenderer, AsciiOnly, Ctx as RenderCtx, DynRenderBackend,
+    HtmlInfo, Minified, RenderBackend, RenderOut,
+};
+pub use decorous_errors::{Diagnostic, Fix, LineCol, Severity};
+use decorous_errors::{DiagnosticBuilder, DynErrStream, Source};
+use decorous_frontend::{errors::ParseErrorType, Component, Ctx as ParseCtx, Parser};
+pub use decorous_frontend::{
+    lint::LintLevels, CodeExecutor, ComponentIdScheme, EnvVars, HashedIds, Messages, NullEnv,
+    NullExecutor, NullMessages, NullPreproc, Preprocessor,
+};
+
+/// How the component is rendered.
+#[derive(Debug)]
+pub enum RenderMethod {
+    /// Rendered on the client, by JavaScript that builds the DOM.
+    Csr(CsrOptions),
+    /// Rendered to HTML ahead of time, with JavaScript that only keeps it up to date.
+    Prerender(PrerenderOptions),
+}
+
+impl Default for RenderMethod {
+    fn default() -> Self {
+        Self::Csr(CsrOptions::default())
+    }
+}
+
+pub struct CompileOptions<'a> {
+    /// The name of the component. Diagnostics are reported in it, and the component is mounted on
+    /// the element with it as its id.
+    pub name: &'a str,
+    pub render_method: RenderMethod,
+    /// Generate an `index.html` that loads the outputs, when they are saved as `{index_html}.js`
+    /// and `{index_html}.css`.
+    pub index_html: Option<String>,
+    /// The page that the `index.html` is rendered into, instead of the built-in one.
+    pub html_template: Option<HtmlTemplate>,
+    /// Minify the generated JavaScript.
+    pub minify: bool,
+    /// Escape every non-ASCII character in the outputs.
+    pub ascii_only: bool,
+    pub lints: LintLevels,
+    pub preprocessor: &'a dyn Preprocessor,
+    pub executor: &'a dyn CodeExecutor,
+    pub wasm_compiler: &'a dyn WasmCompiler,
+    pub use_resolver: &'a dyn UseResolver,
+    pub component_ids: &'a dyn ComponentIdScheme,
+    /// The features that `{#cfg}` blocks are kept for.
+    pub features: &'a [String],
+    /// The values that `$env.NAME` is replaced with.
+    pub env: &'a dyn EnvVars,
+    /// The messages that `{@t "key"}` expressions are replaced with.
+    pub messages: &'a dyn Messages,
+    /// Remove the `{#use}` declarations whose component is never rendered.
+    pub drop_unused_uses: bool,
+}
+
+impl Default for CompileOptions<'_> {
+    fn default() -> Self {
+        Self {
+            name: "component",
+            render_method: RenderMethod::default(),
+            index_html: None,
+            html_template: None,
+            minify: false,
+            ascii_only: false,
+            lints: LintLevels::default(),
+            preprocessor: &NullPreproc,
+            executor: &NullExecutor,
+            wasm_compiler: &NullCompiler,
+            use_resolver: &NullResolver,
+            component_ids: &HashedIds,
+            features: &[],
+            env: &NullEnv,
+            messages: &NullMessages,
+            drop_unused_uses: false,
+        }
+    }
+}
+
+/// The outputs of a compilation.
+#[derive(Debug, Default)]
+pub struct CompileOutput {
+    pub js: String,
+    pub css: Option<String>,
+    pub html: Option<String>,
+    /// Every error and warning about the component. If there are any errors, the outputs are
+    /// empty.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CompileOutput {
+    /// Returns `true` if the component compiled without errors.
+    pub fn is_ok(&self) -> bool {
+        !self
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+/// Compiles the component in `src`. Nothing is written to the filesystem or to stderr: the
+/// outputs and diagnostics are all returned.
+pub fn compile(src: &str, options: CompileOptions) -> CompileOutput {
+    let errs = DynErrStream::new(
+        Box::new(io::sink()),
+        Source {
+            name: options.name.to_owned(),
+            src,
+        },
+    );
+    let mut out = Buffers::default();
+    if let Err(err) = compile_to(src, options, &errs, &mut out) {
+        // Errors that were already reported don't need to be reported again
+        if let Some(msg) = err {
+            errs.emit(DiagnosticBuilder::new(msg, 0).build());
+        }
+        out = Buffers::default();
+    }
+
+    CompileOutput {
+        js: String::from_utf8(out.js).expect("rendered JavaScript should be valid utf-8"),
+        css: out
+            .css
+            .map(|css| String::from_utf8(css).expect("rendered CSS should be valid utf-8")),
+        html: out
+            .html
+            .map(|html| String::from_utf8(html).expect("rendered HTML should be valid utf-8")),
+        diagnostics: errs.take_diagnostics(),
+    }
+}
+
+/// Compiles the component in `src` to `out`. Errors that weren't emitted to `errs` are returned
+/// with their message.
+fn compile_to(
+    src: &str,
+    options: CompileOptions,
+    errs: &DynErrStream,
+    out: &mut Buffers,
+) -> Result<(), Option<String>> {
+    let ctx = ParseCtx {
+        preprocessor: options.preprocessor,
+        executor: options.executor,
+        errs: errs.clone(),
+        lints: options.lints,
+        component_ids: options.component_ids,
+        features: options.features,
+        env: options.env,
+        messages: options.messages,
+        drop_unused_uses: options.drop_unused_uses,
+    };
+    let ast = match Parser::new(src).with_ctx(ctx.clone()).parse() {
+        Ok(ast) => ast,
+        Err(err) => {
+            // Every error has already been emitted if there was more than one
+            if err.err_type() != &ParseErrorType::DidError {
+                errs.emit(err.into());
+            }
+            return Err(None);
+        }
+    };
+    let mut component = Component::new(ast, ctx);
+    component
+        .run_passes()
+        .map_err(|err| Some(err.to_string()))?;
+
+    let render_ctx = RenderCtx {
+        name: options.name,
+        index_html: options.index_html.map(|basename| HtmlInfo {
+            basename,
+            template: options.html_template,
+        }),
+        wasm_compiler: options.wasm_compiler,
+        use_resolver: options.use_resolver,
+        errs: errs.clone(),
+        linker: None,
+    };
+    let backend: Box<dyn DynRenderBackend> = match options.render_method {
+        RenderMethod::Csr(opts) => {
+            let mut renderer = CsrRenderer::new();
+            renderer.with_options(opts);
+            Box::new(renderer)
+        }
+        RenderMethod::Prerender(opts) => {
+            let mut renderer = Prerenderer::new();
+            renderer.with_options(opts);
+            Box::new(renderer)
+        }
+    };
+
+    let render = |out: &mut dyn RenderOut| {
+        if options.minify {
+            let mut minified = Minified::new(out);
+            backend.render_dyn(&component, &mut minified, &render_ctx)?;
+            minified.finish()?;
+        } else {
+            backend.render_dyn(&component, out, &render_ctx)?;
+        }
+        Ok::<_, decorous_backend::RenderError>(())
+    };
+    let rendered = if options.ascii_only {
+        render(&mut AsciiOnly::new(&mut *out))
+    } else {
+        render(out)
+    };
+    rendered.map_err(|err| Some(err.to_string()))
+}
+
+/// The outputs of a compilation, kept in memory.
+#[derive(Debug, Default)]
+struct Buffers {
+    js: Vec<u8>,
+    css: Option<Vec<u8>>,
+    html: Option<Vec<u8>>,
+}
+
+impl RenderOut for Buffers {
+    fn write_js(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.js.write_all(buf)
+    }
+
+    fn write_css(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.css.get_or_insert_with(Vec::new).write_all(buf)
+    }
+
+    fn write_html(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.html.get_or_insert_with(Vec::new).write_all(buf)
+    }
+
+    fn js_handle(&mut self) -> &mut dyn io::Write {
+        &mut self.js
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_without_touching_the_filesystem() {
+        let out = compile(
+            "#p Hello! /p\n---css p { color: red; } ---",
+            CompileOptions {
+                name: "hello",
+                index_html: Some("out".to_owned()),
+                ..Default::default()
+            },
+        );
+        assert!(out.is_ok(), "{:?}", out.diagnostics);
+        assert!(out.js.contains("Hello!"));
+        assert!(out.css.is_some_and(|css| css.contains("color: red")));
+        let html = out.html.expect("index.html should be generated");
+        assert!(html.contains("src=\"out.js\""));
+        assert!(html.contains("<div id=\"hello\"></div>"));
+    }
+
+    #[test]
+    fn prerenders_to_html() {
+        let out = compile(
+            "#p Hello! /p",
+            CompileOptions {
+                render_method: RenderMethod::Prerender(PrerenderOptions::default()),
+                ..Default::default()
+            },
+        );
+        assert!(out.is_ok(), "{:?}", out.diagnostics);
+        assert!(out.html.is_some_and(|html| html.contains("Hello!")));
+        assert_eq!(None, out.css);
+    }
+
+    #[test]
+    fn returns_diagnostics_instead_of_outputs_on_errors() {
+        let out = compile(
+            "#p Hello! /p\n#div[class=\"a\" /div",
+            CompileOptions::default(),
+        );
+        assert!(!out.is_ok());
+        assert!(out.js.is_empty());
+        assert_eq!(None, out.html);
+        let diagnostic = &out.diagnostics[0];
+        assert_eq!(Severity::Error, diagnostic.severity);
+        assert_eq!(Some(2), diagnostic.position.map(|pos| pos.line));
+    }
+
+    #[test]
+    fn reports_warnings_with_outputs() {
+        let out = compile(
+            "#p Hello! /p\n---js let x = 1; ---",
+            CompileOptions::default(),
+        );
+        assert!(out.is_ok());
+        assert!(!out.js.is_empty());
+        assert!(out
+            .diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.severity == Severity::Warning));
+        assert!(!out.diagnostics.is_empty());
+    }
+}
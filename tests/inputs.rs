@@ -10,3 +10,5 @@ pub const SCSS: &str = include_str!("./inputs/scss.decor");
 pub const SCSS_AND_TS: &str = include_str!("./inputs/scss_and_ts.decor");
 pub const GO: &str = include_str!("./inputs/go.decor");
 pub const STATIC: &str = include_str!("./inputs/static.decor");
+pub const STATIC_DATA: &str = include_str!("./inputs/static_data.decor");
+pub const UNICODE: &str = include_str!("./inputs/unicode.decor");
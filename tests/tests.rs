@@ -1,7 +1,7 @@
 mod inputs;
 mod macros;
 
-use std::fs;
+use std::{env, fs};
 
 use assert_cmd::Command;
 use tempdir::TempDir;
@@ -58,6 +58,20 @@ decor_test!(
     }
 );
 
+decor_test!(
+    can_hash_output_names,
+    CSS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        let mut config =
+            File::create(dir.path().join("decor.toml")).expect("unable to create config file");
+        write!(config, "output.hash = true").expect("unable to write to config file");
+
+        cmd.arg("--html");
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
 decor_test!(
     can_change_out_file_stem,
     NO_JS,
@@ -98,11 +112,11 @@ decor_test!(
 );
 
 decor_test!(
-    wasm_for_rust_generates_a_new_project,
+    wasm_for_rust_only_generates_the_outputs,
     WASM,
     |dir: &mut TempDir, mut cmd: Command| {
         cmd.assert().success();
-        assert_all!(dir.path(), ignore: [".git", "target", "Cargo.lock", ".gitignore", "__tmp_bg.wasm", "package.json"]);
+        assert_all!(dir.path(), ignore: ["__tmp_bg.wasm"]);
     }
 );
 
@@ -181,6 +195,107 @@ decor_test!(
     }
 );
 
+decor_test!(
+    can_build_with_profiles,
+    CSS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        let mut config =
+            File::create(dir.path().join("decor.toml")).expect("unable to create config file");
+        write!(config, "[profile.ship]\nminify_css = true\nhtml = true")
+            .expect("unable to write to config file");
+
+        cmd.args(["--profile", "ship"]);
+        cmd.assert().success().stderr("");
+        assert_all!(dir.path());
+
+        let mut missing = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        missing
+            .current_dir(dir.path())
+            .args(["build", "input.decor", "--profile", "nope"]);
+        missing.assert().failure();
+
+        let mut release = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        release.current_dir(dir.path()).args([
+            "build",
+            "input.decor",
+            "--profile",
+            "release",
+            "--no-cache",
+        ]);
+        release.assert().success().stderr("");
+    }
+);
+
+decor_test!(
+    can_include_global_stylesheets,
+    CSS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(dir.path().join("reset.css"), "* {\n  margin: 0;\n}\n")
+            .expect("unable to write stylesheet");
+        fs::write(
+            dir.path().join("fonts.css"),
+            "body {\n  font-family: serif;\n}\n",
+        )
+        .expect("unable to write stylesheet");
+        fs::write(
+            dir.path().join("decor.toml"),
+            "[css]\nglobal = [\"reset.css\"]",
+        )
+        .expect("unable to write config file");
+
+        cmd.args(["--html", "--global-css", "fonts.css"]);
+        cmd.assert().success().stderr("");
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_mount_many_components_on_one_page,
+    JS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(dir.path().join("other.decor"), CSS).expect("unable to write component");
+
+        cmd.args(["other.decor", "--html"]);
+        cmd.assert().success().stderr("");
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_render_into_html_templates,
+    CSS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("page.html"),
+            "<html>\n<head>\n<title>{{title}}</title>\n{{css}}\n</head>\n<body>\n{{html}}\n{{script}}\n</body>\n</html>\n",
+        )
+        .expect("unable to write template");
+        fs::write(
+            dir.path().join("decor.toml"),
+            "[html]\ntemplate = \"page.html\"",
+        )
+        .expect("unable to write config file");
+
+        cmd.arg("--html");
+        cmd.assert().success().stderr("");
+        assert_all!(dir.path());
+
+        fs::write(dir.path().join("bad.html"), "<head></head>{{html}}{{body}}")
+            .expect("unable to write template");
+        let mut invalid = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        invalid.current_dir(dir.path()).args([
+            "build",
+            "input.decor",
+            "--html",
+            "--html-template",
+            "bad.html",
+        ]);
+        let assertion = invalid.assert().failure();
+        let stderr = String::from_utf8_lossy(assertion.get_output().stderr.as_slice());
+        assert!(stderr.contains("unknown placeholder {{body}}"), "{stderr}");
+    }
+);
+
 decor_test!(
     can_merge_configs,
     SCSS_AND_TS,
@@ -205,6 +320,381 @@ decor_test!(can_modularize, JS, |dir: &mut TempDir, mut cmd: Command| {
     assert_all!(dir.path());
 });
 
+decor_test!(
+    can_bundle_used_components,
+    "{#use \"./hello.decor\"} #hello /hello",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(dir.path().join("hello.decor"), "#p Hello /p").unwrap();
+        cmd.args(["--render-method", "csr", "--bundle"]);
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    bundled_components_have_their_imports_hoisted,
+    "{#use \"./hello.decor\"} #hello /hello",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("hello.decor"),
+            "---js\nimport { greet } from \"./greet.js\";\n---\n#p {greet()} /p",
+        )
+        .unwrap();
+        cmd.args(["--render-method", "csr", "--bundle"]);
+        cmd.assert().success();
+        let js = fs::read_to_string(dir.path().join("out.js")).unwrap();
+        assert!(
+            js.starts_with("import { greet } from \"./greet.js\";\n"),
+            "{js}"
+        );
+        assert_eq!(1, js.matches("import ").count(), "{js}");
+        assert!(js.contains("return initialize;"), "{js}");
+    }
+);
+
+decor_test!(
+    can_build_commonjs_modules,
+    "{#use \"./hello.decor\"} #hello /hello",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(dir.path().join("hello.decor"), "#p Hello /p").unwrap();
+        cmd.args(["--modularize", "--format", "cjs"]);
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_inline_the_runtime,
+    "{#use \"./hello.decor\"} #hello /hello",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(dir.path().join("hello.decor"), "#p Hello /p").unwrap();
+        cmd.args(["--render-method", "csr", "--inline-runtime"]);
+        cmd.assert().success();
+        assert!(!dir.path().join("decor-runtime.mjs").exists());
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_build_many_used_components,
+    "{#use \"./hello.decor\"} {#use \"./bye.decor\"} #hello /hello #bye /bye",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("hello.decor"),
+            "{#use \"./a.decor\"} {#use \"./b.decor\"} #p Hello /p #a /a #b /b",
+        )
+        .unwrap();
+        fs::write(dir.path().join("bye.decor"), "#p Bye /p").unwrap();
+        fs::write(dir.path().join("a.decor"), "#p A /p").unwrap();
+        fs::write(dir.path().join("b.decor"), "#p B /p").unwrap();
+        cmd.args(["--render-method", "csr"]);
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_bundle_many_used_components,
+    "{#use \"./hello.decor\"} {#use \"./bye.decor\"} #hello /hello #bye /bye",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("hello.decor"),
+            "{#use \"./a.decor\"} {#use \"./b.decor\"} #p Hello /p #a /a #b /b",
+        )
+        .unwrap();
+        fs::write(dir.path().join("bye.decor"), "#p Bye /p").unwrap();
+        fs::write(dir.path().join("a.decor"), "#p A /p").unwrap();
+        fs::write(dir.path().join("b.decor"), "#p B /p").unwrap();
+        cmd.args(["--render-method", "csr", "--bundle"]);
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_share_components_used_many_times,
+    "{#use \"./hello.decor\"} {#use \"./bye.decor\"} #hello /hello #bye /bye",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("hello.decor"),
+            "{#use \"./child.decor\"} #child /child",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("bye.decor"),
+            "{#use \"./child.decor\"} #child /child",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("child.decor"),
+            "#p Child /p\n---css\np { color: red; }\n---",
+        )
+        .unwrap();
+        cmd.args(["--render-method", "csr"]);
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    links_the_css_of_used_components_in_the_order_they_are_used,
+    "{#use \"./a.decor\"} {#use \"./b.decor\"} #a /a #b /b",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("a.decor"),
+            "{#use \"./c.decor\"} #c /c #p A /p\n---css\np { color: red; }\n---",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("b.decor"),
+            "{#use \"./c.decor\"} #c /c #p B /p\n---css\np { color: blue; }\n---",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("c.decor"),
+            "#p C /p\n---css\np { color: green; }\n---",
+        )
+        .unwrap();
+        cmd.args(["--render-method", "csr"]);
+        cmd.assert().success().stderr("");
+
+        let css = fs::read_to_string(dir.path().join("out.css")).unwrap();
+        let at = |color: &str| css.find(color).unwrap_or_else(|| panic!("{css}"));
+        assert!(at("green") < at("red") && at("red") < at("blue"), "{css}");
+    }
+);
+
+decor_test!(
+    only_drops_unused_uses_when_asked_to,
+    "{#use \"./a.decor\"} #p Hi /p",
+    |dir: &mut TempDir, _cmd: Command| {
+        fs::write(dir.path().join("a.decor"), "#p A /p").unwrap();
+        let build = |args: &[&str]| {
+            let _ = fs::remove_file(dir.path().join("out_a.mjs"));
+            let mut build = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+            build
+                .current_dir(dir.path())
+                .args([
+                    "build",
+                    "input.decor",
+                    "--render-method",
+                    "csr",
+                    "--no-cache",
+                ])
+                .args(args);
+            let assertion = build.assert().success();
+            let stderr = String::from_utf8_lossy(assertion.get_output().stderr.as_slice());
+            assert!(stderr.contains("unused use declaration"), "{stderr}");
+            dir.path().join("out_a.mjs").exists()
+        };
+
+        assert!(build(&[]));
+        assert!(!build(&["--drop-unused-uses"]));
+        assert!(!build(&["--profile", "release"]));
+    }
+);
+
+decor_test!(
+    can_alias_used_components,
+    "{#use \"./widgets/fancy-widget.decor\" as widget} #widget /widget #p Hi /p",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::create_dir(dir.path().join("widgets")).unwrap();
+        fs::write(dir.path().join("widgets/fancy-widget.decor"), "#p Fancy /p").unwrap();
+        cmd.assert().success().stderr("");
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_use_every_component_in_a_directory,
+    "{#use \"./widgets/*.decor\"} #button /button #fancy-card /fancy-card",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::create_dir(dir.path().join("widgets")).unwrap();
+        fs::write(
+            dir.path().join("widgets/button.decor"),
+            "#button Hi /button",
+        )
+        .unwrap();
+        fs::write(dir.path().join("widgets/fancy-card.decor"), "#p Card /p").unwrap();
+        fs::write(dir.path().join("widgets/README.md"), "Widgets").unwrap();
+        cmd.args(["--render-method", "csr"]);
+        cmd.assert().success().stderr("");
+        assert_all!(dir.path());
+
+        let mut missing = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        fs::write(
+            dir.path().join("missing.decor"),
+            "{#use \"./nothing/*.decor\"} #p Hi /p",
+        )
+        .unwrap();
+        missing
+            .current_dir(dir.path())
+            .args(["build", "missing.decor", "--no-cache"]);
+        missing.assert().failure();
+    }
+);
+
+decor_test!(
+    globs_are_relative_to_the_component_they_are_in,
+    "{#use \"./*.decor\"} #card /card",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("card.decor"),
+            "{#use \"./widgets/panel.decor\"} #panel /panel",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("widgets")).unwrap();
+        fs::write(
+            dir.path().join("widgets/panel.decor"),
+            "{#use \"./*.decor\"} #p Panel /p #label /label",
+        )
+        .unwrap();
+        fs::write(dir.path().join("widgets/label.decor"), "#span Label /span").unwrap();
+        cmd.args(["--render-method", "csr"]);
+        cmd.assert().success().stderr("");
+        assert!(dir.path().join("out_label.mjs").exists());
+    }
+);
+
+decor_test!(
+    can_use_components_from_packages,
+    "{#use \"@ui/button.decor\"} #button /button",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::create_dir_all(dir.path().join("vendor/ui")).unwrap();
+        fs::write(
+            dir.path().join("vendor/ui/button.decor"),
+            "#button Hi /button",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("decor.toml"),
+            "[dependencies]\nui = { path = \"vendor/ui\" }",
+        )
+        .expect("unable to write config file");
+        cmd.assert().success().stderr("");
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_use_components_from_git_packages,
+    "{#use \"@ui/button.decor\"} #button /button",
+    |dir: &mut TempDir, mut cmd: Command| {
+        let repo = dir.path().join("ui");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("button.decor"), "#button From git /button").unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=decorous",
+                    "-c",
+                    "user.email=decorous@example.com",
+                ])
+                .args(args)
+                .current_dir(&repo)
+                .status()
+                .expect("git should be installed");
+            assert!(status.success());
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "Add button"]);
+        fs::write(
+            dir.path().join("decor.toml"),
+            format!(
+                "[dependencies]\nui = {{ git = {:?} }}",
+                repo.display().to_string()
+            ),
+        )
+        .expect("unable to write config file");
+
+        cmd.assert().success().stderr("");
+        let commit = std::process::Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(&repo)
+            .output()
+            .unwrap()
+            .stdout;
+        let lock = fs::read_to_string(dir.path().join("decor.lock")).unwrap();
+        assert!(
+            lock.contains(String::from_utf8_lossy(&commit).trim()),
+            "{lock}"
+        );
+        assert!(fs::read_to_string(dir.path().join("out_button.mjs"))
+            .unwrap()
+            .contains("From git"));
+    }
+);
+
+decor_test!(
+    fails_on_components_that_use_each_other,
+    "{#use \"./hello.decor\"} #hello /hello",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("hello.decor"),
+            "{#use \"./bye.decor\"} #bye /bye",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("bye.decor"),
+            "{#use \"./hello.decor\"} #hello /hello",
+        )
+        .unwrap();
+        let assertion = cmd.assert().failure();
+        let stderr = String::from_utf8_lossy(assertion.get_output().stderr.as_slice());
+        assert!(
+            stderr.contains("./hello.decor -> ./bye.decor -> ./hello.decor"),
+            "{stderr}"
+        );
+    }
+);
+
+decor_test!(
+    can_minify_javascript,
+    JS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--render-method", "csr", "--minify"]);
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_minify_css,
+    CSS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        let mut config =
+            File::create(dir.path().join("decor.toml")).expect("unable to create config file");
+        write!(config, "css.targets = [\"safari\", \"firefox\"]")
+            .expect("unable to write to config file");
+
+        cmd.arg("--minify-css");
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_escape_non_ascii_output,
+    UNICODE,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.arg("--ascii-only");
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_build_custom_elements,
+    CSS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--target", "custom-element", "--html"]);
+        cmd.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
 decor_test!(
     dom_render_is_default_when_modularizing,
     JS,
@@ -224,6 +714,109 @@ decor_test!(
     }
 );
 
+decor_test_multiple!(
+    can_compile_cfg_blocks_for_enabled_features,
+    "#nav #a Home /a {#cfg feature=\"admin\"} #a Admin /a {/cfg} /nav",
+    |disabled: String, enabled: String| {
+        assert!(!disabled.contains("Admin"), "{disabled}");
+        assert!(enabled.contains("Admin"), "{enabled}");
+    },
+    disabled: |dir: &mut TempDir, mut cmd: Command| {
+        cmd.assert().success().stderr("");
+        fs::read_to_string(dir.path().join("out.html")).unwrap()
+    },
+    enabled: |dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--features", "beta,admin"]);
+        cmd.assert().success().stderr("");
+        fs::read_to_string(dir.path().join("out.html")).unwrap()
+    }
+);
+
+decor_test_multiple!(
+    can_read_env_vars_when_compiling,
+    "#a[href={$env.API_URL}] Docs /a",
+    |from_env: String, from_config: String| {
+        assert!(from_env.contains("https://env.test"), "{from_env}");
+        assert!(from_config.contains("https://config.test"), "{from_config}");
+    },
+    from_env: |dir: &mut TempDir, mut cmd: Command| {
+        cmd.env("API_URL", "https://env.test");
+        cmd.assert().success().stderr("");
+        fs::read_to_string(dir.path().join("out.js")).unwrap()
+    },
+    from_config: |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("decor.toml"),
+            "[env]\nAPI_URL = \"https://config.test\"",
+        )
+        .expect("unable to write config file");
+        cmd.env_remove("API_URL");
+        cmd.assert().success().stderr("");
+        fs::read_to_string(dir.path().join("out.js")).unwrap()
+    }
+);
+
+decor_test!(
+    fails_on_undefined_env_vars,
+    "#p {$env.API_URL} /p",
+    |_dir: &mut TempDir, mut cmd: Command| {
+        cmd.env_remove("API_URL");
+        let assertion = cmd.assert().failure();
+        let stderr = String::from_utf8_lossy(assertion.get_output().stderr.as_slice());
+        assert!(
+            stderr.contains("environment variable API_URL is not defined"),
+            "{stderr}"
+        );
+    }
+);
+
+decor_test!(
+    can_build_every_locale,
+    "#h1 {@t \"greeting\"} /h1 #p {@t \"farewell\"} /p",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("decor.toml"),
+            "[i18n]\nlocales = [\"en\", \"fr\"]",
+        )
+        .expect("unable to write config file");
+        fs::create_dir(dir.path().join("locales")).unwrap();
+        fs::write(
+            dir.path().join("locales/en.toml"),
+            "greeting = \"Hello\"\nfarewell = \"Goodbye\"",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("locales/fr.toml"),
+            "greeting = \"Bonjour\"\nfarewell = \"Au revoir\"",
+        )
+        .unwrap();
+        cmd.assert().success().stderr("");
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_extract_translation_keys,
+    "#h1 {@t \"greeting\"} /h1 {#if x} #p {@t \"farewell\"} /p {/if}",
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::write(
+            dir.path().join("decor.toml"),
+            "[i18n]\nlocales = [\"en\", \"fr\"]",
+        )
+        .expect("unable to write config file");
+        fs::create_dir(dir.path().join("locales")).unwrap();
+        fs::write(dir.path().join("locales/fr.toml"), "greeting = \"Bonjour\"").unwrap();
+        cmd.arg("extract");
+        cmd.assert().success().stderr("");
+        let en = fs::read_to_string(dir.path().join("locales/en.toml")).unwrap();
+        assert_eq!("farewell = \"\"\ngreeting = \"\"\n", en);
+        // Messages that are already translated are kept
+        let fr = fs::read_to_string(dir.path().join("locales/fr.toml")).unwrap();
+        assert_eq!("farewell = \"\"\ngreeting = \"Bonjour\"\n", fr);
+    },
+    "i18n"
+);
+
 decor_test_multiple!(
     can_strip_binaries,
     WASM_C,
@@ -336,6 +929,59 @@ decor_test!(
     }
 );
 
+decor_test!(
+    quiet_builds_only_print_errors,
+    NO_JS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.arg("--quiet");
+        let assertion = cmd.assert().success();
+        assert!(assertion.get_output().stdout.is_empty());
+        assert!(dir.path().join("out.js").exists());
+    }
+);
+
+decor_test!(
+    can_log_builds_as_json,
+    NO_JS,
+    |_dir: &mut TempDir, mut cmd: Command| {
+        cmd.arg("--log-json");
+        let assertion = cmd.assert().success();
+        let stdout = String::from_utf8_lossy(assertion.get_output().stdout.as_slice());
+        // Durations change with every build
+        let without_durations = stdout
+            .lines()
+            .map(|line| match line.split_once(",\"duration_ms\":") {
+                Some((start, rest)) => {
+                    let end = rest.find([',', '}']).unwrap_or(rest.len());
+                    format!("{start}{}", &rest[end..])
+                }
+                None => line.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        insta::assert_snapshot!(without_durations);
+    }
+);
+
+decor_test!(
+    can_time_the_phases_of_builds,
+    NO_JS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--timings", "--trace", "trace.json", "--color=never"]);
+        let assertion = cmd.assert().success();
+        let stdout = String::from_utf8_lossy(assertion.get_output().stdout.as_slice());
+        for phase in ["parse", "pass: lint", "render: prerender"] {
+            assert!(
+                stdout.contains(&format!("  {phase} ")),
+                "{phase} was not timed"
+            );
+        }
+        let trace = fs::read_to_string(dir.path().join("trace.json")).unwrap();
+        assert!(trace.starts_with('['));
+        assert!(trace.contains(r#""name":"pass: dep_analysis""#));
+    }
+);
+
 decor_test!(
     can_disable_colorization,
     NO_JS,
@@ -361,6 +1007,98 @@ decor_test!(
     }
 );
 
+decor_test!(
+    static_code_blocks_can_only_read_granted_directories,
+    STATIC_DATA,
+    |dir: &mut TempDir, _cmd: Command| {
+        let cache = TempDir::new("cache").unwrap();
+        let build = || {
+            let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+            cmd.current_dir(dir.path())
+                .env("DECOR_CACHE_DIR", cache.path())
+                .args(["build", "input.decor", "--render-method", "prerender"]);
+            cmd.assert()
+        };
+        // The code block is WAT, which wasmtime runs as it is
+        fs::write(
+            dir.path().join("copy.py"),
+            "import os, shutil\nshutil.copy(os.environ['DECOR_INPUT'], os.path.join(os.environ['DECOR_OUT_DIR'], 'data.wasm'))",
+        )
+        .unwrap();
+        let compiler = "[compilers.wast]\nscript = \"./copy.py\"\ndeps = []\n";
+        fs::create_dir(dir.path().join("data")).unwrap();
+        fs::write(dir.path().join("data/n.json"), r#"{ "title": "Hi" }"#).unwrap();
+
+        fs::write(dir.path().join("decor.toml"), compiler).unwrap();
+        build().failure();
+
+        fs::write(
+            dir.path().join("decor.toml"),
+            format!("{compiler}\n[static]\nread = [\"data\"]"),
+        )
+        .unwrap();
+        build().success();
+        assert!(fs::read_to_string(dir.path().join("out.html"))
+            .unwrap()
+            .contains("<h1>Hi</h1>"));
+
+        fs::write(dir.path().join("data/n.json"), r#"{ "title": "Bye" }"#).unwrap();
+        build().success();
+        assert!(fs::read_to_string(dir.path().join("out.html"))
+            .unwrap()
+            .contains("<h1>Bye</h1>"));
+    },
+    "--version"
+);
+
+decor_test!(
+    reuses_static_code_block_output_until_it_changes,
+    STATIC_DATA,
+    |dir: &mut TempDir, _cmd: Command| {
+        let cache = TempDir::new("cache").unwrap();
+        let build = |extra: &[&str]| {
+            let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+            cmd.current_dir(dir.path())
+                .env("DECOR_CACHE_DIR", cache.path())
+                .args(["build", "input.decor", "--render-method", "prerender"])
+                .args(extra);
+            let out = cmd.assert().success().get_output().stdout.clone();
+            String::from_utf8(out).unwrap()
+        };
+        fs::write(
+            dir.path().join("copy.py"),
+            "import os, shutil\nshutil.copy(os.environ['DECOR_INPUT'], os.path.join(os.environ['DECOR_OUT_DIR'], 'data.wasm'))",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("decor.toml"),
+            "[compilers.wast]\nscript = \"./copy.py\"\ndeps = []\n[static]\nread = [\"data\"]",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("data")).unwrap();
+        fs::write(dir.path().join("data/n.json"), r#"{ "title": "Hi" }"#).unwrap();
+        let restored = "static WebAssembly: wast: restored from cache";
+
+        assert!(!build(&[]).contains(restored));
+        // Only the template changes, so the build isn't restored, but the code block is
+        let input = fs::read_to_string(dir.path().join("input.decor")).unwrap();
+        fs::write(
+            dir.path().join("input.decor"),
+            format!("{input}\n#p More /p"),
+        )
+        .unwrap();
+        assert!(build(&[]).contains(restored));
+        assert!(!build(&["--no-cache"]).contains(restored));
+
+        fs::write(dir.path().join("data/n.json"), r#"{ "title": "Bye" }"#).unwrap();
+        assert!(!build(&[]).contains(restored));
+        assert!(fs::read_to_string(dir.path().join("out.html"))
+            .unwrap()
+            .contains("<h1>Bye</h1>"));
+    },
+    "--version"
+);
+
 decor_test!(
     does_not_fail_if_out_dir_already_made,
     STATIC,
@@ -369,3 +1107,390 @@ decor_test!(
         cmd.assert().success();
     }
 );
+
+decor_test!(
+    can_format_files,
+    JS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--check", "input.decor"]);
+        cmd.assert().failure();
+
+        let mut fmt = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        fmt.current_dir(dir.path()).args(["fmt", "input.decor"]);
+        fmt.assert().success();
+        let formatted = fs::read_to_string(dir.path().join("input.decor")).unwrap();
+        insta::assert_snapshot!(formatted);
+
+        let mut check = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        check
+            .current_dir(dir.path())
+            .args(["fmt", "--check", "input.decor"]);
+        check.assert().success();
+    },
+    "fmt"
+);
+
+decor_test!(
+    formatting_keeps_the_text_of_whitespace_sensitive_elements,
+    "#pre\n  let   x = {1 + 1};\n    #b:bold  \n  {#if  true }  y  {/if}\n/pre\n#textarea[rows=\"2\"]  a\n\n  b  /textarea\n",
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--render-method", "csr"]);
+        cmd.assert().success();
+        let before = fs::read_to_string(dir.path().join("out.js")).unwrap();
+
+        let mut fmt = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        fmt.current_dir(dir.path()).args(["fmt", "input.decor"]);
+        fmt.assert().success().stdout("formatted input.decor\n");
+        let mut build = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        build
+            .current_dir(dir.path())
+            .args(["build", "input.decor", "--render-method", "csr"]);
+        build.assert().success();
+        let after = fs::read_to_string(dir.path().join("out.js")).unwrap();
+
+        assert_eq!(before, after);
+    }
+);
+
+decor_test!(
+    formatting_keeps_what_components_render,
+    "---js\nlet count = 0;\n---\n#p\n  #b:bold\n  #i:italic\n/p\n#p:hello   world\n#div[class=\"a\"   id=\"b\"]\n  #button[@click={() => count += 1}] {  count  } /button\n  {#if   count > 1  } #span many   times /span {/if}\n/div\n",
+    |dir: &mut TempDir, _cmd: Command| {
+        let build = |method: &str| {
+            let outs = ["out.js", "out.html"].map(|out| dir.path().join(out));
+            for out in &outs {
+                let _ = fs::remove_file(out);
+            }
+            let mut build = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+            build.current_dir(dir.path()).args([
+                "build",
+                "input.decor",
+                "--render-method",
+                method,
+                "--no-cache",
+            ]);
+            build.assert().success();
+            outs.map(|out| fs::read_to_string(out).unwrap_or_default())
+        };
+        let before = [build("csr"), build("prerender")];
+
+        let mut fmt = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        fmt.current_dir(dir.path()).args(["fmt", "input.decor"]);
+        fmt.assert().success().stdout("formatted input.decor\n");
+        let formatted = fs::read_to_string(dir.path().join("input.decor")).unwrap();
+        assert!(formatted.contains("#b bold /b#i italic /i"), "{formatted}");
+        let after = [build("csr"), build("prerender")];
+
+        assert_eq!(before, after);
+    }
+);
+
+decor_test!(
+    can_scaffold_new_projects,
+    NO_JS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.arg("my-app");
+        cmd.assert().success();
+        assert_all!(dir.path().join("my-app"));
+
+        let mut build = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        build.current_dir(dir.path().join("my-app")).args([
+            "build",
+            "app.decor",
+            "-r",
+            "csr",
+            "--no-cache",
+        ]);
+        build.assert().success().stderr("");
+
+        let mut again = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        again.current_dir(dir.path()).args(["new", "my-app"]);
+        again.assert().failure();
+    },
+    "new"
+);
+
+decor_test!(
+    can_check_components_without_building,
+    NO_JS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.assert().success();
+
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        fs::write(
+            dir.path().join("nested/broken.decor"),
+            "#div[class=\"a\" /div",
+        )
+        .unwrap();
+        let mut check = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        check.current_dir(dir.path()).arg("check");
+        check.assert().failure();
+
+        let mut lints = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        fs::write(
+            dir.path().join("decor.toml"),
+            "lints.unused-variable = \"deny\"",
+        )
+        .unwrap();
+        fs::write(dir.path().join("input.decor"), "---js let x = 1; ---").unwrap();
+        lints.current_dir(dir.path()).args(["check", "input.decor"]);
+        lints.assert().failure();
+
+        assert_all!(dir.path());
+    },
+    "check"
+);
+
+decor_test!(
+    can_compare_outputs_with_golden_files,
+    CSS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.arg("--golden");
+        cmd.assert().failure();
+
+        let golden = |args: &[&str]| {
+            let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+            cmd.current_dir(dir.path())
+                .arg("test")
+                .arg("--golden")
+                .args(args);
+            cmd.assert()
+        };
+        golden(&["--update"]).success();
+        golden(&[]).success();
+        assert_all!(dir.path());
+
+        fs::write(dir.path().join("input.decor"), "#p Changed /p").unwrap();
+        golden(&[]).failure();
+        golden(&["--update"]).success();
+        assert!(!dir.path().join("input.css").exists());
+        golden(&[]).success();
+    },
+    "test"
+);
+
+decor_test!(
+    can_print_tokens_as_json,
+    JS,
+    |_dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--json", "input.decor"]);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap());
+    },
+    "tokens"
+);
+
+decor_test!(
+    reuses_cached_builds_until_a_used_component_changes,
+    "{#use \"./hello.decor\"} #hello /hello",
+    |dir: &mut TempDir, _cmd: Command| {
+        let cache = TempDir::new("cache").unwrap();
+        let build = || {
+            let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+            cmd.current_dir(dir.path())
+                .env("DECOR_CACHE_DIR", cache.path())
+                .args(["build", "input.decor", "--render-method", "csr"]);
+            let out = cmd.assert().success().get_output().stdout.clone();
+            String::from_utf8(out).unwrap()
+        };
+
+        fs::write(dir.path().join("hello.decor"), "#p Hello /p").unwrap();
+        assert!(!build().contains("restored from cache"));
+        let js = fs::read_to_string(dir.path().join("out.js")).unwrap();
+        let used = fs::read_to_string(dir.path().join("out_hello.mjs")).unwrap();
+        fs::remove_file(dir.path().join("out.js")).unwrap();
+        fs::remove_file(dir.path().join("out_hello.mjs")).unwrap();
+
+        assert!(build().contains("restored from cache"));
+        assert_eq!(js, fs::read_to_string(dir.path().join("out.js")).unwrap());
+        assert_eq!(
+            used,
+            fs::read_to_string(dir.path().join("out_hello.mjs")).unwrap()
+        );
+
+        fs::write(dir.path().join("hello.decor"), "#p Goodbye /p").unwrap();
+        assert!(!build().contains("restored from cache"));
+        assert!(fs::read_to_string(dir.path().join("out_hello.mjs"))
+            .unwrap()
+            .contains("Goodbye"));
+    },
+    "--version"
+);
+
+decor_test!(
+    reuses_cached_builds_until_a_preprocessor_script_changes,
+    "---ts let x: number = 0; --- #p {x} /p",
+    |dir: &mut TempDir, _cmd: Command| {
+        let cache = TempDir::new("cache").unwrap();
+        let build = || {
+            let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+            cmd.current_dir(dir.path())
+                .env("DECOR_CACHE_DIR", cache.path())
+                .args(["build", "input.decor", "--render-method", "csr"]);
+            let out = cmd.assert().success().get_output().stdout.clone();
+            String::from_utf8(out).unwrap()
+        };
+        let script = |value: u32| {
+            fs::write(
+                dir.path().join("pre.sh"),
+                format!("cat > /dev/null; echo '{{\"code\": \"let x = {value};\"}}'"),
+            )
+            .unwrap();
+        };
+
+        fs::write(
+            dir.path().join("decor.toml"),
+            "preprocessors.ts = { command = \"sh\", args = [\"pre.sh\"], target = \"js\" }",
+        )
+        .unwrap();
+        script(1);
+        assert!(!build().contains("restored from cache"));
+        assert!(build().contains("restored from cache"));
+
+        script(2);
+        assert!(!build().contains("restored from cache"));
+        assert!(fs::read_to_string(dir.path().join("out.js"))
+            .unwrap()
+            .contains("let x = 2;"));
+    },
+    "--version"
+);
+
+decor_test!(
+    reuses_cached_builds_until_a_preprocessor_is_upgraded,
+    "---ts let x: number = 0; --- #p {x} /p",
+    |dir: &mut TempDir, _cmd: Command| {
+        use std::os::unix::fs::PermissionsExt;
+
+        let cache = TempDir::new("cache").unwrap();
+        let bin = dir.path().join("bin");
+        let path = format!("{}:{}", bin.display(), env::var("PATH").unwrap());
+        let build = || {
+            let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+            cmd.current_dir(dir.path())
+                .env("DECOR_CACHE_DIR", cache.path())
+                .env("PATH", &path)
+                .args(["build", "input.decor", "--render-method", "csr"]);
+            let out = cmd.assert().success().get_output().stdout.clone();
+            String::from_utf8(out).unwrap()
+        };
+        // The preprocessor is a program on the PATH, not a script in the project
+        let install = |version: u32| {
+            let program = bin.join("pre");
+            fs::write(
+                &program,
+                format!(
+                    "#!/bin/sh\nif [ \"$1\" = --version ]; then echo 'pre {version}'; exit; fi\ncat > /dev/null; echo '{{\"code\": \"let x = {version};\"}}'"
+                ),
+            )
+            .unwrap();
+            fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        };
+
+        fs::create_dir(&bin).unwrap();
+        fs::write(
+            dir.path().join("decor.toml"),
+            "preprocessors.ts = { command = \"pre\", target = \"js\" }",
+        )
+        .unwrap();
+        install(1);
+        assert!(!build().contains("restored from cache"));
+        assert!(build().contains("restored from cache"));
+
+        install(2);
+        assert!(!build().contains("restored from cache"));
+        assert!(fs::read_to_string(dir.path().join("out.js"))
+            .unwrap()
+            .contains("let x = 2;"));
+    },
+    "--version"
+);
+
+decor_test!(
+    can_clean_build_outputs,
+    CSS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--html", "-r", "csr"]);
+        cmd.assert().success();
+        fs::write(dir.path().join("outer.js"), "").unwrap();
+
+        let mut clean = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        clean.current_dir(dir.path()).args(["clean", "--html"]);
+        clean.assert().success();
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    clean_only_removes_recorded_outputs,
+    NO_JS,
+    |dir: &mut TempDir, mut cmd: Command| {
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/notes.txt"), "").unwrap();
+        fs::write(dir.path().join("src_utils.js"), "").unwrap();
+        cmd.args(["-o", "src"]);
+        cmd.assert().success();
+
+        let mut clean = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        clean.current_dir(dir.path()).args(["clean", "-o", "src"]);
+        clean.assert().success().stdout("Removed 2 outputs!\n");
+        assert_all!(dir.path());
+    }
+);
+
+decor_test!(
+    can_list_and_clean_cache_entries,
+    CSS,
+    |dir: &mut TempDir, _cmd: Command| {
+        let cache = TempDir::new("cache").unwrap();
+        let run = |args: &[&str]| {
+            let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+            cmd.current_dir(dir.path())
+                .env("DECOR_CACHE_DIR", cache.path())
+                .args(args);
+            let out = cmd.assert().success().get_output().stdout.clone();
+            String::from_utf8(out).unwrap()
+        };
+
+        run(&["build", "input.decor"]);
+        let list = run(&["cache", "list"]);
+        assert!(list.contains("out.js, out.css, out.html"), "{list}");
+        assert!(list.contains("1 entry"), "{list}");
+
+        assert!(run(&["cache", "clean", "--older-than", "1h"]).contains("Removed 0"));
+        assert!(run(&["cache", "clean"]).contains("Removed 1"));
+        assert!(run(&["cache", "list"]).contains("The cache is empty!"));
+    },
+    "--version"
+);
+
+decor_test!(
+    can_analyze_output_size,
+    CSS,
+    |_dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--analyze", "--color=never", "--render-method", "csr"]);
+        let assertion = cmd.assert().success();
+        let stdout = String::from_utf8_lossy(assertion.get_output().stdout.as_slice());
+        let filtered_stdout = stdout
+            .lines()
+            .filter(|line| !line.starts_with("DONE compiled in"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        insta::assert_snapshot!(filtered_stdout);
+    }
+);
+
+decor_test!(
+    can_fix_errors_in_input,
+    "---js:statc let x = 1; ---\n#p[class=\"a\" #span {x} /span /p",
+    |dir: &mut TempDir, mut cmd: Command| {
+        cmd.args(["--fix", "--color=never"]);
+        let assertion = cmd.assert().failure();
+        let stdout = String::from_utf8_lossy(assertion.get_output().stdout.as_slice());
+        assert!(stdout.contains("fixed 2 problems"), "{stdout}");
+        assert_eq!(
+            "---js:static let x = 1; ---\n#p[class=\"a\"] #span {x} /span /p",
+            fs::read_to_string(dir.path().join("input.decor")).unwrap()
+        );
+    }
+);